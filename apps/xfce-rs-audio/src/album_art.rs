@@ -0,0 +1,145 @@
+// Album art for the Now Playing card
+//
+// Players publish mpris:artUrl as a file:// path or an http(s) URL.
+// Remote images are downloaded once into ~/.cache/xfce-rs/album-art, keyed
+// by a hash of the URL; both kinds are decoded and shrunk off the UI thread.
+use anyhow::{bail, Context as _, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::debug;
+
+/// Largest side of the decoded image, the Now Playing card is 300px wide
+const MAX_SIZE: u32 = 600;
+/// Refuse downloads larger than this
+const MAX_DOWNLOAD_BYTES: usize = 16 * 1024 * 1024;
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A decoded RGBA image
+#[derive(Debug, Clone)]
+pub struct AlbumArt {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Fetch and decode the image at `url`
+pub async fn load(url: String) -> Result<AlbumArt> {
+    let bytes = if let Some(path) = url.strip_prefix("file://") {
+        let path = file_path(path);
+        tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        cached_download(&url).await?
+    } else {
+        bail!("Unsupported album art URL: {}", url);
+    };
+
+    tokio::task::spawn_blocking(move || decode(&bytes))
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
+
+fn decode(bytes: &[u8]) -> Result<AlbumArt> {
+    let image = image::load_from_memory(bytes).context("Failed to decode album art")?;
+    let image = if image.width() > MAX_SIZE || image.height() > MAX_SIZE {
+        image.thumbnail(MAX_SIZE, MAX_SIZE)
+    } else {
+        image
+    };
+    let rgba = image.into_rgba8();
+    Ok(AlbumArt {
+        width: rgba.width(),
+        height: rgba.height(),
+        pixels: rgba.into_raw(),
+    })
+}
+
+async fn cached_download(url: &str) -> Result<Vec<u8>> {
+    let cache_file = cache_dir().map(|dir| dir.join(format!("{:016x}", fnv1a(url.as_bytes()))));
+    if let Some(cache_file) = &cache_file {
+        if let Ok(bytes) = tokio::fs::read(cache_file).await {
+            debug!("Album art cache hit for {}", url);
+            return Ok(bytes);
+        }
+    }
+
+    let bytes = download(url).await?;
+
+    if let Some(cache_file) = &cache_file {
+        // Write next to the final name and rename, so a half-written file is never read
+        let partial = cache_file.with_extension("part");
+        let stored = async {
+            if let Some(dir) = cache_file.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&partial, &bytes).await?;
+            tokio::fs::rename(&partial, cache_file).await
+        };
+        if let Err(e) = stored.await {
+            debug!("Failed to cache album art for {}: {}", url, e);
+        }
+    }
+    Ok(bytes)
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    debug!("Downloading album art from {}", url);
+    let client = reqwest::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .context("Failed to create HTTP client")?;
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to download {}", url))?;
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.with_context(|| format!("Failed to download {}", url))? {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() > MAX_DOWNLOAD_BYTES {
+            bail!("Album art at {} is larger than {} bytes", url, MAX_DOWNLOAD_BYTES);
+        }
+    }
+    Ok(bytes)
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("xfce-rs").join("album-art"))
+}
+
+/// The local path of a file:// URL with its host part removed and
+/// percent-escapes decoded
+fn file_path(url_path: &str) -> PathBuf {
+    let path = url_path.strip_prefix("localhost").unwrap_or(url_path);
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// Stable across runs and Rust versions, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
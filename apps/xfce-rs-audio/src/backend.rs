@@ -0,0 +1,191 @@
+// Sound server backends
+//
+// `AudioBackend` is what the mixer, the daemon and xfce-rs-audioctl need
+// from the sound server: volumes, devices and application streams. Two
+// implementations exist, picked once per process by `backend()`:
+//
+// - PulseBackend speaks the PulseAudio protocol through libpulse. It works
+//   with PulseAudio itself and with PipeWire's pipewire-pulse.
+// - PipeWireGraphBackend (the opt-in "pipewire" feature) is PulseBackend
+//   plus a native read of the PipeWire graph for what the PulseAudio
+//   protocol can't show, the latency of nodes and the links between them.
+//   It controls nothing through PipeWire itself: volumes and routing go
+//   over pipewire-pulse, which keeps PipeWire's session manager in charge.
+//
+// Device details, card profiles, ports, recording streams and server
+// events stay in the pulseaudio and source_outputs modules; both
+// backends answer those through the PulseAudio protocol.
+use anyhow::Result;
+use futures_util::future::{BoxFuture, FutureExt};
+use once_cell::sync::Lazy;
+use tracing::info;
+
+use crate::sink_inputs::{self, SinkInput};
+use crate::{pulseaudio, AudioDevice};
+
+/// Forces a backend: "pulse" or "pipewire"
+const BACKEND_VARIABLE: &str = "XFCE_RS_AUDIO_BACKEND";
+
+/// A PipeWire node, with the latency it asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub id: u32,
+    pub name: String,
+    pub description: String,
+    /// e.g. "Audio/Sink" or "Stream/Output/Audio"
+    pub media_class: String,
+    /// Requested latency as "<samples>/<rate>", e.g. "1024/48000"
+    pub latency: Option<String>,
+}
+
+/// A link between two ports of the PipeWire graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub id: u32,
+    pub output_node: u32,
+    pub output_port: String,
+    pub input_node: u32,
+    pub input_port: String,
+}
+
+/// What the audio tools need from a sound server. Volumes are percent,
+/// devices and streams go by their server index.
+pub trait AudioBackend: Send + Sync {
+    /// Short name for logs, e.g. "PulseAudio"
+    fn name(&self) -> &'static str;
+
+    /// Connect, and read the device list
+    fn init(&self) -> BoxFuture<'_, Result<()>>;
+
+    /// Volume and mute state of the default output
+    fn volume(&self) -> BoxFuture<'_, Result<(f32, bool)>>;
+    fn set_volume(&self, volume: f32) -> BoxFuture<'_, Result<()>>;
+    fn set_mute(&self, muted: bool) -> BoxFuture<'_, Result<()>>;
+
+    /// Volume and mute state of the default input
+    fn mic_volume(&self) -> BoxFuture<'_, Result<(f32, bool)>>;
+    fn set_mic_volume(&self, volume: f32) -> BoxFuture<'_, Result<()>>;
+    fn set_mic_mute(&self, muted: bool) -> BoxFuture<'_, Result<()>>;
+
+    /// Outputs and inputs
+    fn devices(&self) -> BoxFuture<'_, Result<(Vec<AudioDevice>, Vec<AudioDevice>)>>;
+    fn set_default_output(&self, device_index: u32) -> BoxFuture<'_, Result<()>>;
+    fn set_default_input(&self, device_index: u32) -> BoxFuture<'_, Result<()>>;
+
+    /// Application playback streams
+    fn sink_inputs(&self) -> BoxFuture<'_, Result<Vec<SinkInput>>>;
+    fn set_sink_input_volume(&self, index: u32, volume: f32) -> BoxFuture<'_, Result<()>>;
+    fn set_sink_input_mute(&self, index: u32, muted: bool) -> BoxFuture<'_, Result<()>>;
+    fn move_sink_input(&self, index: u32, sink_index: u32) -> BoxFuture<'_, Result<()>>;
+
+    /// Whether `nodes` and `links` show anything
+    fn has_graph(&self) -> bool {
+        false
+    }
+
+    /// Nodes of the processing graph
+    fn nodes(&self) -> BoxFuture<'_, Result<Vec<Node>>> {
+        async { Ok(Vec::new()) }.boxed()
+    }
+
+    /// Links of the processing graph
+    fn links(&self) -> BoxFuture<'_, Result<Vec<Link>>> {
+        async { Ok(Vec::new()) }.boxed()
+    }
+}
+
+/// The PulseAudio protocol, through libpulse
+#[derive(Debug, Default)]
+pub struct PulseBackend;
+
+impl AudioBackend for PulseBackend {
+    fn name(&self) -> &'static str {
+        "PulseAudio"
+    }
+
+    fn init(&self) -> BoxFuture<'_, Result<()>> {
+        pulseaudio::init().boxed()
+    }
+
+    fn volume(&self) -> BoxFuture<'_, Result<(f32, bool)>> {
+        pulseaudio::get_volume().boxed()
+    }
+
+    fn set_volume(&self, volume: f32) -> BoxFuture<'_, Result<()>> {
+        pulseaudio::set_volume(volume).boxed()
+    }
+
+    fn set_mute(&self, muted: bool) -> BoxFuture<'_, Result<()>> {
+        pulseaudio::set_mute(muted).boxed()
+    }
+
+    fn mic_volume(&self) -> BoxFuture<'_, Result<(f32, bool)>> {
+        pulseaudio::get_mic_volume().boxed()
+    }
+
+    fn set_mic_volume(&self, volume: f32) -> BoxFuture<'_, Result<()>> {
+        pulseaudio::set_mic_volume(volume).boxed()
+    }
+
+    fn set_mic_mute(&self, muted: bool) -> BoxFuture<'_, Result<()>> {
+        pulseaudio::set_mic_mute(muted).boxed()
+    }
+
+    fn devices(&self) -> BoxFuture<'_, Result<(Vec<AudioDevice>, Vec<AudioDevice>)>> {
+        pulseaudio::get_devices().boxed()
+    }
+
+    fn set_default_output(&self, device_index: u32) -> BoxFuture<'_, Result<()>> {
+        pulseaudio::set_default_output(device_index).boxed()
+    }
+
+    fn set_default_input(&self, device_index: u32) -> BoxFuture<'_, Result<()>> {
+        pulseaudio::set_default_input(device_index).boxed()
+    }
+
+    fn sink_inputs(&self) -> BoxFuture<'_, Result<Vec<SinkInput>>> {
+        sink_inputs::get_sink_inputs().boxed()
+    }
+
+    fn set_sink_input_volume(&self, index: u32, volume: f32) -> BoxFuture<'_, Result<()>> {
+        sink_inputs::set_sink_input_volume(index, volume).boxed()
+    }
+
+    fn set_sink_input_mute(&self, index: u32, muted: bool) -> BoxFuture<'_, Result<()>> {
+        sink_inputs::set_sink_input_mute(index, muted).boxed()
+    }
+
+    fn move_sink_input(&self, index: u32, sink_index: u32) -> BoxFuture<'_, Result<()>> {
+        sink_inputs::move_sink_input(index, sink_index).boxed()
+    }
+}
+
+static BACKEND: Lazy<Box<dyn AudioBackend>> = Lazy::new(|| {
+    let backend = select();
+    info!("Using the {} backend", backend.name());
+    backend
+});
+
+/// The backend of this process: with the PipeWire graph where PipeWire
+/// runs and the feature is built in, plain PulseAudio otherwise, unless
+/// XFCE_RS_AUDIO_BACKEND says which
+pub fn backend() -> &'static dyn AudioBackend {
+    BACKEND.as_ref()
+}
+
+#[cfg(feature = "pipewire")]
+fn select() -> Box<dyn AudioBackend> {
+    match std::env::var(BACKEND_VARIABLE).as_deref() {
+        Ok("pulse") => Box::new(PulseBackend),
+        _ if crate::pipewire::is_running() => Box::new(crate::pipewire::PipeWireGraphBackend::default()),
+        _ => Box::new(PulseBackend),
+    }
+}
+
+#[cfg(not(feature = "pipewire"))]
+fn select() -> Box<dyn AudioBackend> {
+    if std::env::var(BACKEND_VARIABLE).as_deref() == Ok("pipewire") {
+        tracing::warn!("Built without PipeWire support, using PulseAudio");
+    }
+    Box::new(PulseBackend)
+}
@@ -0,0 +1,282 @@
+//! Command line control of volume, devices, application streams and the
+//! active media player, for scripts and key bindings, without the mixer
+//! window
+
+use std::process::ExitCode;
+
+use anyhow::{anyhow, bail, Result};
+use xfce_rs_audio::devices::DeviceManager;
+use xfce_rs_audio::settings::VolumeConfig;
+use xfce_rs_audio::backend::backend;
+use xfce_rs_audio::{mpris, AudioDevice};
+
+const USAGE: &str = "Usage:
+  xfce-rs-audioctl [--input] COMMAND [ARGUMENT...] - control XFCE.rs audio
+
+Commands:
+  get-volume                    Print the volume in percent, and \"muted\" if muted
+  set-volume VOLUME             Set the volume: 50 sets it, +5 and -5 step it
+  mute [on|off|toggle]          Mute or unmute, toggling by default
+  list-sinks                    List output devices, * marking the default
+  list-sources                  List input devices, * marking the default
+  list-sink-inputs              List application streams
+  move-sink-input INDEX SINK    Move an application stream to a sink, by index or name
+  list-nodes                    List PipeWire nodes with their latency
+  list-links                    List PipeWire links between nodes
+  play-pause, next, previous, stop
+                                Control the active media player
+
+Options:
+  -i, --input            Act on the default input instead of the output
+  -V, --version          Print the version and exit
+  -h, --help             Print this help and exit";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    GetVolume,
+    SetVolume(VolumeChange),
+    Mute(Option<bool>),
+    ListSinks,
+    ListSources,
+    ListSinkInputs,
+    MoveSinkInput(u32, String),
+    ListNodes,
+    ListLinks,
+    Player(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VolumeChange {
+    To(f32),
+    By(f32),
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    command: Option<Command>,
+    input: bool,
+    version: bool,
+    help: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut words = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-i" | "--input" => options.input = true,
+            "-V" | "--version" => options.version = true,
+            "-h" | "--help" => options.help = true,
+            // "-5" steps the volume down; other dashes are unknown options
+            _ if arg.starts_with('-') && arg.parse::<f32>().is_err() => {
+                return Err(format!("Unknown option {}", arg));
+            }
+            _ => words.push(arg),
+        }
+    }
+    if options.help || options.version {
+        return Ok(options);
+    }
+
+    let mut words = words.into_iter();
+    let Some(name) = words.next() else {
+        return Err("No command given".to_string());
+    };
+    let mut argument = |what: &str| words.next().ok_or_else(|| format!("{} needs {}", name, what));
+    let command = match name.as_str() {
+        "get-volume" => Command::GetVolume,
+        "set-volume" => Command::SetVolume(parse_volume(&argument("a volume")?)?),
+        "mute" => Command::Mute(match argument("a state").ok().as_deref() {
+            None | Some("toggle") => None,
+            Some("on") => Some(true),
+            Some("off") => Some(false),
+            Some(other) => return Err(format!("Expected on, off or toggle, not {}", other)),
+        }),
+        "list-sinks" => Command::ListSinks,
+        "list-sources" => Command::ListSources,
+        "list-sink-inputs" => Command::ListSinkInputs,
+        "move-sink-input" => {
+            let index = argument("a stream index")?;
+            let index = index.parse().map_err(|_| format!("Not a stream index: {}", index))?;
+            Command::MoveSinkInput(index, argument("a sink")?)
+        }
+        "list-nodes" => Command::ListNodes,
+        "list-links" => Command::ListLinks,
+        "play-pause" | "next" | "previous" | "stop" => Command::Player(name.clone()),
+        _ => return Err(format!("Unknown command {}", name)),
+    };
+    if let Some(extra) = words.next() {
+        return Err(format!("Unexpected argument {}", extra));
+    }
+    options.command = Some(command);
+    Ok(options)
+}
+
+/// "50" or "50%" sets the volume, "+5" and "-5" step it
+fn parse_volume(value: &str) -> Result<VolumeChange, String> {
+    let number = value.trim_end_matches('%');
+    let amount: f32 = number
+        .parse()
+        .ok()
+        .filter(|amount: &f32| amount.is_finite())
+        .ok_or_else(|| format!("Not a volume: {}", value))?;
+    if number.starts_with(['+', '-']) {
+        Ok(VolumeChange::By(amount))
+    } else {
+        Ok(VolumeChange::To(amount))
+    }
+}
+
+async fn run(command: Command, input: bool) -> Result<()> {
+    match command {
+        Command::GetVolume => {
+            let (volume, muted) = get_volume(input).await?;
+            println!("{:.0}%{}", volume, if muted { " muted" } else { "" });
+        }
+        Command::SetVolume(change) => {
+            let target = match change {
+                VolumeChange::To(amount) => amount,
+                VolumeChange::By(amount) => get_volume(input).await?.0 + amount,
+            };
+            // The same range the mixer's sliders have
+            let volume = target.clamp(0.0, VolumeConfig::load().await.max());
+            if input {
+                backend().set_mic_volume(volume).await?;
+            } else {
+                backend().set_volume(volume).await?;
+            }
+        }
+        Command::Mute(state) => {
+            let muted = match state {
+                Some(state) => {
+                    backend().init().await?;
+                    state
+                }
+                None => !get_volume(input).await?.1,
+            };
+            if input {
+                backend().set_mic_mute(muted).await?;
+            } else {
+                backend().set_mute(muted).await?;
+            }
+        }
+        Command::ListSinks | Command::ListSources => {
+            backend().init().await?;
+            let (outputs, inputs) = backend().devices().await?;
+            let devices = if command == Command::ListSinks { outputs } else { inputs };
+            for device in DeviceManager::sort_devices(devices) {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    device.index,
+                    if device.is_default { "*" } else { "" },
+                    device.name,
+                    device.description
+                );
+            }
+        }
+        Command::ListSinkInputs => {
+            for stream in backend().sink_inputs().await? {
+                println!(
+                    "{}\t{}\t{:.0}%{}\t{}",
+                    stream.index,
+                    stream.sink_index,
+                    stream.volume,
+                    if stream.muted { " muted" } else { "" },
+                    stream.application_name
+                );
+            }
+        }
+        Command::MoveSinkInput(index, sink) => {
+            backend().init().await?;
+            let (outputs, _) = backend().devices().await?;
+            let sink = find_device(&outputs, &sink)?;
+            backend().move_sink_input(index, sink.index).await?;
+        }
+        Command::ListNodes | Command::ListLinks if !backend().has_graph() => {
+            bail!("The {} backend has no node graph", backend().name());
+        }
+        Command::ListNodes => {
+            for node in backend().nodes().await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    node.id,
+                    node.media_class,
+                    node.latency.as_deref().unwrap_or("-"),
+                    node.name,
+                    node.description
+                );
+            }
+        }
+        Command::ListLinks => {
+            for link in backend().links().await? {
+                println!(
+                    "{}\t{}:{}\t{}:{}",
+                    link.id, link.output_node, link.output_port, link.input_node, link.input_port
+                );
+            }
+        }
+        Command::Player(action) => control_player(&action).await?,
+    }
+    Ok(())
+}
+
+/// Volume and mute state of the default output, or input
+async fn get_volume(input: bool) -> Result<(f32, bool)> {
+    backend().init().await?;
+    if input {
+        backend().mic_volume().await
+    } else {
+        backend().volume().await
+    }
+}
+
+/// A device by index or name
+fn find_device<'a>(devices: &'a [AudioDevice], sink: &str) -> Result<&'a AudioDevice> {
+    devices
+        .iter()
+        .find(|device| device.name == sink || sink.parse::<u32>().ok() == Some(device.index))
+        .ok_or_else(|| anyhow!("No sink {}", sink))
+}
+
+async fn control_player(action: &str) -> Result<()> {
+    let Some(player) = mpris::active_player().await? else {
+        bail!("No media player is running");
+    };
+    match action {
+        "play-pause" => mpris::play_pause(player).await,
+        "next" => mpris::next(player).await,
+        "previous" => mpris::previous(player).await,
+        _ => mpris::stop(player).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if options.version {
+        println!("xfce-rs-audioctl {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+
+    let Some(command) = options.command else {
+        return ExitCode::FAILURE;
+    };
+    match run(command, options.input).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
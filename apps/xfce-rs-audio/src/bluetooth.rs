@@ -0,0 +1,43 @@
+// Bluetooth audio devices through BlueZ
+//
+// Device discovery and pairing stay with the Bluetooth settings; this lists
+// the paired headsets and speakers so they can be connected from here.
+use anyhow::Result;
+use tokio::sync::OnceCell;
+use tracing::info;
+use xfce_rs_ipc::bluez::{self, BluetoothAudioDevice};
+use zbus::Connection;
+
+// BlueZ lives on the system bus
+static CONNECTION: OnceCell<Connection> = OnceCell::const_new();
+
+async fn connection() -> Result<&'static Connection> {
+    CONNECTION
+        .get_or_try_init(|| async {
+            Connection::system()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to connect to the system bus: {}", e))
+        })
+        .await
+}
+
+/// Paired audio devices, connected ones first
+pub async fn get_devices() -> Result<Vec<BluetoothAudioDevice>> {
+    Ok(bluez::audio_devices(connection().await?).await?)
+}
+
+pub async fn connect(path: String) -> Result<()> {
+    info!("Connecting Bluetooth device {}", path);
+    Ok(bluez::connect(connection().await?, &path).await?)
+}
+
+pub async fn disconnect(path: String) -> Result<()> {
+    info!("Disconnecting Bluetooth device {}", path);
+    Ok(bluez::disconnect(connection().await?, &path).await?)
+}
+
+/// Whether `sink_name` is the output of a Bluetooth device, as named by
+/// PulseAudio (`bluez_sink.*`) or PipeWire (`bluez_output.*`)
+pub fn is_bluetooth_sink(sink_name: &str) -> bool {
+    sink_name.starts_with("bluez_sink.") || sink_name.starts_with("bluez_output.")
+}
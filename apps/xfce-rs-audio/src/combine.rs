@@ -0,0 +1,144 @@
+// Simultaneous output
+//
+// A combined sink plays everything sent to it on several outputs at once,
+// e.g. speakers and a Bluetooth headset. It is module-combine-sink, which
+// PulseAudio ships and pipewire-pulse implements, so both servers get it
+// through the same protocol. The mixer loads and unloads the module; the
+// daemon mode loads it again after login, with the outputs still around.
+//
+// The chosen outputs are stored in the xfce-rs-audio channel.
+use anyhow::{bail, Result};
+use libpulse_binding::callbacks::ListResult;
+use libpulse_binding::context::introspect::SinkInfo;
+use pulsectl::controllers::SinkController;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::rc::Rc;
+use tracing::{debug, info, warn};
+use xfce_rs_config::XfceConfig;
+
+use crate::settings::CHANNEL;
+
+const PROPERTY: &str = "/combine";
+/// Node name of the combined sink
+pub const SINK_NAME: &str = "xfce_rs_combined";
+const DESCRIPTION: &str = "Combined Output";
+/// libpulse's PA_INVALID_INDEX
+const INVALID_INDEX: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CombineSettings {
+    pub enabled: bool,
+    /// Sink names of the outputs to play on
+    pub outputs: Vec<String>,
+}
+
+impl CombineSettings {
+    /// Add `sink_name` to the outputs, or take it out
+    pub fn toggle_output(&mut self, sink_name: &str) {
+        if let Some(position) = self.outputs.iter().position(|output| output == sink_name) {
+            self.outputs.remove(position);
+        } else {
+            self.outputs.push(sink_name.to_string());
+        }
+    }
+}
+
+/// Whether `sink_name` is the combined sink rather than a device
+pub fn is_combined_sink(sink_name: &str) -> bool {
+    sink_name == SINK_NAME
+}
+
+pub async fn load() -> CombineSettings {
+    XfceConfig::default()
+        .get_property(CHANNEL, PROPERTY)
+        .await
+        .ok()
+        .and_then(|value| value.deserialize().ok())
+        .unwrap_or_default()
+}
+
+pub async fn save(settings: CombineSettings) -> Result<()> {
+    crate::settings::store(PROPERTY, &settings).await
+}
+
+/// Replace the combined sink with one over the outputs in `settings` of
+/// those in `available`, or remove it when off. Fewer than two outputs
+/// leave nothing to combine.
+pub async fn apply(settings: CombineSettings, available: Vec<String>) -> Result<()> {
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let mut controller = SinkController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SinkController: {}", e))?;
+        unload(&mut controller)?;
+
+        let outputs: Vec<&String> = settings.outputs.iter().filter(|output| available.contains(output)).collect();
+        if !settings.enabled || outputs.len() < 2 {
+            info!("Simultaneous output off");
+            return Ok(());
+        }
+
+        let argument = format!(
+            "sink_name={} sink_properties=\"device.description='{}'\" slaves={}",
+            SINK_NAME,
+            DESCRIPTION,
+            outputs.iter().map(|output| output.as_str()).collect::<Vec<_>>().join(",")
+        );
+        let index = Rc::new(Cell::new(INVALID_INDEX));
+        let loaded = index.clone();
+        let op = controller
+            .handler
+            .introspect
+            .load_module("module-combine-sink", &argument, move |module| loaded.set(module));
+        controller
+            .handler
+            .wait_for_operation(op)
+            .map_err(|e| anyhow::anyhow!("Failed to load module-combine-sink: {}", e))?;
+        if index.get() == INVALID_INDEX {
+            bail!("The sound server refused to combine the outputs");
+        }
+        info!("Combined {} outputs as module {}", outputs.len(), index.get());
+        Ok(())
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
+
+/// Load the combined sink after login, if it was on
+pub async fn restore(outputs: Vec<String>) {
+    let settings = load().await;
+    if !settings.enabled {
+        return;
+    }
+    if let Err(e) = apply(settings, outputs).await {
+        warn!("Failed to restore simultaneous output: {:#}", e);
+    }
+}
+
+/// Unload the module behind the combined sink, wherever it was loaded
+/// from
+fn unload(controller: &mut SinkController) -> Result<()> {
+    let module = Rc::new(Cell::new(None));
+    let found = module.clone();
+    let op = controller
+        .handler
+        .introspect
+        .get_sink_info_by_name(SINK_NAME, move |result: ListResult<&SinkInfo>| {
+            if let ListResult::Item(sink) = result {
+                found.set(sink.owner_module);
+            }
+        });
+    // Fails when there is no such sink, which is fine
+    let _ = controller.handler.wait_for_operation(op);
+
+    let Some(module) = module.get() else {
+        return Ok(());
+    };
+    let op = controller.handler.introspect.unload_module(module, |_| {});
+    controller
+        .handler
+        .wait_for_operation(op)
+        .map_err(|e| anyhow::anyhow!("Failed to unload module {}: {}", module, e))?;
+    debug!("Unloaded combined sink module {}", module);
+    Ok(())
+}
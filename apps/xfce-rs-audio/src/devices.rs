@@ -30,6 +30,23 @@ impl DeviceManager {
         (outputs, filtered_inputs)
     }
 
+    /// The device to fall back to once the default one is gone: the first
+    /// present device in `priority` (device names, most preferred first),
+    /// else the one the server already picked, else the first one
+    pub fn elect_default<'a>(devices: &'a [crate::AudioDevice], priority: &[String]) -> Option<&'a crate::AudioDevice> {
+        priority
+            .iter()
+            .find_map(|name| devices.iter().find(|device| &device.name == name))
+            .or_else(|| devices.iter().find(|device| device.is_default))
+            .or_else(|| devices.first())
+    }
+
+    /// Sort card profiles available first, then by the server's priority
+    pub fn sort_profiles(mut profiles: Vec<crate::CardProfile>) -> Vec<crate::CardProfile> {
+        profiles.sort_by(|a, b| b.available.cmp(&a.available).then(b.priority.cmp(&a.priority)));
+        profiles
+    }
+
     /// Sort devices by description, with default device first
     pub fn sort_devices(mut devices: Vec<crate::AudioDevice>) -> Vec<crate::AudioDevice> {
         devices.sort_by(|a, b| {
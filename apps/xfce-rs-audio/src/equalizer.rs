@@ -0,0 +1,356 @@
+// Per-output equalizer and loudness normalization
+//
+// An equalized output gets its own `pipewire -c <config>` process running
+// a filter-chain: a preamp, ten biquad bands (shelves at both ends) and,
+// for loudness normalization, the SC4 compressor from swh-plugins. The
+// chain is a WirePlumber smart filter targeting the output, so streams
+// playing there pass through it without the default device changing.
+// Its pid is kept next to the config in the runtime directory, so the
+// mixer and the daemon mode can both replace or stop it.
+//
+// Settings per output and the user's presets are stored in the
+// xfce-rs-audio channel.
+use anyhow::{bail, Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use xfce_rs_config::XfceConfig;
+
+use crate::settings::CHANNEL;
+
+const OUTPUTS_PROPERTY: &str = "/equalizer/outputs";
+const PRESETS_PROPERTY: &str = "/equalizer/presets";
+
+/// Centre frequencies of the bands in Hz, an octave apart
+pub const BANDS: [f32; 10] = [31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+/// Largest boost or cut of a band in dB
+pub const MAX_GAIN: f32 = 12.0;
+/// Node name prefix of the filter sinks, which the device lists hide
+pub const SINK_PREFIX: &str = "xfce_rs_eq.";
+
+/// Q of the peaking bands, about one octave wide
+const BAND_Q: f32 = 1.41;
+/// A chain that hasn't died by then is taken to be running
+const STARTUP_GRACE: Duration = Duration::from_millis(300);
+
+/// Equalizer of one output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    pub enabled: bool,
+    /// Name of the preset the gains came from, if unchanged since
+    pub preset: Option<String>,
+    /// Gain of every band in dB
+    pub gains: Vec<f32>,
+    /// Compress to even out loudness between tracks and applications
+    pub loudness: bool,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            preset: Some("Flat".to_string()),
+            gains: vec![0.0; BANDS.len()],
+            loudness: false,
+        }
+    }
+}
+
+impl EqSettings {
+    /// Gain of `band`, 0 dB for bands missing from older settings
+    pub fn gain(&self, band: usize) -> f32 {
+        self.gains.get(band).copied().unwrap_or(0.0).clamp(-MAX_GAIN, MAX_GAIN)
+    }
+
+    pub fn set_gain(&mut self, band: usize, gain: f32) {
+        self.gains.resize(BANDS.len(), 0.0);
+        if let Some(slot) = self.gains.get_mut(band) {
+            *slot = gain.clamp(-MAX_GAIN, MAX_GAIN);
+            self.preset = None;
+        }
+    }
+
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        self.gains = (0..BANDS.len()).map(|band| preset.gains.get(band).copied().unwrap_or(0.0)).collect();
+        self.preset = Some(preset.name.clone());
+    }
+
+    /// Attenuation ahead of the bands so the loudest boost can't clip
+    fn preamp(&self) -> f32 {
+        -(0..BANDS.len()).map(|band| self.gain(band)).fold(0.0_f32, f32::max)
+    }
+}
+
+/// Named band gains
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub gains: Vec<f32>,
+    /// Shipped with the app rather than saved by the user
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Presets shipped with the app
+pub fn builtin_presets() -> Vec<Preset> {
+    [
+        ("Flat", [0.0; 10]),
+        ("Bass Boost", [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+        ("Treble Boost", [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 2.0, 4.0, 5.0, 6.0]),
+        ("Vocal", [-2.0, -2.0, -1.0, 1.0, 3.0, 4.0, 3.0, 1.0, 0.0, -1.0]),
+        ("Rock", [4.0, 3.0, 2.0, 0.0, -1.0, -1.0, 1.0, 2.0, 3.0, 4.0]),
+        ("Classical", [3.0, 2.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0]),
+        ("Headphones", [3.0, 2.0, 0.0, -1.0, 0.0, 1.0, 2.0, 3.0, 2.0, 1.0]),
+    ]
+    .into_iter()
+    .map(|(name, gains)| Preset {
+        name: name.to_string(),
+        gains: gains.to_vec(),
+        builtin: true,
+    })
+    .collect()
+}
+
+/// Whether `sink_name` is one of our filter sinks rather than a device
+pub fn is_equalizer_sink(sink_name: &str) -> bool {
+    sink_name.starts_with(SINK_PREFIX)
+}
+
+/// The `pipewire -c` configuration of the chain for the output `sink_name`
+pub fn filter_chain_config(sink_name: &str, description: &str, settings: &EqSettings) -> String {
+    let mut nodes = String::new();
+    let mut names = Vec::new();
+    let mut push_biquad = |name: String, label: &str, freq: f32, q: f32, gain: f32| {
+        let _ = writeln!(
+            nodes,
+            "                    {{ type = builtin name = {} label = {} control = {{ \"Freq\" = {:.1} \"Q\" = {:.2} \"Gain\" = {:.1} }} }}",
+            name, label, freq, q, gain
+        );
+        names.push(name);
+    };
+
+    // A high shelf at 0 Hz shifts the whole spectrum, i.e. a plain gain
+    push_biquad("preamp".to_string(), "bq_highshelf", 0.0, 1.0, settings.preamp());
+    for (band, freq) in BANDS.iter().enumerate() {
+        let label = match band {
+            0 => "bq_lowshelf",
+            band if band == BANDS.len() - 1 => "bq_highshelf",
+            _ => "bq_peaking",
+        };
+        push_biquad(format!("band{}", band + 1), label, *freq, BAND_Q, settings.gain(band));
+    }
+
+    let mut links: Vec<(String, String)> = names
+        .windows(2)
+        .map(|pair| (format!("{}:Out", pair[0]), format!("{}:In", pair[1])))
+        .collect();
+    if settings.loudness {
+        nodes.push_str(concat!(
+            "                    { type = ladspa name = loudness plugin = sc4m_1916 label = sc4m control = {\n",
+            "                        \"RMS/peak\" = 0.5 \"Attack time (ms)\" = 10.0 \"Release time (ms)\" = 400.0\n",
+            "                        \"Threshold level (dB)\" = -24.0 \"Ratio (1:n)\" = 4.0 \"Knee radius (dB)\" = 6.0\n",
+            "                        \"Makeup gain (dB)\" = 8.0 } }\n",
+        ));
+        if let Some(last) = names.last() {
+            links.push((format!("{}:Out", last), "loudness:Input".to_string()));
+        }
+    }
+    let links: String = links
+        .iter()
+        .map(|(output, input)| format!("                    {{ output = \"{}\" input = \"{}\" }}\n", output, input))
+        .collect();
+
+    let escaped = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(
+        r#"# Written by xfce-rs-audio, changes are overwritten
+context.properties = {{
+    log.level = 0
+}}
+context.spa-libs = {{
+    audio.convert.* = audioconvert/libspa-audioconvert
+    support.*       = support/libspa-support
+}}
+context.modules = [
+    {{ name = libpipewire-module-rt flags = [ ifexists nofail ] }}
+    {{ name = libpipewire-module-protocol-native }}
+    {{ name = libpipewire-module-client-node }}
+    {{ name = libpipewire-module-adapter }}
+    {{ name = libpipewire-module-filter-chain
+        args = {{
+            node.description = "{description} (Equalizer)"
+            media.name       = "{description} (Equalizer)"
+            filter.graph = {{
+                nodes = [
+{nodes}                ]
+                links = [
+{links}                ]
+            }}
+            audio.channels = 2
+            audio.position = [ FL FR ]
+            capture.props = {{
+                node.name          = "{prefix}{sink}"
+                media.class        = Audio/Sink
+                filter.smart       = true
+                filter.smart.name  = "{prefix}{sink}"
+                filter.smart.target = {{ node.name = "{sink}" }}
+            }}
+            playback.props = {{
+                node.name    = "{prefix}{sink}.output"
+                node.passive = true
+            }}
+        }}
+    }}
+]
+"#,
+        description = escaped(description),
+        sink = escaped(sink_name),
+        prefix = SINK_PREFIX,
+        nodes = nodes,
+        links = links,
+    )
+}
+
+/// Equalizer settings of every output that has any, by sink name
+pub async fn load_outputs() -> HashMap<String, EqSettings> {
+    XfceConfig::default()
+        .get_property(CHANNEL, OUTPUTS_PROPERTY)
+        .await
+        .ok()
+        .and_then(|value| value.deserialize().ok())
+        .unwrap_or_default()
+}
+
+/// Remember the settings of the output `sink_name`
+pub async fn save_output(sink_name: String, settings: EqSettings) -> Result<()> {
+    let mut outputs = load_outputs().await;
+    outputs.insert(sink_name, settings);
+    crate::settings::store(OUTPUTS_PROPERTY, &outputs).await
+}
+
+/// The built-in presets followed by the user's
+pub async fn load_presets() -> Vec<Preset> {
+    let saved: Vec<Preset> = XfceConfig::default()
+        .get_property(CHANNEL, PRESETS_PROPERTY)
+        .await
+        .ok()
+        .and_then(|value| value.deserialize().ok())
+        .unwrap_or_default();
+    let mut presets = builtin_presets();
+    presets.extend(saved.into_iter().filter(|preset| !preset.name.trim().is_empty()));
+    presets
+}
+
+/// Save `preset`, replacing a user preset of the same name. Returns all
+/// presets.
+pub async fn save_preset(preset: Preset) -> Result<Vec<Preset>> {
+    if builtin_presets().iter().any(|builtin| builtin.name == preset.name) {
+        bail!("\"{}\" is a built-in preset", preset.name);
+    }
+    let mut saved: Vec<Preset> = load_presets().await.into_iter().filter(|preset| !preset.builtin).collect();
+    saved.retain(|existing| existing.name != preset.name);
+    saved.push(preset);
+
+    crate::settings::store(PRESETS_PROPERTY, &saved).await?;
+    Ok(load_presets().await)
+}
+
+/// Start, restart or stop the chain of the output `sink_name` to match
+/// `settings`
+pub async fn apply(sink_name: String, description: String, settings: EqSettings) -> Result<()> {
+    tokio::task::spawn_blocking(move || apply_blocking(&sink_name, &description, &settings))
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
+
+/// Start the chains of the connected outputs that have the equalizer on,
+/// after login
+pub async fn restore(outputs: Vec<(String, String)>) {
+    let settings = load_outputs().await;
+    for (sink_name, description) in outputs {
+        let Some(settings) = settings.get(&sink_name).filter(|settings| settings.enabled) else {
+            continue;
+        };
+        if let Err(e) = apply(sink_name.clone(), description, settings.clone()).await {
+            warn!("Failed to restore the equalizer of {}: {:#}", sink_name, e);
+        }
+    }
+}
+
+fn apply_blocking(sink_name: &str, description: &str, settings: &EqSettings) -> Result<()> {
+    let (config_path, pid_path) = chain_paths(sink_name)?;
+    stop(&config_path, &pid_path);
+    if !settings.enabled {
+        info!("Equalizer of {} off", sink_name);
+        return Ok(());
+    }
+
+    std::fs::write(&config_path, filter_chain_config(sink_name, description, settings))
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+    let mut child = Command::new("pipewire")
+        .arg("-c")
+        .arg(&config_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to start pipewire")?;
+
+    std::thread::sleep(STARTUP_GRACE);
+    if let Some(status) = child.try_wait()? {
+        bail!("The filter chain exited at once ({}); is swh-plugins installed for loudness?", status);
+    }
+    std::fs::write(&pid_path, child.id().to_string())
+        .with_context(|| format!("Failed to write {}", pid_path.display()))?;
+    info!("Equalizer of {} running as pid {}", sink_name, child.id());
+
+    // Reap the chain if it ends while this process is still around
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+/// Config and pid file of the chain for `sink_name`
+fn chain_paths(sink_name: &str) -> Result<(PathBuf, PathBuf)> {
+    let dir = dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("xfce-rs-audio");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let file_name: String = sink_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    Ok((
+        dir.join(format!("eq-{}.conf", file_name)),
+        dir.join(format!("eq-{}.pid", file_name)),
+    ))
+}
+
+/// Stop the chain started with `config_path`, if it is still running
+fn stop(config_path: &std::path::Path, pid_path: &std::path::Path) {
+    let Some(pid) = std::fs::read_to_string(pid_path).ok().and_then(|pid| pid.trim().parse::<i32>().ok()) else {
+        return;
+    };
+    let _ = std::fs::remove_file(pid_path);
+
+    // The pid may have been reused since; only stop our own chain
+    let ours = std::fs::read(format!("/proc/{}/cmdline", pid))
+        .is_ok_and(|cmdline| String::from_utf8_lossy(&cmdline).contains(&*config_path.to_string_lossy()));
+    if ours {
+        debug!("Stopping filter chain {}", pid);
+        // SAFETY: kill has no memory effects; the pid was checked above
+        unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Event sounds from the freedesktop sound theme
+//
+// A small stand-in for libcanberra: event ids such as "bell" or
+// "dialog-warning" are looked up in <data dir>/sounds/<theme>/stereo,
+// falling back to shorter ids ("dialog-warning-auth" becomes
+// "dialog-warning"), then to the inherited themes and finally the
+// "freedesktop" theme. The file is played with paplay, or pw-play where
+// only PipeWire is installed. Other programs ask for sounds through the
+// org.xfce_rs.EventSounds service the daemon mode serves.
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::{debug, warn};
+use xfce_rs_ipc::sound::SoundPlayer;
+
+use crate::settings::SoundConfig;
+
+/// The theme every other one ends up inheriting from
+const FALLBACK_THEME: &str = "freedesktop";
+const EXTENSIONS: [&str; 3] = ["oga", "ogg", "wav"];
+/// Guards against inheritance loops in broken themes
+const MAX_THEMES: usize = 16;
+
+/// Plays event sounds with the user's sound settings
+pub struct EventSoundPlayer {
+    config: SoundConfig,
+}
+
+impl EventSoundPlayer {
+    pub async fn load() -> Self {
+        Self {
+            config: SoundConfig::load().await,
+        }
+    }
+}
+
+impl SoundPlayer for EventSoundPlayer {
+    fn play(&self, event_id: &str) -> bool {
+        play_event_sound(&self.config, event_id)
+    }
+}
+
+/// Start playing the sound for `event_id`, e.g. "bell". Returns false if
+/// event sounds are off, the theme has no such sound or no player worked.
+pub fn play_event_sound(config: &SoundConfig, event_id: &str) -> bool {
+    if !config.enabled {
+        return false;
+    }
+    let Some(path) = find_sound(&config.theme_name, event_id) else {
+        debug!("No sound for event {} in theme {}", event_id, config.theme_name);
+        return false;
+    };
+
+    match spawn_player(&path, event_id) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to play {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+/// The sound file for `event_id` in `theme` or the themes it inherits
+pub fn find_sound(theme: &str, event_id: &str) -> Option<PathBuf> {
+    let dirs = sound_dirs();
+    let themes = theme_chain(&dirs, theme);
+    event_names(event_id).find_map(|name| {
+        themes.iter().find_map(|theme| {
+            dirs.iter().find_map(|dir| {
+                let stereo = dir.join(theme).join("stereo");
+                EXTENSIONS
+                    .iter()
+                    .map(|extension| stereo.join(format!("{}.{}", name, extension)))
+                    .find(|path| path.is_file())
+            })
+        })
+    })
+}
+
+/// Directories holding sound themes, the user's own first
+fn sound_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = dirs::data_dir().map(|dir| dir.join("sounds")).into_iter().collect();
+    let system = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs.extend(system.split(':').filter(|dir| !dir.is_empty()).map(|dir| Path::new(dir).join("sounds")));
+    dirs
+}
+
+/// `theme` followed by the themes it inherits from, depth first, ending
+/// with the freedesktop theme
+fn theme_chain(dirs: &[PathBuf], theme: &str) -> Vec<String> {
+    let mut chain: Vec<String> = Vec::new();
+    let mut pending = vec![theme.to_string()];
+    while let Some(theme) = pending.pop() {
+        if chain.contains(&theme) || chain.len() >= MAX_THEMES {
+            continue;
+        }
+        pending.extend(inherited_themes(dirs, &theme).into_iter().rev());
+        chain.push(theme);
+    }
+    if !chain.iter().any(|theme| theme == FALLBACK_THEME) {
+        chain.push(FALLBACK_THEME.to_string());
+    }
+    chain
+}
+
+/// `Inherits=` of the first index.theme found for `theme`
+fn inherited_themes(dirs: &[PathBuf], theme: &str) -> Vec<String> {
+    let Some(index) = dirs
+        .iter()
+        .find_map(|dir| std::fs::read_to_string(dir.join(theme).join("index.theme")).ok())
+    else {
+        return Vec::new();
+    };
+    index
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Inherits="))
+        .map(|themes| {
+            themes
+                .split(',')
+                .map(str::trim)
+                .filter(|theme| !theme.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `event_id` and its ever shorter forms: "dialog-warning-auth",
+/// "dialog-warning", "dialog"
+fn event_names(event_id: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(event_id), |name| name.rsplit_once('-').map(|(shorter, _)| shorter))
+}
+
+fn spawn_player(path: &Path, event_id: &str) -> std::io::Result<()> {
+    let paplay = Command::new("paplay")
+        .arg(format!("--property=event.id={}", event_id))
+        .arg("--property=media.role=event")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+    let mut child = match paplay {
+        Err(e) if e.kind() == ErrorKind::NotFound => Command::new("pw-play")
+            .arg("--media-role=Notification")
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()?,
+        other => other?,
+    };
+
+    // Reap the player once the sound is over
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
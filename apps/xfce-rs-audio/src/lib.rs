@@ -4,6 +4,11 @@ pub mod mpris;
 pub mod devices;
 pub mod notifications;
 pub mod sink_inputs;
+pub mod source_outputs;
+pub mod settings;
+pub mod backend;
+#[cfg(feature = "pipewire")]
+pub mod pipewire;
 
 // Types used across modules
 #[derive(Debug, Clone)]
@@ -22,6 +27,24 @@ pub struct DevicePort {
     pub available: String,
 }
 
+/// A configuration of a sound card, e.g. A2DP or HSP/HFP on a headset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardProfile {
+    pub name: String,
+    pub description: String,
+    pub priority: u32,
+    /// Whether the hardware can use the profile right now
+    pub available: bool,
+}
+
+/// Volume of one channel of a device
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceChannel {
+    /// Position from the channel map, e.g. "Front Left"
+    pub position: String,
+    pub volume_percent: f32,
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioDeviceDetails {
     pub index: u32,
@@ -31,6 +54,8 @@ pub struct AudioDeviceDetails {
 
     pub volume_percent: f32,
     pub muted: bool,
+    /// Per-channel volumes in channel map order
+    pub channels: Vec<DeviceChannel>,
 
     pub state: String,
     pub driver: Option<String>,
@@ -43,6 +68,10 @@ pub struct AudioDeviceDetails {
 
     pub ports: Vec<DevicePort>,
     pub active_port: Option<String>,
+
+    /// Profiles of the card the device belongs to
+    pub profiles: Vec<CardProfile>,
+    pub active_profile: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -51,9 +80,39 @@ pub struct NowPlaying {
     pub artist: String,
     pub album: String,
     pub album_art: Option<String>,
-    pub position: u64,
+    /// Seconds into the track at `updated`
+    pub position: f64,
     pub length: u64,
     pub playing: bool,
+    /// Playback speed, 1.0 being normal
+    pub rate: f64,
+    /// When `position` was read from the player
+    pub updated: std::time::Instant,
     pub player_name: String,
+    /// D-Bus name of the player, where the media controls go
+    pub bus_name: String,
+}
+
+impl NowPlaying {
+    /// Position at `now` in seconds, moved along at the playback rate while
+    /// playing so progress shows between updates from the player
+    pub fn position_at(&self, now: std::time::Instant) -> f64 {
+        let mut position = self.position;
+        if self.playing {
+            position += now.saturating_duration_since(self.updated).as_secs_f64() * self.rate;
+        }
+        if self.length > 0 {
+            position = position.min(self.length as f64);
+        }
+        position
+    }
+}
+
+/// A media player offered in the player picker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaPlayer {
+    pub bus_name: String,
+    pub identity: String,
+    pub playing: bool,
 }
 
@@ -1,19 +1,36 @@
 use iced::widget::{
-    column, container, row, text, button, slider, scrollable, space,
-    mouse_area,
+    canvas, column, container, row, text, text_input, button, image, mouse_area, pick_list, slider,
+    vertical_slider, scrollable, space,
 };
-use iced::{Alignment, Element, Length, Task, Theme, Color, window, Subscription};
+use iced::keyboard::{self, Key};
+use iced::{event, window, Alignment, Element, Event, Length, Task, Theme, Color, Subscription};
 use xfce_rs_ui::styles;
 use xfce_rs_ui::colors;
+use xfce_rs_ui::{fonts, icon, instance, scale, theme};
+use xfce_rs_ui::animation::{self, Animated};
+use xfce_rs_ui::chrome::{self, ChromeEvent};
+use xfce_rs_ui::focus::{self, FocusEvent, FocusRing};
+use std::fmt;
+use std::time::Instant;
 use tracing::{debug, warn, info};
 
-mod pulseaudio;
-mod mpris;
-mod devices;
-mod notifications;
-mod sink_inputs;
+mod album_art;
+mod meters;
+mod bluetooth;
+mod media_keys;
+mod event_sounds;
+mod equalizer;
+mod combine;
+mod osd;
+mod stream_memory;
+mod mic_test;
 
-use xfce_rs_audio::{AudioDevice, AudioDeviceDetails, DevicePort, NowPlaying};
+use xfce_rs_ipc::bluez::BluetoothAudioDevice;
+use xfce_rs_ipc::mpris::MprisEvent;
+// Sound server and player access is shared with xfce-rs-audioctl
+use xfce_rs_audio::backend::backend;
+use xfce_rs_audio::{devices, mpris, pulseaudio, settings, sink_inputs, source_outputs};
+use xfce_rs_audio::{AudioDevice, AudioDeviceDetails, DevicePort, MediaPlayer, NowPlaying};
 
 pub fn main() -> iced::Result {
     // Initialize tracing subscriber for logging
@@ -21,34 +38,100 @@ pub fn main() -> iced::Result {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
     
+    // `--daemon`: no mixer window, just media keys and the volume overlay
+    if std::env::args().skip(1).any(|arg| arg == "--daemon") {
+        return osd::run();
+    }
+
     info!("Audio application starting");
     
-    iced::application(AudioApp::new, AudioApp::update, AudioApp::view)
-        .title(AudioApp::title)
-        .theme(AudioApp::theme)
-        .style(AudioApp::style)
-        .subscription(AudioApp::subscription)
-        .window(iced::window::Settings {
+    // `--popup x,y`: a quick mixer for the panel, its top-left corner at x,y
+    let popup = popup_position(std::env::args().skip(1));
+    // Launching either again raises the one already open
+    instance::claim(if popup.is_some() { POPUP_APP_ID } else { MIXER_APP_ID }, &[]);
+    let window = match popup {
+        Some(position) => iced::window::Settings {
+            size: POPUP_SIZE,
+            position: iced::window::Position::Specific(position),
+            transparent: true,
+            decorations: false,
+            resizable: false,
+            level: iced::window::Level::AlwaysOnTop,
+            ..Default::default()
+        },
+        None => iced::window::Settings {
             size: iced::Size::new(900.0, 650.0),
             position: iced::window::Position::Centered,
             transparent: true,
             decorations: false,
             ..Default::default()
-        })
+        },
+    };
+    
+    fonts::load();
+    iced::application(move || AudioApp::new(popup.is_some()), AudioApp::update, AudioApp::view)
+        .settings(fonts::settings())
+        .title(AudioApp::title)
+        .theme(AudioApp::theme)
+        .style(AudioApp::style)
+        .scale_factor(AudioApp::scale_factor)
+        .subscription(AudioApp::subscription)
+        .window(window)
         .run()
 }
 
+/// Bus names the running mixer and popup own
+const MIXER_APP_ID: &str = "org.xfce_rs.Audio";
+const POPUP_APP_ID: &str = "org.xfce_rs.AudioPopup";
+
+/// Size of the popup mixer
+const POPUP_SIZE: iced::Size = iced::Size::new(360.0, 480.0);
+
+/// Icon size in the application volume rows
+const APP_ICON_SIZE: u16 = 32;
+
+/// Position given with `--popup x,y` or `--popup=x,y`
+fn popup_position(mut args: impl Iterator<Item = String>) -> Option<iced::Point> {
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--popup") {
+            Some("") => args.next()?,
+            Some(value) => value.strip_prefix('=')?.to_string(),
+            None => continue,
+        };
+        let (x, y) = value.split_once(',')?;
+        match (x.trim().parse(), y.trim().parse()) {
+            (Ok(x), Ok(y)) => return Some(iced::Point::new(x, y)),
+            _ => {
+                warn!("Ignoring --popup {}: expected x,y", value);
+                return None;
+            }
+        }
+    }
+    None
+}
+
 struct AudioApp {
+    // Compact mixer opened from the panel, closed when it loses focus
+    popup: bool,
+    
     // Volume state
     #[allow(unused)] // Used in view_volume_controls (line 509, 519, 522)
     volume: f32,
     #[allow(unused)] // Used in view_volume_controls (line 509)
     muted: bool,
+    // Slider position; glides when the volume changes outside this app
+    volume_thumb: Animated,
+    // Time of the latest animation frame
+    now: Instant,
     mic_volume: f32,
     mic_muted: bool,
     
     // Currently playing
     now_playing: Option<NowPlaying>,
+    // Every running player, for the player picker
+    players: Vec<MediaPlayer>,
+    // Decoded album art by mpris:artUrl; None while loading or if it failed
+    album_art: std::collections::HashMap<String, Option<image::Handle>>,
     
     // Devices
     output_devices: Vec<AudioDevice>,
@@ -61,18 +144,58 @@ struct AudioApp {
     // Per-app volume controls
     sink_inputs: Vec<sink_inputs::SinkInput>,
     show_app_volumes: bool,
+    // Per-app capture controls
+    source_outputs: Vec<source_outputs::SourceOutput>,
+    
+    // Slider range and step sizes
+    volume_config: settings::VolumeConfig,
+    // Shift is held: scrolling moves sliders by the fine step
+    fine_adjust: bool,
+    
+    // Paired Bluetooth audio devices
+    bluetooth_devices: Vec<BluetoothAudioDevice>,
+    bluetooth_config: settings::BluetoothConfig,
+    // Device paths with a connect or disconnect underway
+    bluetooth_pending: std::collections::HashSet<String>,
+    
+    // Preferred devices when the default one is unplugged
+    fallback_config: settings::FallbackConfig,
+    
+    // Equalizer settings by output sink name, and the presets to pick from
+    equalizers: std::collections::HashMap<String, equalizer::EqSettings>,
+    eq_presets: Vec<equalizer::Preset>,
+    // Name typed for saving the current curve as a preset
+    eq_preset_name: String,
+    
+    // Outputs picked for simultaneous output, and whether it is on
+    combine: combine::CombineSettings,
+    
+    // Microphone test underway, if any
+    mic_test: Option<MicTest>,
+    
+    // Per-channel sliders in the details panels, and whether they move together
+    show_channels: bool,
+    lock_channels: bool,
+    
+    // Smoothed peak levels, 0.0 to 1.0
+    levels: std::collections::HashMap<meters::MeterTarget, f32>,
     
     // UI state
     show_devices: bool,
     notification: Option<String>,
+    // Keyboard focus over focus_order()
+    focus: FocusRing,
     
     // Debouncing for app volume updates
     pending_app_volume_updates: std::collections::HashMap<u32, f32>,
+    pending_capture_volume_updates: std::collections::HashMap<u32, f32>,
     // Debouncing for master volume updates
     pending_master_volume: Option<f32>,
     pending_mic_volume: Option<f32>,
     // MPRIS metadata per sink input (keyed by application_name)
     sink_input_mpris_metadata: std::collections::HashMap<String, NowPlaying>,
+    // Icon file per application name; None while resolving or if none fits
+    app_icons: std::collections::HashMap<String, Option<String>>,
 }
 
 
@@ -94,34 +217,229 @@ enum Message {
     InputDeviceDetailsUpdate(Option<AudioDeviceDetails>),
     SetOutputPort(u32, String),
     SetInputPort(u32, String),
+    /// Switch a card to another profile; the flag tells which details panel asked
+    SetCardProfile(u32, String, bool),
+    CardProfileSet(bool, Result<Option<AudioDeviceDetails>, String>),
     ToggleDevices,
     #[allow(dead_code)]
     ToggleAppVolumes,
     AppVolumeChanged(u32, f32),
     AppVolumeChangedDebounced(u32, f32), // Debounced version that actually calls PulseAudio
     AppMuteToggled(u32),
+    /// Route a sink input to another output device
+    MoveSinkInput(u32, u32),
+    SinkInputMoved(Result<(), String>),
     SinkInputsUpdate(Vec<sink_inputs::SinkInput>),
+    /// Icon file found for an application name
+    AppIconResolved(String, Option<String>),
+    IconsLoaded,
+    CaptureVolumeChanged(u32, f32),
+    CaptureVolumeChangedDebounced(u32, f32), // Debounced version that actually calls PulseAudio
+    CaptureMuteToggled(u32),
+    SourceOutputsUpdate(Vec<source_outputs::SourceOutput>),
     NowPlayingUpdate(Option<NowPlaying>),
+    PlayersUpdate(Vec<MediaPlayer>),
+    /// Control another player, chosen in the player picker
+    SelectPlayer(String),
+    AlbumArtLoaded(String, Option<image::Handle>),
     VolumeUpdate(f32, bool),
     MicVolumeUpdate(f32, bool),
     DevicesUpdate(Vec<AudioDevice>, Vec<AudioDevice>),
     ClearNotification,
-    WindowDragged,
-    Minimize,
-    Maximize,
-    Close,
+    Chrome(ChromeEvent),
     PollUpdates,
+    Player(MprisEvent),
+    Pulse(pulseaudio::PulseEvent),
+    ThemeChanged,
+    Frame(Instant),
+    Focus(FocusEvent),
+    Levels(meters::Levels),
+    VolumeConfigLoaded(settings::VolumeConfig),
+    BluetoothConfigLoaded(settings::BluetoothConfig),
+    FallbackConfigLoaded(settings::FallbackConfig),
+    EqualizersLoaded(std::collections::HashMap<String, equalizer::EqSettings>, Vec<equalizer::Preset>),
+    /// Turn the equalizer of an output sink on or off
+    EqToggled(String, bool),
+    EqLoudnessToggled(String, bool),
+    EqPresetSelected(String, equalizer::Preset),
+    /// Gain of a band while its slider is dragged
+    EqGainChanged(String, usize, f32),
+    /// A band slider was let go; restart the chain with the new curve
+    EqGainReleased(String),
+    EqApplied(Result<(), String>),
+    EqPresetNameChanged(String),
+    EqSavePreset(String),
+    EqPresetSaved(Result<Vec<equalizer::Preset>, String>),
+    CombineLoaded(combine::CombineSettings),
+    /// Turn simultaneous output on or off
+    CombineToggled(bool),
+    /// Add an output to the combined sink or take it out, by sink name
+    CombineOutputToggled(String),
+    CombineApplied(Result<(), String>),
+    ToggleChannels,
+    ToggleChannelLock,
+    /// A channel slider of the output (true) or input details panel moved
+    ChannelVolumeChanged(bool, usize, f32),
+    /// The stereo balance slider moved, -1.0 being all left
+    BalanceChanged(bool, f32),
+    /// A channel or balance slider was let go; set the device's channels
+    ChannelVolumesReleased(bool),
+    ChannelVolumesSet(Result<(), String>),
+    /// Test an input device, by source name
+    StartMicTest(String, mic_test::TestMode),
+    MicLoopbackStarted(Result<u32, String>),
+    /// The loopback module ran its time
+    MicLoopbackExpired(u32),
+    StopMicTest,
+    MicRecorded(Result<std::path::PathBuf, String>),
+    MicTestFinished(Result<(), String>),
+    BluetoothUpdate(Vec<BluetoothAudioDevice>),
+    /// Connect (true) or disconnect the Bluetooth device at a path
+    BluetoothConnect(String, bool),
+    BluetoothConnected(String, Result<(), String>),
+    ModifiersChanged(keyboard::Modifiers),
+    /// Move the output volume by a number of steps
+    StepVolume(f32),
+    /// The popup lost focus
+    PopupUnfocused,
+    /// Leave the popup for the full mixer
+    OpenMixer,
+    /// Launched again while running
+    Activated,
+}
+
+/// Entry of an application's output device picker
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputChoice {
+    index: u32,
+    description: String,
+}
+
+impl fmt::Display for OutputChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+/// Entry of a card's profile picker
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProfileChoice {
+    name: String,
+    description: String,
+    available: bool,
+}
+
+impl fmt::Display for ProfileChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.available {
+            write!(f, "{}", self.description)
+        } else {
+            write!(f, "{} (unavailable)", self.description)
+        }
+    }
+}
+
+/// A microphone test of the source `source`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MicTest {
+    source: String,
+    phase: MicTestPhase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MicTestPhase {
+    /// Loading the loopback module
+    Starting,
+    /// Looping back through the module with this index
+    Looping(u32),
+    Recording,
+    Playing,
+}
+
+/// Response curve of an equalizer, drawn above its band sliders
+struct EqCurve {
+    gains: Vec<f32>,
+}
+
+impl<Message> canvas::Program<Message> for EqCurve {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &iced::Renderer,
+        _theme: &Theme,
+        bounds: iced::Rectangle,
+        _cursor: iced::mouse::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        // Each band sits in the middle of its slider's column
+        let point = |band: usize, gain: f32| {
+            iced::Point::new(
+                bounds.width * (band as f32 + 0.5) / self.gains.len().max(1) as f32,
+                bounds.height * (0.5 - gain / (2.0 * equalizer::MAX_GAIN)),
+            )
+        };
+
+        let zero = canvas::Path::line(
+            iced::Point::new(0.0, bounds.height / 2.0),
+            iced::Point::new(bounds.width, bounds.height / 2.0),
+        );
+        frame.stroke(&zero, canvas::Stroke::default().with_color(colors::bg_input()).with_width(1.0));
+
+        let curve = canvas::Path::new(|path| {
+            for (band, gain) in self.gains.iter().enumerate() {
+                if band == 0 {
+                    path.move_to(point(band, *gain));
+                } else {
+                    path.line_to(point(band, *gain));
+                }
+            }
+        });
+        frame.stroke(&curve, canvas::Stroke::default().with_color(colors::accent_primary()).with_width(2.0));
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Buttons reachable from the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Mute,
+    MicMute,
+    Devices,
+    Previous,
+    PlayPause,
+    Next,
+}
+
+impl Control {
+    fn message(self) -> Message {
+        match self {
+            Control::Mute => Message::ToggleMute,
+            Control::MicMute => Message::ToggleMicMute,
+            Control::Devices => Message::ToggleDevices,
+            Control::Previous => Message::Previous,
+            Control::PlayPause => Message::PlayPause,
+            Control::Next => Message::Next,
+        }
+    }
 }
 
 impl AudioApp {
-    fn new() -> (Self, Task<Message>) {
+    fn new(popup: bool) -> (Self, Task<Message>) {
         (
             Self {
+                popup,
                 volume: 50.0,
                 muted: false,
+                volume_thumb: Animated::new(50.0),
+                now: Instant::now(),
                 mic_volume: 50.0,
                 mic_muted: false,
                 now_playing: None,
+                players: Vec::new(),
+                album_art: std::collections::HashMap::new(),
                 output_devices: Vec::new(),
                 input_devices: Vec::new(),
                 selected_output: None,
@@ -130,25 +448,55 @@ impl AudioApp {
                 selected_input_details: None,
                 sink_inputs: Vec::new(),
                 show_app_volumes: true, // Show by default
+                source_outputs: Vec::new(),
+                volume_config: settings::VolumeConfig::default(),
+                fine_adjust: false,
+                bluetooth_devices: Vec::new(),
+                bluetooth_config: settings::BluetoothConfig::default(),
+                bluetooth_pending: std::collections::HashSet::new(),
+                fallback_config: settings::FallbackConfig::default(),
+                equalizers: std::collections::HashMap::new(),
+                eq_presets: equalizer::builtin_presets(),
+                eq_preset_name: String::new(),
+                combine: combine::CombineSettings::default(),
+                mic_test: None,
+                show_channels: false,
+                lock_channels: true,
+                levels: std::collections::HashMap::new(),
                 show_devices: false,
                 notification: None,
+                focus: FocusRing::default(),
                 pending_app_volume_updates: std::collections::HashMap::new(),
+                pending_capture_volume_updates: std::collections::HashMap::new(),
                 pending_master_volume: None,
                 pending_mic_volume: None,
                 sink_input_mpris_metadata: std::collections::HashMap::new(),
+                app_icons: std::collections::HashMap::new(),
             },
             Task::batch(vec![
+                Task::perform(settings::VolumeConfig::load(), Message::VolumeConfigLoaded),
+                Task::perform(settings::BluetoothConfig::load(), Message::BluetoothConfigLoaded),
+                Task::perform(settings::FallbackConfig::load(), Message::FallbackConfigLoaded),
+                Task::perform(
+                    async { (equalizer::load_outputs().await, equalizer::load_presets().await) },
+                    |(outputs, presets)| Message::EqualizersLoaded(outputs, presets),
+                ),
+                Task::perform(combine::load(), Message::CombineLoaded),
+                Task::perform(
+                    bluetooth::get_devices(),
+                    |devices| Message::BluetoothUpdate(devices.unwrap_or_default()),
+                ),
                 // Initialize PulseAudio connection
                 Task::perform(
                     async {
                         debug!("Initializing PulseAudio connection...");
-                        if let Err(e) = pulseaudio::init().await {
-                            warn!("Failed to initialize PulseAudio: {}", e);
+                        if let Err(e) = backend().init().await {
+                            warn!("Failed to connect to the sound server: {}", e);
                         } else {
                             debug!("PulseAudio initialized successfully");
                         }
                         // Get initial volume state
-                        let vol_result = pulseaudio::get_volume().await;
+                        let vol_result = backend().volume().await;
                         match vol_result {
                             Ok((vol, muted)) => {
                                 debug!("Initial volume: {:.1}%, muted: {}", vol, muted);
@@ -203,7 +551,7 @@ impl AudioApp {
                 Task::perform(
                     async {
                         debug!("Fetching initial device list...");
-                        match pulseaudio::get_devices().await {
+                        match backend().devices().await {
                             Ok((outputs, inputs)) => {
                                 debug!("Initial devices: {} outputs, {} inputs", outputs.len(), inputs.len());
                                 (outputs, inputs)
@@ -223,7 +571,7 @@ impl AudioApp {
                 Task::perform(
                     async {
                         debug!("Fetching initial mic volume...");
-                        match pulseaudio::get_mic_volume().await {
+                        match backend().mic_volume().await {
                             Ok((vol, muted)) => {
                                 debug!("Initial mic volume: {:.1}%, muted: {}", vol, muted);
                                 (vol, muted)
@@ -243,7 +591,7 @@ impl AudioApp {
                 Task::perform(
                     async {
                         debug!("Fetching initial sink inputs (app volumes)...");
-                        match sink_inputs::get_sink_inputs().await {
+                        match backend().sink_inputs().await {
                             Ok(inputs) => {
                                 debug!("Initial sink inputs: {} applications", inputs.len());
                                 inputs
@@ -259,6 +607,11 @@ impl AudioApp {
                         Message::SinkInputsUpdate(inputs)
                     },
                 ),
+                // Get initial source outputs (apps recording)
+                Task::perform(
+                    source_outputs::get_source_outputs(),
+                    |outputs| Message::SourceOutputsUpdate(outputs.unwrap_or_default()),
+                ),
             ]),
         )
     }
@@ -268,7 +621,11 @@ impl AudioApp {
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        theme::iced_theme()
+    }
+
+    fn scale_factor(&self) -> f32 {
+        scale::factor()
     }
 
     fn style(&self, theme: &Theme) -> iced::theme::Style {
@@ -279,16 +636,295 @@ impl AudioApp {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        // Poll for updates every 2 seconds (reduced from 500ms for better performance)
-        iced::time::every(std::time::Duration::from_secs(2))
-            .map(|_| Message::PollUpdates)
+        Subscription::batch([
+            // Volumes, devices and streams arrive as PulseAudio events
+            pulseaudio::events().map(Message::Pulse),
+            mpris::events().map(Message::Player),
+            // Still polled too, for players that don't announce every change
+            iced::time::every(std::time::Duration::from_secs(2))
+                .map(|_| Message::PollUpdates),
+            theme::subscription().map(|_| Message::ThemeChanged),
+            icon::subscription().map(|_| Message::IconsLoaded),
+            instance::activations().map(|_| Message::Activated),
+            chrome::shortcuts().map(Message::Chrome),
+            animation::frames(self.volume_thumb.is_animating(self.now) || self.shows_progress()).map(Message::Frame),
+            focus::keyboard().map(Message::Focus),
+            event::listen_with(volume_key),
+            meters::levels(self.meter_targets()).map(Message::Levels),
+            if self.popup {
+                window::events().filter_map(|(_id, event)| {
+                    matches!(event, window::Event::Unfocused).then_some(Message::PopupUnfocused)
+                })
+            } else {
+                Subscription::none()
+            },
+        ])
+    }
+
+    /// Meter of an application stream, on the monitor of the sink it plays to
+    fn sink_input_meter(&self, input: &sink_inputs::SinkInput) -> Option<meters::MeterTarget> {
+        let sink = self.output_devices.iter().find(|device| device.index == input.sink_index)?;
+        Some(meters::MeterTarget::SinkInput {
+            index: input.index,
+            monitor: format!("{}.monitor", sink.name),
+        })
+    }
+
+    /// The default device `removed` is gone: make the preferred remaining
+    /// device the default, move the streams left behind over to it and
+    /// tell the user
+    fn fall_back(&mut self, removed: &AudioDevice, is_output: bool) -> Task<Message> {
+        let (devices, priority, kind) = if is_output {
+            (&self.output_devices, &self.fallback_config.output_priority, "Output")
+        } else {
+            (&self.input_devices, &self.fallback_config.input_priority, "Input")
+        };
+        let clear_later = Task::perform(
+            tokio::time::sleep(std::time::Duration::from_secs(3)),
+            |_| Message::ClearNotification,
+        );
+        let Some(fallback) = devices::DeviceManager::elect_default(devices, priority).cloned() else {
+            info!("Default {} {} was removed, nothing to fall back to", kind, removed.name);
+            self.notification = Some(format!("{} was removed", removed.description));
+            return clear_later;
+        };
+
+        // Streams on the removed device, or already rescued by the server
+        // to a device of its own choosing
+        let server_pick = devices.iter().find(|device| device.is_default).map(|device| device.index);
+        let left_behind = |device_index: u32| {
+            device_index != fallback.index && (device_index == removed.index || Some(device_index) == server_pick)
+        };
+        let streams: Vec<u32> = if is_output {
+            self.sink_inputs.iter().filter(|input| left_behind(input.sink_index)).map(|input| input.index).collect()
+        } else {
+            self.source_outputs.iter().filter(|output| left_behind(output.source_index)).map(|output| output.index).collect()
+        };
+
+        info!("Default {} {} was removed, falling back to {} and moving {} streams", kind, removed.name, fallback.name, streams.len());
+        self.notification = Some(format!("{} was removed. {}: {}", removed.description, kind, fallback.description));
+
+        let device_index = fallback.index;
+        Task::batch([
+            Task::perform(
+                async move {
+                    let switched = if is_output {
+                        backend().set_default_output(device_index).await
+                    } else {
+                        backend().set_default_input(device_index).await
+                    };
+                    if let Err(e) = switched {
+                        warn!("Failed to set fallback device: {}", e);
+                    }
+                    for stream in streams {
+                        let moved = if is_output {
+                            backend().move_sink_input(stream, device_index).await
+                        } else {
+                            source_outputs::move_source_output(stream, device_index).await
+                        };
+                        if let Err(e) = moved {
+                            warn!("Failed to move stream {} to the fallback device: {}", stream, e);
+                        }
+                    }
+                    backend().devices().await.unwrap_or((Vec::new(), Vec::new()))
+                },
+                |(outputs, inputs)| Message::DevicesUpdate(outputs, inputs),
+            ),
+            clear_later,
+        ])
+    }
+
+    /// Remember the volume and mute state of application stream `index`
+    /// for the next time the application plays
+    fn remember_app(&self, index: u32) -> Task<Message> {
+        let Some(input) = self.sink_inputs.iter().find(|i| i.index == index) else {
+            return Task::none();
+        };
+        let (key, volume, muted) = (input.app_key(), input.volume, input.muted);
+        Task::future(async move {
+            if let Err(e) = stream_memory::remember_app(key, volume, muted).await {
+                warn!("Failed to remember the application volume: {}", e);
+            }
+        })
+        .discard()
+    }
+
+    /// Store the equalizer of output `sink` and restart its filter chain
+    fn apply_equalizer(&self, sink: String) -> Task<Message> {
+        let settings = self.equalizers.get(&sink).cloned().unwrap_or_default();
+        let description = self
+            .output_devices
+            .iter()
+            .find(|device| device.name == sink)
+            .map(|device| device.description.clone())
+            .unwrap_or_else(|| sink.clone());
+        Task::perform(
+            async move {
+                equalizer::save_output(sink.clone(), settings.clone()).await?;
+                equalizer::apply(sink, description, settings).await
+            },
+            |result: anyhow::Result<()>| Message::EqApplied(result.map_err(|e| format!("{:#}", e))),
+        )
+    }
+
+    fn details_mut(&mut self, is_output: bool) -> Option<&mut AudioDeviceDetails> {
+        if is_output {
+            self.selected_output_details.as_mut()
+        } else {
+            self.selected_input_details.as_mut()
+        }
+    }
+
+    /// Store the simultaneous output settings and rebuild the combined sink
+    fn apply_combine(&self) -> Task<Message> {
+        let settings = self.combine.clone();
+        let available = self.output_devices.iter().map(|device| device.name.clone()).collect();
+        Task::perform(
+            async move {
+                combine::save(settings.clone()).await?;
+                combine::apply(settings, available).await
+            },
+            |result: anyhow::Result<()>| Message::CombineApplied(result.map_err(|e| format!("{:#}", e))),
+        )
+    }
+
+    /// Application streams, the devices while the device list is open and
+    /// the microphone under test
+    fn meter_targets(&self) -> Vec<meters::MeterTarget> {
+        let mut targets: Vec<meters::MeterTarget> =
+            self.sink_inputs.iter().filter_map(|input| self.sink_input_meter(input)).collect();
+        if self.show_devices {
+            targets.extend(self.output_devices.iter().map(|device| meters::MeterTarget::sink(&device.name)));
+            targets.extend(self.input_devices.iter().map(|device| meters::MeterTarget::source(&device.name)));
+        }
+        if let Some(test) = &self.mic_test {
+            targets.push(meters::MeterTarget::source(&test.source));
+        }
+        targets
+    }
+
+    /// Controls in tab order, top to bottom as laid out
+    fn focus_order(&self) -> Vec<Control> {
+        // The popup has neither the device list nor the player
+        if self.popup {
+            return vec![Control::Mute, Control::MicMute];
+        }
+        let mut order = vec![Control::Mute, Control::MicMute, Control::Devices];
+        if self.shows_now_playing() {
+            order.extend([Control::Previous, Control::PlayPause, Control::Next]);
+        }
+        order
+    }
+
+    fn is_focused(&self, control: Control) -> bool {
+        self.focus
+            .focused()
+            .and_then(|index| self.focus_order().get(index).copied())
+            == Some(control)
+    }
+
+    /// Whether the player has real metadata rather than the "Playing from
+    /// X" fallback
+    fn shows_now_playing(&self) -> bool {
+        self.now_playing.as_ref().is_some_and(|np| {
+            np.title != format!("Playing from {}", np.player_name) && !np.title.starts_with("Playing from")
+        })
+    }
+
+    /// The player's progress bar is up and moving, so it needs every frame
+    fn shows_progress(&self) -> bool {
+        !self.popup && self.shows_now_playing() && self.now_playing.as_ref().is_some_and(|np| np.playing)
+    }
+
+    /// Look up icons for applications not seen before, once per name
+    fn resolve_app_icons(&mut self) -> Task<Message> {
+        let mut tasks = Vec::new();
+        for input in &self.sink_inputs {
+            if self.app_icons.contains_key(&input.application_name) {
+                continue;
+            }
+            self.app_icons.insert(input.application_name.clone(), None);
+            let app_name = input.application_name.clone();
+            let candidates = input.icon_candidates();
+            tasks.push(Task::perform(
+                async move {
+                    tokio::task::spawn_blocking(move || {
+                        let resolver = icon::IconResolver::default();
+                        candidates
+                            .iter()
+                            .find_map(|name| resolver.resolve(name, APP_ICON_SIZE))
+                            .map(|path| path.to_string_lossy().into_owned())
+                    })
+                    .await
+                    .ok()
+                    .flatten()
+                },
+                move |path| Message::AppIconResolved(app_name, path),
+            ));
+        }
+        Task::batch(tasks)
+    }
+
+    /// Art URLs of the player and the per-app rows
+    fn album_art_urls(&self) -> impl Iterator<Item = &String> {
+        self.now_playing
+            .iter()
+            .chain(self.sink_input_mpris_metadata.values())
+            .filter_map(|np| np.album_art.as_ref())
+    }
+
+    /// Start loading art that isn't loaded yet, and forget art no longer shown
+    fn load_album_art(&mut self) -> Task<Message> {
+        let urls: std::collections::HashSet<String> = self.album_art_urls().cloned().collect();
+        self.album_art.retain(|url, _| urls.contains(url));
+
+        let missing: Vec<String> = urls.into_iter().filter(|url| !self.album_art.contains_key(url)).collect();
+        Task::batch(missing.into_iter().map(|url| {
+            // Mark as loading so later updates don't fetch it again
+            self.album_art.insert(url.clone(), None);
+            Task::perform(album_art::load(url.clone()), move |result| {
+                let handle = match result {
+                    Ok(art) => Some(image::Handle::from_rgba(art.width, art.height, art.pixels)),
+                    Err(e) => {
+                        warn!("Failed to load album art: {:#}", e);
+                        None
+                    }
+                };
+                Message::AlbumArtLoaded(url, handle)
+            })
+        }).collect::<Vec<_>>())
+    }
+
+    /// Album art of `np` at `size`, or `placeholder` until it's loaded
+    fn view_album_art<'a>(&self, np: Option<&NowPlaying>, size: f32, placeholder: Element<'a, Message>) -> Element<'a, Message> {
+        let handle = np
+            .and_then(|np| np.album_art.as_ref())
+            .and_then(|url| self.album_art.get(url).cloned().flatten());
+        match handle {
+            Some(handle) => image(handle)
+                .width(size)
+                .height(size)
+                .content_fit(iced::ContentFit::Cover)
+                .into(),
+            None => placeholder,
+        }
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
+            Message::Focus(event) => {
+                // The media controls come and go with the player
+                let order = self.focus_order();
+                self.focus.set_len(order.len());
+                match self.focus.update(event).and_then(|index| order.get(index)) {
+                    Some(control) => self.update(control.message()),
+                    None => Task::none(),
+                }
+            }
             Message::VolumeChanged(vol) => {
                 // Update UI immediately for smooth slider movement
                 self.volume = vol;
+                self.volume_thumb.set(vol);
                 
                 // Store pending update for debouncing
                 self.pending_master_volume = Some(vol);
@@ -311,7 +947,7 @@ impl AudioApp {
                         self.pending_master_volume = None;
                         let muted = self.muted;
                         Task::perform(
-                            pulseaudio::set_volume(vol),
+                            backend().set_volume(vol),
                             move |_| Message::VolumeUpdate(vol, muted),
                         )
                     } else {
@@ -328,7 +964,7 @@ impl AudioApp {
                 let muted = self.muted;
                 let volume = self.volume;
                 Task::perform(
-                    pulseaudio::set_mute(muted),
+                    backend().set_mute(muted),
                     move |_| Message::VolumeUpdate(volume, muted),
                 )
             }
@@ -357,7 +993,7 @@ impl AudioApp {
                         self.pending_mic_volume = None;
                         let mic_muted = self.mic_muted;
                         Task::perform(
-                            pulseaudio::set_mic_volume(vol),
+                            backend().set_mic_volume(vol),
                             move |_| Message::MicVolumeUpdate(vol, mic_muted),
                         )
                     } else {
@@ -374,38 +1010,87 @@ impl AudioApp {
                 let mic_muted = self.mic_muted;
                 let mic_volume = self.mic_volume;
                 Task::perform(
-                    pulseaudio::set_mic_mute(mic_muted),
+                    backend().set_mic_mute(mic_muted),
                     move |_| Message::MicVolumeUpdate(mic_volume, mic_muted),
                 )
             }
+            // Media controls go to the player shown, not whichever is active by then
             Message::PlayPause => {
-                let now_playing = self.now_playing.clone();
+                let Some(now_playing) = self.now_playing.clone() else {
+                    return Task::none();
+                };
                 Task::perform(
-                    mpris::play_pause(),
-                    move |_| Message::NowPlayingUpdate(now_playing),
+                    mpris::play_pause(now_playing.bus_name.clone()),
+                    move |_| Message::NowPlayingUpdate(Some(now_playing)),
                 )
             }
             Message::Previous => {
-                let now_playing = self.now_playing.clone();
+                let Some(now_playing) = self.now_playing.clone() else {
+                    return Task::none();
+                };
                 Task::perform(
-                    mpris::previous(),
-                    move |_| Message::NowPlayingUpdate(now_playing),
+                    mpris::previous(now_playing.bus_name.clone()),
+                    move |_| Message::NowPlayingUpdate(Some(now_playing)),
                 )
             }
             Message::Next => {
-                let now_playing = self.now_playing.clone();
+                let Some(now_playing) = self.now_playing.clone() else {
+                    return Task::none();
+                };
                 Task::perform(
-                    mpris::next(),
-                    move |_| Message::NowPlayingUpdate(now_playing),
+                    mpris::next(now_playing.bus_name.clone()),
+                    move |_| Message::NowPlayingUpdate(Some(now_playing)),
                 )
             }
             Message::Seek(pos) => {
-                let now_playing = self.now_playing.clone();
+                let Some(now_playing) = &mut self.now_playing else {
+                    return Task::none();
+                };
+                // Move the slider right away; the Seeked signal or the
+                // refresh below correct it
+                now_playing.position = pos as f64;
+                now_playing.updated = Instant::now();
                 Task::perform(
-                    mpris::seek(pos),
-                    move |_| Message::NowPlayingUpdate(now_playing),
+                    mpris::seek(now_playing.bus_name.clone(), pos),
+                    |result| match result {
+                        Ok(()) => Message::PollUpdates,
+                        Err(e) => {
+                            warn!("Failed to seek: {}", e);
+                            Message::ClearNotification
+                        }
+                    },
                 )
             }
+            Message::Player(event) => match event {
+                MprisEvent::Seeked { player, position } => {
+                    if let Some(now_playing) = self.now_playing.as_mut().filter(|np| np.bus_name == player) {
+                        now_playing.position = position.as_secs_f64();
+                        now_playing.updated = Instant::now();
+                    }
+                    Task::none()
+                }
+                MprisEvent::PlayerChanged(_) | MprisEvent::ActivePlayerChanged(_) => Task::perform(
+                    async { mpris::get_now_playing().await.ok().flatten() },
+                    Message::NowPlayingUpdate,
+                ),
+                MprisEvent::PlayerAdded(_) | MprisEvent::PlayerRemoved(_) => Task::perform(
+                    async { mpris::players().await.unwrap_or_default() },
+                    Message::PlayersUpdate,
+                ),
+            },
+            Message::PlayersUpdate(players) => {
+                self.players = players;
+                Task::none()
+            }
+            Message::SelectPlayer(bus_name) => Task::perform(
+                async move {
+                    if let Err(e) = mpris::select_player(bus_name).await {
+                        warn!("Failed to select player: {}", e);
+                    }
+                    mpris::get_now_playing().await.ok().flatten()
+                },
+                Message::NowPlayingUpdate,
+            ),
             Message::SelectOutputDevice(idx) => {
                 debug!("SelectOutputDevice called with index {}", idx);
                 if let Some(device) = self.output_devices.get(idx) {
@@ -415,8 +1100,8 @@ impl AudioApp {
                     Task::batch(vec![
                         Task::perform(
                             async move {
-                                pulseaudio::set_default_output(device_index).await.ok();
-                                pulseaudio::get_devices().await.unwrap_or((Vec::new(), Vec::new()))
+                                backend().set_default_output(device_index).await.ok();
+                                backend().devices().await.unwrap_or((Vec::new(), Vec::new()))
                             },
                             |(outputs, inputs)| Message::DevicesUpdate(outputs, inputs),
                         ),
@@ -442,8 +1127,8 @@ impl AudioApp {
                     Task::batch(vec![
                         Task::perform(
                             async move {
-                                pulseaudio::set_default_input(device_index).await.ok();
-                                pulseaudio::get_devices().await.unwrap_or((Vec::new(), Vec::new()))
+                                backend().set_default_input(device_index).await.ok();
+                                backend().devices().await.unwrap_or((Vec::new(), Vec::new()))
                             },
                             |(outputs, inputs)| Message::DevicesUpdate(outputs, inputs),
                         ),
@@ -478,7 +1163,9 @@ impl AudioApp {
             }
             Message::SetOutputPort(device_index, port_name) => {
                 let port_name_clone = port_name.clone();
+                let device_name = self.output_devices.iter().find(|d| d.index == device_index).map(|d| d.name.clone());
                 Task::batch(vec![
+                    remember_port(device_name, port_name),
                     Task::perform(
                         pulseaudio::set_output_device_port(device_index, port_name_clone),
                         |_| Message::ClearNotification,
@@ -494,7 +1181,9 @@ impl AudioApp {
             }
             Message::SetInputPort(device_index, port_name) => {
                 let port_name_clone = port_name.clone();
+                let device_name = self.input_devices.iter().find(|d| d.index == device_index).map(|d| d.name.clone());
                 Task::batch(vec![
+                    remember_port(device_name, port_name),
                     Task::perform(
                         pulseaudio::set_input_device_port(device_index, port_name_clone),
                         |_| Message::ClearNotification,
@@ -508,6 +1197,32 @@ impl AudioApp {
                         ),
                 ])
             }
+            Message::SetCardProfile(card_index, profile_name, is_output) => {
+                Task::perform(
+                    pulseaudio::set_card_profile(card_index, profile_name, is_output),
+                    move |result| Message::CardProfileSet(is_output, result.map_err(|e| e.to_string())),
+                )
+            }
+            Message::CardProfileSet(is_output, Ok(details)) => {
+                // The card's devices were replaced, so the old selection is stale
+                if is_output {
+                    self.selected_output_details = details;
+                } else {
+                    self.selected_input_details = details;
+                }
+                Task::perform(
+                    backend().devices(),
+                    |result| {
+                        let (outputs, inputs) = result.unwrap_or((Vec::new(), Vec::new()));
+                        Message::DevicesUpdate(outputs, inputs)
+                    },
+                )
+            }
+            Message::CardProfileSet(_, Err(e)) => {
+                warn!("Failed to switch card profile: {}", e);
+                self.notification = Some(format!("Could not switch profile: {}", e));
+                Task::perform(tokio::time::sleep(tokio::time::Duration::from_secs(3)), |_| Message::ClearNotification)
+            }
             Message::ToggleDevices => {
                 self.show_devices = !self.show_devices;
                 debug!("ToggleDevices: show_devices={}, current output devices={}, input devices={}, selected_output={:?}, selected_input={:?}", 
@@ -517,7 +1232,7 @@ impl AudioApp {
                     // Always refresh devices first to ensure we have latest data
                     debug!("Refreshing devices list before showing device panel");
                     Task::perform(
-                        pulseaudio::get_devices(),
+                        backend().devices(),
                         |result| {
                             let (outputs, inputs) = result.unwrap_or((Vec::new(), Vec::new()));
                             debug!("Device refresh completed: {} outputs, {} inputs", outputs.len(), inputs.len());
@@ -532,7 +1247,7 @@ impl AudioApp {
                     self.show_app_volumes = !self.show_app_volumes;
                     // Always fetch when showing
                     Task::perform(
-                        sink_inputs::get_sink_inputs(),
+                        backend().sink_inputs(),
                         |inputs| Message::SinkInputsUpdate(inputs.unwrap_or_default()),
                     )
                 }
@@ -561,10 +1276,13 @@ impl AudioApp {
                     if (latest_volume - volume).abs() < 0.1 {
                         // This is still the latest, apply it
                         self.pending_app_volume_updates.remove(&index);
-                        Task::perform(
-                            sink_inputs::set_sink_input_volume(index, volume),
-                            |_| Message::ClearNotification,
-                        )
+                        Task::batch([
+                            Task::perform(
+                                backend().set_sink_input_volume(index, volume),
+                                |_| Message::ClearNotification,
+                            ),
+                            self.remember_app(index),
+                        ])
                     } else {
                         // A newer update came in, ignore this one
                         Task::none()
@@ -579,12 +1297,47 @@ impl AudioApp {
                     .find(|i| i.index == index)
                     .map(|i| !i.muted)
                     .unwrap_or(false);
+                // Show it right away; the server event confirms it
+                if let Some(input) = self.sink_inputs.iter_mut().find(|i| i.index == index) {
+                    input.muted = muted;
+                }
+                Task::batch([
+                    Task::perform(
+                        backend().set_sink_input_mute(index, muted),
+                        |_| Message::ClearNotification,
+                    ),
+                    self.remember_app(index),
+                ])
+            }
+            Message::MoveSinkInput(index, sink_index) => {
+                // Show the new device right away; the server event confirms it
+                if let Some(input) = self.sink_inputs.iter_mut().find(|i| i.index == index) {
+                    input.sink_index = sink_index;
+                }
                 Task::perform(
-                    sink_inputs::set_sink_input_mute(index, muted),
-                    |_| Message::ClearNotification,
+                    backend().move_sink_input(index, sink_index),
+                    |result| Message::SinkInputMoved(result.map_err(|e| e.to_string())),
                 )
             }
-            Message::SinkInputsUpdate(inputs) => {
+            Message::SinkInputMoved(Ok(())) => Task::none(),
+            Message::SinkInputMoved(Err(e)) => {
+                warn!("Failed to move application stream: {}", e);
+                self.notification = Some(format!("Could not switch output: {}", e));
+                Task::batch([
+                    Task::perform(
+                        backend().sink_inputs(),
+                        |inputs| Message::SinkInputsUpdate(inputs.unwrap_or_default()),
+                    ),
+                    Task::perform(tokio::time::sleep(tokio::time::Duration::from_secs(3)), |_| Message::ClearNotification),
+                ])
+            }
+            Message::SinkInputsUpdate(mut inputs) => {
+                // Server events arrive mid-drag; keep the sliders where the user has them
+                for input in &mut inputs {
+                    if let Some(&volume) = self.pending_app_volume_updates.get(&input.index) {
+                        input.volume = volume;
+                    }
+                }
                 self.sink_inputs = inputs.clone();
                 
                 // Match sink inputs to MPRIS players
@@ -609,6 +1362,57 @@ impl AudioApp {
                     }
                 }
                 
+                self.resolve_app_icons()
+            }
+            Message::AppIconResolved(app_name, path) => {
+                self.app_icons.insert(app_name, path);
+                Task::none()
+            }
+            Message::IconsLoaded => Task::none(),
+            Message::Activated => instance::raise(),
+            Message::CaptureVolumeChanged(index, volume) => {
+                if let Some(output) = self.source_outputs.iter_mut().find(|o| o.index == index) {
+                    output.volume = volume;
+                }
+                self.pending_capture_volume_updates.insert(index, volume);
+                Task::perform(
+                    async move {
+                        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+                        (index, volume)
+                    },
+                    |(idx, vol)| Message::CaptureVolumeChangedDebounced(idx, vol),
+                )
+            }
+            Message::CaptureVolumeChangedDebounced(index, volume) => {
+                // Only apply the latest value of a drag
+                match self.pending_capture_volume_updates.get(&index) {
+                    Some(&latest_volume) if (latest_volume - volume).abs() < 0.1 => {
+                        self.pending_capture_volume_updates.remove(&index);
+                        Task::perform(
+                            source_outputs::set_source_output_volume(index, volume),
+                            |_| Message::ClearNotification,
+                        )
+                    }
+                    _ => Task::none(),
+                }
+            }
+            Message::CaptureMuteToggled(index) => {
+                let muted = self.source_outputs.iter()
+                    .find(|o| o.index == index)
+                    .map(|o| !o.muted)
+                    .unwrap_or(false);
+                Task::perform(
+                    source_outputs::set_source_output_mute(index, muted),
+                    |_| Message::ClearNotification,
+                )
+            }
+            Message::SourceOutputsUpdate(mut outputs) => {
+                for output in &mut outputs {
+                    if let Some(&volume) = self.pending_capture_volume_updates.get(&output.index) {
+                        output.volume = volume;
+                    }
+                }
+                self.source_outputs = outputs;
                 Task::none()
             }
             Message::NowPlayingUpdate(np) => {
@@ -630,13 +1434,24 @@ impl AudioApp {
                     }
                 }
                 
+                self.load_album_art()
+            }
+            Message::AlbumArtLoaded(url, handle) => {
+                // Unless the track changed in the meantime
+                if let Some(entry) = self.album_art.get_mut(&url) {
+                    *entry = handle;
+                }
                 Task::none()
             }
+            Message::VolumeUpdate(_, _) if self.pending_master_volume.is_some() => Task::none(),
             Message::VolumeUpdate(vol, muted) => {
                 self.volume = vol;
                 self.muted = muted;
+                self.now = Instant::now();
+                self.volume_thumb.go_to(vol, self.now);
                 Task::none()
             }
+            Message::MicVolumeUpdate(_, _) if self.pending_mic_volume.is_some() => Task::none(),
             Message::MicVolumeUpdate(vol, muted) => {
                 self.mic_volume = vol;
                 self.mic_muted = muted;
@@ -644,6 +1459,22 @@ impl AudioApp {
             }
             Message::DevicesUpdate(outputs, inputs) => {
                 debug!("DevicesUpdate received: {} outputs, {} inputs", outputs.len(), inputs.len());
+                let known_outputs: std::collections::HashSet<String> =
+                    self.output_devices.iter().map(|device| device.name.clone()).collect();
+                let previous_output = self.output_devices.iter().find(|device| device.is_default).cloned();
+                let previous_input = self.input_devices.iter().find(|device| device.is_default).cloned();
+                // Selections are list positions, which shift as devices come and go
+                let selected_output_name = self.selected_output
+                    .and_then(|idx| self.output_devices.get(idx))
+                    .map(|device| device.name.clone());
+                let selected_input_name = self.selected_input
+                    .and_then(|idx| self.input_devices.get(idx))
+                    .map(|device| device.name.clone());
+                // The equalizer's filter sinks stand in front of real devices
+                let outputs: Vec<AudioDevice> = outputs
+                    .into_iter()
+                    .filter(|device| !equalizer::is_equalizer_sink(&device.name))
+                    .collect();
                 // Filter and sort devices
                 let (filtered_outputs, filtered_inputs) = devices::DeviceManager::filter_devices(
                     outputs,
@@ -654,9 +1485,45 @@ impl AudioApp {
                 self.input_devices = devices::DeviceManager::sort_devices(filtered_inputs);
                 debug!("After filtering/sorting: {} output devices, {} input devices", self.output_devices.len(), self.input_devices.len());
                 
+                self.selected_output = selected_output_name
+                    .and_then(|name| self.output_devices.iter().position(|device| device.name == name));
+                if self.selected_output.is_none() {
+                    self.selected_output_details = None;
+                }
+                self.selected_input = selected_input_name
+                    .and_then(|name| self.input_devices.iter().position(|device| device.name == name));
+                if self.selected_input.is_none() {
+                    self.selected_input_details = None;
+                }
+                
+                let mut tasks = Vec::new();
+                
+                // The default device was unplugged
+                if let Some(removed) = previous_output.filter(|removed| !self.output_devices.iter().any(|device| device.name == removed.name)) {
+                    tasks.push(self.fall_back(&removed, true));
+                }
+                if let Some(removed) = previous_input.filter(|removed| !self.input_devices.iter().any(|device| device.name == removed.name)) {
+                    tasks.push(self.fall_back(&removed, false));
+                }
+                
+                // A Bluetooth headset that just connected takes over the output
+                let connected_headset = self.output_devices.iter().find(|device| {
+                    bluetooth::is_bluetooth_sink(&device.name) && !device.is_default && !known_outputs.contains(&device.name)
+                });
+                if let Some(headset) = connected_headset.filter(|_| self.bluetooth_config.auto_switch && !known_outputs.is_empty()) {
+                    info!("Switching output to Bluetooth device {}", headset.description);
+                    let device_index = headset.index;
+                    tasks.push(Task::perform(
+                        async move {
+                            backend().set_default_output(device_index).await.ok();
+                            backend().devices().await.unwrap_or((Vec::new(), Vec::new()))
+                        },
+                        |(outputs, inputs)| Message::DevicesUpdate(outputs, inputs),
+                    ));
+                }
+                
                 // If show_devices is true and no device selected, auto-select defaults
                 if self.show_devices {
-                    let mut tasks = Vec::new();
                     
                     if self.selected_output.is_none() {
                         if let Some((idx, device)) = self.output_devices.iter().enumerate().find(|(_, d)| d.is_default) {
@@ -704,120 +1571,363 @@ impl AudioApp {
                             debug!("No input devices available to auto-select");
                         }
                     }
-                    
-                    if !tasks.is_empty() {
-                        return Task::batch(tasks);
-                    }
                 }
                 
-                Task::none()
+                Task::batch(tasks)
             }
             Message::ClearNotification => {
                 self.notification = None;
                 Task::none()
             }
-            Message::WindowDragged => {
-                window::latest().and_then(|id| window::drag(id))
+            Message::Chrome(event) => chrome::perform(event),
+            Message::ThemeChanged => Task::none(),
+            Message::Levels(peaks) => {
+                // Meters without news fall towards silence
+                for level in self.levels.values_mut() {
+                    *level = meters::smooth(*level, 0.0);
+                }
+                for (target, peak) in peaks {
+                    let level = self.levels.entry(target).or_insert(0.0);
+                    *level = meters::smooth(*level, peak);
+                }
+                self.levels.retain(|_, level| *level > 0.001);
+                Task::none()
             }
-            Message::Minimize => {
-                window::latest().and_then(|id| window::minimize(id, true))
+            Message::PopupUnfocused => iced::exit(),
+            Message::OpenMixer => {
+                match std::env::current_exe() {
+                    Ok(exe) => {
+                        if let Err(e) = std::process::Command::new(exe).spawn() {
+                            warn!("Failed to open the mixer: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Failed to find the audio app binary: {}", e),
+                }
+                iced::exit()
             }
-            Message::Maximize => {
-                window::latest().and_then(|id| window::maximize(id, true))
+            Message::BluetoothConfigLoaded(config) => {
+                self.bluetooth_config = config;
+                Task::none()
             }
-            Message::Close => {
-                window::latest().and_then(|id| window::close(id))
+            Message::FallbackConfigLoaded(config) => {
+                self.fallback_config = config;
+                Task::none()
             }
-            Message::PollUpdates => {
-                // Poll for volume updates
-                let current_vol = self.volume;
-                let current_muted = self.muted;
-                let current_mic_vol = self.mic_volume;
-                let current_mic_muted = self.mic_muted;
-                let current_now_playing = self.now_playing.clone();
-                let current_sink_inputs = self.sink_inputs.clone();
-                
-                let current_vol_clone = current_vol;
-                let current_muted_clone = current_muted;
-                let current_mic_vol_clone = current_mic_vol;
-                let current_mic_muted_clone = current_mic_muted;
-                let current_now_playing_clone = current_now_playing.clone();
-                
-                Task::batch(vec![
-                    // Poll PulseAudio volume
-                    Task::perform(
-                        async move { pulseaudio::get_volume().await.unwrap_or((current_vol_clone, current_muted_clone)) },
-                        move |(vol, muted)| {
-                            let vol_diff = (vol - current_vol_clone).abs();
-                            if vol_diff > 0.1 || muted != current_muted_clone {
-                                Message::VolumeUpdate(vol, muted)
-                            } else {
-                                Message::ClearNotification
-                            }
-                        },
-                    ),
-                    // Poll mic volume
+            Message::EqualizersLoaded(outputs, presets) => {
+                self.equalizers = outputs;
+                self.eq_presets = presets;
+                Task::none()
+            }
+            Message::EqToggled(sink, enabled) => {
+                self.equalizers.entry(sink.clone()).or_default().enabled = enabled;
+                self.apply_equalizer(sink)
+            }
+            Message::EqLoudnessToggled(sink, loudness) => {
+                self.equalizers.entry(sink.clone()).or_default().loudness = loudness;
+                self.apply_equalizer(sink)
+            }
+            Message::EqPresetSelected(sink, preset) => {
+                self.equalizers.entry(sink.clone()).or_default().apply_preset(&preset);
+                self.apply_equalizer(sink)
+            }
+            Message::EqGainChanged(sink, band, gain) => {
+                self.equalizers.entry(sink).or_default().set_gain(band, gain);
+                Task::none()
+            }
+            Message::EqGainReleased(sink) => self.apply_equalizer(sink),
+            Message::EqApplied(Ok(())) => Task::none(),
+            Message::EqApplied(Err(e)) | Message::EqPresetSaved(Err(e)) => {
+                warn!("Equalizer: {}", e);
+                self.notification = Some(format!("Equalizer: {}", e));
+                Task::perform(tokio::time::sleep(tokio::time::Duration::from_secs(3)), |_| Message::ClearNotification)
+            }
+            Message::EqPresetNameChanged(name) => {
+                self.eq_preset_name = name;
+                Task::none()
+            }
+            Message::EqSavePreset(sink) => {
+                let name = self.eq_preset_name.trim().to_string();
+                if name.is_empty() {
+                    return Task::none();
+                }
+                let settings = self.equalizers.entry(sink).or_default();
+                let preset = equalizer::Preset {
+                    name: name.clone(),
+                    gains: (0..equalizer::BANDS.len()).map(|band| settings.gain(band)).collect(),
+                    builtin: false,
+                };
+                settings.preset = Some(name);
+                Task::perform(
+                    equalizer::save_preset(preset),
+                    |result| Message::EqPresetSaved(result.map_err(|e| format!("{:#}", e))),
+                )
+            }
+            Message::EqPresetSaved(Ok(presets)) => {
+                self.eq_presets = presets;
+                self.eq_preset_name.clear();
+                Task::none()
+            }
+            Message::CombineLoaded(settings) => {
+                self.combine = settings;
+                Task::none()
+            }
+            Message::CombineToggled(enabled) => {
+                self.combine.enabled = enabled;
+                self.apply_combine()
+            }
+            Message::CombineOutputToggled(sink) => {
+                self.combine.toggle_output(&sink);
+                if self.combine.enabled {
+                    self.apply_combine()
+                } else {
+                    Task::perform(combine::save(self.combine.clone()), |result| {
+                        Message::CombineApplied(result.map_err(|e| format!("{:#}", e)))
+                    })
+                }
+            }
+            // The new sink shows up through the device change events
+            Message::CombineApplied(Ok(())) => Task::none(),
+            Message::CombineApplied(Err(e)) => {
+                warn!("Simultaneous output: {}", e);
+                self.notification = Some(format!("Simultaneous output: {}", e));
+                Task::perform(tokio::time::sleep(tokio::time::Duration::from_secs(3)), |_| Message::ClearNotification)
+            }
+            Message::ToggleChannels => {
+                self.show_channels = !self.show_channels;
+                Task::none()
+            }
+            Message::ToggleChannelLock => {
+                self.lock_channels = !self.lock_channels;
+                Task::none()
+            }
+            Message::ChannelVolumeChanged(is_output, channel, volume) => {
+                let lock = self.lock_channels;
+                if let Some(details) = self.details_mut(is_output) {
+                    for (index, slot) in details.channels.iter_mut().enumerate() {
+                        if lock || index == channel {
+                            slot.volume_percent = volume;
+                        }
+                    }
+                }
+                Task::none()
+            }
+            Message::BalanceChanged(is_output, balance) => {
+                if let Some(details) = self.details_mut(is_output) {
+                    if let [left, right] = details.channels.as_mut_slice() {
+                        let loudest = left.volume_percent.max(right.volume_percent);
+                        left.volume_percent = loudest * (1.0 - balance.max(0.0));
+                        right.volume_percent = loudest * (1.0 + balance.min(0.0));
+                    }
+                }
+                Task::none()
+            }
+            Message::ChannelVolumesReleased(is_output) => {
+                let Some(details) = self.details_mut(is_output) else {
+                    return Task::none();
+                };
+                let volumes = details.channels.iter().map(|channel| channel.volume_percent).collect();
+                Task::perform(
+                    pulseaudio::set_device_channel_volumes(details.index, is_output, volumes),
+                    |result| Message::ChannelVolumesSet(result.map_err(|e| format!("{:#}", e))),
+                )
+            }
+            // The master sliders follow through the volume change events
+            Message::ChannelVolumesSet(Ok(())) => Task::none(),
+            Message::ChannelVolumesSet(Err(e)) => {
+                warn!("Failed to set the channel volumes: {}", e);
+                self.notification = Some(format!("Failed to set the channel volumes: {}", e));
+                Task::perform(tokio::time::sleep(tokio::time::Duration::from_secs(3)), |_| Message::ClearNotification)
+            }
+            Message::StartMicTest(source, mode) => {
+                if self.mic_test.is_some() {
+                    return Task::none();
+                }
+                match mode {
+                    mic_test::TestMode::Loopback => {
+                        self.mic_test = Some(MicTest { source: source.clone(), phase: MicTestPhase::Starting });
+                        Task::perform(
+                            mic_test::start_loopback(source),
+                            |result| Message::MicLoopbackStarted(result.map_err(|e| format!("{:#}", e))),
+                        )
+                    }
+                    mic_test::TestMode::Record => {
+                        self.mic_test = Some(MicTest { source: source.clone(), phase: MicTestPhase::Recording });
+                        Task::perform(
+                            mic_test::record(source),
+                            |result| Message::MicRecorded(result.map_err(|e| format!("{:#}", e))),
+                        )
+                    }
+                }
+            }
+            Message::MicLoopbackStarted(Ok(module)) => match &mut self.mic_test {
+                Some(test) if test.phase == MicTestPhase::Starting => {
+                    test.phase = MicTestPhase::Looping(module);
+                    Task::perform(tokio::time::sleep(mic_test::LOOPBACK_DURATION), move |_| {
+                        Message::MicLoopbackExpired(module)
+                    })
+                }
+                // The test ended some other way meanwhile
+                _ => stop_loopback(module),
+            },
+            Message::MicLoopbackExpired(module) => {
+                if self.mic_test.as_ref().map(|test| test.phase) != Some(MicTestPhase::Looping(module)) {
+                    return Task::none();
+                }
+                self.mic_test = None;
+                stop_loopback(module)
+            }
+            Message::StopMicTest => match self.mic_test.as_ref().map(|test| test.phase) {
+                Some(MicTestPhase::Looping(module)) => {
+                    self.mic_test = None;
+                    stop_loopback(module)
+                }
+                // A recording is short, let it finish
+                _ => Task::none(),
+            },
+            Message::MicRecorded(Ok(path)) => {
+                if let Some(test) = &mut self.mic_test {
+                    test.phase = MicTestPhase::Playing;
+                }
+                Task::perform(
+                    mic_test::play(path),
+                    |result| Message::MicTestFinished(result.map_err(|e| format!("{:#}", e))),
+                )
+            }
+            Message::MicTestFinished(Ok(())) => {
+                self.mic_test = None;
+                Task::none()
+            }
+            Message::MicLoopbackStarted(Err(e)) | Message::MicRecorded(Err(e)) | Message::MicTestFinished(Err(e)) => {
+                warn!("Microphone test failed: {}", e);
+                self.mic_test = None;
+                self.notification = Some(format!("Microphone test failed: {}", e));
+                Task::perform(tokio::time::sleep(tokio::time::Duration::from_secs(3)), |_| Message::ClearNotification)
+            }
+            Message::BluetoothUpdate(devices) => {
+                self.bluetooth_devices = devices;
+                Task::none()
+            }
+            Message::BluetoothConnect(path, connect) => {
+                if !self.bluetooth_pending.insert(path.clone()) {
+                    return Task::none();
+                }
+                let done = path.clone();
+                Task::perform(
+                    async move {
+                        if connect {
+                            bluetooth::connect(path).await
+                        } else {
+                            bluetooth::disconnect(path).await
+                        }
+                    },
+                    move |result| Message::BluetoothConnected(done, result.map_err(|e| e.to_string())),
+                )
+            }
+            Message::BluetoothConnected(path, result) => {
+                self.bluetooth_pending.remove(&path);
+                let refresh = Task::perform(
+                    bluetooth::get_devices(),
+                    |devices| Message::BluetoothUpdate(devices.unwrap_or_default()),
+                );
+                match result {
+                    Ok(()) => refresh,
+                    Err(e) => {
+                        warn!("Bluetooth connection change failed: {}", e);
+                        self.notification = Some(format!("Bluetooth: {}", e));
+                        Task::batch([
+                            refresh,
+                            Task::perform(tokio::time::sleep(tokio::time::Duration::from_secs(3)), |_| Message::ClearNotification),
+                        ])
+                    }
+                }
+            }
+            Message::VolumeConfigLoaded(config) => {
+                self.volume_config = config;
+                Task::none()
+            }
+            Message::ModifiersChanged(modifiers) => {
+                self.fine_adjust = modifiers.shift();
+                Task::none()
+            }
+            Message::StepVolume(steps) => {
+                let volume = self.volume_config.adjust(self.volume, steps, false);
+                self.update(Message::VolumeChanged(volume))
+            }
+            Message::Frame(now) => {
+                self.now = now;
+                Task::none()
+            }
+            Message::PollUpdates => {
+                let current_now_playing = self.now_playing.clone();
+                let current_players = self.players.clone();
+                Task::batch([
                     Task::perform(
-                        async move { pulseaudio::get_mic_volume().await.unwrap_or((current_mic_vol_clone, current_mic_muted_clone)) },
-                        move |(vol, muted)| {
-                            let vol_diff = (vol - current_mic_vol_clone).abs();
-                            if vol_diff > 0.1 || muted != current_mic_muted_clone {
-                                Message::MicVolumeUpdate(vol, muted)
+                        async move { mpris::get_now_playing().await.ok().flatten() },
+                        move |np| {
+                            if np != current_now_playing {
+                                Message::NowPlayingUpdate(np)
                             } else {
                                 Message::ClearNotification
                             }
                         },
                     ),
-                    // Poll MPRIS now playing
                     Task::perform(
-                        async move { mpris::get_now_playing().await.ok().flatten() },
-                        move |np| {
-                            if np != current_now_playing_clone {
-                                Message::NowPlayingUpdate(np)
+                        async move { mpris::players().await.unwrap_or_default() },
+                        move |players| {
+                            if players != current_players {
+                                Message::PlayersUpdate(players)
                             } else {
                                 Message::ClearNotification
                             }
                         },
                     ),
-                    // Poll sink inputs if app volumes are shown
-                    if self.show_app_volumes {
-                        let current_sink_inputs_clone = current_sink_inputs.clone();
-                        Task::perform(
-                            async move { sink_inputs::get_sink_inputs().await.unwrap_or_default() },
-                            move |inputs| {
-                                // Simple comparison: check if lengths differ or any index changed
-                                let changed = inputs.len() != current_sink_inputs_clone.len() ||
-                                    inputs.iter().any(|i| {
-                                        !current_sink_inputs_clone.iter().any(|c| c.index == i.index && c.volume == i.volume && c.muted == i.muted)
-                                    });
-                                if changed {
-                                    Message::SinkInputsUpdate(inputs)
-                                } else {
-                                    Message::ClearNotification
-                                }
-                            },
-                        )
-                    } else {
-                        Task::none()
-                    },
                 ])
             }
+            Message::Pulse(event) => match event {
+                pulseaudio::PulseEvent::VolumeChanged => Task::batch([
+                    Task::perform(backend().volume(), |result| match result {
+                        Ok((vol, muted)) => Message::VolumeUpdate(vol, muted),
+                        Err(_) => Message::ClearNotification,
+                    }),
+                    Task::perform(backend().mic_volume(), |result| match result {
+                        Ok((vol, muted)) => Message::MicVolumeUpdate(vol, muted),
+                        Err(_) => Message::ClearNotification,
+                    }),
+                ]),
+                pulseaudio::PulseEvent::DevicesChanged => Task::batch([
+                    Task::perform(backend().devices(), |result| match result {
+                        Ok((outputs, inputs)) => Message::DevicesUpdate(outputs, inputs),
+                        Err(_) => Message::ClearNotification,
+                    }),
+                    // Bluetooth devices come and go with their sinks
+                    Task::perform(
+                        bluetooth::get_devices(),
+                        |devices| Message::BluetoothUpdate(devices.unwrap_or_default()),
+                    ),
+                    // The default device may have changed, and its volume with it
+                    self.update(Message::Pulse(pulseaudio::PulseEvent::VolumeChanged)),
+                ]),
+                pulseaudio::PulseEvent::SinkInputChanged if self.show_app_volumes => Task::perform(
+                    backend().sink_inputs(),
+                    |inputs| Message::SinkInputsUpdate(inputs.unwrap_or_default()),
+                ),
+                pulseaudio::PulseEvent::SinkInputChanged => Task::none(),
+                pulseaudio::PulseEvent::SourceOutputChanged => Task::perform(
+                    source_outputs::get_source_outputs(),
+                    |outputs| Message::SourceOutputsUpdate(outputs.unwrap_or_default()),
+                ),
+            },
         }
     }
 
     fn view(&self) -> Element<'_, Message> {
-        let header = self.view_header();
+        if self.popup {
+            return self.view_popup();
+        }
         
-        // Show Now Playing if we have any real metadata (title is not just "Playing from X")
-        let now_playing = if let Some(np) = &self.now_playing {
-            // Show if title is not the fallback pattern - even if artist is unknown, show the title
-            let is_real_metadata = np.title != format!("Playing from {}", np.player_name) && 
-                                   !np.title.starts_with("Playing from");
-            if is_real_metadata {
-                self.view_now_playing()
-            } else {
-                Element::from(space().height(0))
-            }
+        // Show Now Playing if we have any real metadata, even if the artist is unknown
+        let now_playing = if self.shows_now_playing() {
+            self.view_now_playing()
         } else {
             Element::from(space().height(0))
         };
@@ -827,6 +1937,19 @@ impl AudioApp {
         // Per-app volume controls are ALWAYS shown - this is the main feature
         let app_volume_controls = self.view_app_volume_controls();
         
+        // Only when something records, so the mic indicator means something
+        let app_capture_controls = if self.source_outputs.is_empty() {
+            Element::from(space().height(0))
+        } else {
+            self.view_app_capture_controls()
+        };
+        
+        let bluetooth_controls = if self.bluetooth_devices.is_empty() {
+            Element::from(space().height(0))
+        } else {
+            self.view_bluetooth_controls()
+        };
+        
         let device_controls = if self.show_devices {
             self.view_device_controls()
         } else {
@@ -834,43 +1957,18 @@ impl AudioApp {
         };
 
         let main_content = column![
-            header,
             volume_controls,
             app_volume_controls,  // Primary feature - show prominently
+            app_capture_controls,
+            bluetooth_controls,
             now_playing,  // Secondary - only if we have real metadata
             device_controls,
         ]
         .spacing(20)
-        .padding(30);
+        .padding([0, 10]);
 
-        let mut layers = vec![
-            // Glass base
-            container(space())
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .style(|theme| styles::glass_base(theme))
-                .into(),
-            // Highlights
-            container(space())
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .style(|theme| styles::glass_highlight_top(theme))
-                .into(),
-            container(space())
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .style(|theme| styles::glass_highlight_bottom(theme))
-                .into(),
-            // Drag area
-            mouse_area(container(space()).width(Length::Fill).height(Length::Fill))
-                .on_press(Message::WindowDragged)
-                .into(),
-            // Content
-            container(main_content)
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .into(),
-        ];
+        let title = text("🎵 Audio Control").size(20).color(colors::text_primary());
+        let mut layers = vec![chrome::window_frame(title, main_content, Message::Chrome)];
 
         // Notification
         if let Some(note) = &self.notification {
@@ -892,46 +1990,18 @@ impl AudioApp {
         iced::widget::Stack::with_children(layers).into()
     }
 
-    fn view_header(&self) -> Element<'_, Message> {
-        row![
-            // Window controls
-            row![
-                button(space().width(12).height(12))
-                    .on_press(Message::Close)
-                    .style(|theme, status| styles::window_control(theme, status, colors::CONTROL_CLOSE))
-                    .width(12).height(12),
-                button(space().width(12).height(12))
-                    .on_press(Message::Minimize)
-                    .style(|theme, status| styles::window_control(theme, status, colors::CONTROL_MIN))
-                    .width(12).height(12),
-                button(space().width(12).height(12))
-                    .on_press(Message::Maximize)
-                    .style(|theme, status| styles::window_control(theme, status, colors::CONTROL_MAX))
-                    .width(12).height(12),
-            ]
-            .spacing(8)
-            .padding(10),
-            
-            // Title
-            row![
-                text("🎵 Audio Control").size(20).color(colors::TEXT_PRIMARY),
-            ]
-            .width(Length::Fill)
-            .align_y(Alignment::Center),
-        ]
-        .height(40)
-        .align_y(Alignment::Center)
-        .into()
-    }
-
     fn view_now_playing(&self) -> Element<'_, Message> {
         if let Some(np) = &self.now_playing {
             let play_pause_icon = if np.playing { "⏸" } else { "▶" };
+            let previous_focused = self.is_focused(Control::Previous);
+            let play_pause_focused = self.is_focused(Control::PlayPause);
+            let next_focused = self.is_focused(Control::Next);
             
             column![
-                // Album art placeholder
+                self.view_player_picker(&np.bus_name),
+                // Album art, or a placeholder until it's loaded
                 container(
-                    text("🎵").size(120)
+                    self.view_album_art(Some(np), 300.0, text("🎵").size(120).into())
                 )
                 .width(300)
                 .height(300)
@@ -941,41 +2011,41 @@ impl AudioApp {
                 // Track info
                 column![
                     // Show title - always show if we have it
-                    text(&np.title).size(24).color(colors::TEXT_PRIMARY),
+                    text(&np.title).size(24).color(colors::text_primary()),
                     // Show artist or hide if unknown
                     if np.artist != "Unknown Artist" && !np.artist.is_empty() {
-                        text(&np.artist).size(18).color(colors::TEXT_SECONDARY)
+                        text(&np.artist).size(18).color(colors::text_secondary())
                     } else {
                         text("").size(18)
                     },
                     // Show album or hide if unknown
                     if np.album != "Unknown Album" && !np.album.is_empty() {
-                        text(&np.album).size(14).color(colors::TEXT_SECONDARY)
+                        text(&np.album).size(14).color(colors::text_secondary())
                     } else {
                         text("").size(14)
                     },
-                    text(format!("Source: {}", np.player_name)).size(12).color(colors::TEXT_SECONDARY),
+                    text(format!("Source: {}", np.player_name)).size(12).color(colors::text_secondary()),
                 ]
                 .spacing(5)
                 .align_x(Alignment::Center),
                 
                 // Progress bar
-                slider(0.0..=np.length.max(1) as f64, np.position as f64, |v| Message::Seek(v as u64))
+                slider(0.0..=np.length.max(1) as f64, np.position_at(self.now), |v| Message::Seek(v as u64))
                     .width(Length::Fill),
                 
                 // Controls
                 row![
                     button(text("⏮").size(24))
                         .on_press(Message::Previous)
-                        .style(|theme, status| styles::app_card(theme, status))
+                        .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), previous_focused))
                         .padding(10),
                     button(text(play_pause_icon).size(32))
                         .on_press(Message::PlayPause)
-                        .style(|theme, status| styles::app_card(theme, status))
+                        .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), play_pause_focused))
                         .padding(15),
                     button(text("⏭").size(24))
                         .on_press(Message::Next)
-                        .style(|theme, status| styles::app_card(theme, status))
+                        .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), next_focused))
                         .padding(10),
                 ]
                 .spacing(15),
@@ -985,7 +2055,7 @@ impl AudioApp {
         } else {
             container(
                 column![
-                    text("No media playing").size(18).color(colors::TEXT_SECONDARY),
+                    text("No media playing").size(18).color(colors::text_secondary()),
                 ]
                 .align_x(Alignment::Center)
             )
@@ -1000,18 +2070,20 @@ impl AudioApp {
     fn view_volume_controls(&self) -> Element<'_, Message> {
         let mute_icon = if self.muted { "🔇" } else { "🔊" };
         let mic_mute_icon = if self.mic_muted { "🎤🚫" } else { "🎤" };
+        let mute_focused = self.is_focused(Control::Mute);
+        let mic_mute_focused = self.is_focused(Control::MicMute);
+        let devices_focused = self.is_focused(Control::Devices);
         
         column![
             // Output volume
             row![
                 button(text(mute_icon).size(24))
                     .on_press(Message::ToggleMute)
-                    .style(|theme, status| styles::app_card(theme, status))
+                    .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), mute_focused))
                     .padding(8),
-                slider(0.0..=100.0, self.volume, Message::VolumeChanged)
-                    .width(Length::Fill)
-                    .step(1.0),
-                text(format!("{:.0}%", self.volume)).size(14).color(colors::TEXT_SECONDARY).width(50),
+                self.volume_slider(self.volume_thumb.value(self.now), self.volume, Message::VolumeChanged),
+                text(format!("{:.0}%", self.volume)).size(14).color(colors::text_secondary()).width(50),
+                text(db_label(self.volume)).size(12).color(colors::text_secondary()).width(70),
             ]
             .spacing(10)
             .align_y(Alignment::Center),
@@ -1020,23 +2092,27 @@ impl AudioApp {
             row![
                 button(text(mic_mute_icon).size(24))
                     .on_press(Message::ToggleMicMute)
-                    .style(|theme, status| styles::app_card(theme, status))
+                    .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), mic_mute_focused))
                     .padding(8),
-                slider(0.0..=100.0, self.mic_volume, Message::MicVolumeChanged)
-                    .width(Length::Fill)
-                    .step(1.0),
-                text(format!("{:.0}%", self.mic_volume)).size(14).color(colors::TEXT_SECONDARY).width(50),
+                self.volume_slider(self.mic_volume, self.mic_volume, Message::MicVolumeChanged),
+                text(format!("{:.0}%", self.mic_volume)).size(14).color(colors::text_secondary()).width(50),
+                text(db_label(self.mic_volume)).size(12).color(colors::text_secondary()).width(70),
             ]
             .spacing(10)
             .align_y(Alignment::Center),
             
             // App volumes are always shown now, so remove this toggle
             
-            // Device toggle
-            button(text(if self.show_devices { "Hide Devices" } else { "Show Devices" }).size(14))
-                .on_press(Message::ToggleDevices)
-                .style(|theme, status| styles::app_card(theme, status))
-                .padding(10),
+            // Device toggle, left to the full mixer
+            if self.popup {
+                Element::from(space().height(0))
+            } else {
+                button(text(if self.show_devices { "Hide Devices" } else { "Show Devices" }).size(14))
+                    .on_press(Message::ToggleDevices)
+                    .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), devices_focused))
+                    .padding(10)
+                    .into()
+            },
         ]
         .spacing(10)
         .into()
@@ -1047,7 +2123,7 @@ impl AudioApp {
         let input_details = self.view_device_details_panel(false);
 
         column![
-            text("Output Devices").size(16).color(colors::TEXT_PRIMARY),
+            text("Output Devices").size(16).color(colors::text_primary()),
             scrollable(
                 column(
                     self.output_devices.iter().enumerate().map(|(idx, device)| {
@@ -1056,12 +2132,13 @@ impl AudioApp {
                         let description = device.description.clone();
                         button(
                             column![
-                                text(description).size(14).color(colors::TEXT_PRIMARY),
+                                text(description).size(14).color(colors::text_primary()),
                                 if is_default {
-                                    text("Default").size(12).color(colors::ACCENT_PRIMARY)
+                                    text("Default").size(12).color(colors::accent_primary())
                                 } else {
                                     text("").size(12)
                                 },
+                                self.view_level(&meters::MeterTarget::sink(&device.name)),
                             ]
                             .spacing(2)
                         )
@@ -1086,7 +2163,7 @@ impl AudioApp {
             output_details,
             space().height(10),
             
-            text("Input Devices").size(16).color(colors::TEXT_PRIMARY),
+            text("Input Devices").size(16).color(colors::text_primary()),
             scrollable(
                 column(
                     self.input_devices.iter().enumerate().map(|(idx, device)| {
@@ -1095,12 +2172,13 @@ impl AudioApp {
                         let description = device.description.clone();
                         button(
                             column![
-                                text(description).size(14).color(colors::TEXT_PRIMARY),
+                                text(description).size(14).color(colors::text_primary()),
                                 if is_default {
-                                    text("Default").size(12).color(colors::ACCENT_PRIMARY)
+                                    text("Default").size(12).color(colors::accent_primary())
                                 } else {
                                     text("").size(12)
                                 },
+                                self.view_level(&meters::MeterTarget::source(&device.name)),
                             ]
                             .spacing(2)
                         )
@@ -1151,8 +2229,8 @@ impl AudioApp {
             };
             return container(
                 column![
-                    text(title).size(16).color(colors::TEXT_PRIMARY),
-                    text(message).size(12).color(colors::TEXT_SECONDARY),
+                    text(title).size(16).color(colors::text_primary()),
+                    text(message).size(12).color(colors::text_secondary()),
                 ]
                 .spacing(8),
             )
@@ -1166,7 +2244,7 @@ impl AudioApp {
         let active_port = details.active_port.clone().unwrap_or_default();
 
         let ports_row: Element<Message> = if details.ports.is_empty() {
-            container(text("No ports exposed by server").size(12).color(colors::TEXT_SECONDARY))
+            container(text("No ports exposed by server").size(12).color(colors::text_secondary()))
                 .width(Length::Fill)
                 .into()
         } else {
@@ -1209,10 +2287,44 @@ impl AudioApp {
                 .into()
         };
 
+        let profiles: Vec<ProfileChoice> = details
+            .profiles
+            .iter()
+            .map(|profile| ProfileChoice {
+                name: profile.name.clone(),
+                description: if profile.description.is_empty() {
+                    profile.name.clone()
+                } else {
+                    profile.description.clone()
+                },
+                available: profile.available,
+            })
+            .collect();
+        let profile_row: Element<Message> = match details.card {
+            Some(card_index) if !profiles.is_empty() => {
+                let active = profiles
+                    .iter()
+                    .find(|choice| Some(&choice.name) == details.active_profile.as_ref())
+                    .cloned();
+                column![
+                    text("Profile").size(13).color(colors::text_primary()),
+                    pick_list(profiles, active, move |choice: ProfileChoice| {
+                        Message::SetCardProfile(card_index, choice.name, is_output)
+                    })
+                    .placeholder("Card profile")
+                    .text_size(12)
+                    .width(Length::Fill),
+                ]
+                .spacing(8)
+                .into()
+            }
+            _ => column![].into(),
+        };
+
         container(
             column![
-                text(title).size(14).color(colors::TEXT_PRIMARY),
-                text(details.description.clone()).size(12).color(colors::TEXT_SECONDARY),
+                text(title).size(14).color(colors::text_primary()),
+                text(details.description.clone()).size(12).color(colors::text_secondary()),
                 text(format!(
                     "State: {}   Driver: {}   Card: {}",
                     details.state,
@@ -1220,22 +2332,29 @@ impl AudioApp {
                     details.card.map(|c| c.to_string()).unwrap_or_else(|| "n/a".to_string()),
                 ))
                 .size(12)
-                .color(colors::TEXT_SECONDARY),
+                .color(colors::text_secondary()),
                 text(format!(
                     "Sample: {}   Channels: {}",
                     details.sample_spec,
                     details.channel_map
                 ))
                 .size(12)
-                .color(colors::TEXT_SECONDARY),
+                .color(colors::text_secondary()),
                 text(format!(
                     "Latency: {} µs   Configured: {} µs",
                     details.latency_usec, details.configured_latency_usec
                 ))
                 .size(12)
-                .color(colors::TEXT_SECONDARY),
-                text("Ports").size(13).color(colors::TEXT_PRIMARY),
+                .color(colors::text_secondary()),
+                self.view_channels(&details, is_output),
+                text("Ports").size(13).color(colors::text_primary()),
                 ports_row,
+                profile_row,
+                if is_output {
+                    column![self.view_equalizer(details.name.clone()), self.view_combine()].spacing(12).into()
+                } else {
+                    self.view_mic_test(details.name.clone())
+                },
             ]
             .spacing(8),
         )
@@ -1244,17 +2363,300 @@ impl AudioApp {
         .into()
     }
 
+    /// Channel volumes of a device, folded away until asked for: a balance
+    /// slider for stereo devices and a slider per channel
+    fn view_channels(&self, details: &AudioDeviceDetails, is_output: bool) -> Element<'_, Message> {
+        let header = row![
+            button(text(if self.show_channels { "▾ Channels" } else { "▸ Channels" }).size(13))
+                .on_press(Message::ToggleChannels)
+                .style(|theme, status| styles::app_card(theme, status))
+                .padding(6),
+            space().width(Length::Fill),
+        ]
+        .align_y(Alignment::Center);
+        if !self.show_channels || details.channels.is_empty() {
+            return header.into();
+        }
+
+        let lock = self.lock_channels;
+        let header = header.push(
+            button(text(if lock { "🔒 Locked" } else { "🔓 Unlocked" }).size(12))
+                .on_press(Message::ToggleChannelLock)
+                .style(move |theme, status| {
+                    if lock {
+                        styles::app_card(theme, iced::widget::button::Status::Active)
+                    } else {
+                        styles::app_card(theme, status)
+                    }
+                })
+                .padding(6),
+        );
+
+        let max = self.volume_config.max();
+        let mut channels = column![header].spacing(6);
+        if let [left, right] = details.channels.as_slice() {
+            let loudest = left.volume_percent.max(right.volume_percent);
+            let balance = if loudest <= 0.0 {
+                0.0
+            } else {
+                (right.volume_percent - left.volume_percent) / loudest
+            };
+            channels = channels.push(
+                row![
+                    text("L").size(12).color(colors::text_secondary()),
+                    slider(-1.0..=1.0, balance, move |balance| Message::BalanceChanged(is_output, balance))
+                        .on_release(Message::ChannelVolumesReleased(is_output))
+                        .step(0.01),
+                    text("R").size(12).color(colors::text_secondary()),
+                ]
+                .spacing(8)
+                .align_y(Alignment::Center),
+            );
+        }
+        for (index, channel) in details.channels.iter().enumerate() {
+            channels = channels.push(
+                row![
+                    text(channel.position.clone()).size(12).color(colors::text_secondary()).width(110),
+                    slider(0.0..=max, channel.volume_percent, move |volume| {
+                        Message::ChannelVolumeChanged(is_output, index, volume)
+                    })
+                    .on_release(Message::ChannelVolumesReleased(is_output))
+                    .step(1.0),
+                    text(format!("{:.0}%", channel.volume_percent)).size(12).color(colors::text_secondary()).width(40),
+                    text(db_label(channel.volume_percent)).size(12).color(colors::text_secondary()).width(70),
+                ]
+                .spacing(8)
+                .align_y(Alignment::Center),
+            );
+        }
+        channels.into()
+    }
+
+    /// Equalizer of output `sink`: switches, presets, the response curve
+    /// and a slider per band
+    fn view_equalizer(&self, sink: String) -> Element<'_, Message> {
+        let settings = self.equalizers.get(&sink).cloned().unwrap_or_default();
+        let switch = |label: &'static str, on: bool, message: Message| {
+            button(text(label).size(12))
+                .on_press(message)
+                .style(move |theme, status| {
+                    if on {
+                        styles::app_card(theme, iced::widget::button::Status::Active)
+                    } else {
+                        styles::app_card(theme, status)
+                    }
+                })
+                .padding(6)
+        };
+
+        let header = row![
+            text("Equalizer").size(13).color(colors::text_primary()).width(Length::Fill),
+            switch(
+                if settings.loudness { "Loudness: On" } else { "Loudness: Off" },
+                settings.loudness,
+                Message::EqLoudnessToggled(sink.clone(), !settings.loudness),
+            ),
+            switch(
+                if settings.enabled { "On" } else { "Off" },
+                settings.enabled,
+                Message::EqToggled(sink.clone(), !settings.enabled),
+            ),
+        ]
+        .spacing(8)
+        .align_y(Alignment::Center);
+
+        let selected = settings
+            .preset
+            .as_ref()
+            .and_then(|name| self.eq_presets.iter().find(|preset| &preset.name == name))
+            .cloned();
+        let preset_sink = sink.clone();
+        let presets = pick_list(self.eq_presets.clone(), selected, move |preset| {
+            Message::EqPresetSelected(preset_sink.clone(), preset)
+        })
+        .placeholder("Custom")
+        .text_size(12)
+        .width(Length::Fill);
+
+        let curve = canvas(EqCurve {
+            gains: (0..equalizer::BANDS.len()).map(|band| settings.gain(band)).collect(),
+        })
+        .width(Length::Fill)
+        .height(60);
+
+        let bands = row(equalizer::BANDS.iter().enumerate().map(|(band, frequency)| -> Element<Message> {
+            let band_sink = sink.clone();
+            let label = if *frequency >= 1000.0 {
+                format!("{}k", frequency / 1000.0)
+            } else {
+                format!("{}", frequency)
+            };
+            column![
+                vertical_slider(
+                    -equalizer::MAX_GAIN..=equalizer::MAX_GAIN,
+                    settings.gain(band),
+                    move |gain| Message::EqGainChanged(band_sink.clone(), band, gain),
+                )
+                .on_release(Message::EqGainReleased(sink.clone()))
+                .step(0.5)
+                .height(110),
+                text(label).size(10).color(colors::text_secondary()),
+            ]
+            .spacing(4)
+            .width(Length::Fill)
+            .align_x(Alignment::Center)
+            .into()
+        }))
+        .width(Length::Fill);
+
+        let save = row![
+            text_input("Preset name", &self.eq_preset_name)
+                .on_input(Message::EqPresetNameChanged)
+                .on_submit(Message::EqSavePreset(sink.clone()))
+                .size(12)
+                .padding(6)
+                .style(|theme, status| styles::search_input(theme, status))
+                .width(Length::Fill),
+            button(text("Save Preset").size(12))
+                .on_press(Message::EqSavePreset(sink))
+                .style(|theme, status| styles::app_card(theme, status))
+                .padding(6),
+        ]
+        .spacing(8)
+        .align_y(Alignment::Center);
+
+        column![header, presets, curve, bands, save].spacing(8).into()
+    }
+
+    /// Simultaneous output: the outputs to play on at once and a switch
+    fn view_combine(&self) -> Element<'_, Message> {
+        let choice = |label: String, on: bool, message: Message| {
+            button(text(label).size(12))
+                .on_press(message)
+                .style(move |theme, status| {
+                    if on {
+                        styles::app_card(theme, iced::widget::button::Status::Active)
+                    } else {
+                        styles::app_card(theme, status)
+                    }
+                })
+                .padding(6)
+        };
+
+        let header = row![
+            text("Simultaneous Output").size(13).color(colors::text_primary()).width(Length::Fill),
+            choice(
+                if self.combine.enabled { "On" } else { "Off" }.to_string(),
+                self.combine.enabled,
+                Message::CombineToggled(!self.combine.enabled),
+            ),
+        ]
+        .spacing(8)
+        .align_y(Alignment::Center);
+
+        let outputs = self
+            .output_devices
+            .iter()
+            .filter(|device| !combine::is_combined_sink(&device.name))
+            .map(|device| -> Element<Message> {
+                choice(
+                    device.description.clone(),
+                    self.combine.outputs.contains(&device.name),
+                    Message::CombineOutputToggled(device.name.clone()),
+                )
+                .into()
+            });
+
+        column![
+            header,
+            text("Play on every output picked here, through the Combined Output device")
+                .size(11)
+                .color(colors::text_secondary()),
+            scrollable(row(outputs).spacing(8)).height(Length::Shrink),
+        ]
+        .spacing(8)
+        .into()
+    }
+
+    /// Loopback and record buttons for input `source`, with its level while
+    /// a test runs
+    fn view_mic_test(&self, source: String) -> Element<'_, Message> {
+        let test = self.mic_test.as_ref();
+        let testing_this = test.is_some_and(|test| test.source == source);
+
+        let status = match test.map(|test| test.phase) {
+            Some(MicTestPhase::Starting) if testing_this => "Starting loopback...".to_string(),
+            Some(MicTestPhase::Looping(_)) if testing_this => "Speak now, you should hear yourself".to_string(),
+            Some(MicTestPhase::Recording) if testing_this => {
+                format!("Recording for {} seconds, speak now", mic_test::RECORD_DURATION.as_secs())
+            }
+            Some(MicTestPhase::Playing) if testing_this => "Playing the recording back".to_string(),
+            _ => "Check that this input picks you up".to_string(),
+        };
+
+        let action = |label: &'static str, message: Option<Message>| {
+            button(text(label).size(12))
+                .on_press_maybe(message)
+                .style(|theme, status| styles::app_card(theme, status))
+                .padding(6)
+        };
+        let buttons: Element<Message> = match test.map(|test| test.phase) {
+            Some(MicTestPhase::Starting) if testing_this => action("Stop", None).into(),
+            Some(MicTestPhase::Looping(_)) if testing_this => action("Stop", Some(Message::StopMicTest)).into(),
+            _ => {
+                // One test at a time
+                let idle = test.is_none();
+                row![
+                    action(
+                        "Loopback",
+                        idle.then(|| Message::StartMicTest(source.clone(), mic_test::TestMode::Loopback)),
+                    ),
+                    action(
+                        "Record & Play",
+                        idle.then(|| Message::StartMicTest(source.clone(), mic_test::TestMode::Record)),
+                    ),
+                ]
+                .spacing(8)
+                .into()
+            }
+        };
+
+        let mut section = column![
+            row![
+                text("Test Microphone").size(13).color(colors::text_primary()).width(Length::Fill),
+                buttons,
+            ]
+            .spacing(8)
+            .align_y(Alignment::Center),
+            text(status).size(12).color(colors::text_secondary()),
+        ]
+        .spacing(8);
+        if testing_this {
+            section = section.push(self.view_level(&meters::MeterTarget::source(&source)));
+        }
+        section.into()
+    }
+
     fn view_app_volume_controls(&self) -> Element<'_, Message> {
+        let outputs: Vec<OutputChoice> = self
+            .output_devices
+            .iter()
+            .map(|device| OutputChoice {
+                index: device.index,
+                description: device.description.clone(),
+            })
+            .collect();
+
         container(
             column![
                 // Title - make it prominent
-                text("Application Volumes").size(20).color(colors::TEXT_PRIMARY).width(Length::Fill),
+                text("Application Volumes").size(20).color(colors::text_primary()).width(Length::Fill),
                 
                 // App list or empty state
                 if self.sink_inputs.is_empty() {
                     Element::from(
                         container(
-                            text("No applications playing audio").size(14).color(colors::TEXT_SECONDARY)
+                            text("No applications playing audio").size(14).color(colors::text_secondary())
                         )
                         .padding(20)
                         .width(Length::Fill)
@@ -1265,15 +2667,25 @@ impl AudioApp {
                             self.sink_inputs.iter().map(|input| -> Element<Message> {
                                 let mute_icon = if input.muted { "🔇" } else { "🔊" };
                                 let app_name = input.application_name.clone();
-                                let app_icon = "🎵".to_string(); // For now use emoji, can load real icons later
+                                // Album art of the player wins over the app's icon
+                                let app_icon: Element<Message> = match self.app_icons.get(&app_name).cloned().flatten() {
+                                    Some(path) => icon::icon(&path, APP_ICON_SIZE),
+                                    None => text("🎵").size(28).into(),
+                                };
                                 let input_index = input.index;
                                 let input_volume = input.volume;
+                                let output = outputs.iter().find(|choice| choice.index == input.sink_index).cloned();
+                                let meter = self.sink_input_meter(input);
                                 
                                 container(
                                     row![
                                         // App icon - larger and more prominent
                                         container(
-                                            text(app_icon.clone()).size(28)
+                                            self.view_album_art(
+                                                self.sink_input_mpris_metadata.get(&app_name),
+                                                48.0,
+                                                app_icon,
+                                            )
                                         )
                                         .width(48)
                                         .height(48)
@@ -1281,14 +2693,14 @@ impl AudioApp {
                                         .center_y(Length::Fill),
                                         // App name, MPRIS metadata, and volume info
                                         column![
-                                            text(app_name.clone()).size(16).color(colors::TEXT_PRIMARY),
+                                            text(app_name.clone()).size(16).color(colors::text_primary()),
                                             // Show MPRIS metadata if available
                                             if let Some(mpris_meta) = self.sink_input_mpris_metadata.get(&app_name) {
                                                 if !mpris_meta.title.is_empty() && mpris_meta.title != format!("Playing from {}", mpris_meta.player_name) {
                                                     column![
                                                         text(format!("{} - {}", mpris_meta.title, mpris_meta.artist))
                                                             .size(12)
-                                                            .color(colors::TEXT_SECONDARY),
+                                                            .color(colors::text_secondary()),
                                                     ]
                                                     .spacing(2)
                                                 } else {
@@ -1297,14 +2709,24 @@ impl AudioApp {
                                             } else {
                                                 column![].spacing(2)
                                             },
-                                            text(format!("{:.0}%", input_volume)).size(12).color(colors::TEXT_SECONDARY),
+                                            text(format!("{:.0}%", input_volume)).size(12).color(colors::text_secondary()),
+                                            match &meter {
+                                                Some(meter) => self.view_level(meter),
+                                                None => Element::from(space().height(0)),
+                                            },
+                                            // Output device, as in pavucontrol's playback tab
+                                            pick_list(outputs.clone(), output, move |choice: OutputChoice| {
+                                                Message::MoveSinkInput(input_index, choice.index)
+                                            })
+                                            .placeholder("Output device")
+                                            .text_size(12)
+                                            .width(220),
                                         ]
                                         .width(Length::Fill)
                                         .spacing(4),
                                         // Volume slider - make it prominent and wider
-                                        slider(0.0..=100.0, input_volume, move |v| Message::AppVolumeChanged(input_index, v))
-                                            .width(250)
-                                            .step(1.0),
+                                        container(self.volume_slider(input_volume, input_volume, move |v| Message::AppVolumeChanged(input_index, v)))
+                                            .width(250),
                                         // Mute button - larger
                                         button(text(mute_icon).size(24))
                                             .on_press(Message::AppMuteToggled(input_index))
@@ -1333,5 +2755,278 @@ impl AudioApp {
         .style(|theme| styles::glass_base(theme))
         .into()
     }
+
+    /// A volume slider over the configured range that also follows the
+    /// scroll wheel. `shown` is where the thumb is drawn, `volume` the
+    /// value scrolling starts from.
+    fn volume_slider<'a>(&self, shown: f32, volume: f32, on_change: impl Fn(f32) -> Message + Clone + 'a) -> Element<'a, Message> {
+        let config = self.volume_config;
+        let fine = self.fine_adjust;
+        let on_scroll = on_change.clone();
+        mouse_area(
+            slider(0.0..=config.max(), shown, on_change)
+                .width(Length::Fill)
+                .step(1.0),
+        )
+        .on_scroll(move |delta| on_scroll(config.scrolled(volume, delta, fine)))
+        .into()
+    }
+
+    /// The popup mixer: master and mic volume and one slider per
+    /// application
+    fn view_popup(&self) -> Element<'_, Message> {
+        let apps = self.sink_inputs.iter().map(|input| -> Element<Message> {
+            let input_index = input.index;
+            let mute_icon = if input.muted { "🔇" } else { "🔊" };
+            column![
+                text(input.application_name.clone()).size(13).color(colors::text_primary()),
+                row![
+                    self.volume_slider(input.volume, input.volume, move |v| Message::AppVolumeChanged(input_index, v)),
+                    button(text(mute_icon).size(16))
+                        .on_press(Message::AppMuteToggled(input_index))
+                        .style(|theme, status| styles::app_card(theme, status))
+                        .padding(4),
+                ]
+                .spacing(8)
+                .align_y(Alignment::Center),
+            ]
+            .spacing(4)
+            .into()
+        });
+
+        container(
+            column![
+                self.view_volume_controls(),
+                scrollable(column(apps).spacing(10)).height(Length::Fill),
+                button(text("Open Mixer").size(14))
+                    .on_press(Message::OpenMixer)
+                    .style(|theme, status| styles::app_card(theme, status))
+                    .width(Length::Fill)
+                    .padding(8),
+            ]
+            .spacing(12),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(16)
+        .style(|theme| styles::glass_base(theme))
+        .into()
+    }
+
+    fn view_bluetooth_controls(&self) -> Element<'_, Message> {
+        container(
+            column![
+                text("Bluetooth").size(20).color(colors::text_primary()).width(Length::Fill),
+                column(
+                    self.bluetooth_devices.iter().map(|device| -> Element<Message> {
+                        let pending = self.bluetooth_pending.contains(&device.path);
+                        let mut details = vec![if device.connected { "Connected".to_string() } else { "Not connected".to_string() }];
+                        if let Some(battery) = device.battery {
+                            details.push(format!("Battery {}%", battery));
+                        }
+                        if let Some(codec) = &device.codec {
+                            details.push(codec.clone());
+                        }
+                        let label = match (pending, device.connected) {
+                            (true, _) => "…",
+                            (false, true) => "Disconnect",
+                            (false, false) => "Connect",
+                        };
+
+                        row![
+                            column![
+                                text(device.name.clone()).size(16).color(colors::text_primary()),
+                                text(details.join(" · ")).size(12).color(if device.connected {
+                                    colors::accent_primary()
+                                } else {
+                                    colors::text_secondary()
+                                }),
+                            ]
+                            .width(Length::Fill)
+                            .spacing(4),
+                            button(text(label).size(14))
+                                .on_press_maybe((!pending).then(|| Message::BluetoothConnect(device.path.clone(), !device.connected)))
+                                .style(|theme, status| styles::app_card(theme, status))
+                                .padding(10),
+                        ]
+                        .spacing(20)
+                        .align_y(Alignment::Center)
+                        .into()
+                    }).collect::<Vec<Element<Message>>>()
+                )
+                .spacing(10),
+            ]
+            .spacing(15)
+        )
+        .width(Length::Fill)
+        .padding(20)
+        .style(|theme| styles::glass_base(theme))
+        .into()
+    }
+
+    /// Level meter bar, accent-filled up to the current peak
+    fn view_level(&self, target: &meters::MeterTarget) -> Element<'_, Message> {
+        let level = self.levels.get(target).copied().unwrap_or(0.0);
+        // Portions are integers; a thousandth is finer than a pixel here
+        let filled = (level * 1000.0).round() as u16;
+        let bar = |portion: u16, color: fn() -> Color| {
+            container(space())
+                .width(Length::FillPortion(portion))
+                .height(4)
+                .style(move |_theme| container::Style {
+                    background: Some(iced::Background::Color(color())),
+                    border: iced::Border {
+                        radius: 2.0.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+        };
+
+        let mut meter = row![].width(Length::Fill).height(4);
+        if filled > 0 {
+            meter = meter.push(bar(filled, colors::accent_primary));
+        }
+        if filled < 1000 {
+            meter = meter.push(bar(1000 - filled, colors::bg_input));
+        }
+        meter.into()
+    }
+
+    /// A tab per running player, when there is more than one
+    fn view_player_picker(&self, active: &str) -> Element<'_, Message> {
+        if self.players.len() < 2 {
+            return Element::from(space().height(0));
+        }
+
+        row(self.players.iter().map(|player| -> Element<Message> {
+            let selected = player.bus_name == active;
+            let label = if player.playing {
+                format!("▶ {}", player.identity)
+            } else {
+                player.identity.clone()
+            };
+            button(text(label).size(12))
+                .on_press(Message::SelectPlayer(player.bus_name.clone()))
+                .style(move |theme, status| {
+                    if selected {
+                        styles::app_card(theme, iced::widget::button::Status::Hovered)
+                    } else {
+                        styles::app_card(theme, status)
+                    }
+                })
+                .padding([6, 12])
+                .into()
+        }))
+        .spacing(8)
+        .wrap()
+        .into()
+    }
+
+    fn view_app_capture_controls(&self) -> Element<'_, Message> {
+        let recording = self.source_outputs.iter().filter(|output| output.recording && !output.muted).count();
+        let heading = if recording > 0 {
+            text(format!("🔴 Recording ({})", recording)).size(20).color(colors::control_close())
+        } else {
+            text("Recording").size(20).color(colors::text_primary())
+        };
+
+        container(
+            column![
+                heading.width(Length::Fill),
+                column(
+                    self.source_outputs.iter().map(|output| -> Element<Message> {
+                        let mute_icon = if output.muted { "🔇" } else { "🎤" };
+                        let output_index = output.index;
+                        let (state, state_color) = match (output.recording, output.muted) {
+                            (true, false) => ("● Capturing", colors::control_close()),
+                            (true, true) => ("Muted", colors::text_secondary()),
+                            (false, _) => ("Paused", colors::text_secondary()),
+                        };
+
+                        container(
+                            row![
+                                column![
+                                    text(output.application_name.clone()).size(16).color(colors::text_primary()),
+                                    row![
+                                        text(state).size(12).color(state_color),
+                                        text(format!("{:.0}%", output.volume)).size(12).color(colors::text_secondary()),
+                                    ]
+                                    .spacing(10),
+                                ]
+                                .width(Length::Fill)
+                                .spacing(4),
+                                container(self.volume_slider(output.volume, output.volume, move |v| Message::CaptureVolumeChanged(output_index, v)))
+                                    .width(250),
+                                button(text(mute_icon).size(24))
+                                    .on_press(Message::CaptureMuteToggled(output_index))
+                                    .style(|theme, status| styles::app_card(theme, status))
+                                    .padding(10),
+                            ]
+                            .spacing(20)
+                            .align_y(Alignment::Center)
+                            .padding(15)
+                        )
+                        .style(|theme| styles::glass_base(theme))
+                        .padding(8)
+                        .into()
+                    }).collect::<Vec<Element<Message>>>()
+                )
+                .spacing(10),
+            ]
+            .spacing(15)
+        )
+        .width(Length::Fill)
+        .padding(20)
+        .style(|theme| styles::glass_base(theme))
+        .into()
+    }
 }
 
+/// Volume in dB for the labels next to the percentages
+fn db_label(percent: f32) -> String {
+    match pulseaudio::volume_db(percent) {
+        Some(db) => format!("{:+.1} dB", db),
+        None => "-∞ dB".to_string(),
+    }
+}
+
+/// Unload the loopback module of a microphone test
+fn stop_loopback(module: u32) -> Task<Message> {
+    Task::future(async move {
+        if let Err(e) = mic_test::stop_loopback(module).await {
+            warn!("Failed to stop the microphone loopback: {}", e);
+        }
+    })
+    .discard()
+}
+
+/// Remember `port` as the one picked on the device `device_name`
+fn remember_port(device_name: Option<String>, port: String) -> Task<Message> {
+    let Some(device_name) = device_name else {
+        return Task::none();
+    };
+    Task::future(async move {
+        if let Err(e) = stream_memory::remember_port(device_name, port).await {
+            warn!("Failed to remember the port: {}", e);
+        }
+    })
+    .discard()
+}
+
+/// Shift state for fine scrolling, and +/- to step the output volume
+fn volume_key(event: Event, status: event::Status, _window: window::Id) -> Option<Message> {
+    let Event::Keyboard(event) = event else {
+        return None;
+    };
+    match event {
+        keyboard::Event::ModifiersChanged(modifiers) => Some(Message::ModifiersChanged(modifiers)),
+        _ if status == event::Status::Captured => None,
+        keyboard::Event::KeyPressed { key: Key::Character(c), .. } => match c.as_str() {
+            "+" | "=" => Some(Message::StepVolume(1.0)),
+            "-" => Some(Message::StepVolume(-1.0)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,145 @@
+// Global media keys
+//
+// Grabs the XF86Audio* keys on the root window so they work whatever
+// window has focus, like xfce4-volumed and xfce4-pulseaudio-plugin do.
+// Keys another client already grabbed are left to it. Keycodes are looked
+// up from the keysyms once at startup.
+use anyhow::{Context as _, Result};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use iced::futures::channel::mpsc as iced_mpsc;
+use iced::futures::SinkExt;
+use iced::Subscription;
+use tokio::sync::mpsc as tokio_mpsc;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode, Keycode, Keysym, ModMask, Window};
+use x11rb::protocol::Event;
+
+/// Wait before connecting again after the X connection broke
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// A media key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKey {
+    RaiseVolume,
+    LowerVolume,
+    Mute,
+    MicMute,
+    PlayPause,
+    Stop,
+    Previous,
+    Next,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub key: MediaKey,
+    /// Shift was held, for fine volume steps
+    pub shift: bool,
+}
+
+/// Keysyms from X11/XF86keysym.h
+const KEYSYMS: &[(Keysym, MediaKey)] = &[
+    (0x1008_ff13, MediaKey::RaiseVolume),
+    (0x1008_ff11, MediaKey::LowerVolume),
+    (0x1008_ff12, MediaKey::Mute),
+    (0x1008_ffb2, MediaKey::MicMute),
+    (0x1008_ff14, MediaKey::PlayPause), // XF86AudioPlay
+    (0x1008_ff31, MediaKey::PlayPause), // XF86AudioPause
+    (0x1008_ff15, MediaKey::Stop),
+    (0x1008_ff16, MediaKey::Previous),
+    (0x1008_ff17, MediaKey::Next),
+];
+
+/// The media key bound to `keysym`, if any
+pub fn media_key(keysym: Keysym) -> Option<MediaKey> {
+    KEYSYMS.iter().find(|(sym, _)| *sym == keysym).map(|(_, key)| *key)
+}
+
+/// Presses of the media keys
+pub fn presses() -> Subscription<KeyPress> {
+    Subscription::run(follow_presses)
+}
+
+fn follow_presses() -> impl iced::futures::Stream<Item = KeyPress> {
+    iced::stream::channel(16, async |mut output: iced_mpsc::Sender<KeyPress>| loop {
+        let (sender, mut receiver) = tokio_mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            if let Err(e) = grab_keys(&sender) {
+                warn!("Media keys stopped: {:#}", e);
+            }
+        });
+
+        while let Some(press) = receiver.recv().await {
+            if output.send(press).await.is_err() {
+                return;
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    })
+}
+
+/// Grab the media keys and report their presses until the X connection
+/// fails or nobody listens anymore
+fn grab_keys(sender: &tokio_mpsc::UnboundedSender<KeyPress>) -> Result<()> {
+    let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X server")?;
+    let root = conn.setup().roots[screen_num].root;
+
+    let bindings = keycodes(&conn)?;
+    let mut grabbed = Vec::new();
+    for &(keycode, key) in &bindings {
+        if grab(&conn, root, keycode) {
+            grabbed.push((keycode, key));
+        } else {
+            warn!("{:?} key is grabbed by another client, leaving it alone", key);
+        }
+    }
+    info!("Grabbed {} media keys", grabbed.len());
+
+    loop {
+        let event = conn.wait_for_event().context("Lost the X connection")?;
+        if sender.is_closed() {
+            return Ok(());
+        }
+        let Event::KeyPress(event) = event else {
+            continue;
+        };
+        let Some(&(_, key)) = grabbed.iter().find(|(keycode, _)| *keycode == event.detail) else {
+            continue;
+        };
+        debug!("Media key {:?} pressed", key);
+        let press = KeyPress {
+            key,
+            shift: event.state.contains(ModMask::SHIFT),
+        };
+        if sender.send(press).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Keycodes that produce a media keysym on this keyboard
+fn keycodes(conn: &impl Connection) -> Result<Vec<(Keycode, MediaKey)>> {
+    let setup = conn.setup();
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let mapping = conn
+        .get_keyboard_mapping(min, max - min + 1)?
+        .reply()
+        .context("Failed to read the keyboard mapping")?;
+
+    let per_keycode = usize::from(mapping.keysyms_per_keycode.max(1));
+    Ok(mapping
+        .keysyms
+        .chunks(per_keycode)
+        .zip(min..=max)
+        .filter_map(|(keysyms, keycode)| keysyms.iter().find_map(|&sym| media_key(sym)).map(|key| (keycode, key)))
+        .collect())
+}
+
+/// Grab `keycode` under any modifiers; false if another client holds it
+fn grab(conn: &impl Connection, root: Window, keycode: Keycode) -> bool {
+    conn.grab_key(false, root, ModMask::ANY, keycode, GrabMode::ASYNC, GrabMode::ASYNC)
+        .is_ok_and(|cookie| cookie.check().is_ok())
+}
+
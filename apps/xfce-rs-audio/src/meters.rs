@@ -0,0 +1,220 @@
+// Live peak level meters
+//
+// Like pavucontrol, every meter is a tiny PEAK_DETECT record stream: the
+// server sends one float per 1/25 s holding the loudest sample since the
+// last one. Devices are metered on their source (the monitor source for
+// outputs); an application is metered with a monitor stream on its sink
+// input. These streams use the "peaks" resample method, which
+// source_outputs hides from the recording list.
+use anyhow::{bail, Context as _, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+use iced::futures::channel::mpsc as iced_mpsc;
+use iced::futures::SinkExt;
+use iced::Subscription;
+use libpulse_binding::context::{Context, FlagSet as ContextFlagSet, State as ContextState};
+use libpulse_binding::def::BufferAttr;
+use libpulse_binding::mainloop::standard::{IterateResult, Mainloop};
+use libpulse_binding::sample::{Format, Spec};
+use libpulse_binding::stream::{FlagSet as StreamFlagSet, PeekResult, Stream};
+use tokio::sync::mpsc as tokio_mpsc;
+
+/// Peak updates per second asked of the server
+const PEAK_RATE: u32 = 25;
+/// How often collected levels are handed to the app
+const REPORT_INTERVAL: Duration = Duration::from_millis(40);
+/// Wait before reconnecting after the server goes away
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// Share of the shown level kept per update while the sound gets quieter
+const RELEASE: f32 = 0.8;
+
+/// Something to meter
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MeterTarget {
+    /// A source, or the monitor source of a sink, by name
+    Device(String),
+    /// An application stream, metered on the monitor source of its sink
+    SinkInput { index: u32, monitor: String },
+}
+
+impl MeterTarget {
+    /// The meter of output device `sink_name`
+    pub fn sink(sink_name: &str) -> Self {
+        Self::Device(format!("{}.monitor", sink_name))
+    }
+
+    pub fn source(source_name: &str) -> Self {
+        Self::Device(source_name.to_string())
+    }
+}
+
+/// Latest peak of each target, 0.0 to 1.0
+pub type Levels = Vec<(MeterTarget, f32)>;
+
+/// `shown` moved towards `peak`: rising at once, falling slowly so the bar
+/// doesn't flicker
+pub fn smooth(shown: f32, peak: f32) -> f32 {
+    let peak = peak.clamp(0.0, 1.0);
+    if peak >= shown {
+        peak
+    } else {
+        (shown * RELEASE + peak * (1.0 - RELEASE)).max(peak)
+    }
+}
+
+/// Levels of `targets`, restarted whenever the targets change
+pub fn levels(mut targets: Vec<MeterTarget>) -> Subscription<Levels> {
+    targets.sort();
+    targets.dedup();
+    Subscription::run_with(targets, follow_levels)
+}
+
+// Subscription::run_with hands the data over by reference
+#[allow(clippy::ptr_arg)]
+fn follow_levels(targets: &Vec<MeterTarget>) -> impl iced::futures::Stream<Item = Levels> {
+    let targets = targets.clone();
+    iced::stream::channel(4, async move |mut output: iced_mpsc::Sender<Levels>| loop {
+        if targets.is_empty() {
+            return;
+        }
+
+        let (sender, mut receiver) = tokio_mpsc::unbounded_channel();
+        let thread_targets = targets.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = run_meters(&thread_targets, &sender) {
+                warn!("Peak meters stopped: {:#}", e);
+            }
+        });
+
+        while let Some(levels) = receiver.recv().await {
+            if output.send(levels).await.is_err() {
+                return;
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    })
+}
+
+/// Open a peak stream per target and report their levels until the
+/// connection fails or nobody listens anymore
+fn run_meters(targets: &[MeterTarget], sender: &tokio_mpsc::UnboundedSender<Levels>) -> Result<()> {
+    let mut mainloop = Mainloop::new().context("Failed to create PulseAudio mainloop")?;
+    let mut context = Context::new(&mainloop, "xfce-rs-audio-meters").context("Failed to create PulseAudio context")?;
+    context.connect(None, ContextFlagSet::NOFLAGS, None)?;
+
+    loop {
+        iterate(&mut mainloop)?;
+        match context.get_state() {
+            ContextState::Ready => break,
+            ContextState::Failed | ContextState::Terminated => bail!("Failed to connect to PulseAudio"),
+            _ => {}
+        }
+    }
+
+    let peaks: Rc<RefCell<HashMap<MeterTarget, f32>>> = Rc::default();
+    let mut streams = Vec::new();
+    for target in targets {
+        match open_peak_stream(&mut context, target, peaks.clone()) {
+            Ok(stream) => streams.push(stream),
+            Err(e) => debug!("No peak meter for {:?}: {:#}", target, e),
+        }
+    }
+    debug!("Metering {} of {} targets", streams.len(), targets.len());
+
+    let mut reported_at = Instant::now();
+    let result = loop {
+        if let Err(e) = iterate(&mut mainloop) {
+            break Err(e);
+        }
+        if sender.is_closed() {
+            break Ok(());
+        }
+        if !matches!(context.get_state(), ContextState::Ready) {
+            break Err(anyhow::anyhow!("Lost the PulseAudio connection"));
+        }
+
+        if reported_at.elapsed() >= REPORT_INTERVAL {
+            reported_at = Instant::now();
+            let levels: Levels = peaks.borrow_mut().drain().collect();
+            if !levels.is_empty() && sender.send(levels).is_err() {
+                break Ok(());
+            }
+        }
+    };
+
+    for stream in &streams {
+        let mut stream = stream.borrow_mut();
+        // The read callback holds the stream; dropping it breaks the cycle
+        stream.set_read_callback(None);
+        let _ = stream.disconnect();
+    }
+    result
+}
+
+fn open_peak_stream(
+    context: &mut Context,
+    target: &MeterTarget,
+    peaks: Rc<RefCell<HashMap<MeterTarget, f32>>>,
+) -> Result<Rc<RefCell<Stream>>> {
+    let spec = Spec {
+        format: Format::FLOAT32NE,
+        channels: 1,
+        rate: PEAK_RATE,
+    };
+    let mut stream = Stream::new(context, "Peak detect", &spec, None).context("Failed to create peak stream")?;
+
+    let source = match target {
+        MeterTarget::Device(source) => source,
+        MeterTarget::SinkInput { index, monitor } => {
+            stream.set_monitor_stream(*index)?;
+            monitor
+        }
+    };
+
+    // One float per fragment, so every peak arrives as soon as it's measured
+    let attr = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: 0,
+        prebuf: 0,
+        minreq: 0,
+        fragsize: std::mem::size_of::<f32>() as u32,
+    };
+    let flags = StreamFlagSet::PEAK_DETECT
+        | StreamFlagSet::ADJUST_LATENCY
+        | StreamFlagSet::DONT_MOVE
+        | StreamFlagSet::DONT_INHIBIT_AUTO_SUSPEND;
+    stream.connect_record(Some(source), Some(&attr), flags)?;
+
+    let stream = Rc::new(RefCell::new(stream));
+    let reader = stream.clone();
+    let target = target.clone();
+    stream.borrow_mut().set_read_callback(Some(Box::new(move |_length| {
+        let mut stream = reader.borrow_mut();
+        let peak = match stream.peek() {
+            Ok(PeekResult::Data(data)) => data
+                .chunks_exact(std::mem::size_of::<f32>())
+                .map(|sample| f32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]]))
+                .fold(0.0_f32, f32::max),
+            Ok(PeekResult::Hole(_)) => 0.0,
+            Ok(PeekResult::Empty) | Err(_) => return,
+        };
+        let _ = stream.discard();
+
+        let mut peaks = peaks.borrow_mut();
+        let level = peaks.entry(target.clone()).or_insert(0.0);
+        *level = level.max(peak);
+    })));
+    Ok(stream)
+}
+
+fn iterate(mainloop: &mut Mainloop) -> Result<()> {
+    match mainloop.iterate(true) {
+        IterateResult::Success(_) => Ok(()),
+        IterateResult::Quit(_) => bail!("PulseAudio mainloop quit"),
+        IterateResult::Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,159 @@
+// Microphone test
+//
+// Two ways to hear what an input device picks up, from its details panel:
+// a loopback that plays the source live on the default output for a few
+// seconds (module-loopback), or a short recording played back right after
+// (parecord and paplay, or pw-record and pw-play where only PipeWire is
+// installed). The panel meters the source meanwhile.
+use anyhow::{bail, Context as _, Result};
+use std::cell::Cell;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::rc::Rc;
+use std::time::Duration;
+use tracing::{debug, info};
+use pulsectl::controllers::SinkController;
+
+/// How long a loopback runs unless stopped earlier
+pub const LOOPBACK_DURATION: Duration = Duration::from_secs(10);
+/// Length of the test recording
+pub const RECORD_DURATION: Duration = Duration::from_secs(3);
+/// Kept low so speech lines up with what the user sees on the meter
+const LOOPBACK_LATENCY_MS: u32 = 60;
+/// libpulse's PA_INVALID_INDEX
+const INVALID_INDEX: u32 = u32::MAX;
+
+/// How to test the microphone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestMode {
+    Loopback,
+    Record,
+}
+
+/// Play `source_name` on the default output until [`stop_loopback`].
+/// Returns the module index.
+pub async fn start_loopback(source_name: String) -> Result<u32> {
+    tokio::task::spawn_blocking(move || -> Result<u32> {
+        let mut controller = SinkController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SinkController: {}", e))?;
+        let argument = format!(
+            "source={} latency_msec={} source_dont_move=true",
+            source_name, LOOPBACK_LATENCY_MS
+        );
+        let index = Rc::new(Cell::new(INVALID_INDEX));
+        let loaded = index.clone();
+        let op = controller
+            .handler
+            .introspect
+            .load_module("module-loopback", &argument, move |module| loaded.set(module));
+        controller
+            .handler
+            .wait_for_operation(op)
+            .map_err(|e| anyhow::anyhow!("Failed to load module-loopback: {}", e))?;
+
+        let index = index.get();
+        if index == INVALID_INDEX {
+            bail!("The sound server refused the loopback");
+        }
+        info!("Looping {} back as module {}", source_name, index);
+        Ok(index)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
+
+pub async fn stop_loopback(module: u32) -> Result<()> {
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let mut controller = SinkController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SinkController: {}", e))?;
+        let op = controller.handler.introspect.unload_module(module, |_| {});
+        controller
+            .handler
+            .wait_for_operation(op)
+            .map_err(|e| anyhow::anyhow!("Failed to unload module {}: {}", module, e))?;
+        debug!("Stopped loopback module {}", module);
+        Ok(())
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
+
+/// Record [`RECORD_DURATION`] of `source_name`. Returns the WAV file.
+pub async fn record(source_name: String) -> Result<PathBuf> {
+    let path = clip_path()?;
+    let mut child = spawn_recorder(&source_name, &path)?;
+    tokio::time::sleep(RECORD_DURATION).await;
+
+    // Interrupted, the recorders finish the file properly
+    // SAFETY: kill has no memory effects; the child is ours and not reaped yet
+    unsafe {
+        libc::kill(child.id() as i32, libc::SIGINT);
+    }
+    let status = tokio::task::spawn_blocking(move || child.wait())
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+    debug!("Recorder exited with {}", status);
+    if !std::fs::metadata(&path).is_ok_and(|metadata| metadata.len() > 0) {
+        bail!("Nothing was recorded");
+    }
+    Ok(path)
+}
+
+/// Play the clip made by [`record`] and remove it
+pub async fn play(path: PathBuf) -> Result<()> {
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let result = play_blocking(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
+
+fn play_blocking(path: &Path) -> Result<()> {
+    let paplay = Command::new("paplay").arg(path).stdin(Stdio::null()).stdout(Stdio::null()).status();
+    let status = match paplay {
+        Err(e) if e.kind() == ErrorKind::NotFound => Command::new("pw-play")
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()
+            .context("Neither paplay nor pw-play could be started")?,
+        other => other.context("Failed to start paplay")?,
+    };
+    if !status.success() {
+        bail!("Playback failed ({})", status);
+    }
+    Ok(())
+}
+
+fn spawn_recorder(source_name: &str, path: &Path) -> Result<Child> {
+    let parecord = Command::new("parecord")
+        .arg(format!("--device={}", source_name))
+        .arg("--file-format=wav")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+    match parecord {
+        Err(e) if e.kind() == ErrorKind::NotFound => Command::new("pw-record")
+            .arg(format!("--target={}", source_name))
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .context("Neither parecord nor pw-record could be started"),
+        other => other.context("Failed to start parecord"),
+    }
+}
+
+/// Where the test clip goes, in the runtime directory like the equalizer
+/// configs
+fn clip_path() -> Result<PathBuf> {
+    let dir = dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("xfce-rs-audio");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir.join("mic-test.wav"))
+}
@@ -1,41 +1,91 @@
-// Notification system for audio events
-// Currently unused but available for future use
-#[allow(dead_code)]
-pub mod notifications {
-    use anyhow::Result;
-    use tracing::info;
-    use notify_rust::Notification;
+// Desktop notifications for audio events
+//
+// Sent to whatever serves org.freedesktop.Notifications, the session's own
+// daemon included. Volume popups carry the level as the "value" hint, which
+// notification daemons draw as a bar, and all of them share one
+// synchronous tag so a burst of changes updates a single popup instead of
+// stacking.
+use anyhow::Result;
+use notify_rust::{Hint, Notification, Timeout};
+use tracing::debug;
 
-    pub async fn show_notification(title: &str, message: &str) -> Result<()> {
-        info!("Showing notification: {} - {}", title, message);
-        
-        Notification::new()
-            .summary(title)
-            .body(message)
-            .timeout(notify_rust::Timeout::Milliseconds(3000))
+const APP_NAME: &str = "xfce-rs-audio";
+/// Tag shared by all popups of the audio tools
+const SYNCHRONOUS_TAG: &str = "xfce-rs-audio";
+const TIMEOUT_MS: u32 = 2000;
+
+/// Show a popup, with a level bar if `value` is given
+pub async fn show(summary: String, body: String, icon: &'static str, value: Option<f32>) -> Result<()> {
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        debug!("Showing notification: {} - {}", summary, body);
+        let mut notification = Notification::new();
+        notification
+            .appname(APP_NAME)
+            .summary(&summary)
+            .body(&body)
+            .icon(icon)
+            .hint(Hint::Custom("x-canonical-private-synchronous".to_string(), SYNCHRONOUS_TAG.to_string()))
+            .hint(Hint::Transient(true))
+            .timeout(Timeout::Milliseconds(TIMEOUT_MS));
+        if let Some(value) = value {
+            notification.hint(Hint::CustomInt("value".to_string(), value.round().clamp(0.0, 100.0) as i32));
+        }
+        notification
             .show()
             .map_err(|e| anyhow::anyhow!("Failed to show notification: {}", e))?;
-        
         Ok(())
-    }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
 
-    pub async fn show_volume_notification(volume: f32, muted: bool) -> Result<()> {
-        if muted {
-            show_notification("Audio", "Muted").await
-        } else {
-            show_notification("Volume", &format!("{}%", volume as u32)).await
-        }
-    }
+/// The volume of the default output or input changed
+pub async fn show_volume(volume: f32, muted: bool, is_input: bool) -> Result<()> {
+    let summary = if is_input { "Microphone" } else { "Volume" };
+    let body = if muted { "Muted".to_string() } else { format!("{:.0}%", volume) };
+    let value = if muted { 0.0 } else { volume };
+    show(summary.to_string(), body, volume_icon(volume, muted, is_input), Some(value)).await
+}
+
+/// A device was plugged in
+pub async fn show_device_added(description: String, is_input: bool) -> Result<()> {
+    let summary = if is_input { "Input Device Connected" } else { "Output Device Connected" };
+    show(summary.to_string(), description, device_icon(is_input), None).await
+}
 
-    pub async fn show_device_notification(device_name: &str, is_input: bool) -> Result<()> {
-        let device_type = if is_input { "Input" } else { "Output" };
-        show_notification(
-            &format!("Audio {}", device_type),
-            &format!("Switched to {}", device_name),
-        ).await
+/// Another device became the default, `volume` being its level
+pub async fn show_default_changed(description: String, is_input: bool, volume: Option<f32>) -> Result<()> {
+    let summary = if is_input { "Audio Input" } else { "Audio Output" };
+    show(summary.to_string(), format!("Switched to {}", description), device_icon(is_input), volume).await
+}
+
+fn device_icon(is_input: bool) -> &'static str {
+    if is_input {
+        "audio-input-microphone"
+    } else {
+        "audio-speakers"
     }
+}
 
-    pub async fn show_track_notification(title: &str, artist: &str) -> Result<()> {
-        show_notification(title, artist).await
+/// Icon theme name for a level
+fn volume_icon(volume: f32, muted: bool, is_input: bool) -> &'static str {
+    let level = if muted || volume <= 0.0 {
+        0
+    } else if volume < 34.0 {
+        1
+    } else if volume < 67.0 {
+        2
+    } else {
+        3
+    };
+    match (is_input, level) {
+        (false, 0) => "audio-volume-muted",
+        (false, 1) => "audio-volume-low",
+        (false, 2) => "audio-volume-medium",
+        (false, _) => "audio-volume-high",
+        (true, 0) => "microphone-sensitivity-muted",
+        (true, 1) => "microphone-sensitivity-low",
+        (true, 2) => "microphone-sensitivity-medium",
+        (true, _) => "microphone-sensitivity-high",
     }
 }
@@ -0,0 +1,468 @@
+// Media key daemon with an on-screen volume display
+//
+// `xfce-rs-audio --daemon` runs without a window of its own. The volume and
+// mute keys change the default devices and flash a small overlay near the
+// bottom of the screen; the playback keys drive the active MPRIS player.
+// The daemon also serves event sounds to the rest of the session, starts
+// the equalizers and the combined output left on in the last one,
+// restores the saved volumes of applications and ports of devices as they
+// appear, and pops up a notification when a device is plugged in, the
+// default device changes or another program changes the volume.
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use iced::widget::{column, container, row, space, text};
+use iced::{window, Alignment, Color, Element, Length, Point, Size, Subscription, Task, Theme};
+use tracing::{debug, info, warn};
+use xfce_rs_ipc::sound::EventSoundService;
+use xfce_rs_ui::{colors, fonts, scale, styles, theme};
+
+use crate::media_keys::{self, KeyPress, MediaKey};
+use crate::event_sounds::EventSoundPlayer;
+use crate::pulseaudio::PulseEvent;
+use crate::stream_memory::Restorer;
+use xfce_rs_audio::backend::backend;
+use crate::{combine, equalizer, mpris, pulseaudio, settings};
+use xfce_rs_audio::{notifications, AudioDevice};
+
+const OSD_SIZE: Size = Size::new(300.0, 64.0);
+/// How long the overlay stays after the last key press
+const OSD_TIMEOUT: Duration = Duration::from_millis(1500);
+/// Played on every volume step, like GNOME does
+const VOLUME_CHANGE_SOUND: &str = "audio-volume-change";
+/// Gap between the overlay and the bottom edge of the screen
+const BOTTOM_MARGIN: f32 = 96.0;
+
+pub fn run() -> iced::Result {
+    info!("Media key daemon starting");
+
+    fonts::load();
+    iced::daemon(VolumeOsd::new, VolumeOsd::update, VolumeOsd::view)
+        .settings(fonts::settings())
+        .title(VolumeOsd::title)
+        .theme(VolumeOsd::theme)
+        .style(VolumeOsd::style)
+        .scale_factor(VolumeOsd::scale_factor)
+        .subscription(VolumeOsd::subscription)
+        .run()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Output,
+    Input,
+}
+
+/// Devices and default levels, compared between server events to tell
+/// what changed
+#[derive(Debug, Clone)]
+struct SoundState {
+    outputs: Vec<AudioDevice>,
+    inputs: Vec<AudioDevice>,
+    output: (f32, bool),
+    input: (f32, bool),
+}
+
+/// What the overlay shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Level {
+    channel: Channel,
+    volume: f32,
+    muted: bool,
+}
+
+struct VolumeOsd {
+    volume_config: settings::VolumeConfig,
+    level: Option<Level>,
+    window: Option<window::Id>,
+    // Bumped on every display, so only the latest one's timeout hides it
+    shown: u64,
+    // Serves org.xfce_rs.EventSounds while the daemon runs
+    sounds: Option<EventSoundService>,
+    // Shared with the restore tasks, which run one at a time
+    restorer: Arc<tokio::sync::Mutex<Restorer>>,
+    notification_config: settings::NotificationConfig,
+    // Last seen, unknown until the sound server first answers
+    sound_state: Option<SoundState>,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    VolumeConfigLoaded(settings::VolumeConfig),
+    NotificationConfigLoaded(settings::NotificationConfig),
+    SoundServiceStarted(EventSoundService),
+    Key(KeyPress),
+    LevelChanged(Result<Level, String>),
+    PlayerControlled(Result<(), String>),
+    Hide(u64),
+    WindowClosed(window::Id),
+    Pulse(PulseEvent),
+    StateRead(Result<SoundState, String>),
+    ThemeChanged,
+}
+
+impl VolumeOsd {
+    fn new() -> (Self, Task<Message>) {
+        let restorer = Arc::new(tokio::sync::Mutex::new(Restorer::default()));
+        let startup_restorer = restorer.clone();
+        (
+            Self {
+                volume_config: settings::VolumeConfig::default(),
+                level: None,
+                window: None,
+                shown: 0,
+                sounds: None,
+                restorer,
+                notification_config: settings::NotificationConfig::default(),
+                sound_state: None,
+            },
+            Task::batch([
+                Task::perform(settings::VolumeConfig::load(), Message::VolumeConfigLoaded),
+                Task::perform(settings::NotificationConfig::load(), Message::NotificationConfigLoaded),
+                Task::perform(
+                    async {
+                        let service = EventSoundService::new(EventSoundPlayer::load().await);
+                        if let Err(e) = service.start().await {
+                            warn!("Failed to start the event sound service: {}", e);
+                        }
+                        service
+                    },
+                    Message::SoundServiceStarted,
+                ),
+                Task::perform(
+                    async move {
+                        if let Err(e) = backend().init().await {
+                            warn!("Failed to connect to the sound server: {}", e);
+                        }
+                        if let Ok((outputs, _)) = backend().devices().await {
+                            let outputs: Vec<_> = outputs
+                                .into_iter()
+                                .filter(|device| {
+                                    !equalizer::is_equalizer_sink(&device.name) && !combine::is_combined_sink(&device.name)
+                                })
+                                .map(|device| (device.name, device.description))
+                                .collect();
+                            combine::restore(outputs.iter().map(|(name, _)| name.clone()).collect()).await;
+                            equalizer::restore(outputs).await;
+                        }
+                        let mut restorer = startup_restorer.lock().await;
+                        restorer.restore_ports().await;
+                        restorer.restore_streams().await;
+                        read_state().await
+                    },
+                    Message::StateRead,
+                ),
+            ]),
+        )
+    }
+
+    fn title(&self, _window: window::Id) -> String {
+        String::from("Volume")
+    }
+
+    fn theme(&self, _window: window::Id) -> Theme {
+        theme::iced_theme()
+    }
+
+    fn scale_factor(&self, window: window::Id) -> f32 {
+        scale::factor_for(window)
+    }
+
+    fn style(&self, theme: &Theme) -> iced::theme::Style {
+        iced::theme::Style {
+            background_color: iced::Color::TRANSPARENT,
+            text_color: theme.palette().text,
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::batch([
+            media_keys::presses().map(Message::Key),
+            window::close_events().map(Message::WindowClosed),
+            pulseaudio::events().map(Message::Pulse),
+            theme::subscription().map(|_| Message::ThemeChanged),
+        ])
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::VolumeConfigLoaded(config) => {
+                self.volume_config = config;
+                Task::none()
+            }
+            Message::NotificationConfigLoaded(config) => {
+                self.notification_config = config;
+                Task::none()
+            }
+            Message::SoundServiceStarted(service) => {
+                self.sounds = Some(service);
+                Task::none()
+            }
+            Message::Key(press) => self.handle_key(press),
+            Message::LevelChanged(Ok(level)) => {
+                if level.channel == Channel::Output && !level.muted {
+                    if let Some(sounds) = &self.sounds {
+                        sounds.play(VOLUME_CHANGE_SOUND);
+                    }
+                }
+                self.level = Some(level);
+                self.shown += 1;
+                let shown = self.shown;
+                let hide = Task::perform(tokio::time::sleep(OSD_TIMEOUT), move |_| Message::Hide(shown));
+                if self.window.is_some() {
+                    return hide;
+                }
+
+                let (id, open) = window::open(window::Settings {
+                    size: OSD_SIZE,
+                    position: window::Position::SpecificWith(|size, screen| {
+                        Point::new((screen.width - size.width) / 2.0, screen.height - size.height - BOTTOM_MARGIN)
+                    }),
+                    resizable: false,
+                    transparent: true,
+                    decorations: false,
+                    level: window::Level::AlwaysOnTop,
+                    // Unmanaged, so the overlay never takes the focus
+                    platform_specific: window::settings::PlatformSpecific {
+                        override_redirect: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+                self.window = Some(id);
+                Task::batch([open.discard(), hide])
+            }
+            Message::LevelChanged(Err(e)) | Message::PlayerControlled(Err(e)) => {
+                warn!("Media key failed: {}", e);
+                Task::none()
+            }
+            Message::PlayerControlled(Ok(())) => Task::none(),
+            Message::Hide(shown) => match self.window {
+                Some(id) if shown == self.shown => {
+                    self.window = None;
+                    window::close(id)
+                }
+                _ => Task::none(),
+            },
+            Message::WindowClosed(id) => {
+                if self.window == Some(id) {
+                    self.window = None;
+                }
+                Task::none()
+            }
+            Message::Pulse(event) => {
+                let restorer = self.restorer.clone();
+                match event {
+                    PulseEvent::SinkInputChanged => {
+                        Task::future(async move { restorer.lock().await.restore_streams().await }).discard()
+                    }
+                    PulseEvent::DevicesChanged => Task::batch([
+                        Task::future(async move { restorer.lock().await.restore_ports().await }).discard(),
+                        Task::perform(read_state(), Message::StateRead),
+                    ]),
+                    PulseEvent::VolumeChanged => Task::perform(read_state(), Message::StateRead),
+                    _ => Task::none(),
+                }
+            }
+            Message::StateRead(Ok(state)) => self.notify_changes(state),
+            Message::StateRead(Err(e)) => {
+                debug!("Failed to read the sound server state: {}", e);
+                Task::none()
+            }
+            Message::ThemeChanged => Task::none(),
+        }
+    }
+
+    fn handle_key(&mut self, press: KeyPress) -> Task<Message> {
+        debug!("Handling {:?}", press);
+        let config = self.volume_config;
+        let steps = match press.key {
+            MediaKey::RaiseVolume => 1.0,
+            MediaKey::LowerVolume => -1.0,
+            MediaKey::Mute => {
+                return Task::perform(
+                    async {
+                        let (volume, muted) = backend().volume().await?;
+                        backend().set_mute(!muted).await?;
+                        Ok(Level { channel: Channel::Output, volume, muted: !muted })
+                    },
+                    |result: anyhow::Result<Level>| Message::LevelChanged(result.map_err(|e| e.to_string())),
+                );
+            }
+            MediaKey::MicMute => {
+                return Task::perform(
+                    async {
+                        let (volume, muted) = backend().mic_volume().await?;
+                        backend().set_mic_mute(!muted).await?;
+                        Ok(Level { channel: Channel::Input, volume, muted: !muted })
+                    },
+                    |result: anyhow::Result<Level>| Message::LevelChanged(result.map_err(|e| e.to_string())),
+                );
+            }
+            key => return control_player(key),
+        };
+
+        Task::perform(
+            async move {
+                let (volume, mut muted) = backend().volume().await?;
+                let volume = config.adjust(volume, steps, press.shift);
+                backend().set_volume(volume).await?;
+                // Turning it up should make it audible
+                if muted && steps > 0.0 {
+                    backend().set_mute(false).await?;
+                    muted = false;
+                }
+                Ok(Level { channel: Channel::Output, volume, muted })
+            },
+            |result: anyhow::Result<Level>| Message::LevelChanged(result.map_err(|e| e.to_string())),
+        )
+    }
+
+    /// Pop up what changed since the last state: new devices, another
+    /// default device, or a volume changed by something other than the
+    /// media keys
+    fn notify_changes(&mut self, state: SoundState) -> Task<Message> {
+        let Some(old) = self.sound_state.replace(state.clone()) else {
+            return Task::none();
+        };
+        let config = self.notification_config;
+        // The overlay is up for changes made with the keys
+        let from_keys = self.window.is_some();
+
+        let mut popups = Vec::new();
+        let channels = [
+            (false, &old.outputs, &state.outputs, old.output, state.output),
+            (true, &old.inputs, &state.inputs, old.input, state.input),
+        ];
+        for (is_input, old_devices, devices, old_level, (volume, muted)) in channels {
+            let default = devices.iter().find(|device| device.is_default);
+            let old_default = old_devices.iter().find(|device| device.is_default);
+            if let Some(default) = default.filter(|default| old_default.is_some_and(|old| old.name != default.name)) {
+                if config.devices {
+                    let level = (!muted).then_some(volume);
+                    popups.push(notify(notifications::show_default_changed(default.description.clone(), is_input, level)));
+                }
+                // The new default's level is shown with it
+                continue;
+            }
+            if config.devices {
+                for device in devices.iter().filter(|device| !old_devices.iter().any(|old| old.name == device.name)) {
+                    popups.push(notify(notifications::show_device_added(device.description.clone(), is_input)));
+                }
+            }
+            let changed = (volume - old_level.0).abs() >= 0.5 || muted != old_level.1;
+            if config.volume && changed && !from_keys {
+                popups.push(notify(notifications::show_volume(volume, muted, is_input)));
+            }
+        }
+        Task::batch(popups)
+    }
+
+    fn view(&self, _window: window::Id) -> Element<'_, Message> {
+        let Some(level) = self.level else {
+            return space().into();
+        };
+
+        let icon = match (level.channel, level.muted) {
+            (Channel::Output, true) => "🔇",
+            (Channel::Output, false) => "🔊",
+            (Channel::Input, true) => "🎤🚫",
+            (Channel::Input, false) => "🎤",
+        };
+        let label = if level.muted {
+            String::from("Muted")
+        } else {
+            format!("{:.0}%", level.volume)
+        };
+
+        // Overamplified volume fills the bar
+        let shown = if level.muted { 0.0 } else { (level.volume / 100.0).clamp(0.0, 1.0) };
+        let filled = (shown * 1000.0).round() as u16;
+        let bar = |portion: u16, color: fn() -> Color| {
+            container(space())
+                .width(Length::FillPortion(portion))
+                .height(6)
+                .style(move |_theme| container::Style {
+                    background: Some(iced::Background::Color(color())),
+                    border: iced::Border {
+                        radius: 3.0.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+        };
+        let mut meter = row![].width(Length::Fill).height(6);
+        if filled > 0 {
+            meter = meter.push(bar(filled, colors::accent_primary));
+        }
+        if filled < 1000 {
+            meter = meter.push(bar(1000 - filled, colors::bg_input));
+        }
+
+        container(
+            row![
+                text(icon).size(24),
+                column![
+                    text(label).size(13).color(colors::text_primary()),
+                    meter,
+                ]
+                .spacing(6)
+                .width(Length::Fill),
+            ]
+            .spacing(14)
+            .align_y(Alignment::Center),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(14)
+        .style(|theme| styles::glass_base(theme))
+        .into()
+    }
+}
+
+/// Devices and default levels, without our own filter and combined sinks
+async fn read_state() -> Result<SoundState, String> {
+    let read = async {
+        let (outputs, inputs) = backend().devices().await?;
+        let outputs = outputs
+            .into_iter()
+            .filter(|device| !equalizer::is_equalizer_sink(&device.name) && !combine::is_combined_sink(&device.name))
+            .collect();
+        Ok(SoundState {
+            outputs,
+            inputs,
+            output: backend().volume().await?,
+            input: backend().mic_volume().await?,
+        })
+    };
+    read.await.map_err(|e: anyhow::Error| e.to_string())
+}
+
+/// Show a popup, only logging failures
+fn notify(popup: impl Future<Output = anyhow::Result<()>> + Send + 'static) -> Task<Message> {
+    Task::future(async move {
+        if let Err(e) = popup.await {
+            warn!("Failed to show a notification: {}", e);
+        }
+    })
+    .discard()
+}
+
+/// Send a playback key to the active player
+fn control_player(key: MediaKey) -> Task<Message> {
+    Task::perform(
+        async move {
+            let Some(player) = mpris::active_player().await? else {
+                return Ok(());
+            };
+            match key {
+                MediaKey::PlayPause => mpris::play_pause(player).await,
+                MediaKey::Stop => mpris::stop(player).await,
+                MediaKey::Previous => mpris::previous(player).await,
+                MediaKey::Next => mpris::next(player).await,
+                _ => Ok(()),
+            }
+        },
+        |result: anyhow::Result<()>| Message::PlayerControlled(result.map_err(|e| e.to_string())),
+    )
+}
@@ -0,0 +1,241 @@
+// PipeWire graph on top of the PulseAudio backend
+//
+// Reads the PipeWire graph over PipeWire's own protocol: one round trip
+// through the registry lists every node, port and link. That's where
+// node latencies and the links between nodes are, which the PulseAudio
+// protocol has no notion of. Everything else is PulseBackend's: volumes,
+// default devices and stream routing go through pipewire-pulse, so the
+// session manager (WirePlumber) keeps storing and applying them as it
+// does for every other client.
+use anyhow::{anyhow, Result};
+use futures_util::future::{BoxFuture, FutureExt};
+use pipewire as pw;
+use pw::types::ObjectType;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use tracing::debug;
+
+use crate::backend::{AudioBackend, Link, Node, PulseBackend};
+use crate::sink_inputs::SinkInput;
+use crate::AudioDevice;
+
+/// Whether a PipeWire server answers on this session
+pub fn is_running() -> bool {
+    pw::init();
+    let Ok(mainloop) = pw::main_loop::MainLoop::new(None) else {
+        return false;
+    };
+    let Ok(context) = pw::context::Context::new(&mainloop) else {
+        return false;
+    };
+    context.connect(None).is_ok()
+}
+
+/// [`PulseBackend`] that also shows the PipeWire graph
+#[derive(Debug, Default)]
+pub struct PipeWireGraphBackend {
+    pulse: PulseBackend,
+}
+
+/// Everything the registry announced
+#[derive(Debug, Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    links: Vec<Link>,
+}
+
+/// Raw link, with port ids rather than names
+struct LinkGlobal {
+    id: u32,
+    output_node: u32,
+    output_port: u32,
+    input_node: u32,
+    input_port: u32,
+}
+
+#[derive(Default)]
+struct Globals {
+    nodes: Vec<Node>,
+    ports: HashMap<u32, String>,
+    links: Vec<LinkGlobal>,
+}
+
+impl PipeWireGraphBackend {
+    async fn graph(&self) -> Result<Graph> {
+        tokio::task::spawn_blocking(read_graph)
+            .await
+            .map_err(|e| anyhow!("Task error: {}", e))?
+    }
+}
+
+/// One registry round trip
+fn read_graph() -> Result<Graph> {
+    pw::init();
+    let mainloop = pw::main_loop::MainLoop::new(None).map_err(|e| anyhow!("Failed to create the PipeWire loop: {}", e))?;
+    let context = pw::context::Context::new(&mainloop).map_err(|e| anyhow!("Failed to create the PipeWire context: {}", e))?;
+    let core = context.connect(None).map_err(|e| anyhow!("Failed to connect to PipeWire: {}", e))?;
+    let registry = core.get_registry().map_err(|e| anyhow!("Failed to get the PipeWire registry: {}", e))?;
+
+    let globals = Rc::new(RefCell::new(Globals::default()));
+    let collected = globals.clone();
+    let _registry_listener = registry
+        .add_listener_local()
+        .global(move |global| {
+            let Some(props) = global.props else {
+                return;
+            };
+            let number = |key: &str| props.get(key).and_then(|value| value.parse::<u32>().ok());
+            let mut globals = collected.borrow_mut();
+            match global.type_ {
+                ObjectType::Node => globals.nodes.push(Node {
+                    id: global.id,
+                    name: props.get("node.name").unwrap_or_default().to_string(),
+                    description: props
+                        .get("node.description")
+                        .or_else(|| props.get("node.nick"))
+                        .or_else(|| props.get("application.name"))
+                        .unwrap_or_default()
+                        .to_string(),
+                    media_class: props.get("media.class").unwrap_or_default().to_string(),
+                    latency: props.get("node.latency").map(str::to_string),
+                }),
+                ObjectType::Port => {
+                    if let Some(name) = props.get("port.name") {
+                        globals.ports.insert(global.id, name.to_string());
+                    }
+                }
+                ObjectType::Link => {
+                    if let (Some(output_node), Some(output_port), Some(input_node), Some(input_port)) = (
+                        number("link.output.node"),
+                        number("link.output.port"),
+                        number("link.input.node"),
+                        number("link.input.port"),
+                    ) {
+                        globals.links.push(LinkGlobal { id: global.id, output_node, output_port, input_node, input_port });
+                    }
+                }
+                _ => {}
+            }
+        })
+        .register();
+
+    // The registry announces what exists before the reply to this sync
+    let pending = core.sync(0).map_err(|e| anyhow!("Failed to sync with PipeWire: {}", e))?;
+    let done = Rc::new(Cell::new(false));
+    let failed = Rc::new(RefCell::new(None));
+    let (done_flag, done_loop) = (done.clone(), mainloop.clone());
+    let (error, error_loop) = (failed.clone(), mainloop.clone());
+    let _core_listener = core
+        .add_listener_local()
+        .done(move |id, seq| {
+            if id == pw::core::PW_ID_CORE && seq == pending {
+                done_flag.set(true);
+                done_loop.quit();
+            }
+        })
+        .error(move |id, _seq, _res, message| {
+            if id == pw::core::PW_ID_CORE {
+                *error.borrow_mut() = Some(message.to_string());
+                error_loop.quit();
+            }
+        })
+        .register();
+
+    while !done.get() {
+        mainloop.run();
+        if let Some(message) = failed.borrow_mut().take() {
+            return Err(anyhow!("PipeWire error: {}", message));
+        }
+    }
+
+    let globals = globals.take();
+    let port_name = |id: u32| globals.ports.get(&id).cloned().unwrap_or_else(|| id.to_string());
+    let links = globals
+        .links
+        .iter()
+        .map(|link| Link {
+            id: link.id,
+            output_node: link.output_node,
+            output_port: port_name(link.output_port),
+            input_node: link.input_node,
+            input_port: port_name(link.input_port),
+        })
+        .collect();
+    debug!("PipeWire graph: {} nodes, {} links", globals.nodes.len(), globals.links.len());
+    Ok(Graph { nodes: globals.nodes, links })
+}
+
+impl AudioBackend for PipeWireGraphBackend {
+    fn name(&self) -> &'static str {
+        "PulseAudio with PipeWire graph"
+    }
+
+    fn init(&self) -> BoxFuture<'_, Result<()>> {
+        self.pulse.init()
+    }
+
+    fn volume(&self) -> BoxFuture<'_, Result<(f32, bool)>> {
+        self.pulse.volume()
+    }
+
+    fn set_volume(&self, volume: f32) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_volume(volume)
+    }
+
+    fn set_mute(&self, muted: bool) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_mute(muted)
+    }
+
+    fn mic_volume(&self) -> BoxFuture<'_, Result<(f32, bool)>> {
+        self.pulse.mic_volume()
+    }
+
+    fn set_mic_volume(&self, volume: f32) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_mic_volume(volume)
+    }
+
+    fn set_mic_mute(&self, muted: bool) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_mic_mute(muted)
+    }
+
+    fn devices(&self) -> BoxFuture<'_, Result<(Vec<AudioDevice>, Vec<AudioDevice>)>> {
+        self.pulse.devices()
+    }
+
+    fn set_default_output(&self, device_index: u32) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_default_output(device_index)
+    }
+
+    fn set_default_input(&self, device_index: u32) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_default_input(device_index)
+    }
+
+    fn sink_inputs(&self) -> BoxFuture<'_, Result<Vec<SinkInput>>> {
+        self.pulse.sink_inputs()
+    }
+
+    fn set_sink_input_volume(&self, index: u32, volume: f32) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_sink_input_volume(index, volume)
+    }
+
+    fn set_sink_input_mute(&self, index: u32, muted: bool) -> BoxFuture<'_, Result<()>> {
+        self.pulse.set_sink_input_mute(index, muted)
+    }
+
+    fn move_sink_input(&self, index: u32, sink_index: u32) -> BoxFuture<'_, Result<()>> {
+        self.pulse.move_sink_input(index, sink_index)
+    }
+
+    fn has_graph(&self) -> bool {
+        true
+    }
+
+    fn nodes(&self) -> BoxFuture<'_, Result<Vec<Node>>> {
+        async { Ok(self.graph().await?.nodes) }.boxed()
+    }
+
+    fn links(&self) -> BoxFuture<'_, Result<Vec<Link>>> {
+        async { Ok(self.graph().await?.links) }.boxed()
+    }
+}
@@ -9,12 +9,25 @@
 // 6. set_default_device - Change default sink/source
 // 7. Port switching - Already implemented via set_sink_port_by_index/set_source_port_by_index
 //
-use anyhow::Result;
-use std::collections::HashMap;
+use anyhow::{bail, Context as _, Result};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
-use tracing::{info, debug, error};
+use std::time::Duration;
+use tracing::{info, debug, error, warn};
+use iced::futures::channel::mpsc as iced_mpsc;
+use iced::futures::{SinkExt, Stream};
+use iced::Subscription;
+use libpulse_binding::context::subscribe::{Facility, InterestMaskSet, Operation};
+use libpulse_binding::context::{Context, FlagSet as ContextFlagSet, State as ContextState};
+use libpulse_binding::mainloop::standard::{IterateResult, Mainloop};
 use pulsectl::controllers::{SinkController, SourceController, DeviceControl};
 use pulsectl::controllers::types::DeviceInfo;
+use libpulse_binding::callbacks::ListResult;
+use libpulse_binding::context::introspect::CardInfo;
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::devices::DeviceManager;
+use tokio::sync::mpsc as tokio_mpsc;
 
 // PulseAudio constants
 const PA_VOLUME_NORM: u32 = 0x10000; // 65536
@@ -57,6 +70,30 @@ fn volume_percent_from_cvol(volume: &libpulse_binding::volume::ChannelVolumes) -
     }
 }
 
+/// `percent` on the software volume scale in dB, `None` for silence
+pub fn volume_db(percent: f32) -> Option<f32> {
+    let volume = libpulse_binding::volume::Volume((percent.max(0.0) / 100.0 * PA_VOLUME_NORM as f32) as u32);
+    let db = libpulse_binding::volume::VolumeDB::from(volume).0;
+    db.is_finite().then_some(db as f32)
+}
+
+fn device_channels(device: &DeviceInfo) -> Vec<crate::DeviceChannel> {
+    let positions = device.channel_map.get();
+    device
+        .volume
+        .get()
+        .iter()
+        .enumerate()
+        .map(|(channel, volume)| crate::DeviceChannel {
+            position: positions
+                .get(channel)
+                .and_then(|position| position.to_pretty_string())
+                .unwrap_or_else(|| format!("Channel {}", channel + 1)),
+            volume_percent: (volume.0 as f32 / PA_VOLUME_NORM as f32) * 100.0,
+        })
+        .collect()
+}
+
 fn device_details_from_device_info(device: DeviceInfo, is_default: bool) -> crate::AudioDeviceDetails {
     let ports = device
         .ports
@@ -85,6 +122,7 @@ fn device_details_from_device_info(device: DeviceInfo, is_default: bool) -> crat
         is_default,
         volume_percent: volume_percent_from_cvol(&device.volume),
         muted: device.mute,
+        channels: device_channels(&device),
         state: format!("{:?}", device.state),
         driver: device.driver.clone(),
         card: device.card,
@@ -94,7 +132,44 @@ fn device_details_from_device_info(device: DeviceInfo, is_default: bool) -> crat
         configured_latency_usec: device.configured_latency.0,
         ports,
         active_port: device.active_port.and_then(|p| p.name),
+        profiles: Vec::new(),
+        active_profile: None,
+    }
+}
+
+/// Profiles of card `card_index` and the name of the active one
+fn card_profiles(handler: &mut pulsectl::Handler, card_index: u32) -> (Vec<crate::CardProfile>, Option<String>) {
+    let found = Rc::new(RefCell::new((Vec::new(), None)));
+    let found_ref = found.clone();
+    let op = handler.introspect.get_card_info_by_index(card_index, move |result: ListResult<&CardInfo>| {
+        if let ListResult::Item(card) = result {
+            let profiles = card
+                .profiles
+                .iter()
+                .map(|profile| crate::CardProfile {
+                    name: profile.name.as_deref().unwrap_or_default().to_string(),
+                    description: profile.description.as_deref().unwrap_or_default().to_string(),
+                    priority: profile.priority,
+                    available: profile.available,
+                })
+                .collect();
+            let active = card.active_profile.as_ref().and_then(|profile| profile.name.as_deref()).map(str::to_string);
+            *found_ref.borrow_mut() = (profiles, active);
+        }
+    });
+    if let Err(e) = handler.wait_for_operation(op) {
+        warn!("Failed to get profiles of card {}: {}", card_index, e);
     }
+
+    let (profiles, active) = found.take();
+    (DeviceManager::sort_profiles(profiles), active)
+}
+
+fn with_card_profiles(handler: &mut pulsectl::Handler, mut details: crate::AudioDeviceDetails) -> crate::AudioDeviceDetails {
+    if let Some(card) = details.card {
+        (details.profiles, details.active_profile) = card_profiles(handler, card);
+    }
+    details
 }
 
 impl PulseAudioManager {
@@ -532,7 +607,7 @@ pub async fn get_output_device_details(device_index: u32) -> Result<crate::Audio
 
         let is_default = device.name.clone().unwrap_or_default() == default_name;
         debug!("Successfully fetched output device details for index {}: {} ports", device_index, device.ports.len());
-        Ok(device_details_from_device_info(device, is_default))
+        Ok(with_card_profiles(&mut controller.handler, device_details_from_device_info(device, is_default)))
     })
     .await
     .map_err(|e| {
@@ -571,7 +646,7 @@ pub async fn get_input_device_details(device_index: u32) -> Result<crate::AudioD
 
         let is_default = device.name.clone().unwrap_or_default() == default_name;
         debug!("Successfully fetched input device details for index {}: {} ports", device_index, device.ports.len());
-        Ok(device_details_from_device_info(device, is_default))
+        Ok(with_card_profiles(&mut controller.handler, device_details_from_device_info(device, is_default)))
     })
     .await
     .map_err(|e| {
@@ -584,6 +659,84 @@ pub async fn get_input_device_details(device_index: u32) -> Result<crate::AudioD
     })
 }
 
+/// Switch card `card_index` to `profile_name`. The card's devices are
+/// replaced when the profile changes, so this returns the details of its
+/// new output (or input) device, if the profile has one.
+pub async fn set_card_profile(card_index: u32, profile_name: String, is_output: bool) -> Result<Option<crate::AudioDeviceDetails>> {
+    debug!("Setting card profile: card={}, profile={}", card_index, profile_name);
+    let device_index = tokio::task::spawn_blocking(move || -> Result<Option<u32>, anyhow::Error> {
+        let mut controller = SinkController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SinkController: {}", e))?;
+        let op = controller
+            .handler
+            .introspect
+            .set_card_profile_by_index(card_index, &profile_name, None);
+        controller
+            .handler
+            .wait_for_operation(op)
+            .map_err(|e| anyhow::anyhow!("Failed to set card profile: {}", e))?;
+        info!("Switched card {} to profile {}", card_index, profile_name);
+
+        let devices = if is_output {
+            controller.list_devices()
+        } else {
+            SourceController::create()
+                .map_err(|e| anyhow::anyhow!("Failed to create SourceController: {}", e))?
+                .list_devices()
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to list devices: {}", e))?;
+
+        Ok(devices
+            .into_iter()
+            .find(|device| {
+                device.card == Some(card_index) && !device.name.as_deref().unwrap_or_default().ends_with(".monitor")
+            })
+            .map(|device| device.index))
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+    match device_index {
+        Some(index) if is_output => get_output_device_details(index).await.map(Some),
+        Some(index) => get_input_device_details(index).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Set each channel of a device on its own, `volumes` being percent in
+/// channel map order
+pub async fn set_device_channel_volumes(device_index: u32, is_output: bool, volumes: Vec<f32>) -> Result<()> {
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        if is_output {
+            let mut controller = SinkController::create()
+                .map_err(|e| anyhow::anyhow!("Failed to create SinkController: {}", e))?;
+            set_channel_volumes(&mut controller, device_index, &volumes)
+        } else {
+            let mut controller = SourceController::create()
+                .map_err(|e| anyhow::anyhow!("Failed to create SourceController: {}", e))?;
+            set_channel_volumes(&mut controller, device_index, &volumes)
+        }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+}
+
+fn set_channel_volumes<C: DeviceControl<DeviceInfo>>(controller: &mut C, device_index: u32, volumes: &[f32]) -> Result<()> {
+    let device = controller
+        .get_device_by_index(device_index)
+        .map_err(|e| anyhow::anyhow!("Failed to get device by index {}: {}", device_index, e))?;
+    let mut channel_volumes = device.volume;
+    if channel_volumes.len() as usize != volumes.len() {
+        bail!("Device {} has {} channels, not {}", device_index, channel_volumes.len(), volumes.len());
+    }
+    for (slot, percent) in channel_volumes.get_mut().iter_mut().zip(volumes) {
+        *slot = libpulse_binding::volume::Volume((percent.max(0.0) / 100.0 * PA_VOLUME_NORM as f32) as u32);
+    }
+    controller.set_device_volume_by_index(device_index, &channel_volumes);
+    debug!("Set channel volumes of device {} to {:?}", device_index, volumes);
+    Ok(())
+}
+
 pub async fn set_output_device_port(device_index: u32, port_name: String) -> Result<()> {
     debug!("Setting output device port: index={}, port={}", device_index, port_name);
     tokio::task::spawn_blocking(move || -> Result<(), anyhow::Error> {
@@ -645,3 +798,134 @@ pub async fn set_input_device_port(device_index: u32, port_name: String) -> Resu
         anyhow::anyhow!("Task error: {}", e)
     })?
 }
+
+// Server events
+//
+// A dedicated thread runs a libpulse mainloop subscribed to sink, source,
+// sink input, source output and server changes. Bursts of events (a volume drag produces
+// dozens) are coalesced before they reach the app, which refetches only
+// what changed instead of polling.
+
+/// Something changed on the sound server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PulseEvent {
+    /// Volume or mute state of a device changed
+    VolumeChanged,
+    /// A device was added or removed, or the default device changed
+    DevicesChanged,
+    /// An application stream appeared, went away or changed its volume
+    SinkInputChanged,
+    /// A recording stream appeared, went away or changed
+    SourceOutputChanged,
+}
+
+impl PulseEvent {
+    fn from_pulse(facility: Option<Facility>, operation: Option<Operation>) -> Option<Self> {
+        match (facility?, operation?) {
+            (Facility::Sink | Facility::Source, Operation::Changed) => Some(Self::VolumeChanged),
+            (Facility::Sink | Facility::Source | Facility::Server, _) => Some(Self::DevicesChanged),
+            (Facility::SinkInput, _) => Some(Self::SinkInputChanged),
+            (Facility::SourceOutput, _) => Some(Self::SourceOutputChanged),
+            _ => None,
+        }
+    }
+}
+
+/// Time to gather a burst of events into one update
+const COALESCE_WINDOW: Duration = Duration::from_millis(30);
+/// Wait before reconnecting after the server goes away
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Server events as they happen, reconnecting if the server restarts
+pub fn events() -> Subscription<PulseEvent> {
+    Subscription::run(follow_events)
+}
+
+fn follow_events() -> impl Stream<Item = PulseEvent> {
+    iced::stream::channel(16, async |mut output: iced_mpsc::Sender<PulseEvent>| loop {
+        let (sender, mut receiver) = tokio_mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            if let Err(e) = run_event_loop(&sender) {
+                warn!("PulseAudio event subscription ended: {:#}", e);
+            }
+        });
+
+        while let Some(first) = receiver.recv().await {
+            let mut burst = BTreeSet::from([first]);
+            let deadline = tokio::time::Instant::now() + COALESCE_WINDOW;
+            while let Ok(Some(event)) = tokio::time::timeout_at(deadline, receiver.recv()).await {
+                burst.insert(event);
+            }
+            for event in burst {
+                if output.send(event).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+        // Whatever changed while disconnected
+        for event in [
+            PulseEvent::DevicesChanged,
+            PulseEvent::VolumeChanged,
+            PulseEvent::SinkInputChanged,
+            PulseEvent::SourceOutputChanged,
+        ] {
+            if output.send(event).await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+/// Connect, subscribe and forward events until the connection fails
+fn run_event_loop(sender: &tokio_mpsc::UnboundedSender<PulseEvent>) -> Result<()> {
+    let mut mainloop = Mainloop::new().context("Failed to create PulseAudio mainloop")?;
+    let mut context = Context::new(&mainloop, "xfce-rs-audio-events").context("Failed to create PulseAudio context")?;
+    context.connect(None, ContextFlagSet::NOFLAGS, None)?;
+
+    loop {
+        iterate(&mut mainloop)?;
+        match context.get_state() {
+            ContextState::Ready => break,
+            ContextState::Failed | ContextState::Terminated => bail!("Failed to connect to PulseAudio"),
+            _ => {}
+        }
+    }
+
+    let events = sender.clone();
+    context.set_subscribe_callback(Some(Box::new(move |facility, operation, _index| {
+        if let Some(event) = PulseEvent::from_pulse(facility, operation) {
+            let _ = events.send(event);
+        }
+    })));
+    let interest = InterestMaskSet::SINK
+        | InterestMaskSet::SOURCE
+        | InterestMaskSet::SINK_INPUT
+        | InterestMaskSet::SOURCE_OUTPUT
+        | InterestMaskSet::SERVER;
+    let _subscribed = context.subscribe(interest, |success| {
+        if !success {
+            error!("PulseAudio refused the event subscription");
+        }
+    });
+    debug!("Subscribed to PulseAudio events");
+
+    loop {
+        iterate(&mut mainloop)?;
+        if sender.is_closed() {
+            return Ok(());
+        }
+        if !matches!(context.get_state(), ContextState::Ready) {
+            bail!("Lost the PulseAudio connection");
+        }
+    }
+}
+
+fn iterate(mainloop: &mut Mainloop) -> Result<()> {
+    match mainloop.iterate(true) {
+        IterateResult::Success(_) => Ok(()),
+        IterateResult::Quit(_) => bail!("PulseAudio mainloop quit"),
+        IterateResult::Err(e) => Err(e.into()),
+    }
+}
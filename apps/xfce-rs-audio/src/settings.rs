@@ -0,0 +1,139 @@
+// Audio app settings, stored in the xfce-rs-audio channel
+use iced::mouse::ScrollDelta;
+use serde::Serialize;
+use xfce_rs_config::{config_section, ConfigValue, XfceConfig};
+
+pub const CHANNEL: &str = "xfce-rs-audio";
+
+/// Set `property` of the xfce-rs-audio channel to `value` and write it out
+pub async fn store<T: Serialize + ?Sized>(property: &str, value: &T) -> anyhow::Result<()> {
+    let config = XfceConfig::default();
+    config
+        .set_property(CHANNEL, property, ConfigValue::from_serialize(value)?)
+        .await?;
+    // Write right away, the config handle doesn't outlive this call
+    config.save().await?;
+    Ok(())
+}
+
+/// Loudest volume offered when overamplification is on, like GNOME and
+/// pavucontrol (+11 dB)
+pub const MAX_OVERAMPLIFIED: i32 = 153;
+
+config_section! {
+    /// How the volume sliders behave
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct VolumeConfig in "xfce-rs-audio" {
+        /// Let the sliders go above 100%, up to `/volume/max-volume`
+        pub allow_overamplification: bool = "/volume/allow-overamplification" => false,
+        pub max_volume: i32 = "/volume/max-volume" => MAX_OVERAMPLIFIED,
+        /// Percent per key press or scroll notch
+        pub step: i32 = "/volume/step" => 5,
+        /// Percent per step while Shift is held
+        pub fine_step: i32 = "/volume/fine-step" => 1,
+    }
+}
+
+impl VolumeConfig {
+    pub async fn load() -> Self {
+        XfceConfig::default().load_section::<Self>().await
+    }
+
+    /// Upper end of the volume sliders
+    pub fn max(&self) -> f32 {
+        if self.allow_overamplification {
+            self.max_volume.clamp(100, MAX_OVERAMPLIFIED) as f32
+        } else {
+            100.0
+        }
+    }
+
+    pub fn step(&self, fine: bool) -> f32 {
+        let step = if fine { self.fine_step } else { self.step };
+        step.clamp(1, 100) as f32
+    }
+
+    /// `volume` moved `steps` steps, within the slider range
+    pub fn adjust(&self, volume: f32, steps: f32, fine: bool) -> f32 {
+        (volume + steps * self.step(fine)).clamp(0.0, self.max())
+    }
+
+    /// `volume` after one scroll notch, up or down
+    pub fn scrolled(&self, volume: f32, delta: ScrollDelta, fine: bool) -> f32 {
+        let (x, y) = match delta {
+            ScrollDelta::Lines { x, y } | ScrollDelta::Pixels { x, y } => (x, y),
+        };
+        // Some setups turn Shift+wheel into horizontal scrolling
+        let amount = if y != 0.0 { y } else { x };
+        if amount == 0.0 {
+            return volume;
+        }
+        self.adjust(volume, amount.signum(), fine)
+    }
+}
+
+config_section! {
+    /// Bluetooth audio devices
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct BluetoothConfig in "xfce-rs-audio" {
+        /// Make a Bluetooth headset or speaker the default output when it connects
+        pub auto_switch: bool = "/bluetooth/auto-switch" => true,
+    }
+}
+
+impl BluetoothConfig {
+    pub async fn load() -> Self {
+        XfceConfig::default().load_section::<Self>().await
+    }
+}
+
+config_section! {
+    /// Which device takes over when the default one goes away, e.g. an
+    /// unplugged USB DAC
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct FallbackConfig in "xfce-rs-audio" {
+        /// Output device names, most preferred first
+        pub output_priority: Vec<String> = "/devices/output-priority" => Vec::<String>::new(),
+        /// Input device names, most preferred first
+        pub input_priority: Vec<String> = "/devices/input-priority" => Vec::<String>::new(),
+    }
+}
+
+impl FallbackConfig {
+    pub async fn load() -> Self {
+        XfceConfig::default().load_section::<Self>().await
+    }
+}
+
+config_section! {
+    /// Popups the daemon mode sends through the notification daemon
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct NotificationConfig in "xfce-rs-audio" {
+        /// When a device is plugged in or becomes the default
+        pub devices: bool = "/notifications/devices" => true,
+        /// When something other than the media keys changes the volume
+        pub volume: bool = "/notifications/volume" => true,
+    }
+}
+
+impl NotificationConfig {
+    pub async fn load() -> Self {
+        XfceConfig::default().load_section::<Self>().await
+    }
+}
+
+config_section! {
+    /// Event sounds, shared with GTK applications through XSETTINGS
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SoundConfig in "xsettings" {
+        pub enabled: bool = "/Net/EnableEventSounds" => true,
+        /// Directory name of the freedesktop sound theme
+        pub theme_name: String = "/Net/SoundThemeName" => "freedesktop".to_string(),
+    }
+}
+
+impl SoundConfig {
+    pub async fn load() -> Self {
+        XfceConfig::default().load_section::<Self>().await
+    }
+}
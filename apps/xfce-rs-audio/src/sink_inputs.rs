@@ -12,6 +12,7 @@ const PA_VOLUME_NORM: u32 = 0x10000; // 65536
 const PA_PROP_APPLICATION_NAME: &str = "application.name";
 const PA_PROP_APPLICATION_ICON_NAME: &str = "application.icon_name";
 const PA_PROP_APPLICATION_ID: &str = "application.id";
+const PA_PROP_APPLICATION_PROCESS_BINARY: &str = "application.process.binary";
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SinkInput {
@@ -19,11 +20,38 @@ pub struct SinkInput {
     pub name: String,
     pub application_name: String,
     pub application_icon: Option<String>,
+    /// Executable name of the application's process, e.g. "firefox"
+    pub application_binary: Option<String>,
     pub volume: f32,
     pub muted: bool,
     pub sink_index: u32,
 }
 
+impl SinkInput {
+    /// What identifies the application across runs: its binary, or its
+    /// name where the binary is unknown
+    pub fn app_key(&self) -> String {
+        self.application_binary.clone().unwrap_or_else(|| self.application_name.clone())
+    }
+
+    /// Icon names to try for the application, best first: its icon name,
+    /// its binary, then its name in lowercase
+    pub fn icon_candidates(&self) -> Vec<String> {
+        let mut candidates: Vec<String> = Vec::new();
+        let names = [
+            self.application_icon.clone(),
+            self.application_binary.clone(),
+            Some(self.application_name.to_lowercase()),
+        ];
+        for name in names.into_iter().flatten() {
+            if !name.is_empty() && !candidates.contains(&name) {
+                candidates.push(name);
+            }
+        }
+        candidates
+    }
+}
+
 pub struct SinkInputManager {
     inputs: Arc<Mutex<HashMap<u32, SinkInput>>>,
 }
@@ -74,6 +102,9 @@ impl SinkInputManager {
                 .get_str(PA_PROP_APPLICATION_ICON_NAME)
                 .or_else(|| app.proplist.get_str(PA_PROP_APPLICATION_ID));
             
+            // Get the process binary, which stays the same across runs
+            let application_binary = app.proplist.get_str(PA_PROP_APPLICATION_PROCESS_BINARY);
+            
             // Calculate volume percentage
             // ChannelVolumes has a get() method that returns a slice of Volume
             let volume_percent = if app.volume.get().len() > 0 {
@@ -94,6 +125,7 @@ impl SinkInputManager {
                 name: name.clone(),
                 application_name: application_name.clone(),
                 application_icon: application_icon.map(|s| s.to_string()),
+                application_binary,
                 volume: volume_percent,
                 muted,
                 sink_index,
@@ -212,6 +244,33 @@ impl SinkInputManager {
         Ok(())
     }
 
+    /// Route sink input `index` to the sink `sink_index`
+    pub async fn move_sink_input(&self, index: u32, sink_index: u32) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            Self::move_sink_input_blocking(index, sink_index)
+        }).await.map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        let mut inputs = self.inputs.lock().unwrap();
+        if let Some(input) = inputs.get_mut(&index) {
+            input.sink_index = sink_index;
+        }
+        Ok(())
+    }
+
+    fn move_sink_input_blocking(index: u32, sink_index: u32) -> Result<()> {
+        let mut controller = SinkController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SinkController: {}", e))?;
+
+        let moved = controller.move_app_by_index(index, sink_index)
+            .map_err(|e| anyhow::anyhow!("Failed to move sink input: {}", e))?;
+        if !moved {
+            anyhow::bail!("PulseAudio refused to move sink input {} to sink {}", index, sink_index);
+        }
+
+        info!("Moved sink input {} to sink {}", index, sink_index);
+        Ok(())
+    }
+
     fn set_sink_input_mute_blocking(
         index: u32,
         muted: bool,
@@ -245,3 +304,7 @@ pub async fn set_sink_input_volume(index: u32, volume: f32) -> Result<()> {
 pub async fn set_sink_input_mute(index: u32, muted: bool) -> Result<()> {
     MANAGER.set_sink_input_mute(index, muted).await
 }
+
+pub async fn move_sink_input(index: u32, sink_index: u32) -> Result<()> {
+    MANAGER.move_sink_input(index, sink_index).await
+}
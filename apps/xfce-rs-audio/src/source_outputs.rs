@@ -0,0 +1,218 @@
+// PulseAudio source output management for per-application capture volume
+// The recording counterpart of sink_inputs, using pulsectl-rs
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::{info, debug};
+use once_cell::sync::Lazy;
+use pulsectl::controllers::{SourceController, AppControl};
+
+// PulseAudio constants
+const PA_VOLUME_NORM: u32 = 0x10000; // 65536
+const PA_PROP_APPLICATION_NAME: &str = "application.name";
+const PA_PROP_APPLICATION_ICON_NAME: &str = "application.icon_name";
+const PA_PROP_APPLICATION_ID: &str = "application.id";
+/// Resample method of the streams level meters open to watch a source;
+/// they capture nothing anyone would want to control
+const PEAK_DETECT_RESAMPLE_METHOD: &str = "peaks";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceOutput {
+    pub index: u32,
+    pub name: String,
+    pub application_name: String,
+    pub application_icon: Option<String>,
+    pub volume: f32,
+    pub muted: bool,
+    pub source_index: u32,
+    /// The stream is running, i.e. the application is capturing right now
+    pub recording: bool,
+}
+
+pub struct SourceOutputManager {
+    outputs: Arc<Mutex<HashMap<u32, SourceOutput>>>,
+}
+
+impl SourceOutputManager {
+    pub fn new() -> Self {
+        Self {
+            outputs: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn get_source_outputs(&self) -> Result<Vec<SourceOutput>> {
+        // Create controller in the blocking task since it's not Send
+        let outputs_cache = self.outputs.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            Self::get_source_outputs_blocking(outputs_cache)
+        }).await.map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        Ok(result)
+    }
+
+    fn get_source_outputs_blocking(
+        outputs_cache: Arc<Mutex<HashMap<u32, SourceOutput>>>,
+    ) -> Result<Vec<SourceOutput>> {
+        let mut controller = SourceController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SourceController: {}", e))?;
+
+        let apps = controller.list_applications()
+            .map_err(|e| anyhow::anyhow!("Failed to list applications: {}", e))?;
+
+        let mut source_outputs = Vec::new();
+
+        for app in apps {
+            if app.resample_method.as_deref() == Some(PEAK_DETECT_RESAMPLE_METHOD) {
+                continue;
+            }
+
+            let index = app.index;
+            let name = app.name.clone().unwrap_or_else(|| format!("Unknown-{}", index));
+
+            let application_name = app.proplist
+                .get_str(PA_PROP_APPLICATION_NAME)
+                .unwrap_or_else(|| name.clone());
+
+            let application_icon = app.proplist
+                .get_str(PA_PROP_APPLICATION_ICON_NAME)
+                .or_else(|| app.proplist.get_str(PA_PROP_APPLICATION_ID));
+
+            let volume_percent = match app.volume.get().first() {
+                Some(vol) => (vol.0 as f32 / PA_VOLUME_NORM as f32) * 100.0,
+                None => 0.0,
+            };
+
+            debug!("Source output {}: {} (app: {}, volume: {:.1}%, muted: {}, corked: {})",
+                index, name, application_name, volume_percent, app.mute, app.corked);
+
+            source_outputs.push(SourceOutput {
+                index,
+                name,
+                application_name,
+                application_icon,
+                volume: volume_percent,
+                muted: app.mute,
+                source_index: app.connection_id,
+                recording: !app.corked,
+            });
+        }
+
+        let mut cache = outputs_cache.lock().unwrap();
+        cache.clear();
+        for output in &source_outputs {
+            cache.insert(output.index, output.clone());
+        }
+
+        info!("Found {} source outputs", source_outputs.len());
+        Ok(source_outputs)
+    }
+
+    pub async fn set_source_output_volume(&self, index: u32, volume: f32) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            Self::set_source_output_volume_blocking(index, volume)
+        }).await.map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        let mut outputs = self.outputs.lock().unwrap();
+        if let Some(output) = outputs.get_mut(&index) {
+            output.volume = volume;
+        }
+        Ok(())
+    }
+
+    fn set_source_output_volume_blocking(index: u32, volume: f32) -> Result<()> {
+        let mut controller = SourceController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SourceController: {}", e))?;
+
+        let mut app = controller.get_app_by_index(index)
+            .map_err(|e| anyhow::anyhow!("Failed to get app by index {}: {}", index, e))?;
+
+        // Every channel at the same level, like the slider shows it
+        let channels = app.volume.len();
+        let level = libpulse_binding::volume::Volume((volume.max(0.0) / 100.0 * PA_VOLUME_NORM as f32) as u32);
+        app.volume.set(channels, level);
+
+        let op = controller.handler.introspect.set_source_output_volume(index, &app.volume, None);
+        controller.handler.wait_for_operation(op)
+            .map_err(|e| anyhow::anyhow!("Failed to set volume: {}", e))?;
+
+        debug!("Set source output {} volume to {:.1}%", index, volume);
+        Ok(())
+    }
+
+    pub async fn set_source_output_mute(&self, index: u32, muted: bool) -> Result<()> {
+        // Update local cache immediately for UI responsiveness
+        {
+            let mut outputs = self.outputs.lock().unwrap();
+            if let Some(output) = outputs.get_mut(&index) {
+                output.muted = muted;
+            }
+        }
+
+        tokio::task::spawn_blocking(move || {
+            Self::set_source_output_mute_blocking(index, muted)
+        }).await.map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        Ok(())
+    }
+
+    /// Record source output `index` from the source `source_index`
+    pub async fn move_source_output(&self, index: u32, source_index: u32) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            Self::move_source_output_blocking(index, source_index)
+        }).await.map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        let mut outputs = self.outputs.lock().unwrap();
+        if let Some(output) = outputs.get_mut(&index) {
+            output.source_index = source_index;
+        }
+        Ok(())
+    }
+
+    fn move_source_output_blocking(index: u32, source_index: u32) -> Result<()> {
+        let mut controller = SourceController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SourceController: {}", e))?;
+
+        let moved = controller.move_app_by_index(index, source_index)
+            .map_err(|e| anyhow::anyhow!("Failed to move source output: {}", e))?;
+        if !moved {
+            anyhow::bail!("PulseAudio refused to move source output {} to source {}", index, source_index);
+        }
+
+        info!("Moved source output {} to source {}", index, source_index);
+        Ok(())
+    }
+
+    fn set_source_output_mute_blocking(index: u32, muted: bool) -> Result<()> {
+        let mut controller = SourceController::create()
+            .map_err(|e| anyhow::anyhow!("Failed to create SourceController: {}", e))?;
+
+        controller.set_app_mute(index, muted)
+            .map_err(|e| anyhow::anyhow!("Failed to set mute: {}", e))?;
+
+        debug!("Set source output {} mute to {}", index, muted);
+        Ok(())
+    }
+}
+
+// Global manager instance
+static MANAGER: Lazy<Arc<SourceOutputManager>> = Lazy::new(|| {
+    Arc::new(SourceOutputManager::new())
+});
+
+// Public API functions
+pub async fn get_source_outputs() -> Result<Vec<SourceOutput>> {
+    MANAGER.get_source_outputs().await
+}
+
+pub async fn set_source_output_volume(index: u32, volume: f32) -> Result<()> {
+    MANAGER.set_source_output_volume(index, volume).await
+}
+
+pub async fn set_source_output_mute(index: u32, muted: bool) -> Result<()> {
+    MANAGER.set_source_output_mute(index, muted).await
+}
+
+pub async fn move_source_output(index: u32, source_index: u32) -> Result<()> {
+    MANAGER.move_source_output(index, source_index).await
+}
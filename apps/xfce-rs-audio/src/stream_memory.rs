@@ -0,0 +1,162 @@
+// Stream and port memory across sessions
+//
+// The mixer records the volume and mute state the user gives an
+// application, keyed by its binary (or its name where the binary is
+// unknown), and the port picked on each device. The daemon mode puts them
+// back whenever such a stream or device appears, rather than leaving it
+// to whatever the sound server restores on its own.
+//
+// Both are stored in the xfce-rs-audio channel.
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, info, warn};
+use xfce_rs_config::XfceConfig;
+
+use crate::backend::backend;
+use crate::pulseaudio;
+use crate::settings::{self, CHANNEL};
+
+const APPLICATIONS_PROPERTY: &str = "/restore/applications";
+const PORTS_PROPERTY: &str = "/restore/ports";
+
+/// What an application was last set to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AppVolume {
+    pub volume: f32,
+    pub muted: bool,
+}
+
+/// Saved volumes by application key, see [`crate::sink_inputs::SinkInput::app_key`]
+pub async fn load_apps() -> HashMap<String, AppVolume> {
+    load(APPLICATIONS_PROPERTY).await
+}
+
+/// Remember the volume and mute state of the application `key`
+pub async fn remember_app(key: String, volume: f32, muted: bool) -> Result<()> {
+    let mut apps = load_apps().await;
+    if apps.get(&key) == Some(&AppVolume { volume, muted }) {
+        return Ok(());
+    }
+    apps.insert(key, AppVolume { volume, muted });
+    settings::store(APPLICATIONS_PROPERTY, &apps).await
+}
+
+/// Saved ports by device name
+pub async fn load_ports() -> HashMap<String, String> {
+    load(PORTS_PROPERTY).await
+}
+
+/// Remember `port` as the one picked on the device `device_name`
+pub async fn remember_port(device_name: String, port: String) -> Result<()> {
+    let mut ports = load_ports().await;
+    if ports.get(&device_name) == Some(&port) {
+        return Ok(());
+    }
+    ports.insert(device_name, port);
+    settings::store(PORTS_PROPERTY, &ports).await
+}
+
+async fn load<T: serde::de::DeserializeOwned + Default>(property: &str) -> T {
+    XfceConfig::default()
+        .get_property(CHANNEL, property)
+        .await
+        .ok()
+        .and_then(|value| value.deserialize().ok())
+        .unwrap_or_default()
+}
+
+/// Puts saved settings back on streams and devices the first time they
+/// are seen
+#[derive(Debug, Default)]
+pub struct Restorer {
+    streams: HashSet<u32>,
+    devices: HashSet<String>,
+}
+
+impl Restorer {
+    /// Apply saved volumes to application streams that appeared since the
+    /// last call
+    pub async fn restore_streams(&mut self) {
+        let inputs = match backend().sink_inputs().await {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                warn!("Failed to list application streams: {}", e);
+                return;
+            }
+        };
+        // Forget streams that went away, indexes are not reused but the
+        // set shouldn't grow forever
+        self.streams.retain(|index| inputs.iter().any(|input| input.index == *index));
+
+        let new: Vec<_> = inputs.into_iter().filter(|input| self.streams.insert(input.index)).collect();
+        if new.is_empty() {
+            return;
+        }
+        let apps = load_apps().await;
+        for input in new {
+            let key = input.app_key();
+            let Some(saved) = apps.get(&key) else {
+                continue;
+            };
+            debug!("Restoring {} to {:.0}%{}", key, saved.volume, if saved.muted { ", muted" } else { "" });
+            if (input.volume - saved.volume).abs() >= 0.5 {
+                if let Err(e) = backend().set_sink_input_volume(input.index, saved.volume).await {
+                    warn!("Failed to restore the volume of {}: {}", key, e);
+                }
+            }
+            if input.muted != saved.muted {
+                if let Err(e) = backend().set_sink_input_mute(input.index, saved.muted).await {
+                    warn!("Failed to restore the mute state of {}: {}", key, e);
+                }
+            }
+        }
+    }
+
+    /// Switch devices that appeared since the last call to their saved port
+    pub async fn restore_ports(&mut self) {
+        let (outputs, inputs) = match backend().devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                warn!("Failed to list devices: {}", e);
+                return;
+            }
+        };
+        self.devices
+            .retain(|name| outputs.iter().chain(&inputs).any(|device| device.name == *name));
+
+        let new_outputs: Vec<_> = outputs.into_iter().filter(|device| self.devices.insert(device.name.clone())).collect();
+        let new_inputs: Vec<_> = inputs.into_iter().filter(|device| self.devices.insert(device.name.clone())).collect();
+        if new_outputs.is_empty() && new_inputs.is_empty() {
+            return;
+        }
+        let ports = load_ports().await;
+        for (device, is_output) in new_outputs.into_iter().map(|d| (d, true)).chain(new_inputs.into_iter().map(|d| (d, false))) {
+            let Some(port) = ports.get(&device.name) else {
+                continue;
+            };
+            let details = if is_output {
+                pulseaudio::get_output_device_details(device.index).await
+            } else {
+                pulseaudio::get_input_device_details(device.index).await
+            };
+            let Ok(details) = details else {
+                continue;
+            };
+            // The port may be gone with a profile change, or already active
+            if details.active_port.as_ref() == Some(port) || !details.ports.iter().any(|p| p.name == *port) {
+                continue;
+            }
+
+            info!("Restoring port {} on {}", port, device.description);
+            let result = if is_output {
+                pulseaudio::set_output_device_port(device.index, port.clone()).await
+            } else {
+                pulseaudio::set_input_device_port(device.index, port.clone()).await
+            };
+            if let Err(e) = result {
+                warn!("Failed to restore port {} on {}: {}", port, device.description, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Force Quit dialog for XFCE.rs
+//!
+//! Opened by xfwm4-rs when a window it asked to close does not answer
+//! `_NET_WM_PING`. Takes the window title and, if known, the process id
+//! as arguments, and prints `kill` when the user chooses to force quit;
+//! the window manager does the killing.
+
+use iced::widget::{button, column, container, mouse_area, row, space, text};
+use iced::{window, Alignment, Element, Length, Size, Subscription, Task, Theme};
+use xfce_rs_ui::{colors, fonts, scale, styles, theme};
+
+pub fn main() -> iced::Result {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let mut args = std::env::args().skip(1);
+    let title = args.next().unwrap_or_default();
+    let pid = args.next().and_then(|pid| pid.parse().ok());
+
+    fonts::load();
+    iced::application(move || ForceQuit::new(title.clone(), pid), ForceQuit::update, ForceQuit::view)
+        .settings(fonts::settings())
+        .title(ForceQuit::title)
+        .theme(ForceQuit::theme)
+        .style(ForceQuit::style)
+        .scale_factor(ForceQuit::scale_factor)
+        .subscription(ForceQuit::subscription)
+        .window(window::Settings {
+            size: Size::new(420.0, 180.0),
+            position: window::Position::Centered,
+            resizable: false,
+            transparent: true,
+            decorations: false,
+            level: window::Level::AlwaysOnTop,
+            ..Default::default()
+        })
+        .run()
+}
+
+struct ForceQuit {
+    /// Title of the window that stopped responding
+    title: String,
+    pid: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Wait,
+    ForceQuit,
+    WindowDragged,
+    ThemeChanged,
+}
+
+impl ForceQuit {
+    fn new(title: String, pid: Option<u32>) -> (Self, Task<Message>) {
+        (Self { title, pid }, Task::none())
+    }
+
+    fn title(&self) -> String {
+        String::from("Not Responding")
+    }
+
+    fn theme(&self) -> Theme {
+        theme::iced_theme()
+    }
+
+    fn scale_factor(&self) -> f32 {
+        scale::factor()
+    }
+
+    fn style(&self, theme: &Theme) -> iced::theme::Style {
+        iced::theme::Style {
+            background_color: iced::Color::TRANSPARENT,
+            text_color: theme.palette().text,
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        theme::subscription().map(|_| Message::ThemeChanged)
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Wait => iced::exit(),
+            Message::ForceQuit => {
+                // The window manager reads the answer once we exit
+                println!("kill");
+                iced::exit()
+            }
+            Message::WindowDragged => window::latest().and_then(window::drag),
+            Message::ThemeChanged => Task::none(),
+        }
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        let name = if self.title.is_empty() { "This window" } else { self.title.as_str() };
+        let detail = match self.pid {
+            Some(pid) => format!("Force quitting it may lose unsaved work. Process {}.", pid),
+            None => String::from("Force quitting it may lose unsaved work."),
+        };
+
+        let header = mouse_area(
+            column![
+                text(format!("“{}” is not responding", name)).size(18).color(colors::text_primary()),
+                text(detail).size(13).color(colors::text_secondary()),
+            ]
+            .spacing(6)
+            .width(Length::Fill),
+        )
+        .on_press(Message::WindowDragged);
+
+        let buttons = row![
+            space().width(Length::Fill),
+            button(text("Wait").size(14))
+                .on_press(Message::Wait)
+                .padding([8, 16])
+                .style(|theme, status| styles::app_card(theme, status)),
+            button(text("Force Quit").size(14).color(colors::control_close()))
+                .on_press(Message::ForceQuit)
+                .padding([8, 16])
+                .style(|theme, status| styles::app_card(theme, status)),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        container(column![header, space().height(Length::Fill), buttons].spacing(14))
+            .padding(24)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(|theme| styles::glass_base(theme))
+            .into()
+    }
+}
@@ -1,22 +1,35 @@
 use iced::widget::{
-    column, container, row, text, text_input, scrollable, button, image, svg, space,
+    column, container, row, text, text_input, scrollable, button, svg, space,
     mouse_area,
 };
-use iced::{Alignment, Element, Length, Task, Theme, Color, window, Point};
+use iced::{Alignment, Element, Length, Task, Theme, Color, window, Point, Subscription};
 use freedesktop_desktop_entry::{DesktopEntry, Iter as DesktopIter};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::process::Command as StdCommand;
-use linicon;
 use xfce_rs_ui::styles;
 use xfce_rs_ui::colors;
+use xfce_rs_ui::{fonts, icon, instance, scale, theme};
+use xfce_rs_ui::chrome::{self, ChromeEvent};
+use xfce_rs_ui::focus::{self, FocusEvent, FocusRing};
+use xfce_rs_ui::menu::{self, ContextMenu, MenuEvent, MenuItem, MenuOutcome};
+
+/// Bus name the running navigator owns
+const APP_ID: &str = "org.xfce_rs.Navigator";
 
 pub fn main() -> iced::Result {
+    // A second launch raises the running navigator and exits
+    instance::claim(APP_ID, &[]);
+
+    fonts::load();
     iced::application(Navigator::new, Navigator::update, Navigator::view)
+        .settings(fonts::settings())
         .title(Navigator::title)
         .theme(Navigator::theme)
         .style(Navigator::style)
+        .scale_factor(Navigator::scale_factor)
+        .subscription(Navigator::subscription)
         .window(iced::window::Settings {
             size: iced::Size::new(800.0, 600.0), // Increased size for new features
             position: iced::window::Position::Centered,
@@ -33,40 +46,30 @@ struct Navigator {
     filtered_apps: Vec<AppEntry>,
     favorites: Vec<AppEntry>,
     suggestions: Vec<AppEntry>,
-    maximized: bool,
-    context_menu: Option<ContextMenu>,
+    context_menu: Option<ContextMenu<Message>>,
+    /// Keyboard focus over [`Navigator::focus_order`]
+    focus: FocusRing,
     notification: Option<String>,
     last_mouse_pos: Point,
 }
 
-#[derive(Debug, Clone)]
-struct ContextMenu {
-    app: AppEntry,
-    position: Point,
-}
-
 #[derive(Debug, Clone)]
 enum Message {
     QueryChanged(String),
     LaunchApp(String),
-    WindowDragged,
-    Minimize,
-    Maximize,
-    Close,
+    Chrome(ChromeEvent),
     AddFavorite(AppEntry),
-    CloseContextMenu,
+    Menu(MenuEvent),
+    Focus(FocusEvent),
     UninstallApp(AppEntry),
     ClearNotification,
     ShowMoreSuggestions,
     MouseMoved(Point),
     RightClickApp(AppEntry),
-}
-
-/// Represents the source of an icon to render differently in the view
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum IconSource {
-    Svg(PathBuf),
-    Raster(PathBuf),
+    ThemeChanged,
+    IconsLoaded,
+    /// Launched again while running
+    Activated,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -74,7 +77,8 @@ struct AppEntry {
     name: String,
     exec: String,
     id: String,
-    icon: Option<IconSource>,
+    /// Icon key from the .desktop file, a theme name or a path
+    icon: Option<String>,
 }
 
 impl Navigator {
@@ -86,20 +90,43 @@ impl Navigator {
         let favorites = apps.iter().take(5).cloned().collect();
         let suggestions = apps.iter().skip(10).take(6).cloned().collect();
 
-        (
-            Self {
-                query: String::new(),
-                apps,
-                filtered_apps,
-                favorites,
-                suggestions,
-                maximized: false,
-                context_menu: None,
-                notification: None,
-                last_mouse_pos: Point::ORIGIN,
-            },
-            Task::none(),
-        )
+        let mut navigator = Self {
+            query: String::new(),
+            apps,
+            filtered_apps,
+            favorites,
+            suggestions,
+            context_menu: None,
+            focus: FocusRing::default(),
+            notification: None,
+            last_mouse_pos: Point::ORIGIN,
+        };
+        navigator.refresh_focus();
+
+        (navigator, Task::none())
+    }
+
+    /// Launchable entries in tab order: favorites, suggestions while they
+    /// are shown, then the app list
+    fn focus_order(&self) -> Vec<&AppEntry> {
+        let suggestions = if self.query.is_empty() { &self.suggestions[..] } else { &[] };
+        self.favorites
+            .iter()
+            .chain(suggestions)
+            .chain(&self.filtered_apps)
+            .collect()
+    }
+
+    fn refresh_focus(&mut self) {
+        let len = self.focus_order().len();
+        self.focus.set_len(len);
+    }
+
+    /// The entry the keyboard focus is on, if any
+    fn focused_app(&self) -> Option<&AppEntry> {
+        self.focus
+            .focused()
+            .and_then(|index| self.focus_order().get(index).copied())
     }
 
     fn title(&self) -> String {
@@ -107,7 +134,26 @@ impl Navigator {
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        theme::iced_theme()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::batch([
+            theme::subscription().map(|_| Message::ThemeChanged),
+            chrome::shortcuts().map(Message::Chrome),
+            icon::subscription().map(|_| Message::IconsLoaded),
+            instance::activations().map(|_| Message::Activated),
+            // The menu takes the arrow keys and Enter while it is open
+            if self.context_menu.is_some() {
+                menu::keyboard().map(Message::Menu)
+            } else {
+                focus::keyboard().map(Message::Focus)
+            },
+        ])
+    }
+
+    fn scale_factor(&self) -> f32 {
+        scale::factor()
     }
 
     fn style(&self, theme: &Theme) -> iced::theme::Style {
@@ -119,6 +165,9 @@ impl Navigator {
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
+            Message::ThemeChanged | Message::IconsLoaded => Task::none(),
+            // Minimized after launching an app, so bring it back
+            Message::Activated => instance::raise(),
             Message::QueryChanged(new_query) => {
                 self.query = new_query;
                 if self.query.is_empty() {
@@ -135,6 +184,11 @@ impl Navigator {
                     scored.sort_by(|a, b| b.0.cmp(&a.0));
                     self.filtered_apps = scored.into_iter().map(|(_, app)| app).collect();
                 }
+                self.refresh_focus();
+                // Enter launches the best match straight from the search field
+                let first_match = (!self.query.is_empty() && !self.filtered_apps.is_empty())
+                    .then_some(self.favorites.len());
+                self.focus.focus(first_match);
                 Task::none()
             }
             Message::LaunchApp(exec) => {
@@ -163,29 +217,39 @@ impl Navigator {
                 // Hide window instead of exiting to keep app alive
                 window::latest().and_then(|id| window::minimize(id, true))
             }
-            Message::WindowDragged => {
-                 window::latest().and_then(|id| window::drag(id))
-            }
-            Message::Minimize => {
-                window::latest().and_then(|id| window::minimize(id, true))
-            }
-            Message::Maximize => {
-                self.maximized = !self.maximized;
-                let maximized = self.maximized;
-                window::latest().and_then(move |id| window::maximize(id, maximized))
-            }
-            Message::Close => {
-                window::latest().and_then(|id| window::close(id))
+            Message::Chrome(event) => chrome::perform(event),
+            Message::Menu(event) => {
+                let Some(menu) = &mut self.context_menu else {
+                    return Task::none();
+                };
+                match menu.update(event) {
+                    MenuOutcome::Open => Task::none(),
+                    MenuOutcome::Selected(message) => {
+                        self.context_menu = None;
+                        self.update(message)
+                    }
+                    MenuOutcome::Dismissed => {
+                        self.context_menu = None;
+                        Task::none()
+                    }
+                }
             }
-            Message::CloseContextMenu => {
-                self.context_menu = None;
-                Task::none()
+            Message::Focus(event) => {
+                let launch = self
+                    .focus
+                    .update(event)
+                    .and_then(|index| self.focus_order().get(index).map(|app| app.exec.clone()));
+                match launch {
+                    Some(exec) => self.update(Message::LaunchApp(exec)),
+                    None => Task::none(),
+                }
             }
             Message::AddFavorite(app) => {
                 if !self.favorites.iter().any(|f| f.id == app.id) {
                     self.favorites.push(app);
                 }
                 self.context_menu = None;
+                self.refresh_focus();
                 Task::none()
             }
             Message::UninstallApp(app) => {
@@ -201,6 +265,7 @@ impl Navigator {
                 // Just add 50 more random apps to suggestions
                 let more: Vec<AppEntry> = self.apps.iter().skip(20).take(50).cloned().collect();
                 self.suggestions.extend(more);
+                self.refresh_focus();
                 Task::none()
             }
             Message::MouseMoved(p) => {
@@ -208,59 +273,38 @@ impl Navigator {
                 Task::none()
             }
             Message::RightClickApp(app) => {
-                self.context_menu = Some(ContextMenu { app, position: self.last_mouse_pos });
+                self.context_menu = Some(ContextMenu::new(self.last_mouse_pos, vec![
+                    MenuItem::action("Open", Message::LaunchApp(app.exec.clone())),
+                    MenuItem::action("Add to Favorites", Message::AddFavorite(app.clone())),
+                    MenuItem::separator(),
+                    MenuItem::action("Uninstall", Message::UninstallApp(app)),
+                ]));
                 Task::none()
             }
         }
     }
 
     fn view(&self) -> Element<'_, Message> {
+        let focused_app = self.focused_app();
+        let is_focused = |app: &AppEntry| focused_app.is_some_and(|focused| std::ptr::eq(focused, app));
+
         let logo_path = "crates/navigator/src/navigator-icon.svg";
         
-        let header = row![
-             // Buttons
-             row![
-                button(space().width(12).height(12))
-                    .on_press(Message::Close)
-                    .style(|theme, status| styles::window_control(theme, status, colors::CONTROL_CLOSE))
-                    .width(12).height(12),
-                button(space().width(12).height(12))
-                     .on_press(Message::Minimize)
-                     .style(|theme, status| styles::window_control(theme, status, colors::CONTROL_MIN))
-                     .width(12).height(12),
-                button(space().width(12).height(12))
-                     .on_press(Message::Maximize)
-                     .style(|theme, status| styles::window_control(theme, status, colors::CONTROL_MAX))
-                     .width(12).height(12),
-            ]
-            .spacing(8)
-            .padding(10),
-            
-            // Logo + Title
-            row![
-                svg(svg::Handle::from_path(logo_path)).width(20).height(20),
-                text("Navigator").size(14).color(colors::TEXT_SECONDARY),
-            ]
-            .spacing(10)
-            .width(Length::Fill)
-            .align_y(Alignment::Center),
+        let title = row![
+            svg(svg::Handle::from_path(logo_path)).width(20).height(20),
+            text("Navigator").size(14).color(colors::text_secondary()),
         ]
-        .height(40)
+        .spacing(10)
         .align_y(Alignment::Center);
 
         // Favorites Bar
         let favorites_bar = container(
             row(self.favorites.iter().map(|app| {
-                let icon: Element<Message> = match &app.icon {
-                    Some(IconSource::Svg(path)) => svg(svg::Handle::from_path(path)).width(32).height(32).into(),
-                    Some(IconSource::Raster(path)) => image(path).width(32).height(32).into(),
-                    None => text("📦").size(32).into(),
-                };
-                
-                button(icon)
+                let focused = is_focused(app);
+                button(icon(app.icon.as_deref().unwrap_or_default(), 32))
                     .on_press(Message::LaunchApp(app.exec.clone()))
                     .padding(8)
-                    .style(|theme, status| styles::app_card(theme, status))
+                    .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), focused))
                     .into()
             }))
             .spacing(15)
@@ -279,9 +323,9 @@ impl Navigator {
         let suggestions_section: Element<Message> = if self.query.is_empty() {
             column![
                 row![
-                    text("Suggestions").size(14).color(colors::TEXT_SECONDARY),
+                    text("Suggestions").size(14).color(colors::text_secondary()),
                     horizontal_space(),
-                    button(text("search more -> shows more").size(12).color(colors::ACCENT_PRIMARY))
+                    button(text("search more -> shows more").size(12).color(colors::accent_primary()))
                         .on_press(Message::ShowMoreSuggestions)
                         .style(|_, _| button::Style { background: None, ..Default::default() }),
                 ]
@@ -290,15 +334,10 @@ impl Navigator {
 
                 scrollable(
                     row(self.suggestions.iter().map(|app| {
-                        let icon: Element<Message> = match &app.icon {
-                            Some(IconSource::Svg(path)) => svg(svg::Handle::from_path(path)).width(40).height(40).into(),
-                            Some(IconSource::Raster(path)) => image(path).width(40).height(40).into(),
-                            None => text("📦").size(40).into(),
-                        };
-                        
+                        let focused = is_focused(app);
                         button(
                             column![
-                                icon,
+                                icon(app.icon.as_deref().unwrap_or_default(), 40),
                                 text(&app.name).size(12).color(Color::WHITE).width(60).align_x(Alignment::Center)
                             ]
                             .spacing(5)
@@ -306,7 +345,7 @@ impl Navigator {
                         )
                         .on_press(Message::LaunchApp(app.exec.clone()))
                         .padding(10)
-                        .style(|theme, status| styles::app_card(theme, status))
+                        .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), focused))
                         .into()
                     }))
                     .spacing(20)
@@ -321,11 +360,8 @@ impl Navigator {
         let content = self.filtered_apps.iter().fold(
             column![].spacing(10).width(Length::Fill),
             |column, app| {
-                let icon_widget: Element<Message> = match &app.icon {
-                    Some(IconSource::Svg(path)) => svg(svg::Handle::from_path(path)).width(32).height(32).into(),
-                    Some(IconSource::Raster(path)) => image(path).width(32).height(32).into(),
-                    None => text("📦").size(32).into(),
-                };
+                let icon_widget = icon(app.icon.as_deref().unwrap_or_default(), 32);
+                let focused = is_focused(app);
 
                 // Track position for context menu
                 let app_clone = app.clone();
@@ -341,7 +377,7 @@ impl Navigator {
                     .on_press(Message::LaunchApp(app.exec.clone()))
                     .width(Length::Fill)
                     .padding(12)
-                    .style(|theme, status| styles::app_card(theme, status))
+                    .style(move |theme, status| styles::with_focus_ring(styles::app_card(theme, status), focused))
                 )
                 .on_move(Message::MouseMoved)
                 .on_right_press(Message::RightClickApp(app_clone));
@@ -351,78 +387,22 @@ impl Navigator {
         );
 
         let main_content = column![
-            header,
             favorites_bar,
             input,
             suggestions_section,
             scrollable(content).height(Length::Fill)
         ]
-        .spacing(15)
-        .padding(20);
-
-        let mut layers = vec![
-            // Layer 1: Base Glass
-            container(space()).width(Length::Fill).height(Length::Fill).style(|theme| styles::glass_base(theme)).into(),
-            // Layer 2: Edge Highlights (Boxed Gloss)
-            container(space()).width(Length::Fill).height(Length::Fill).style(|theme| styles::glass_highlight_top(theme)).into(),
-            container(space()).width(Length::Fill).height(Length::Fill).style(|theme| styles::glass_highlight_bottom(theme)).into(),
-            container(space()).width(Length::Fill).height(Length::Fill).style(|theme| styles::glass_highlight_left(theme)).into(),
-            container(space()).width(Length::Fill).height(Length::Fill).style(|theme| styles::glass_highlight_right(theme)).into(),
-
-            // Layer 3: Global Drag Listener
-            mouse_area(container(space()).width(Length::Fill).height(Length::Fill))
-                .on_press(Message::WindowDragged).into(),
-
-            // Layer 4: Content
-            container(main_content).width(Length::Fill).height(Length::Fill).into(),
-        ];
-
-        // Layer 5: Context Menu
-        if let Some(menu) = &self.context_menu {
-            let menu_content = container(
-                column![
-                    button(text("Open").size(14))
-                        .on_press(Message::LaunchApp(menu.app.exec.clone()))
-                        .width(Length::Fill)
-                        .padding(10)
-                        .style(|theme, status| styles::app_card(theme, status)),
-                    button(text("Add to Favorites").size(14))
-                        .on_press(Message::AddFavorite(menu.app.clone()))
-                        .width(Length::Fill)
-                        .padding(10)
-                        .style(|theme, status| styles::app_card(theme, status)),
-                    button(text("Uninstall").size(14))
-                        .on_press(Message::UninstallApp(menu.app.clone()))
-                        .width(Length::Fill)
-                        .padding(10)
-                        .style(|theme, status| styles::app_card(theme, status)),
-                ]
-                .width(200)
-            )
-            .padding(5)
-            .style(|theme| styles::glass_base(theme));
+        .spacing(15);
 
-            layers.push(
-                mouse_area(
-                    container(
-                        container(menu_content)
-                            .padding(iced::Padding {
-                                top: menu.position.y.max(0.0),
-                                left: menu.position.x.max(0.0),
-                                right: 0.0,
-                                bottom: 0.0,
-                            })
-                    )
-                    .width(Length::Fill)
-                    .height(Length::Fill)
-                )
-                .on_press(Message::CloseContextMenu)
-                .on_right_press(Message::CloseContextMenu)
-                .into()
-            );
+        // Glass window, controls, drag listener and content
+        let mut layers = vec![chrome::window_frame(title, main_content, Message::Chrome)];
+
+        // Context Menu
+        if let Some(menu) = &self.context_menu {
+            layers.push(menu.view(Message::Menu));
         }
 
-        // Layer 6: Notification
+        // Notification
         if let Some(note) = &self.notification {
             layers.push(
                 container(
@@ -447,62 +427,6 @@ fn horizontal_space() -> Element<'static, Message> {
     space().width(Length::Fill).into()
 }
 
-/// Resolves an icon source from a .desktop Icon key.
-/// Follows the xfce4-panel fallback strategy:
-/// 1. Absolute path -> use directly
-/// 2. Icon theme lookup
-/// 3. Strip extension and try icon theme again
-/// 4. Look in /usr/share/pixmaps
-fn resolve_icon(icon_key: &str) -> Option<IconSource> {
-    let path = Path::new(icon_key);
-
-    // 1. Check if it's an absolute path
-    if path.is_absolute() && path.exists() {
-        return path_to_icon_source(path);
-    }
-
-    // 2. Try linicon (icon theme lookup)
-    if let Some(found) = linicon::lookup_icon(icon_key)
-        .with_size(32)
-        .next()
-        .and_then(|r| r.ok())
-    {
-        return path_to_icon_source(&found.path);
-    }
-
-    // 3. Strip extension and try icon theme again (e.g., "app.png" -> "app")
-    let name_without_ext = path.file_stem().and_then(|s| s.to_str()).unwrap_or(icon_key);
-    if name_without_ext != icon_key {
-        if let Some(found) = linicon::lookup_icon(name_without_ext)
-            .with_size(32)
-            .next()
-            .and_then(|r| r.ok())
-        {
-            return path_to_icon_source(&found.path);
-        }
-    }
-
-    // 4. Look in /usr/share/pixmaps
-    for ext in &["svg", "png", "xpm"] {
-        let pixmap_path = PathBuf::from(format!("/usr/share/pixmaps/{}.{}", icon_key, ext));
-        if pixmap_path.exists() {
-            return path_to_icon_source(&pixmap_path);
-        }
-    }
-
-    None
-}
-
-fn path_to_icon_source(path: &Path) -> Option<IconSource> {
-    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-    match ext.to_lowercase().as_str() {
-        "svg" => Some(IconSource::Svg(path.to_path_buf())),
-        "png" | "jpg" | "jpeg" | "xpm" => Some(IconSource::Raster(path.to_path_buf())),
-        _ => None,
-    }
-}
-
-
 fn scan_desktop_entries() -> Vec<AppEntry> {
     let mut entries = Vec::new();
     let data_dirs = std::env::var("XDG_DATA_DIRS")
@@ -540,7 +464,7 @@ fn scan_desktop_entries() -> Vec<AppEntry> {
                     .map(|s| s.to_string())
                     .unwrap_or_else(|| id.clone());
                 
-                let icon = desktop.icon().and_then(resolve_icon);
+                let icon = desktop.icon().map(str::to_string);
 
                 entries.push(AppEntry { name, exec, id, icon });
             }
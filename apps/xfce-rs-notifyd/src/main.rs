@@ -0,0 +1,54 @@
+//! Notification daemon for XFCE.rs
+//!
+//! Owns `org.freedesktop.Notifications` and shows each notification as a
+//! popup in the top right corner of the screen, like xfce4-notifyd. A
+//! click runs the action under the pointer, or the default one, and
+//! otherwise dismisses the popup.
+//!
+//! Also serves the XFCE.rs IPC service when no other component does, so
+//! desktop notifications sent through it show up as popups too.
+
+use tracing::{error, warn};
+use xfce_rs_ipc::notifications::CloseReason;
+use xfce_rs_ipc::{NotificationDaemon, XfceIpcService};
+
+mod popup;
+
+use popup::{PopupEvent, PopupRenderer};
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let (renderer, mut events) = match PopupRenderer::connect() {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            error!("Cannot show notifications: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let daemon = NotificationDaemon::new(renderer);
+    if let Err(e) = daemon.start().await {
+        error!("Failed to start notification daemon: {}", e);
+        std::process::exit(1);
+    }
+
+    let ipc = XfceIpcService::new();
+    ipc.add_handler(daemon.ipc_handler()).await;
+    if let Err(e) = ipc.start().await {
+        warn!("Not showing notifications sent through the XFCE.rs IPC service: {}", e);
+    }
+
+    while let Some(event) = events.recv().await {
+        match event {
+            PopupEvent::Action(id, key) => daemon.invoke_action(id, &key),
+            PopupEvent::Dismissed(id) => {
+                daemon.close(id, CloseReason::Dismissed);
+            }
+        }
+    }
+    error!("Lost the connection to the X server");
+    std::process::exit(1);
+}
@@ -0,0 +1,425 @@
+//! Notification popups drawn with core X11 requests
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ChangeGCAux, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, CreateGCAux, CreateWindowAux,
+    EventMask, Gcontext, Rectangle, Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use xfce_rs_ipc::notifications::Urgency;
+use xfce_rs_ipc::{Notification, NotificationRenderer};
+
+const WIDTH: u16 = 340;
+/// Gap to the screen edges and between popups
+const MARGIN: i16 = 12;
+const PADDING: u16 = 10;
+const MAX_BODY_LINES: usize = 4;
+/// Space around an action button's label
+const BUTTON_PADDING: u16 = 6;
+/// Width of the X border drawn around each popup
+const BORDER_WIDTH: u16 = 1;
+
+const BACKGROUND: u32 = 0x2b2b2b;
+const BUTTON: u32 = 0x3c3c3c;
+const TEXT: u32 = 0xe0e0e0;
+const BORDER: u32 = 0x4a6a94;
+const CRITICAL_BORDER: u32 = 0xc0392b;
+
+/// What the user did with a popup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PopupEvent {
+    /// Picked the action with this key
+    Action(u32, String),
+    Dismissed(u32),
+}
+
+/// Core font the popups are drawn in
+struct TextFont {
+    id: u32,
+    char_width: u16,
+    ascent: u16,
+    line_height: u16,
+}
+
+/// An action button, in popup coordinates
+struct Button {
+    area: Rectangle,
+    key: String,
+    label: String,
+}
+
+/// What a popup shows, and where
+struct Layout {
+    /// The summary, then the body
+    lines: Vec<String>,
+    buttons: Vec<Button>,
+    height: u16,
+}
+
+struct Popup {
+    window: Window,
+    layout: Layout,
+    /// Clicking anywhere but a button invokes the "default" action
+    has_default: bool,
+}
+
+struct Shared {
+    conn: RustConnection,
+    root: Window,
+    screen_width: u16,
+    gc: Gcontext,
+    font: TextFont,
+    /// Open popups by notification id, so the oldest is on top
+    popups: Mutex<BTreeMap<u32, Popup>>,
+}
+
+/// [`NotificationRenderer`] stacking popups in the top right corner of
+/// the X screen
+pub struct PopupRenderer {
+    shared: Arc<Shared>,
+}
+
+impl PopupRenderer {
+    /// Connect to the X server. What the user does with popups arrives on
+    /// the returned receiver, which closes if the connection is lost.
+    pub fn connect() -> Result<(Self, mpsc::UnboundedReceiver<PopupEvent>)> {
+        let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X server")?;
+        let screen = &conn.setup().roots[screen_num];
+        let (root, screen_width) = (screen.root, screen.width_in_pixels);
+        let font = open_font(&conn, b"10x20")
+            .or_else(|| open_font(&conn, b"fixed"))
+            .context("X server has neither the 10x20 nor the fixed font")?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, root, &CreateGCAux::new().font(font.id))?;
+        conn.flush()?;
+
+        let shared = Arc::new(Shared {
+            conn,
+            root,
+            screen_width,
+            gc,
+            font,
+            popups: Mutex::default(),
+        });
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let events = shared.clone();
+        std::thread::spawn(move || events.run(sender));
+        Ok((Self { shared }, receiver))
+    }
+}
+
+impl NotificationRenderer for PopupRenderer {
+    fn show(&self, notification: &Notification) {
+        if let Err(e) = self.shared.show(notification) {
+            warn!("Failed to show notification {}: {:#}", notification.id, e);
+        }
+    }
+
+    fn close(&self, id: u32) {
+        self.shared.close(id);
+    }
+}
+
+impl Shared {
+    fn popups(&self) -> MutexGuard<'_, BTreeMap<u32, Popup>> {
+        self.popups.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn show(&self, notification: &Notification) -> Result<()> {
+        let mut popups = self.popups();
+        let layout = layout(notification, self.font.char_width, self.font.line_height);
+        let border = if notification.urgency == Urgency::Critical { CRITICAL_BORDER } else { BORDER };
+        let (window, update) = match popups.get(&notification.id) {
+            Some(popup) => {
+                self.conn
+                    .change_window_attributes(popup.window, &ChangeWindowAttributesAux::new().border_pixel(border))?;
+                (popup.window, true)
+            }
+            None => {
+                let window = self.conn.generate_id()?;
+                self.conn.create_window(
+                    x11rb::COPY_DEPTH_FROM_PARENT,
+                    window,
+                    self.root,
+                    0,
+                    0,
+                    WIDTH,
+                    layout.height,
+                    BORDER_WIDTH,
+                    WindowClass::INPUT_OUTPUT,
+                    x11rb::COPY_FROM_PARENT,
+                    &CreateWindowAux::new()
+                        .override_redirect(1)
+                        .background_pixel(BACKGROUND)
+                        .border_pixel(border)
+                        .event_mask(EventMask::EXPOSURE | EventMask::BUTTON_PRESS),
+                )?;
+                (window, false)
+            }
+        };
+
+        let has_default = notification.actions.iter().any(|(key, _)| key == "default");
+        popups.insert(notification.id, Popup { window, layout, has_default });
+        self.restack(&popups)?;
+        if update {
+            // New windows are drawn once exposed
+            self.draw(&popups[&notification.id])?;
+        } else {
+            self.conn.map_window(window)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn close(&self, id: u32) {
+        let mut popups = self.popups();
+        if let Some(popup) = popups.remove(&id) {
+            let _ = self.conn.destroy_window(popup.window);
+            if let Err(e) = self.restack(&popups).and_then(|_| Ok(self.conn.flush()?)) {
+                debug!("Failed to move popups up: {}", e);
+            }
+        }
+    }
+
+    /// Line the popups up below each other, oldest first
+    fn restack(&self, popups: &BTreeMap<u32, Popup>) -> Result<()> {
+        let outer_width = (WIDTH + 2 * BORDER_WIDTH) as i16;
+        let x = self.screen_width as i16 - outer_width - MARGIN;
+        let mut y = MARGIN;
+        for popup in popups.values() {
+            self.conn.configure_window(
+                popup.window,
+                &ConfigureWindowAux::new()
+                    .x(x as i32)
+                    .y(y as i32)
+                    .height(popup.layout.height as u32),
+            )?;
+            y += (popup.layout.height + 2 * BORDER_WIDTH) as i16 + MARGIN;
+        }
+        Ok(())
+    }
+
+    fn draw(&self, popup: &Popup) -> Result<()> {
+        let (conn, gc, window) = (&self.conn, self.gc, popup.window);
+        let layout = &popup.layout;
+        conn.change_gc(gc, &ChangeGCAux::new().foreground(BACKGROUND))?;
+        conn.poly_fill_rectangle(window, gc, &[Rectangle { x: 0, y: 0, width: WIDTH, height: layout.height }])?;
+
+        conn.change_gc(gc, &ChangeGCAux::new().foreground(TEXT).background(BACKGROUND))?;
+        let mut y = PADDING + self.font.ascent;
+        for line in &layout.lines {
+            conn.image_text8(window, gc, PADDING as i16, y as i16, &latin1(line))?;
+            y += self.font.line_height;
+        }
+
+        for button in &layout.buttons {
+            conn.change_gc(gc, &ChangeGCAux::new().foreground(BUTTON))?;
+            conn.poly_fill_rectangle(window, gc, &[button.area])?;
+            conn.change_gc(gc, &ChangeGCAux::new().foreground(TEXT).background(BUTTON))?;
+            let text_x = button.area.x + BUTTON_PADDING as i16;
+            let text_y = button.area.y + (BUTTON_PADDING / 2 + self.font.ascent) as i16;
+            conn.image_text8(window, gc, text_x, text_y, &latin1(&button.label))?;
+        }
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// Draw popups as they are exposed and report clicks, until the
+    /// connection or the receiver goes away
+    fn run(&self, events: mpsc::UnboundedSender<PopupEvent>) {
+        loop {
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    debug!("X connection closed: {}", e);
+                    return;
+                }
+            };
+
+            let event = match event {
+                Event::Expose(event) if event.count == 0 => {
+                    let popups = self.popups();
+                    if let Some(popup) = popups.values().find(|popup| popup.window == event.window) {
+                        if let Err(e) = self.draw(popup) {
+                            debug!("Failed to draw notification: {}", e);
+                        }
+                    }
+                    continue;
+                }
+                Event::ButtonPress(event) => {
+                    let popups = self.popups();
+                    let Some((&id, popup)) = popups.iter().find(|(_, popup)| popup.window == event.event) else {
+                        continue;
+                    };
+                    let (x, y) = (event.event_x, event.event_y);
+                    let action = popup
+                        .layout
+                        .buttons
+                        .iter()
+                        .find(|button| contains(&button.area, x, y))
+                        .map(|button| button.key.clone())
+                        .or_else(|| popup.has_default.then(|| "default".to_string()))
+                        .filter(|_| event.detail == 1);
+                    match action {
+                        Some(key) => PopupEvent::Action(id, key),
+                        None => PopupEvent::Dismissed(id),
+                    }
+                }
+                _ => continue,
+            };
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Open the core font `name`, `None` if the server lacks it. Only a
+/// checked request tells: an unchecked one's error arrives as an event.
+fn open_font(conn: &RustConnection, name: &[u8]) -> Option<TextFont> {
+    let id = conn.generate_id().ok()?;
+    conn.open_font(id, name).ok()?.check().ok()?;
+    let info = conn.query_font(id).ok()?.reply().ok()?;
+    Some(TextFont {
+        id,
+        char_width: info.max_bounds.character_width.max(1) as u16,
+        ascent: info.font_ascent.max(0) as u16,
+        line_height: (info.font_ascent + info.font_descent).max(1) as u16,
+    })
+}
+
+/// Lay out the summary on one line, the body wrapped below it and a row
+/// of buttons for the actions other than "default"
+fn layout(notification: &Notification, char_width: u16, line_height: u16) -> Layout {
+    let inner_width = WIDTH - 2 * PADDING;
+    let columns = (inner_width / char_width) as usize;
+    let mut lines = wrap(&notification.summary, columns, 1);
+    lines.extend(wrap(&notification.body, columns, MAX_BODY_LINES));
+    let text_height = lines.len() as u16 * line_height;
+
+    let mut buttons = Vec::new();
+    let button_y = (PADDING + text_height + PADDING) as i16;
+    let mut x = PADDING;
+    for (key, label) in notification.actions.iter().filter(|(key, _)| key != "default") {
+        let width = label.chars().count() as u16 * char_width + 2 * BUTTON_PADDING;
+        if x + width > PADDING + inner_width {
+            break;
+        }
+        buttons.push(Button {
+            area: Rectangle { x: x as i16, y: button_y, width, height: line_height + BUTTON_PADDING },
+            key: key.clone(),
+            label: label.clone(),
+        });
+        x += width + PADDING;
+    }
+
+    let buttons_height = if buttons.is_empty() { 0 } else { line_height + BUTTON_PADDING + PADDING };
+    Layout {
+        lines,
+        buttons,
+        height: PADDING + text_height + buttons_height + PADDING,
+    }
+}
+
+/// Break `text` into lines of at most `columns` characters at spaces,
+/// splitting words longer than a line. Past `max_lines` the last line
+/// kept ends in "...".
+fn wrap(text: &str, columns: usize, max_lines: usize) -> Vec<String> {
+    let columns = columns.max(4);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut len = 0;
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+            loop {
+                let word_len = word.chars().count();
+                let needed = if len == 0 { word_len } else { len + 1 + word_len };
+                if needed <= columns {
+                    if len > 0 {
+                        line.push(' ');
+                    }
+                    line.push_str(word);
+                    len = needed;
+                    break;
+                }
+                if len > 0 {
+                    lines.push(std::mem::take(&mut line));
+                    len = 0;
+                    continue;
+                }
+                let split = word.char_indices().nth(columns).map_or(word.len(), |(i, _)| i);
+                lines.push(word[..split].to_string());
+                word = &word[split..];
+            }
+        }
+        if len > 0 {
+            lines.push(line);
+        }
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            let kept: String = last.chars().take(columns - 3).collect();
+            *last = kept + "...";
+        }
+    }
+    lines
+}
+
+/// `text` in the Latin-1 encoding of core fonts, other characters as '?'
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?')).collect()
+}
+
+fn contains(area: &Rectangle, x: i16, y: i16) -> bool {
+    x >= area.x && y >= area.y && x < area.x + area.width as i16 && y < area.y + area.height as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("Battery low", 20, 4), ["Battery low"]);
+        assert_eq!(wrap("one two three four", 9, 4), ["one two", "three", "four"]);
+        assert_eq!(wrap("first\n\nsecond", 20, 4), ["first", "second"]);
+        assert_eq!(wrap("abcdefghij", 4, 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("one two three four", 9, 2), ["one two", "three..."]);
+        assert_eq!(wrap("Dateien über Größe", 8, 4), ["Dateien", "über", "Größe"]);
+        assert!(wrap("", 20, 4).is_empty());
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut notification = Notification::new("firefox", "Download complete", "report.pdf");
+        let plain = layout(&notification, 10, 20);
+        assert_eq!(plain.lines, ["Download complete", "report.pdf"]);
+        assert!(plain.buttons.is_empty());
+        assert_eq!(plain.height, PADDING + 40 + PADDING);
+
+        notification.actions = vec![
+            ("default".to_string(), "Open".to_string()),
+            ("open-folder".to_string(), "Show in folder".to_string()),
+        ];
+        let with_actions = layout(&notification, 10, 20);
+        assert_eq!(with_actions.buttons.len(), 1);
+        assert_eq!(with_actions.buttons[0].key, "open-folder");
+        assert_eq!(with_actions.buttons[0].area.y, (PADDING + 40 + PADDING) as i16);
+        assert!(with_actions.height > plain.height);
+    }
+
+    #[test]
+    fn test_latin1() {
+        assert_eq!(latin1("Größe €5"), b"Gr\xf6\xdfe ?5");
+    }
+}
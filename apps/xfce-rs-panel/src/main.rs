@@ -1,7 +1,9 @@
-use iced::widget::{container, row, mouse_area, button, text, column};
+use iced::widget::{container, row, mouse_area};
 use iced::{Alignment, Element, Length, Task, Theme, Point};
 use tracing::{info, warn};
-use xfce_rs_ui::styles;
+use xfce_rs_ui::menu::{ContextMenu, MenuEvent, MenuItem, MenuOutcome};
+use xfce_rs_ui::{fonts, scale, styles, theme};
+use xfce_rs_config::XfceConfig;
 
 mod plugin_manager;
 mod plugin_slot;
@@ -19,11 +21,22 @@ pub fn main() -> iced::Result {
         .init();
     
     info!("XFCE.rs Panel starting");
+
+    // The window is sized for the interface scale before it opens
+    match tokio::runtime::Runtime::new() {
+        Ok(runtime) => {
+            runtime.block_on(scale::apply_from_config(&XfceConfig::default()));
+        }
+        Err(e) => warn!("Failed to read the interface scale: {}", e),
+    }
     
+    fonts::load();
     iced::application(PanelApp::new, PanelApp::update, PanelApp::view)
+        .settings(fonts::settings())
         .title(PanelApp::title)
         .theme(PanelApp::theme)
         .style(PanelApp::style)
+        .scale_factor(PanelApp::scale_factor)
         .window({
             let settings = PanelSettings::load();
             let (width, height) = settings.get_window_size(1920.0, 1080.0);
@@ -40,12 +53,18 @@ pub fn main() -> iced::Result {
         .subscription(|app: &PanelApp| {
             // Only poll for settings changes if settings panel is not open
             // (to avoid conflicts with live editing)
-            if !app.show_settings {
+            let reload = if !app.show_settings {
                 iced::time::every(std::time::Duration::from_secs(2))
                     .map(|_| Message::ReloadSettings)
             } else {
                 iced::Subscription::none()
-            }
+            };
+            let menu_keys = if app.context_menu.is_some() {
+                xfce_rs_ui::menu::keyboard().map(Message::Menu)
+            } else {
+                iced::Subscription::none()
+            };
+            iced::Subscription::batch([reload, menu_keys, theme::subscription().map(Message::ThemeChanged)])
         })
         .run()
 }
@@ -54,17 +73,12 @@ struct PanelApp {
     plugin_manager: PluginManager,
     plugins: Vec<PluginSlot>,
     settings: PanelSettings,
-    context_menu: Option<ContextMenu>,
+    context_menu: Option<ContextMenu<Message>>,
     mouse_pos: Point,
     show_settings: bool,
     settings_app: Option<SettingsApp>,
 }
 
-#[derive(Debug, Clone)]
-struct ContextMenu {
-    position: Point,
-}
-
 #[derive(Debug, Clone)]
 enum Message {
     #[allow(dead_code)] // Will be used for future plugin management
@@ -73,12 +87,13 @@ enum Message {
     PluginUnloaded(String),
     Refresh,
     RightClick(Point),
-    CloseContextMenu,
+    Menu(MenuEvent),
     OpenSettings,
     CloseSettings,
     SettingsChanged(settings_app::Message),
     MouseMoved(Point),
     ReloadSettings,
+    ThemeChanged(String),
 }
 
 impl PanelApp {
@@ -115,11 +130,11 @@ impl PanelApp {
     }
 
     fn theme(&self) -> Theme {
-        if self.settings.dark_mode {
-            Theme::Dark
-        } else {
-            Theme::Light
-        }
+        theme::iced_theme()
+    }
+
+    fn scale_factor(&self) -> f32 {
+        scale::factor()
     }
 
     fn style(&self, theme: &Theme) -> iced::theme::Style {
@@ -132,19 +147,33 @@ impl PanelApp {
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::RightClick(pos) => {
-                self.context_menu = Some(ContextMenu { position: pos });
+                self.context_menu = Some(ContextMenu::new(pos, vec![
+                    MenuItem::action("Settings", Message::OpenSettings),
+                ]));
                 Task::none()
             }
-            Message::CloseContextMenu => {
-                self.context_menu = None;
-                Task::none()
+            Message::Menu(event) => {
+                let Some(menu) = &mut self.context_menu else {
+                    return Task::none();
+                };
+                match menu.update(event) {
+                    MenuOutcome::Open => Task::none(),
+                    MenuOutcome::Selected(message) => {
+                        self.context_menu = None;
+                        self.update(message)
+                    }
+                    MenuOutcome::Dismissed => {
+                        self.context_menu = None;
+                        Task::none()
+                    }
+                }
             }
             Message::OpenSettings => {
                 self.context_menu = None;
                 self.show_settings = true;
-                let (settings_app, _) = SettingsApp::new(self.settings.clone());
+                let (settings_app, task) = SettingsApp::new(self.settings.clone());
                 self.settings_app = Some(settings_app);
-                Task::none()
+                task.map(Message::SettingsChanged)
             }
             Message::CloseSettings => {
                 self.show_settings = false;
@@ -162,10 +191,10 @@ impl PanelApp {
                 Task::none()
             }
             Message::SettingsChanged(msg) => {
-                if let Some(ref mut settings_app) = self.settings_app {
-                    let _ = settings_app.update(msg);
+                match self.settings_app {
+                    Some(ref mut settings_app) => settings_app.update(msg).map(Message::SettingsChanged),
+                    None => Task::none(),
                 }
-                Task::none()
             }
             Message::ReloadSettings => {
                 // Check if settings file changed
@@ -173,9 +202,9 @@ impl PanelApp {
                 let size_changed = new_settings.size != self.settings.size;
                 let position_changed = new_settings.position != self.settings.position;
                 let mode_changed = new_settings.mode != self.settings.mode;
-                let dark_mode_changed = new_settings.dark_mode != self.settings.dark_mode;
+                let mut load_themes = Task::none();
                 
-                if size_changed || position_changed || mode_changed || dark_mode_changed {
+                if size_changed || position_changed || mode_changed {
                     info!("Settings changed, applying: size={}, position={:?}, mode={:?}", 
                         new_settings.size, new_settings.position, new_settings.mode);
                     
@@ -183,25 +212,22 @@ impl PanelApp {
                     
                     // Update settings app if it's open
                     if let Some(ref mut settings_app) = self.settings_app {
-                        let (new_app, _) = SettingsApp::new(self.settings.clone());
+                        let (new_app, task) = SettingsApp::new(self.settings.clone());
                         *settings_app = new_app;
+                        load_themes = task.map(Message::SettingsChanged);
                     }
                     
                     // Apply window size/position changes
                     // Note: Window size/position changes require restarting the panel
                     // This is common in desktop environments (xfce4-panel also requires restart for some changes)
-                    if size_changed || position_changed || mode_changed {
-                        let (width, height) = self.settings.get_window_size(1920.0, 1080.0);
-                        let (x, y) = self.settings.get_window_position(1920.0, 1080.0);
-                        info!("Window settings changed - size: {}x{}, position: ({}, {}). Restart panel to apply.", width, height, x, y);
-                    }
-                    
-                    // Apply theme change
-                    if dark_mode_changed {
-                        info!("Dark mode changed to {}", self.settings.dark_mode);
-                        // Theme is applied via the theme() method, which is called on each render
-                    }
+                    let (width, height) = self.settings.get_window_size(1920.0, 1080.0);
+                    let (x, y) = self.settings.get_window_position(1920.0, 1080.0);
+                    info!("Window settings changed - size: {}x{}, position: ({}, {}). Restart panel to apply.", width, height, x, y);
                 }
+                load_themes
+            }
+            Message::ThemeChanged(name) => {
+                info!("Theme changed to {}", name);
                 Task::none()
             }
             Message::MouseMoved(pos) => {
@@ -279,43 +305,7 @@ impl PanelApp {
         
         // Context menu layer
         if let Some(menu) = &self.context_menu {
-            let menu_content = container(
-                column![
-                    button(text("Settings").size(14))
-                        .on_press(Message::OpenSettings)
-                        .width(Length::Fill)
-                        .padding(10)
-                        .style(|theme, status| styles::app_card(theme, status)),
-                    button(text("Close").size(14))
-                        .on_press(Message::CloseContextMenu)
-                        .width(Length::Fill)
-                        .padding(10)
-                        .style(|theme, status| styles::app_card(theme, status)),
-                ]
-                .spacing(5)
-            )
-            .width(150)
-            .padding(5)
-            .style(|theme| styles::glass_base(theme));
-
-            layers.push(
-                mouse_area(
-                    container(
-                        container(menu_content)
-                            .padding(iced::Padding {
-                                top: menu.position.y.max(0.0),
-                                left: menu.position.x.max(0.0),
-                                right: 0.0,
-                                bottom: 0.0,
-                            })
-                    )
-                    .width(Length::Fill)
-                    .height(Length::Fill)
-                )
-                .on_press(Message::CloseContextMenu)
-                .on_right_press(Message::CloseContextMenu)
-                .into()
-            );
+            layers.push(menu.view(Message::Menu));
         }
 
         // Settings overlay layer
@@ -28,7 +28,7 @@ impl PluginSlot {
             container(
                 text(&self.plugin.name)
                     .size(12)
-                    .color(if self.is_running { colors::ACCENT_PRIMARY } else { colors::TEXT_SECONDARY })
+                    .color(if self.is_running { colors::accent_primary() } else { colors::text_secondary() })
             )
             .width(Length::Shrink)
             .height(Length::Fill)
@@ -41,7 +41,7 @@ impl PluginSlot {
             container(
                 text(&self.plugin.description)
                     .size(12)
-                    .color(colors::TEXT_PRIMARY)
+                    .color(colors::text_primary())
             )
             .width(Length::Shrink)
             .height(Length::Fill)
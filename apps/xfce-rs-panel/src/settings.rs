@@ -1,13 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use dirs;
+use xfce_rs_ui::scale;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PanelSettings {
     // Panel appearance
     pub size: u32,              // Panel height/width (16-128)
     pub icon_size: u32,         // Icon size (0-256, 0 = auto)
-    pub dark_mode: bool,        // Dark mode
     
     // Panel position
     pub position: PanelPosition,
@@ -86,7 +86,6 @@ impl Default for PanelSettings {
         Self {
             size: 48,
             icon_size: 0,  // Auto
-            dark_mode: false,
             position: PanelPosition::Bottom,
             position_locked: false,
             span_monitors: false,
@@ -133,15 +132,18 @@ impl PanelSettings {
         Ok(())
     }
 
+    /// Window size with the thickness grown by the interface scale, which
+    /// iced applies to everything drawn inside
     pub fn get_window_size(&self, screen_width: f32, screen_height: f32) -> (f32, f32) {
+        let thickness = self.size as f32 * scale::factor();
         match self.mode {
             PanelMode::Horizontal => {
                 let width = self.length.unwrap_or(screen_width as u32) as f32;
-                let height = self.size as f32;
+                let height = thickness;
                 (width, height)
             }
             PanelMode::Vertical => {
-                let width = self.size as f32;
+                let width = thickness;
                 let height = self.length.unwrap_or(screen_height as u32) as f32;
                 (width, height)
             }
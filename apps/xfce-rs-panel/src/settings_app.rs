@@ -1,31 +1,36 @@
 use iced::widget::{
     column, container, row, text, button, slider, pick_list, space,
 };
-use iced::widget::checkbox;
 use iced::{Alignment, Element, Length, Task};
+use xfce_rs_ui::form::{form_row, spin_button, toggle_switch};
 use xfce_rs_ui::styles;
 use xfce_rs_ui::colors;
+use xfce_rs_ui::theme;
 
 use crate::settings::{PanelSettings, PanelPosition, PanelMode, AutohideBehavior};
 
 pub struct SettingsApp {
     settings: PanelSettings,
     saved: bool,
+    /// Themes to pick from, loaded from the theme channel
+    themes: Vec<String>,
+    theme: String,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     SizeChanged(f32),
     IconSizeChanged(f32),
-    DarkModeToggled(bool),
+    ThemesLoaded(Vec<String>),
+    ThemeSelected(String),
     PositionChanged(PanelPosition),
     PositionLockedToggled(bool),
     SpanMonitorsToggled(bool),
     AutohideChanged(AutohideBehavior),
-    AutohideSizeChanged(f32),
+    AutohideSizeChanged(u32),
     PopdownSpeedChanged(f32),
     ModeChanged(PanelMode),
-    NRowsChanged(f32),
+    NRowsChanged(u32),
     EnableStrutsToggled(bool),
     KeepBelowToggled(bool),
     Save,
@@ -38,8 +43,10 @@ impl SettingsApp {
             Self {
                 settings,
                 saved: false,
+                themes: Vec::new(),
+                theme: theme::current_name(),
             },
-            Task::none(),
+            Task::perform(theme::available(), Message::ThemesLoaded),
         )
     }
 
@@ -55,11 +62,21 @@ impl SettingsApp {
                 self.saved = false;
                 Task::none()
             }
-            Message::DarkModeToggled(val) => {
-                self.settings.dark_mode = val;
-                self.saved = false;
+            Message::ThemesLoaded(themes) => {
+                self.themes = themes;
                 Task::none()
             }
+            Message::ThemeSelected(name) => {
+                // Shared by every app, so it applies right away rather
+                // than on Save
+                self.theme = name.clone();
+                Task::perform(theme::select(name), |result| {
+                    if let Err(e) = result {
+                        tracing::error!("Failed to save theme: {}", e);
+                    }
+                })
+                .discard()
+            }
             Message::PositionChanged(pos) => {
                 self.settings.position = pos;
                 self.saved = false;
@@ -81,7 +98,7 @@ impl SettingsApp {
                 Task::none()
             }
             Message::AutohideSizeChanged(val) => {
-                self.settings.autohide_size = val as u32;
+                self.settings.autohide_size = val;
                 self.saved = false;
                 Task::none()
             }
@@ -96,7 +113,7 @@ impl SettingsApp {
                 Task::none()
             }
             Message::NRowsChanged(val) => {
-                self.settings.nrows = val as u32;
+                self.settings.nrows = val;
                 self.saved = false;
                 Task::none()
             }
@@ -130,10 +147,10 @@ impl SettingsApp {
 
     pub fn view(&self) -> Element<'_, Message> {
         let header = row![
-            text("Panel Settings").size(24).color(colors::TEXT_PRIMARY),
+            text("Panel Settings").size(24).color(colors::text_primary()),
             space().width(Length::Fill),
             if self.saved {
-                text("✓ Saved").size(14).color(colors::ACCENT_PRIMARY)
+                text("✓ Saved").size(14).color(colors::accent_primary())
             } else {
                 text("").size(14)
             },
@@ -180,34 +197,37 @@ impl SettingsApp {
     fn view_appearance_section(&self) -> Element<'_, Message> {
         container(
             column![
-                text("Appearance").size(18).color(colors::TEXT_PRIMARY),
+                text("Appearance").size(18).color(colors::text_primary()),
                 row![
-                    text("Panel Size:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    slider(16.0..=128.0, self.settings.size as f32, Message::SizeChanged)
-                        .width(200),
-                    text(format!("{}px", self.settings.size)).size(12).color(colors::TEXT_SECONDARY).width(60),
+                    text("Theme:").size(14).color(colors::text_secondary()).width(150),
+                    pick_list(
+                        self.themes.as_slice(),
+                        Some(self.theme.clone()),
+                        Message::ThemeSelected,
+                    )
+                    .width(200),
                 ]
                 .spacing(10)
                 .align_y(Alignment::Center),
                 row![
-                    text("Icon Size:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    slider(0.0..=256.0, self.settings.icon_size as f32, Message::IconSizeChanged)
+                    text("Panel Size:").size(14).color(colors::text_secondary()).width(150),
+                    slider(16.0..=128.0, self.settings.size as f32, Message::SizeChanged)
                         .width(200),
-                    text(if self.settings.icon_size == 0 { "Auto".to_string() } else { format!("{}px", self.settings.icon_size) })
-                        .size(12).color(colors::TEXT_SECONDARY).width(60),
+                    text(format!("{}px", self.settings.size)).size(12).color(colors::text_secondary()).width(60),
                 ]
                 .spacing(10)
                 .align_y(Alignment::Center),
                 row![
-                    text("Dark Mode:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    checkbox(self.settings.dark_mode)
-                        .label("Dark Mode")
-                        .on_toggle(Message::DarkModeToggled),
+                    text("Icon Size:").size(14).color(colors::text_secondary()).width(150),
+                    slider(0.0..=256.0, self.settings.icon_size as f32, Message::IconSizeChanged)
+                        .width(200),
+                    text(if self.settings.icon_size == 0 { "Auto".to_string() } else { format!("{}px", self.settings.icon_size) })
+                        .size(12).color(colors::text_secondary()).width(60),
                 ]
                 .spacing(10)
                 .align_y(Alignment::Center),
                 row![
-                    text("Mode:").size(14).color(colors::TEXT_SECONDARY).width(150),
+                    text("Mode:").size(14).color(colors::text_secondary()).width(150),
                     pick_list(
                         vec![PanelMode::Horizontal, PanelMode::Vertical],
                         Some(self.settings.mode),
@@ -217,15 +237,7 @@ impl SettingsApp {
                 ]
                 .spacing(10)
                 .align_y(Alignment::Center),
-                row![
-                    text("Rows:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    slider(1.0..=6.0, self.settings.nrows as f32, Message::NRowsChanged)
-                        .width(200)
-                        .step(1.0),
-                    text(format!("{}", self.settings.nrows)).size(12).color(colors::TEXT_SECONDARY).width(60),
-                ]
-                .spacing(10)
-                .align_y(Alignment::Center),
+                form_row("Rows:", spin_button(self.settings.nrows, 1..=6, 1, "", Message::NRowsChanged)),
             ]
             .spacing(15)
         )
@@ -237,9 +249,9 @@ impl SettingsApp {
     fn view_position_section(&self) -> Element<'_, Message> {
         container(
             column![
-                text("Position").size(18).color(colors::TEXT_PRIMARY),
+                text("Position").size(18).color(colors::text_primary()),
                 row![
-                    text("Position:").size(14).color(colors::TEXT_SECONDARY).width(150),
+                    text("Position:").size(14).color(colors::text_secondary()).width(150),
                     pick_list(
                         vec![PanelPosition::Top, PanelPosition::Bottom, PanelPosition::Left, PanelPosition::Right],
                         Some(self.settings.position),
@@ -249,22 +261,8 @@ impl SettingsApp {
                 ]
                 .spacing(10)
                 .align_y(Alignment::Center),
-                row![
-                    text("Lock Position:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    checkbox(self.settings.position_locked)
-                        .label("Lock Position")
-                        .on_toggle(Message::PositionLockedToggled),
-                ]
-                .spacing(10)
-                .align_y(Alignment::Center),
-                row![
-                    text("Span Monitors:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    checkbox(self.settings.span_monitors)
-                        .label("Span Monitors")
-                        .on_toggle(Message::SpanMonitorsToggled),
-                ]
-                .spacing(10)
-                .align_y(Alignment::Center),
+                form_row("Lock Position:", toggle_switch(self.settings.position_locked, Message::PositionLockedToggled)),
+                form_row("Span Monitors:", toggle_switch(self.settings.span_monitors, Message::SpanMonitorsToggled)),
             ]
             .spacing(15)
         )
@@ -276,9 +274,9 @@ impl SettingsApp {
     fn view_behavior_section(&self) -> Element<'_, Message> {
         container(
             column![
-                text("Behavior").size(18).color(colors::TEXT_PRIMARY),
+                text("Behavior").size(18).color(colors::text_primary()),
                 row![
-                    text("Autohide:").size(14).color(colors::TEXT_SECONDARY).width(150),
+                    text("Autohide:").size(14).color(colors::text_secondary()).width(150),
                     pick_list(
                         vec![AutohideBehavior::Never, AutohideBehavior::Intelligently, AutohideBehavior::Always],
                         Some(self.settings.autohide),
@@ -288,20 +286,15 @@ impl SettingsApp {
                 ]
                 .spacing(10)
                 .align_y(Alignment::Center),
+                form_row(
+                    "Autohide Size:",
+                    spin_button(self.settings.autohide_size, 1..=10, 1, "px", Message::AutohideSizeChanged),
+                ),
                 row![
-                    text("Autohide Size:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    slider(1.0..=10.0, self.settings.autohide_size as f32, Message::AutohideSizeChanged)
-                        .width(200)
-                        .step(1.0),
-                    text(format!("{}px", self.settings.autohide_size)).size(12).color(colors::TEXT_SECONDARY).width(60),
-                ]
-                .spacing(10)
-                .align_y(Alignment::Center),
-                row![
-                    text("Popdown Speed:").size(14).color(colors::TEXT_SECONDARY).width(150),
+                    text("Popdown Speed:").size(14).color(colors::text_secondary()).width(150),
                     slider(1.0..=100.0, self.settings.popdown_speed as f32, Message::PopdownSpeedChanged)
                         .width(200),
-                    text(format!("{}", self.settings.popdown_speed)).size(12).color(colors::TEXT_SECONDARY).width(60),
+                    text(format!("{}", self.settings.popdown_speed)).size(12).color(colors::text_secondary()).width(60),
                 ]
                 .spacing(10)
                 .align_y(Alignment::Center),
@@ -316,25 +309,25 @@ impl SettingsApp {
     fn view_advanced_section(&self) -> Element<'_, Message> {
         container(
             column![
-                text("Advanced").size(18).color(colors::TEXT_PRIMARY),
-                row![
-                    text("Enable Struts:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    checkbox(self.settings.enable_struts)
-                        .label("Enable Struts")
-                        .on_toggle(Message::EnableStrutsToggled),
-                    text("(Reserve screen space)").size(12).color(colors::TEXT_SECONDARY),
-                ]
-                .spacing(10)
-                .align_y(Alignment::Center),
-                row![
-                    text("Keep Below:").size(14).color(colors::TEXT_SECONDARY).width(150),
-                    checkbox(self.settings.keep_below)
-                        .label("Keep Below")
-                        .on_toggle(Message::KeepBelowToggled),
-                    text("(Keep panel below other windows)").size(12).color(colors::TEXT_SECONDARY),
-                ]
-                .spacing(10)
-                .align_y(Alignment::Center),
+                text("Advanced").size(18).color(colors::text_primary()),
+                form_row(
+                    "Enable Struts:",
+                    row![
+                        toggle_switch(self.settings.enable_struts, Message::EnableStrutsToggled),
+                        text("(Reserve screen space)").size(12).color(colors::text_secondary()),
+                    ]
+                    .spacing(10)
+                    .align_y(Alignment::Center),
+                ),
+                form_row(
+                    "Keep Below:",
+                    row![
+                        toggle_switch(self.settings.keep_below, Message::KeepBelowToggled),
+                        text("(Keep panel below other windows)").size(12).color(colors::text_secondary()),
+                    ]
+                    .spacing(10)
+                    .align_y(Alignment::Center),
+                ),
             ]
             .spacing(15)
         )
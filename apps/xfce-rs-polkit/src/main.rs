@@ -0,0 +1,306 @@
+//! PolicyKit authentication agent for XFCE.rs
+//!
+//! Registers as the session's agent and opens a credential prompt for
+//! every request polkitd sends, such as pkexec or mounting an internal
+//! disk. Started by `xfce-rs-session`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use iced::widget::{button, column, container, mouse_area, pick_list, row, space, text, text_input};
+use iced::{window, Alignment, Element, Length, Size, Subscription, Task, Theme};
+use tracing::{error, info};
+use xfce_rs_ipc::polkit::Identity;
+use xfce_rs_ipc::{AuthenticationRequest, PolkitAgent};
+use xfce_rs_ui::{colors, fonts, scale, styles, theme};
+
+pub fn main() -> iced::Result {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    fonts::load();
+    iced::daemon(PolkitPrompt::new, PolkitPrompt::update, PolkitPrompt::view)
+        .settings(fonts::settings())
+        .title(PolkitPrompt::title)
+        .theme(PolkitPrompt::theme)
+        .style(PolkitPrompt::style)
+        .scale_factor(PolkitPrompt::scale_factor)
+        .subscription(PolkitPrompt::subscription)
+        .run()
+}
+
+/// Entry of the user picker
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct User(Identity);
+
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.display_name == self.0.name {
+            write!(f, "{}", self.0.name)
+        } else {
+            write!(f, "{} ({})", self.0.display_name, self.0.name)
+        }
+    }
+}
+
+/// One open credential window
+struct Prompt {
+    request: AuthenticationRequest,
+    user: User,
+    password: String,
+    error: Option<String>,
+    /// Waiting for the helper to check the password
+    checking: bool,
+}
+
+#[derive(Default)]
+struct PolkitPrompt {
+    prompts: BTreeMap<window::Id, Prompt>,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Request(AuthenticationRequest),
+    AgentFailed,
+    UserSelected(window::Id, User),
+    PasswordChanged(window::Id, String),
+    Submit(window::Id),
+    Checked(window::Id, Result<(), String>),
+    Cancel(window::Id),
+    WindowDragged(window::Id),
+    WindowClosed(window::Id),
+    /// Close prompts polkitd cancelled
+    Prune,
+    ThemeChanged,
+}
+
+impl PolkitPrompt {
+    fn new() -> (Self, Task<Message>) {
+        (Self::default(), Task::none())
+    }
+
+    fn title(&self, _window: window::Id) -> String {
+        String::from("Authentication Required")
+    }
+
+    fn theme(&self, _window: window::Id) -> Theme {
+        theme::iced_theme()
+    }
+
+    fn scale_factor(&self, window: window::Id) -> f32 {
+        scale::factor_for(window)
+    }
+
+    fn style(&self, theme: &Theme) -> iced::theme::Style {
+        iced::theme::Style {
+            background_color: iced::Color::TRANSPARENT,
+            text_color: theme.palette().text,
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let mut subscriptions = vec![
+            Subscription::run(agent_requests),
+            window::close_events().map(Message::WindowClosed),
+            theme::subscription().map(|_| Message::ThemeChanged),
+        ];
+        if !self.prompts.is_empty() {
+            subscriptions.push(iced::time::every(Duration::from_millis(500)).map(|_| Message::Prune));
+        }
+        Subscription::batch(subscriptions)
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Request(request) => {
+                let Some(identity) = request.identities.first().cloned() else {
+                    request.session.cancel();
+                    return Task::none();
+                };
+                info!("Authentication requested for {}", request.action_id);
+                let (id, open) = window::open(window::Settings {
+                    size: Size::new(460.0, 300.0),
+                    position: window::Position::Centered,
+                    resizable: false,
+                    transparent: true,
+                    decorations: false,
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                self.prompts.insert(
+                    id,
+                    Prompt {
+                        request,
+                        user: User(identity),
+                        password: String::new(),
+                        error: None,
+                        checking: false,
+                    },
+                );
+                open.discard()
+            }
+            Message::AgentFailed => iced::exit(),
+            Message::UserSelected(id, user) => {
+                if let Some(prompt) = self.prompts.get_mut(&id) {
+                    prompt.user = user;
+                    prompt.error = None;
+                }
+                Task::none()
+            }
+            Message::PasswordChanged(id, password) => {
+                if let Some(prompt) = self.prompts.get_mut(&id) {
+                    prompt.password = password;
+                }
+                Task::none()
+            }
+            Message::Submit(id) => {
+                let Some(prompt) = self.prompts.get_mut(&id) else {
+                    return Task::none();
+                };
+                if prompt.checking {
+                    return Task::none();
+                }
+                prompt.checking = true;
+                prompt.error = None;
+
+                let session = prompt.request.session.clone();
+                let identity = prompt.user.0.clone();
+                let password = std::mem::take(&mut prompt.password);
+                Task::perform(
+                    async move {
+                        session
+                            .authenticate(&identity, &password)
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    move |result| Message::Checked(id, result),
+                )
+            }
+            Message::Checked(id, Ok(())) => {
+                self.prompts.remove(&id);
+                window::close(id)
+            }
+            Message::Checked(id, Err(e)) => {
+                if let Some(prompt) = self.prompts.get_mut(&id) {
+                    prompt.checking = false;
+                    prompt.error = Some(e);
+                }
+                Task::none()
+            }
+            Message::Cancel(id) => {
+                if let Some(prompt) = self.prompts.remove(&id) {
+                    prompt.request.session.cancel();
+                }
+                window::close(id)
+            }
+            Message::WindowDragged(id) => window::drag(id),
+            Message::ThemeChanged => Task::none(),
+            Message::WindowClosed(id) => {
+                if let Some(prompt) = self.prompts.remove(&id) {
+                    prompt.request.session.cancel();
+                }
+                Task::none()
+            }
+            Message::Prune => {
+                let cancelled: Vec<window::Id> = self
+                    .prompts
+                    .iter()
+                    .filter(|(_, prompt)| !prompt.checking && prompt.request.session.is_closed())
+                    .map(|(id, _)| *id)
+                    .collect();
+                Task::batch(cancelled.into_iter().map(|id| {
+                    self.prompts.remove(&id);
+                    window::close(id)
+                }))
+            }
+        }
+    }
+
+    fn view(&self, id: window::Id) -> Element<'_, Message> {
+        let Some(prompt) = self.prompts.get(&id) else {
+            return space().into();
+        };
+
+        let header = mouse_area(
+            column![
+                text("Authentication Required").size(18).color(colors::text_primary()),
+                text(&prompt.request.message).size(13).color(colors::text_secondary()),
+            ]
+            .spacing(6)
+            .width(Length::Fill),
+        )
+        .on_press(Message::WindowDragged(id));
+
+        let users: Vec<User> = prompt.request.identities.iter().cloned().map(User).collect();
+        let user: Element<'_, Message> = if users.len() > 1 {
+            pick_list(users, Some(prompt.user.clone()), move |user| Message::UserSelected(id, user))
+                .width(Length::Fill)
+                .into()
+        } else {
+            text(prompt.user.to_string()).size(14).color(colors::text_primary()).into()
+        };
+
+        let mut password = text_input("Password", &prompt.password)
+            .secure(true)
+            .padding(10)
+            .style(|theme, status| styles::search_input(theme, status));
+        if !prompt.checking {
+            password = password
+                .on_input(move |password| Message::PasswordChanged(id, password))
+                .on_submit(Message::Submit(id));
+        }
+
+        let status = match (&prompt.error, prompt.checking) {
+            (_, true) => text("Checking…").size(12).color(colors::text_secondary()),
+            (Some(error), false) => text(error).size(12).color(colors::control_close()),
+            (None, false) => text(&prompt.request.action_id).size(11).color(colors::text_secondary()),
+        };
+
+        let mut authenticate = button(text("Authenticate").size(14))
+            .padding([8, 16])
+            .style(|theme, status| styles::app_card(theme, status));
+        if !prompt.checking {
+            authenticate = authenticate.on_press(Message::Submit(id));
+        }
+        let buttons = row![
+            space().width(Length::Fill),
+            button(text("Cancel").size(14))
+                .on_press(Message::Cancel(id))
+                .padding([8, 16])
+                .style(|theme, status| styles::app_card(theme, status)),
+            authenticate,
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        container(column![header, user, password, status, buttons].spacing(14))
+            .padding(24)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(|theme| styles::glass_base(theme))
+            .into()
+    }
+}
+
+/// Register the agent and forward its requests; exits the app if another
+/// agent already serves the session
+fn agent_requests() -> impl Stream<Item = Message> {
+    iced::stream::channel(16, async |mut output: mpsc::Sender<Message>| {
+        let (agent, mut requests) = PolkitAgent::new();
+        if let Err(e) = agent.start().await {
+            error!("Failed to register PolicyKit agent: {}", e);
+            let _ = output.send(Message::AgentFailed).await;
+            return;
+        }
+
+        while let Some(request) = requests.recv().await {
+            if output.send(Message::Request(request)).await.is_err() {
+                break;
+            }
+        }
+    })
+}
@@ -0,0 +1,104 @@
+//! xdg-desktop-portal backend for XFCE.rs
+//!
+//! Started by D-Bus activation when a sandboxed application uses a portal
+//! that `xfce-rs-portals.conf` routes here.
+
+use std::sync::Arc;
+
+use tracing::{error, info, warn};
+use xfce_rs_config::{ConfigValue, XfceConfig};
+use xfce_rs_ipc::portal::{ColorScheme, PortalBackend, PortalRequest};
+
+mod screenshot;
+
+const THEME_PROPERTY: &str = "/Net/ThemeName";
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let (backend, mut requests) = PortalBackend::new();
+    if let Err(e) = backend.start().await {
+        error!("Failed to start portal backend: {}", e);
+        std::process::exit(1);
+    }
+
+    let config = Arc::new(XfceConfig::default());
+    let _reloader = match config.watch_files() {
+        Ok(reloader) => Some(reloader),
+        Err(e) => {
+            warn!("Theme changes will not reach applications: {}", e);
+            None
+        }
+    };
+    tokio::spawn(follow_theme(config, backend.clone()));
+
+    while let Some(request) = requests.recv().await {
+        tokio::spawn(handle(request));
+    }
+    info!("Portal backend shutting down");
+}
+
+/// Publish the dark-mode preference implied by the GTK theme
+async fn follow_theme(config: Arc<XfceConfig>, backend: PortalBackend) {
+    let mut changes = config.watch("xsettings", THEME_PROPERTY);
+    let theme = config.get_string("xsettings", THEME_PROPERTY).await.unwrap_or_default();
+    let mut scheme = ColorScheme::from_theme_name(&theme);
+    if let Err(e) = backend.set_color_scheme(scheme).await {
+        warn!("Failed to publish color scheme: {}", e);
+    }
+
+    while let Ok(change) = changes.recv().await {
+        let theme = match change.new_value {
+            Some(ConfigValue::String(theme)) => theme,
+            _ => String::new(),
+        };
+        let changed = ColorScheme::from_theme_name(&theme);
+        if changed == scheme {
+            continue;
+        }
+        scheme = changed;
+        if let Err(e) = backend.set_color_scheme(scheme).await {
+            warn!("Failed to publish color scheme: {}", e);
+        }
+    }
+}
+
+async fn handle(request: PortalRequest) {
+    match request {
+        PortalRequest::Screenshot {
+            app_id,
+            interactive,
+            reply,
+            ..
+        } => {
+            // There is no area picker to show; the dropped reply tells the
+            // application the request failed
+            if interactive {
+                warn!("Interactive screenshot requested by {}, which is not supported", app_id);
+                return;
+            }
+            info!("Screenshot requested by {}", app_id);
+            match tokio::task::spawn_blocking(screenshot::capture_screen).await {
+                Ok(Ok(uri)) => reply.succeed(uri),
+                Ok(Err(e)) => error!("Screenshot failed: {}", e),
+                Err(e) => error!("Screenshot task failed: {}", e),
+            }
+        }
+        PortalRequest::PickColor { reply, .. } => {
+            match tokio::task::spawn_blocking(screenshot::color_under_pointer).await {
+                Ok(Ok(color)) => reply.succeed(color),
+                Ok(Err(e)) => error!("Picking a color failed: {}", e),
+                Err(e) => error!("Color picker task failed: {}", e),
+            }
+        }
+        // `xfce-rs.portal` does not offer FileChooser, so these only come
+        // from callers addressing the backend directly
+        PortalRequest::OpenFile { app_id, reply, .. } | PortalRequest::SaveFile { app_id, reply, .. } => {
+            warn!("No file chooser dialog available for {}", app_id);
+            reply.cancel();
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Screen capture for the Screenshot portal, straight from the X server
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, ImageFormat};
+
+/// Capture the whole screen into the pictures folder and return its URI
+pub fn capture_screen() -> Result<String> {
+    let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X server")?;
+    let screen = &conn.setup().roots[screen_num];
+    let (width, height) = (screen.width_in_pixels, screen.height_in_pixels);
+
+    let image = conn
+        .get_image(ImageFormat::Z_PIXMAP, screen.root, 0, 0, width, height, !0)?
+        .reply()
+        .context("Failed to read the screen")?;
+    if image.depth < 24 || image.data.len() < usize::from(width) * usize::from(height) * 4 {
+        bail!("Unsupported screen depth {}", image.depth);
+    }
+
+    // 32 bits per pixel, BGRX in memory
+    let rgb: Vec<u8> = image
+        .data
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0]])
+        .collect();
+
+    let path = screenshot_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), u32::from(width), u32::from(height));
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&rgb)?;
+
+    Ok(file_uri(&path))
+}
+
+/// Color of the pixel under the pointer, as RGB in 0..=1
+pub fn color_under_pointer() -> Result<(f64, f64, f64)> {
+    let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X server")?;
+    let root = conn.setup().roots[screen_num].root;
+    let pointer = conn.query_pointer(root)?.reply()?;
+
+    let image = conn
+        .get_image(ImageFormat::Z_PIXMAP, root, pointer.root_x, pointer.root_y, 1, 1, !0)?
+        .reply()?;
+    let [blue, green, red, ..] = image.data[..] else {
+        bail!("Empty image under the pointer");
+    };
+    let channel = |value: u8| f64::from(value) / 255.0;
+    Ok((channel(red), channel(green), channel(blue)))
+}
+
+fn screenshot_path() -> PathBuf {
+    let dir = dirs::picture_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(std::env::temp_dir);
+    let name = chrono::Local::now().format("Screenshot_%Y-%m-%d_%H-%M-%S.png");
+    dir.join(name.to_string())
+}
+
+/// `file://` URI with everything but unreserved characters and `/`
+/// percent-encoded
+fn file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
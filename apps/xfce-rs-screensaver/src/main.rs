@@ -0,0 +1,91 @@
+//! Screensaver service for XFCE.rs
+//!
+//! Owns `org.freedesktop.ScreenSaver` so video players can keep the screen
+//! from locking and powering down, and blanks the screen after the idle
+//! delay set in the `xfce4-screensaver` channel. While inhibited, the
+//! window manager keeps the display from powering down too. Idle time
+//! comes from the X server; Wayland sessions are not supported yet.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+use xfce_rs_config::XfceConfig;
+use xfce_rs_ipc::screensaver::ScreenSaverEvent;
+use xfce_rs_ipc::ScreenSaverService;
+
+mod x11;
+
+const CHANNEL: &str = "xfce4-screensaver";
+const ENABLED_PROPERTY: &str = "/saver/idle-activation/enabled";
+/// Minutes, as xfce4-screensaver stores it
+const DELAY_PROPERTY: &str = "/saver/idle-activation/delay";
+const DEFAULT_DELAY_MINUTES: i64 = 5;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let idle = match x11::X11Idle::connect() {
+        Ok(idle) => idle,
+        Err(e) => {
+            error!("Cannot track idle time: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let service = ScreenSaverService::new(idle.clone());
+    if let Err(e) = service.start().await {
+        error!("Failed to start screensaver service: {}", e);
+        std::process::exit(1);
+    }
+
+    let config = Arc::new(XfceConfig::default());
+    let _reloader = match config.watch_files() {
+        Ok(reloader) => Some(reloader),
+        Err(e) => {
+            warn!("Idle delay changes will need a restart: {}", e);
+            None
+        }
+    };
+    let mut changes = config.watch(CHANNEL, "/saver");
+    let mut timeout = idle_timeout(&config).await;
+    info!("Screensaver activates after {:?}", timeout);
+
+    let mut events = service.subscribe();
+    let mut ticks = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {
+                service.poll(timeout);
+            }
+            Ok(_) = changes.recv() => {
+                timeout = idle_timeout(&config).await;
+                info!("Screensaver activates after {:?}", timeout);
+            }
+            Ok(event) = events.recv() => match event {
+                ScreenSaverEvent::ActiveChanged(true) => idle.blank(),
+                ScreenSaverEvent::LockRequested => warn!("Lock requested, but no lock screen is available"),
+                ScreenSaverEvent::Inhibited(_) | ScreenSaverEvent::Uninhibited(_) => {
+                    if let Err(e) = idle.inhibit_display(service.is_inhibited()) {
+                        warn!("Failed to tell the window manager about inhibitors: {}", e);
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+/// Idle time before activating, `None` when idle activation is off
+async fn idle_timeout(config: &XfceConfig) -> Option<Duration> {
+    let enabled = config.get_bool(CHANNEL, ENABLED_PROPERTY).await.unwrap_or(true);
+    let minutes = config
+        .get_int(CHANNEL, DELAY_PROPERTY)
+        .await
+        .unwrap_or(DEFAULT_DELAY_MINUTES);
+    let minutes = u64::try_from(minutes).ok().filter(|minutes| *minutes > 0)?;
+    enabled.then(|| Duration::from_secs(minutes * 60))
+}
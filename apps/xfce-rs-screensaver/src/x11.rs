@@ -0,0 +1,71 @@
+//! Idle time from the X server's MIT-SCREEN-SAVER extension
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::screensaver::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{ClientMessageEvent, ConnectionExt as _, EventMask, ScreenSaver, Window};
+use x11rb::rust_connection::RustConnection;
+use xfce_rs_ipc::IdleSource;
+
+/// Client message asking xfwm4-rs to keep the display on, or let it power
+/// down again
+const DPMS_MESSAGE: &str = "_XFWM4_RS_DPMS";
+const DPMS_ALLOW: u32 = 0;
+const DPMS_INHIBIT: u32 = 1;
+
+/// Idle source and blanker backed by the X server; clones share the
+/// connection
+#[derive(Clone)]
+pub struct X11Idle {
+    conn: Arc<RustConnection>,
+    root: Window,
+}
+
+impl X11Idle {
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X server")?;
+        conn.extension_information(screensaver::X11_EXTENSION_NAME)?
+            .context("X server lacks the MIT-SCREEN-SAVER extension")?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self {
+            conn: Arc::new(conn),
+            root,
+        })
+    }
+
+    /// Blank the screen with the X server's own screen saver, which ends
+    /// on the next input
+    pub fn blank(&self) {
+        if self.conn.force_screen_saver(ScreenSaver::ACTIVE).is_ok() {
+            let _ = self.conn.flush();
+        }
+    }
+
+    /// Have the window manager keep the display from blanking and powering
+    /// down while `inhibit`, counted under our pid
+    pub fn inhibit_display(&self, inhibit: bool) -> Result<()> {
+        let atom = self.conn.intern_atom(false, DPMS_MESSAGE.as_bytes())?.reply()?.atom;
+        let command = if inhibit { DPMS_INHIBIT } else { DPMS_ALLOW };
+        let event = ClientMessageEvent::new(32, self.root, atom, [command, std::process::id(), 0, 0, 0]);
+        self.conn.send_event(false, self.root, EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY, event)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+impl IdleSource for X11Idle {
+    fn idle_time(&self) -> Option<Duration> {
+        let info = self.conn.screensaver_query_info(self.root).ok()?.reply().ok()?;
+        Some(Duration::from_millis(info.ms_since_user_input.into()))
+    }
+
+    /// Also restarts the server's screen saver and DPMS timers
+    fn reset(&self) {
+        if self.conn.force_screen_saver(ScreenSaver::RESET).is_ok() {
+            let _ = self.conn.flush();
+        }
+    }
+}
@@ -7,6 +7,9 @@ use crate::ewmh::atoms::AtomCollection;
 
 pub struct Context {
     pub conn: RustConnection,
+    /// Makes the frames and holds the clients' save-set; the only
+    /// connection whose resources the server keeps when restarting in place
+    pub frames: RustConnection,
     pub screen_num: usize,
     pub root_window: u32,
     pub root_depth: u8,
@@ -25,6 +28,7 @@ impl Context {
         let atoms = AtomCollection::new(&conn)?.reply()?;
         let screen_width = screen.width_in_pixels;
         let screen_height = screen.height_in_pixels;
+        let (frames, _) = x11rb::connect(None)?;
         
         // Select events on root window
         use x11rb::protocol::xproto::{ChangeWindowAttributesAux, EventMask};
@@ -32,6 +36,6 @@ impl Context {
             .event_mask(EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY);
         conn.change_window_attributes(root_window, &values)?;
         
-        Ok(Self { conn, screen_num, root_window, root_depth, atoms, screen_width, screen_height })
+        Ok(Self { conn, frames, screen_num, root_window, root_depth, atoms, screen_width, screen_height })
     }
 }
@@ -4,6 +4,7 @@ atom_manager! {
     pub AtomCollection: AtomCollectionCookie {
         _NET_SUPPORTED,
         _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
         _NET_NUMBER_OF_DESKTOPS,
         _NET_DESKTOP_GEOMETRY,
         _NET_DESKTOP_VIEWPORT,
@@ -14,6 +15,7 @@ atom_manager! {
         _NET_SUPPORTING_WM_CHECK,
         _NET_WM_DESKTOP,
         _NET_WM_NAME,
+        _NET_WM_ICON,
         _NET_WM_STATE,
         _NET_WM_STATE_FULLSCREEN,
         _NET_WM_STATE_MAXIMIZED_VERT,
@@ -60,12 +62,20 @@ atom_manager! {
         _NET_FRAME_EXTENTS,
         _NET_WM_STATE_FOCUSED,
         _NET_STARTUP_ID,
+        _NET_STARTUP_INFO_BEGIN,
+        _NET_STARTUP_INFO,
         _NET_WM_WINDOW_OPACITY,
         _NET_WM_OPAQUE_REGION,
         _NET_WM_STATE_SHADED,
         _NET_WM_STATE_ABOVE,
         _NET_WM_STATE_BELOW,
         UTF8_STRING,
+        WM_WINDOW_ROLE,
+        XdndSelection,
+        _XFWM4_RS_SAVE_SESSION,
+        _XFWM4_RS_DPMS,
+        _XFWM4_RS_RESTART,
+        _XFWM4_RS_FRAME,
     }
 }
 
@@ -0,0 +1,112 @@
+use x11rb::protocol::xproto::Window;
+
+/// Managed windows in the two orders EWMH publishes them in:
+/// `_NET_CLIENT_LIST` in the order they were mapped, and
+/// `_NET_CLIENT_LIST_STACKING` from bottom to top.
+#[derive(Debug, Clone, Default)]
+pub struct ClientList {
+    mapping: Vec<Window>,
+    stacking: Vec<Window>,
+}
+
+impl ClientList {
+    /// Track a newly managed window, on top of the others or, for the
+    /// desktop, below them
+    pub fn add(&mut self, window: Window, on_top: bool) {
+        self.remove(window);
+        self.mapping.push(window);
+        if on_top {
+            self.stacking.push(window);
+        } else {
+            self.stacking.insert(0, window);
+        }
+    }
+
+    pub fn remove(&mut self, window: Window) {
+        self.mapping.retain(|&w| w != window);
+        self.stacking.retain(|&w| w != window);
+    }
+
+    /// Move `window` to the top. Returns whether the order changed.
+    pub fn raise(&mut self, window: Window) -> bool {
+        self.restack(window, true)
+    }
+
+    /// Move `window` to the bottom. Returns whether the order changed.
+    pub fn lower(&mut self, window: Window) -> bool {
+        self.restack(window, false)
+    }
+
+    fn restack(&mut self, window: Window, top: bool) -> bool {
+        let Some(position) = self.stacking.iter().position(|&w| w == window) else {
+            return false;
+        };
+        let target = if top { self.stacking.len() - 1 } else { 0 };
+        if position == target {
+            return false;
+        }
+        self.stacking.remove(position);
+        if top {
+            self.stacking.push(window);
+        } else {
+            self.stacking.insert(0, window);
+        }
+        true
+    }
+
+    /// Windows in mapping order, for `_NET_CLIENT_LIST`
+    pub fn mapping(&self) -> &[Window] {
+        &self.mapping
+    }
+
+    /// Windows bottom to top, for `_NET_CLIENT_LIST_STACKING`. Lower
+    /// layers stay below higher ones whatever was raised last.
+    pub fn stacking(&self, layer: impl Fn(Window) -> u16) -> Vec<Window> {
+        let mut stacking = self.stacking.clone();
+        stacking.sort_by_key(|&window| layer(window));
+        stacking
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window::{LAYER_DESKTOP, LAYER_DOCK, LAYER_NORMAL};
+
+    #[test]
+    fn test_mapping_and_stacking_order() {
+        let mut list = ClientList::default();
+        list.add(0x1000, true);
+        list.add(0x2000, true);
+        list.add(0x3000, false);
+        list.add(0x4000, true);
+
+        assert!(list.raise(0x1000));
+        assert!(!list.raise(0x1000));
+        assert!(list.lower(0x4000));
+        list.remove(0x2000);
+
+        // What `xprop -root _NET_CLIENT_LIST _NET_CLIENT_LIST_STACKING` lists
+        assert_eq!(list.mapping(), [0x1000, 0x3000, 0x4000]);
+        assert_eq!(list.stacking(|_| LAYER_NORMAL), [0x4000, 0x3000, 0x1000]);
+        assert!(!list.raise(0x2000));
+    }
+
+    #[test]
+    fn test_stacking_keeps_layers() {
+        let mut list = ClientList::default();
+        list.add(0x1, true); // panel
+        list.add(0x2, false); // desktop
+        list.add(0x3, true);
+        list.add(0x4, true);
+        list.raise(0x3);
+        list.raise(0x2);
+
+        let layer = |window| match window {
+            0x1 => LAYER_DOCK,
+            0x2 => LAYER_DESKTOP,
+            _ => LAYER_NORMAL,
+        };
+        assert_eq!(list.stacking(layer), [0x2, 0x4, 0x3, 0x1]);
+    }
+}
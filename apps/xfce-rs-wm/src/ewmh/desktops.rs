@@ -0,0 +1,137 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ClientMessageEvent, ConnectionExt, EventMask, PropMode};
+use x11rb::wrapper::ConnectionExt as _;
+
+/// The most workspaces a pager or the settings can ask for, as in xfwm4
+pub const MAX_WORKSPACES: u32 = 32;
+
+/// `_NET_WM_DESKTOP` of windows shown on every workspace
+pub const ALL_WORKSPACES: u32 = 0xFFFFFFFF;
+
+/// The workspaces: how many there are and what they are called, as
+/// published in `_NET_NUMBER_OF_DESKTOPS` and `_NET_DESKTOP_NAMES`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspaces {
+    names: Vec<String>,
+}
+
+impl Workspaces {
+    /// `count` workspaces, named from `names` and numbered past its end
+    pub fn new(count: u32, names: &[String]) -> Self {
+        let mut workspaces = Self { names: names.to_vec() };
+        workspaces.set_count(count);
+        workspaces
+    }
+
+    pub fn count(&self) -> u32 {
+        self.names.len() as u32
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Add or remove workspaces at the end, keeping at least one
+    pub fn set_count(&mut self, count: u32) {
+        let count = count.clamp(1, MAX_WORKSPACES) as usize;
+        self.names.truncate(count);
+        while self.names.len() < count {
+            self.names.push(format!("Workspace {}", self.names.len() + 1));
+        }
+    }
+
+    /// Rename the workspaces from a `_NET_DESKTOP_NAMES` value; names it
+    /// leaves out keep their numbered default
+    pub fn set_names(&mut self, value: &[u8]) {
+        let mut names = parse_names(value).into_iter();
+        for (index, name) in self.names.iter_mut().enumerate() {
+            *name = names.next().filter(|n| !n.is_empty()).unwrap_or_else(|| format!("Workspace {}", index + 1));
+        }
+    }
+
+    /// `_NET_DESKTOP_NAMES`: the names, each NUL terminated
+    pub fn names_property(&self) -> Vec<u8> {
+        self.names.iter().flat_map(|name| name.bytes().chain([0])).collect()
+    }
+
+    /// `_NET_DESKTOP_VIEWPORT`: workspaces don't scroll, so (0, 0) each
+    pub fn viewport_property(&self) -> Vec<u32> {
+        vec![0; 2 * self.names.len()]
+    }
+
+    /// The workspace a window asking for `workspace` goes to: the last
+    /// one when it is past the end
+    pub fn clamp(&self, workspace: u32) -> u32 {
+        if workspace == ALL_WORKSPACES {
+            workspace
+        } else {
+            workspace.min(self.count() - 1)
+        }
+    }
+}
+
+/// Names in a `_NET_DESKTOP_NAMES` value, which may lack the final NUL
+pub fn parse_names(value: &[u8]) -> Vec<String> {
+    let value = value.strip_suffix(&[0]).unwrap_or(value);
+    if value.is_empty() {
+        return Vec::new();
+    }
+    value.split(|&b| b == 0).map(|name| String::from_utf8_lossy(name).into_owned()).collect()
+}
+
+/// Ask the running window manager for `count` workspaces named `names`,
+/// the way pagers do: a `_NET_NUMBER_OF_DESKTOPS` message and the
+/// `_NET_DESKTOP_NAMES` property on the root window. Used for settings
+/// changed while it runs, which arrive outside its X event loop.
+pub fn request_workspaces(count: Option<u32>, names: Option<&[String]>) -> Result<()> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    if let Some(count) = count {
+        let atom = conn.intern_atom(false, b"_NET_NUMBER_OF_DESKTOPS")?.reply()?.atom;
+        let event = ClientMessageEvent::new(32, root, atom, [count, 0, 0, 0, 0]);
+        conn.send_event(false, root, EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY, event)?;
+    }
+    if let Some(names) = names {
+        let atom = conn.intern_atom(false, b"_NET_DESKTOP_NAMES")?.reply()?.atom;
+        let utf8 = conn.intern_atom(false, b"UTF8_STRING")?.reply()?.atom;
+        let value: Vec<u8> = names.iter().flat_map(|name| name.bytes().chain([0])).collect();
+        conn.change_property8(PropMode::REPLACE, root, atom, utf8, &value)?;
+    }
+    conn.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_count_and_names() {
+        let mut workspaces = Workspaces::new(3, &["Web".to_string()]);
+        assert_eq!(workspaces.count(), 3);
+        assert_eq!(workspaces.names_property(), b"Web\0Workspace 2\0Workspace 3\0");
+        assert_eq!(workspaces.viewport_property(), [0; 6]);
+        assert_eq!(workspaces.clamp(7), 2);
+        assert_eq!(workspaces.clamp(ALL_WORKSPACES), ALL_WORKSPACES);
+
+        workspaces.set_count(0);
+        assert_eq!(workspaces.count(), 1);
+        workspaces.set_count(100);
+        assert_eq!(workspaces.count(), MAX_WORKSPACES);
+        workspaces.set_count(2);
+        assert_eq!(workspaces.names_property(), b"Web\0Workspace 2\0");
+    }
+
+    #[test]
+    fn test_desktop_names() {
+        assert_eq!(parse_names(b"One\0Two\0"), ["One", "Two"]);
+        assert_eq!(parse_names(b"One\0Two"), ["One", "Two"]);
+        assert!(parse_names(b"").is_empty());
+
+        // A pager renaming only the first of three
+        let mut workspaces = Workspaces::new(3, &[]);
+        workspaces.set_names(b"Mail\0");
+        assert_eq!(workspaces.names_property(), b"Mail\0Workspace 2\0Workspace 3\0");
+    }
+}
@@ -1,2 +1,4 @@
 pub mod atoms;
+pub mod client_list;
+pub mod desktops;
 pub mod setup;
@@ -50,8 +50,13 @@ pub fn setup_hints(ctx: &Context) -> Result<()> {
     let supported = [
         ctx.atoms._NET_SUPPORTED,
         ctx.atoms._NET_CLIENT_LIST,
+        ctx.atoms._NET_CLIENT_LIST_STACKING,
         ctx.atoms._NET_NUMBER_OF_DESKTOPS,
         ctx.atoms._NET_CURRENT_DESKTOP,
+        ctx.atoms._NET_DESKTOP_NAMES,
+        ctx.atoms._NET_DESKTOP_VIEWPORT,
+        ctx.atoms._NET_DESKTOP_GEOMETRY,
+        ctx.atoms._NET_WM_DESKTOP,
         ctx.atoms._NET_ACTIVE_WINDOW,
         ctx.atoms._NET_WM_NAME,
         ctx.atoms._NET_SUPPORTING_WM_CHECK,
@@ -110,14 +115,9 @@ pub fn setup_hints(ctx: &Context) -> Result<()> {
         &supported,
     )?;
 
-    ctx.conn.change_property32(
-        PropMode::REPLACE,
-        ctx.root_window,
-        ctx.atoms._NET_NUMBER_OF_DESKTOPS,
-        AtomEnum::CARDINAL,
-        &[4],
-    )?;
-    
+    // _NET_NUMBER_OF_DESKTOPS, _NET_DESKTOP_NAMES and _NET_DESKTOP_VIEWPORT
+    // follow the workspace settings, see WindowManager::update_desktop_props
+
     ctx.conn.change_property32(
         PropMode::REPLACE,
         ctx.root_window,
@@ -134,22 +134,5 @@ pub fn setup_hints(ctx: &Context) -> Result<()> {
         &[ctx.screen_width as u32, ctx.screen_height as u32],
     )?;
 
-    ctx.conn.change_property32(
-        PropMode::REPLACE,
-        ctx.root_window,
-        ctx.atoms._NET_DESKTOP_VIEWPORT,
-        AtomEnum::CARDINAL,
-        &[0, 0, 0, 0, 0, 0, 0, 0], // (0,0) for each of 4 desktops
-    )?;
-
-    let desktop_names = "Alpha\0Beta\0Gamma\0Delta\0";
-    ctx.conn.change_property8(
-        PropMode::REPLACE,
-        ctx.root_window,
-        ctx.atoms._NET_DESKTOP_NAMES,
-        ctx.atoms.UTF8_STRING,
-        desktop_names.as_bytes(),
-    )?;
-
     Ok(())
 }
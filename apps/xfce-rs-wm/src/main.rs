@@ -18,6 +18,11 @@ struct Args {
     #[arg(long)]
     replace: bool,
 
+    /// Take over from an instance restarting in place, keeping the frames
+    /// and workspace it leaves; implies --replace
+    #[arg(long)]
+    restart: bool,
+
     /// Session management client ID
     #[arg(long = "sm-client-id")]
     sm_client_id: Option<String>,
@@ -82,57 +87,98 @@ async fn main() -> anyhow::Result<()> {
     
     info!("Starting xfwm4-rs...");
 
-    match Context::new() {
-        Ok(ctx) => {
-            info!("Successfully connected to X11 server.");
-            info!("Screen: {}, Root Window: {}", ctx.screen_num, ctx.root_window);
-            
-            // Check replacement
-            if let Err(e) = acquire_wm_selection(&ctx, args.replace) {
-                 error!("{}", e);
-                 return Err(e);
-            }
-            
-            crate::ewmh::setup::setup_hints(&ctx)?;
-            
-            // Initialize Settings
-            let settings_manager = crate::window::settings::SettingsManager::new().await?;
-            
-            // Initialize Session
-            let mut session_manager = crate::window::session::SessionManager::new().await?;
-            if let Err(e) = session_manager.register(args.sm_client_id.as_deref()).await {
-                warn!("Session registration failed: {}", e);
-            }
-            
-            let mut wm = WindowManager::new(ctx, settings_manager)?;
-            wm.scan_windows()?;
-            
-            // Run with error handling - don't let X11 errors crash us
-            loop {
-                match wm.run() {
-                    Ok(_) => break, // Normal exit
-                    Err(e) => {
-                        // Check if it's a fatal error or recoverable
-                        let error_msg = format!("{}", e);
-                        if error_msg.contains("closed the connection") || 
-                           error_msg.contains("broken pipe") ||
-                           error_msg.contains("I/O error") {
-                            error!("Fatal X11 error - server disconnected: {}", e);
-                            break;
-                        } else {
-                            // Log but try to continue for other errors
-                            error!("X11 error (continuing): {}", e);
-                            std::thread::sleep(std::time::Duration::from_millis(100));
-                        }
-                    }
+    let mut wm = match start(&args).await {
+        Ok(wm) => wm,
+        Err(e) => {
+            error!("{}", e);
+            // Nobody takes the windows out of the frames kept for us
+            if args.restart {
+                if let Err(e) = crate::window::restart::release_kept_frames() {
+                    error!("Failed to release the frames kept from before the restart: {}", e);
                 }
             }
-        }
-        Err(e) => {
-            error!("Failed to connect to X11 server: {}", e);
             return Err(e);
         }
+    };
+    if let Err(e) = crate::window::restart::watch_restart_signal() {
+        warn!("Not restarting on SIGUSR1: {}", e);
+    }
+    
+    // Run with error handling - don't let X11 errors crash us
+    loop {
+        match wm.run() {
+            Ok(_) => break, // Normal exit
+            Err(e) => {
+                // Check if it's a fatal error or recoverable
+                let error_msg = format!("{}", e);
+                if error_msg.contains("closed the connection") || 
+                   error_msg.contains("broken pipe") ||
+                   error_msg.contains("I/O error") {
+                    error!("Fatal X11 error - server disconnected: {}", e);
+                    break;
+                } else {
+                    // Log but try to continue for other errors
+                    error!("X11 error (continuing): {}", e);
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Connect, take over the screen and manage the windows on it
+async fn start(args: &Args) -> anyhow::Result<WindowManager> {
+    let ctx = Context::new().map_err(|e| anyhow::anyhow!("Failed to connect to X11 server: {}", e))?;
+    info!("Successfully connected to X11 server.");
+    info!("Screen: {}, Root Window: {}", ctx.screen_num, ctx.root_window);
+    
+    // Check replacement
+    acquire_wm_selection(&ctx, args.replace || args.restart)?;
+
+    // Go on with the workspace on screen before the restart, which
+    // setting up the hints resets
+    let desktop = args.restart
+        .then(|| crate::window::restart::current_desktop(&ctx.conn, ctx.root_window, ctx.atoms._NET_CURRENT_DESKTOP))
+        .flatten();
+    
+    crate::ewmh::setup::setup_hints(&ctx)?;
+    
+    // Initialize Settings
+    let settings_manager = crate::window::settings::SettingsManager::new().await?;
+
+    // Workspace settings changed while running reach the event loop
+    // the way a pager's requests do
+    let watched = crate::window::settings::watch_workspace_settings(|setting| {
+        use crate::window::settings::WorkspaceSetting;
+        let result = match setting {
+            WorkspaceSetting::Count(count) => crate::ewmh::desktops::request_workspaces(Some(count), None),
+            WorkspaceSetting::Names(names) => crate::ewmh::desktops::request_workspaces(None, Some(&names)),
+        };
+        if let Err(e) = result {
+            warn!("Failed to apply workspace setting: {}", e);
+        }
+    }).await;
+    if let Err(e) = watched {
+        warn!("Not watching workspace settings: {}", e);
+    }
+    
+    // Initialize Session
+    let client_id = crate::window::session::client_id(args.sm_client_id.as_deref());
+    let mut session_manager = crate::window::session::SessionManager::new().await?;
+    if let Err(e) = session_manager.register(args.sm_client_id.as_deref(), &client_id).await {
+        warn!("Session registration failed: {}", e);
+    }
+    
+    let mut wm = WindowManager::new(ctx, settings_manager)?;
+    // Windows the session manager starts again go back where they were
+    wm.set_session(crate::window::session::state_path(&client_id));
+    if let Some(desktop) = desktop {
+        if let Err(e) = wm.switch_workspace(desktop) {
+            warn!("Failed to go back to workspace {}: {}", desktop, e);
+        }
+    }
+    wm.scan_windows()?;
+    Ok(wm)
+}
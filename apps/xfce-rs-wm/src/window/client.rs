@@ -1,6 +1,9 @@
 use x11rb::protocol::xproto::Window;
 use x11rb::protocol::render::Picture;
 
+use crate::window::resize::SizeHints;
+use crate::window::tiling::SnapZone;
+
 #[derive(Debug, Clone)]
 pub struct Client {
     /// The window ID of the application window
@@ -57,6 +60,8 @@ pub struct Client {
     pub is_shaped: bool,
     pub sync_alarm: Option<u32>,
     pub opacity: u32,
+    /// WM_CLASS, as "instance.class"
+    pub class: Option<String>,
     pub demands_attention: bool,
     pub skip_taskbar: bool,
     pub skip_pager: bool,
@@ -64,6 +69,11 @@ pub struct Client {
     pub is_above: bool,
     pub is_below: bool,
     pub startup_id: Option<String>,
+    /// WM_NORMAL_HINTS, which interactive resizing keeps to
+    pub size_hints: SizeHints,
+    /// Where a tiling shortcut or edge drag put the window, until it is
+    /// moved or resized by hand
+    pub snap: SnapZone,
 }
 
 
@@ -112,6 +122,7 @@ impl Client {
             is_shaped: false,
             sync_alarm: None,
             opacity: 0xFFFFFFFF,
+            class: None,
             demands_attention: false,
             skip_taskbar: false,
             skip_pager: false,
@@ -119,8 +130,17 @@ impl Client {
             is_above: false,
             is_below: false,
             startup_id: None,
+            size_hints: SizeHints::default(),
+            snap: SnapZone::None,
         }
     }
+
+    /// Size of the frame around the client for the given border and
+    /// titlebar; a shaded window's frame keeps only its titlebar
+    pub fn frame_size(&self, border: u16, title: u16) -> (u16, u16) {
+        let client_height = if self.is_shaded && title > 0 { 0 } else { self.height };
+        (self.width + 2 * border, client_height + title + 2 * border)
+    }
 }
 
 
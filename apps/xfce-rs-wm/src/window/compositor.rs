@@ -1,22 +1,74 @@
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::{Window, ConnectionExt as XProtoExt};
-use x11rb::protocol::render::{Picture, PictType, ConnectionExt as RenderExt, CreatePictureAux};
+use x11rb::protocol::xproto::{Pixmap, Rectangle, Window, ConnectionExt as XProtoExt};
+use x11rb::protocol::render::{Pictformat, Picture, PictType, ConnectionExt as RenderExt, CreatePictureAux};
 use x11rb::protocol::composite::{ConnectionExt as CompositeExt, Redirect};
-use x11rb::protocol::xfixes::ConnectionExt as XFixesExt;
+use x11rb::protocol::xfixes::{ConnectionExt as XFixesExt, Region};
 use x11rb::protocol::shape::{ConnectionExt as ShapeExt, SK, SO};
 use tracing::{error, warn, debug, info};
 use crate::window::error::{log_warn, log_and_ignore};
+use crate::window::settings::Settings;
+use crate::window::present::Presenter;
+use crate::window::shadow::ShadowTextures;
+
+/// Compositing effects, as set in the WM settings channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Effects {
+    pub shadows: bool,
+    /// Alpha of shadows at their darkest
+    pub shadow_alpha: u16,
+    /// Honor `_NET_WM_WINDOW_OPACITY`
+    pub window_opacity: bool,
+    /// Zero when windows don't fade
+    pub fade_duration: Duration,
+    /// Present frames at the vertical blank
+    pub vsync: bool,
+}
+
+impl Effects {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            shadows: settings.show_frame_shadow,
+            shadow_alpha: (settings.shadow_opacity.min(100) * 0xffff / 100) as u16,
+            window_opacity: settings.use_window_opacity,
+            fade_duration: if settings.fade_windows { Duration::from_millis(settings.fade_duration.into()) } else { Duration::ZERO },
+            vsync: settings.vblank_mode != "off",
+        }
+    }
+}
 
 pub struct Compositor {
     pub root: Window,
     pub overlay_window: Window,
     pub root_picture: Picture,
     pub active: bool,
+    pub effects: Effects,
+    /// Rendered once the compositor is enabled, if shadows are on
+    shadows: Option<ShadowTextures>,
+    /// Back buffer and frame pacing with vsync; without it frames are
+    /// painted straight onto the overlay
+    present: Option<Presenter>,
+    /// Depth and format of the overlay, for offscreen buffers like it
+    depth: u8,
+    format: Pictformat,
+    /// Set while the desktop zoom is in
+    zoom: Option<Magnifier>,
+}
+
+/// Offscreen frame painted 1:1 while zoomed, of which `source` is shown
+/// magnified over the whole screen
+struct Magnifier {
+    pixmap: Pixmap,
+    picture: Picture,
+    width: u16,
+    height: u16,
+    source: Rectangle,
 }
 
 impl Compositor {
-    pub fn new<C: Connection>(_conn: &C, root: Window, _screen_num: usize) -> Result<Self> {
+    pub fn new<C: Connection>(_conn: &C, root: Window, _screen_num: usize, effects: Effects) -> Result<Self> {
         // We will defer activation to explicit call to avoid freezing screen during startup
         // Placeholder
         Ok(Self {
@@ -24,6 +76,12 @@ impl Compositor {
             overlay_window: x11rb::NONE,
             root_picture: x11rb::NONE,
             active: false,
+            effects,
+            shadows: None,
+            present: None,
+            depth: 0,
+            format: x11rb::NONE,
+            zoom: None,
         })
     }
 
@@ -61,6 +119,8 @@ impl Compositor {
         self.root_picture = conn.generate_id()?;
         conn.render_create_picture(self.root_picture, self.overlay_window, root_format, &CreatePictureAux::new())?;
         info!("🎬 Compositor root picture {} created for overlay {} with depth {}", self.root_picture, self.overlay_window, target_depth);
+        self.depth = target_depth;
+        self.format = root_format;
         
         // Making overlay window input-transparent so clicks pass through to windows below
         if let Ok(region) = conn.generate_id() {
@@ -77,6 +137,23 @@ impl Compositor {
         if let Err(e) = conn.map_window(self.overlay_window) {
             error!("Failed to map overlay window: {}", e);
         }
+
+        if self.effects.vsync {
+            match Presenter::new(conn, self.overlay_window, target_depth, root_format, geom.width, geom.height) {
+                Ok(present) => {
+                    info!("Compositor presenting at vertical blank");
+                    self.present = Some(present);
+                }
+                Err(e) => warn!("No vsync, painting straight to the screen: {}", e),
+            }
+        }
+
+        if self.effects.shadows {
+            match ShadowTextures::new(conn, self.root) {
+                Ok(shadows) => self.shadows = Some(shadows),
+                Err(e) => warn!("Shadows unavailable: {}", e),
+            }
+        }
         
         self.active = true;
         Ok(())
@@ -107,8 +184,14 @@ impl Compositor {
         screen_w: u16,
         screen_h: u16,
         clients: impl Iterator<Item = (Option<Picture>, Picture, i16, i16, u16, u16, u16, u16, u16, u16, bool, u32)>,
+        clip: Option<Region>,
     ) -> Result<()> {
         if !self.active { return Ok(()); }
+
+        // Only the damaged part of the screen changes
+        if let Some(region) = clip {
+            XFixesExt::xfixes_set_picture_clip_region(conn, self.target(), region, 0, 0)?;
+        }
         
         use x11rb::protocol::xproto::Rectangle;
         use x11rb::protocol::render::Color;
@@ -120,7 +203,7 @@ impl Compositor {
         
         conn.render_fill_rectangles(
             x11rb::protocol::render::PictOp::SRC,
-            self.root_picture,
+            self.target(),
             Color { red: 0x2424, green: 0x2424, blue: 0x3030, alpha: 0xffff },
             &[rect],
         )?;
@@ -128,23 +211,15 @@ impl Compositor {
         // Create a vector to avoid double iteration issues
         let client_list: Vec<_> = clients.collect();
 
-        // 1. Draw all shadows first
-        for (frame_pic_opt, _, x, y, frame_w, frame_h, _, _, _, _, has_shadow, _) in &client_list {
-            if !has_shadow || frame_pic_opt.is_none() { continue; }
-            let shadow_rect = Rectangle {
-                x: x.wrapping_add(6),
-                y: y.wrapping_add(6),
-                width: *frame_w,
-                height: *frame_h,
-            };
-            
-            if let Err(e) = conn.render_fill_rectangles(
-                x11rb::protocol::render::PictOp::OVER,
-                self.root_picture,
-                Color { red: 0, green: 0, blue: 0, alpha: 0x7000 }, // ~44% alpha
-                &[shadow_rect],
-            ) {
-                warn!("Failed to render shadow rectangle: {}", e);
+        // 1. Draw all shadows first, as dark as the window is opaque
+        if let Some(shadows) = &self.shadows {
+            for (frame_pic_opt, _, x, y, frame_w, frame_h, _, _, _, _, has_shadow, opacity) in &client_list {
+                if !has_shadow || frame_pic_opt.is_none() { continue; }
+                let frame = Rectangle { x: *x, y: *y, width: *frame_w, height: *frame_h };
+                let alpha = (u64::from(self.effects.shadow_alpha) * u64::from(*opacity) / 0xFFFFFFFF) as u16;
+                if let Err(e) = shadows.paint(conn, self.target(), frame, alpha) {
+                    warn!("Failed to render shadow: {}", e);
+                }
             }
         }
 
@@ -169,7 +244,7 @@ impl Compositor {
                     x11rb::protocol::render::PictOp::OVER,
                     *frame_pic,
                     mask,
-                    self.root_picture,
+                    self.target(),
                     0, 0,
                     0, 0,
                     *x, *y,
@@ -185,7 +260,7 @@ impl Compositor {
                     x11rb::protocol::render::PictOp::OVER,
                     *content_pic,
                     mask,
-                    self.root_picture,
+                    self.target(),
                     0, 0,
                     0, 0,
                     *x + *border as i16, *y + (*title_h + *border) as i16,
@@ -199,10 +274,148 @@ impl Compositor {
                 let _ = conn.render_free_picture(m);
             }
         }
+        if clip.is_some() {
+            XFixesExt::xfixes_set_picture_clip_region(conn, self.target(), x11rb::NONE, 0, 0)?;
+        }
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// Stop compositing, handing the windows back to the server, e.g. for
+    /// a restarted instance to redirect them anew
+    pub fn release<C: Connection>(&mut self, conn: &C) -> Result<()> {
+        if !self.active { return Ok(()); }
+        self.set_zoom(conn, None, 0, 0)?;
+        if let Some(present) = self.present.take() {
+            present.free(conn)?;
+        }
+        conn.render_free_picture(self.root_picture)?;
+        conn.composite_release_overlay_window(self.root)?;
+        conn.composite_unredirect_subwindows(self.root, Redirect::MANUAL)?;
+        conn.flush()?;
+        self.active = false;
+        Ok(())
+    }
+
+    /// Composite `picture`, `width` x `height`, scaled down into `dest`
+    pub fn paint_scaled<C: Connection>(&self, conn: &C, picture: Picture, width: u16, height: u16, dest: x11rb::protocol::xproto::Rectangle) -> Result<()> {
+        use x11rb::protocol::render::{PictOp, Transform};
+
+        if !self.active || dest.width == 0 || dest.height == 0 { return Ok(()); }
+        const ONE: i32 = 1 << 16;
+        // The transform maps destination pixels to source pixels, in 16.16 fixed point
+        let scale = |source: u16, target: u16| ((i64::from(source) << 16) / i64::from(target)) as i32;
+        let transform = |sx: i32, sy: i32| Transform {
+            matrix11: sx, matrix12: 0, matrix13: 0,
+            matrix21: 0, matrix22: sy, matrix23: 0,
+            matrix31: 0, matrix32: 0, matrix33: ONE,
+        };
+
+        conn.render_set_picture_transform(picture, transform(scale(width, dest.width), scale(height, dest.height)))?;
+        conn.render_set_picture_filter(picture, b"bilinear", &[])?;
+        let result = conn.render_composite(PictOp::OVER, picture, x11rb::NONE, self.target(), 0, 0, 0, 0, dest.x, dest.y, dest.width, dest.height);
+        // Back to 1:1 for the regular paint
+        conn.render_set_picture_transform(picture, transform(ONE, ONE))?;
+        conn.render_set_picture_filter(picture, b"nearest", &[])?;
+        result?;
         conn.flush()?;
         Ok(())
     }
 
+    /// The picture frames are painted into
+    fn target(&self) -> Picture {
+        self.zoom.as_ref().map_or_else(|| self.output(), |zoom| zoom.picture)
+    }
+
+    /// The picture that goes on screen
+    fn output(&self) -> Picture {
+        self.present.as_ref().map_or(self.root_picture, Presenter::picture)
+    }
+
+    /// Show `source` of a `width` x `height` screen magnified over all of
+    /// it, or everything 1:1 again for `None`. The next frame has to be
+    /// painted whole when the zoom comes in.
+    pub fn set_zoom<C: Connection>(&mut self, conn: &C, source: Option<Rectangle>, width: u16, height: u16) -> Result<()> {
+        if !self.active { return Ok(()); }
+        let Some(source) = source else {
+            if let Some(zoom) = self.zoom.take() {
+                conn.render_free_picture(zoom.picture)?;
+                conn.free_pixmap(zoom.pixmap)?;
+            }
+            return Ok(());
+        };
+        if let Some(zoom) = self.zoom.as_mut().filter(|zoom| zoom.width == width && zoom.height == height) {
+            zoom.source = source;
+            return Ok(());
+        }
+        // Zooming in, or the screen changed size
+        self.set_zoom(conn, None, width, height)?;
+        let pixmap = conn.generate_id()?;
+        conn.create_pixmap(self.depth, pixmap, self.overlay_window, width.max(1), height.max(1))?;
+        let picture = conn.generate_id()?;
+        conn.render_create_picture(picture, pixmap, self.format, &CreatePictureAux::new())?;
+        conn.render_set_picture_filter(picture, b"bilinear", &[])?;
+        self.zoom = Some(Magnifier { pixmap, picture, width, height, source });
+        Ok(())
+    }
+
+    /// Scale the zoomed part of the frame up onto the screen
+    fn paint_magnified<C: Connection>(&self, conn: &C, zoom: &Magnifier) -> Result<()> {
+        use x11rb::protocol::render::{PictOp, Transform};
+
+        const ONE: i32 = 1 << 16;
+        let scale = |source: u16, target: u16| ((i64::from(source) << 16) / i64::from(target.max(1))) as i32;
+        // Destination pixels to source pixels, offset to the zoomed part
+        let transform = Transform {
+            matrix11: scale(zoom.source.width, zoom.width), matrix12: 0, matrix13: i32::from(zoom.source.x) << 16,
+            matrix21: 0, matrix22: scale(zoom.source.height, zoom.height), matrix23: i32::from(zoom.source.y) << 16,
+            matrix31: 0, matrix32: 0, matrix33: ONE,
+        };
+        conn.render_set_picture_transform(zoom.picture, transform)?;
+        conn.render_composite(PictOp::SRC, zoom.picture, x11rb::NONE, self.output(), 0, 0, 0, 0, 0, 0, zoom.width, zoom.height)?;
+        Ok(())
+    }
+
+    /// Put the frame painted into the back buffer on screen, `update` of
+    /// it or all for `None`. Without vsync it is there already, unless
+    /// zoomed.
+    pub fn present<C: Connection>(&mut self, conn: &C, update: Option<Region>) -> Result<()> {
+        if !self.active { return Ok(()); }
+        // The magnified view changes all over with any change
+        let update = match &self.zoom {
+            Some(zoom) => {
+                self.paint_magnified(conn, zoom)?;
+                conn.flush()?;
+                None
+            }
+            None => update,
+        };
+        if let Some(present) = &mut self.present {
+            present.present(conn, update)?;
+            conn.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the last frame is still on its way to the screen, and the
+    /// next one has to wait for it
+    pub fn frame_pending(&self) -> bool {
+        self.present.as_ref().is_some_and(|present| present.is_pending(Instant::now()))
+    }
+
+    /// Note a frame reaching the screen, from a `PresentCompleteNotify`
+    pub fn frame_complete(&mut self, window: Window, serial: u32) -> bool {
+        self.present.as_mut().is_some_and(|present| present.complete(window, serial))
+    }
+
+    /// Follow the screen size; the next frame has to be painted whole
+    pub fn resize<C: Connection>(&mut self, conn: &C, width: u16, height: u16) -> Result<()> {
+        if let Some(present) = &mut self.present {
+            present.resize(conn, width, height)?;
+        }
+        Ok(())
+    }
+
     pub fn set_cursor<C: Connection>(&self, conn: &C, cursor: x11rb::protocol::xproto::Cursor) -> Result<()> {
         if self.overlay_window != x11rb::NONE {
             use x11rb::protocol::xproto::ChangeWindowAttributesAux;
@@ -1,9 +1,11 @@
 use anyhow::Result;
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::Cursor;
-use x11rb::resource_manager::new_from_default;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, Cursor, Window};
+use x11rb::resource_manager::{new_from_default, Database};
 use x11rb::cursor::Handle;
 
+use crate::window::settings::Settings;
+
 pub struct Cursors {
     pub normal: Cursor,
     pub move_: Cursor,
@@ -16,11 +18,12 @@ pub struct Cursors {
     pub resize_e: Cursor,  // Right
     pub resize_w: Cursor,  // Left
     pub hand: Cursor,      // For buttons
+    pub busy: Cursor,      // While applications launch
 }
 
 impl Cursors {
-    pub fn new<C: Connection>(conn: &C, screen_num: usize) -> Result<Self> {
-        let db = new_from_default(conn)?;
+    pub fn new<C: Connection>(conn: &C, screen_num: usize, root: Window, settings: &Settings) -> Result<Self> {
+        let db = resource_database(conn, root, settings.cursor_theme.as_deref(), settings.cursor_size)?;
         let handle = Handle::new(conn, screen_num, &db)?.reply()?;
         
         let load = |name: &str| -> Result<Cursor> {
@@ -39,6 +42,64 @@ impl Cursors {
             resize_e: load("right_side")?,
             resize_w: load("left_side")?,
             hand: load("hand2")?,
+            busy: load("left_ptr_watch")?,
+        })
+    }
+}
+
+/// The X resources with `theme` and `size` laid over them, for loading the
+/// WM's own cursors. The XSETTINGS manager publishes the same settings to
+/// the root window for everyone else; it alone writes `RESOURCE_MANAGER`,
+/// so this one may not have them yet when the WM starts.
+fn resource_database<C: Connection>(conn: &C, root: Window, theme: Option<&str>, size: Option<u32>) -> Result<Database> {
+    if theme.is_none() && size.is_none() {
+        return Ok(new_from_default(conn)?);
+    }
+    let existing = conn
+        .get_property(false, root, AtomEnum::RESOURCE_MANAGER, AtomEnum::STRING, 0, u32::MAX)?
+        .reply()?
+        .value;
+    let merged = merge_resources(&String::from_utf8_lossy(&existing), theme, size);
+    Ok(Database::new_from_data(merged.as_bytes()))
+}
+
+/// `resources` with the `Xcursor.theme` and `Xcursor.size` lines replaced
+/// by those given
+fn merge_resources(resources: &str, theme: Option<&str>, size: Option<u32>) -> String {
+    let mut merged: Vec<String> = resources
+        .lines()
+        .filter(|line| {
+            let key = line.split(':').next().unwrap_or("").trim();
+            !((theme.is_some() && key == "Xcursor.theme") || (size.is_some() && key == "Xcursor.size"))
         })
+        .map(str::to_string)
+        .collect();
+
+    if let Some(theme) = theme {
+        merged.push(format!("Xcursor.theme:\t{}", theme));
+    }
+    if let Some(size) = size {
+        merged.push(format!("Xcursor.size:\t{}", size));
+    }
+    merged.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_resources_replaces_cursor_lines() {
+        let existing = "Xft.dpi:\t96\nXcursor.theme:\tAdwaita\nXcursor.size:\t24\n";
+        assert_eq!(
+            merge_resources(existing, Some("Bibata"), Some(32)),
+            "Xft.dpi:\t96\nXcursor.theme:\tBibata\nXcursor.size:\t32\n"
+        );
+        assert_eq!(
+            merge_resources(existing, None, Some(48)),
+            "Xft.dpi:\t96\nXcursor.theme:\tAdwaita\nXcursor.size:\t48\n"
+        );
+        assert_eq!(merge_resources(existing, Some("Bibata"), None), "Xft.dpi:\t96\nXcursor.size:\t24\nXcursor.theme:\tBibata\n");
+        assert_eq!(merge_resources("", Some("Bibata"), Some(24)), "Xcursor.theme:\tBibata\nXcursor.size:\t24\n");
     }
 }
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::damage::{ConnectionExt as DamageExt, Damage};
+use x11rb::protocol::xfixes::{ConnectionExt as XFixesExt, Region};
+
+/// What the next frame has to repaint: the damage reported since the
+/// last one, or everything after windows moved, stacked or changed state
+#[derive(Debug)]
+pub struct Repaint {
+    /// Union of the damage, in screen coordinates
+    region: Region,
+    /// Scratch region each window's damage is fetched into
+    parts: Region,
+    damaged: bool,
+    full: bool,
+}
+
+impl Repaint {
+    pub fn new<C: Connection>(conn: &C) -> Result<Self> {
+        let region = conn.generate_id()?;
+        conn.xfixes_create_region(region, &[])?;
+        let parts = conn.generate_id()?;
+        conn.xfixes_create_region(parts, &[])?;
+        // The first frame paints everything
+        Ok(Self { region, parts, damaged: false, full: true })
+    }
+
+    /// Repaint the whole screen next frame
+    pub fn add_all(&mut self) {
+        self.full = true;
+    }
+
+    /// Take the damage of a window whose contents are at (`x`, `y`) on
+    /// screen, repairing it
+    pub fn add_damage<C: Connection>(&mut self, conn: &C, damage: Damage, x: i16, y: i16) -> Result<()> {
+        conn.damage_subtract(damage, x11rb::NONE, self.parts)?;
+        if self.full {
+            return Ok(());
+        }
+        conn.xfixes_translate_region(self.parts, x, y)?;
+        conn.xfixes_union_region(self.region, self.parts, self.region)?;
+        self.damaged = true;
+        Ok(())
+    }
+
+    /// Whether there is anything to paint
+    pub fn is_pending(&self) -> bool {
+        self.full || self.damaged
+    }
+
+    /// The region to clip the next frame to, `None` to paint everything
+    pub fn clip(&self) -> Option<Region> {
+        (!self.full).then_some(self.region)
+    }
+
+    /// Start over after a frame was painted
+    pub fn clear<C: Connection>(&mut self, conn: &C) -> Result<()> {
+        if self.damaged {
+            conn.xfixes_set_region(self.region, &[])?;
+        }
+        self.damaged = false;
+        self.full = false;
+        Ok(())
+    }
+}
+
+/// How many frames a frame time summary covers
+pub const SUMMARY_FRAMES: u32 = 300;
+
+/// Frame times over the last `SUMMARY_FRAMES` frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSummary {
+    pub frames: u32,
+    /// Frames clipped to the damage rather than painted whole
+    pub partial: u32,
+    pub average: Duration,
+    pub worst: Duration,
+}
+
+/// Running frame time statistics, for the tracing output
+#[derive(Debug, Default)]
+pub struct FrameTimes {
+    frames: u32,
+    partial: u32,
+    total: Duration,
+    worst: Duration,
+}
+
+impl FrameTimes {
+    /// Count a frame that took `elapsed`; every `SUMMARY_FRAMES` frames,
+    /// the summary of them, after which counting starts over
+    pub fn record(&mut self, elapsed: Duration, partial: bool) -> Option<FrameSummary> {
+        self.frames += 1;
+        self.partial += u32::from(partial);
+        self.total += elapsed;
+        self.worst = self.worst.max(elapsed);
+        if self.frames < SUMMARY_FRAMES {
+            return None;
+        }
+        let summary = FrameSummary { frames: self.frames, partial: self.partial, average: self.total / self.frames, worst: self.worst };
+        *self = Self::default();
+        Some(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_summary() {
+        let mut times = FrameTimes::default();
+        for frame in 1..SUMMARY_FRAMES {
+            assert_eq!(times.record(Duration::from_millis(u64::from(frame % 3)), frame % 2 == 0), None);
+        }
+        // The last frame takes 3ms, for 303ms over the 300
+        let summary = times.record(Duration::from_millis(3), true).unwrap();
+        assert_eq!(summary.frames, SUMMARY_FRAMES);
+        assert_eq!(summary.partial, 150);
+        assert_eq!(summary.worst, Duration::from_millis(3));
+        assert_eq!(summary.average, Duration::from_micros(303_000 / 300));
+    }
+
+    #[test]
+    fn test_summary_starts_over() {
+        let mut times = FrameTimes::default();
+        for _ in 0..SUMMARY_FRAMES {
+            times.record(Duration::from_millis(8), false);
+        }
+        assert_eq!(times.record(Duration::from_millis(1), false), None);
+        assert_eq!(times.frames, 1);
+        assert_eq!(times.worst, Duration::from_millis(1));
+    }
+}
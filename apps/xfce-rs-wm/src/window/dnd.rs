@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::Window;
+
+use crate::window::settings::Settings;
+
+/// Raising of the window a drag-and-drop rests over, so files can be
+/// dropped onto windows hidden behind others.
+///
+/// `XdndPosition` goes from the drag source to the window under the
+/// pointer only, so the window manager learns of a drag from the
+/// `XdndSelection` owner changing and follows the pointer itself until the
+/// button comes up.
+#[derive(Debug, Default)]
+pub struct DndRaise {
+    /// `/general/raise_on_dnd`
+    pub enabled: bool,
+    /// How long a drag rests over a window before it is raised
+    pub delay: Duration,
+    dragging: bool,
+    /// The window under the drag, and when it is due to be raised; `None`
+    /// once it has been
+    hovered: Option<(Window, Option<Instant>)>,
+}
+
+impl DndRaise {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            enabled: settings.raise_on_dnd,
+            delay: Duration::from_millis(u64::from(settings.dnd_raise_delay)),
+            ..Self::default()
+        }
+    }
+
+    /// A drag started: the `XdndSelection` got a new owner
+    pub fn start(&mut self) {
+        self.dragging = self.enabled;
+        self.hovered = None;
+    }
+
+    /// The drag ended, dropped or cancelled
+    pub fn stop(&mut self) {
+        self.dragging = false;
+        self.hovered = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// The drag is over `window` at `now`, or over no window; returns the
+    /// window due to be raised, once per window the drag rests on
+    pub fn hover(&mut self, window: Option<Window>, now: Instant) -> Option<Window> {
+        if !self.dragging {
+            return None;
+        }
+        let Some(window) = window else {
+            self.hovered = None;
+            return None;
+        };
+        match self.hovered {
+            Some((w, Some(at))) if w == window && now >= at => {
+                self.hovered = Some((w, None));
+                Some(w)
+            }
+            Some((w, _)) if w == window => None,
+            _ => {
+                self.hovered = Some((window, Some(now + self.delay)));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raise(delay: u64) -> DndRaise {
+        DndRaise { enabled: true, delay: Duration::from_millis(delay), ..DndRaise::default() }
+    }
+
+    #[test]
+    fn test_raise_after_delay() {
+        let start = Instant::now();
+        let mut dnd = raise(500);
+        // Nothing happens before a drag
+        assert_eq!(dnd.hover(Some(1), start), None);
+
+        dnd.start();
+        assert_eq!(dnd.hover(Some(1), start), None);
+        assert_eq!(dnd.hover(Some(1), start + Duration::from_millis(499)), None);
+        assert_eq!(dnd.hover(Some(1), start + Duration::from_millis(500)), Some(1));
+        // Only once while it stays there
+        assert_eq!(dnd.hover(Some(1), start + Duration::from_millis(900)), None);
+
+        // Moving to another window starts over
+        assert_eq!(dnd.hover(Some(2), start + Duration::from_millis(900)), None);
+        assert_eq!(dnd.hover(Some(1), start + Duration::from_millis(1000)), None);
+        assert_eq!(dnd.hover(Some(1), start + Duration::from_millis(1500)), Some(1));
+
+        dnd.stop();
+        assert!(!dnd.is_dragging());
+        assert_eq!(dnd.hover(Some(2), start + Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let start = Instant::now();
+        let mut dnd = DndRaise { enabled: false, ..raise(0) };
+        dnd.start();
+        assert!(!dnd.is_dragging());
+        assert_eq!(dnd.hover(Some(1), start), None);
+        assert_eq!(dnd.hover(Some(1), start + Duration::from_secs(1)), None);
+    }
+}
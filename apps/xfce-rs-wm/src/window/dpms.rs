@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use tracing::{debug, info};
+use x11rb::connection::Connection;
+use x11rb::protocol::dpms::{ConnectionExt as DpmsExt, DPMSMode};
+use x11rb::protocol::xproto::{Blanking, ConnectionExt, Exposures, ScreenSaver};
+
+use crate::window::settings::Settings;
+
+/// Client message to the root window controlling blanking and display
+/// power. `data[0]` is one of the commands below, `data[1]` an id of the
+/// sender's choosing (its pid, say) that inhibitions are counted by.
+pub const DPMS_MESSAGE: &str = "_XFWM4_RS_DPMS";
+/// Let the display blank and power down again
+pub const DPMS_ALLOW: u32 = 0;
+/// Keep the display on, e.g. while a video plays
+pub const DPMS_INHIBIT: u32 = 1;
+/// Lock the screen and turn the display off now
+pub const DPMS_LOCK_AND_BLANK: u32 = 2;
+
+/// Seconds of no input before each step, 0 for never, as the X server
+/// takes them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub blank: u16,
+    pub suspend: u16,
+    pub off: u16,
+}
+
+impl Timeouts {
+    /// From the minutes in the settings, with DPMS off when disabled
+    pub fn from_settings(settings: &Settings) -> Self {
+        let seconds = |minutes: u32| u16::try_from(minutes.saturating_mul(60)).unwrap_or(u16::MAX);
+        let (suspend, off) = if settings.dpms_enabled {
+            (seconds(settings.dpms_suspend_time), seconds(settings.dpms_off_time))
+        } else {
+            (0, 0)
+        };
+        Self { blank: seconds(settings.blank_time), suspend, off }.ordered()
+    }
+
+    /// The server refuses DPMS timeouts that come before an earlier step,
+    /// so a later step no sooner than the one before it
+    fn ordered(self) -> Self {
+        let off = if self.suspend != 0 && self.off != 0 { self.off.max(self.suspend) } else { self.off };
+        Self { off, ..self }
+    }
+}
+
+/// Screen blanking and display power management: the X server counts the
+/// idle time and steps through the timeouts, unless something inhibits it
+#[derive(Debug)]
+pub struct Dpms {
+    timeouts: Timeouts,
+    /// Senders of `DPMS_INHIBIT`, by the id they sent
+    inhibitors: HashSet<u32>,
+    /// Whether the server has the DPMS extension
+    capable: bool,
+}
+
+impl Dpms {
+    pub fn new(conn: &impl Connection, settings: &Settings) -> Self {
+        let capable = conn.dpms_capable().ok().and_then(|c| c.reply().ok()).is_some_and(|reply| reply.capable);
+        if !capable {
+            info!("Display power management unavailable, blanking only");
+        }
+        Self { timeouts: Timeouts::from_settings(settings), inhibitors: HashSet::new(), capable }
+    }
+
+    /// Keep the display on for `id`; returns whether that changed anything
+    pub fn inhibit(&mut self, id: u32) -> bool {
+        let was = self.is_inhibited();
+        self.inhibitors.insert(id);
+        was != self.is_inhibited()
+    }
+
+    /// `id` no longer keeps the display on; returns whether that changed
+    /// anything
+    pub fn allow(&mut self, id: u32) -> bool {
+        let was = self.is_inhibited();
+        self.inhibitors.remove(&id);
+        was != self.is_inhibited()
+    }
+
+    pub fn is_inhibited(&self) -> bool {
+        !self.inhibitors.is_empty()
+    }
+
+    /// Hand the timeouts to the server, or turn its timers off while
+    /// inhibited
+    pub fn apply(&self, conn: &impl Connection) -> Result<()> {
+        let timeouts = if self.is_inhibited() { Timeouts { blank: 0, suspend: 0, off: 0 } } else { self.timeouts };
+        debug!("Blanking after {}s, display suspended after {}s and off after {}s", timeouts.blank, timeouts.suspend, timeouts.off);
+        let blank = i16::try_from(timeouts.blank).unwrap_or(i16::MAX);
+        conn.set_screen_saver(blank, 0, Blanking::PREFERRED, Exposures::DEFAULT)?;
+        if self.capable {
+            if timeouts.suspend == 0 && timeouts.off == 0 {
+                conn.dpms_disable()?;
+            } else {
+                conn.dpms_set_timeouts(0, timeouts.suspend, timeouts.off)?;
+                conn.dpms_enable()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Blank the screen and turn the display off now, until the next input
+    pub fn blank_now(&self, conn: &impl Connection) -> Result<()> {
+        conn.force_screen_saver(ScreenSaver::ACTIVE)?;
+        if self.capable {
+            // Forcing a level needs DPMS on, even while inhibited
+            conn.dpms_enable()?;
+            conn.dpms_force_level(DPMSMode::OFF)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeouts() {
+        let mut settings = Settings::default();
+        assert_eq!(Timeouts::from_settings(&settings), Timeouts { blank: 600, suspend: 900, off: 1200 });
+
+        // Off no sooner than suspend
+        settings.dpms_off_time = 5;
+        assert_eq!(Timeouts::from_settings(&settings).off, 900);
+        // Never suspending leaves off alone
+        settings.dpms_suspend_time = 0;
+        assert_eq!(Timeouts::from_settings(&settings), Timeouts { blank: 600, suspend: 0, off: 300 });
+
+        settings.dpms_enabled = false;
+        settings.blank_time = 100_000;
+        assert_eq!(Timeouts::from_settings(&settings), Timeouts { blank: u16::MAX, suspend: 0, off: 0 });
+    }
+
+    #[test]
+    fn test_inhibitors() {
+        let mut dpms = Dpms { timeouts: Timeouts::from_settings(&Settings::default()), inhibitors: HashSet::new(), capable: false };
+        assert!(dpms.inhibit(42));
+        assert!(!dpms.inhibit(7));
+        assert!(!dpms.allow(42));
+        assert!(dpms.is_inhibited());
+        assert!(dpms.allow(7));
+        assert!(!dpms.allow(7));
+        assert!(!dpms.is_inhibited());
+    }
+}
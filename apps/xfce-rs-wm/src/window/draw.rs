@@ -1,80 +1,138 @@
 use anyhow::Result;
-use x11rb::protocol::xproto::{ConnectionExt, Window, CreateGCAux, ChangeGCAux, Rectangle};
+use x11rb::protocol::xproto::{ConnectionExt, Window, CreateGCAux, ChangeGCAux, Rectangle, Char2b, ImageFormat, Font};
 use x11rb::connection::Connection;
 use tracing::debug;
 
 use crate::core::context::Context;
+use crate::window::frame::FramePart;
+use crate::window::theme::{ButtonState, Theme, TitleAlignment};
 
-pub fn draw_decoration(ctx: &Context, frame: Window, title: &str, width: u16, height: u16, title_height: u16) -> Result<()> {
+/// Open the theme's title font, or a core font every server has
+fn open_title_font(ctx: &Context, theme: &Theme) -> Option<Font> {
+    let font = ctx.conn.generate_id().ok()?;
+    let names = theme.font.iter().map(|name| name.as_bytes()).chain([&b"10x20"[..], &b"fixed"[..]]);
+    for name in names {
+        // Checked, so a missing font doesn't end up as an X error later
+        if ctx.conn.open_font(font, name).ok().and_then(|cookie| cookie.check().ok()).is_some() {
+            return Some(font);
+        }
+        debug!("Font '{}' not available", String::from_utf8_lossy(name));
+    }
+    None
+}
+
+/// Draw a frame's border, titlebar, title and buttons. `highlight` is the
+/// button under the pointer, see `frame::button_highlight`.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_decoration(ctx: &Context, theme: &Theme, frame: Window, title: &str, width: u16, height: u16, title_height: u16, active: bool, highlight: Option<(FramePart, ButtonState)>) -> Result<()> {
     if width == 0 || height == 0 { return Ok(()); }
+    let colors = theme.colors(active);
 
     // 1. Create IDs
     let gc = ctx.conn.generate_id()?;
-    let font = ctx.conn.generate_id()?;
+    let font = if title_height > 0 { open_title_font(ctx, theme) } else { None };
 
-    // Try to open a font. 10x20 is bigger and clearer than fixed.
-    let mut font_opened = true;
-    if let Err(_) = ctx.conn.open_font(font, b"10x20") {
-        if let Err(e) = ctx.conn.open_font(font, b"fixed") {
-            debug!("Failed to open font 'fixed': {}. Continuing without text.", e);
-            font_opened = false;
-        }
+    let mut values = CreateGCAux::new().foreground(colors.border);
+    if let Some(font) = font {
+        values = values.font(font);
     }
-    
-    // Create GC with colors
-    let values = CreateGCAux::new()
-        .foreground(0x3c3c3c) // Dark charcoal background
-        .font(font);
-        
     ctx.conn.create_gc(gc, frame, &values)?;
-    
+
     // 2. Clear Background (fills the entire frame including borders)
     let bg_rect = Rectangle { x: 0, y: 0, width, height };
     ctx.conn.poly_fill_rectangle(frame, gc, &[bg_rect])?;
-    
-    if title_height > 0 && font_opened {
-        // 3. Draw Title Text
-        ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(0xe0e0e0))?;
-        if !title.is_empty() {
-            // Adjust y for better vertical centering with 10x20 font
-            // 10x20 font usually has baseline around 15-16
-            let text_y = 15 + (title_height as i16 / 10); 
-            if let Err(e) = ctx.conn.image_text8(frame, gc, 12, text_y, title.as_bytes()) {
-                debug!("Failed to draw title text: {}", e);
+
+    // Images are blended for 24-bit visuals only, which is what X servers run
+    let draw_images = ctx.root_depth == 24 || ctx.root_depth == 32;
+
+    if title_height > 0 {
+        // 3. Titlebar, flat or tiled with the theme's image
+        ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(colors.title))?;
+        ctx.conn.poly_fill_rectangle(frame, gc, &[Rectangle { x: 0, y: 0, width, height: title_height }])?;
+        if let Some(tile) = theme.title_tiles.get(&active).filter(|tile| draw_images && tile.width > 0) {
+            let data = tile.to_zpixmap(colors.title);
+            let tile_height = tile.height.min(title_height);
+            let row_bytes = tile.width as usize * 4;
+            for x in (0..width).step_by(tile.width as usize) {
+                ctx.conn.put_image(ImageFormat::Z_PIXMAP, frame, gc, tile.width, tile_height, x as i16, 0, 0, ctx.root_depth, &data[..row_bytes * tile_height as usize])?;
             }
         }
-        
-        // 4. Draw Decoration Buttons (Mock)
-        let btn_y = 6;
-        let btn_size = 12;
 
-        // Close Button (Red)
-        let close_x = width as i16 - 20;
-        let gc_red = ctx.conn.generate_id()?;
-        ctx.conn.create_gc(gc_red, frame, &CreateGCAux::new().foreground(0xff5555))?;
-        ctx.conn.poly_fill_rectangle(frame, gc_red, &[Rectangle { x: close_x, y: btn_y, width: btn_size, height: btn_size }])?;
-        let _ = ctx.conn.free_gc(gc_red);
-
-        // Maximize Button (Green)
-        let max_x = width as i16 - 40;
-        let gc_green = ctx.conn.generate_id()?;
-        ctx.conn.create_gc(gc_green, frame, &CreateGCAux::new().foreground(0x50fa7b))?;
-        ctx.conn.poly_fill_rectangle(frame, gc_green, &[Rectangle { x: max_x, y: btn_y, width: btn_size, height: btn_size }])?;
-        let _ = ctx.conn.free_gc(gc_green);
+        // 4. Title text, aligned within the space the buttons leave
+        if let (Some(font), false) = (font, title.is_empty()) {
+            ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(colors.text).background(colors.title))?;
+            let chars: Vec<Char2b> = title.bytes().map(|b| Char2b { byte1: 0, byte2: b }).collect();
+            let (text_width, ascent, descent) = match ctx.conn.query_text_extents(font, &chars)?.reply() {
+                Ok(extents) => (extents.overall_width as i16, extents.font_ascent, extents.font_descent),
+                Err(_) => (0, 15, 5),
+            };
+            let (start, end) = theme.button_layout.title_span(width);
+            let offset = theme.title_horizontal_offset;
+            let text_x = match theme.title_alignment {
+                TitleAlignment::Left => start + offset,
+                TitleAlignment::Center => start + (end - start - text_width) / 2,
+                TitleAlignment::Right => end - offset - text_width,
+            }.max(start);
+            let text_y = (title_height as i16 + ascent - descent) / 2;
+            // image_text8 fills the text box too, so keep it over the titlebar
+            if let Err(e) = ctx.conn.poly_text8(frame, gc, text_x, text_y, &text_item(title.as_bytes())) {
+                debug!("Failed to draw title text: {}", e);
+            }
+        }
 
-        // Minimize Button (Yellow)
-        let min_x = width as i16 - 60;
-        let gc_yellow = ctx.conn.generate_id()?;
-        ctx.conn.create_gc(gc_yellow, frame, &CreateGCAux::new().foreground(0xf1fa8c))?;
-        ctx.conn.poly_fill_rectangle(frame, gc_yellow, &[Rectangle { x: min_x, y: btn_y, width: btn_size, height: btn_size }])?;
-        let _ = ctx.conn.free_gc(gc_yellow);
+        // 5. Buttons, the theme's images or the flat colored squares
+        let layout = &theme.button_layout;
+        let resting = ButtonState::resting(active);
+        for (part, x) in layout.positions(width) {
+            let state = match highlight {
+                Some((lit, state)) if lit == part => state,
+                _ => resting,
+            };
+            // Themes without prelight or pressed images show the resting one
+            let image = theme.buttons.get(&(part, state)).or_else(|| theme.buttons.get(&(part, resting)));
+            if let Some(image) = image.filter(|_| draw_images) {
+                let y = (title_height.saturating_sub(image.height) / 2) as i16;
+                ctx.conn.put_image(ImageFormat::Z_PIXMAP, frame, gc, image.width, image.height, x, y, 0, ctx.root_depth, &image.to_zpixmap(colors.title))?;
+                continue;
+            }
+            let color = match part {
+                FramePart::CloseButton => 0xff5555, // Red
+                FramePart::MaximizeButton => 0x50fa7b, // Green
+                FramePart::ShadeButton => 0x8be9fd, // Cyan
+                FramePart::MenuButton => 0xbd93f9, // Purple
+                _ => 0xf1fa8c, // Yellow
+            };
+            ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(tint(color, state)))?;
+            ctx.conn.poly_fill_rectangle(frame, gc, &[Rectangle { x, y: layout.y(), width: layout.size, height: layout.size }])?;
+        }
     }
-    
+
     // Cleanup
     let _ = ctx.conn.free_gc(gc);
-    if font_opened {
+    if let Some(font) = font {
         let _ = ctx.conn.close_font(font);
     }
-    
+
     Ok(())
 }
+
+/// A flat button color lightened under the pointer and darkened while
+/// pressed
+fn tint(color: u32, state: ButtonState) -> u32 {
+    let channel = |shift: u32| {
+        let c = (color >> shift) & 0xff;
+        let c = match state {
+            ButtonState::Prelight => c + (0xff - c) / 3,
+            ButtonState::Pressed => c * 2 / 3,
+            ButtonState::Active | ButtonState::Inactive => c,
+        };
+        c << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+/// PolyText8 items for `text`: runs of at most 254 characters, each a length,
+/// a delta of 0 and the characters
+fn text_item(text: &[u8]) -> Vec<u8> {
+    text.chunks(254).flat_map(|run| [run.len() as u8, 0].into_iter().chain(run.iter().copied())).collect()
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConfigureWindowAux, ConnectionExt, CreateWindowAux, EventMask, Rectangle, StackMode, Window, WindowClass};
+
+use crate::core::context::Context;
+use crate::window::settings::Settings;
+
+/// A corner or side of the screen that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScreenEdge {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl ScreenEdge {
+    pub const ALL: [ScreenEdge; 8] = [
+        ScreenEdge::TopLeft,
+        ScreenEdge::TopRight,
+        ScreenEdge::BottomLeft,
+        ScreenEdge::BottomRight,
+        ScreenEdge::Top,
+        ScreenEdge::Bottom,
+        ScreenEdge::Left,
+        ScreenEdge::Right,
+    ];
+
+    /// Name under `/general/edge_actions/`
+    pub fn name(self) -> &'static str {
+        match self {
+            ScreenEdge::TopLeft => "top_left",
+            ScreenEdge::TopRight => "top_right",
+            ScreenEdge::BottomLeft => "bottom_left",
+            ScreenEdge::BottomRight => "bottom_right",
+            ScreenEdge::Top => "top",
+            ScreenEdge::Bottom => "bottom",
+            ScreenEdge::Left => "left",
+            ScreenEdge::Right => "right",
+        }
+    }
+
+    /// The edge or corner a point on a `width` by `height` screen touches
+    pub fn at(x: i16, y: i16, width: u16, height: u16) -> Option<Self> {
+        let (left, top) = (x <= 0, y <= 0);
+        let (right, bottom) = (i32::from(x) >= i32::from(width) - 1, i32::from(y) >= i32::from(height) - 1);
+        match (left, right, top, bottom) {
+            (true, _, true, _) => Some(ScreenEdge::TopLeft),
+            (_, true, true, _) => Some(ScreenEdge::TopRight),
+            (true, _, _, true) => Some(ScreenEdge::BottomLeft),
+            (_, true, _, true) => Some(ScreenEdge::BottomRight),
+            (_, _, true, _) => Some(ScreenEdge::Top),
+            (_, _, _, true) => Some(ScreenEdge::Bottom),
+            (true, _, _, _) => Some(ScreenEdge::Left),
+            (_, true, _, _) => Some(ScreenEdge::Right),
+            _ => None,
+        }
+    }
+
+    /// The strip a trigger window covers: the corner pixel, or the side
+    /// between the corners
+    pub fn strip(self, width: u16, height: u16) -> Rectangle {
+        let (right, bottom) = (width as i16 - 1, height as i16 - 1);
+        let side = |len: u16| len.saturating_sub(2).max(1);
+        match self {
+            ScreenEdge::TopLeft => Rectangle { x: 0, y: 0, width: 1, height: 1 },
+            ScreenEdge::TopRight => Rectangle { x: right, y: 0, width: 1, height: 1 },
+            ScreenEdge::BottomLeft => Rectangle { x: 0, y: bottom, width: 1, height: 1 },
+            ScreenEdge::BottomRight => Rectangle { x: right, y: bottom, width: 1, height: 1 },
+            ScreenEdge::Top => Rectangle { x: 1, y: 0, width: side(width), height: 1 },
+            ScreenEdge::Bottom => Rectangle { x: 1, y: bottom, width: side(width), height: 1 },
+            ScreenEdge::Left => Rectangle { x: 0, y: 1, width: 1, height: side(height) },
+            ScreenEdge::Right => Rectangle { x: right, y: 1, width: 1, height: side(height) },
+        }
+    }
+}
+
+/// What resting the pointer on an edge or corner does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeAction {
+    /// Show all windows side by side
+    Overview,
+    /// Bring the panels along the edge above the windows covering them
+    RevealPanel,
+    NextWorkspace,
+    PreviousWorkspace,
+}
+
+impl EdgeAction {
+    /// Parse the names in `/general/edge_actions/*`; "none" and unknown
+    /// names leave the edge alone
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "overview" => Some(EdgeAction::Overview),
+            "reveal_panel" => Some(EdgeAction::RevealPanel),
+            "next_workspace" => Some(EdgeAction::NextWorkspace),
+            "previous_workspace" => Some(EdgeAction::PreviousWorkspace),
+            _ => None,
+        }
+    }
+}
+
+/// The edge and corner actions, and the InputOnly strips along the screen
+/// edges that notice the pointer arriving
+#[derive(Debug, Default)]
+pub struct EdgeTriggers {
+    actions: HashMap<ScreenEdge, EdgeAction>,
+    strips: Vec<(Window, ScreenEdge)>,
+    /// How long the pointer rests on a strip before its action runs
+    pub dwell: Duration,
+    /// Switch workspace when a window is dragged against the left or
+    /// right edge
+    pub wrap_windows: bool,
+    /// How long a dragged window rests against the edge before that
+    pub wrap_delay: Duration,
+}
+
+impl EdgeTriggers {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let actions = ScreenEdge::ALL
+            .into_iter()
+            .filter_map(|edge| {
+                let name = settings.edge_actions.get(edge.name())?;
+                EdgeAction::from_name(name).map(|action| (edge, action))
+            })
+            .collect();
+        Self {
+            actions,
+            strips: Vec::new(),
+            dwell: Duration::from_millis(u64::from(settings.edge_dwell)),
+            wrap_windows: settings.wrap_windows,
+            wrap_delay: Duration::from_millis(u64::from(settings.wrap_delay)),
+        }
+    }
+
+    pub fn action(&self, edge: ScreenEdge) -> Option<EdgeAction> {
+        self.actions.get(&edge).copied()
+    }
+
+    /// Put strips on the edges with an action, on top of everything, for
+    /// a screen of the context's size; called again when that changes
+    pub fn create_strips(&mut self, ctx: &Context) -> Result<()> {
+        self.destroy_strips(&ctx.conn);
+        for &edge in self.actions.keys() {
+            let rect = edge.strip(ctx.screen_width, ctx.screen_height);
+            let window = ctx.conn.generate_id()?;
+            ctx.conn.create_window(
+                0,
+                window,
+                ctx.root_window,
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+                0,
+                WindowClass::INPUT_ONLY,
+                0,
+                &CreateWindowAux::new().override_redirect(1).event_mask(EventMask::ENTER_WINDOW | EventMask::LEAVE_WINDOW),
+            )?;
+            ctx.conn.map_window(window)?;
+            self.strips.push((window, edge));
+        }
+        Ok(())
+    }
+
+    pub fn destroy_strips(&mut self, conn: &impl Connection) {
+        for (window, _) in self.strips.drain(..) {
+            let _ = conn.destroy_window(window);
+        }
+    }
+
+    /// Keep the strips above windows raised or mapped since
+    pub fn raise_strips(&self, conn: &impl Connection) {
+        for &(window, _) in &self.strips {
+            let _ = conn.configure_window(window, &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE));
+        }
+    }
+
+    /// The edge whose strip `window` is
+    pub fn edge_of(&self, window: Window) -> Option<ScreenEdge> {
+        self.strips.iter().find(|&&(w, _)| w == window).map(|&(_, edge)| edge)
+    }
+}
+
+/// The edge the pointer rests on, which triggers once it has stayed
+/// there long enough
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EdgeDwell {
+    pending: Option<(ScreenEdge, Instant)>,
+}
+
+impl EdgeDwell {
+    /// The pointer arrived at `edge` at `now`; staying on it keeps the
+    /// first deadline
+    pub fn enter(&mut self, edge: ScreenEdge, now: Instant, delay: Duration) {
+        if !self.pending.is_some_and(|(e, _)| e == edge) {
+            self.pending = Some((edge, now + delay));
+        }
+    }
+
+    /// The pointer left whatever edge it was on
+    pub fn leave(&mut self) {
+        self.pending = None;
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.map(|(_, at)| at)
+    }
+
+    /// The edge due to trigger at `now`, if any
+    pub fn take_due(&mut self, now: Instant) -> Option<ScreenEdge> {
+        let (edge, at) = self.pending?;
+        if now < at {
+            return None;
+        }
+        self.pending = None;
+        Some(edge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edge_at() {
+        assert_eq!(ScreenEdge::at(0, 0, 1920, 1080), Some(ScreenEdge::TopLeft));
+        assert_eq!(ScreenEdge::at(1919, 1079, 1920, 1080), Some(ScreenEdge::BottomRight));
+        assert_eq!(ScreenEdge::at(900, 1079, 1920, 1080), Some(ScreenEdge::Bottom));
+        assert_eq!(ScreenEdge::at(1919, 500, 1920, 1080), Some(ScreenEdge::Right));
+        assert_eq!(ScreenEdge::at(900, 500, 1920, 1080), None);
+    }
+
+    #[test]
+    fn test_strips() {
+        assert_eq!(ScreenEdge::BottomRight.strip(1920, 1080), Rectangle { x: 1919, y: 1079, width: 1, height: 1 });
+        assert_eq!(ScreenEdge::Bottom.strip(1920, 1080), Rectangle { x: 1, y: 1079, width: 1918, height: 1 });
+        assert_eq!(ScreenEdge::Left.strip(1920, 1080), Rectangle { x: 0, y: 1, width: 1, height: 1078 });
+        // Every strip lies where the pointer counts as on that edge
+        for edge in ScreenEdge::ALL {
+            let rect = edge.strip(1920, 1080);
+            assert_eq!(ScreenEdge::at(rect.x, rect.y, 1920, 1080), Some(edge));
+        }
+    }
+
+    #[test]
+    fn test_dwell() {
+        let start = Instant::now();
+        let delay = Duration::from_millis(200);
+        let mut dwell = EdgeDwell::default();
+        dwell.enter(ScreenEdge::Right, start, delay);
+        // Motion along the same edge does not start over
+        dwell.enter(ScreenEdge::Right, start + Duration::from_millis(150), delay);
+        assert_eq!(dwell.take_due(start + Duration::from_millis(199)), None);
+        assert_eq!(dwell.take_due(start + delay), Some(ScreenEdge::Right));
+        assert_eq!(dwell.deadline(), None);
+
+        dwell.enter(ScreenEdge::TopLeft, start, delay);
+        dwell.leave();
+        assert_eq!(dwell.take_due(start + delay), None);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::Window;
+
+/// Time between animation frames while something fades, about 60 Hz
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    from: f64,
+    to: f64,
+    start: Instant,
+}
+
+impl Fade {
+    /// Opacity factor at `now`; a fade over part of the way takes that part
+    /// of `duration`, so one reversed midway gets back at the same speed
+    fn value(&self, duration: Duration, now: Instant) -> f64 {
+        let span = duration.as_secs_f64() * (self.to - self.from).abs();
+        if span <= 0.0 {
+            return self.to;
+        }
+        let progress = now.saturating_duration_since(self.start).as_secs_f64() / span;
+        if progress >= 1.0 {
+            return self.to;
+        }
+        self.from + (self.to - self.from) * progress
+    }
+}
+
+/// Windows fading in as they appear and out as they are hidden, as an
+/// opacity factor the compositor multiplies their opacity with
+#[derive(Debug, Clone)]
+pub struct Fades {
+    duration: Duration,
+    fades: HashMap<Window, Fade>,
+}
+
+impl Fades {
+    /// Fades taking `duration` from invisible to opaque; zero turns them off
+    pub fn new(duration: Duration) -> Self {
+        Self { duration, fades: HashMap::new() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.duration.is_zero()
+    }
+
+    /// Fade `window` in, from wherever a fade out left it. False when
+    /// fades are off.
+    pub fn fade_in(&mut self, window: Window, now: Instant) -> bool {
+        self.start(window, 1.0, now)
+    }
+
+    /// Fade `window` out; it is to be hidden once `finish` returns it.
+    /// False when fades are off, and the window should be hidden now.
+    pub fn fade_out(&mut self, window: Window, now: Instant) -> bool {
+        self.start(window, 0.0, now)
+    }
+
+    fn start(&mut self, window: Window, to: f64, now: Instant) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+        let from = self.opacity(window, now).unwrap_or(1.0 - to);
+        self.fades.insert(window, Fade { from, to, start: now });
+        true
+    }
+
+    /// Opacity factor of `window` at `now`, or `None` when it isn't fading
+    pub fn opacity(&self, window: Window, now: Instant) -> Option<f64> {
+        self.fades.get(&window).map(|fade| fade.value(self.duration, now))
+    }
+
+    pub fn is_fading(&self, window: Window) -> bool {
+        self.fades.contains_key(&window)
+    }
+
+    /// Whether anything still fades, so the screen needs repainting
+    pub fn is_active(&self) -> bool {
+        !self.fades.is_empty()
+    }
+
+    /// Drop the fades that are over at `now`, returning the windows that
+    /// faded out
+    pub fn finish(&mut self, now: Instant) -> Vec<Window> {
+        let duration = self.duration;
+        let done: Vec<(Window, f64)> = self
+            .fades
+            .iter()
+            .filter(|(_, fade)| fade.value(duration, now) == fade.to)
+            .map(|(&window, fade)| (window, fade.to))
+            .collect();
+        done.into_iter()
+            .filter_map(|(window, to)| {
+                self.fades.remove(&window);
+                (to == 0.0).then_some(window)
+            })
+            .collect()
+    }
+
+    /// Forget `window`, e.g. once it is gone
+    pub fn remove(&mut self, window: Window) {
+        self.fades.remove(&window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade_in_and_out() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut fades = Fades::new(Duration::from_millis(100));
+
+        assert!(fades.fade_in(1, start));
+        assert!(fades.fade_out(2, start));
+        assert_eq!(fades.opacity(1, ms(50)), Some(0.5));
+        assert_eq!(fades.opacity(2, ms(25)), Some(0.75));
+        assert_eq!(fades.opacity(3, ms(25)), None);
+
+        // Only the window that faded out is to be hidden
+        assert!(fades.finish(ms(50)).is_empty());
+        assert_eq!(fades.finish(ms(100)), [2]);
+        assert!(!fades.is_active());
+    }
+
+    #[test]
+    fn test_reversed_fade() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut fades = Fades::new(Duration::from_millis(100));
+
+        // Minimized and restored before the fade out is over: back to
+        // opaque from 0.6, in the 40ms it took to get there
+        fades.fade_out(1, start);
+        fades.fade_in(1, ms(40));
+        assert!((fades.opacity(1, ms(70)).unwrap() - 0.9).abs() < 1e-9);
+        assert!(fades.finish(ms(100)).is_empty());
+        assert!(!fades.is_fading(1));
+
+        let mut off = Fades::new(Duration::ZERO);
+        assert!(!off.fade_out(1, start));
+        assert!(!off.is_active());
+    }
+}
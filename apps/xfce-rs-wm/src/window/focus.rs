@@ -0,0 +1,176 @@
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::Window;
+
+use crate::window::settings::Settings;
+
+/// How windows get the focus from the pointer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusModel {
+    /// Clicking a window focuses it
+    Click,
+    /// The window the pointer rests over gets the focus, which stays when
+    /// the pointer goes on to the desktop
+    Sloppy,
+}
+
+/// The focus settings, from xfwm4's
+#[derive(Debug, Clone, Copy)]
+pub struct FocusPolicy {
+    pub model: FocusModel,
+    /// How long the pointer rests on a window before it gets the focus,
+    /// with sloppy focus
+    pub delay: Duration,
+    /// Raise windows as the pointer focuses them
+    pub raise_on_focus: bool,
+    /// Raise windows clicked into; clicking the frame always raises
+    pub raise_on_click: bool,
+    /// Focus windows as they appear
+    pub focus_new: bool,
+    /// Leave the focus be when a window appears or asks for it without the
+    /// user having done anything since the focused one was used
+    pub prevent_focus_stealing: bool,
+}
+
+impl FocusPolicy {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            model: if settings.click_to_focus { FocusModel::Click } else { FocusModel::Sloppy },
+            delay: Duration::from_millis(u64::from(settings.focus_delay)),
+            raise_on_focus: settings.raise_on_focus,
+            raise_on_click: settings.raise_on_click,
+            focus_new: settings.focus_new,
+            prevent_focus_stealing: settings.prevent_focus_stealing,
+        }
+    }
+
+    /// Whether a window appearing with `user_time`, its
+    /// `_NET_WM_USER_TIME` if it has one, takes the focus from one last
+    /// used at `focused_time`, `None` when nothing has it
+    pub fn focuses_new(&self, user_time: Option<u32>, focused_time: Option<u32>) -> bool {
+        match (user_time, focused_time) {
+            // Asked not to be focused as it maps
+            (Some(0), _) => false,
+            (_, None) => true,
+            _ if !self.focus_new => false,
+            (Some(time), Some(focused)) if self.prevent_focus_stealing => !timestamp_is_before(time, focused),
+            _ => true,
+        }
+    }
+
+    /// Whether an application's `_NET_ACTIVE_WINDOW` request from `time`
+    /// takes the focus from a window last used at `focused_time`. Pagers
+    /// and taskbars act for the user, so theirs always do.
+    pub fn allows_activation(&self, from_application: bool, time: u32, focused_time: Option<u32>) -> bool {
+        match focused_time {
+            Some(focused) if from_application && self.prevent_focus_stealing && time != 0 => !timestamp_is_before(time, focused),
+            _ => true,
+        }
+    }
+}
+
+/// Whether X server time `time1` is before `time2`, across the wrap of
+/// the 32-bit millisecond counter. Zero, the current time, comes first.
+pub fn timestamp_is_before(time1: u32, time2: u32) -> bool {
+    if time1 == 0 {
+        return true;
+    }
+    if time2 == 0 {
+        return false;
+    }
+    let diff = time2.wrapping_sub(time1);
+    diff != 0 && diff < (u32::MAX >> 1)
+}
+
+/// With sloppy focus, the window the pointer went into, which gets the
+/// focus once the pointer has rested there for the focus delay
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PointerFocus {
+    pending: Option<(Window, Instant)>,
+}
+
+impl PointerFocus {
+    /// The pointer went into `window` at `now`
+    pub fn enter(&mut self, window: Window, now: Instant, delay: Duration) {
+        self.pending = Some((window, now + delay));
+    }
+
+    /// The pointer left `window` before it got the focus
+    pub fn leave(&mut self, window: Window) {
+        if self.pending.is_some_and(|(w, _)| w == window) {
+            self.pending = None;
+        }
+    }
+
+    /// When the pending window is due the focus
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.map(|(_, at)| at)
+    }
+
+    /// The window due the focus at `now`, if any
+    pub fn take_due(&mut self, now: Instant) -> Option<Window> {
+        let (window, at) = self.pending?;
+        if now < at {
+            return None;
+        }
+        self.pending = None;
+        Some(window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> FocusPolicy {
+        FocusPolicy::from_settings(&Settings::default())
+    }
+
+    #[test]
+    fn test_new_window_focus() {
+        let policy = policy();
+        assert!(policy.focuses_new(None, Some(500)));
+        assert!(policy.focuses_new(Some(600), Some(500)));
+        // Mapped from something the user did before using the focused one
+        assert!(!policy.focuses_new(Some(400), Some(500)));
+        assert!(!policy.focuses_new(Some(0), None));
+        assert!(policy.focuses_new(Some(400), None));
+        // Across the wrap of the server clock
+        assert!(policy.focuses_new(Some(5), Some(u32::MAX - 5)));
+
+        let quiet = FocusPolicy { focus_new: false, ..policy };
+        assert!(!quiet.focuses_new(Some(600), Some(500)));
+        assert!(quiet.focuses_new(None, None));
+        let trusting = FocusPolicy { prevent_focus_stealing: false, ..policy };
+        assert!(trusting.focuses_new(Some(400), Some(500)));
+    }
+
+    #[test]
+    fn test_activation() {
+        let policy = policy();
+        assert!(!policy.allows_activation(true, 400, Some(500)));
+        assert!(policy.allows_activation(true, 500, Some(500)));
+        assert!(policy.allows_activation(false, 400, Some(500)));
+        assert!(policy.allows_activation(true, 400, None));
+    }
+
+    #[test]
+    fn test_focus_delay() {
+        let start = Instant::now();
+        let delay = Duration::from_millis(250);
+        let mut pointer = PointerFocus::default();
+
+        pointer.enter(1, start, delay);
+        pointer.enter(2, start + Duration::from_millis(100), delay);
+        assert_eq!(pointer.take_due(start + Duration::from_millis(300)), None);
+        assert_eq!(pointer.take_due(start + Duration::from_millis(350)), Some(2));
+        assert_eq!(pointer.deadline(), None);
+
+        // Passed over on the way somewhere else
+        pointer.enter(3, start, delay);
+        pointer.leave(2);
+        assert!(pointer.deadline().is_some());
+        pointer.leave(3);
+        assert_eq!(pointer.take_due(start + delay), None);
+    }
+}
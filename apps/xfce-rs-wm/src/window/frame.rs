@@ -1,4 +1,8 @@
 
+use x11rb::protocol::xproto::Window;
+
+use crate::window::theme::{ButtonLayout, ButtonState};
+
 pub const TITLE_HEIGHT: u16 = 24;
 pub const BORDER_WIDTH: u16 = 4;
 
@@ -16,7 +20,7 @@ impl FrameGeometry {
 
     pub const RESIZE_HANDLE_SIZE: i16 = 10;
 
-    pub fn hit_test(width: u16, height: u16, x: i16, y: i16) -> FramePart {
+    pub fn hit_test(buttons: &ButtonLayout, width: u16, height: u16, x: i16, y: i16) -> FramePart {
         // x, y are relative to the frame window (0,0 is top-left of frame)
         
         let w = width as i16;
@@ -42,25 +46,9 @@ impl FrameGeometry {
         if x > w - border { return FramePart::RightBorder; }
         if y > h - border { return FramePart::BottomBorder; }
         
-        // Buttons
-        // Close Button (Right - 20)
-        let close_x = w - 20;
-        let btn_y = 6;
-        let btn_size = 12;
-        if y >= btn_y && y < btn_y + btn_size && x >= close_x && x < close_x + btn_size {
-            return FramePart::CloseButton;
-        }
-
-        // Maximize Button (Right - 40)
-        let max_x = w - 40;
-        if y >= btn_y && y < btn_y + btn_size && x >= max_x && x < max_x + btn_size {
-            return FramePart::MaximizeButton;
-        }
-
-        // Minimize Button (Right - 60)
-        let min_x = w - 60;
-        if y >= btn_y && y < btn_y + btn_size && x >= min_x && x < min_x + btn_size {
-            return FramePart::MinimizeButton;
+        // Buttons, where the theme's layout puts them
+        if let Some(part) = buttons.button_at(width, x, y) {
+            return part;
         }
 
         // Top Edge vs TitleBar
@@ -77,7 +65,7 @@ impl FrameGeometry {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum FramePart {
     TitleBar,
     ClientArea,
@@ -92,9 +80,29 @@ pub enum FramePart {
     CloseButton,
     MaximizeButton,
     MinimizeButton,
+    ShadeButton,
+    MenuButton,
     None,
 }
 
+impl FramePart {
+    pub fn is_button(self) -> bool {
+        matches!(self, Self::CloseButton | Self::MaximizeButton | Self::MinimizeButton | Self::ShadeButton | Self::MenuButton)
+    }
+}
+
+/// The button of `window`'s frame to draw out of its resting state, from
+/// the button under the pointer and the one held down, if any: lit when
+/// nothing is held, pressed while the held one is under the pointer
+pub fn button_highlight(window: Window, hovered: Option<(Window, FramePart)>, pressed: Option<(Window, FramePart)>) -> Option<(FramePart, ButtonState)> {
+    let (hover_window, part) = hovered.filter(|&(w, _)| w == window)?;
+    match pressed {
+        None => Some((part, ButtonState::Prelight)),
+        Some(held) if held == (hover_window, part) => Some((part, ButtonState::Pressed)),
+        Some(_) => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,20 +114,32 @@ mod tests {
         let h = 632;
         let _border = 4;
         let _title = 24;
+        let buttons = ButtonLayout::default();
         
         // Top Left Corner
-        assert_eq!(FrameGeometry::hit_test(w, h, 0, 0), FramePart::CornerTopLeft);
+        assert_eq!(FrameGeometry::hit_test(&buttons, w, h, 0, 0), FramePart::CornerTopLeft);
         
         // Title Bar (click at 100, 10)
-        assert_eq!(FrameGeometry::hit_test(w, h, 100, 10), FramePart::TitleBar);
+        assert_eq!(FrameGeometry::hit_test(&buttons, w, h, 100, 10), FramePart::TitleBar);
         
         // Close Button (Right - 20) = 788. Button size 12. click at 790, 8
-        assert_eq!(FrameGeometry::hit_test(w, h, 790, 8), FramePart::CloseButton);
+        assert_eq!(FrameGeometry::hit_test(&buttons, w, h, 790, 8), FramePart::CloseButton);
         
         // Client Area (click at 100, 100)
-        assert_eq!(FrameGeometry::hit_test(w, h, 100, 100), FramePart::ClientArea);
+        assert_eq!(FrameGeometry::hit_test(&buttons, w, h, 100, 100), FramePart::ClientArea);
         
         // Bottom Right
-        assert_eq!(FrameGeometry::hit_test(w, h, 807, 631), FramePart::CornerBottomRight);
+        assert_eq!(FrameGeometry::hit_test(&buttons, w, h, 807, 631), FramePart::CornerBottomRight);
+    }
+
+    #[test]
+    fn test_button_highlight() {
+        let close = Some((1, FramePart::CloseButton));
+        assert_eq!(button_highlight(1, close, None), Some((FramePart::CloseButton, ButtonState::Prelight)));
+        assert_eq!(button_highlight(2, close, None), None);
+        assert_eq!(button_highlight(1, close, close), Some((FramePart::CloseButton, ButtonState::Pressed)));
+        // Held down on close, then dragged over maximize
+        assert_eq!(button_highlight(1, Some((1, FramePart::MaximizeButton)), close), None);
+        assert_eq!(button_highlight(1, None, close), None);
     }
 }
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use tracing::{debug, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, GrabMode, ModMask, Window};
+
+use crate::window::tiling::SnapZone;
+
+/// Shortcuts used when the channel has none, in xfwm4's own format
+pub const DEFAULT_SHORTCUTS: &[(&str, &str)] = &[
+    ("<Alt>F4", "close_window_key"),
+    ("<Alt>F7", "move_window_key"),
+    ("<Alt>F8", "resize_window_key"),
+    ("<Alt>F9", "hide_window_key"),
+    ("<Alt>F10", "maximize_window_key"),
+    ("<Alt>F11", "fullscreen_key"),
+    ("<Alt>Tab", "cycle_windows_key"),
+    ("<Alt><Shift>Tab", "cycle_reverse_windows_key"),
+    ("<Alt>F2", "launcher_key"),
+    ("<Primary><Alt>l", "lock_screen_key"),
+    ("<Alt>space", "popup_menu_key"),
+    ("<Super>Left", "tile_left_key"),
+    ("<Super>Right", "tile_right_key"),
+    ("<Super>Up", "tile_up_key"),
+    ("<Super>Down", "tile_down_key"),
+    ("<Super>Tab", "cycle_tiled_key"),
+    ("<Super>t", "toggle_tiling_key"),
+    ("<Super>w", "overview_key"),
+    ("<Primary>F1", "workspace_1_key"),
+    ("<Primary>F2", "workspace_2_key"),
+    ("<Primary>F3", "workspace_3_key"),
+    ("<Primary>F4", "workspace_4_key"),
+];
+
+/// Keys read while the window switcher or the window menu has the
+/// keyboard: Escape, Return, Enter on the keypad, the arrows and space
+const NAVIGATION_KEYSYMS: [u32; 8] = [0xff1b, 0xff0d, 0xff8d, 0xff51, 0xff52, 0xff53, 0xff54, 0x0020];
+
+/// Lock keys that must not keep a shortcut from matching
+pub(crate) const IGNORED_MODIFIERS: [u16; 4] = [0, 0x02, 0x10, 0x12]; // none, Lock, Mod2 (Num Lock), both
+
+/// What a shortcut does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Close,
+    Minimize,
+    Maximize,
+    Fullscreen,
+    /// Move or resize the focused window with the arrow keys
+    Move,
+    Resize,
+    CycleWindows,
+    CycleWindowsReverse,
+    /// Switch to a workspace, counting from 0
+    Workspace(u32),
+    /// Tile the focused window to part of its monitor
+    Tile(SnapZone),
+    /// Focus the next tiled window, or move the windows of the automatic
+    /// layout up a place
+    CycleTiled,
+    /// Turn the automatic layout on or off for the current workspace
+    ToggleTiling,
+    /// Open the window menu of the focused window
+    WindowMenu,
+    Launcher,
+    /// Show all windows of the workspace side by side
+    Overview,
+    /// Lock the screen and turn the display off
+    LockScreen,
+}
+
+impl Action {
+    /// Parse xfwm4's action names, e.g. "close_window_key" or "workspace_2_key"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "close_window_key" => Action::Close,
+            "hide_window_key" => Action::Minimize,
+            "maximize_window_key" => Action::Maximize,
+            "fullscreen_key" => Action::Fullscreen,
+            "move_window_key" => Action::Move,
+            "resize_window_key" => Action::Resize,
+            "cycle_windows_key" => Action::CycleWindows,
+            "cycle_reverse_windows_key" => Action::CycleWindowsReverse,
+            "tile_left_key" => Action::Tile(SnapZone::Left),
+            "tile_right_key" => Action::Tile(SnapZone::Right),
+            "tile_up_key" => Action::Tile(SnapZone::TopHalf),
+            "tile_down_key" => Action::Tile(SnapZone::BottomHalf),
+            "tile_up_left_key" => Action::Tile(SnapZone::TopLeft),
+            "tile_up_right_key" => Action::Tile(SnapZone::TopRight),
+            "tile_down_left_key" => Action::Tile(SnapZone::BottomLeft),
+            "tile_down_right_key" => Action::Tile(SnapZone::BottomRight),
+            "tile_left_third_key" => Action::Tile(SnapZone::LeftThird),
+            "tile_center_third_key" => Action::Tile(SnapZone::CenterThird),
+            "tile_right_third_key" => Action::Tile(SnapZone::RightThird),
+            "cycle_tiled_key" => Action::CycleTiled,
+            "toggle_tiling_key" => Action::ToggleTiling,
+            "popup_menu_key" => Action::WindowMenu,
+            "launcher_key" => Action::Launcher,
+            "overview_key" => Action::Overview,
+            "lock_screen_key" => Action::LockScreen,
+            _ => {
+                let number: u32 = name.strip_prefix("workspace_")?.strip_suffix("_key")?.parse().ok()?;
+                Action::Workspace(number.checked_sub(1)?)
+            }
+        };
+        Some(action)
+    }
+}
+
+/// A key with modifiers, e.g. `<Primary><Alt>Delete`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub modifiers: u16,
+    pub keysym: u32,
+}
+
+impl Chord {
+    pub fn parse(chord: &str) -> Option<Self> {
+        let mut modifiers = 0u16;
+        let mut rest = chord.trim();
+        while let Some(tail) = rest.strip_prefix('<') {
+            let (name, tail) = tail.split_once('>')?;
+            modifiers |= match name.to_ascii_lowercase().as_str() {
+                "shift" => u16::from(ModMask::SHIFT),
+                "primary" | "control" | "ctrl" => u16::from(ModMask::CONTROL),
+                "alt" | "mod1" => u16::from(ModMask::M1),
+                "super" | "mod4" => u16::from(ModMask::M4),
+                _ => return None,
+            };
+            rest = tail;
+        }
+        Some(Self { modifiers, keysym: keysym_from_name(rest)? })
+    }
+}
+
+/// Keysym of a key name as used in shortcut chords, for the keys
+/// shortcuts are made of
+fn keysym_from_name(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphanumeric() {
+            // Latin-1 keysyms are the lowercase characters
+            return Some(c.to_ascii_lowercase() as u32);
+        }
+    }
+    if let Some(number) = name.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        return (1..=24).contains(&number).then(|| 0xffbd + number);
+    }
+    let keysym = match name {
+        "space" => 0x0020,
+        "Tab" => 0xff09,
+        "Return" => 0xff0d,
+        "Escape" => 0xff1b,
+        "Delete" => 0xffff,
+        "BackSpace" => 0xff08,
+        "Home" => 0xff50,
+        "Left" => 0xff51,
+        "Up" => 0xff52,
+        "Right" => 0xff53,
+        "Down" => 0xff54,
+        "Page_Up" => 0xff55,
+        "Page_Down" => 0xff56,
+        "End" => 0xff57,
+        "Print" => 0xff61,
+        "Insert" => 0xff63,
+        _ => return None,
+    };
+    Some(keysym)
+}
+
+/// Keycodes producing `keysym` in a `GetKeyboardMapping` reply covering
+/// keycodes from `min_keycode`, `per_keycode` keysyms each
+pub fn keycodes_for(keysym: u32, min_keycode: u8, per_keycode: u8, keysyms: &[u32]) -> Vec<u8> {
+    if per_keycode == 0 {
+        return Vec::new();
+    }
+    keysyms
+        .chunks(per_keycode as usize)
+        .enumerate()
+        // Unshifted and shifted columns; shortcuts ignore the others
+        .filter(|(_, row)| row.iter().take(2).any(|&sym| sym == keysym))
+        .filter_map(|(offset, _)| u8::try_from(min_keycode as usize + offset).ok())
+        .collect()
+}
+
+/// Keycodes of the modifiers in `mask`, from a `GetModifierMapping` reply
+/// listing `per_modifier` keycodes for each of Shift, Lock, Control and
+/// Mod1 to Mod5
+pub fn modifier_keycodes(mask: u16, per_modifier: u8, keycodes: &[u8]) -> Vec<u8> {
+    if per_modifier == 0 {
+        return Vec::new();
+    }
+    keycodes
+        .chunks(per_modifier as usize)
+        .enumerate()
+        .filter(|(modifier, _)| mask & (1 << modifier) != 0)
+        .flat_map(|(_, row)| row.iter().copied().filter(|&keycode| keycode != 0))
+        .collect()
+}
+
+/// Keycodes of the keys that produce the modifiers in `mask`, e.g. both
+/// Alt keys for Mod1
+pub fn held_modifier_keycodes<C: Connection>(conn: &C, mask: u16) -> Result<Vec<u8>> {
+    let mapping = conn.get_modifier_mapping()?.reply()?;
+    Ok(modifier_keycodes(mask, mapping.keycodes_per_modifier(), &mapping.keycodes))
+}
+
+/// Shortcuts from the xfce4-keyboard-shortcuts channel and the keys
+/// grabbed for them
+#[derive(Debug, Default)]
+pub struct Keybindings {
+    bindings: Vec<(Chord, Action)>,
+    /// Grabbed keycode and modifiers to action
+    grabbed: HashMap<(u8, u16), Action>,
+    /// Keycode to keysym of the navigation keys
+    navigation: HashMap<u8, u32>,
+}
+
+impl Keybindings {
+    /// Bindings from `shortcuts`, chord to action name, or the defaults
+    /// when there are none
+    pub fn new(shortcuts: &HashMap<String, String>) -> Self {
+        let pairs: Vec<(&str, &str)> = if shortcuts.is_empty() {
+            DEFAULT_SHORTCUTS.to_vec()
+        } else {
+            shortcuts.iter().map(|(chord, action)| (chord.as_str(), action.as_str())).collect()
+        };
+        let bindings = pairs
+            .into_iter()
+            .filter_map(|(chord, action)| match (Chord::parse(chord), Action::from_name(action)) {
+                (Some(chord), Some(action)) => Some((chord, action)),
+                _ => {
+                    debug!("Ignoring shortcut {} = {}", chord, action);
+                    None
+                }
+            })
+            .collect();
+        Self { bindings, grabbed: HashMap::new(), navigation: HashMap::new() }
+    }
+
+    /// Grab every binding on `root`, replacing earlier grabs. Called again
+    /// when the keyboard mapping changes.
+    pub fn grab<C: Connection>(&mut self, conn: &C, root: Window) -> Result<()> {
+        conn.ungrab_key(x11rb::protocol::xproto::Grab::ANY, root, ModMask::ANY)?;
+        self.grabbed.clear();
+
+        let setup = conn.setup();
+        let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn.get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?.reply()?;
+
+        for &(chord, action) in &self.bindings {
+            let keycodes = keycodes_for(chord.keysym, min_keycode, mapping.keysyms_per_keycode, &mapping.keysyms);
+            if keycodes.is_empty() {
+                warn!("No key produces keysym {:#x}, for {:?}", chord.keysym, action);
+            }
+            for keycode in keycodes {
+                for ignored in IGNORED_MODIFIERS {
+                    let modifiers = ModMask::from(chord.modifiers | ignored);
+                    if let Err(e) = conn.grab_key(false, root, modifiers, keycode, GrabMode::ASYNC, GrabMode::ASYNC) {
+                        warn!("Failed to grab keycode {} for {:?}: {}", keycode, action, e);
+                    }
+                }
+                self.grabbed.insert((keycode, chord.modifiers), action);
+            }
+        }
+        self.navigation = NAVIGATION_KEYSYMS
+            .iter()
+            .flat_map(|&keysym| {
+                keycodes_for(keysym, min_keycode, mapping.keysyms_per_keycode, &mapping.keysyms).into_iter().map(move |keycode| (keycode, keysym))
+            })
+            .collect();
+        debug!("Grabbed {} shortcut keys", self.grabbed.len());
+        Ok(())
+    }
+
+    /// The action bound to a key press, by keycode and modifier state
+    pub fn action_for(&self, keycode: u8, state: u16) -> Option<Action> {
+        let relevant = u16::from(ModMask::SHIFT | ModMask::CONTROL | ModMask::M1 | ModMask::M4);
+        self.grabbed.get(&(keycode, state & relevant)).copied()
+    }
+
+    pub fn is_escape(&self, keycode: u8) -> bool {
+        self.navigation_keysym(keycode) == Some(0xff1b)
+    }
+
+    /// Keysym of `keycode` if it is one of the navigation keys
+    pub fn navigation_keysym(&self, keycode: u8) -> Option<u32> {
+        self.navigation.get(&keycode).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chords_and_actions() {
+        assert_eq!(
+            Chord::parse("<Primary><Alt>Delete"),
+            Some(Chord { modifiers: u16::from(ModMask::CONTROL | ModMask::M1), keysym: 0xffff })
+        );
+        assert_eq!(Chord::parse("<Super>T"), Some(Chord { modifiers: u16::from(ModMask::M4), keysym: 0x74 }));
+        assert_eq!(Chord::parse("<Alt>F4").map(|chord| chord.keysym), Some(0xffc1));
+        assert_eq!(Chord::parse("<Hyper>a"), None);
+        assert_eq!(Chord::parse("<Alt>NoSuchKey"), None);
+
+        assert_eq!(Action::from_name("workspace_3_key"), Some(Action::Workspace(2)));
+        assert_eq!(Action::from_name("workspace_0_key"), None);
+        assert_eq!(Action::from_name("tile_left_key"), Some(Action::Tile(SnapZone::Left)));
+        assert_eq!(Action::from_name("tile_down_right_key"), Some(Action::Tile(SnapZone::BottomRight)));
+        assert_eq!(Action::from_name("popup_menu_key"), Some(Action::WindowMenu));
+        assert_eq!(Keybindings::new(&HashMap::new()).bindings.len(), DEFAULT_SHORTCUTS.len());
+    }
+
+    #[test]
+    fn test_keycodes_for() {
+        // Keycodes 8..=10 with two keysyms each: a/A, Tab, 1/exclam
+        let keysyms = [0x61, 0x41, 0xff09, 0, 0x31, 0x21];
+        assert_eq!(keycodes_for(0x61, 8, 2, &keysyms), [8]);
+        assert_eq!(keycodes_for(0xff09, 8, 2, &keysyms), [9]);
+        assert_eq!(keycodes_for(0x21, 8, 2, &keysyms), [10]);
+        assert!(keycodes_for(0xffc1, 8, 2, &keysyms).is_empty());
+        assert!(keycodes_for(0x61, 8, 0, &keysyms).is_empty());
+
+        // Shift: 50, 62; Lock: 66; Control: 37; Mod1: 64, 108
+        let modifiers = [50, 62, 66, 0, 37, 0, 64, 108, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(modifier_keycodes(u16::from(ModMask::M1), 2, &modifiers), [64, 108]);
+        assert_eq!(modifier_keycodes(u16::from(ModMask::M1 | ModMask::CONTROL), 2, &modifiers), [37, 64, 108]);
+        assert!(modifier_keycodes(u16::from(ModMask::M4), 2, &modifiers).is_empty());
+    }
+}
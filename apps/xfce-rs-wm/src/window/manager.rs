@@ -1,36 +1,53 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use anyhow::Result;
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::{Window, ConnectionExt, CreateWindowAux, WindowClass, EventMask, AtomEnum, PropMode, MapState, SubwindowMode, ConfigWindow, ConfigureWindowAux};
+use x11rb::protocol::xproto::{Window, ConnectionExt, CreateWindowAux, WindowClass, EventMask, AtomEnum, PropMode, MapState, SubwindowMode, ConfigWindow, ConfigureWindowAux, GrabMode, GrabStatus, KeyButMask, ModMask, SetMode};
 use x11rb::protocol::composite::ConnectionExt as CompositeExt;
 use x11rb::protocol::damage::{ConnectionExt as DamageExt, ReportLevel, Damage};
 use x11rb::protocol::render::{ConnectionExt as RenderExt, CreatePictureAux, Picture};
-use x11rb::protocol::xfixes::ConnectionExt as XFixesExt;
+use x11rb::protocol::xfixes::{ConnectionExt as XFixesExt, SelectionEventMask};
 use x11rb::protocol::shape::{ConnectionExt as ShapeExt, SO, SK};
 use x11rb::protocol::sync::ConnectionExt as SyncExt;
+use x11rb::protocol::randr::{ConnectionExt as RandrExt, NotifyMask};
 use x11rb::wrapper::ConnectionExt as _;
 use x11rb::protocol::Event;
-use tracing::{info, debug, warn, error};
+use tracing::{info, debug, warn, error, trace};
 
 use crate::core::context::Context;
+use crate::ewmh::client_list::ClientList;
+use crate::ewmh::desktops::{Workspaces, ALL_WORKSPACES};
 use crate::window::client::Client;
-use crate::window::frame::{FrameGeometry, FramePart, TITLE_HEIGHT, BORDER_WIDTH};
+use crate::window::frame::{button_highlight, FrameGeometry, FramePart, TITLE_HEIGHT, BORDER_WIDTH};
 use crate::window::draw::draw_decoration;
+use crate::window::theme::Theme;
 use crate::window::placement::{center_window, cascade_placement};
 use crate::window::cursors::Cursors;
-use crate::window::compositor::Compositor;
+use crate::window::compositor::{Compositor, Effects};
+use crate::window::fade::{self, Fades};
+use crate::window::opacity::OpacityRules;
+use crate::window::focus::{FocusModel, FocusPolicy, PointerFocus};
+use crate::window::damage::{FrameTimes, Repaint};
 use crate::window::settings::SettingsManager;
+use crate::window::keybindings::{held_modifier_keycodes, Action, Keybindings};
+use crate::window::switcher::{fit, Entry, Icon, Switcher, ICON_SIZE};
+use crate::window::overview::{self, Overview};
+use crate::window::menu::{self, Command, MenuKey, Outcome, WindowMenu};
+use crate::window::monitors::{self, Monitor};
+use crate::window::resize::{arrow_delta, is_resize_edge, moveresize_part, resize_rect, SizeHints, SizePopup, MOVERESIZE_CANCEL};
+use crate::window::tiling::{is_tileable, master_stack, SnapZone, Tiling};
+use crate::window::snapping::Snapping;
+use crate::window::ping::{ForceQuitDialog, Pings};
+use crate::window::startup::{self, Assembler, Launches, StartupMessage};
+use crate::window::edges::{EdgeAction, EdgeDwell, EdgeTriggers, ScreenEdge};
+use crate::window::dnd::DndRaise;
+use crate::window::dpms::{self, Dpms};
+use crate::window::zoom::Zoom;
+use crate::window::session::{self, SavedWindow, SessionState};
+use crate::window::restart;
 use crate::window::error::{ErrorTracker, log_warn};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum SnapZone {
-    None,
-    Left,
-    Right,
-    Top,
-}
-
-
 #[derive(Debug, Clone, Copy, PartialEq)]
 
 pub enum DragState {
@@ -45,8 +62,13 @@ pub enum DragState {
     },
     Resizing {
         window: Window,
+        /// The border or corner being dragged
+        edge: FramePart,
         start_pointer_x: i16,
         start_pointer_y: i16,
+        start_frame_x: i16,
+        start_frame_y: i16,
+        /// Client size when the drag began
         start_width: u16,
         start_height: u16,
     },
@@ -66,14 +88,77 @@ pub struct WindowManager {
     pub ctx: Context,
     pub clients: HashMap<Window, Client>,
     pub drag_state: DragState,
+    /// Pixels an arrow key takes the pointer in a move or resize
+    pub keyboard_step: u16,
     pub current_workspace: u32,
+    /// Number and names of the workspaces
+    pub workspaces: Workspaces,
     pub cursors: Cursors,
     pub compositor: Compositor,
+    /// Windows fading in or out
+    pub fades: Fades,
+    /// Opacity by window class, and dimming of windows without the focus
+    pub opacity_rules: OpacityRules,
+    /// What the next frame repaints
+    pub repaint: Repaint,
+    pub frame_times: FrameTimes,
     pub last_click_time: u32,
     pub last_click_window: Window,
     pub mru_stack: Vec<Window>,
     pub focused_window: Option<Window>,
+    /// Click or sloppy focus, and when windows are raised and focused
+    pub focus_policy: FocusPolicy,
+    /// Window sloppy focus is waiting to give the focus to
+    pub pointer_focus: PointerFocus,
+    pub client_list: ClientList,
+    /// Monitor layout from RandR, left to right
+    pub monitors: Vec<Monitor>,
     pub settings_manager: SettingsManager,
+    /// Look of the window frames
+    pub theme: Theme,
+    pub keybindings: Keybindings,
+    /// Automatic master/stack layout
+    pub tiling: Tiling,
+    /// Edge snapping of windows being moved
+    pub snapping: Snapping,
+    /// Windows of the last session yet to come back
+    pub session: SessionState,
+    /// Where the windows are saved when the session ends
+    pub session_path: Option<PathBuf>,
+    /// The Alt+Tab popup while it is up
+    pub switcher: Option<Switcher>,
+    /// The window operations menu while it is up
+    pub window_menu: Option<WindowMenu>,
+    /// All windows of the workspace side by side, while shown
+    pub overview: Option<Overview>,
+    /// Windows asked to close that have yet to answer `_NET_WM_PING`
+    pub pings: Pings,
+    /// Force Quit dialogs open for windows that stopped answering
+    pub force_quit: HashMap<Window, ForceQuitDialog>,
+    /// Startup notification messages still coming in
+    pub startup_messages: Assembler,
+    /// Applications launched with startup notification that have yet to
+    /// show a window
+    pub launches: Launches,
+    /// Hot corners and screen edge actions
+    pub edges: EdgeTriggers,
+    /// The screen edge the pointer, or a window dragged, rests on
+    pub edge_dwell: EdgeDwell,
+    /// Panels brought above the windows covering them from a screen edge,
+    /// until the pointer leaves them
+    pub revealed_panels: Vec<Window>,
+    /// Raising of the window a drag-and-drop rests over
+    pub dnd_raise: DndRaise,
+    /// Screen blanking and display power
+    pub dpms: Dpms,
+    /// Desktop zoom, magnifying the screen around the pointer
+    pub zoom: Zoom,
+    /// Titlebar button under the pointer, by client window
+    pub hovered_button: Option<(Window, FramePart)>,
+    /// Titlebar button the pointer went down on, until it is released
+    pub pressed_button: Option<(Window, FramePart)>,
+    /// Size readout while a window is resized
+    pub size_popup: Option<SizePopup>,
     pub unmanaged_windows: HashMap<Window, UnmanagedWindow>,
     pub error_tracker: ErrorTracker,
 }
@@ -104,8 +189,9 @@ impl WindowManager {
             crate::window::error::ErrorCategory::X11
         );
 
-        let cursors = Cursors::new(&ctx.conn, ctx.screen_num)?;
-        let mut compositor = Compositor::new(&ctx.conn, ctx.root_window, ctx.screen_num)?;
+        let cursors = Cursors::new(&ctx.conn, ctx.screen_num, ctx.root_window, &settings_manager.current)?;
+        let effects = Effects::from_settings(&settings_manager.current);
+        let mut compositor = Compositor::new(&ctx.conn, ctx.root_window, ctx.screen_num, effects)?;
 
         // Enable compositor immediately
         if let Err(e) = compositor.enable(&ctx.conn) {
@@ -134,65 +220,278 @@ impl WindowManager {
             "set root window event mask",
         );
         
-        // Grab Alt+Tab (Mod1 + 23)
-        let modifiers = [
-             x11rb::protocol::xproto::ModMask::M1,
-             x11rb::protocol::xproto::ModMask::M1 | x11rb::protocol::xproto::ModMask::LOCK,
-             x11rb::protocol::xproto::ModMask::M1 | x11rb::protocol::xproto::ModMask::M2,
-             x11rb::protocol::xproto::ModMask::M1 | x11rb::protocol::xproto::ModMask::LOCK | x11rb::protocol::xproto::ModMask::M2,
-        ];
-        
-        for mods in modifiers {
-             if let Err(e) = ctx.conn.grab_key(
-                 false,
-                 ctx.root_window,
-                 mods,
-                 23, // Tab
-                 x11rb::protocol::xproto::GrabMode::ASYNC,
-                 x11rb::protocol::xproto::GrabMode::ASYNC
-             ) {
-                 warn!("Failed to grab Alt+Tab with modifiers {:?}: {}", mods, e);
-             }
+        // Monitor layout, followed through hotplug
+        let monitors = match Self::init_randr(&ctx) {
+            Ok(monitors) => monitors,
+            Err(e) => {
+                warn!("RandR unavailable, treating the screen as one monitor: {}", e);
+                monitors::single(ctx.screen_width, ctx.screen_height)
+            }
+        };
+
+        // Fading needs the compositor to draw the in-between
+        let fades = Fades::new(if compositor.active { effects.fade_duration } else { Duration::ZERO });
+        let repaint = Repaint::new(&ctx.conn)?;
+
+        let theme = Theme::load(&settings_manager.current);
+        let workspaces = Workspaces::new(settings_manager.current.workspace_count, &settings_manager.current.workspace_names);
+
+        let tiling = Tiling::new(settings_manager.current.tiling_layout, f64::from(settings_manager.current.tiling_master_ratio) / 100.0);
+        let snapping = Snapping::from_settings(&settings_manager.current);
+        let mut edges = EdgeTriggers::from_settings(&settings_manager.current);
+        log_warn(edges.create_strips(&ctx), "create screen edge triggers");
+        let dnd_raise = DndRaise::from_settings(&settings_manager.current);
+        let keyboard_step = u16::try_from(settings_manager.current.keyboard_step).unwrap_or(u16::MAX);
+        let dpms = Dpms::new(&ctx.conn, &settings_manager.current);
+        log_warn(dpms.apply(&ctx.conn), "set screen blanking and display power timeouts");
+        // A drag-and-drop starts with the XdndSelection changing hands
+        log_warn(
+            ctx.conn.xfixes_select_selection_input(ctx.root_window, ctx.atoms.XdndSelection, SelectionEventMask::SET_SELECTION_OWNER),
+            "watch the drag-and-drop selection",
+        );
+
+        let mut keybindings = Keybindings::new(&settings_manager.current.shortcuts);
+        if let Err(e) = keybindings.grab(&ctx.conn, ctx.root_window) {
+            warn!("Failed to grab shortcut keys: {}", e);
         }
+        let zoom = Zoom::from_settings(&settings_manager.current);
+        log_warn(zoom.grab(&ctx.conn, ctx.root_window), "grab Super+scroll for the desktop zoom");
 
         Ok(Self {
             ctx,
             clients: HashMap::new(),
             drag_state: DragState::None,
+            keyboard_step,
             current_workspace: 0,
+            workspaces,
             cursors,
             compositor,
+            fades,
+            opacity_rules: OpacityRules::from_settings(&settings_manager.current),
+            repaint,
+            frame_times: FrameTimes::default(),
             last_click_time: 0,
             last_click_window: x11rb::NONE,
             mru_stack: Vec::new(),
             focused_window: None,
+            focus_policy: FocusPolicy::from_settings(&settings_manager.current),
+            pointer_focus: PointerFocus::default(),
+            client_list: ClientList::default(),
+            monitors,
             settings_manager,
+            theme,
+            keybindings,
+            tiling,
+            snapping,
+            session: SessionState::default(),
+            session_path: None,
+            switcher: None,
+            window_menu: None,
+            overview: None,
+            pings: Pings::default(),
+            force_quit: HashMap::new(),
+            startup_messages: Assembler::default(),
+            launches: Launches::default(),
+            edges,
+            edge_dwell: EdgeDwell::default(),
+            dnd_raise,
+            dpms,
+            zoom,
+            revealed_panels: Vec::new(),
+            hovered_button: None,
+            pressed_button: None,
+            size_popup: None,
             unmanaged_windows: HashMap::new(),
             error_tracker,
         })
     }
 
+    fn init_randr(ctx: &Context) -> Result<Vec<Monitor>> {
+        ctx.conn.randr_query_version(1, 3)?.reply()?;
+        ctx.conn.randr_select_input(ctx.root_window, NotifyMask::SCREEN_CHANGE | NotifyMask::OUTPUT_CHANGE | NotifyMask::CRTC_CHANGE)?;
+        monitors::query(&ctx.conn, ctx.root_window, ctx.screen_width, ctx.screen_height)
+    }
+
+    /// Read the monitor layout again after an output was plugged, unplugged
+    /// or reconfigured, and move windows to match
+    fn update_monitors(&mut self) -> Result<()> {
+        let root = self.ctx.conn.get_geometry(self.ctx.root_window)?.reply()?;
+        self.ctx.screen_width = root.width;
+        self.ctx.screen_height = root.height;
+        log_warn(self.compositor.resize(&self.ctx.conn, root.width, root.height), "resize compositor back buffer");
+        let monitors = monitors::query(&self.ctx.conn, self.ctx.root_window, root.width, root.height)?;
+        if monitors == self.monitors {
+            return Ok(());
+        }
+        info!("🖥️ Monitor layout changed: {} monitor(s), screen {}x{}", monitors.len(), root.width, root.height);
+        self.monitors = monitors;
+
+        self.ctx.conn.change_property32(PropMode::REPLACE, self.ctx.root_window, self.ctx.atoms._NET_DESKTOP_GEOMETRY, AtomEnum::CARDINAL, &[root.width as u32, root.height as u32])?;
+        self.update_net_workarea()?;
+        self.replace_windows();
+        self.relayout();
+        log_warn(self.edges.create_strips(&self.ctx), "move screen edge triggers");
+        Ok(())
+    }
+
+    /// Fit windows to the current monitors: the desktop covers the screen,
+    /// maximized and fullscreen windows their monitor, and windows left
+    /// where no monitor shows them move onto the nearest one
+    fn replace_windows(&mut self) {
+        let windows: Vec<Window> = self.clients.keys().copied().collect();
+        for window in windows {
+            let client = &self.clients[&window];
+            let Some(frame) = client.frame else { continue };
+            if client.is_dock {
+                // Panels place themselves on the monitors they want
+                continue;
+            }
+            let (b, t) = if client.is_desktop || client.is_fullscreen || client.is_csd { (0, 0) } else { (BORDER_WIDTH, TITLE_HEIGHT) };
+            let frame_rect = x11rb::protocol::xproto::Rectangle { x: client.x, y: client.y, width: client.width + 2 * b, height: client.height + t + 2 * b };
+            let monitor = monitors::monitor_for_rect(&self.monitors, frame_rect);
+
+            let area = if client.is_desktop {
+                x11rb::protocol::xproto::Rectangle { x: 0, y: 0, width: self.ctx.screen_width, height: self.ctx.screen_height }
+            } else if client.is_fullscreen {
+                self.monitors[monitor].rect()
+            } else if client.is_maximized {
+                self.monitor_workarea(monitor)
+            } else if let Some((x, y)) = monitors::bring_on_screen(&self.monitors, frame_rect) {
+                x11rb::protocol::xproto::Rectangle { x, y, ..frame_rect }
+            } else {
+                continue;
+            };
+            if area == frame_rect {
+                continue;
+            }
+            debug!("Moving window {} to {:?} for the new monitor layout", window, area);
+
+            let (client_w, client_h) = (area.width.saturating_sub(2 * b), area.height.saturating_sub(t + 2 * b));
+            let _ = self.ctx.conn.configure_window(frame, &ConfigureWindowAux::new().x(area.x as i32).y(area.y as i32).width(area.width as u32).height(area.height as u32));
+            let _ = self.ctx.conn.configure_window(window, &ConfigureWindowAux::new().width(client_w as u32).height(client_h as u32));
+            if let Some(client) = self.clients.get_mut(&window) {
+                client.x = area.x;
+                client.y = area.y;
+                client.width = client_w;
+                client.height = client_h;
+            }
+            self.send_configure_notify(window);
+        }
+    }
+
+    /// Monitor showing most of `window`
+    fn window_monitor(&self, window: Window) -> usize {
+        let Some(client) = self.clients.get(&window) else { return self.pointer_monitor() };
+        let rect = x11rb::protocol::xproto::Rectangle { x: client.x, y: client.y, width: client.width, height: client.height };
+        monitors::monitor_for_rect(&self.monitors, rect)
+    }
+
+    /// Monitor with the pointer, where new windows go; the primary one if
+    /// the pointer can't be found
+    fn pointer_monitor(&self) -> usize {
+        match self.ctx.conn.query_pointer(self.ctx.root_window).ok().and_then(|c| c.reply().ok()) {
+            Some(pointer) => monitors::monitor_at(&self.monitors, pointer.root_x, pointer.root_y),
+            None => self.monitors.iter().position(|m| m.primary).unwrap_or(0),
+        }
+    }
+
+    /// Part of a monitor panels leave free, for maximizing, tiling and
+    /// placing windows
+    fn monitor_workarea(&self, monitor: usize) -> x11rb::protocol::xproto::Rectangle {
+        self.workarea_on(monitor, self.current_workspace)
+    }
+
+    /// Work area of a monitor on `workspace`, left free by the panels shown
+    /// there
+    fn workarea_on(&self, monitor: usize, workspace: u32) -> x11rb::protocol::xproto::Rectangle {
+        let struts = self.clients.values()
+            .filter(|c| !c.is_minimized && (c.workspace == workspace || c.workspace == ALL_WORKSPACES))
+            .filter_map(|c| c.strut.as_deref());
+        monitors::workarea(&self.monitors[monitor], struts, self.ctx.screen_width, self.ctx.screen_height)
+    }
+
+    /// Panels came, went or changed what they reserve: publish the new
+    /// work areas and fit maximized and tiled windows to them
+    fn struts_changed(&mut self) {
+        log_warn(self.update_net_workarea(), "publish work area");
+        let current = self.current_workspace;
+        let fitted: Vec<(Window, x11rb::protocol::xproto::Rectangle, bool)> = self.clients.values()
+            .filter(|c| c.frame.is_some() && !c.is_fullscreen && !c.is_shaded)
+            .filter_map(|c| {
+                let workspace = if c.workspace == ALL_WORKSPACES { current } else { c.workspace };
+                let area = self.workarea_on(self.window_monitor(c.window), workspace);
+                let rect = if c.is_maximized { Some(area) } else { c.snap.frame_rect(area) }?;
+                let (width, height) = c.frame_size(BORDER_WIDTH, TITLE_HEIGHT);
+                let frame = x11rb::protocol::xproto::Rectangle { x: c.x, y: c.y, width, height };
+                (rect != frame).then_some((c.window, rect, c.is_maximized))
+            })
+            .collect();
+        for (window, rect, maximized) in fitted {
+            debug!("Fitting window {} to the new work area {:?}", window, rect);
+            self.place_frame(window, rect);
+            if let Some(client) = self.clients.get_mut(&window) {
+                client.is_maximized = maximized;
+            }
+        }
+        self.relayout();
+    }
+
     pub fn scan_windows(&mut self) -> Result<()> {
         let tree = self.ctx.conn.query_tree(self.ctx.root_window)?.reply()?;
         info!("Scanning {} windows...", tree.children.len());
 
         let mut to_manage = Vec::new();
+        // Frames kept by an instance that restarted in place, mapped or not
+        let mut adopted = Vec::new();
+        // And those whose client went away meanwhile
+        let mut empty = Vec::new();
 
         for &win in &tree.children {
+            if let Some(client) = restart::adoptable_client(&self.ctx.conn, win, self.ctx.atoms._XFWM4_RS_FRAME) {
+                adopted.push(win);
+                to_manage.push((client, Some(win)));
+                continue;
+            }
+            if restart::frame_client(&self.ctx.conn, win, self.ctx.atoms._XFWM4_RS_FRAME).is_some() {
+                empty.push(win);
+                continue;
+            }
             if let Ok(attrs) = self.ctx.conn.get_window_attributes(win)?.reply() {
                 if !attrs.override_redirect && attrs.map_state != x11rb::protocol::xproto::MapState::UNMAPPED {
-                    to_manage.push((win, attrs));
+                    to_manage.push((win, None));
                 }
             }
         }
 
-        for (win, _attrs) in to_manage {
-            self.manage_window(win)?;
+        // Kept frames go with what kept them once none of them is in use
+        let mask = self.ctx.conn.setup().resource_id_mask;
+        let mut released: Vec<Window> = Vec::new();
+        for frame in empty {
+            if adopted.iter().any(|&other| restart::same_owner(frame, other, mask)) {
+                log_warn(self.ctx.conn.destroy_window(frame), "destroy empty frame left from before the restart");
+            } else if !released.iter().any(|&other| restart::same_owner(frame, other, mask)) {
+                debug!("Freeing the frames left from before the restart with {}", frame);
+                log_warn(self.ctx.conn.kill_client(frame), "free frames left from before the restart");
+                released.push(frame);
+            }
+        }
+        if !adopted.is_empty() {
+            info!("🔄 Taking over {} frame(s) from before the restart", adopted.len());
+        }
+
+        for (win, frame) in to_manage {
+            self.manage(win, frame)?;
         }
         Ok(())
     }
 
     pub fn manage_window(&mut self, win: Window) -> Result<()> {
+        self.manage(win, None)
+    }
+
+    /// Manage `win` in a new frame, or in `adopted`, the frame it was left
+    /// in by an instance that restarted in place
+    fn manage(&mut self, win: Window, adopted: Option<Window>) -> Result<()> {
         // 1. Get Window Name (with fallbacks)
         let mut name = "Unnamed".to_string();
         for &atom in &[self.ctx.atoms._NET_WM_NAME, self.ctx.atoms.UTF8_STRING, AtomEnum::WM_NAME.into()] {
@@ -209,6 +508,7 @@ impl WindowManager {
         
         // Check for _NET_WM_DESKTOP
         let mut workspace = self.current_workspace;
+        let mut desktop_requested = false;
 
         let reply = self.ctx.conn.get_property(
             false,
@@ -222,7 +522,8 @@ impl WindowManager {
         if let Ok(prop) = reply {
             if prop.type_ == u32::from(AtomEnum::CARDINAL) && prop.format == 32 && prop.value_len == 1 {
                 if let Some(w) = prop.value32().and_then(|mut i| i.next()) {
-                     workspace = w;
+                     workspace = self.workspaces.clamp(w);
+                     desktop_requested = true;
                      debug!("Window {} is on workspace {}", win, workspace);
                 }
             }
@@ -259,6 +560,7 @@ impl WindowManager {
         let mut is_shaded = false;
         let mut is_above = false;
         let mut is_below = false;
+        let mut is_hidden = false;
         if let Ok(reply) = self.ctx.conn.get_property(false, win, self.ctx.atoms._NET_WM_STATE, AtomEnum::ATOM, 0, 1024)?.reply() {
             if reply.type_ == u32::from(AtomEnum::ATOM) && reply.format == 32 {
                 for atom in reply.value32().unwrap() {
@@ -272,11 +574,14 @@ impl WindowManager {
                     else if atom == self.ctx.atoms._NET_WM_STATE_SHADED { is_shaded = true; }
                     else if atom == self.ctx.atoms._NET_WM_STATE_ABOVE { is_above = true; }
                     else if atom == self.ctx.atoms._NET_WM_STATE_BELOW { is_below = true; }
+                    else if atom == self.ctx.atoms._NET_WM_STATE_HIDDEN { is_hidden = true; }
                 }
             }
         }
 
         if is_sticky { workspace = 0xFFFFFFFF; }
+        // Sticky as well when it asked for every workspace
+        is_sticky |= workspace == ALL_WORKSPACES;
 
         // Smart Placement if position is 0,0 (ported from xfwm4 clientPlace)
         if x == 0 && y == 0 && !is_dock && !is_desktop {
@@ -298,20 +603,57 @@ impl WindowManager {
             }
         }
         
+        // Where the window was when the last session ended, for windows
+        // the session manager starts again
+        let saved = if is_dock || is_desktop || transient_for.is_some() || adopted.is_some() {
+            None
+        } else {
+            self.read_session_key(win).and_then(|(class, role)| self.session.take(&class, &role))
+        };
+        if let Some(saved) = &saved {
+            debug!("Restoring window {} from the last session: {:?}", win, saved);
+            workspace = self.workspaces.clamp(saved.workspace);
+            is_sticky = workspace == ALL_WORKSPACES;
+            is_shaded |= saved.shaded;
+            is_above |= saved.above;
+            is_below |= saved.below && !is_above;
+        }
+
         let (group_leader, accepts_input, is_urgent) = self.read_wm_hints(win);
         let client_leader = self.read_client_leader(win);
 
         let user_time_window = self.read_user_time_window(win);
-        let startup_id = self.read_startup_id(win);
-        let user_time = if let Some(utw) = user_time_window {
+        let startup_id = self.read_startup_id(win).or_else(|| group_leader.and_then(|leader| self.read_startup_id(leader)));
+        let mut user_time = if let Some(utw) = user_time_window {
              self.read_user_time(utw)
         } else {
              self.read_user_time(win)
         };
+
+        // Launched with startup notification: the window goes to the
+        // workspace it was launched from, and the launch counts as the
+        // user's last action in it
+        if let Some(id) = &startup_id {
+            let launch = self.launches.complete(id);
+            if launch.is_some() {
+                self.update_launch_cursor();
+            }
+            if let Some(desktop) = launch.and_then(|l| l.desktop) {
+                if !desktop_requested && saved.is_none() && workspace != ALL_WORKSPACES {
+                    workspace = self.workspaces.clamp(desktop);
+                    debug!("Window {} launched from workspace {}", win, workspace);
+                }
+            }
+            let launched_at = launch.and_then(|l| l.timestamp).or_else(|| startup::id_timestamp(id));
+            if user_time != Some(0) {
+                user_time = launched_at.or(user_time);
+            }
+        }
         let pid = self.read_pid(win);
         let frame_extents = self.read_frame_extents(win);
 
-        let (gravity, _min_w, _min_h, _max_w, _max_h) = self.read_size_hints(win);
+        let size_hints = self.read_size_hints(win);
+        let gravity = size_hints.gravity;
         let sync_counter = self.read_sync_counter(win);
         let is_shaped = self.read_is_shaped(win);
         
@@ -345,20 +687,26 @@ impl WindowManager {
         };
         
         // Final Frame coordinates calculation
-        let (frame_x, frame_y) = if x == 0 && y == 0 && !is_dock && !is_desktop {
+        let adopted_at = adopted.and_then(|frame| self.ctx.conn.get_geometry(frame).ok()?.reply().ok());
+        let (frame_x, frame_y) = if let Some(at) = &adopted_at {
+             (at.x, at.y)
+        } else if let Some(saved) = &saved {
+             (saved.x, saved.y)
+        } else if x == 0 && y == 0 && !is_dock && !is_desktop {
              let (nx, ny) = self.place_window(geom.width, geom.height);
              debug!("Smart placed window {} at ({}, {})", win, nx, ny);
              (nx, ny)
         } else if (x <= 1 || y <= 1) && !is_dock && !is_desktop && !is_splash && !is_menu {
-             // Handle "near corner" placement with centering or cascading
-             let screen = &self.ctx.conn.setup().roots[self.ctx.screen_num];
+             // Handle "near corner" placement with centering or cascading,
+             // on the monitor with the pointer
+             let area = self.monitor_workarea(self.pointer_monitor());
              if is_dialog || is_utility {
-                 let (nx, ny) = center_window(screen.width_in_pixels, screen.height_in_pixels, geom.width, geom.height);
-                 (nx, ny)
+                 let (nx, ny) = center_window(area.width, area.height, geom.width, geom.height);
+                 (area.x + nx, area.y + ny)
              } else {
-                  let origins: Vec<(i16, i16)> = self.clients.values().map(|c| (c.x, c.y)).collect();
-                  let (nx, ny) = cascade_placement(screen.width_in_pixels, screen.height_in_pixels, geom.width, geom.height, &origins);
-                  (nx, ny)
+                  let origins: Vec<(i16, i16)> = self.clients.values().map(|c| (c.x - area.x, c.y - area.y)).collect();
+                  let (nx, ny) = cascade_placement(area.width, area.height, geom.width, geom.height, &origins);
+                  (area.x + nx, area.y + ny)
              }
         } else {
              // Explicitly provided coordinates are for client area (usually)
@@ -370,6 +718,9 @@ impl WindowManager {
 
         let (fix_x, fix_y, fix_w, fix_h) = if is_desktop {
             (0, 0, self.ctx.screen_width as u16, self.ctx.screen_height as u16)
+        } else if let Some(saved) = &saved {
+            log_warn(self.ctx.conn.configure_window(win, &ConfigureWindowAux::new().width(u32::from(saved.width)).height(u32::from(saved.height))), "restore window size");
+            (frame_x, frame_y, saved.width, saved.height)
         } else {
             (frame_x, frame_y, geom.width, geom.height)
         };
@@ -383,32 +734,53 @@ impl WindowManager {
             client_y: (title + border) as i16,
         };
         debug!("Frame geometry for window {}: {:?}", win, frame_geom);
-        let frame_win = self.ctx.conn.generate_id()?;
         
-        // Listen for frame events (decorations) and motion
-        let values = CreateWindowAux::new()
-            .event_mask(EventMask::SUBSTRUCTURE_NOTIFY | EventMask::SUBSTRUCTURE_REDIRECT | EventMask::EXPOSURE | EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::PROPERTY_CHANGE)
-            .background_pixel(0)
-            .border_pixel(0x000000);
-            
-        self.ctx.conn.create_window(
-            self.ctx.root_depth,
-            frame_win,
-            self.ctx.root_window,
-            frame_geom.x,
-            frame_geom.y,
-            frame_geom.width,
-            frame_geom.height,
-            if is_dock { 0 } else { 1 },
-            WindowClass::INPUT_OUTPUT,
-            0,
-            &values,
-        )?;
+        let frame_win = if let Some(frame) = adopted {
+            let aux = ConfigureWindowAux::new()
+                .x(i32::from(frame_geom.x))
+                .y(i32::from(frame_geom.y))
+                .width(u32::from(frame_geom.width))
+                .height(u32::from(frame_geom.height))
+                .border_width(if is_dock { 0 } else { 1 });
+            self.ctx.conn.configure_window(frame, &aux)?;
+            frame
+        } else {
+            // Made on the frames connection, so they can outlive this one
+            // across a restart
+            let frame_win = self.ctx.frames.generate_id()?;
+            let values = CreateWindowAux::new()
+                .background_pixel(0)
+                .border_pixel(0x000000);
+
+            self.ctx.frames.create_window(
+                self.ctx.root_depth,
+                frame_win,
+                self.ctx.root_window,
+                frame_geom.x,
+                frame_geom.y,
+                frame_geom.width,
+                frame_geom.height,
+                if is_dock { 0 } else { 1 },
+                WindowClass::INPUT_OUTPUT,
+                0,
+                &values,
+            )?;
+            // Should we go away without putting it back, the server does
+            self.ctx.frames.change_save_set(SetMode::INSERT, win)?;
+            self.sync_frames()?;
+            frame_win
+        };
+        // Listen for frame events (decorations), motion, which lights the
+        // buttons, and the pointer coming in, for sloppy focus
+        let frame_events = EventMask::SUBSTRUCTURE_NOTIFY | EventMask::SUBSTRUCTURE_REDIRECT | EventMask::EXPOSURE | EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION | EventMask::ENTER_WINDOW | EventMask::LEAVE_WINDOW | EventMask::PROPERTY_CHANGE;
+        self.ctx.conn.change_window_attributes(frame_win, &x11rb::protocol::xproto::ChangeWindowAttributesAux::new().event_mask(frame_events))?;
+        // Found again if we restart in place
+        self.ctx.conn.change_property32(PropMode::REPLACE, frame_win, self.ctx.atoms._XFWM4_RS_FRAME, AtomEnum::WINDOW, &[win])?;
 
         if !is_dock && !is_desktop {
             // Passive grab for click-to-focus on the client window
             // SYNC mode is crucial: it let's us "AllowEvents(REPLAY_POINTER)" so the app gets the click
-            use x11rb::protocol::xproto::{ButtonIndex, ModMask, GrabMode};
+            use x11rb::protocol::xproto::ButtonIndex;
             self.ctx.conn.grab_button(
                 true,
                 win,
@@ -422,7 +794,12 @@ impl WindowManager {
             )?;
         }
         
-        self.ctx.conn.reparent_window(win, frame_win, frame_geom.client_x, frame_geom.client_y)?;
+        if adopted.is_some() {
+            // Already in it: reparenting would unmap and map it again
+            self.ctx.conn.configure_window(win, &ConfigureWindowAux::new().x(i32::from(frame_geom.client_x)).y(i32::from(frame_geom.client_y)))?;
+        } else {
+            self.ctx.conn.reparent_window(win, frame_win, frame_geom.client_x, frame_geom.client_y)?;
+        }
         
         // HACK: Force NorthWest gravity on the client window to avoid it moving 
         // relative to the frame when the frame resizes. Ported from xfwm4 client.c.
@@ -444,12 +821,18 @@ impl WindowManager {
             }
         }
         
-        if workspace == self.current_workspace || workspace == 0xFFFFFFFF {
+        // Minimized before the restart, and hidden since
+        let is_minimized = adopted.is_some() && is_hidden;
+        if (workspace == self.current_workspace || workspace == 0xFFFFFFFF) && !is_minimized {
 
 
              self.ctx.conn.map_window(frame_win)?;
              self.ctx.conn.map_window(win)?;
              let _ = self.update_window_shape(win);
+             // Kept frames are on screen already
+             if adopted.is_none() {
+                 self.fades.fade_in(win, Instant::now());
+             }
         }
         
         let mut client = Client::new(
@@ -467,11 +850,12 @@ impl WindowManager {
         client.transient_for = transient_for;
         client.group_leader = group_leader;
         client.client_leader = client_leader;
-        client.user_time = user_time;
+        client.user_time = user_time.unwrap_or(0);
         client.user_time_window = user_time_window;
         client.is_modal = is_modal;
         client.is_fullscreen = is_fullscreen;
         client.is_maximized = is_maximized;
+        client.is_minimized = is_minimized;
         client.is_sticky = is_sticky;
         client.demands_attention = demands_attention;
         client.skip_taskbar = skip_taskbar;
@@ -480,6 +864,7 @@ impl WindowManager {
         client.is_above = is_above;
         client.is_below = is_below;
         client.startup_id = startup_id;
+        client.size_hints = size_hints;
 
         client.frame_extents = frame_extents;
         client.gravity = gravity;
@@ -494,6 +879,7 @@ impl WindowManager {
         client.sync_counter = sync_counter;
         client.is_shaped = is_shaped;
         client.opacity = self.read_opacity(win);
+        client.class = self.read_class(win);
 
         // Select Shape events
         let _ = ShapeExt::shape_select_input(&self.ctx.conn, win, true);
@@ -577,22 +963,47 @@ impl WindowManager {
         let height = geom.height + title + (2 * border);
         debug!("Drawing decoration for frame {} (title: {})", frame_win, client.name);
         let _ = self.error_tracker.warn_if_failed(
-            draw_decoration(&self.ctx, frame_win, &client.name, width, height, title),
+            draw_decoration(&self.ctx, &self.theme, frame_win, &client.name, width, height, title, false, None),
             "draw initial decoration",
             crate::window::error::ErrorCategory::Window
         );
         
         self.clients.insert(win, client);
+        self.update_wm_desktop(win);
         self.mru_stack.retain(|&w| w != win);
         self.mru_stack.insert(0, win);
+        self.client_list.add(win, !is_desktop);
+        self.update_client_list();
+        self.tiling.add(win);
+        if self.clients.get(&win).is_some_and(|c| c.strut.is_some()) {
+            self.struts_changed();
+        }
         
         // Create XSync Alarm if supported
         if let Err(e) = self.client_create_xsync_alarm(win) {
              warn!("Failed to create XSync alarm for window {}: {}", win, e);
         }
         
+        if is_shaded {
+            log_warn(self.set_shaded(win, true), "shade new window");
+        }
+
+        if let Some(saved) = &saved {
+            if saved.maximized {
+                log_warn(self.toggle_maximize(win), "maximize restored window");
+            }
+            if saved.minimized {
+                log_warn(self.toggle_minimize(win), "minimize restored window");
+                return Ok(());
+            }
+        }
+
+        self.relayout();
+        // The new frame went on top of the screen edge triggers
+        self.edges.raise_strips(&self.ctx.conn);
+
         // Focus the new window (ported from xfwm4 clientFrame)
-        let _ = self.focus_window(win);
+        self.focus_new_window(win, user_time);
         
         Ok(())
     }
@@ -600,9 +1011,27 @@ impl WindowManager {
     pub fn unmanage_window(&mut self, win: Window) -> Result<()> {
         if self.clients.contains_key(&win) {
             debug!("Unmanaging window {}", win);
+            let had_strut = self.clients.get(&win).is_some_and(|c| c.strut.is_some());
+            self.fades.remove(win);
+            self.opacity_rules.remove(win);
+            self.pointer_focus.leave(win);
+            self.pings.forget(win);
+            if let Some(dialog) = self.force_quit.remove(&win) {
+                dialog.dismiss();
+            }
+            if self.window_menu.as_ref().is_some_and(|m| m.window == win) {
+                self.close_window_menu();
+            }
             if let Some(client) = self.clients.remove(&win) {
+                // Out of the frame first: destroying it takes what is in it
+                // along. Gone already if it was destroyed.
+                if let Some((x, y)) = self.ungravitated_position(&client) {
+                    let _ = self.ctx.conn.reparent_window(win, self.ctx.root_window, x, y);
+                    let _ = self.ctx.frames.change_save_set(SetMode::DELETE, win);
+                    let _ = self.ctx.frames.flush();
+                }
                 if let Some(frame) = client.frame {
-                    let _ = self.ctx.conn.destroy_window(frame);
+                    self.destroy_frame(frame);
                 }
                 
                 if let Some(pict) = client.picture {
@@ -615,13 +1044,20 @@ impl WindowManager {
                 if let Some(dmg) = client.damage {
                      let _ = self.ctx.conn.damage_destroy(dmg);
                 }
-                
-                let (b, t) = if client.is_desktop || client.is_dock || client.is_fullscreen { (0, 0) } else { (crate::window::frame::BORDER_WIDTH, crate::window::frame::TITLE_HEIGHT) };
-                let client_x = client.x + b as i16;
-                let client_y = client.y + (t + b) as i16;
-                let _ = self.ctx.conn.reparent_window(win, self.ctx.root_window, client_x, client_y);
             }
             self.mru_stack.retain(|&w| w != win);
+            self.client_list.remove(win);
+            self.update_client_list();
+            self.tiling.remove(win);
+            if had_strut {
+                self.struts_changed();
+            } else {
+                self.relayout();
+            }
+            if self.focused_window == Some(win) {
+                self.focused_window = None;
+                let _ = self.ctx.conn.change_property32(PropMode::REPLACE, self.ctx.root_window, self.ctx.atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW, &[x11rb::NONE]);
+            }
             
             // Focus next window in MRU stack (ported from xfwm4 clientFocusTop)
             if let Some(&next) = self.mru_stack.first() {
@@ -631,19 +1067,906 @@ impl WindowManager {
         Ok(())
     }
 
+    /// Destroy `frame`, out of use. A frame kept from before a restart goes
+    /// with the connection that kept it when it is the last one, freeing
+    /// what the server retained for it.
+    fn destroy_frame(&self, frame: Window) {
+        let mask = self.ctx.conn.setup().resource_id_mask;
+        let kept = !restart::same_owner(frame, self.ctx.frames.setup().resource_id_base, mask);
+        let last = kept && !self.clients.values().filter_map(|c| c.frame).any(|other| restart::same_owner(frame, other, mask));
+        if last {
+            let _ = self.ctx.conn.kill_client(frame);
+        } else {
+            let _ = self.ctx.conn.destroy_window(frame);
+        }
+    }
+
+    /// Wait for the server to have done what was asked on the frames
+    /// connection, for this one to use, logging what it refused
+    fn sync_frames(&self) -> Result<()> {
+        self.ctx.frames.sync()?;
+        while let Some(event) = self.ctx.frames.poll_for_event()? {
+            warn!("Frames connection: {:?}", event);
+        }
+        Ok(())
+    }
+
+    /// Where `client` goes on the root window without its frame: where its
+    /// gravity has the frame stay put when managed again, like xfwm4's
+    /// clientUngravitate. `None` once the window is gone.
+    fn ungravitated_position(&self, client: &Client) -> Option<(i16, i16)> {
+        let in_frame = self.ctx.conn.get_geometry(client.window).ok()?.reply().ok()?;
+        let (border, title) = (in_frame.x.max(0) as u16, (in_frame.y - in_frame.x).max(0) as u16);
+        let mut x = client.x + in_frame.x;
+        let mut y = client.y + in_frame.y;
+        Self::gravitate(client.gravity, -1, border, title, &mut x, &mut y);
+        Some((x, y))
+    }
+
+    /// Publish _NET_CLIENT_LIST and _NET_CLIENT_LIST_STACKING on the root
+    /// window, for panels and pagers
+    fn update_client_list(&self) {
+        let stacking = self.client_list.stacking(|w| self.clients.get(&w).map_or(crate::window::LAYER_NORMAL, |c| c.layer));
+        let _ = self.ctx.conn.change_property32(PropMode::REPLACE, self.ctx.root_window, self.ctx.atoms._NET_CLIENT_LIST, AtomEnum::WINDOW, self.client_list.mapping());
+        let _ = self.ctx.conn.change_property32(PropMode::REPLACE, self.ctx.root_window, self.ctx.atoms._NET_CLIENT_LIST_STACKING, AtomEnum::WINDOW, &stacking);
+    }
+
+    /// Raise the frame of `window` and record the new stacking order
+    fn raise_window(&mut self, window: Window) {
+        let Some(frame) = self.clients.get(&window).and_then(|c| c.frame) else { return };
+        let _ = self.ctx.conn.configure_window(frame, &ConfigureWindowAux::new().stack_mode(x11rb::protocol::xproto::StackMode::ABOVE));
+        if self.client_list.raise(window) {
+            self.update_client_list();
+        }
+        self.edges.raise_strips(&self.ctx.conn);
+    }
+
+    /// The pointer, or a window dragged, rested on `edge` of the screen
+    fn trigger_edge(&mut self, edge: ScreenEdge) -> Result<bool> {
+        if let DragState::Moving { window, .. } = self.drag_state {
+            return self.wrap_dragged_window(window, edge);
+        }
+        let Some(action) = self.edges.action(edge) else { return Ok(false) };
+        info!("🔲 EDGE: {:?} at {:?}", action, edge);
+        let current = self.current_workspace;
+        match action {
+            EdgeAction::Overview => return self.open_overview(x11rb::CURRENT_TIME),
+            EdgeAction::RevealPanel => return Ok(self.reveal_panels(edge)),
+            EdgeAction::NextWorkspace => self.switch_workspace(current + 1)?,
+            EdgeAction::PreviousWorkspace => if let Some(previous) = current.checked_sub(1) { self.switch_workspace(previous)?; },
+        }
+        Ok(true)
+    }
+
+    /// Take `window`, dragged against the left or right `edge`, to the
+    /// workspace on that side, the pointer coming in from the other side
+    fn wrap_dragged_window(&mut self, window: Window, edge: ScreenEdge) -> Result<bool> {
+        let current = self.current_workspace;
+        let (target, pointer_x) = match edge {
+            ScreenEdge::Left => (current.checked_sub(1), self.ctx.screen_width as i16 - 2),
+            ScreenEdge::Right => (Some(current + 1).filter(|&w| w < self.workspaces.count()), 1),
+            _ => return Ok(false),
+        };
+        let Some(target) = target else { return Ok(false) };
+        let Some(client) = self.clients.get_mut(&window) else { return Ok(false) };
+        if client.workspace != ALL_WORKSPACES {
+            client.workspace = target;
+            self.update_wm_desktop(window);
+        }
+        self.switch_workspace(target)?;
+        // Already on screen, so no fading in
+        self.fades.remove(window);
+        let pointer = self.ctx.conn.query_pointer(self.ctx.root_window)?.reply()?;
+        self.ctx.conn.warp_pointer(x11rb::NONE, self.ctx.root_window, 0, 0, 0, 0, pointer_x, pointer.root_y)?;
+        Ok(true)
+    }
+
+    /// Bring the panels along `edge` above the windows covering them
+    fn reveal_panels(&mut self, edge: ScreenEdge) -> bool {
+        let (width, height) = (i32::from(self.ctx.screen_width), i32::from(self.ctx.screen_height));
+        let touches = |c: &Client, side: ScreenEdge| match side {
+            ScreenEdge::Top => c.y <= 0,
+            ScreenEdge::Bottom => i32::from(c.y) + i32::from(c.height) >= height,
+            ScreenEdge::Left => c.x <= 0,
+            ScreenEdge::Right => i32::from(c.x) + i32::from(c.width) >= width,
+            _ => false,
+        };
+        let sides = match edge {
+            ScreenEdge::TopLeft => [ScreenEdge::Top, ScreenEdge::Left],
+            ScreenEdge::TopRight => [ScreenEdge::Top, ScreenEdge::Right],
+            ScreenEdge::BottomLeft => [ScreenEdge::Bottom, ScreenEdge::Left],
+            ScreenEdge::BottomRight => [ScreenEdge::Bottom, ScreenEdge::Right],
+            side => [side, side],
+        };
+        let panels: Vec<Window> = self.clients.values()
+            .filter(|&c| c.is_dock && !c.is_minimized && sides.iter().any(|&side| touches(c, side)))
+            .map(|c| c.window)
+            .collect();
+        for frame in panels.iter().filter_map(|w| self.clients.get(w).and_then(|c| c.frame)) {
+            let _ = self.ctx.conn.configure_window(frame, &ConfigureWindowAux::new().stack_mode(x11rb::protocol::xproto::StackMode::ABOVE));
+        }
+        let revealed = !panels.is_empty();
+        self.revealed_panels = panels;
+        revealed
+    }
+
+    /// Put the panels revealed from a screen edge back under the windows
+    /// above their layer
+    fn hide_revealed_panels(&mut self) {
+        if self.revealed_panels.is_empty() {
+            return;
+        }
+        let panel_layer = self.revealed_panels.iter().filter_map(|w| self.clients.get(w)).map(|c| c.layer).min().unwrap_or(crate::window::LAYER_DOCK);
+        self.revealed_panels.clear();
+        let stacking = self.client_list.stacking(|w| self.clients.get(&w).map_or(crate::window::LAYER_NORMAL, |c| c.layer));
+        for frame in stacking.iter().filter_map(|w| self.clients.get(w)).filter(|c| c.layer > panel_layer).filter_map(|c| c.frame) {
+            let _ = self.ctx.conn.configure_window(frame, &ConfigureWindowAux::new().stack_mode(x11rb::protocol::xproto::StackMode::ABOVE));
+        }
+        self.edges.raise_strips(&self.ctx.conn);
+    }
+
+    /// Run a keyboard shortcut. `time` is the key press timestamp, which
+    /// counts as user activity for focus stealing prevention, and `state`
+    /// the modifiers held.
+    fn perform_action(&mut self, action: Action, time: u32, state: u16) -> Result<()> {
+        info!("⌨️ Shortcut: {:?}", action);
+        let focused = self.focused_window.filter(|w| self.clients.contains_key(w));
+        match action {
+            Action::Close => if let Some(w) = focused { self.close_window(w)?; },
+            Action::Minimize => if let Some(w) = focused { self.toggle_minimize(w)?; },
+            Action::Maximize => if let Some(w) = focused { self.toggle_maximize(w)?; },
+            Action::Fullscreen => if let Some(w) = focused { self.toggle_fullscreen(w)?; },
+            Action::Move | Action::Resize => {
+                let part = if action == Action::Move { FramePart::TitleBar } else { FramePart::CornerBottomRight };
+                let movable = focused.and_then(|w| self.clients.get(&w)).is_some_and(|c| {
+                    !c.is_maximized && !c.is_fullscreen && !c.is_desktop && !c.is_dock && (action == Action::Move || !c.size_hints.is_fixed())
+                });
+                if let Some(w) = focused.filter(|_| movable && self.drag_state == DragState::None) {
+                    self.begin_pointer_drag(w, part)?;
+                }
+            }
+            Action::Tile(zone) => if let Some(w) = focused { self.apply_snap(w, zone)?; },
+            Action::CycleTiled => self.cycle_tiled(time)?,
+            Action::ToggleTiling => {
+                let on = self.tiling.toggle(self.current_workspace);
+                info!("Automatic tiling {} on workspace {}", if on { "on" } else { "off" }, self.current_workspace + 1);
+                self.relayout();
+            }
+            Action::Workspace(workspace) => self.switch_workspace(workspace)?,
+            Action::CycleWindows | Action::CycleWindowsReverse => {
+                let reverse = action == Action::CycleWindowsReverse;
+                let held = state & u16::from(ModMask::CONTROL | ModMask::M1 | ModMask::M4);
+                if held == 0 || !self.open_switcher(reverse, time, held)? {
+                    // Nothing to hold down, so no popup: switch right away
+                    // to the least recently used window, so repeated
+                    // presses go through all of them
+                    let next = self.mru_stack.iter().rev().copied().find(|&w| {
+                        Some(w) != focused && self.is_switchable(w) && !self.clients[&w].is_minimized
+                    });
+                    if let Some(next) = next {
+                        self.activate_window(next, time)?;
+                    }
+                }
+            }
+            Action::WindowMenu => if let Some(w) = focused { self.show_window_menu_below_title(w); },
+            Action::Launcher => {
+                std::process::Command::new("xfce-rs-navigator").spawn()?;
+            }
+            Action::Overview => {
+                self.open_overview(time)?;
+            }
+            Action::LockScreen => self.lock_and_blank(),
+        }
+        Ok(())
+    }
+
+    /// Whether Alt+Tab offers `window`: a managed window on the current
+    /// workspace, other than the desktop and panels
+    fn is_switchable(&self, window: Window) -> bool {
+        self.clients.get(&window).is_some_and(|c| {
+            !c.is_desktop && !c.is_dock && (c.workspace == self.current_workspace || c.workspace == 0xFFFFFFFF)
+        })
+    }
+
+    /// Raise and focus `window`, restoring it if minimized, for a keyboard
+    /// action at `time`
+    fn activate_window(&mut self, window: Window, time: u32) -> Result<()> {
+        let Some(client) = self.clients.get_mut(&window) else { return Ok(()) };
+        client.user_time = time;
+        if client.is_minimized {
+            self.toggle_minimize(window)?;
+        }
+        self.raise_window(window);
+        self.focus_window(window)
+    }
+
+    /// Show the window switcher for as long as the `held` modifiers stay
+    /// down. Returns whether there was anything to switch to.
+    fn open_switcher(&mut self, reverse: bool, time: u32, held: u16) -> Result<bool> {
+        let entries: Vec<Entry> = self.mru_stack.iter().copied().filter(|&w| self.is_switchable(w)).map(|window| Entry {
+            window,
+            title: self.clients[&window].name.clone(),
+            icon: self.read_icon(window),
+        }).collect();
+        let modifier_keycodes = held_modifier_keycodes(&self.ctx.conn, held)?;
+        let Some(mut switcher) = Switcher::new(entries, reverse, modifier_keycodes, self.compositor.active) else {
+            return Ok(false);
+        };
+
+        // The passive grab ends with the Tab key; keep the keyboard to see
+        // the modifiers go up
+        let grab = self.ctx.conn.grab_keyboard(false, self.ctx.root_window, time, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            warn!("Failed to grab the keyboard for the window switcher: {:?}", grab.status);
+            return Ok(false);
+        }
+        switcher.show(&self.ctx)?;
+        self.switcher = Some(switcher);
+
+        // They may have been released before the grab
+        let pointer = self.ctx.conn.query_pointer(self.ctx.root_window)?.reply()?;
+        if u16::from(pointer.mask) & held == 0 {
+            self.close_switcher(true, time)?;
+        }
+        Ok(true)
+    }
+
+    /// Take the window switcher down, switching to the selected window if
+    /// `activate`
+    fn close_switcher(&mut self, activate: bool, time: u32) -> Result<()> {
+        let Some(mut switcher) = self.switcher.take() else { return Ok(()) };
+        switcher.hide(&self.ctx.conn);
+        self.ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        if activate {
+            self.activate_window(switcher.selected(), time)?;
+        }
+        Ok(())
+    }
+
+    /// A key press while the window switcher is up: the cycle shortcut
+    /// moves the selection, backwards with Shift, and Escape cancels
+    fn handle_switcher_key(&mut self, keycode: u8, state: u16, time: u32) -> Result<()> {
+        let shift = u16::from(ModMask::SHIFT);
+        match self.keybindings.action_for(keycode, state & !shift) {
+            Some(Action::CycleWindows | Action::CycleWindowsReverse) => {
+                if let Some(switcher) = &mut self.switcher {
+                    switcher.advance(state & shift != 0);
+                    switcher.draw(&self.ctx)?;
+                }
+            }
+            _ if self.keybindings.is_escape(keycode) => self.close_switcher(false, time)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Show the windows of the workspace side by side on the monitor
+    /// with the pointer, holding the keyboard until one is picked.
+    /// Returns whether there was anything to show.
+    fn open_overview(&mut self, time: u32) -> Result<bool> {
+        if self.overview.is_some() || self.switcher.is_some() || self.window_menu.is_some() || self.drag_state != DragState::None {
+            return Ok(false);
+        }
+        let entries: Vec<Entry> = self.mru_stack.iter().copied().filter(|&w| self.is_switchable(w)).map(|window| Entry {
+            window,
+            title: self.clients[&window].name.clone(),
+            icon: self.read_icon(window),
+        }).collect();
+        let count = entries.len();
+        let pointer = self.ctx.conn.query_pointer(self.ctx.root_window)?.reply()?;
+        let area = self.monitors[monitors::monitor_at(&self.monitors, pointer.root_x, pointer.root_y)].rect();
+        let Some(mut overview) = Overview::new(entries, area, self.compositor.active) else {
+            return Ok(false);
+        };
+
+        let grab = self.ctx.conn.grab_keyboard(false, self.ctx.root_window, time, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            warn!("Failed to grab the keyboard for the window overview: {:?}", grab.status);
+            return Ok(false);
+        }
+        info!("🔲 OVERVIEW: {} windows", count);
+        if let Err(e) = overview.show(&self.ctx) {
+            overview.hide(&self.ctx.conn);
+            self.ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+            return Err(e);
+        }
+        self.overview = Some(overview);
+        Ok(true)
+    }
+
+    /// Take the overview down, switching to the selected window if
+    /// `activate`
+    fn close_overview(&mut self, activate: bool, time: u32) -> Result<()> {
+        let Some(mut overview) = self.overview.take() else { return Ok(()) };
+        overview.hide(&self.ctx.conn);
+        self.ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        if activate {
+            self.activate_window(overview.selected(), time)?;
+        }
+        Ok(())
+    }
+
+    /// Act on what a key press or click in the overview came to
+    fn finish_overview(&mut self, outcome: overview::Outcome, time: u32) -> Result<()> {
+        match outcome {
+            overview::Outcome::Stay => match &self.overview {
+                Some(overview) => overview.draw(&self.ctx),
+                None => Ok(()),
+            },
+            overview::Outcome::Close => self.close_overview(false, time),
+            overview::Outcome::Activate => self.close_overview(true, time),
+        }
+    }
+
+    fn read_icon(&self, window: Window) -> Option<Icon> {
+        let reply = self.ctx.conn.get_property(false, window, self.ctx.atoms._NET_WM_ICON, AtomEnum::CARDINAL, 0, u32::MAX / 4).ok()?.reply().ok()?;
+        let data: Vec<u32> = reply.value32()?.collect();
+        Icon::from_property(&data, ICON_SIZE as u32)
+    }
+
+    /// Draw the frame of `window` again, e.g. when it gains or loses focus
+    fn redraw_decoration(&self, window: Window) {
+        let Some(client) = self.clients.get(&window) else { return };
+        let Some(frame) = client.frame else { return };
+        let (border, title) = if client.is_fullscreen || client.is_desktop || client.is_dock || client.is_csd { (0, 0) } else { (BORDER_WIDTH, TITLE_HEIGHT) };
+        let active = self.focused_window == Some(window);
+        let (width, height) = client.frame_size(border, title);
+        let highlight = button_highlight(window, self.hovered_button, self.pressed_button);
+        log_warn(draw_decoration(&self.ctx, &self.theme, frame, &client.name, width, height, title, active, highlight), "redraw decoration");
+    }
+
+    /// Light the titlebar button under the pointer at `root_x`, `root_y`,
+    /// over the frame `event_window` or, while a button is held, the frame
+    /// of that button. Returns whether a frame was redrawn.
+    fn update_button_hover(&mut self, event_window: Window, root_x: i16, root_y: i16) -> bool {
+        let target = match self.pressed_button {
+            Some((window, _)) => self.clients.get(&window),
+            None => self.find_client_by_frame(event_window),
+        };
+        let hovered = target
+            .filter(|c| !(c.is_fullscreen || c.is_desktop || c.is_dock || c.is_csd))
+            .and_then(|c| {
+                let (width, _) = c.frame_size(BORDER_WIDTH, TITLE_HEIGHT);
+                self.theme.button_layout.button_at(width, root_x - c.x, root_y - c.y).map(|part| (c.window, part))
+            });
+        if hovered == self.hovered_button {
+            return false;
+        }
+        let previous = std::mem::replace(&mut self.hovered_button, hovered);
+        for window in [previous, hovered].into_iter().flatten().map(|(window, _)| window) {
+            self.redraw_decoration(window);
+        }
+        true
+    }
+
+    /// Run the titlebar button `part` of `window`'s frame
+    fn activate_button(&mut self, window: Window, part: FramePart) -> Result<()> {
+        match part {
+            FramePart::CloseButton => self.close_window(window)?,
+            FramePart::MaximizeButton => self.toggle_maximize(window)?,
+            FramePart::MinimizeButton => self.toggle_minimize(window)?,
+            FramePart::ShadeButton => self.toggle_shade(window)?,
+            FramePart::MenuButton => self.show_window_menu_below_title(window),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Show the size of `window` in the middle of it, in columns and rows
+    /// for terminals, creating the popup at the start of a resize
+    fn update_size_popup(&mut self, window: Window) {
+        let Some(client) = self.clients.get(&window) else { return };
+        let text = client.size_hints.label(client.width, client.height);
+        let (width, height) = client.frame_size(BORDER_WIDTH, TITLE_HEIGHT);
+        let frame = x11rb::protocol::xproto::Rectangle { x: client.x, y: client.y, width, height };
+        if self.size_popup.is_none() {
+            self.size_popup = log_warn(SizePopup::new(&self.ctx), "create size popup");
+        }
+        if let Some(popup) = self.size_popup.as_mut() {
+            log_warn(popup.update(&self.ctx, &text, frame), "update size popup");
+        }
+    }
+
+    /// Open the window menu of `window` under the left end of its titlebar,
+    /// for the menu button and the keyboard
+    fn show_window_menu_below_title(&mut self, window: Window) {
+        let Some(client) = self.clients.get(&window) else { return };
+        let (x, y) = (client.x + BORDER_WIDTH as i16, client.y + (BORDER_WIDTH + TITLE_HEIGHT) as i16);
+        self.show_window_menu(window, x, y);
+    }
+
+    /// Open the window menu of `window` with its corner at (`x`, `y`),
+    /// holding the pointer and keyboard until it closes
+    fn show_window_menu(&mut self, window: Window, x: i16, y: i16) {
+        self.close_window_menu();
+        let Some(client) = self.clients.get(&window).filter(|c| !c.is_desktop && !c.is_dock) else { return };
+        let state = menu::WindowState {
+            maximized: client.is_maximized,
+            fullscreen: client.is_fullscreen,
+            shaded: client.is_shaded,
+            above: client.is_above,
+            below: client.is_below,
+            sticky: client.is_sticky,
+            fixed_size: client.size_hints.is_fixed(),
+        };
+        let items = menu::window_items(state);
+        let workspaces = menu::workspace_items(self.workspaces.names(), client.workspace);
+        let bounds = self.monitors[monitors::monitor_at(&self.monitors, x, y)].rect();
+
+        let mask = EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION;
+        let pointer = self.ctx.conn.grab_pointer(false, self.ctx.root_window, mask, GrabMode::ASYNC, GrabMode::ASYNC, x11rb::NONE, self.cursors.normal, x11rb::CURRENT_TIME);
+        if pointer.ok().and_then(|c| c.reply().ok()).is_none_or(|reply| reply.status != GrabStatus::SUCCESS) {
+            warn!("Failed to grab the pointer for the window menu");
+            return;
+        }
+        let keyboard = self.ctx.conn.grab_keyboard(false, self.ctx.root_window, x11rb::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC);
+        if keyboard.ok().and_then(|c| c.reply().ok()).is_none_or(|reply| reply.status != GrabStatus::SUCCESS) {
+            warn!("Failed to grab the keyboard for the window menu");
+        }
+
+        let mut window_menu = WindowMenu::new(window, items, workspaces, bounds);
+        match window_menu.show(&self.ctx, x, y) {
+            Ok(()) => self.window_menu = Some(window_menu),
+            Err(e) => {
+                warn!("Failed to show the window menu: {}", e);
+                window_menu.hide(&self.ctx.conn);
+                self.ungrab_menu();
+            }
+        }
+    }
+
+    /// Take the window menu down
+    fn close_window_menu(&mut self) {
+        if let Some(mut window_menu) = self.window_menu.take() {
+            window_menu.hide(&self.ctx.conn);
+            self.ungrab_menu();
+        }
+    }
+
+    fn ungrab_menu(&self) {
+        let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME);
+        let _ = self.ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME);
+    }
+
+    /// Close the window menu as it came to `outcome`, running the command
+    /// picked
+    fn finish_window_menu(&mut self, outcome: Outcome) -> Result<()> {
+        let Some(window) = self.window_menu.as_ref().map(|m| m.window) else { return Ok(()) };
+        match outcome {
+            Outcome::Stay => return Ok(()),
+            Outcome::Close => self.close_window_menu(),
+            Outcome::Run(command) => {
+                self.close_window_menu();
+                if self.clients.contains_key(&window) {
+                    info!("🖱️ Window menu: {:?} on window {}", command, window);
+                    self.run_menu_command(window, command)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run_menu_command(&mut self, window: Window, command: Command) -> Result<()> {
+        match command {
+            Command::Move => self.begin_pointer_drag(window, FramePart::TitleBar)?,
+            Command::Resize => self.begin_pointer_drag(window, FramePart::CornerBottomRight)?,
+            Command::Minimize => self.toggle_minimize(window)?,
+            Command::Maximize => self.toggle_maximize(window)?,
+            Command::AlwaysOnTop => self.toggle_above(window)?,
+            Command::AlwaysBelow => self.toggle_below(window)?,
+            Command::Sticky => self.toggle_sticky(window)?,
+            Command::Workspace(workspace) => self.move_to_workspace(window, workspace)?,
+            Command::Workspaces => {}
+            Command::Close => self.close_window(window)?,
+        }
+        Ok(())
+    }
+
+    /// Move `window` with the pointer, taken to the middle of its titlebar,
+    /// or resize it from the corner `part` the pointer is taken to, until
+    /// a click or Enter puts it down. The arrow keys move the pointer on.
+    fn begin_pointer_drag(&mut self, window: Window, part: FramePart) -> Result<()> {
+        let Some(client) = self.clients.get(&window) else { return Ok(()) };
+        let (frame_w, frame_h) = client.frame_size(BORDER_WIDTH, TITLE_HEIGHT);
+        let (x, y) = if part == FramePart::TitleBar {
+            (client.x + (frame_w / 2) as i16, client.y + (TITLE_HEIGHT / 2) as i16)
+        } else {
+            (client.x + frame_w as i16 - 1, client.y + frame_h as i16 - 1)
+        };
+        let drag_state = if part == FramePart::TitleBar {
+            DragState::Moving { window, start_pointer_x: x, start_pointer_y: y, start_frame_x: client.x, start_frame_y: client.y, snap: SnapZone::None }
+        } else {
+            DragState::Resizing { window, edge: part, start_pointer_x: x, start_pointer_y: y, start_frame_x: client.x, start_frame_y: client.y, start_width: client.width, start_height: client.height }
+        };
+        self.ctx.conn.warp_pointer(x11rb::NONE, self.ctx.root_window, 0, 0, 0, 0, x, y)?;
+        let cursor = self.get_cursor_for_part(part);
+        let grab = self.ctx.conn.grab_pointer(false, self.ctx.root_window, EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION, GrabMode::ASYNC, GrabMode::ASYNC, x11rb::NONE, cursor, x11rb::CURRENT_TIME)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            warn!("Failed to grab the pointer to move window {}: {:?}", window, grab.status);
+            return Ok(());
+        }
+        let keyboard = self.ctx.conn.grab_keyboard(false, self.ctx.root_window, x11rb::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+        if keyboard.status != GrabStatus::SUCCESS {
+            warn!("Failed to grab the keyboard to move window {}: {:?}", window, keyboard.status);
+        }
+        self.drag_state = drag_state;
+        if matches!(self.drag_state, DragState::Resizing { .. }) {
+            self.update_size_popup(window);
+        }
+        Ok(())
+    }
+
+    /// A `_NET_WM_MOVERESIZE` request, which client-side decorations send
+    /// when their titlebar or border is dragged: follow the pointer from
+    /// (`x`, `y`), or move or resize with the keyboard
+    fn client_moveresize(&mut self, window: Window, x: i16, y: i16, direction: u32) -> Result<()> {
+        if direction == MOVERESIZE_CANCEL {
+            if self.drag_window() == Some(window) {
+                self.finish_drag();
+            }
+            return Ok(());
+        }
+        let Some(client) = self.clients.get(&window).filter(|c| !c.is_fullscreen && !c.is_desktop && !c.is_dock) else { return Ok(()) };
+        if self.drag_state != DragState::None {
+            return Ok(());
+        }
+        let Some((part, keyboard)) = moveresize_part(direction) else {
+            debug!("Unknown _NET_WM_MOVERESIZE direction {} from window {}", direction, window);
+            return Ok(());
+        };
+        if part != FramePart::TitleBar && client.size_hints.is_fixed() {
+            return Ok(());
+        }
+        if keyboard {
+            return self.begin_pointer_drag(window, part);
+        }
+
+        // The button may have come up before the request got here
+        let pointer = self.ctx.conn.query_pointer(self.ctx.root_window)?.reply()?;
+        let buttons = u16::from(KeyButMask::BUTTON1 | KeyButMask::BUTTON2 | KeyButMask::BUTTON3);
+        if u16::from(pointer.mask) & buttons == 0 {
+            debug!("Button already released, not moving window {}", window);
+            return Ok(());
+        }
+        let drag_state = if part == FramePart::TitleBar {
+            DragState::Moving { window, start_pointer_x: x, start_pointer_y: y, start_frame_x: client.x, start_frame_y: client.y, snap: SnapZone::None }
+        } else {
+            DragState::Resizing { window, edge: part, start_pointer_x: x, start_pointer_y: y, start_frame_x: client.x, start_frame_y: client.y, start_width: client.width, start_height: client.height }
+        };
+        let cursor = self.get_cursor_for_part(part);
+        let grab = self.ctx.conn.grab_pointer(false, self.ctx.root_window, EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION, GrabMode::ASYNC, GrabMode::ASYNC, x11rb::NONE, cursor, x11rb::CURRENT_TIME)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            warn!("Failed to grab the pointer for the move or resize of window {}: {:?}", window, grab.status);
+            return Ok(());
+        }
+        info!("🎯 MOVERESIZE: {:?} on window {}", part, window);
+        self.drag_state = drag_state;
+        if matches!(self.drag_state, DragState::Resizing { .. }) {
+            self.update_size_popup(window);
+        }
+        Ok(())
+    }
+
+    /// The window being moved or resized
+    fn drag_window(&self) -> Option<Window> {
+        match self.drag_state {
+            DragState::Moving { window, .. } | DragState::Resizing { window, .. } => Some(window),
+            DragState::None => None,
+        }
+    }
+
+    /// A key press during a move or resize: the arrow keys take the
+    /// pointer a step on, a pixel with Shift, Enter puts the window down
+    /// and Escape puts it back. Returns whether the drag ended.
+    fn handle_drag_key(&mut self, keycode: u8, state: u16) -> Result<bool> {
+        let Some(key) = self.keybindings.navigation_keysym(keycode).and_then(MenuKey::from_keysym) else { return Ok(false) };
+        let fine = state & u16::from(ModMask::SHIFT) != 0;
+        match key {
+            MenuKey::Enter => self.finish_drag(),
+            MenuKey::Escape => self.cancel_drag(),
+            _ => {
+                if let Some((dx, dy)) = arrow_delta(key, self.keyboard_step, fine) {
+                    // The motion this makes moves or resizes the window
+                    self.ctx.conn.warp_pointer(x11rb::NONE, x11rb::NONE, 0, 0, 0, 0, dx, dy)?;
+                }
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Put a moved or resized window down, tiling it if it was dropped
+    /// on a snap zone
+    fn finish_drag(&mut self) {
+        if let DragState::Moving { window, snap, start_frame_x, start_frame_y, .. } = self.drag_state {
+            if snap != SnapZone::None {
+                let _ = self.apply_snap(window, snap);
+            } else if let Some(client) = self.clients.get_mut(&window).filter(|c| (c.x, c.y) != (start_frame_x, start_frame_y)) {
+                // Moved by hand, so no longer tiled
+                client.snap = SnapZone::None;
+            }
+        }
+        if let DragState::Resizing { window, .. } = self.drag_state {
+            if let Some(popup) = self.size_popup.take() { popup.hide(&self.ctx.conn); }
+            if let Some(client) = self.clients.get_mut(&window) { client.snap = SnapZone::None; }
+            self.send_configure_notify(window);
+        }
+        if self.drag_state != DragState::None {
+            let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME);
+            let _ = self.ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME);
+            self.drag_state = DragState::None;
+            self.edge_dwell.leave();
+        }
+    }
+
+    /// Give up a move or resize, putting the window back as it was
+    fn cancel_drag(&mut self) {
+        match self.drag_state {
+            DragState::Moving { window, start_frame_x, start_frame_y, ref mut snap, .. } => {
+                *snap = SnapZone::None;
+                self.move_frame(window, start_frame_x, start_frame_y);
+            }
+            DragState::Resizing { window, start_frame_x, start_frame_y, start_width, start_height, .. } => {
+                let start = x11rb::protocol::xproto::Rectangle { x: start_frame_x, y: start_frame_y, width: start_width, height: start_height };
+                self.resize_frame(window, start);
+            }
+            DragState::None => return,
+        }
+        self.finish_drag();
+    }
+
+    /// Move the frame of `window` to (`x`, `y`)
+    fn move_frame(&mut self, window: Window, x: i16, y: i16) {
+        let Some(client) = self.clients.get_mut(&window) else { return };
+        if let Some(frame) = client.frame {
+            let _ = self.ctx.conn.configure_window(frame, &ConfigureWindowAux::new().x(Some(x as i32)).y(Some(y as i32)));
+        }
+        client.x = x;
+        client.y = y;
+    }
+
+    /// Give `window` the frame position and client size of `rect`, as
+    /// resizing does
+    fn resize_frame(&mut self, window: Window, rect: x11rb::protocol::xproto::Rectangle) {
+        let Some(client) = self.clients.get_mut(&window) else { return };
+        client.x = rect.x;
+        client.y = rect.y;
+        client.width = rect.width;
+        client.height = rect.height;
+        if let Some(frame) = client.frame {
+            let (border, title) = if client.is_fullscreen || client.is_desktop || client.is_dock { (0, 0) } else { (BORDER_WIDTH, TITLE_HEIGHT) };
+            let (frame_w, frame_h) = client.frame_size(border, title);
+            let _ = self.ctx.conn.configure_window(frame, &ConfigureWindowAux::new().x(rect.x as i32).y(rect.y as i32).width(Some(frame_w as u32)).height(Some(frame_h as u32)));
+            let _ = self.ctx.conn.configure_window(window, &ConfigureWindowAux::new().width(Some(rect.width as u32)).height(Some(rect.height as u32)));
+            let _ = draw_decoration(&self.ctx, &self.theme, frame, &client.name, frame_w, frame_h, title, self.focused_window == Some(window), None);
+            let _ = self.update_window_shape(window);
+        }
+        self.client_xsync_request(window);
+    }
+
+    /// Keep `window` above normal windows, or stop doing so
+    fn toggle_above(&mut self, window: Window) -> Result<()> {
+        let Some(client) = self.clients.get(&window) else { return Ok(()) };
+        self.set_layer_state(window, !client.is_above, false)
+    }
+
+    /// Keep `window` below normal windows, or stop doing so
+    fn toggle_below(&mut self, window: Window) -> Result<()> {
+        let Some(client) = self.clients.get(&window) else { return Ok(()) };
+        self.set_layer_state(window, false, !client.is_below)
+    }
+
+    /// Keep `window` above or below normal windows, or neither, and
+    /// restack it into its new layer
+    fn set_layer_state(&mut self, window: Window, above: bool, below: bool) -> Result<()> {
+        use crate::window::{LAYER_BELOW, LAYER_NORMAL, LAYER_ONTOP};
+        let Some(client) = self.clients.get_mut(&window) else { return Ok(()) };
+        client.is_above = above;
+        client.is_below = below && !above;
+        // Desktops, docks and fullscreen windows keep their own layer
+        if matches!(client.layer, LAYER_BELOW | LAYER_NORMAL | LAYER_ONTOP) {
+            client.layer = if client.is_above { LAYER_ONTOP } else if client.is_below { LAYER_BELOW } else { LAYER_NORMAL };
+        }
+        self.update_net_wm_state(window)?;
+        self.raise_window(window);
+        self.relayout();
+        Ok(())
+    }
+
+    /// Put `window` on every workspace, or on the current one only
+    fn toggle_sticky(&mut self, window: Window) -> Result<()> {
+        let Some(client) = self.clients.get(&window) else { return Ok(()) };
+        self.set_sticky(window, !client.is_sticky)
+    }
+
+    fn set_sticky(&mut self, window: Window, sticky: bool) -> Result<()> {
+        self.move_to_workspace(window, if sticky { ALL_WORKSPACES } else { self.current_workspace })
+    }
+
     pub fn find_client_by_frame(&self, frame: Window) -> Option<&Client> {
         self.clients.values().find(|c| c.frame == Some(frame))
     }
 
-    pub fn update_current_desktop_prop(&self) -> Result<()> {
-        self.ctx.conn.change_property32(
-            PropMode::REPLACE,
-            self.ctx.root_window,
-            self.ctx.atoms._NET_CURRENT_DESKTOP,
-            AtomEnum::CARDINAL,
-            &[self.current_workspace],
-        )?;
-        Ok(())
+    pub fn update_current_desktop_prop(&self) -> Result<()> {
+        self.ctx.conn.change_property32(
+            PropMode::REPLACE,
+            self.ctx.root_window,
+            self.ctx.atoms._NET_CURRENT_DESKTOP,
+            AtomEnum::CARDINAL,
+            &[self.current_workspace],
+        )?;
+        Ok(())
+    }
+
+    /// Ask `window` to close, pinging it to notice if it hung. Windows
+    /// that cannot be asked are killed, as in xfwm4.
+    fn close_window(&mut self, window: Window) -> Result<()> {
+        if !self.is_protocol_supported(window, self.ctx.atoms.WM_DELETE_WINDOW) {
+            return self.kill_window(window);
+        }
+        self.send_delete_window(window)?;
+        if self.is_protocol_supported(window, self.ctx.atoms._NET_WM_PING) {
+            self.send_ping(window)?;
+            self.pings.sent(window, Instant::now());
+        }
+        Ok(())
+    }
+
+    fn send_ping(&self, window: Window) -> Result<()> {
+        use x11rb::protocol::xproto::{ClientMessageEvent, ClientMessageData};
+
+        let event = ClientMessageEvent {
+            response_type: x11rb::protocol::xproto::CLIENT_MESSAGE_EVENT,
+            format: 32,
+            window,
+            type_: self.ctx.atoms.WM_PROTOCOLS,
+            data: ClientMessageData::from([self.ctx.atoms._NET_WM_PING, x11rb::CURRENT_TIME, window, 0, 0]),
+            sequence: 0,
+        };
+        self.ctx.conn.send_event(false, window, EventMask::NO_EVENT, event)?;
+        Ok(())
+    }
+
+    /// Kill the application behind `window`: its process, from
+    /// `_NET_WM_PID`, when it runs on this machine, and its X connection
+    fn kill_window(&mut self, window: Window) -> Result<()> {
+        let pid = self.clients.get(&window).map_or(0, |c| c.pid);
+        if pid > 0 && self.is_local_client(window) {
+            info!("💀 KILL: Killing process {} of window {}", pid, window);
+            // SAFETY: kill() only sends a signal and touches no memory
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+        info!("💀 KILL: Closing the X connection of window {}", window);
+        self.ctx.conn.kill_client(window)?;
+        Ok(())
+    }
+
+    /// Whether `window`'s WM_CLIENT_MACHINE is this host, so its pid is
+    /// one of ours
+    fn is_local_client(&self, window: Window) -> bool {
+        let Ok(hostname) = std::fs::read_to_string("/proc/sys/kernel/hostname") else { return false };
+        self.ctx.conn.get_property(false, window, AtomEnum::WM_CLIENT_MACHINE, AtomEnum::STRING, 0, 64)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|reply| reply.value == hostname.trim().as_bytes())
+    }
+
+    /// Offer to force quit the windows that did not answer their ping in
+    /// time, and kill those the user chose to
+    fn check_hung_windows(&mut self) {
+        for window in self.pings.take_expired(Instant::now()) {
+            let Some(client) = self.clients.get(&window) else { continue };
+            if self.force_quit.contains_key(&window) {
+                continue;
+            }
+            warn!("🏓 PING: Window {} ('{}') is not responding", window, client.name);
+            match ForceQuitDialog::open(&client.name, (client.pid > 0).then_some(client.pid)) {
+                Ok(dialog) => { self.force_quit.insert(window, dialog); }
+                Err(e) => warn!("Failed to open the Force Quit dialog: {}", e),
+            }
+        }
+        let answered: Vec<(Window, bool)> = self.force_quit.iter_mut()
+            .filter_map(|(&window, dialog)| dialog.answer().map(|kill| (window, kill)))
+            .collect();
+        for (window, kill) in answered {
+            self.force_quit.remove(&window);
+            if kill {
+                log_warn(self.kill_window(window), "kill hung window");
+            }
+        }
+    }
+
+    /// When the run loop next has work besides events: sloppy focus or an
+    /// edge action coming due, a ping or launch running out, Force Quit
+    /// dialogs to check on, a drag-and-drop or a zoomed view to follow, or
+    /// windows dimming
+    fn next_timer(&self) -> Option<Instant> {
+        let dialogs = (!self.force_quit.is_empty()).then(|| Instant::now() + fade::FRAME_INTERVAL);
+        let drag = self.dnd_raise.is_dragging().then(|| Instant::now() + fade::FRAME_INTERVAL);
+        let zoom = self.zoom.is_active().then(|| Instant::now() + fade::FRAME_INTERVAL);
+        let dimming = self.opacity_rules.is_animating(Instant::now()).then(|| Instant::now() + fade::FRAME_INTERVAL);
+        [self.pointer_focus.deadline(), self.pings.deadline(), self.launches.deadline(), self.edge_dwell.deadline(), dialogs, drag, zoom, dimming].into_iter().flatten().min()
+    }
+
+    /// Pan the zoomed view after the pointer; returns whether it moved
+    fn follow_zoom(&mut self) -> bool {
+        if !self.compositor.active {
+            // Nothing to magnify with
+            self.zoom.reset();
+            return true;
+        }
+        let Some(pointer) = self.ctx.conn.query_pointer(self.ctx.root_window).ok().and_then(|c| c.reply().ok()) else { return false };
+        self.zoom.follow((pointer.root_x, pointer.root_y), (self.ctx.screen_width, self.ctx.screen_height))
+    }
+
+    /// Follow a drag-and-drop, whose source holds the pointer, and raise
+    /// the window it rests over once the delay has passed. Returns whether
+    /// a window was raised.
+    fn follow_drag(&mut self) -> bool {
+        let Some(pointer) = self.ctx.conn.query_pointer(self.ctx.root_window).ok().and_then(|c| c.reply().ok()) else { return false };
+        let buttons = u16::from(KeyButMask::BUTTON1 | KeyButMask::BUTTON2 | KeyButMask::BUTTON3);
+        if u16::from(pointer.mask) & buttons == 0 {
+            debug!("Drag-and-drop ended");
+            self.dnd_raise.stop();
+            return false;
+        }
+        let under = self.find_client_by_frame(pointer.child).map(|c| c.window).filter(|&w| self.is_switchable(w));
+        let Some(window) = self.dnd_raise.hover(under, Instant::now()) else { return false };
+        info!("🎯 DND: Raising window {} under the drag", window);
+        self.raise_window(window);
+        true
+    }
+
+    /// Run again in place, keeping the frames for the new instance to take
+    /// the windows over in. Returns only when that fails, compositing again.
+    fn restart(&mut self) -> Result<()> {
+        info!("🔄 Restarting in place");
+        // Kept windows stay redirected otherwise, and the new instance
+        // couldn't redirect them itself
+        let compositing = self.compositor.active;
+        self.compositor.release(&self.ctx.conn)?;
+        let e = match restart::exec_self(&self.ctx.frames) {
+            Ok(never) => match never {},
+            Err(e) => e,
+        };
+        if compositing {
+            if let Err(e) = self.compositor.enable(&self.ctx.conn) {
+                self.error_tracker.record_compositor_error("enable compositor again", e);
+            }
+        }
+        Err(e)
+    }
+
+    /// Ask the screensaver to lock the screen, and turn the display off
+    fn lock_and_blank(&self) {
+        info!("🔒 Locking the screen and turning the display off");
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async {
+                    let locked = async {
+                        let conn = zbus::Connection::session().await?;
+                        conn.call_method(Some("org.freedesktop.ScreenSaver"), "/org/freedesktop/ScreenSaver", Some("org.freedesktop.ScreenSaver"), "Lock", &()).await?;
+                        zbus::Result::Ok(())
+                    };
+                    if let Err(e) = locked.await {
+                        warn!("Failed to lock the screen: {}", e);
+                    }
+                });
+            }
+            Err(_) => warn!("No runtime to ask the screensaver to lock the screen from"),
+        }
+        log_warn(self.dpms.blank_now(&self.ctx.conn), "turn the display off");
+    }
+
+    /// Show the busy cursor over the desktop while applications launch
+    fn update_launch_cursor(&self) {
+        let cursor = if self.launches.is_busy() { self.cursors.busy } else { self.cursors.normal };
+        log_warn(
+            self.ctx.conn.change_window_attributes(self.ctx.root_window, &x11rb::protocol::xproto::ChangeWindowAttributesAux::new().cursor(cursor)),
+            "set root cursor",
+        );
     }
 
     pub fn send_delete_window(&self, window: Window) -> Result<()> {
@@ -676,52 +1999,132 @@ impl WindowManager {
              FramePart::RightBorder => self.cursors.resize_e,
              FramePart::TopBorder => self.cursors.resize_n,
              FramePart::BottomBorder => self.cursors.resize_s,
-             FramePart::CloseButton => self.cursors.hand,
+             part if part.is_button() => self.cursors.hand,
              FramePart::TitleBar => self.cursors.move_,
              _ => self.cursors.normal,
         }
     }
 
     pub fn apply_snap(&mut self, window: Window, zone: SnapZone) -> Result<()> {
-        let (wa_x, wa_y, wa_w, wa_h) = self.calculate_workarea();
-        use crate::window::frame::{BORDER_WIDTH, TITLE_HEIGHT};
-        
         if zone == SnapZone::Top {
             return self.toggle_maximize(window);
         }
+        let area = self.monitor_workarea(self.window_monitor(window));
+        let Some(rect) = zone.frame_rect(area) else { return Ok(()) };
 
-        let (new_x, new_y, f_w, f_h) = match zone {
-            SnapZone::Left => (wa_x, wa_y, wa_w / 2, wa_h),
-            SnapZone::Right => (wa_x + (wa_w / 2) as i16, wa_y, wa_w / 2, wa_h),
-            _ => return Ok(()),
+        if let Some(client) = self.clients.get_mut(&window) {
+            // Tiling again keeps the size from before the first time
+            if !client.is_maximized && !client.is_fullscreen && client.snap == SnapZone::None {
+                client.saved_geometry = Some((client.x, client.y, client.width, client.height));
+            }
+            client.snap = zone;
+        }
+        self.place_frame(window, rect);
+        self.update_net_wm_state(window)?;
+        Ok(())
+    }
+
+    /// Where the frame of `window`, being moved to (`x`, `y`) with the
+    /// pointer at (`pointer_x`, `pointer_y`), goes once snapped to the work
+    /// area of the monitor with the pointer and to the other windows shown
+    fn snap_moving_frame(&self, window: Window, x: i16, y: i16, pointer_x: i16, pointer_y: i16) -> (i16, i16) {
+        let frame_rect = |c: &Client| {
+            let (b, t) = if c.is_fullscreen || c.is_desktop || c.is_dock || c.is_csd { (0, 0) } else { (BORDER_WIDTH, TITLE_HEIGHT) };
+            let (width, height) = c.frame_size(b, t);
+            x11rb::protocol::xproto::Rectangle { x: c.x, y: c.y, width, height }
         };
+        let Some(client) = self.clients.get(&window) else { return (x, y) };
+        let rect = x11rb::protocol::xproto::Rectangle { x, y, ..frame_rect(client) };
+        let area = self.monitor_workarea(monitors::monitor_at(&self.monitors, pointer_x, pointer_y));
+        let others: Vec<_> = self.clients.values()
+            .filter(|c| c.window != window && c.frame.is_some() && !c.is_desktop && !c.is_minimized)
+            .filter(|c| c.workspace == self.current_workspace || c.workspace == ALL_WORKSPACES)
+            .map(frame_rect)
+            .collect();
+        self.snapping.snap(rect, area, &others)
+    }
 
-        if let Some(client) = self.clients.get_mut(&window) {
-            if let Some(frame) = client.frame {
-                if !client.is_maximized && !client.is_fullscreen {
-                    client.saved_geometry = Some((client.x, client.y, client.width, client.height));
-                }
+    /// Move and resize the frame of `window` to `rect`, the client filling
+    /// it inside the decorations
+    fn place_frame(&mut self, window: Window, rect: x11rb::protocol::xproto::Rectangle) {
+        use crate::window::frame::{BORDER_WIDTH, TITLE_HEIGHT};
 
-                let c_w = f_w.saturating_sub((2 * BORDER_WIDTH) as u16);
-                let c_h = f_h.saturating_sub((TITLE_HEIGHT + 2 * BORDER_WIDTH) as u16);
+        let Some(client) = self.clients.get_mut(&window) else { return };
+        let Some(frame) = client.frame else { return };
+        let c_w = rect.width.saturating_sub(2 * BORDER_WIDTH);
+        let c_h = rect.height.saturating_sub(TITLE_HEIGHT + 2 * BORDER_WIDTH);
 
-                let _ = self.ctx.conn.configure_window(frame, &x11rb::protocol::xproto::ConfigureWindowAux::new().x(new_x as i32).y(new_y as i32).width(f_w as u32).height(f_h as u32));
-                let _ = self.ctx.conn.configure_window(window, &x11rb::protocol::xproto::ConfigureWindowAux::new().width(c_w as u32).height(c_h as u32));
-                
-                client.x = new_x; client.y = new_y; client.width = c_w; client.height = c_h;
-                client.is_maximized = false;
+        let _ = self.ctx.conn.configure_window(frame, &x11rb::protocol::xproto::ConfigureWindowAux::new().x(rect.x as i32).y(rect.y as i32).width(rect.width as u32).height(rect.height as u32));
+        let _ = self.ctx.conn.configure_window(window, &x11rb::protocol::xproto::ConfigureWindowAux::new().width(c_w as u32).height(c_h as u32));
+
+        client.x = rect.x; client.y = rect.y; client.width = c_w; client.height = c_h;
+        client.is_maximized = false;
+        self.send_configure_notify(window);
+    }
+
+    /// Lay out the windows of the current workspace master/stack, each
+    /// monitor on its own, if the workspace is tiled automatically
+    fn relayout(&mut self) {
+        let workspace = self.current_workspace;
+        if !self.tiling.is_enabled(workspace) { return; }
+        let windows = self.clients.values().filter(|c| c.workspace == workspace && is_tileable(c)).map(|c| c.window).collect();
+        let windows = self.tiling.ordered(windows);
+        for monitor in 0..self.monitors.len() {
+            let on_monitor: Vec<Window> = windows.iter().copied().filter(|&w| self.window_monitor(w) == monitor).collect();
+            let rects = master_stack(self.monitor_workarea(monitor), on_monitor.len(), self.tiling.master_ratio);
+            for (window, rect) in on_monitor.into_iter().zip(rects) {
+                self.place_frame(window, rect);
             }
         }
-        self.update_net_wm_state(window)?;
+    }
+
+    /// With the automatic layout, move the windows of the current workspace
+    /// up a place, the master going last; otherwise focus the next window
+    /// tiled by shortcut
+    fn cycle_tiled(&mut self, time: u32) -> Result<()> {
+        let workspace = self.current_workspace;
+        if self.tiling.is_enabled(workspace) {
+            let windows = self.clients.values().filter(|c| c.workspace == workspace && is_tileable(c)).map(|c| c.window).collect();
+            let windows = self.tiling.ordered(windows);
+            self.tiling.rotate(&windows);
+            self.relayout();
+            if let Some(&master) = self.tiling.ordered(windows).first() {
+                self.activate_window(master, time)?;
+            }
+            return Ok(());
+        }
+        let tiled = self.clients.values()
+            .filter(|c| c.snap != SnapZone::None && !c.is_minimized && (c.workspace == workspace || c.workspace == ALL_WORKSPACES))
+            .map(|c| c.window)
+            .collect();
+        let tiled = self.tiling.ordered(tiled);
+        let next = match self.focused_window.and_then(|f| tiled.iter().position(|&w| w == f)) {
+            Some(index) => tiled.get(index + 1).or(tiled.first()),
+            None => tiled.first(),
+        };
+        if let Some(&next) = next {
+            self.activate_window(next, time)?;
+        }
         Ok(())
     }
 
-    pub fn paint(&self) -> Result<()> {
+    pub fn paint(&mut self) -> Result<()> {
         if !self.compositor.active { return Ok(()); }
         debug!("Compositor painting...");
+        let screen = (self.ctx.screen_width, self.ctx.screen_height);
+        self.compositor.set_zoom(&self.ctx.conn, self.zoom.source(screen), screen.0, screen.1)?;
+
+        let now = Instant::now();
+        // Windows losing the focus dim, the one getting it lights up
+        for client in self.clients.values() {
+            let dimmed = Some(client.window) != self.focused_window && !client.is_desktop && !client.is_dock;
+            self.opacity_rules.set_dimmed(client.window, dimmed, now);
+        }
 
         let mut layered_clients: Vec<(u16, usize, &Client)> = self.mru_stack.iter().enumerate().filter_map(|(idx, &win_id)| {
-            self.clients.get(&win_id).map(|c| (c.layer, idx, c))
+            // Panels revealed from a screen edge go over everything
+            let revealed = self.revealed_panels.contains(&win_id);
+            self.clients.get(&win_id).map(|c| (if revealed { crate::window::LAYER_NOTIFICATION } else { c.layer }, idx, c))
         }).collect();
         
         // Sort by layer (ascending), then by mru index (descending - Painter's Algorithm)
@@ -734,7 +2137,9 @@ impl WindowManager {
         });
 
         let sorted_clients = layered_clients.into_iter().filter_map(|(_, _, client)| {
-            if (client.workspace == self.current_workspace || client.workspace == 4294967295) && !client.is_minimized {
+            // Windows being hidden stay on screen until they faded out
+            let fade = self.fades.opacity(client.window, now);
+            if ((client.workspace == self.current_workspace || client.workspace == 4294967295) && !client.is_minimized) || fade.is_some() {
                 if let Some(content_pic) = client.content_picture {
                    // Docks and Desktops have no borders
                    let (b, t) = if client.is_desktop || client.is_dock || client.is_fullscreen { 
@@ -743,10 +2148,13 @@ impl WindowManager {
                        (crate::window::frame::BORDER_WIDTH, crate::window::frame::TITLE_HEIGHT) 
                    };
                    
-                   let w = client.width + (2 * b);
-                   let h = client.height + t + (2 * b);
+                   let (w, h) = client.frame_size(b, t);
+                   // Shaded windows show their titlebar only
+                   let content_h = if client.is_shaded && t > 0 { 0 } else { client.height };
                    let has_shadow = !client.is_csd && !client.is_desktop && !client.is_dock;
-                   return Some((client.picture, content_pic, client.x, client.y, w, h, b, t, client.width, client.height, has_shadow, client.opacity));
+                   let opacity = if self.compositor.effects.window_opacity { client.opacity } else { 0xFFFFFFFF };
+                   let opacity = (f64::from(opacity) * fade.unwrap_or(1.0) * self.opacity_rules.factor(client.window, client.class.as_deref(), now)) as u32;
+                   return Some((client.picture, content_pic, client.x, client.y, w, h, b, t, client.width, content_h, has_shadow, opacity));
                 }
             }
             None
@@ -758,11 +2166,39 @@ impl WindowManager {
         
         let all_items = sorted_clients.chain(unmanaged_list);
 
-        self.compositor.paint(&self.ctx.conn, self.ctx.screen_width, self.ctx.screen_height, all_items)?;
+        self.compositor.paint(&self.ctx.conn, self.ctx.screen_width, self.ctx.screen_height, all_items, self.repaint.clip())?;
+
+        // Live previews over the switcher's cells
+        if let Some(switcher) = &self.switcher {
+            for (window, area) in switcher.thumbnail_areas() {
+                let Some(client) = self.clients.get(&window).filter(|c| !c.is_minimized) else { continue };
+                let Some(content_pic) = client.content_picture else { continue };
+                let dest = fit(client.width, client.height, area);
+                if let Err(e) = self.compositor.paint_scaled(&self.ctx.conn, content_pic, client.width, client.height, dest) {
+                    debug!("Failed to paint thumbnail of window {}: {}", window, e);
+                }
+            }
+        }
+        if let Some(overview) = &self.overview {
+            for (window, area) in overview.thumbnail_areas() {
+                let Some(client) = self.clients.get(&window).filter(|c| !c.is_minimized) else { continue };
+                let Some(content_pic) = client.content_picture else { continue };
+                let dest = fit(client.width, client.height, area);
+                if let Err(e) = self.compositor.paint_scaled(&self.ctx.conn, content_pic, client.width, client.height, dest) {
+                    debug!("Failed to paint overview of window {}: {}", window, e);
+                }
+            }
+        }
+        // Thumbnails change outside the damage
+        let update = if self.switcher.is_some() || self.overview.is_some() { None } else { self.repaint.clip() };
+        self.compositor.present(&self.ctx.conn, update)?;
         Ok(())
     }
 
     pub fn toggle_maximize(&mut self, window: Window) -> Result<()> {
+        if self.clients.get(&window).is_some_and(|c| c.is_shaded) {
+            self.set_shaded(window, false)?;
+        }
         let (maximized, saved_geom, frame_win, client_width, client_height, start_x, start_y) = {
              if let Some(client) = self.clients.get(&window) {
                  if client.frame.is_none() { return Ok(()); }
@@ -802,7 +2238,8 @@ impl WindowManager {
                  self.update_net_wm_state(window)?;
              }
         } else {
-             let (wa_x, wa_y, wa_w, wa_h) = self.calculate_workarea();
+             let area = self.monitor_workarea(self.window_monitor(window));
+             let (wa_x, wa_y, wa_w, wa_h) = (area.x, area.y, area.width, area.height);
              let saved = (start_x, start_y, client_width, client_height);
              
              let new_client_w = (wa_w as u32).saturating_sub((2 * BORDER_WIDTH) as u32);
@@ -825,6 +2262,7 @@ impl WindowManager {
              }
              self.update_net_wm_state(window)?;
         }
+        self.relayout();
         Ok(())
     }
 
@@ -842,26 +2280,29 @@ impl WindowManager {
             // Restore: Map frame and client
             self.ctx.conn.map_window(frame_win)?;
             self.ctx.conn.map_window(window)?;
+            self.fades.fade_in(window, Instant::now());
             
             if let Some(client) = self.clients.get_mut(&window) {
                 client.is_minimized = false;
             }
             let _ = self.focus_window(window);
         } else {
-            // Minimize: Unmap frame and client
-            self.ctx.conn.unmap_window(frame_win)?;
-            self.ctx.conn.unmap_window(window)?;
-            
+            // Minimize: Unmap frame and client, once faded out
             if let Some(client) = self.clients.get_mut(&window) {
                 client.is_minimized = true;
             }
+            self.fade_out_and_hide(window)?;
         }
         
         self.update_net_wm_state(window)?;
+        self.relayout();
         Ok(())
     }
 
     pub fn toggle_fullscreen(&mut self, window: Window) -> Result<()> {
+        if self.clients.get(&window).is_some_and(|c| c.is_shaded) {
+            self.set_shaded(window, false)?;
+        }
         let (fullscreen, saved_geom, frame_win, client_width, client_height, start_x, start_y) = {
              if let Some(client) = self.clients.get(&window) {
                  if client.frame.is_none() { return Ok(()); }
@@ -901,13 +2342,13 @@ impl WindowManager {
                  self.update_net_wm_state(window)?;
              }
         } else {
-             let screen = &self.ctx.conn.setup().roots[self.ctx.screen_num];
-             let screen_w = screen.width_in_pixels;
-             let screen_h = screen.height_in_pixels;
+             // The monitor the window is on, not the whole screen
+             let monitor = self.monitors[self.window_monitor(window)].rect();
+             let (screen_w, screen_h) = (monitor.width, monitor.height);
              let saved = (start_x, start_y, client_width, client_height);
              
              use x11rb::protocol::xproto::ConfigureWindowAux;
-             let values = ConfigureWindowAux::new().x(0).y(0).width(screen_w as u32).height(screen_h as u32);
+             let values = ConfigureWindowAux::new().x(monitor.x as i32).y(monitor.y as i32).width(screen_w as u32).height(screen_h as u32);
              self.ctx.conn.configure_window(frame_win, &values)?;
              
              let c_values = ConfigureWindowAux::new().width(screen_w as u32).height(screen_h as u32);
@@ -916,16 +2357,39 @@ impl WindowManager {
              if let Some(client) = self.clients.get_mut(&window) {
                  client.is_fullscreen = true;
                  client.saved_geometry = Some(saved);
-                 client.x = 0;
-                 client.y = 0;
+                 client.x = monitor.x;
+                 client.y = monitor.y;
                  client.width = screen_w;
                  client.height = screen_h;
              }
              self.update_net_wm_state(window)?;
         }
+        self.relayout();
         Ok(())
     }
 
+    pub fn toggle_shade(&mut self, window: Window) -> Result<()> {
+        let Some(shaded) = self.clients.get(&window).map(|c| c.is_shaded) else { return Ok(()) };
+        self.set_shaded(window, !shaded)
+    }
+
+    /// Roll the frame of `window` up to its titlebar, or back down. The
+    /// client stays mapped, clipped by the frame, since unmapping it would
+    /// read as the client withdrawing. Windows without a titlebar don't shade.
+    fn set_shaded(&mut self, window: Window, shaded: bool) -> Result<()> {
+        let Some(client) = self.clients.get_mut(&window) else { return Ok(()) };
+        let Some(frame) = client.frame else { return Ok(()) };
+        if client.is_fullscreen || client.is_desktop || client.is_dock || client.is_csd {
+            return Ok(());
+        }
+        client.is_shaded = shaded;
+        let (_, height) = client.frame_size(BORDER_WIDTH, TITLE_HEIGHT);
+        self.ctx.conn.configure_window(frame, &ConfigureWindowAux::new().height(height as u32))?;
+        self.redraw_decoration(window);
+        log_warn(self.update_window_shape(window), "update shape of shaded window");
+        self.update_net_wm_state(window)
+    }
+
     fn update_net_wm_state(&self, window: Window) -> Result<()> {
         let client = if let Some(c) = self.clients.get(&window) { c } else { return Ok(()); };
         let mut states = Vec::new();
@@ -960,6 +2424,9 @@ impl WindowManager {
         if client.is_below {
             states.push(self.ctx.atoms._NET_WM_STATE_BELOW);
         }
+        if client.is_sticky {
+            states.push(self.ctx.atoms._NET_WM_STATE_STICKY);
+        }
         
         self.ctx.conn.change_property32(
             PropMode::REPLACE,
@@ -989,52 +2456,158 @@ impl WindowManager {
         Ok(None)
     }
     
-    fn calculate_workarea(&self) -> (i16, i16, u16, u16) {
-        let screen = &self.ctx.conn.setup().roots[self.ctx.screen_num];
-        let screen_w = screen.width_in_pixels as i32;
-        let screen_h = screen.height_in_pixels as i32;
-        
-        let mut left_margin = 0;
-        let mut right_margin = 0;
-        let mut top_margin = 0;
-        let mut bottom_margin = 0;
-        
-        for client in self.clients.values() {
-            if let Some(strut) = &client.strut {
-                 if strut.len() >= 4 {
-                     left_margin = left_margin.max(strut[0] as i32);
-                     right_margin = right_margin.max(strut[1] as i32);
-                     top_margin = top_margin.max(strut[2] as i32);
-                     bottom_margin = bottom_margin.max(strut[3] as i32);
-                 }
+    /// `_NET_WORKAREA`: for each workspace, the box around the work areas
+    /// of the monitors, with the panels shown on that workspace
+    fn update_net_workarea(&self) -> Result<()> {
+        let mut workarea = Vec::with_capacity(4 * self.workspaces.count() as usize);
+        for workspace in 0..self.workspaces.count() {
+            let areas: Vec<_> = (0..self.monitors.len()).map(|monitor| self.workarea_on(monitor, workspace)).collect();
+            // Follows RandR resizes, unlike the connection setup
+            let area = monitors::bounding(&areas).unwrap_or(x11rb::protocol::xproto::Rectangle { x: 0, y: 0, width: self.ctx.screen_width, height: self.ctx.screen_height });
+            workarea.extend_from_slice(&[area.x as u32, area.y as u32, u32::from(area.width), u32::from(area.height)]);
+        }
+        self.ctx.conn.change_property32(PropMode::REPLACE, self.ctx.root_window, self.ctx.atoms._NET_WORKAREA, AtomEnum::CARDINAL, &workarea)?;
+        Ok(())
+    }
+
+    /// Publish the workspaces for pagers: their number, names and
+    /// viewports, and a work area for each
+    fn update_desktop_props(&self) -> Result<()> {
+        let root = self.ctx.root_window;
+        self.ctx.conn.change_property32(PropMode::REPLACE, root, self.ctx.atoms._NET_NUMBER_OF_DESKTOPS, AtomEnum::CARDINAL, &[self.workspaces.count()])?;
+        self.ctx.conn.change_property8(PropMode::REPLACE, root, self.ctx.atoms._NET_DESKTOP_NAMES, self.ctx.atoms.UTF8_STRING, &self.workspaces.names_property())?;
+        self.ctx.conn.change_property32(PropMode::REPLACE, root, self.ctx.atoms._NET_DESKTOP_VIEWPORT, AtomEnum::CARDINAL, &self.workspaces.viewport_property())?;
+        self.update_net_workarea()
+    }
+
+    /// Add or remove workspaces at the end. Windows on removed workspaces
+    /// move to the last one left, and so does the view if it was on one.
+    pub fn set_workspace_count(&mut self, count: u32) -> Result<()> {
+        self.workspaces.set_count(count);
+        let last = self.workspaces.count() - 1;
+        info!("🗂️ {} workspace(s)", last + 1);
+        let orphans: Vec<Window> = self.clients.values()
+            .filter(|c| c.workspace != ALL_WORKSPACES && c.workspace > last)
+            .map(|c| c.window)
+            .collect();
+        for window in orphans {
+            self.move_to_workspace(window, last)?;
+        }
+        if self.current_workspace > last {
+            self.switch_workspace(last)?;
+        }
+        self.update_desktop_props()
+    }
+
+    /// Put `window` on `workspace`, or on all of them for
+    /// `ALL_WORKSPACES`, showing or hiding it as needed
+    pub fn move_to_workspace(&mut self, window: Window, workspace: u32) -> Result<()> {
+        let workspace = self.workspaces.clamp(workspace);
+        let current = self.current_workspace;
+        let Some(client) = self.clients.get_mut(&window) else { return Ok(()) };
+        if client.workspace == workspace { return Ok(()); }
+        let was_visible = client.workspace == current || client.workspace == ALL_WORKSPACES;
+        client.workspace = workspace;
+        client.is_sticky = workspace == ALL_WORKSPACES;
+        let visible = workspace == current || workspace == ALL_WORKSPACES;
+        if let Some(frame) = client.frame.filter(|_| !client.is_minimized && visible != was_visible) {
+            if visible {
+                self.ctx.conn.map_window(frame)?;
+                self.ctx.conn.map_window(window)?;
+                self.fades.fade_in(window, Instant::now());
+            } else {
+                self.fade_out_and_hide(window)?;
+            }
+        }
+        self.update_wm_desktop(window);
+        self.update_net_wm_state(window)?;
+        if !visible && self.focused_window == Some(window) {
+            let next = self.mru_stack.iter().copied().find(|&w| w != window && self.clients.get(&w).is_some_and(|c| !c.is_minimized && (c.workspace == current || c.workspace == ALL_WORKSPACES)));
+            if let Some(next) = next {
+                let _ = self.focus_window(next);
             }
         }
-        (left_margin as i16, top_margin as i16, (screen_w - left_margin - right_margin).max(1) as u16, (screen_h - top_margin - bottom_margin).max(1) as u16)
+        self.relayout();
+        Ok(())
     }
 
-    fn update_net_workarea(&self) -> Result<()> {
-        let (x, y, w, h) = self.calculate_workarea();
-        let single_wa = [x as u32, y as u32, w as u32, h as u32];
-        let mut workarea = Vec::with_capacity(16);
-        for _ in 0..4 {
-            workarea.extend_from_slice(&single_wa);
+    /// Hide `window` once it faded out, or right away when windows don't fade
+    fn fade_out_and_hide(&mut self, window: Window) -> Result<()> {
+        if !self.fades.fade_out(window, Instant::now()) {
+            self.hide_window(window)?;
         }
-        self.ctx.conn.change_property32(PropMode::REPLACE, self.ctx.root_window, self.ctx.atoms._NET_WORKAREA, AtomEnum::CARDINAL, &workarea)?;
         Ok(())
     }
 
+    /// Unmap `window` as its state is by the time it faded out: all of it
+    /// when minimized, the frame when on another workspace
+    fn hide_window(&self, window: Window) -> Result<()> {
+        let Some(client) = self.clients.get(&window) else { return Ok(()) };
+        let Some(frame) = client.frame else { return Ok(()) };
+        if client.is_minimized {
+            self.ctx.conn.unmap_window(frame)?;
+            self.ctx.conn.unmap_window(window)?;
+        } else if client.workspace != self.current_workspace && client.workspace != ALL_WORKSPACES {
+            self.ctx.conn.unmap_window(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Where the contents of a client or unmanaged window are on screen,
+    /// `None` when they aren't painted
+    fn content_origin(&self, window: Window) -> Option<(i16, i16)> {
+        if let Some(unmanaged) = self.unmanaged_windows.get(&window) {
+            return Some((unmanaged.x, unmanaged.y));
+        }
+        let client = self.clients.get(&window)?;
+        let visible = (client.workspace == self.current_workspace || client.workspace == ALL_WORKSPACES) && !client.is_minimized;
+        if !(visible || self.fades.is_fading(window)) {
+            return None;
+        }
+        if client.is_desktop || client.is_dock || client.is_fullscreen {
+            return Some((client.x, client.y));
+        }
+        if client.is_shaded {
+            return None;
+        }
+        let (b, t) = (crate::window::frame::BORDER_WIDTH, crate::window::frame::TITLE_HEIGHT);
+        Some((client.x + b as i16, client.y + (b + t) as i16))
+    }
+
+    /// Hide the windows that finished fading out
+    fn finish_fades(&mut self) {
+        for window in self.fades.finish(Instant::now()) {
+            log_warn(self.hide_window(window), "hide faded window");
+        }
+    }
+
+    /// Publish the workspace of `window` in its `_NET_WM_DESKTOP`
+    fn update_wm_desktop(&self, window: Window) {
+        let Some(client) = self.clients.get(&window) else { return };
+        log_warn(self.ctx.conn.change_property32(PropMode::REPLACE, window, self.ctx.atoms._NET_WM_DESKTOP, AtomEnum::CARDINAL, &[client.workspace]), "set _NET_WM_DESKTOP");
+    }
+
     pub fn switch_workspace(&mut self, workspace: u32) -> Result<()> {
         if workspace == self.current_workspace { return Ok(()); }
-        self.current_workspace = workspace;
-        for client in self.clients.values() {
-            if client.workspace == 0xFFFFFFFF { continue; }
-            if let Some(frame) = client.frame {
-                if client.workspace == workspace {
-                    self.ctx.conn.map_window(frame)?;
-                    self.ctx.conn.map_window(client.window)?;
-                } else {
-                    self.ctx.conn.unmap_window(frame)?;
-                }
+        if workspace >= self.workspaces.count() {
+            debug!("No workspace {}, there are {}", workspace, self.workspaces.count());
+            return Ok(());
+        }
+        let previous = std::mem::replace(&mut self.current_workspace, workspace);
+        let now = Instant::now();
+        let windows: Vec<(Window, Window, u32)> = self.clients.values()
+            .filter(|c| c.workspace != 0xFFFFFFFF && !c.is_minimized)
+            .filter_map(|c| Some((c.window, c.frame?, c.workspace)))
+            .collect();
+        for (window, frame, on) in windows {
+            if on == workspace {
+                self.ctx.conn.map_window(frame)?;
+                self.ctx.conn.map_window(window)?;
+                self.fades.fade_in(window, now);
+            } else if on == previous {
+                self.fade_out_and_hide(window)?;
+            } else {
+                self.hide_window(window)?;
             }
         }
         self.update_current_desktop_prop()?;
@@ -1046,6 +2619,7 @@ impl WindowManager {
         }) {
              let _ = self.focus_window(top_win);
         }
+        self.relayout();
         Ok(())
     }
 
@@ -1063,6 +2637,54 @@ impl WindowManager {
         false
     }
 
+    /// Focus a window that just appeared, unless that would take the focus
+    /// from what the user is doing; it asks for attention instead then.
+    /// `user_time` is its `_NET_WM_USER_TIME`, if it set one.
+    fn focus_new_window(&mut self, window: Window, user_time: Option<u32>) {
+        let Some(client) = self.clients.get(&window) else { return };
+        let focused = self.focused_window.filter(|&w| w != window).and_then(|w| self.clients.get(&w));
+        // Dialogs go on with what the user is doing in their parent
+        let for_focused = client.is_modal || focused.is_some_and(|f| client.transient_for == Some(f.window));
+        let focus = for_focused
+            || (self.drag_state == DragState::None
+                && focused.is_none_or(|f| f.layer <= client.layer)
+                && self.focus_policy.focuses_new(user_time, focused.map(|f| f.user_time)));
+        if focus {
+            let _ = self.focus_window(window);
+            return;
+        }
+        info!("🎯 FOCUS: Leaving the focus be as window {} appears", window);
+        // Next in line for Alt+Tab, after the focused window
+        self.mru_stack.retain(|&w| w != window);
+        let at = usize::from(!self.mru_stack.is_empty());
+        self.mru_stack.insert(at, window);
+        self.demand_attention(window);
+    }
+
+    /// Flag `window` as wanting the user's attention, for the taskbar
+    fn demand_attention(&mut self, window: Window) {
+        let Some(client) = self.clients.get_mut(&window) else { return };
+        if !client.demands_attention {
+            client.demands_attention = true;
+            log_warn(self.update_net_wm_state(window), "flag window for attention");
+        }
+    }
+
+    /// With sloppy focus, focus `window` the pointer rests over, raising it
+    /// if so configured. Returns whether the focus moved.
+    fn focus_under_pointer(&mut self, window: Window) -> bool {
+        let Some(client) = self.clients.get(&window) else { return false };
+        let shown = !client.is_minimized && (client.workspace == self.current_workspace || client.workspace == ALL_WORKSPACES);
+        if !shown || self.focused_window == Some(window) || self.drag_state != DragState::None || self.switcher.is_some() || self.overview.is_some() {
+            return false;
+        }
+        if self.focus_policy.raise_on_focus {
+            self.raise_window(window);
+        }
+        log_warn(self.focus_window(window), "focus window under the pointer");
+        true
+    }
+
         pub fn focus_window(&mut self, window: Window) -> Result<()> {
         use x11rb::protocol::xproto::{InputFocus, ClientMessageEvent, ClientMessageData, EventMask};
         
@@ -1078,13 +2700,13 @@ impl WindowManager {
         }
 
     let mut update_new_state = false;
-    let (accepts_input, layer, user_time, is_modal, name) = {
+    let (accepts_input, name) = {
         if let Some(client) = self.clients.get_mut(&target_window) {
             if client.demands_attention {
                 client.demands_attention = false;
                 update_new_state = true;
             }
-            (client.accepts_input, client.layer, client.user_time, client.is_modal, client.name.clone())
+            (client.accepts_input, client.name.clone())
         } else {
             return Ok(());
         }
@@ -1094,28 +2716,6 @@ impl WindowManager {
         let _ = self.update_net_wm_state(target_window);
     }
 
-    // Focus Stealing Prevention
-    if let Some(&current_focus) = self.mru_stack.first() {
-        if current_focus != target_window {
-            if let Some(focused_client) = self.clients.get(&current_focus) {
-                let mut prevent = false;
-                if focused_client.layer > layer {
-                    prevent = true;
-                }
-                if user_time == 0 || Self::timestamp_is_before(user_time, focused_client.user_time) {
-                    prevent = true;
-                }
-                if self.drag_state != DragState::None {
-                    prevent = true;
-                }
-                if prevent && !is_modal {
-                     info!("🎯 FOCUS: Prevention active for window {}", target_window);
-                     return Ok(());
-                }
-            }
-        }
-    }
-
     info!("🎯 FOCUS: Focusing window {}, name='{}'", target_window, name);
     
     let supports_take_focus = self.is_protocol_supported(target_window, self.ctx.atoms.WM_TAKE_FOCUS);
@@ -1139,8 +2739,10 @@ impl WindowManager {
                 let _ = self.ctx.conn.change_property32(PropMode::REPLACE, self.ctx.root_window, self.ctx.atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW, &[target_window]);
                 if let Some(old) = old_focus {
                     let _ = self.update_net_wm_state(old);
+                    self.redraw_decoration(old);
                 }
                 let _ = self.update_net_wm_state(target_window);
+                self.redraw_decoration(target_window);
             },
             Err(e) => error!("❌ FOCUS: Failed for window {}: {}", target_window, e),
         }
@@ -1198,15 +2800,16 @@ impl WindowManager {
     }
 
 
-    fn read_user_time(&self, window: Window) -> u32 {
+    /// `_NET_WM_USER_TIME` of `window`, `None` when it hasn't set one
+    fn read_user_time(&self, window: Window) -> Option<u32> {
         if let Ok(cookie) = self.ctx.conn.get_property(false, window, self.ctx.atoms._NET_WM_USER_TIME, AtomEnum::CARDINAL, 0, 1) {
              if let Ok(reply) = cookie.reply() {
                  if let Some(val) = reply.value32().and_then(|mut i| i.next()) {
-                     return val;
+                     return Some(val);
                  }
              }
         }
-        0
+        None
     }
 
     fn read_opacity(&self, window: Window) -> u32 {
@@ -1330,15 +2933,6 @@ impl WindowManager {
         None
     }
 
-    fn timestamp_is_before(time1: u32, time2: u32) -> bool {
-        if time1 == 0 { return true; }
-        if time2 == 0 { return false; }
-        
-        // Wrapping sub for 32-bit timestamps
-        let diff = time2.wrapping_sub(time1);
-        diff < (u32::MAX >> 1)
-    }
-
     fn read_user_time_window(&self, window: Window) -> Option<Window> {
         if let Ok(cookie) = self.ctx.conn.get_property(false, window, self.ctx.atoms._NET_WM_USER_TIME_WINDOW, AtomEnum::WINDOW, 0, 1) {
             if let Ok(reply) = cookie.reply() {
@@ -1361,26 +2955,18 @@ impl WindowManager {
         None
     }
 
-    fn read_size_hints(&self, window: Window) -> (i32, i16, i16, u16, u16) {
-        // Returns (gravity, min_w, min_h, max_w, max_h)
+    fn read_size_hints(&self, window: Window) -> SizeHints {
         if let Ok(cookie) = self.ctx.conn.get_property(false, window, AtomEnum::WM_NORMAL_HINTS, AtomEnum::ANY, 0, 18) {
             if let Ok(reply) = cookie.reply() {
                 if reply.format == 32 && reply.value_len >= 15 {
                     if let Some(vals) = reply.value32() {
                         let data: Vec<u32> = vals.collect();
-                        let flags = data[0];
-                        let min_w = if flags & (1 << 4) != 0 { data[5] as i16 } else { 0 };
-                        let min_h = if flags & (1 << 4) != 0 { data[6] as i16 } else { 0 };
-                        let max_w = if flags & (1 << 5) != 0 { data[7] as u16 } else { 0 };
-                        let max_h = if flags & (1 << 5) != 0 { data[8] as u16 } else { 0 };
-                        let gravity = if flags & (1 << 8) != 0 && data.len() >= 18 { data[17] as i32 } else { 1 };
-                        
-                        return (gravity, min_w, min_h, max_w, max_h);
+                        return SizeHints::from_property(&data);
                     }
                 }
             }
         }
-        (1, 0, 0, 0, 0)
+        SizeHints::default()
     }
 
     fn gravitate(gravity: i32, mode: i32, border: u16, title: u16, x: &mut i16, y: &mut i16) {
@@ -1565,23 +3151,34 @@ impl WindowManager {
                                 resized = true;
                             }
                             if mask.contains(ConfigWindow::HEIGHT) { 
-                                let fh = req_h + t + (2 * b);
-                                aux = aux.height(fh as u32); 
-                                client.height = req_h; 
+                                client.height = req_h;
+                                aux = aux.height(client.frame_size(b, t).1 as u32);  
                                 resized = true;
                             }
                             if mask.contains(ConfigWindow::SIBLING) { aux = aux.sibling(sibling_resolved); }
                             if mask.contains(ConfigWindow::STACK_MODE) { aux = aux.stack_mode(event.stack_mode); }
                             
                             let _ = self.ctx.conn.configure_window(frame, &aux);
+                            if mask.contains(ConfigWindow::STACK_MODE) && !mask.contains(ConfigWindow::SIBLING) {
+                                match event.stack_mode {
+                                    x11rb::protocol::xproto::StackMode::ABOVE => { self.client_list.raise(event.window); }
+                                    x11rb::protocol::xproto::StackMode::BELOW => { self.client_list.lower(event.window); }
+                                    _ => {}
+                                }
+                            }
                             
                             if resized {
                                 let _ = self.ctx.conn.configure_window(event.window, &x11rb::protocol::xproto::ConfigureWindowAux::new().width(client.width as u32).height(client.height as u32));
-                                if let Err(_) = draw_decoration(&self.ctx, event.window, &client.name, client.width + 2*b, client.height + t + 2*b, t) { }
+                                let (fw, fh) = client.frame_size(b, t);
+                                let highlight = button_highlight(event.window, self.hovered_button, self.pressed_button);
+                                if let Err(_) = draw_decoration(&self.ctx, &self.theme, frame, &client.name, fw, fh, t, self.focused_window == Some(event.window), highlight) { }
                                 let _ = self.update_window_shape(event.window);
                             }
                         }
                         needs_paint = true;
+                        if mask.contains(ConfigWindow::STACK_MODE) {
+                            self.update_client_list();
+                        }
                         self.send_configure_notify(event.window);
                     } else {
                         self.send_configure_notify(event.window);
@@ -1665,9 +3262,15 @@ impl WindowManager {
                 }
             }
             Event::DamageNotify(event) => { 
-                if self.clients.contains_key(&event.drawable) { needs_paint = true; }
-                if self.unmanaged_windows.contains_key(&event.drawable) { needs_paint = true; }
-                let _ = self.ctx.conn.damage_subtract(event.damage, x11rb::NONE, x11rb::NONE); 
+                // Repaint where the contents changed, if they are on screen
+                match self.content_origin(event.drawable) {
+                    Some((x, y)) => { log_warn(self.repaint.add_damage(&self.ctx.conn, event.damage, x, y), "collect damage"); }
+                    None => { let _ = self.ctx.conn.damage_subtract(event.damage, x11rb::NONE, x11rb::NONE); }
+                }
+            }
+            Event::PresentCompleteNotify(event) => {
+                // The damage held back meanwhile is painted next
+                self.compositor.frame_complete(event.window, event.serial);
             }
             Event::ShapeNotify(event) => {
                 let win = event.affected_window;
@@ -1686,6 +3289,19 @@ impl WindowManager {
                 }
             }
             Event::PropertyNotify(event) => {
+                 if event.window == self.ctx.root_window {
+                     if event.atom == self.ctx.atoms._NET_DESKTOP_NAMES {
+                         // Pagers rename workspaces by setting the property themselves
+                         let reply = self.ctx.conn.get_property(false, self.ctx.root_window, self.ctx.atoms._NET_DESKTOP_NAMES, self.ctx.atoms.UTF8_STRING, 0, 4096)?.reply()?;
+                         let before = self.workspaces.clone();
+                         self.workspaces.set_names(&reply.value);
+                         if self.workspaces != before {
+                             debug!("Workspaces renamed");
+                             log_warn(self.update_desktop_props(), "publish workspace names");
+                         }
+                     }
+                     return Ok(false);
+                 }
                  let mut target_win = event.window;
                  if !self.clients.contains_key(&target_win) {
                      if let Some(w) = self.find_client_by_user_time_window(event.window) {
@@ -1695,7 +3311,12 @@ impl WindowManager {
                      }
                  }
 
-                 if event.atom == self.ctx.atoms._NET_WM_WINDOW_OPACITY {
+                 if event.atom == u32::from(AtomEnum::WM_NORMAL_HINTS) {
+                      let hints = self.read_size_hints(target_win);
+                      if let Some(client) = self.clients.get_mut(&target_win) {
+                          client.size_hints = hints;
+                      }
+                 } else if event.atom == self.ctx.atoms._NET_WM_WINDOW_OPACITY {
                       let opacity = self.read_opacity(target_win);
                       if let Some(client) = self.clients.get_mut(&target_win) {
                           client.opacity = opacity;
@@ -1705,7 +3326,8 @@ impl WindowManager {
                       if let Ok(strut) = self.read_strut_property(target_win) {
                           if let Some(client) = self.clients.get_mut(&target_win) {
                                client.strut = strut;
-                               let _ = self.update_net_workarea();
+                               self.struts_changed();
+                               needs_paint = true;
                           }
                       }
                  } else if event.atom == self.ctx.atoms._NET_WM_NAME {
@@ -1731,7 +3353,7 @@ impl WindowManager {
                           }
                       }
                  } else if event.atom == self.ctx.atoms._NET_WM_USER_TIME {
-                      let user_time = self.read_user_time(event.window); // Read from event.window which might be utw
+                      let user_time = self.read_user_time(event.window).unwrap_or(0); // Read from event.window which might be utw
                       if let Some(client) = self.clients.get_mut(&target_win) {
                            client.user_time = user_time;
                            debug!("User time updated for window {} to {}", target_win, user_time);
@@ -1762,7 +3384,7 @@ impl WindowManager {
                       let utw = self.read_user_time_window(target_win);
                       if let Some(w) = utw {
                            let _ = self.ctx.conn.change_window_attributes(w, &x11rb::protocol::xproto::ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE));
-                           let user_time = self.read_user_time(w);
+                           let user_time = self.read_user_time(w).unwrap_or(0);
                            if let Some(client) = self.clients.get_mut(&target_win) {
                                 client.user_time_window = Some(w);
                                 client.user_time = user_time;
@@ -1781,36 +3403,125 @@ impl WindowManager {
                 if event.count == 0 {
                     if let Some(client) = self.find_client_by_frame(event.window) {
                         let (border, title) = if client.is_fullscreen || client.is_desktop || client.is_dock || client.is_csd { (0, 0) } else { (BORDER_WIDTH, TITLE_HEIGHT) };
-                        if let Err(_) = draw_decoration(&self.ctx, event.window, &client.name, client.width + 2*border, client.height + title + 2*border, title) { }
+                        let (width, height) = client.frame_size(border, title);
+                        let highlight = button_highlight(client.window, self.hovered_button, self.pressed_button);
+                        if let Err(_) = draw_decoration(&self.ctx, &self.theme, event.window, &client.name, width, height, title, self.focused_window == Some(client.window), highlight) { }
                         needs_paint = true;
                     }
                     if event.window == self.compositor.overlay_window || event.window == self.ctx.root_window { needs_paint = true; }
+                    if let Some(popup) = self.size_popup.as_ref().filter(|p| p.window() == event.window) {
+                        log_warn(popup.draw(&self.ctx), "draw size popup");
+                        needs_paint = true;
+                    }
+                    if let Some(switcher) = self.switcher.as_ref().filter(|s| s.popup() == event.window) {
+                        log_warn(switcher.draw(&self.ctx), "draw window switcher");
+                        needs_paint = true;
+                    }
+                    if let Some(window_menu) = self.window_menu.as_ref().filter(|m| m.owns(event.window)) {
+                        log_warn(window_menu.draw(&self.ctx), "draw window menu");
+                        needs_paint = true;
+                    }
+                    if let Some(overview) = self.overview.as_ref().filter(|o| o.popup() == event.window) {
+                        log_warn(overview.draw(&self.ctx), "draw window overview");
+                        needs_paint = true;
+                    }
                 }
             }
             Event::ClientMessage(event) => {
                  if event.type_ == self.ctx.atoms._NET_CURRENT_DESKTOP {
                      if let Some(new_idx) = event.data.as_data32().get(0) { let _ = self.switch_workspace(*new_idx); needs_paint = true; }
+                 } else if event.type_ == self.ctx.atoms._NET_NUMBER_OF_DESKTOPS {
+                     // From pagers adding or removing workspaces, and from the settings watcher
+                     let count = event.data.as_data32()[0];
+                     if count > 0 {
+                         log_warn(self.set_workspace_count(count), "change number of workspaces");
+                         needs_paint = true;
+                     }
+                 } else if event.type_ == self.ctx.atoms._XFWM4_RS_RESTART {
+                     // From SIGUSR1, or anyone wanting a new build run
+                     if let Err(e) = self.restart() {
+                         warn!("Failed to restart in place: {}", e);
+                     }
+                     needs_paint = true;
+                 } else if event.type_ == self.ctx.atoms._XFWM4_RS_SAVE_SESSION {
+                     // From the session client, as the session ends
+                     self.save_session();
+                 } else if event.type_ == self.ctx.atoms._XFWM4_RS_DPMS {
+                     // From the screensaver service and the power manager
+                     let data = event.data.as_data32();
+                     let changed = match data[0] {
+                         dpms::DPMS_ALLOW => self.dpms.allow(data[1]),
+                         dpms::DPMS_INHIBIT => self.dpms.inhibit(data[1]),
+                         dpms::DPMS_LOCK_AND_BLANK => {
+                             self.lock_and_blank();
+                             false
+                         }
+                         command => {
+                             debug!("Unknown {} command {}", dpms::DPMS_MESSAGE, command);
+                             false
+                         }
+                     };
+                     if changed {
+                         info!("Display power management {}", if self.dpms.is_inhibited() { "inhibited" } else { "allowed again" });
+                         log_warn(self.dpms.apply(&self.ctx.conn), "update display power timeouts");
+                     }
+                 } else if event.type_ == self.ctx.atoms._NET_WM_DESKTOP {
+                     if self.clients.contains_key(&event.window) {
+                         let workspace = event.data.as_data32()[0];
+                         log_warn(self.move_to_workspace(event.window, workspace), "move window to workspace");
+                         needs_paint = true;
+                     }
                  } else if event.type_ == self.ctx.atoms._NET_ACTIVE_WINDOW {
-                     if let Some(client) = self.clients.get(&event.window) {
-                         if let Some(frame) = client.frame { let _ = self.ctx.conn.configure_window(frame, &x11rb::protocol::xproto::ConfigureWindowAux::new().stack_mode(x11rb::protocol::xproto::StackMode::ABOVE)); } 
-                         let _ = self.focus_window(event.window);
+                     if self.clients.contains_key(&event.window) {
+                         // Source 1 is the application itself, 2 a pager
+                         let data = event.data.as_data32();
+                         let focused_time = self.focused_window.filter(|&w| w != event.window).and_then(|w| self.clients.get(&w)).map(|c| c.user_time);
+                         if self.focus_policy.allows_activation(data[0] == 1, data[1], focused_time) {
+                             self.raise_window(event.window);
+                             let _ = self.focus_window(event.window);
+                         } else {
+                             info!("🎯 FOCUS: Window {} asked for the focus without user activity", event.window);
+                             self.demand_attention(event.window);
+                         }
                          needs_paint = true;
                      }
+                 } else if event.type_ == self.ctx.atoms._NET_STARTUP_INFO_BEGIN || event.type_ == self.ctx.atoms._NET_STARTUP_INFO {
+                      let begin = event.type_ == self.ctx.atoms._NET_STARTUP_INFO_BEGIN;
+                      if let Some(text) = self.startup_messages.push(event.window, begin, &event.data.as_data8()) {
+                          debug!("🚀 STARTUP: {}", text);
+                          if let Some(message) = StartupMessage::parse(&text) {
+                              self.launches.update(message, Instant::now());
+                              self.update_launch_cursor();
+                          }
+                      }
                  } else if event.type_ == self.ctx.atoms.WM_PROTOCOLS {
                       let data = event.data.as_data32();
+                      // Pongs come back to the root, naming the window
                       if data[0] == self.ctx.atoms._NET_WM_PING {
-                          debug!("🏓 PONG: Window {} is alive!", event.window);
+                          let window = data[2];
+                          debug!("🏓 PONG: Window {} is alive!", window);
+                          self.pings.pong(window);
+                          if let Some(dialog) = self.force_quit.remove(&window) {
+                              info!("🏓 PONG: Window {} answered again, closing its Force Quit dialog", window);
+                              dialog.dismiss();
+                          }
                       }
                  } else if event.type_ == self.ctx.atoms._NET_WM_STATE {
                     let data = event.data.as_data32();
                     let action = data[0]; // 0: remove, 1: add, 2: toggle
                     let atoms = [data[1], data[2]];
+                    // Both maximize atoms usually come together, and are
+                    // one change
+                    let mut maximize_seen = false;
 
                     for atom in atoms {
                         if atom == 0 { continue; }
                         
                         let mut toggle_fs = false;
                         let mut toggle_max = false;
+                        let mut shade = None;
+                        let mut sticky = None;
+                        let mut layer_state = None;
                         
                         if let Some(client) = self.clients.get_mut(&event.window) {
                             if atom == self.ctx.atoms._NET_WM_STATE_FULLSCREEN {
@@ -1818,7 +3529,8 @@ impl WindowManager {
                                     0 => false, 1 => true, 2 => !client.is_fullscreen, _ => client.is_fullscreen,
                                 };
                                 if next != client.is_fullscreen { toggle_fs = true; }
-                            } else if atom == self.ctx.atoms._NET_WM_STATE_MAXIMIZED_VERT || atom == self.ctx.atoms._NET_WM_STATE_MAXIMIZED_HORZ {
+                            } else if (atom == self.ctx.atoms._NET_WM_STATE_MAXIMIZED_VERT || atom == self.ctx.atoms._NET_WM_STATE_MAXIMIZED_HORZ) && !maximize_seen {
+                                maximize_seen = true;
                                 let next = match action {
                                     0 => false, 1 => true, 2 => !client.is_maximized, _ => client.is_maximized,
                                 };
@@ -1832,10 +3544,10 @@ impl WindowManager {
                                     0 => false, 1 => true, 2 => !client.demands_attention, _ => client.demands_attention,
                                 };
                             } else if atom == self.ctx.atoms._NET_WM_STATE_STICKY {
-                                client.is_sticky = match action {
+                                let next = match action {
                                     0 => false, 1 => true, 2 => !client.is_sticky, _ => client.is_sticky,
                                 };
-                                client.workspace = if client.is_sticky { 0xFFFFFFFF } else { self.current_workspace };
+                                if next != client.is_sticky { sticky = Some(next); }
                             } else if atom == self.ctx.atoms._NET_WM_STATE_SKIP_TASKBAR {
                                 client.skip_taskbar = match action {
                                     0 => false, 1 => true, 2 => !client.skip_taskbar, _ => client.skip_taskbar,
@@ -1845,69 +3557,138 @@ impl WindowManager {
                                     0 => false, 1 => true, 2 => !client.skip_pager, _ => client.skip_pager,
                                 };
                             } else if atom == self.ctx.atoms._NET_WM_STATE_SHADED {
-                                client.is_shaded = match action {
+                                let next = match action {
                                     0 => false, 1 => true, 2 => !client.is_shaded, _ => client.is_shaded,
                                 };
-                                // TODO: shading implementation
+                                if next != client.is_shaded { shade = Some(next); }
                             } else if atom == self.ctx.atoms._NET_WM_STATE_ABOVE {
-                                client.is_above = match action {
+                                let above = match action {
                                     0 => false, 1 => true, 2 => !client.is_above, _ => client.is_above,
                                 };
-                                if client.is_above { client.is_below = false; client.layer = crate::window::LAYER_ONTOP; }
-                                else { client.layer = crate::window::LAYER_NORMAL; }
+                                // Above takes the window out of below
+                                layer_state = Some((above, client.is_below && !above));
                             } else if atom == self.ctx.atoms._NET_WM_STATE_BELOW {
-                                client.is_below = match action {
+                                let below = match action {
                                     0 => false, 1 => true, 2 => !client.is_below, _ => client.is_below,
                                 };
-                                if client.is_below { client.is_above = false; client.layer = crate::window::LAYER_BELOW; }
-                                else { client.layer = crate::window::LAYER_NORMAL; }
+                                layer_state = Some((client.is_above && !below, below));
                             }
                         }
                         
                         if toggle_fs { let _ = self.toggle_fullscreen(event.window); }
                         if toggle_max { let _ = self.toggle_maximize(event.window); }
+                        if let Some(shaded) = shade { log_warn(self.set_shaded(event.window, shaded), "shade window"); }
+                        if let Some(sticky) = sticky { log_warn(self.set_sticky(event.window, sticky), "make window sticky"); }
+                        if let Some((above, below)) = layer_state { log_warn(self.set_layer_state(event.window, above, below), "keep window above or below"); }
                         let _ = self.update_net_wm_state(event.window);
+                        self.update_wm_desktop(event.window);
                     }
                     needs_paint = true;
 
 
                  } else if event.type_ == self.ctx.atoms._NET_WM_MOVERESIZE {
                      let data = event.data.as_data32();
-                     let x = data[0] as i16;
-                     let y = data[1] as i16;
-                     let direction = data[2];
-                     
-                     if let Some(client) = self.clients.get(&event.window) {
-                         if let Some(frame) = client.frame {
-                             if direction == 8 { // _NET_WM_MOVERESIZE_MOVE
-                                 let frame_geom = self.ctx.conn.get_geometry(frame)?.reply()?;
-                                 self.drag_state = DragState::Moving {
-                                     window: event.window,
-                                     start_pointer_x: x,
-                                     start_pointer_y: y,
-                                     start_frame_x: frame_geom.x,
-                                     start_frame_y: frame_geom.y,
-                                     snap: SnapZone::None,
-                                 };
-                                 // Grab pointer to receive motion events
-                                 self.ctx.conn.grab_pointer(
-                                     false,
-                                     self.ctx.root_window,
-                                     EventMask::POINTER_MOTION | EventMask::BUTTON_RELEASE,
-                                     x11rb::protocol::xproto::GrabMode::ASYNC,
-                                     x11rb::protocol::xproto::GrabMode::ASYNC,
-                                     x11rb::NONE,
-                                     self.cursors.normal,
-                                     x11rb::CURRENT_TIME,
-                                 )?;
-                                 info!("Started MOVERESIZE_MOVE for window {}", event.window);
+                     let (x, y, direction) = (data[0] as i16, data[1] as i16, data[2]);
+                     log_warn(self.client_moveresize(event.window, x, y, direction), "start client move or resize");
+                     needs_paint = true;
+                 }
+            }
+            Event::KeyPress(event) => {
+                 debug!("⌨️ KeyPress: detail={}, state={:?}, window={}", event.detail, event.state, event.event);
+                 if self.switcher.is_some() {
+                     log_warn(self.handle_switcher_key(event.detail, u16::from(event.state), event.time), "handle window switcher key");
+                     needs_paint = true;
+                 } else if self.drag_state != DragState::None {
+                     match self.handle_drag_key(event.detail, u16::from(event.state)) {
+                         Ok(ended) => needs_paint |= ended,
+                         Err(e) => warn!("Keyboard move failed: {}", e),
+                     }
+                 } else if self.overview.is_some() {
+                     // The overview shortcut again puts it away
+                     let outcome = if self.keybindings.action_for(event.detail, u16::from(event.state)) == Some(Action::Overview) {
+                         Some(overview::Outcome::Close)
+                     } else {
+                         let key = self.keybindings.navigation_keysym(event.detail).and_then(MenuKey::from_keysym);
+                         key.zip(self.overview.as_mut()).map(|(key, overview)| overview.key(key))
+                     };
+                     if let Some(outcome) = outcome {
+                         log_warn(self.finish_overview(outcome, event.time), "handle window overview key");
+                         needs_paint = true;
+                     }
+                 } else if self.window_menu.is_some() {
+                     let key = self.keybindings.navigation_keysym(event.detail).and_then(MenuKey::from_keysym);
+                     if let (Some(key), Some(window_menu)) = (key, self.window_menu.as_mut()) {
+                         match window_menu.key(&self.ctx, key) {
+                             Ok(outcome) => {
+                                 log_warn(self.finish_window_menu(outcome), "run window menu command");
                              }
+                             Err(e) => warn!("Window menu failed: {}", e),
                          }
+                         needs_paint = true;
+                     }
+                 } else if let Some(action) = self.keybindings.action_for(event.detail, u16::from(event.state)) {
+                     if let Err(e) = self.perform_action(action, event.time, u16::from(event.state)) {
+                         warn!("Shortcut {:?} failed: {}", action, e);
                      }
+                     needs_paint = true;
                  }
             }
-            Event::KeyPress(event) => {
-                 debug!("⌨️ KeyPress: detail={}, state={:?}, window={}", event.detail, event.state, event.event);
+            Event::KeyRelease(event) if self.switcher.as_ref().is_some_and(|s| s.modifier_keycodes.contains(&event.detail)) => {
+                 // Letting go of Alt picks the window
+                 log_warn(self.close_switcher(true, event.time), "close window switcher");
+                 needs_paint = true;
+            }
+            Event::XfixesSelectionNotify(event) if event.selection == self.ctx.atoms.XdndSelection && event.owner != x11rb::NONE => {
+                 debug!("Drag-and-drop started by window {}", event.owner);
+                 self.dnd_raise.start();
+            }
+            Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => {
+                 if let Err(e) = self.update_monitors() {
+                     warn!("Failed to read the new monitor layout: {}", e);
+                 }
+                 needs_paint = true;
+            }
+            Event::MappingNotify(event) if event.request != x11rb::protocol::xproto::Mapping::POINTER => {
+                 // Keycodes behind the shortcuts may have moved, e.g. after setxkbmap
+                 debug!("Keyboard mapping changed, grabbing shortcuts again");
+                 log_warn(self.keybindings.grab(&self.ctx.conn, self.ctx.root_window), "regrab shortcut keys");
+            }
+            Event::ButtonPress(event) if self.zoom.is_zoom_button(event.detail, u16::from(event.state)) => {
+                if !self.compositor.active {
+                    debug!("Desktop zoom needs the compositor");
+                } else if self.zoom.scroll(event.detail == 4, (event.root_x, event.root_y), (self.ctx.screen_width, self.ctx.screen_height)) {
+                    info!("🔍 Zoom at {:.2}x", self.zoom.factor());
+                    needs_paint = true;
+                }
+            }
+            Event::ButtonPress(event) if self.overview.is_some() => {
+                if let Some(outcome) = self.overview.as_mut().map(|o| o.click(event.root_x, event.root_y)) {
+                    log_warn(self.finish_overview(outcome, event.time), "pick window in overview");
+                    needs_paint = true;
+                }
+            }
+            Event::MotionNotify(event) if self.overview.as_mut().is_some_and(|o| o.motion(event.root_x, event.root_y)) => {
+                if let Some(overview) = &self.overview {
+                    log_warn(overview.draw(&self.ctx), "draw window overview");
+                }
+                needs_paint = true;
+            }
+            Event::ButtonPress(event) if self.window_menu.as_ref().is_some_and(|m| !m.contains(event.root_x, event.root_y)) => {
+                // A click off the menu closes it
+                self.close_window_menu();
+                needs_paint = true;
+            }
+            Event::ButtonRelease(event) if self.window_menu.is_some() => {
+                if let Some(outcome) = self.window_menu.as_ref().map(|m| m.release(event.root_x, event.root_y)) {
+                    log_warn(self.finish_window_menu(outcome), "run window menu command");
+                    needs_paint = true;
+                }
+            }
+            Event::MotionNotify(event) if self.window_menu.is_some() => {
+                if let Some(window_menu) = self.window_menu.as_mut() {
+                    log_warn(window_menu.motion(&self.ctx, event.root_x, event.root_y), "update window menu");
+                    needs_paint = true;
+                }
             }
             Event::ButtonPress(event) => {
                 debug!("🎯 ButtonPress: window={}, root=({}, {}), event=({}, {}), detail={}", event.event, event.root_x, event.root_y, event.event_x, event.event_y, event.detail);
@@ -1927,10 +3708,12 @@ impl WindowManager {
                 }
 
                 if let (Some(win), Some(frame)) = (client_window, frame_window) {
-                    if let Some(c) = self.clients.get(&win) {
-                        if !c.is_desktop {
-                            let _ = self.ctx.conn.configure_window(frame, &x11rb::protocol::xproto::ConfigureWindowAux::new().stack_mode(x11rb::protocol::xproto::StackMode::ABOVE));
-                        }
+                    // Clicking the frame raises whatever the settings say
+                    if self.clients.get(&win).is_some_and(|c| !c.is_desktop) && (!is_client_click || self.focus_policy.raise_on_click) {
+                        self.raise_window(win);
+                    }
+                    if let Some(client) = self.clients.get_mut(&win) {
+                        client.user_time = event.time;
                     }
                     let _ = self.focus_window(win);
                     needs_paint = true;
@@ -1945,35 +3728,52 @@ impl WindowManager {
                     } else if event.detail == 1 {
                         let geom_data = self.ctx.conn.get_geometry(frame).ok().and_then(|c| c.reply().ok());
                         if let Some(geom) = geom_data {
-                            let part = FrameGeometry::hit_test(geom.width, geom.height, event.event_x, event.event_y);
+                            let part = FrameGeometry::hit_test(&self.theme.button_layout, geom.width, geom.height, event.event_x, event.event_y);
                             let cursor = self.get_cursor_for_part(part);
                             let grab_ok = self.ctx.conn.grab_pointer(false, self.ctx.root_window, EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION, x11rb::protocol::xproto::GrabMode::ASYNC, x11rb::protocol::xproto::GrabMode::ASYNC, x11rb::NONE, cursor, x11rb::CURRENT_TIME).ok().and_then(|c| c.reply().ok());
                             if let Some(reply) = grab_ok {
                                 if reply.status == x11rb::protocol::xproto::GrabStatus::SUCCESS {
                                     let is_double_click = (win == self.last_click_window) && (event.time.wrapping_sub(self.last_click_time) < 400);
                                     if !is_double_click { self.last_click_time = event.time; self.last_click_window = win; }
-                                    let should_maximize = self.settings_manager.current.double_click_action == "maximize";
+                                    let double_click_action = self.settings_manager.current.double_click_action.clone();
                                     match part {
                                         FramePart::TitleBar => {
                                             if is_double_click {
-                                                if should_maximize { let _ = self.toggle_maximize(win); }
+                                                match double_click_action.as_str() {
+                                                    "maximize" => { let _ = self.toggle_maximize(win); }
+                                                    "shade" => { log_warn(self.toggle_shade(win), "shade window"); }
+                                                    _ => {}
+                                                }
                                                 let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME);
                                                 self.drag_state = DragState::None;
                                             } else {
                                                 self.drag_state = DragState::Moving { window: win, start_pointer_x: event.root_x, start_pointer_y: event.root_y, start_frame_x: geom.x, start_frame_y: geom.y, snap: SnapZone::None };
                                             }
                                         }
-                                        FramePart::CornerBottomRight => { self.drag_state = DragState::Resizing { window: win, start_pointer_x: event.root_x, start_pointer_y: event.root_y, start_width: geom.width, start_height: geom.height }; }
-                                        FramePart::CloseButton => { let _ = self.send_delete_window(win); let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME); }
-                                        FramePart::MaximizeButton => { let _ = self.toggle_maximize(win); let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME); }
-                                        FramePart::MinimizeButton => { let _ = self.toggle_minimize(win); let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME); }
+                                        edge if is_resize_edge(edge) => {
+                                            let start = self.clients.get(&win).map(|c| (c.width, c.height, c.is_shaded));
+                                            match start {
+                                                Some((start_width, start_height, false)) => {
+                                                    self.drag_state = DragState::Resizing { window: win, edge, start_pointer_x: event.root_x, start_pointer_y: event.root_y, start_frame_x: geom.x, start_frame_y: geom.y, start_width, start_height };
+                                                    self.update_size_popup(win);
+                                                }
+                                                // Shaded windows keep their size until unshaded
+                                                _ => { let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME); }
+                                            }
+                                        }
+                                        part if part.is_button() => {
+                                            // Acts on release, if the pointer is still over it; the grab stays until then
+                                            self.pressed_button = Some((win, part));
+                                            self.hovered_button = Some((win, part));
+                                            self.redraw_decoration(win);
+                                        }
                                         _ => { let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME); }
                                     }
                                 }
                             }
                         }
                     } else if event.detail == 3 {
-                        info!("🖱️ Right click on frame (button 3) for window {} - Menu not implemented yet", win);
+                        self.show_window_menu(win, event.root_x, event.root_y);
                     }
                 }
             }
@@ -1992,56 +3792,94 @@ impl WindowManager {
                                    else { SnapZone::None };
                            if ns != snap { next_snap = Some(ns); ns_val = Some(window); }
                            
-                           let new_x = start_frame_x + dx;
-                           let new_y = start_frame_y + dy;
-                           
-                           if let Some(client) = self.clients.get_mut(&window) {
-                               if let Some(frame) = client.frame {
-                                   let _ = self.ctx.conn.configure_window(frame, &x11rb::protocol::xproto::ConfigureWindowAux::new().x(Some(new_x as i32)).y(Some(new_y as i32)));
+                           let (new_x, new_y) = self.snap_moving_frame(window, start_frame_x + dx, start_frame_y + dy, event.root_x, event.root_y);
+
+                           // Held against the left or right edge, the window
+                           // goes on to the workspace there
+                           match ScreenEdge::at(event.root_x, event.root_y, self.ctx.screen_width, self.ctx.screen_height) {
+                               Some(edge @ (ScreenEdge::Left | ScreenEdge::Right)) if self.edges.wrap_windows => {
+                                   self.edge_dwell.enter(edge, Instant::now(), self.edges.wrap_delay);
                                }
-                               client.x = new_x;
-                               client.y = new_y;
+                               _ => self.edge_dwell.leave(),
                            }
+                           
+                           self.move_frame(window, new_x, new_y);
                            needs_paint = true;
                      }
-                     DragState::Resizing { window, start_pointer_x, start_pointer_y, start_width, start_height } => {
+                     DragState::Resizing { window, edge, start_pointer_x, start_pointer_y, start_frame_x, start_frame_y, start_width, start_height } => {
                            let dx = event.root_x - start_pointer_x; let dy = event.root_y - start_pointer_y;
-                           let new_w = (start_width as i16 + dx).max(100) as u16; 
-                           let new_h = (start_height as i16 + dy).max(50) as u16;
                            
-                           if let Some(client) = self.clients.get_mut(&window) {
-                               client.width = new_w;
-                               client.height = new_h;
-                               if let Some(frame) = client.frame {
-                                   let (border, title) = if client.is_fullscreen || client.is_desktop || client.is_dock { (0, 0) } else { (BORDER_WIDTH, TITLE_HEIGHT) };
-                                   let frame_w = new_w as u32 + (2 * border) as u32;
-                                   let frame_h = new_h as u32 + title as u32 + (2 * border) as u32;
-                                   
-                                   let _ = self.ctx.conn.configure_window(frame, &x11rb::protocol::xproto::ConfigureWindowAux::new().width(Some(frame_w)).height(Some(frame_h)));
-                                   let _ = self.ctx.conn.configure_window(window, &x11rb::protocol::xproto::ConfigureWindowAux::new().width(Some(new_w as u32)).height(Some(new_h as u32)));
-                                   let _ = draw_decoration(&self.ctx, frame, &client.name, new_w + 2*border, new_h + title + 2*border, title);
-                                   let _ = self.update_window_shape(window);
-                               }
-                               self.client_xsync_request(window);
+                           if let Some(client) = self.clients.get(&window) {
+                               let start = x11rb::protocol::xproto::Rectangle { x: start_frame_x, y: start_frame_y, width: start_width, height: start_height };
+                               let rect = resize_rect(edge, start, dx, dy, &client.size_hints);
+                               self.resize_frame(window, rect);
                            }
+                           self.update_size_popup(window);
                            needs_paint = true;
                      }
-                     _ => {}
+                     DragState::None => {
+                          if self.update_button_hover(event.event, event.root_x, event.root_y) { needs_paint = true; }
+                     }
                  }
                  if let (Some(ns), Some(_win)) = (next_snap, ns_val) {
                       if let DragState::Moving { ref mut snap, .. } = self.drag_state { *snap = ns; }
                  }
             }
+            Event::EnterNotify(event) => {
+                 // Grabs send an EnterNotify too, and coming out of the
+                 // client window into its frame isn't coming in
+                 use x11rb::protocol::xproto::{NotifyDetail, NotifyMode};
+                 if let Some(edge) = self.edges.edge_of(event.event).filter(|_| self.drag_state == DragState::None) {
+                     self.edge_dwell.enter(edge, Instant::now(), self.edges.dwell);
+                 }
+                 if self.focus_policy.model == FocusModel::Sloppy && event.mode == NotifyMode::NORMAL && event.detail != NotifyDetail::INFERIOR {
+                     let entered = self.clients.values().find(|c| c.frame == Some(event.event) && !c.is_desktop && !c.is_dock).map(|c| c.window);
+                     if let Some(window) = entered {
+                         if self.focus_policy.delay.is_zero() {
+                             needs_paint |= self.focus_under_pointer(window);
+                         } else {
+                             self.pointer_focus.enter(window, Instant::now(), self.focus_policy.delay);
+                         }
+                     }
+                 }
+            }
+            Event::LeaveNotify(event) => {
+                 if self.edges.edge_of(event.event).is_some() && self.drag_state == DragState::None {
+                     self.edge_dwell.leave();
+                 }
+                 if event.mode == x11rb::protocol::xproto::NotifyMode::NORMAL && event.detail != x11rb::protocol::xproto::NotifyDetail::INFERIOR {
+                     if let Some(window) = self.clients.values().find(|c| c.frame == Some(event.event)).map(|c| c.window) {
+                         self.pointer_focus.leave(window);
+                         if self.revealed_panels.contains(&window) {
+                             self.hide_revealed_panels();
+                             needs_paint = true;
+                         }
+                     }
+                 }
+                 // Grabs send a LeaveNotify too, but the pointer hasn't moved
+                 if event.mode == x11rb::protocol::xproto::NotifyMode::NORMAL && self.pressed_button.is_none() {
+                     if let Some((window, _)) = self.hovered_button.filter(|&(w, _)| self.clients.get(&w).and_then(|c| c.frame) == Some(event.event)) {
+                         self.hovered_button = None;
+                         self.redraw_decoration(window);
+                         needs_paint = true;
+                     }
+                 }
+            }
             Event::ButtonRelease(event) => {
                  if event.detail == 1 {
-                     if let DragState::Moving { window, snap, .. } = self.drag_state {
-                         if snap != SnapZone::None { let _ = self.apply_snap(window, snap); }
+                     if let Some((window, part)) = self.pressed_button.take() {
+                         let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME);
+                         let released_over = self.hovered_button == Some((window, part));
+                         self.redraw_decoration(window);
+                         if released_over {
+                             log_warn(self.activate_button(window, part), "activate frame button");
+                         }
+                         needs_paint = true;
                      }
-                     if !matches!(self.drag_state, DragState::None) { 
-                         let _ = self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME); 
-                         self.drag_state = DragState::None; 
+                     if self.drag_state != DragState::None {
+                         self.finish_drag();
                          needs_paint = true;
-                     } 
+                     }
                  }
             }
             _ => {}
@@ -2050,10 +3888,11 @@ impl WindowManager {
     }
 
     fn place_window(&self, width: u16, height: u16) -> (i16, i16) {
-        let (wx, wy, ww, wh) = self.calculate_workarea();
+        let area = self.monitor_workarea(self.pointer_monitor());
+        let (wx, wy, ww, wh) = (area.x, area.y, area.width, area.height);
         let existing: Vec<(i16, i16)> = self.clients.values()
             .filter(|c| c.workspace == self.current_workspace)
-            .map(|c| (c.x, c.y))
+            .map(|c| (c.x - wx, c.y - wy))
             .collect();
         
         let (x, y) = cascade_placement(ww, wh, width, height, &existing);
@@ -2116,31 +3955,82 @@ impl WindowManager {
 
     pub fn run(&mut self) -> Result<()> {
         if let Err(e) = self.paint() { warn!("Initial paint failed: {}", e); }
-        let _ = self.update_net_workarea();
+        log_warn(self.repaint.clear(&self.ctx.conn), "reset damage");
+        log_warn(self.update_desktop_props(), "publish workspaces");
         loop {
             self.ctx.conn.flush()?;
             let mut needs_paint = false;
             
-            // Wait for at least one event
-            match self.ctx.conn.wait_for_event() {
-                Ok(event) => {
+            if self.fades.is_active() {
+                // Animation frame: take the events that came in meanwhile
+                std::thread::sleep(fade::FRAME_INTERVAL);
+                while let Some(event) = self.ctx.conn.poll_for_event()? {
+                    self.handle_event(event)?;
+                }
+                self.finish_fades();
+                needs_paint = true;
+            } else if let Some(deadline) = self.next_timer() {
+                // Sloppy focus waits for the pointer to rest, pings for an
+                // answer and launches for a window: take events until one
+                // is due
+                std::thread::sleep(deadline.saturating_duration_since(Instant::now()).min(fade::FRAME_INTERVAL));
+                while let Some(event) = self.ctx.conn.poll_for_event()? {
                     needs_paint |= self.handle_event(event)?;
-                    
-                    // Drain all other pending events before painting to avoid flooding
-                    while let Some(event) = self.ctx.conn.poll_for_event()? {
+                }
+            } else {
+                // Wait for at least one event
+                match self.ctx.conn.wait_for_event() {
+                    Ok(event) => {
                         needs_paint |= self.handle_event(event)?;
+
+                        // Drain all other pending events before painting to avoid flooding
+                        while let Some(event) = self.ctx.conn.poll_for_event()? {
+                            needs_paint |= self.handle_event(event)?;
+                        }
+                    }
+                    Err(e) => {
+                        error!("X11 server connection closed or error: {}", e);
+                        break;
                     }
-                }
-                Err(e) => {
-                    error!("X11 server connection closed or error: {}", e);
-                    break;
                 }
             }
             
+            if let Some(window) = self.pointer_focus.take_due(Instant::now()) {
+                needs_paint |= self.focus_under_pointer(window);
+            }
+            if let Some(edge) = self.edge_dwell.take_due(Instant::now()) {
+                match self.trigger_edge(edge) {
+                    Ok(changed) => needs_paint |= changed,
+                    Err(e) => warn!("Screen edge action failed: {}", e),
+                }
+            }
+            if self.dnd_raise.is_dragging() {
+                needs_paint |= self.follow_drag();
+            }
+            if self.zoom.is_active() {
+                needs_paint |= self.follow_zoom();
+            }
+            needs_paint |= self.opacity_rules.is_animating(Instant::now());
+            self.check_hung_windows();
+            if self.launches.expire(Instant::now()) {
+                self.update_launch_cursor();
+            }
             if needs_paint {
+                self.repaint.add_all();
+            }
+            // With vsync, a frame waits for the last one to reach the screen
+            if self.repaint.is_pending() && !self.compositor.frame_pending() {
+                let started = Instant::now();
+                let partial = self.repaint.clip().is_some();
                 if let Err(e) = self.paint() {
                     self.error_tracker.record_compositor_error("paint loop", e);
                 }
+                log_warn(self.repaint.clear(&self.ctx.conn), "reset damage");
+                let elapsed = started.elapsed();
+                trace!(elapsed_us = elapsed.as_micros() as u64, partial, "Frame painted");
+                if let Some(summary) = self.frame_times.record(elapsed, partial) {
+                    debug!("{} frames, {} partial: {:?} average, {:?} worst", summary.frames, summary.partial, summary.average, summary.worst);
+                }
             }
 
             // Periodic health check
@@ -2176,6 +4066,61 @@ impl WindowManager {
         Ok(())
     }
 
+    /// WM_CLASS of `window`, as "instance.class"
+    fn read_class(&self, window: Window) -> Option<String> {
+        let class = self.ctx.conn.get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256).ok()?.reply().ok()?;
+        session::class_key(&class.value)
+    }
+
+    /// WM_CLASS and WM_WINDOW_ROLE of `window`, which find it again in the
+    /// next session
+    fn read_session_key(&self, window: Window) -> Option<(String, String)> {
+        let class = self.read_class(window)?;
+        let role = self.ctx.conn.get_property(false, window, self.ctx.atoms.WM_WINDOW_ROLE, AtomEnum::STRING, 0, 256).ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+            .unwrap_or_default();
+        Some((class, role))
+    }
+
+    /// Take the windows saved in `path` at the end of the last session, and
+    /// save them there at the end of this one
+    pub fn set_session(&mut self, path: PathBuf) {
+        self.session = SessionState::load(&path);
+        self.session_path = Some(path);
+    }
+
+    /// Save where every window is, for the session manager to start them
+    /// again there
+    fn save_session(&self) {
+        let Some(path) = &self.session_path else { return };
+        let windows: Vec<SavedWindow> = self.client_list.mapping().iter().filter_map(|&window| {
+            let client = self.clients.get(&window)?;
+            if client.is_dock || client.is_desktop || client.transient_for.is_some() { return None; }
+            let (class, role) = self.read_session_key(window)?;
+            let current = (client.x, client.y, client.width, client.height);
+            let (x, y, width, height) = if client.is_maximized || client.is_fullscreen { client.saved_geometry.unwrap_or(current) } else { current };
+            Some(SavedWindow {
+                class,
+                role,
+                x,
+                y,
+                width,
+                height,
+                workspace: client.workspace,
+                maximized: client.is_maximized,
+                minimized: client.is_minimized,
+                shaded: client.is_shaded,
+                above: client.is_above,
+                below: client.is_below,
+            })
+        }).collect();
+        match SessionState::save(path, &windows) {
+            Ok(()) => info!("Saved {} window(s) for the next session", windows.len()),
+            Err(e) => warn!("Failed to save the session to {}: {}", path.display(), e),
+        }
+    }
+
     fn read_startup_id(&self, window: Window) -> Option<String> {
         if let Ok(cookie) = self.ctx.conn.get_property(false, window, self.ctx.atoms._NET_STARTUP_ID, self.ctx.atoms.UTF8_STRING, 0, 1024) {
              if let Ok(reply) = cookie.reply() {
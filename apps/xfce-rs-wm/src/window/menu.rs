@@ -0,0 +1,468 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ChangeGCAux, ConfigureWindowAux, ConnectionExt, CreateGCAux, EventMask, Rectangle, StackMode, Window,
+};
+use tracing::debug;
+
+use crate::core::context::Context;
+use crate::window::overlay::{self, Painter};
+
+const ITEM_HEIGHT: u16 = 26;
+const PADDING: u16 = 4;
+/// Room left of the labels for the mark of checked items
+const MARK_WIDTH: u16 = 22;
+/// Room right of the labels for the arrow of submenus
+const ARROW_WIDTH: u16 = 22;
+/// Width of a character of the 10x20 font
+const CHAR_WIDTH: u16 = 10;
+
+const BACKGROUND: u32 = 0x2b2b2b;
+const SELECTED: u32 = 0x4a6a94;
+const TEXT: u32 = 0xe0e0e0;
+const DISABLED: u32 = 0x808080;
+
+/// What a window menu item does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Move,
+    Resize,
+    Minimize,
+    Maximize,
+    AlwaysOnTop,
+    AlwaysBelow,
+    /// On every workspace, `_NET_WM_STATE_STICKY`
+    Sticky,
+    /// Opens the list of workspaces
+    Workspaces,
+    /// Send the window to a workspace, counting from 0
+    Workspace(u32),
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub label: String,
+    pub command: Command,
+    pub checked: bool,
+    pub enabled: bool,
+}
+
+impl Item {
+    fn new(label: &str, command: Command) -> Self {
+        Self { label: label.to_string(), command, checked: false, enabled: true }
+    }
+}
+
+/// State of the window the menu is for, which decides what it offers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WindowState {
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub shaded: bool,
+    pub above: bool,
+    pub below: bool,
+    pub sticky: bool,
+    /// Its size hints pin its size
+    pub fixed_size: bool,
+}
+
+/// The operations on a window in `state`
+pub fn window_items(state: WindowState) -> Vec<Item> {
+    let placed = state.maximized || state.fullscreen;
+    vec![
+        Item { enabled: !placed, ..Item::new("Move", Command::Move) },
+        Item { enabled: !placed && !state.shaded && !state.fixed_size, ..Item::new("Resize", Command::Resize) },
+        Item::new("Minimize", Command::Minimize),
+        Item {
+            enabled: !state.fixed_size && !state.fullscreen,
+            ..Item::new(if state.maximized { "Unmaximize" } else { "Maximize" }, Command::Maximize)
+        },
+        Item { checked: state.above, ..Item::new("Always on Top", Command::AlwaysOnTop) },
+        Item { checked: state.below, ..Item::new("Always Below", Command::AlwaysBelow) },
+        Item { checked: state.sticky, ..Item::new("Always on Visible Workspace", Command::Sticky) },
+        Item::new("Move to Workspace", Command::Workspaces),
+        Item::new("Close", Command::Close),
+    ]
+}
+
+/// The workspaces named `names` to send a window on `workspace` to, its
+/// own checked
+pub fn workspace_items(names: &[String], workspace: u32) -> Vec<Item> {
+    (0..names.len() as u32)
+        .map(|index| {
+            let label = format!("{} {}", index + 1, names[index as usize]);
+            Item { checked: index == workspace, enabled: index != workspace, ..Item::new(&label, Command::Workspace(index)) }
+        })
+        .collect()
+}
+
+/// Size of a popup listing `items`
+fn popup_size(items: &[Item]) -> (u16, u16) {
+    let chars = items.iter().map(|item| item.label.chars().count()).max().unwrap_or(0) as u16;
+    (MARK_WIDTH + chars * CHAR_WIDTH + ARROW_WIDTH, 2 * PADDING + items.len() as u16 * ITEM_HEIGHT)
+}
+
+/// Where a popup `width` x `height` goes to open at (`x`, `y`) inside
+/// `bounds`: right of and below the point, or left of and above it where
+/// there is no room
+pub fn place(width: u16, height: u16, x: i16, y: i16, bounds: Rectangle) -> Rectangle {
+    let right = bounds.x as i32 + bounds.width as i32;
+    let bottom = bounds.y as i32 + bounds.height as i32;
+    let fit = |at: i16, size: u16, end: i32, start: i16| {
+        let at = at as i32;
+        let placed = if at + size as i32 > end { at - size as i32 } else { at };
+        placed.clamp(start as i32, (end - size as i32).max(start as i32)) as i16
+    };
+    Rectangle { x: fit(x, width, right, bounds.x), y: fit(y, height, bottom, bounds.y), width, height }
+}
+
+/// A key the menu answers to, by keysym
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+}
+
+impl MenuKey {
+    pub fn from_keysym(keysym: u32) -> Option<Self> {
+        Some(match keysym {
+            0xff52 => MenuKey::Up,
+            0xff54 => MenuKey::Down,
+            0xff51 => MenuKey::Left,
+            0xff53 => MenuKey::Right,
+            0xff0d | 0xff8d | 0x0020 => MenuKey::Enter,
+            0xff1b => MenuKey::Escape,
+            _ => return None,
+        })
+    }
+}
+
+/// What a key press or click in the menu came to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The menu stays up, perhaps with another item selected
+    Stay,
+    /// The menu goes away without doing anything
+    Close,
+    /// The menu goes away and the window gets `Command`
+    Run(Command),
+}
+
+/// A popup list of items, one of them maybe selected
+#[derive(Debug)]
+struct Popup {
+    items: Vec<Item>,
+    selected: Option<usize>,
+    window: Window,
+    rect: Rectangle,
+}
+
+impl Popup {
+    fn new(items: Vec<Item>) -> Self {
+        Self { items, selected: None, window: x11rb::NONE, rect: Rectangle { x: 0, y: 0, width: 0, height: 0 } }
+    }
+
+    /// The item at (`x`, `y`) on screen
+    fn item_at(&self, x: i16, y: i16) -> Option<usize> {
+        let (dx, dy) = (x as i32 - self.rect.x as i32, y as i32 - self.rect.y as i32 - PADDING as i32);
+        if self.window == x11rb::NONE || dx < 0 || dx >= self.rect.width as i32 || dy < 0 {
+            return None;
+        }
+        let index = (dy / ITEM_HEIGHT as i32) as usize;
+        (index < self.items.len()).then_some(index)
+    }
+
+    /// Select the next enabled item down, or up, going round
+    fn step(&mut self, down: bool) {
+        let count = self.items.len();
+        if count == 0 {
+            return;
+        }
+        let start = match self.selected {
+            Some(index) => index,
+            None if down => count - 1,
+            None => 0,
+        };
+        self.selected = (1..=count)
+            .map(|n| if down { (start + n) % count } else { (start + count - n) % count })
+            .find(|&index| self.items[index].enabled);
+    }
+
+    fn selected_item(&self) -> Option<&Item> {
+        self.selected.map(|index| &self.items[index])
+    }
+
+    fn show(&mut self, ctx: &Context, rect: Rectangle) -> Result<()> {
+        self.rect = rect;
+        self.window = overlay::create(ctx, rect, BACKGROUND, EventMask::EXPOSURE)?;
+        ctx.conn.map_window(self.window)?;
+        ctx.conn.configure_window(self.window, &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE))?;
+        self.draw(ctx)
+    }
+
+    fn draw(&self, ctx: &Context) -> Result<()> {
+        if self.window == x11rb::NONE {
+            return Ok(());
+        }
+        let painter = Painter::new(ctx, self.window, CreateGCAux::new().foreground(BACKGROUND))?;
+        let gc = painter.gc();
+        ctx.conn.poly_fill_rectangle(self.window, gc, &[Rectangle { x: 0, y: 0, ..self.rect }])?;
+
+        for (index, item) in self.items.iter().enumerate() {
+            let top = (PADDING + index as u16 * ITEM_HEIGHT) as i16;
+            let background = if self.selected == Some(index) { SELECTED } else { BACKGROUND };
+            let foreground = if item.enabled { TEXT } else { DISABLED };
+            if self.selected == Some(index) {
+                ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(SELECTED))?;
+                ctx.conn.poly_fill_rectangle(self.window, gc, &[Rectangle { x: 0, y: top, width: self.rect.width, height: ITEM_HEIGHT }])?;
+            }
+            ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(foreground).background(background))?;
+            if item.checked {
+                let mark = Rectangle { x: (MARK_WIDTH as i16 - 8) / 2, y: top + (ITEM_HEIGHT as i16 - 8) / 2, width: 8, height: 8 };
+                ctx.conn.poly_fill_rectangle(self.window, gc, &[mark])?;
+            }
+            if painter.has_font() {
+                // Core fonts are Latin-1
+                let text: Vec<u8> = item.label.chars().map(|c| u8::try_from(c as u32).unwrap_or(b'?')).collect();
+                let baseline = top + ITEM_HEIGHT as i16 - 8;
+                if let Err(e) = ctx.conn.image_text8(self.window, gc, MARK_WIDTH as i16, baseline, &text) {
+                    debug!("Failed to draw menu item: {}", e);
+                }
+                if item.command == Command::Workspaces {
+                    let arrow_x = (self.rect.width - ARROW_WIDTH + (ARROW_WIDTH - CHAR_WIDTH) / 2) as i16;
+                    let _ = ctx.conn.image_text8(self.window, gc, arrow_x, baseline, b">");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn hide<C: Connection>(&mut self, conn: &C) {
+        overlay::destroy(conn, &mut self.window);
+    }
+}
+
+/// The window operations menu, from right-clicking the titlebar, its menu
+/// button or Alt+Space, with the workspaces as a submenu
+#[derive(Debug)]
+pub struct WindowMenu {
+    /// The client window it acts on
+    pub window: Window,
+    main: Popup,
+    workspaces: Popup,
+    /// Whether the workspaces are open
+    submenu: bool,
+    /// Where the menu may go, the monitor it opened on
+    bounds: Rectangle,
+}
+
+impl WindowMenu {
+    pub fn new(window: Window, items: Vec<Item>, workspaces: Vec<Item>, bounds: Rectangle) -> Self {
+        Self { window, main: Popup::new(items), workspaces: Popup::new(workspaces), submenu: false, bounds }
+    }
+
+    /// Map the menu with its corner at (`x`, `y`)
+    pub fn show(&mut self, ctx: &Context, x: i16, y: i16) -> Result<()> {
+        let (width, height) = popup_size(&self.main.items);
+        self.main.show(ctx, place(width, height, x, y, self.bounds))
+    }
+
+    /// Whether `window` is one of the menu's popups
+    pub fn owns(&self, window: Window) -> bool {
+        window != x11rb::NONE && (window == self.main.window || window == self.workspaces.window)
+    }
+
+    /// Draw the popups again, e.g. on Expose
+    pub fn draw(&self, ctx: &Context) -> Result<()> {
+        self.main.draw(ctx)?;
+        self.workspaces.draw(ctx)
+    }
+
+    /// Whether (`x`, `y`) is over the menu
+    pub fn contains(&self, x: i16, y: i16) -> bool {
+        let inside = |popup: &Popup| {
+            popup.window != x11rb::NONE
+                && (popup.rect.x..popup.rect.x + popup.rect.width as i16).contains(&x)
+                && (popup.rect.y..popup.rect.y + popup.rect.height as i16).contains(&y)
+        };
+        inside(&self.main) || inside(&self.workspaces)
+    }
+
+    /// The pointer moved to (`x`, `y`): select the item under it, opening
+    /// the workspaces over their item. Off the menu the selection stays.
+    pub fn motion(&mut self, ctx: &Context, x: i16, y: i16) -> Result<()> {
+        if let Some(index) = self.workspaces.item_at(x, y) {
+            if self.workspaces.selected != Some(index) {
+                self.workspaces.selected = Some(index).filter(|&i| self.workspaces.items[i].enabled);
+                self.workspaces.draw(ctx)?;
+            }
+        } else if let Some(index) = self.main.item_at(x, y) {
+            if self.main.selected != Some(index) {
+                self.main.selected = Some(index).filter(|&i| self.main.items[i].enabled);
+                self.set_submenu(ctx, self.main.selected_item().is_some_and(|item| item.command == Command::Workspaces))?;
+                self.main.draw(ctx)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A button went up at (`x`, `y`): the command of an enabled item
+    /// there runs
+    pub fn release(&self, x: i16, y: i16) -> Outcome {
+        let item = match self.workspaces.item_at(x, y) {
+            Some(index) => &self.workspaces.items[index],
+            None => match self.main.item_at(x, y) {
+                Some(index) => &self.main.items[index],
+                None => return Outcome::Stay,
+            },
+        };
+        match item.command {
+            _ if !item.enabled => Outcome::Stay,
+            Command::Workspaces => Outcome::Stay,
+            command => Outcome::Run(command),
+        }
+    }
+
+    /// Move through the menu with the keyboard
+    pub fn key(&mut self, ctx: &Context, key: MenuKey) -> Result<Outcome> {
+        let outcome = self.navigate(key);
+        if self.submenu && self.workspaces.window == x11rb::NONE {
+            self.open_workspaces(ctx)?;
+        } else if !self.submenu {
+            self.workspaces.hide(&ctx.conn);
+        }
+        self.draw(ctx)?;
+        Ok(outcome)
+    }
+
+    /// The selection change for `key`, with the submenu opened or closed
+    /// but not yet shown
+    fn navigate(&mut self, key: MenuKey) -> Outcome {
+        let on_workspaces = self.main.selected_item().is_some_and(|item| item.command == Command::Workspaces);
+        match key {
+            MenuKey::Up | MenuKey::Down if self.submenu => self.workspaces.step(key == MenuKey::Down),
+            MenuKey::Up | MenuKey::Down => self.main.step(key == MenuKey::Down),
+            MenuKey::Right | MenuKey::Enter if on_workspaces && !self.submenu => {
+                self.submenu = true;
+                self.workspaces.selected = None;
+                self.workspaces.step(true);
+            }
+            MenuKey::Enter => {
+                let popup = if self.submenu { &self.workspaces } else { &self.main };
+                return popup.selected_item().map_or(Outcome::Stay, |item| Outcome::Run(item.command));
+            }
+            MenuKey::Left | MenuKey::Escape if self.submenu => self.submenu = false,
+            MenuKey::Escape => return Outcome::Close,
+            MenuKey::Left | MenuKey::Right => {}
+        }
+        Outcome::Stay
+    }
+
+    fn set_submenu(&mut self, ctx: &Context, open: bool) -> Result<()> {
+        if open == self.submenu {
+            return Ok(());
+        }
+        self.submenu = open;
+        if open {
+            self.workspaces.selected = None;
+            self.open_workspaces(ctx)
+        } else {
+            self.workspaces.hide(&ctx.conn);
+            Ok(())
+        }
+    }
+
+    /// Show the workspaces next to their item
+    fn open_workspaces(&mut self, ctx: &Context) -> Result<()> {
+        let Some(index) = self.main.items.iter().position(|item| item.command == Command::Workspaces) else {
+            return Ok(());
+        };
+        let (width, height) = popup_size(&self.workspaces.items);
+        let x = self.main.rect.x + self.main.rect.width as i16;
+        let y = self.main.rect.y + (PADDING + index as u16 * ITEM_HEIGHT) as i16;
+        let mut rect = place(width, height, x, y, self.bounds);
+        if rect.x < x {
+            // No room on the right: on the left of the menu instead
+            rect.x = (self.main.rect.x - width as i16).max(self.bounds.x);
+        }
+        self.workspaces.show(ctx, rect)
+    }
+
+    /// Destroy the popups
+    pub fn hide<C: Connection>(&mut self, conn: &C) {
+        self.main.hide(conn);
+        self.workspaces.hide(conn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Rectangle = Rectangle { x: 0, y: 0, width: 1920, height: 1080 };
+
+    fn menu(state: WindowState) -> WindowMenu {
+        let names = vec!["Web".to_string(), "Mail".to_string(), "Code".to_string()];
+        let mut menu = WindowMenu::new(1, window_items(state), workspace_items(&names, 1), SCREEN);
+        menu.main.window = 2;
+        menu.main.rect = Rectangle { x: 100, y: 100, width: 200, height: 2 * PADDING + 9 * ITEM_HEIGHT };
+        menu
+    }
+
+    #[test]
+    fn test_placement() {
+        assert_eq!(place(200, 100, 50, 60, SCREEN), Rectangle { x: 50, y: 60, width: 200, height: 100 });
+        // Opens up and to the left in the bottom right corner
+        assert_eq!(place(200, 100, 1900, 1050, SCREEN), Rectangle { x: 1700, y: 950, width: 200, height: 100 });
+        // Kept on the monitor when it doesn't fit either way
+        let left = Rectangle { x: -1280, y: 0, width: 1280, height: 300 };
+        assert_eq!(place(200, 400, -1200, 100, left).y, 0);
+    }
+
+    #[test]
+    fn test_items() {
+        let menu = menu(WindowState { maximized: true, above: true, sticky: true, ..WindowState::default() });
+        let labels: Vec<&str> = menu.main.items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            ["Move", "Resize", "Minimize", "Unmaximize", "Always on Top", "Always Below", "Always on Visible Workspace", "Move to Workspace", "Close"]
+        );
+        assert!(!menu.main.items[0].enabled);
+        assert!(menu.main.items[4].checked);
+        assert!(!menu.main.items[5].checked);
+        assert!(menu.main.items[6].checked);
+        assert_eq!(menu.workspaces.items[2].label, "3 Code");
+        assert!(!menu.workspaces.items[1].enabled);
+
+        // Clicks land on items by row; disabled ones do nothing
+        assert_eq!(menu.release(150, 100 + (PADDING + 2 * ITEM_HEIGHT) as i16 + 5), Outcome::Run(Command::Minimize));
+        assert_eq!(menu.release(150, 100 + PADDING as i16 + 5), Outcome::Stay);
+        assert_eq!(menu.release(350, 150), Outcome::Stay);
+    }
+
+    #[test]
+    fn test_keyboard_navigation() {
+        let mut menu = menu(WindowState { maximized: true, ..WindowState::default() });
+        // Move and Resize are disabled, so Down starts at Minimize and Up
+        // goes round to Close
+        assert_eq!(menu.navigate(MenuKey::Down), Outcome::Stay);
+        assert_eq!(menu.main.selected, Some(2));
+        menu.navigate(MenuKey::Up);
+        assert_eq!(menu.main.selected, Some(8));
+        menu.navigate(MenuKey::Up);
+        menu.navigate(MenuKey::Right);
+        assert!(menu.submenu);
+        // The window's own workspace is skipped
+        assert_eq!(menu.workspaces.selected, Some(0));
+        menu.navigate(MenuKey::Down);
+        assert_eq!(menu.navigate(MenuKey::Enter), Outcome::Run(Command::Workspace(2)));
+        menu.navigate(MenuKey::Escape);
+        assert!(!menu.submenu);
+        assert_eq!(menu.navigate(MenuKey::Escape), Outcome::Close);
+    }
+}
@@ -2,11 +2,34 @@ pub mod client;
 pub mod manager;
 pub mod frame;
 pub mod draw;
+pub mod theme;
 pub mod placement;
 pub mod cursors;
 pub mod compositor;
+pub mod shadow;
+pub mod fade;
+pub mod opacity;
+pub mod focus;
+pub mod damage;
+pub mod present;
 pub mod settings;
+pub mod keybindings;
+pub mod overlay;
+pub mod switcher;
+pub mod overview;
+pub mod menu;
+pub mod monitors;
+pub mod resize;
+pub mod tiling;
+pub mod snapping;
+pub mod ping;
+pub mod startup;
+pub mod edges;
+pub mod dnd;
+pub mod dpms;
+pub mod zoom;
 pub mod session;
+pub mod restart;
 pub mod error;
 
 pub const LAYER_DESKTOP: u16 = 0;
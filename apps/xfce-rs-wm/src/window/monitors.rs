@@ -0,0 +1,237 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{ConnectionExt as RandrExt, Connection as OutputConnection};
+use x11rb::protocol::xproto::{Rectangle, Window};
+use tracing::{debug, info};
+
+/// A monitor: the area of the root window one CRTC shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// RandR output name, e.g. "HDMI-1"
+    pub name: String,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub primary: bool,
+}
+
+impl Monitor {
+    pub fn rect(&self) -> Rectangle {
+        Rectangle { x: self.x, y: self.y, width: self.width, height: self.height }
+    }
+
+    /// Squared distance from the monitor to a point, 0 inside it
+    fn distance(&self, x: i16, y: i16) -> i64 {
+        let clamp = |v: i16, start: i16, len: u16| (v as i64).clamp(start as i64, start as i64 + len as i64 - 1);
+        let dx = x as i64 - clamp(x, self.x, self.width);
+        let dy = y as i64 - clamp(y, self.y, self.height);
+        dx * dx + dy * dy
+    }
+}
+
+/// The whole screen as one monitor, for X servers without RandR 1.2
+pub fn single(width: u16, height: u16) -> Vec<Monitor> {
+    vec![Monitor { name: "default".to_string(), x: 0, y: 0, width, height, primary: true }]
+}
+
+/// Monitors of the screen, from the outputs that are lit. Outputs cloning
+/// the same CRTC count once.
+pub fn query<C: Connection>(conn: &C, root: Window, width: u16, height: u16) -> Result<Vec<Monitor>> {
+    let resources = conn.randr_get_screen_resources_current(root)?.reply()?;
+    let primary = conn.randr_get_output_primary(root)?.reply()?.output;
+
+    let mut monitors: Vec<(u32, Monitor)> = Vec::new();
+    for &output in &resources.outputs {
+        let info = conn.randr_get_output_info(output, resources.config_timestamp)?.reply()?;
+        if info.connection != OutputConnection::CONNECTED || info.crtc == x11rb::NONE {
+            continue;
+        }
+        let crtc = conn.randr_get_crtc_info(info.crtc, resources.config_timestamp)?.reply()?;
+        if crtc.width == 0 || crtc.height == 0 {
+            continue;
+        }
+        if let Some((_, monitor)) = monitors.iter_mut().find(|(id, _)| *id == info.crtc) {
+            monitor.primary |= output == primary;
+            continue;
+        }
+        monitors.push((info.crtc, Monitor {
+            name: String::from_utf8_lossy(&info.name).into_owned(),
+            x: crtc.x,
+            y: crtc.y,
+            width: crtc.width,
+            height: crtc.height,
+            primary: output == primary,
+        }));
+    }
+
+    let mut monitors: Vec<Monitor> = monitors.into_iter().map(|(_, monitor)| monitor).collect();
+    if monitors.is_empty() {
+        debug!("RandR reports no lit outputs, using the whole screen");
+        return Ok(single(width, height));
+    }
+    // Left to right, so that monitor numbers follow the layout
+    monitors.sort_by_key(|m| (m.x, m.y));
+    for monitor in &monitors {
+        info!("🖥️ Monitor {}: {}x{}+{}+{}{}", monitor.name, monitor.width, monitor.height, monitor.x, monitor.y, if monitor.primary { " (primary)" } else { "" });
+    }
+    Ok(monitors)
+}
+
+/// Index of the monitor showing a point, or the nearest one when it falls
+/// in a gap between monitors
+pub fn monitor_at(monitors: &[Monitor], x: i16, y: i16) -> usize {
+    monitors
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, monitor)| monitor.distance(x, y))
+        .map_or(0, |(index, _)| index)
+}
+
+/// Index of the monitor showing most of `rect`; the one nearest to its
+/// center when it is off screen
+pub fn monitor_for_rect(monitors: &[Monitor], rect: Rectangle) -> usize {
+    let overlap = |monitor: &Monitor| {
+        let left = (rect.x as i32).max(monitor.x as i32);
+        let right = (rect.x as i32 + rect.width as i32).min(monitor.x as i32 + monitor.width as i32);
+        let top = (rect.y as i32).max(monitor.y as i32);
+        let bottom = (rect.y as i32 + rect.height as i32).min(monitor.y as i32 + monitor.height as i32);
+        (right - left).max(0) as i64 * (bottom - top).max(0) as i64
+    };
+    match monitors.iter().enumerate().max_by_key(|(_, monitor)| overlap(monitor)) {
+        Some((index, monitor)) if overlap(monitor) > 0 => index,
+        _ => monitor_at(monitors, rect.x + (rect.width / 2) as i16, rect.y + (rect.height / 2) as i16),
+    }
+}
+
+/// Where to move `rect` when no monitor shows any of it, e.g. after the
+/// monitor it was on went away: inside the nearest one
+pub fn bring_on_screen(monitors: &[Monitor], rect: Rectangle) -> Option<(i16, i16)> {
+    let visible = monitors.iter().any(|monitor| {
+        (rect.x as i32) < monitor.x as i32 + monitor.width as i32
+            && rect.x as i32 + rect.width as i32 > monitor.x as i32
+            && (rect.y as i32) < monitor.y as i32 + monitor.height as i32
+            && rect.y as i32 + rect.height as i32 > monitor.y as i32
+    });
+    if visible {
+        return None;
+    }
+    let monitor = monitors.get(monitor_for_rect(monitors, rect))?;
+    let clamp = |v: i16, len: u16, start: i16, area: u16| {
+        let max = start as i32 + area as i32 - len as i32;
+        (v as i32).min(max).max(start as i32) as i16
+    };
+    Some((clamp(rect.x, rect.width, monitor.x, monitor.width), clamp(rect.y, rect.height, monitor.y, monitor.height)))
+}
+
+/// The part of `monitor` panels leave free. `struts` are `_NET_WM_STRUT`
+/// (4 values) or `_NET_WM_STRUT_PARTIAL` (12 values), which reserve space
+/// from the edges of the whole `screen_width` x `screen_height` screen and
+/// so only shrink the monitors along those edges.
+pub fn workarea<'a>(monitor: &Monitor, struts: impl Iterator<Item = &'a [u32]>, screen_width: u16, screen_height: u16) -> Rectangle {
+    let (mut left, mut top) = (monitor.x as i32, monitor.y as i32);
+    let mut right = monitor.x as i32 + monitor.width as i32;
+    let mut bottom = monitor.y as i32 + monitor.height as i32;
+    let (screen_width, screen_height) = (screen_width as i32, screen_height as i32);
+
+    // Whether the strut's span along its edge crosses the monitor
+    let spans = |strut: &[u32], index: usize, start: i32, end: i32| {
+        strut.len() < 12 || ((strut[index] as i32) < end && strut[index + 1] as i32 >= start)
+    };
+    for strut in struts.filter(|strut| strut.len() >= 4) {
+        let (l, r, t, b) = (strut[0] as i32, strut[1] as i32, strut[2] as i32, strut[3] as i32);
+        if l > 0 && l > left && spans(strut, 4, top, bottom) {
+            left = l;
+        }
+        if r > 0 && screen_width - r < right && spans(strut, 6, top, bottom) {
+            right = screen_width - r;
+        }
+        if t > 0 && t > top && spans(strut, 8, left, right) {
+            top = t;
+        }
+        if b > 0 && screen_height - b < bottom && spans(strut, 10, left, right) {
+            bottom = screen_height - b;
+        }
+    }
+    Rectangle {
+        x: left as i16,
+        y: top as i16,
+        width: (right - left).max(1) as u16,
+        height: (bottom - top).max(1) as u16,
+    }
+}
+
+/// The smallest rectangle holding all of `areas`, for `_NET_WORKAREA`,
+/// which has room for one rectangle where each monitor has its own work
+/// area. A panel along an edge the monitors share shrinks it; one on a
+/// single monitor doesn't, or it would shrink the others too.
+pub fn bounding(areas: &[Rectangle]) -> Option<Rectangle> {
+    let left = areas.iter().map(|a| a.x as i32).min()?;
+    let top = areas.iter().map(|a| a.y as i32).min()?;
+    let right = areas.iter().map(|a| a.x as i32 + a.width as i32).max()?;
+    let bottom = areas.iter().map(|a| a.y as i32 + a.height as i32).max()?;
+    Some(Rectangle { x: left as i16, y: top as i16, width: (right - left) as u16, height: (bottom - top) as u16 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dual() -> Vec<Monitor> {
+        vec![
+            Monitor { name: "eDP-1".to_string(), x: 0, y: 0, width: 1920, height: 1080, primary: true },
+            Monitor { name: "HDMI-1".to_string(), x: 1920, y: 0, width: 2560, height: 1440, primary: false },
+        ]
+    }
+
+    #[test]
+    fn test_monitor_lookup() {
+        let monitors = dual();
+        assert_eq!(monitor_at(&monitors, 100, 100), 0);
+        assert_eq!(monitor_at(&monitors, 1920, 0), 1);
+        // Below the smaller monitor, nearest to it
+        assert_eq!(monitor_at(&monitors, 1000, 1300), 0);
+
+        // Mostly on the second monitor
+        assert_eq!(monitor_for_rect(&monitors, Rectangle { x: 1800, y: 100, width: 800, height: 600 }), 1);
+        // Off screen to the left
+        assert_eq!(monitor_for_rect(&monitors, Rectangle { x: -3000, y: 0, width: 800, height: 600 }), 0);
+
+        // Left where an unplugged third monitor was
+        assert_eq!(bring_on_screen(&monitors, Rectangle { x: 5000, y: 200, width: 800, height: 600 }), Some((3680, 200)));
+        assert_eq!(bring_on_screen(&monitors, Rectangle { x: 1800, y: 100, width: 800, height: 600 }), None);
+    }
+
+    #[test]
+    fn test_workarea_with_struts() {
+        let monitors = dual();
+        // A 30 px panel along the top of the first monitor only, and a
+        // 40 px one along the bottom of the whole screen, which is the
+        // bottom of the taller second monitor
+        let top_panel = [0, 0, 30, 0, 0, 0, 0, 0, 0, 1919, 0, 0];
+        let bottom_panel = [0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 1920, 4479];
+        let struts = [&top_panel[..], &bottom_panel[..]];
+
+        assert_eq!(
+            workarea(&monitors[0], struts.iter().copied(), 4480, 1440),
+            Rectangle { x: 0, y: 30, width: 1920, height: 1050 }
+        );
+        assert_eq!(
+            workarea(&monitors[1], struts.iter().copied(), 4480, 1440),
+            Rectangle { x: 1920, y: 0, width: 2560, height: 1400 }
+        );
+        // A plain _NET_WM_STRUT applies across the screen
+        assert_eq!(
+            workarea(&monitors[1], [&[0, 0, 24, 0][..]].into_iter(), 4480, 1440),
+            Rectangle { x: 1920, y: 24, width: 2560, height: 1416 }
+        );
+
+        // The top panel leaves the screen's work area be, as the second
+        // monitor reaches the top; the bottom one only clips the second
+        let areas: Vec<Rectangle> = monitors.iter().map(|m| workarea(m, struts.iter().copied(), 4480, 1440)).collect();
+        assert_eq!(bounding(&areas), Some(Rectangle { x: 0, y: 0, width: 4480, height: 1400 }));
+        let both_top: Vec<Rectangle> = monitors.iter().map(|m| workarea(m, [&[0, 0, 24, 0][..]].into_iter(), 4480, 1440)).collect();
+        assert_eq!(bounding(&both_top), Some(Rectangle { x: 0, y: 24, width: 4480, height: 1416 }));
+        assert_eq!(bounding(&[]), None);
+    }
+}
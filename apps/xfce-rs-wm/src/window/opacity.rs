@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::Window;
+
+use crate::window::settings::Settings;
+
+#[derive(Debug, Clone, Copy)]
+struct Dim {
+    from: f64,
+    to: f64,
+    start: Instant,
+}
+
+impl Dim {
+    fn value(&self, duration: Duration, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.start);
+        if elapsed >= duration {
+            return self.to;
+        }
+        self.from + (self.to - self.from) * (elapsed.as_secs_f64() / duration.as_secs_f64())
+    }
+}
+
+/// Opacity the compositor gives windows on top of their own
+/// `_NET_WM_WINDOW_OPACITY`: set per window class by the rules, and lower
+/// for windows without the focus, which glide there and back as the
+/// focus moves.
+#[derive(Debug, Clone)]
+pub struct OpacityRules {
+    /// `/general/inactive_opacity`, as a factor; 1 leaves them be
+    inactive: f64,
+    /// `/general/opacity_rules/<name>`: factors for windows whose
+    /// WM_CLASS instance or class is `name`, lowercased
+    rules: HashMap<String, f64>,
+    /// How long a window takes to dim or light up again
+    duration: Duration,
+    dims: HashMap<Window, Dim>,
+}
+
+impl OpacityRules {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let factor = |percent: u32| f64::from(percent.clamp(10, 100)) / 100.0;
+        Self {
+            inactive: factor(settings.inactive_opacity),
+            rules: settings.opacity_rules.iter().map(|(name, &percent)| (name.to_lowercase(), factor(percent))).collect(),
+            duration: if settings.fade_windows { Duration::from_millis(settings.fade_duration.into()) } else { Duration::ZERO },
+            dims: HashMap::new(),
+        }
+    }
+
+    /// Dim `window` from `now` on, or light it up again, gliding there
+    /// from wherever it is
+    pub fn set_dimmed(&mut self, window: Window, dimmed: bool, now: Instant) {
+        let to = if dimmed { self.inactive } else { 1.0 };
+        let from = self.dim(window, now);
+        if self.dims.get(&window).map_or(to == 1.0, |dim| dim.to == to) {
+            return;
+        }
+        self.dims.insert(window, Dim { from, to, start: now });
+    }
+
+    fn dim(&self, window: Window, now: Instant) -> f64 {
+        self.dims.get(&window).map_or(1.0, |dim| dim.value(self.duration, now))
+    }
+
+    /// The rule's factor for a window of `class`, "instance.class"
+    fn rule(&self, class: Option<&str>) -> f64 {
+        let Some((instance, class)) = class.and_then(|key| key.split_once('.')) else { return 1.0 };
+        [instance, class]
+            .into_iter()
+            .find_map(|name| self.rules.get(&name.to_lowercase()).copied())
+            .unwrap_or(1.0)
+    }
+
+    /// Opacity factor of `window`, of `class`, at `now`
+    pub fn factor(&self, window: Window, class: Option<&str>, now: Instant) -> f64 {
+        self.rule(class) * self.dim(window, now)
+    }
+
+    /// Whether a window is still dimming or lighting up at `now`, so the
+    /// screen needs repainting
+    pub fn is_animating(&self, now: Instant) -> bool {
+        self.dims.values().any(|dim| dim.value(self.duration, now) != dim.to)
+    }
+
+    /// Forget `window`, e.g. once it is gone
+    pub fn remove(&mut self, window: Window) {
+        self.dims.remove(&window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> OpacityRules {
+        let settings = Settings {
+            inactive_opacity: 80,
+            opacity_rules: HashMap::from([("xfce4-terminal".to_string(), 92)]),
+            fade_duration: 100,
+            ..Settings::default()
+        };
+        OpacityRules::from_settings(&settings)
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = rules();
+        let now = Instant::now();
+        assert_eq!(rules.factor(1, Some("xfce4-terminal.Xfce4-terminal"), now), 0.92);
+        // By class too, whatever the case
+        assert_eq!(rules.factor(1, Some("terminal.XFCE4-Terminal"), now), 0.92);
+        assert_eq!(rules.factor(1, Some("mousepad.Mousepad"), now), 1.0);
+        assert_eq!(rules.factor(1, None, now), 1.0);
+    }
+
+    #[test]
+    fn test_dimming_glides() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut rules = rules();
+
+        // Lighting up a window that never dimmed is nothing to animate
+        rules.set_dimmed(1, false, start);
+        assert!(!rules.is_animating(start));
+
+        rules.set_dimmed(1, true, start);
+        assert!(rules.is_animating(ms(50)));
+        assert!((rules.factor(1, None, ms(50)) - 0.9).abs() < 1e-9);
+        // Asking again doesn't start over
+        rules.set_dimmed(1, true, ms(50));
+        assert_eq!(rules.factor(1, None, ms(100)), 0.8);
+        assert!(!rules.is_animating(ms(100)));
+
+        // Focused midway back: from where it got to
+        rules.set_dimmed(1, false, ms(200));
+        rules.set_dimmed(1, true, ms(250));
+        assert!((rules.factor(1, None, ms(250)) - 0.9).abs() < 1e-9);
+        assert_eq!(rules.factor(1, Some("xfce4-terminal.Xfce4-terminal"), ms(400)), 0.92 * 0.8);
+    }
+}
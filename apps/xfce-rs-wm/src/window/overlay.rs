@@ -0,0 +1,101 @@
+//! Popups the WM draws itself with core X requests: the window switcher,
+//! the overview, the window menu and the size readout
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ConnectionExt, CreateGCAux, CreateWindowAux, EventMask, Font, Gcontext, Rectangle, Window, WindowClass,
+};
+use x11rb::rust_connection::RustConnection;
+use tracing::debug;
+
+use crate::core::context::Context;
+
+/// Core fonts for text, best first. The popups are laid out for 10x20;
+/// every X server has "fixed".
+const FONTS: [&[u8]; 2] = [b"10x20", b"fixed"];
+
+/// Create an unmapped override-redirect popup at `rect` on the root window
+pub fn create(ctx: &Context, rect: Rectangle, background: u32, event_mask: EventMask) -> Result<Window> {
+    let window = ctx.conn.generate_id()?;
+    ctx.conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        ctx.root_window,
+        rect.x,
+        rect.y,
+        rect.width,
+        rect.height,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new()
+            .override_redirect(1)
+            .background_pixel(background)
+            .event_mask(event_mask),
+    )?;
+    Ok(window)
+}
+
+/// Destroy the popup in `window`, if there is one, and forget it
+pub fn destroy<C: Connection>(conn: &C, window: &mut Window) {
+    if *window != x11rb::NONE {
+        let _ = conn.destroy_window(*window);
+        *window = x11rb::NONE;
+    }
+}
+
+/// Graphics context for one redraw of a popup, with the first of
+/// [`FONTS`] the server has. Both are freed when it drops.
+pub struct Painter<'a> {
+    conn: &'a RustConnection,
+    gc: Gcontext,
+    font: Option<Font>,
+}
+
+impl<'a> Painter<'a> {
+    pub fn new(ctx: &'a Context, window: Window, values: CreateGCAux) -> Result<Self> {
+        let painter = Self { conn: &ctx.conn, gc: ctx.conn.generate_id()?, font: open_font(&ctx.conn) };
+        let values = match painter.font {
+            Some(font) => values.font(font),
+            None => values,
+        };
+        ctx.conn.create_gc(painter.gc, window, &values)?;
+        Ok(painter)
+    }
+
+    pub fn gc(&self) -> Gcontext {
+        self.gc
+    }
+
+    /// Whether text can be drawn, no core font being available otherwise
+    pub fn has_font(&self) -> bool {
+        self.font.is_some()
+    }
+}
+
+impl Drop for Painter<'_> {
+    fn drop(&mut self) {
+        let _ = self.conn.free_gc(self.gc);
+        if let Some(font) = self.font {
+            let _ = self.conn.close_font(font);
+        }
+    }
+}
+
+/// Open the first of [`FONTS`] the server has. Sending OpenFont succeeds
+/// whether or not the font exists, so each is checked with a round trip.
+fn open_font(conn: &RustConnection) -> Option<Font> {
+    let font = conn.generate_id().ok()?;
+    for name in FONTS {
+        let opened = match conn.open_font(font, name) {
+            Ok(cookie) => cookie.check(),
+            Err(e) => Err(e.into()),
+        };
+        match opened {
+            Ok(()) => return Some(font),
+            Err(e) => debug!("Cannot open font {}: {}", String::from_utf8_lossy(name), e),
+        }
+    }
+    None
+}
@@ -0,0 +1,300 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ChangeGCAux, ConnectionExt, CreateGCAux, EventMask, ImageFormat, Rectangle, Window,
+};
+use tracing::debug;
+
+use crate::core::context::Context;
+use crate::window::menu::MenuKey;
+use crate::window::overlay::{self, Painter};
+use crate::window::switcher::{label, Entry, ICON_SIZE};
+
+const LABEL_HEIGHT: u16 = 26;
+/// Space between cells and around the grid
+const PADDING: u16 = 24;
+/// Width of a character of the 10x20 font
+const CHAR_WIDTH: u16 = 10;
+
+const BACKGROUND: u32 = 0x1e1e1e;
+const SELECTED: u32 = 0x4a6a94;
+const TEXT: u32 = 0xe0e0e0;
+
+/// Cells for `count` windows over `area`: as many columns as keep the
+/// cells about as wide as the area's shape, the last row centered.
+/// Returns the number of columns and the cells.
+pub fn grid(count: usize, area: Rectangle) -> (usize, Vec<Rectangle>) {
+    if count == 0 {
+        return (1, Vec::new());
+    }
+    let aspect = area.width as f32 / area.height.max(1) as f32;
+    let columns = ((count as f32 * aspect).sqrt().ceil() as usize).clamp(1, count);
+    let rows = count.div_ceil(columns);
+
+    let cell_width = area.width.saturating_sub(PADDING * (columns as u16 + 1)) / columns as u16;
+    let cell_height = area.height.saturating_sub(PADDING * (rows as u16 + 1)) / rows as u16;
+    let grid_height = PADDING + rows as u16 * (cell_height + PADDING);
+    let top = area.y + ((area.height - grid_height) / 2) as i16;
+
+    let cells = (0..count)
+        .map(|i| {
+            let (row, column) = (i / columns, i % columns);
+            let in_row = (count - row * columns).min(columns) as u16;
+            let row_width = PADDING + in_row * (cell_width + PADDING);
+            let left = area.x + ((area.width - row_width) / 2) as i16;
+            Rectangle {
+                x: left + (PADDING + column as u16 * (cell_width + PADDING)) as i16,
+                y: top + (PADDING + row as u16 * (cell_height + PADDING)) as i16,
+                width: cell_width,
+                height: cell_height,
+            }
+        })
+        .collect();
+    (columns, cells)
+}
+
+/// What a key press or click in the overview came to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The overview stays up, perhaps with another window selected
+    Stay,
+    /// The overview goes away without doing anything
+    Close,
+    /// The overview goes away and the selected window is activated
+    Activate,
+}
+
+/// The exposé overview: every window of the workspace scaled down side by
+/// side on a screen-sized popup, one of them selected
+pub struct Overview {
+    entries: Vec<Entry>,
+    selected: usize,
+    columns: usize,
+    cells: Vec<Rectangle>,
+    popup: Window,
+    /// Whether the compositor draws live thumbnails over the cells
+    thumbnails: bool,
+}
+
+impl Overview {
+    /// Lay the windows out over `area`, the first one selected. Returns
+    /// `None` when there are none.
+    pub fn new(entries: Vec<Entry>, area: Rectangle, thumbnails: bool) -> Option<Self> {
+        if entries.is_empty() {
+            return None;
+        }
+        let (columns, cells) = grid(entries.len(), area);
+        Some(Self { entries, selected: 0, columns, cells, popup: x11rb::NONE, thumbnails })
+    }
+
+    pub fn selected(&self) -> Window {
+        self.entries[self.selected].window
+    }
+
+    pub fn popup(&self) -> Window {
+        self.popup
+    }
+
+    /// The window whose cell holds the point, in root coordinates
+    fn item_at(&self, x: i16, y: i16) -> Option<usize> {
+        self.cells.iter().position(|cell| {
+            let (x, y) = (i32::from(x), i32::from(y));
+            let (left, top) = (i32::from(cell.x), i32::from(cell.y));
+            x >= left && x < left + i32::from(cell.width) && y >= top && y < top + i32::from(cell.height)
+        })
+    }
+
+    /// Move the selection with the arrow keys, or pick it with Enter
+    pub fn key(&mut self, key: MenuKey) -> Outcome {
+        let count = self.entries.len();
+        let selected = self.selected;
+        self.selected = match key {
+            MenuKey::Left => (selected + count - 1) % count,
+            MenuKey::Right => (selected + 1) % count,
+            MenuKey::Up => selected.checked_sub(self.columns).unwrap_or(selected),
+            MenuKey::Down => Some(selected + self.columns).filter(|&i| i < count).unwrap_or(selected),
+            MenuKey::Enter => return Outcome::Activate,
+            MenuKey::Escape => return Outcome::Close,
+        };
+        Outcome::Stay
+    }
+
+    /// The pointer moved to (`x`, `y`): select the window under it.
+    /// Returns whether the selection changed.
+    pub fn motion(&mut self, x: i16, y: i16) -> bool {
+        match self.item_at(x, y) {
+            Some(i) if i != self.selected => {
+                self.selected = i;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// A click at (`x`, `y`) picks the window there; anywhere else closes
+    pub fn click(&mut self, x: i16, y: i16) -> Outcome {
+        match self.item_at(x, y) {
+            Some(i) => {
+                self.selected = i;
+                Outcome::Activate
+            }
+            None => Outcome::Close,
+        }
+    }
+
+    /// Create and map the popup over the whole screen
+    pub fn show(&mut self, ctx: &Context) -> Result<()> {
+        let screen = Rectangle { x: 0, y: 0, width: ctx.screen_width, height: ctx.screen_height };
+        let events = EventMask::EXPOSURE | EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION;
+        self.popup = overlay::create(ctx, screen, BACKGROUND, events)?;
+        ctx.conn.map_window(self.popup)?;
+        self.draw(ctx)
+    }
+
+    /// Draw the cells with their labels and icons, the selected one
+    /// highlighted. Thumbnails cover the icons later, in the compositor's
+    /// paint.
+    pub fn draw(&self, ctx: &Context) -> Result<()> {
+        if self.popup == x11rb::NONE {
+            return Ok(());
+        }
+        let painter = Painter::new(ctx, self.popup, CreateGCAux::new().foreground(BACKGROUND))?;
+        let gc = painter.gc();
+        ctx.conn.poly_fill_rectangle(self.popup, gc, &[Rectangle { x: 0, y: 0, width: ctx.screen_width, height: ctx.screen_height }])?;
+
+        let draw_icons = ctx.root_depth == 24 || ctx.root_depth == 32;
+        for (i, (entry, cell)) in self.entries.iter().zip(&self.cells).enumerate() {
+            let background = if i == self.selected { SELECTED } else { BACKGROUND };
+            if i == self.selected {
+                // A frame around the cell, the thumbnail going inside it
+                ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(SELECTED))?;
+                let frame = Rectangle {
+                    x: cell.x - (PADDING / 4) as i16,
+                    y: cell.y - (PADDING / 4) as i16,
+                    width: cell.width + PADDING / 2,
+                    height: cell.height + PADDING / 2,
+                };
+                ctx.conn.poly_fill_rectangle(self.popup, gc, &[frame])?;
+            }
+
+            let preview = preview_area(cell);
+            if let (Some(icon), true) = (&entry.icon, draw_icons && preview.width >= ICON_SIZE && preview.height >= ICON_SIZE) {
+                let x = preview.x + ((preview.width - ICON_SIZE) / 2) as i16;
+                let y = preview.y + ((preview.height - ICON_SIZE) / 2) as i16;
+                if let Err(e) = ctx.conn.put_image(
+                    ImageFormat::Z_PIXMAP,
+                    self.popup,
+                    gc,
+                    ICON_SIZE,
+                    ICON_SIZE,
+                    x,
+                    y,
+                    0,
+                    ctx.root_depth,
+                    &icon.to_image(ICON_SIZE, background),
+                ) {
+                    debug!("Failed to draw icon of window {}: {}", entry.window, e);
+                }
+            }
+
+            if painter.has_font() {
+                let text = label(&entry.title, (cell.width.saturating_sub(8) / CHAR_WIDTH) as usize);
+                let text_x = cell.x + (cell.width.saturating_sub(text.len() as u16 * CHAR_WIDTH) / 2) as i16;
+                let text_y = cell.y + cell.height as i16 - 7;
+                ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(TEXT).background(background))?;
+                if let Err(e) = ctx.conn.image_text8(self.popup, gc, text_x, text_y, text.as_bytes()) {
+                    debug!("Failed to draw overview label: {}", e);
+                }
+                ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(BACKGROUND))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Where each window's thumbnail goes, in root coordinates, which the
+    /// popup's are too
+    pub fn thumbnail_areas(&self) -> impl Iterator<Item = (Window, Rectangle)> + '_ {
+        self.entries.iter().zip(&self.cells).filter(|_| self.thumbnails).map(|(entry, cell)| (entry.window, preview_area(cell)))
+    }
+
+    /// Destroy the popup
+    pub fn hide<C: Connection>(&mut self, conn: &C) {
+        overlay::destroy(conn, &mut self.popup);
+    }
+}
+
+/// Part of a cell above the label, for the thumbnail or the icon
+fn preview_area(cell: &Rectangle) -> Rectangle {
+    Rectangle { height: cell.height.saturating_sub(LABEL_HEIGHT), ..*cell }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: u32) -> Vec<Entry> {
+        (1..=count).map(|window| Entry { window, title: String::new(), icon: None }).collect()
+    }
+
+    const SCREEN: Rectangle = Rectangle { x: 0, y: 0, width: 1920, height: 1080 };
+
+    #[test]
+    fn test_grid() {
+        assert_eq!(grid(0, SCREEN).1, Vec::new());
+
+        let (columns, cells) = grid(1, SCREEN);
+        assert_eq!(columns, 1);
+        assert_eq!(cells[0], Rectangle { x: 24, y: 24, width: 1872, height: 1032 });
+
+        // Five windows on a wide screen: three, then two centered below
+        let (columns, cells) = grid(5, SCREEN);
+        assert_eq!(columns, 3);
+        assert_eq!(cells[0].y, cells[2].y);
+        assert!(cells[3].y > cells[0].y);
+        assert!(cells[3].x > cells[0].x);
+        assert_eq!(cells[3].x - SCREEN.x, SCREEN.width as i16 - (cells[4].x + cells[4].width as i16));
+        for cell in &cells {
+            assert!(cell.x + cell.width as i16 <= 1920 && cell.y + cell.height as i16 <= 1080);
+        }
+
+        // Offset to the monitor it is shown on
+        let (_, cells) = grid(1, Rectangle { x: 1920, ..SCREEN });
+        assert_eq!(cells[0].x, 1944);
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut overview = Overview::new(entries(5), SCREEN, true).unwrap();
+        assert_eq!(overview.selected(), 1);
+        assert_eq!(overview.key(MenuKey::Left), Outcome::Stay);
+        assert_eq!(overview.selected(), 5);
+        overview.key(MenuKey::Right);
+        overview.key(MenuKey::Down);
+        assert_eq!(overview.selected(), 4);
+        // Nothing below the second row, or above the first
+        overview.key(MenuKey::Down);
+        assert_eq!(overview.selected(), 4);
+        overview.key(MenuKey::Up);
+        overview.key(MenuKey::Up);
+        assert_eq!(overview.selected(), 1);
+        assert_eq!(overview.key(MenuKey::Enter), Outcome::Activate);
+        assert_eq!(overview.key(MenuKey::Escape), Outcome::Close);
+        assert!(Overview::new(Vec::new(), SCREEN, true).is_none());
+    }
+
+    #[test]
+    fn test_pointer() {
+        let mut overview = Overview::new(entries(4), SCREEN, false).unwrap();
+        let cell = overview.cells[2];
+        assert!(overview.motion(cell.x + 5, cell.y + 5));
+        assert!(!overview.motion(cell.x + 6, cell.y + 6));
+        assert_eq!(overview.selected(), 3);
+        // Between cells the selection stays
+        assert!(!overview.motion(2, 2));
+        assert_eq!(overview.click(2, 2), Outcome::Close);
+        let cell = overview.cells[1];
+        assert_eq!(overview.click(cell.x, cell.y), Outcome::Activate);
+        assert_eq!(overview.selected(), 2);
+        assert_eq!(overview.thumbnail_areas().count(), 0);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::Window;
+
+/// How long a window asked to close has to answer `_NET_WM_PING` before
+/// it counts as hung
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The iced helper that asks whether to force quit a hung window
+const FORCE_QUIT_HELPER: &str = "xfce-rs-force-quit";
+
+/// What the helper prints when the user chose to force quit
+const KILL_ANSWER: &str = "kill";
+
+/// Windows pinged with `_NET_WM_PING` that have not answered yet
+#[derive(Debug, Default)]
+pub struct Pings {
+    pending: HashMap<Window, Instant>,
+}
+
+impl Pings {
+    /// `window` was pinged at `now`; pinging it again before it answers
+    /// keeps the first deadline
+    pub fn sent(&mut self, window: Window, now: Instant) {
+        self.pending.entry(window).or_insert(now + PING_TIMEOUT);
+    }
+
+    /// `window` answered; whether it was waited on
+    pub fn pong(&mut self, window: Window) -> bool {
+        self.pending.remove(&window).is_some()
+    }
+
+    /// `window` went away
+    pub fn forget(&mut self, window: Window) {
+        self.pending.remove(&window);
+    }
+
+    /// When the next window runs out of time
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.values().min().copied()
+    }
+
+    /// The windows that ran out of time by `now`
+    pub fn take_expired(&mut self, now: Instant) -> Vec<Window> {
+        let expired: Vec<Window> = self.pending.iter().filter(|(_, &at)| at <= now).map(|(&w, _)| w).collect();
+        for window in &expired {
+            self.pending.remove(window);
+        }
+        expired
+    }
+}
+
+/// The Force Quit dialog shown for a hung window, run as a helper
+/// process that prints its answer when it closes
+#[derive(Debug)]
+pub struct ForceQuitDialog {
+    child: Child,
+}
+
+impl ForceQuitDialog {
+    /// Ask about the window titled `title`, from process `pid` if known
+    pub fn open(title: &str, pid: Option<u32>) -> io::Result<Self> {
+        let mut command = Command::new(helper_path());
+        command.arg(title).stdin(Stdio::null()).stdout(Stdio::piped());
+        if let Some(pid) = pid {
+            command.arg(pid.to_string());
+        }
+        Ok(Self { child: command.spawn()? })
+    }
+
+    /// Whether the user chose to force quit, once the dialog has closed
+    pub fn answer(&mut self) -> Option<bool> {
+        match self.child.try_wait() {
+            Ok(None) => None,
+            Ok(Some(_)) => {
+                let mut output = String::new();
+                if let Some(stdout) = self.child.stdout.as_mut() {
+                    let _ = stdout.read_to_string(&mut output);
+                }
+                Some(output.trim() == KILL_ANSWER)
+            }
+            Err(_) => Some(false),
+        }
+    }
+
+    /// Close the dialog unanswered, as the window came back or went away
+    pub fn dismiss(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The helper next to the WM's own executable, as in a build tree, or
+/// where packages install it
+fn helper_path() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(FORCE_QUIT_HELPER)))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("/usr/lib").join(FORCE_QUIT_HELPER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping_timeout() {
+        let start = Instant::now();
+        let mut pings = Pings::default();
+        pings.sent(1, start);
+        pings.sent(2, start + Duration::from_secs(2));
+        // Asked to close again while still waiting
+        pings.sent(1, start + Duration::from_secs(3));
+        assert_eq!(pings.deadline(), Some(start + PING_TIMEOUT));
+
+        assert!(pings.take_expired(start + Duration::from_secs(4)).is_empty());
+        assert_eq!(pings.take_expired(start + PING_TIMEOUT), vec![1]);
+        assert_eq!(pings.deadline(), Some(start + Duration::from_secs(2) + PING_TIMEOUT));
+    }
+
+    #[test]
+    fn test_pong() {
+        let start = Instant::now();
+        let mut pings = Pings::default();
+        pings.sent(1, start);
+        assert!(pings.pong(1));
+        assert!(!pings.pong(1));
+        pings.sent(2, start);
+        pings.forget(2);
+        assert_eq!(pings.deadline(), None);
+        assert!(pings.take_expired(start + PING_TIMEOUT).is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use x11rb::connection::Connection;
+use x11rb::protocol::present::{self, ConnectionExt as PresentExt, EventMask, Option as PresentOption};
+use x11rb::protocol::render::{ConnectionExt as RenderExt, CreatePictureAux, Pictformat, Picture};
+use x11rb::protocol::xfixes::Region;
+use x11rb::protocol::xproto::{ConnectionExt, Pixmap, Window};
+
+/// How long a frame may take to reach the screen before painting goes on
+/// without word from the server, which can happen with the monitor off
+const COMPLETE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Puts the compositor's frames on screen through the Present extension.
+/// Frames are painted into a back buffer that is copied to the overlay at
+/// the next vertical blank, so they don't tear, and a frame isn't painted
+/// before the last one is on screen, which paces painting to the refresh
+/// rate.
+#[derive(Debug)]
+pub struct Presenter {
+    window: Window,
+    depth: u8,
+    format: Pictformat,
+    pixmap: Pixmap,
+    picture: Picture,
+    serial: u32,
+    /// When the frame on its way to the screen was presented
+    pending: Option<Instant>,
+}
+
+impl Presenter {
+    /// Present onto `window`, `width` x `height` with `depth` and
+    /// `format`; fails without the Present extension
+    pub fn new<C: Connection>(conn: &C, window: Window, depth: u8, format: Pictformat, width: u16, height: u16) -> Result<Self> {
+        if conn.extension_information(present::X11_EXTENSION_NAME)?.is_none() {
+            bail!("No Present extension");
+        }
+        conn.present_query_version(1, 2)?.reply()?;
+        let eid = conn.generate_id()?;
+        conn.present_select_input(eid, window, EventMask::COMPLETE_NOTIFY)?;
+        let (pixmap, picture) = Self::create_buffer(conn, window, depth, format, width, height)?;
+        Ok(Self { window, depth, format, pixmap, picture, serial: 0, pending: None })
+    }
+
+    fn create_buffer<C: Connection>(conn: &C, window: Window, depth: u8, format: Pictformat, width: u16, height: u16) -> Result<(Pixmap, Picture)> {
+        let pixmap = conn.generate_id()?;
+        conn.create_pixmap(depth, pixmap, window, width.max(1), height.max(1))?;
+        let picture = conn.generate_id()?;
+        conn.render_create_picture(picture, pixmap, format, &CreatePictureAux::new())?;
+        Ok((pixmap, picture))
+    }
+
+    /// The back buffer frames are painted into
+    pub fn picture(&self) -> Picture {
+        self.picture
+    }
+
+    /// Make the back buffer `width` x `height`, after the screen changed
+    /// size; it needs painting whole again
+    pub fn resize<C: Connection>(&mut self, conn: &C, width: u16, height: u16) -> Result<()> {
+        let (pixmap, picture) = Self::create_buffer(conn, self.window, self.depth, self.format, width, height)?;
+        conn.render_free_picture(self.picture)?;
+        conn.free_pixmap(self.pixmap)?;
+        self.pixmap = pixmap;
+        self.picture = picture;
+        Ok(())
+    }
+
+    /// Free the back buffer
+    pub fn free<C: Connection>(&self, conn: &C) -> Result<()> {
+        conn.render_free_picture(self.picture)?;
+        conn.free_pixmap(self.pixmap)?;
+        Ok(())
+    }
+
+    /// Whether the last frame is yet to reach the screen at `now`, so the
+    /// next one has to wait
+    pub fn is_pending(&self, now: Instant) -> bool {
+        self.pending.is_some_and(|presented| now.saturating_duration_since(presented) < COMPLETE_TIMEOUT)
+    }
+
+    /// Copy `update` of the back buffer to the screen at the next vertical
+    /// blank, or all of it for `None`
+    pub fn present<C: Connection>(&mut self, conn: &C, update: Option<Region>) -> Result<()> {
+        self.serial = self.serial.wrapping_add(1);
+        // Copied rather than flipped, so the back buffer keeps the frame
+        // and the next one only paints what changed
+        conn.present_pixmap(
+            self.window,
+            self.pixmap,
+            self.serial,
+            x11rb::NONE,
+            update.unwrap_or(x11rb::NONE),
+            0,
+            0,
+            x11rb::NONE,
+            x11rb::NONE,
+            x11rb::NONE,
+            PresentOption::COPY.into(),
+            0,
+            0,
+            0,
+            &[],
+        )?;
+        self.pending = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Note a `PresentCompleteNotify`; true when it was for the frame
+    /// being waited for
+    pub fn complete(&mut self, window: Window, serial: u32) -> bool {
+        if window != self.window || serial != self.serial {
+            return false;
+        }
+        self.pending = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presenter() -> Presenter {
+        Presenter { window: 1, depth: 24, format: 2, pixmap: 3, picture: 4, serial: 7, pending: Some(Instant::now()) }
+    }
+
+    #[test]
+    fn test_frame_completion() {
+        let mut presenter = presenter();
+        assert!(presenter.is_pending(Instant::now()));
+        // Another window's frame, or an earlier one of ours
+        assert!(!presenter.complete(2, 7));
+        assert!(!presenter.complete(1, 6));
+        assert!(presenter.is_pending(Instant::now()));
+        assert!(presenter.complete(1, 7));
+        assert!(!presenter.is_pending(Instant::now()));
+    }
+
+    #[test]
+    fn test_lost_frame() {
+        let presenter = presenter();
+        let presented = presenter.pending.unwrap();
+        assert!(presenter.is_pending(presented + Duration::from_millis(16)));
+        assert!(!presenter.is_pending(presented + COMPLETE_TIMEOUT));
+    }
+}
@@ -0,0 +1,382 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ConfigureWindowAux, ConnectionExt, CreateGCAux, EventMask, Rectangle, StackMode, Window,
+};
+use tracing::debug;
+
+use crate::core::context::Context;
+use crate::window::frame::FramePart;
+use crate::window::menu::MenuKey;
+use crate::window::overlay::{self, Painter};
+
+/// Smallest client size interactive resizing goes down to on its own
+const MIN_WIDTH: u16 = 100;
+const MIN_HEIGHT: u16 = 50;
+
+// WM_NORMAL_HINTS flags
+const P_MIN_SIZE: u32 = 1 << 4;
+const P_MAX_SIZE: u32 = 1 << 5;
+const P_RESIZE_INC: u32 = 1 << 6;
+const P_ASPECT: u32 = 1 << 7;
+const P_BASE_SIZE: u32 = 1 << 8;
+const P_WIN_GRAVITY: u32 = 1 << 9;
+
+const POPUP_BACKGROUND: u32 = 0x2b2b2b;
+const POPUP_TEXT: u32 = 0xe0e0e0;
+const POPUP_PADDING: u16 = 8;
+/// Size of a character of the 10x20 font
+const CHAR_WIDTH: u16 = 10;
+const CHAR_HEIGHT: u16 = 20;
+
+/// WM_NORMAL_HINTS: the sizes a client can take
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeHints {
+    pub min_width: u16,
+    pub min_height: u16,
+    /// 0 when unbounded
+    pub max_width: u16,
+    pub max_height: u16,
+    pub base_width: u16,
+    pub base_height: u16,
+    pub width_inc: u16,
+    pub height_inc: u16,
+    /// Smallest and largest width to height ratio, as numerator and denominator
+    pub min_aspect: Option<(u32, u32)>,
+    pub max_aspect: Option<(u32, u32)>,
+    /// ICCCM win_gravity, NorthWest when unset
+    pub gravity: i32,
+}
+
+impl Default for SizeHints {
+    fn default() -> Self {
+        Self::from_property(&[])
+    }
+}
+
+impl SizeHints {
+    /// Parse a WM_NORMAL_HINTS value: flags, 4 unused fields, min, max,
+    /// increments, aspects, base size and gravity. Clients predating
+    /// ICCCM 1.0 leave out the last three fields.
+    pub fn from_property(data: &[u32]) -> Self {
+        let get = |index: usize| data.get(index).copied().unwrap_or(0);
+        let size = |index: usize| get(index).min(u16::MAX as u32) as u16;
+        let flags = get(0);
+        let mut hints = Self {
+            min_width: 0,
+            min_height: 0,
+            max_width: 0,
+            max_height: 0,
+            base_width: 0,
+            base_height: 0,
+            width_inc: 1,
+            height_inc: 1,
+            min_aspect: None,
+            max_aspect: None,
+            gravity: 1,
+        };
+        if flags & P_MIN_SIZE != 0 {
+            (hints.min_width, hints.min_height) = (size(5), size(6));
+        }
+        if flags & P_MAX_SIZE != 0 {
+            (hints.max_width, hints.max_height) = (size(7), size(8));
+        }
+        if flags & P_RESIZE_INC != 0 {
+            (hints.width_inc, hints.height_inc) = (size(9).max(1), size(10).max(1));
+        }
+        if flags & P_ASPECT != 0 {
+            let ratio = |x: u32, y: u32| (x > 0 && y > 0).then_some((x, y));
+            hints.min_aspect = ratio(get(11), get(12));
+            hints.max_aspect = ratio(get(13), get(14));
+        }
+        if flags & P_BASE_SIZE != 0 {
+            (hints.base_width, hints.base_height) = (size(15), size(16));
+        }
+        if flags & P_WIN_GRAVITY != 0 && data.len() >= 18 {
+            hints.gravity = get(17) as i32;
+        }
+        // ICCCM 4.1.2.3: base and minimum size stand in for each other
+        if flags & P_BASE_SIZE == 0 {
+            (hints.base_width, hints.base_height) = (hints.min_width, hints.min_height);
+        } else if flags & P_MIN_SIZE == 0 {
+            (hints.min_width, hints.min_height) = (hints.base_width, hints.base_height);
+        }
+        hints
+    }
+
+    /// Whether the minimum and maximum sizes pin the window to one size
+    pub fn is_fixed(&self) -> bool {
+        self.max_width != 0 && self.min_width == self.max_width && self.min_height == self.max_height
+    }
+
+    /// The size the client accepts nearest to `width` x `height`, rounding
+    /// down to whole increments
+    pub fn constrain(&self, width: u16, height: u16) -> (u16, u16) {
+        let clamp = |value: u32, min: u16, max: u16| {
+            let min = min.max(1) as u32;
+            let max = if max > 0 { (max as u32).max(min) } else { u16::MAX as u32 };
+            value.clamp(min, max)
+        };
+        let mut w = clamp(width as u32, self.min_width, self.max_width);
+        let mut h = clamp(height as u32, self.min_height, self.max_height);
+
+        // Aspect ratios apply to the size beyond the base size
+        let (base_w, base_h) = (self.base_width as u32, self.base_height as u32);
+        if let Some((x, y)) = self.min_aspect {
+            let (dw, dh) = (w.saturating_sub(base_w) as u64, h.saturating_sub(base_h) as u64);
+            if dw * (y as u64) < (x as u64) * dh {
+                // Too tall for its width
+                h = base_h + (dw * y as u64 / x as u64) as u32;
+            }
+        }
+        if let Some((x, y)) = self.max_aspect {
+            let (dw, dh) = (w.saturating_sub(base_w) as u64, h.saturating_sub(base_h) as u64);
+            if dw * (y as u64) > (x as u64) * dh {
+                // Too wide for its height
+                w = base_w + (dh * x as u64 / y as u64) as u32;
+            }
+        }
+
+        let round = |value: u32, base: u32, inc: u16| match value.checked_sub(base) {
+            Some(extra) => base + extra / inc as u32 * inc as u32,
+            None => value,
+        };
+        w = round(w, base_w, self.width_inc);
+        h = round(h, base_h, self.height_inc);
+
+        (clamp(w, self.min_width, self.max_width) as u16, clamp(h, self.min_height, self.max_height) as u16)
+    }
+
+    /// What the size popup shows: columns by rows for clients with resize
+    /// increments, such as terminals, pixels otherwise
+    pub fn label(&self, width: u16, height: u16) -> String {
+        if self.width_inc > 1 || self.height_inc > 1 {
+            let cols = width.saturating_sub(self.base_width) / self.width_inc;
+            let rows = height.saturating_sub(self.base_height) / self.height_inc;
+            format!("{} × {}", cols, rows)
+        } else {
+            format!("{} × {}", width, height)
+        }
+    }
+}
+
+/// Whether `part` of a frame resizes the window when dragged
+pub fn is_resize_edge(part: FramePart) -> bool {
+    matches!(
+        part,
+        FramePart::LeftBorder
+            | FramePart::RightBorder
+            | FramePart::TopBorder
+            | FramePart::BottomBorder
+            | FramePart::CornerTopLeft
+            | FramePart::CornerTopRight
+            | FramePart::CornerBottomLeft
+            | FramePart::CornerBottomRight
+    )
+}
+
+/// New geometry of a window resized by dragging `edge` by `dx`, `dy`.
+/// `start` holds the frame position and the client size when the drag
+/// began, and so does the result; the opposite edges stay put.
+pub fn resize_rect(edge: FramePart, start: Rectangle, dx: i16, dy: i16, hints: &SizeHints) -> Rectangle {
+    let left = matches!(edge, FramePart::LeftBorder | FramePart::CornerTopLeft | FramePart::CornerBottomLeft);
+    let right = matches!(edge, FramePart::RightBorder | FramePart::CornerTopRight | FramePart::CornerBottomRight);
+    let top = matches!(edge, FramePart::TopBorder | FramePart::CornerTopLeft | FramePart::CornerTopRight);
+    let bottom = matches!(edge, FramePart::BottomBorder | FramePart::CornerBottomLeft | FramePart::CornerBottomRight);
+
+    let mut width = start.width as i32;
+    let mut height = start.height as i32;
+    if left {
+        width -= dx as i32;
+    } else if right {
+        width += dx as i32;
+    }
+    if top {
+        height -= dy as i32;
+    } else if bottom {
+        height += dy as i32;
+    }
+    // Not smaller than the floor, unless the window already was
+    let width = width.clamp(MIN_WIDTH.min(start.width) as i32, u16::MAX as i32) as u16;
+    let height = height.clamp(MIN_HEIGHT.min(start.height) as i32, u16::MAX as i32) as u16;
+    let (width, height) = hints.constrain(width, height);
+
+    let x = if left { start.x + (start.width as i16 - width as i16) } else { start.x };
+    let y = if top { start.y + (start.height as i16 - height as i16) } else { start.y };
+    Rectangle { x, y, width, height }
+}
+
+/// `_NET_WM_MOVERESIZE` direction that ends the move or resize under way
+pub const MOVERESIZE_CANCEL: u32 = 11;
+
+/// The frame part a `_NET_WM_MOVERESIZE` direction drags, the titlebar
+/// for a move, and whether it is done with the keyboard
+pub fn moveresize_part(direction: u32) -> Option<(FramePart, bool)> {
+    let part = match direction {
+        0 => FramePart::CornerTopLeft,
+        1 => FramePart::TopBorder,
+        2 => FramePart::CornerTopRight,
+        3 => FramePart::RightBorder,
+        4 => FramePart::CornerBottomRight,
+        5 => FramePart::BottomBorder,
+        6 => FramePart::CornerBottomLeft,
+        7 => FramePart::LeftBorder,
+        8 => FramePart::TitleBar,
+        9 => return Some((FramePart::CornerBottomRight, true)),
+        10 => return Some((FramePart::TitleBar, true)),
+        _ => return None,
+    };
+    Some((part, false))
+}
+
+/// How far an arrow key takes the pointer when moving or resizing with
+/// the keyboard: `step` pixels, or one for a fine step
+pub fn arrow_delta(key: MenuKey, step: u16, fine: bool) -> Option<(i16, i16)> {
+    let step = if fine { 1 } else { step.min(i16::MAX as u16) as i16 };
+    match key {
+        MenuKey::Left => Some((-step, 0)),
+        MenuKey::Right => Some((step, 0)),
+        MenuKey::Up => Some((0, -step)),
+        MenuKey::Down => Some((0, step)),
+        MenuKey::Enter | MenuKey::Escape => None,
+    }
+}
+
+/// The size readout in the middle of a window while it is resized
+#[derive(Debug)]
+pub struct SizePopup {
+    window: Window,
+    text: String,
+}
+
+impl SizePopup {
+    pub fn new(ctx: &Context) -> Result<Self> {
+        let window = overlay::create(ctx, Rectangle { x: 0, y: 0, width: 1, height: 1 }, POPUP_BACKGROUND, EventMask::EXPOSURE)?;
+        Ok(Self { window, text: String::new() })
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    /// Show `text` centered over `frame`, in root coordinates
+    pub fn update(&mut self, ctx: &Context, text: &str, frame: Rectangle) -> Result<()> {
+        let width = text.chars().count() as u16 * CHAR_WIDTH + 2 * POPUP_PADDING;
+        let height = CHAR_HEIGHT + 2 * POPUP_PADDING;
+        let x = frame.x as i32 + (frame.width as i32 - width as i32) / 2;
+        let y = frame.y as i32 + (frame.height as i32 - height as i32) / 2;
+        ctx.conn.configure_window(
+            self.window,
+            &ConfigureWindowAux::new().x(x).y(y).width(width as u32).height(height as u32).stack_mode(StackMode::ABOVE),
+        )?;
+        if self.text.is_empty() {
+            ctx.conn.map_window(self.window)?;
+        }
+        if self.text != text {
+            self.text = text.to_string();
+            ctx.conn.clear_area(false, self.window, 0, 0, 0, 0)?;
+        }
+        self.draw(ctx)
+    }
+
+    pub fn draw(&self, ctx: &Context) -> Result<()> {
+        let painter = Painter::new(ctx, self.window, CreateGCAux::new().foreground(POPUP_TEXT).background(POPUP_BACKGROUND))?;
+        let gc = painter.gc();
+        // Core fonts are Latin-1, which has the × sign
+        let text: Vec<u8> = self.text.chars().map(|c| u8::try_from(c as u32).unwrap_or(b'?')).collect();
+        if let Err(e) = ctx.conn.image_text8(self.window, gc, POPUP_PADDING as i16, (POPUP_PADDING + CHAR_HEIGHT) as i16 - 5, &text) {
+            debug!("Failed to draw size popup: {}", e);
+        }        Ok(())
+    }
+
+    /// Destroy the popup
+    pub fn hide<C: Connection>(mut self, conn: &C) {
+        overlay::destroy(conn, &mut self.window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// WM_NORMAL_HINTS of an xterm: 6x13 cells around a 4 px border,
+    /// at least one cell
+    fn terminal() -> SizeHints {
+        let mut data = [0u32; 18];
+        data[0] = P_MIN_SIZE | P_RESIZE_INC | P_BASE_SIZE | P_WIN_GRAVITY;
+        (data[5], data[6]) = (10, 17);
+        (data[9], data[10]) = (6, 13);
+        (data[15], data[16]) = (4, 4);
+        data[17] = 1;
+        SizeHints::from_property(&data)
+    }
+
+    #[test]
+    fn test_size_hints() {
+        let hints = terminal();
+        assert_eq!((hints.width_inc, hints.height_inc), (6, 13));
+        assert_eq!(hints.constrain(500, 300), (496, 290));
+        assert_eq!(hints.constrain(1, 1), (10, 17));
+        assert_eq!(hints.label(496, 290), "82 × 22");
+
+        // Gravity is the 18th field, flagged by bit 9
+        let mut data = [0u32; 18];
+        data[0] = P_BASE_SIZE;
+        data[17] = 5;
+        assert_eq!(SizeHints::from_property(&data).gravity, 1);
+        data[0] |= P_WIN_GRAVITY;
+        assert_eq!(SizeHints::from_property(&data).gravity, 5);
+
+        // A fixed size dialog
+        let mut data = [0u32; 15];
+        data[0] = P_MIN_SIZE | P_MAX_SIZE;
+        (data[5], data[6], data[7], data[8]) = (300, 200, 300, 200);
+        let fixed = SizeHints::from_property(&data);
+        assert_eq!(fixed.constrain(640, 480), (300, 200));
+        assert_eq!(fixed.label(300, 200), "300 × 200");
+        assert!(fixed.is_fixed());
+        assert!(!hints.is_fixed());
+
+        // Square video: width and height follow each other
+        let mut data = [0u32; 15];
+        data[0] = P_ASPECT;
+        (data[11], data[12], data[13], data[14]) = (1, 1, 1, 1);
+        let square = SizeHints::from_property(&data);
+        assert_eq!(square.constrain(400, 300), (300, 300));
+        assert_eq!(square.constrain(300, 400), (300, 300));
+    }
+
+    #[test]
+    fn test_resize_rect() {
+        let start = Rectangle { x: 100, y: 100, width: 400, height: 300 };
+        let hints = SizeHints::default();
+        // The left edge moves, the right one stays at 500
+        assert_eq!(resize_rect(FramePart::LeftBorder, start, -50, 20, &hints), Rectangle { x: 50, y: 100, width: 450, height: 300 });
+        assert_eq!(resize_rect(FramePart::CornerBottomRight, start, 20, 30, &hints), Rectangle { x: 100, y: 100, width: 420, height: 330 });
+        assert_eq!(resize_rect(FramePart::CornerTopLeft, start, 1000, 1000, &hints), Rectangle { x: 400, y: 350, width: 100, height: 50 });
+
+        // Increments round the size, and the top edge lands where the
+        // rounded height puts it
+        let hints = terminal();
+        let start = Rectangle { x: 0, y: 100, width: 490, height: 290 };
+        assert_eq!(resize_rect(FramePart::TopBorder, start, 0, -20, &hints), Rectangle { x: 0, y: 87, width: 490, height: 303 });
+    }
+
+    #[test]
+    fn test_moveresize_part() {
+        assert_eq!(moveresize_part(0), Some((FramePart::CornerTopLeft, false)));
+        assert_eq!(moveresize_part(7), Some((FramePart::LeftBorder, false)));
+        assert_eq!(moveresize_part(8), Some((FramePart::TitleBar, false)));
+        assert_eq!(moveresize_part(9), Some((FramePart::CornerBottomRight, true)));
+        assert_eq!(moveresize_part(MOVERESIZE_CANCEL), None);
+    }
+
+    #[test]
+    fn test_arrow_delta() {
+        assert_eq!(arrow_delta(MenuKey::Left, 10, false), Some((-10, 0)));
+        assert_eq!(arrow_delta(MenuKey::Down, 10, false), Some((0, 10)));
+        // Shift steps a pixel at a time
+        assert_eq!(arrow_delta(MenuKey::Up, 10, true), Some((0, -1)));
+        assert_eq!(arrow_delta(MenuKey::Enter, 10, false), None);
+    }
+}
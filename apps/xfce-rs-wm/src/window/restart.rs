@@ -0,0 +1,164 @@
+use std::convert::Infallible;
+use std::os::unix::process::CommandExt;
+use std::process::Stdio;
+
+use anyhow::Result;
+use tracing::{info, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Atom, AtomEnum, ClientMessageEvent, CloseDown, ConnectionExt, EventMask, Window};
+use x11rb::wrapper::ConnectionExt as _;
+
+/// Client message asking the window manager to restart in place, sent to
+/// the root window
+pub const RESTART_MESSAGE: &str = "_XFWM4_RS_RESTART";
+
+/// Property on each frame naming the client window in it, so an instance
+/// taking over after a restart finds the frames it can keep
+pub const FRAME_PROPERTY: &str = "_XFWM4_RS_FRAME";
+
+/// Ask the running window manager to restart in place, from outside its
+/// event loop
+pub fn request_restart() -> Result<()> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atom = conn.intern_atom(false, RESTART_MESSAGE.as_bytes())?.reply()?.atom;
+    let event = ClientMessageEvent::new(32, root, atom, [0, 0, 0, 0, 0]);
+    conn.send_event(false, root, EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY, event)?;
+    conn.flush()?;
+    Ok(())
+}
+
+/// Turn SIGUSR1 into a restart request; needs the Tokio runtime
+pub fn watch_restart_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = signal(SignalKind::user_defined1())?;
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            info!("SIGUSR1: restarting in place");
+            if let Err(e) = request_restart() {
+                warn!("Failed to request a restart: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The command line for the restarted instance: the same one, taking over
+/// with `--restart` rather than `--replace`
+pub fn restart_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    args.into_iter()
+        .filter(|arg| arg != "--restart" && arg != "--replace")
+        .chain(std::iter::once("--restart".to_string()))
+        .collect()
+}
+
+/// Run the window manager again in this process; only returns when that
+/// fails. The server keeps what `frames` made when it closes on the way,
+/// for the new instance to take the windows over in; everything else goes
+/// with its connection as usual.
+pub fn exec_self(frames: &impl Connection) -> Result<Infallible> {
+    let program = std::env::current_exe()?;
+    // A broken build left in place would take the frames and leave nobody
+    // to manage them
+    if !starts(&program) {
+        return Err(anyhow::anyhow!("Not restarting: {} doesn't run", program.display()));
+    }
+    let args = restart_args(std::env::args().skip(1));
+    frames.set_close_down_mode(CloseDown::RETAIN_PERMANENT)?;
+    frames.sync()?;
+    let e = std::process::Command::new(&program).args(&args).exec();
+    // Still here: frames go with the connection again
+    frames.set_close_down_mode(CloseDown::DESTROY_ALL)?;
+    frames.flush()?;
+    Err(anyhow::anyhow!("Failed to run {} again: {}", program.display(), e))
+}
+
+/// Whether `program` runs at all, asked for its version
+fn starts(program: &std::path::Path) -> bool {
+    std::process::Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The client window in `frame`, if it is a frame left by an instance that
+/// restarted and still holds the client
+pub fn adoptable_client(conn: &impl Connection, frame: Window, frame_property: Atom) -> Option<Window> {
+    let client = frame_client(conn, frame, frame_property)?;
+    let tree = conn.query_tree(frame).ok()?.reply().ok()?;
+    tree.children.contains(&client).then_some(client)
+}
+
+/// The client `frame` was made for, whether or not still in it, if it is
+/// one of our frames
+pub fn frame_client(conn: &impl Connection, frame: Window, frame_property: Atom) -> Option<Window> {
+    let property = conn.get_property(false, frame, frame_property, AtomEnum::WINDOW, 0, 1).ok()?.reply().ok()?;
+    property.value32().and_then(|mut values| values.next())
+}
+
+/// Whether `window` and `other` were made by the same X client, by the
+/// resource id base in their upper bits
+pub fn same_owner(window: Window, other: Window, resource_id_mask: u32) -> bool {
+    window & !resource_id_mask == other & !resource_id_mask
+}
+
+/// Put the windows in the frames an instance restarting in place kept back
+/// on the root window, where they were, and free what it kept; for when
+/// the new instance can't take them over after all
+pub fn release_kept_frames() -> Result<()> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let mask = conn.setup().resource_id_mask;
+    let frame_property = conn.intern_atom(false, FRAME_PROPERTY.as_bytes())?.reply()?.atom;
+    let mut owners: Vec<Window> = Vec::new();
+    for frame in conn.query_tree(root)?.reply()?.children {
+        if frame_client(&conn, frame, frame_property).is_none() {
+            continue;
+        }
+        if let Some(client) = adoptable_client(&conn, frame, frame_property) {
+            let at = conn.get_geometry(frame)?.reply()?;
+            let inside = conn.get_geometry(client)?.reply()?;
+            conn.reparent_window(client, root, at.x + inside.x, at.y + inside.y)?;
+        }
+        if !owners.iter().any(|&owner| same_owner(owner, frame, mask)) {
+            owners.push(frame);
+        }
+    }
+    for owner in owners {
+        conn.kill_client(owner)?;
+    }
+    conn.sync()?;
+    info!("Released the frames kept from before the restart");
+    Ok(())
+}
+
+/// The workspace on screen before the restart, which the new instance
+/// goes on with
+pub fn current_desktop(conn: &impl Connection, root: Window, current_desktop: Atom) -> Option<u32> {
+    let property = conn.get_property(false, root, current_desktop, AtomEnum::CARDINAL, 0, 1).ok()?.reply().ok()?;
+    property.value32().and_then(|mut values| values.next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_args() {
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(restart_args(args(&["--replace", "--sm-client-id", "2a"])), args(&["--sm-client-id", "2a", "--restart"]));
+        // Restarting again doesn't pile them up
+        assert_eq!(restart_args(args(&["--restart"])), args(&["--restart"]));
+    }
+
+    #[test]
+    fn test_same_owner() {
+        let mask = 0x001f_ffff;
+        assert!(same_owner(0x0060_0003, 0x0060_1a00, mask));
+        assert!(!same_owner(0x0060_0003, 0x0080_0003, mask));
+    }
+}
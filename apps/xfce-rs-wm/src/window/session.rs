@@ -1,8 +1,172 @@
 use zbus::{proxy, Connection};
 use anyhow::Result;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use futures_util::StreamExt;
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{ClientMessageEvent, ConnectionExt as _, EventMask};
+
+/// Client message asking the window manager to write its session state,
+/// sent to the root window
+pub const SAVE_SESSION_MESSAGE: &str = "_XFWM4_RS_SAVE_SESSION";
+
+/// How long the end of the session waits for the state to be written
+const SAVE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A window's place when the session ended, found again at the next login
+/// by its WM_CLASS and WM_WINDOW_ROLE
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedWindow {
+    /// Instance and class, e.g. "xfce4-terminal.Xfce4-terminal"
+    pub class: String,
+    pub role: String,
+    /// Frame position and client size, from before maximizing
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub workspace: u32,
+    pub maximized: bool,
+    pub minimized: bool,
+    pub shaded: bool,
+    pub above: bool,
+    pub below: bool,
+}
+
+impl SavedWindow {
+    /// One line of the state file: tab separated fields, the states as
+    /// letters
+    fn to_line(&self) -> String {
+        let clean = |s: &str| s.replace(['\t', '\n'], " ");
+        let states: String = [(self.maximized, 'M'), (self.minimized, 'm'), (self.shaded, 's'), (self.above, 'a'), (self.below, 'b')]
+            .iter()
+            .filter_map(|&(on, letter)| on.then_some(letter))
+            .collect();
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", clean(&self.class), clean(&self.role), self.x, self.y, self.width, self.height, self.workspace, states)
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [class, role, x, y, width, height, workspace, states] = fields[..] else { return None };
+        Some(Self {
+            class: class.to_string(),
+            role: role.to_string(),
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+            workspace: workspace.parse().ok()?,
+            maximized: states.contains('M'),
+            minimized: states.contains('m'),
+            shaded: states.contains('s'),
+            above: states.contains('a'),
+            below: states.contains('b'),
+        })
+    }
+}
+
+/// The windows saved when the last session ended, each given back once
+#[derive(Debug, Default)]
+pub struct SessionState {
+    windows: Vec<SavedWindow>,
+}
+
+impl SessionState {
+    pub fn parse(text: &str) -> Self {
+        Self { windows: text.lines().filter_map(SavedWindow::from_line).collect() }
+    }
+
+    /// The state in `path`; none when there is no such file, as in a
+    /// first session
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let state = Self::parse(&text);
+                info!("Restoring {} window(s) from {}", state.windows.len(), path.display());
+                state
+            }
+            Err(e) => {
+                debug!("No session state in {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Write `windows` to `path`, replacing it whole
+    pub fn save(path: &Path, windows: &[SavedWindow]) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text: String = windows.iter().map(|w| w.to_line() + "\n").collect();
+        let partial = path.with_extension("tmp");
+        std::fs::write(&partial, text)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Take the saved place of a window of `class` and `role`; windows
+    /// alike get the places in the order they were saved
+    pub fn take(&mut self, class: &str, role: &str) -> Option<SavedWindow> {
+        let index = self.windows.iter().position(|w| w.class == class && w.role == role)?;
+        Some(self.windows.remove(index))
+    }
+}
+
+/// "instance.class" from a WM_CLASS value, two NUL terminated strings
+pub fn class_key(value: &[u8]) -> Option<String> {
+    let mut parts = value.split(|&b| b == 0).filter(|part| !part.is_empty());
+    let instance = String::from_utf8_lossy(parts.next()?);
+    let class = parts.next().map(String::from_utf8_lossy).unwrap_or_default();
+    Some(format!("{}.{}", instance, class))
+}
+
+/// The file windows are saved in for session client `client_id`, next to
+/// xfwm4's own
+pub fn state_path(client_id: &str) -> PathBuf {
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    cache.join("sessions").join(format!("xfwm4-rs-{}.state", client_id))
+}
+
+/// The session client ID: the one the session manager restarted us with,
+/// or a new one it will restart us with next time
+pub fn client_id(sm_client_id: Option<&str>) -> String {
+    match sm_client_id {
+        Some(id) => id.to_string(),
+        None => {
+            let secs = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            format!("{}-{}", secs, std::process::id())
+        }
+    }
+}
+
+/// Ask the window manager to save its windows into `path`, from outside
+/// its X event loop, and wait for the file to be written
+async fn save_windows(path: &Path) -> Result<()> {
+    let before = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atom = conn.intern_atom(false, SAVE_SESSION_MESSAGE.as_bytes())?.reply()?.atom;
+    let event = ClientMessageEvent::new(32, root, atom, [0, 0, 0, 0, 0]);
+    conn.send_event(false, root, EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY, event)?;
+    conn.flush()?;
+
+    let started = SystemTime::now();
+    loop {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified != before {
+            return Ok(());
+        }
+        if started.elapsed().unwrap_or_default() > SAVE_TIMEOUT {
+            anyhow::bail!("Timed out waiting for {}", path.display());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
 
 #[proxy(
     interface = "org.xfce.Session.Manager",
@@ -36,7 +200,9 @@ impl SessionManager {
         Ok(Self { client_path: None })
     }
 
-    pub async fn register(&mut self, sm_client_id: Option<&str>) -> Result<()> {
+    /// Register with the session manager, to be restarted as `client_id`
+    /// and save the windows when the session ends
+    pub async fn register(&mut self, sm_client_id: Option<&str>, client_id: &str) -> Result<()> {
         let conn = Connection::session().await?;
         let proxy = SessionProxy::new(&conn).await?;
         
@@ -53,6 +219,7 @@ impl SessionManager {
                 
                 let path_clone = path.clone();
                 let conn_clone = conn.clone();
+                let client_id = client_id.to_string();
                 
                 // Spawn signal listener
                 tokio::spawn(async move {
@@ -64,7 +231,7 @@ impl SessionManager {
                         // Set initial properties
                         let mut props = HashMap::new();
                         props.insert("SmProgram", zbus::zvariant::Value::from("xfwm4-rs"));
-                        let restart_cmd = vec!["xfwm4-rs", "--replace"];
+                        let restart_cmd = vec!["xfwm4-rs", "--replace", "--sm-client-id", client_id.as_str()];
                         props.insert("SmRestartCommand", zbus::zvariant::Value::from(restart_cmd));
                         
                         let _ = client_proxy.set_sm_properties(props).await;
@@ -84,6 +251,9 @@ impl SessionManager {
                                 Some(sig) = query_end.next() => {
                                     if let Ok(args) = sig.args() {
                                         info!("Received QueryEndSession: flags={}", args.flags);
+                                        if let Err(e) = save_windows(&state_path(&client_id)).await {
+                                            warn!("Failed to save the windows for the next session: {}", e);
+                                        }
                                         // Respond OK
                                         let _ = client_proxy.end_session_response(true, "").await;
                                     }
@@ -109,3 +279,53 @@ impl SessionManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_file_round_trip() {
+        let terminal = SavedWindow {
+            class: "xfce4-terminal.Xfce4-terminal".to_string(),
+            role: "xfce4-terminal-1".to_string(),
+            x: -20,
+            y: 40,
+            width: 640,
+            height: 480,
+            workspace: 2,
+            maximized: true,
+            minimized: false,
+            shaded: true,
+            above: true,
+            below: false,
+        };
+        assert_eq!(terminal.to_line(), "xfce4-terminal.Xfce4-terminal\txfce4-terminal-1\t-20\t40\t640\t480\t2\tMsa");
+        // On every workspace, and kept below
+        let editor = SavedWindow {
+            class: "mousepad.Mousepad".to_string(),
+            role: String::new(),
+            workspace: 0xFFFFFFFF,
+            maximized: false,
+            shaded: false,
+            above: false,
+            below: true,
+            ..terminal.clone()
+        };
+
+        let text: String = [&terminal, &editor].iter().map(|w| w.to_line() + "\n").collect();
+        let mut state = SessionState::parse(&(text + "garbage\n"));
+        assert_eq!(state.windows.len(), 2);
+        assert_eq!(state.take("mousepad.Mousepad", ""), Some(editor));
+        assert_eq!(state.take("mousepad.Mousepad", ""), None);
+        assert_eq!(state.take("xfce4-terminal.Xfce4-terminal", "xfce4-terminal-1"), Some(terminal));
+    }
+
+    #[test]
+    fn test_class_key() {
+        assert_eq!(class_key(b"xterm\0XTerm\0").as_deref(), Some("xterm.XTerm"));
+        assert_eq!(class_key(b"xterm").as_deref(), Some("xterm."));
+        assert_eq!(class_key(b""), None);
+        assert!(state_path("42").ends_with("sessions/xfwm4-rs-42.state"));
+    }
+}
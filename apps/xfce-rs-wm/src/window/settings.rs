@@ -1,17 +1,172 @@
-use zbus::Connection;
+use zbus::{Connection, MatchRule, MessageStream};
+use zbus::zvariant::{Array, OwnedValue, Value};
 use anyhow::Result;
+use futures_util::StreamExt;
 use tracing::{debug, warn};
 use std::collections::HashMap;
+use xfce_rs_config::XfceConfig;
+
+/// Channel of the XFCE.rs configuration the XSETTINGS manager publishes
+const XSETTINGS_CHANNEL: &str = "xsettings";
 
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub double_click_action: String,
+    /// XCursor theme from `/Gtk/CursorThemeName` in the xsettings channel;
+    /// `None` leaves the choice to the X resources
+    pub cursor_theme: Option<String>,
+    /// `/Gtk/CursorThemeSize`, likewise
+    pub cursor_size: Option<u32>,
+    /// xfwm4 theme for the window frames, `/general/theme`
+    pub theme: String,
+    /// Pango description of the title font, e.g. "Sans Bold 9"
+    pub title_font: Option<String>,
+    /// Overrides the theme's `button_layout`, e.g. "O|HMC"
+    pub button_layout: Option<String>,
+    /// Overrides the theme's `title_alignment`: left, center or right
+    pub title_alignment: Option<String>,
+    /// Window manager shortcuts from the xfce4-keyboard-shortcuts channel,
+    /// chord (`<Alt>F4`) to action name (`close_window_key`); empty means
+    /// the built-in defaults
+    pub shortcuts: HashMap<String, String>,
+    /// `/general/workspace_count`
+    pub workspace_count: u32,
+    /// `/general/workspace_names`, possibly fewer than there are workspaces
+    pub workspace_names: Vec<String>,
+    /// `/general/show_frame_shadow`: drop shadows under normal windows
+    pub show_frame_shadow: bool,
+    /// `/general/shadow_opacity`, in percent
+    pub shadow_opacity: u32,
+    /// `/general/use_window_opacity`: honor `_NET_WM_WINDOW_OPACITY`
+    pub use_window_opacity: bool,
+    /// `/general/inactive_opacity`, in percent, of windows without the
+    /// focus
+    pub inactive_opacity: u32,
+    /// `/general/opacity_rules/<name>`: opacity in percent of windows
+    /// whose WM_CLASS instance or class is `name`
+    pub opacity_rules: HashMap<String, u32>,
+    /// `/general/fade_windows`: fade windows in and out as they appear,
+    /// minimize and change workspace
+    pub fade_windows: bool,
+    /// `/general/fade_duration`, in milliseconds
+    pub fade_duration: u32,
+    /// `/general/vblank_mode`: "off" paints as soon as anything changes,
+    /// e.g. in virtual machines without vertical blanks; anything else
+    /// syncs to the monitor
+    pub vblank_mode: String,
+    /// `/general/tiling_layout`: lay windows out master/stack on every
+    /// workspace from the start, rather than once toggled
+    pub tiling_layout: bool,
+    /// `/general/tiling_master_ratio`: percent of the width the master
+    /// window gets
+    pub tiling_master_ratio: u32,
+    /// `/general/click_to_focus`: off, focus follows the pointer
+    pub click_to_focus: bool,
+    /// `/general/focus_delay`, in milliseconds, before focus follows the
+    /// pointer
+    pub focus_delay: u32,
+    /// `/general/raise_on_focus`: raise windows focus follows the pointer to
+    pub raise_on_focus: bool,
+    /// `/general/raise_on_click`: raise windows clicked into
+    pub raise_on_click: bool,
+    /// `/general/focus_new`: focus windows as they appear
+    pub focus_new: bool,
+    /// `/general/prevent_focus_stealing`: compare `_NET_WM_USER_TIME`
+    /// before new windows and activation requests take the focus
+    pub prevent_focus_stealing: bool,
+    /// `/general/snap_to_border`: snap moved windows to the work area's
+    /// edges
+    pub snap_to_border: bool,
+    /// `/general/snap_to_windows`: snap moved windows to other windows
+    pub snap_to_windows: bool,
+    /// `/general/snap_width`, in pixels
+    pub snap_width: u32,
+    /// `/general/snap_resist`: resist edges being crossed instead of
+    /// snapping to them
+    pub snap_resist: bool,
+    /// `/general/edge_actions/<edge>`: what resting the pointer on a
+    /// screen corner or side does, by edge name (`top_left`, `bottom`...)
+    pub edge_actions: HashMap<String, String>,
+    /// `/general/edge_dwell`, in milliseconds, before an edge action runs
+    pub edge_dwell: u32,
+    /// `/general/wrap_windows`: dragging a window against the left or
+    /// right edge takes it to the workspace there
+    pub wrap_windows: bool,
+    /// `/general/wrap_delay`, in milliseconds, before it does
+    pub wrap_delay: u32,
+    /// `/general/raise_on_dnd`: raise the window a drag-and-drop rests
+    /// over
+    pub raise_on_dnd: bool,
+    /// `/general/dnd_raise_delay`, in milliseconds, before it does
+    pub dnd_raise_delay: u32,
+    /// `/general/keyboard_step`: pixels an arrow key moves or resizes a
+    /// window by in keyboard move and resize
+    pub keyboard_step: u32,
+    /// `/general/blank_time`, in minutes of no input before the screen
+    /// blanks, 0 for never
+    pub blank_time: u32,
+    /// `/general/dpms_enabled`: power the display down when idle
+    pub dpms_enabled: bool,
+    /// `/general/dpms_suspend_time`, in minutes before the display is
+    /// suspended, 0 for never
+    pub dpms_suspend_time: u32,
+    /// `/general/dpms_off_time`, in minutes before it is turned off
+    pub dpms_off_time: u32,
+    /// `/general/zoom_desktop`: Super+scroll magnifies the screen
+    pub zoom_desktop: bool,
+    /// `/general/zoom_max_factor`: how far it magnifies at most
+    pub zoom_max_factor: u32,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             double_click_action: "maximize".to_string(),
+            cursor_theme: None,
+            cursor_size: None,
+            theme: "Default".to_string(),
+            title_font: None,
+            button_layout: None,
+            title_alignment: None,
+            shortcuts: HashMap::new(),
+            workspace_count: 4,
+            workspace_names: Vec::new(),
+            show_frame_shadow: true,
+            shadow_opacity: 50,
+            use_window_opacity: true,
+            inactive_opacity: 100,
+            opacity_rules: HashMap::new(),
+            fade_windows: true,
+            fade_duration: 150,
+            vblank_mode: "auto".to_string(),
+            tiling_layout: false,
+            tiling_master_ratio: 55,
+            click_to_focus: true,
+            focus_delay: 250,
+            raise_on_focus: false,
+            raise_on_click: true,
+            focus_new: true,
+            prevent_focus_stealing: true,
+            snap_to_border: true,
+            snap_to_windows: false,
+            snap_width: 10,
+            snap_resist: false,
+            edge_actions: HashMap::from([
+                ("top_left".to_string(), "overview".to_string()),
+                ("bottom".to_string(), "reveal_panel".to_string()),
+            ]),
+            edge_dwell: 150,
+            wrap_windows: true,
+            wrap_delay: 500,
+            raise_on_dnd: true,
+            dnd_raise_delay: 600,
+            keyboard_step: 10,
+            blank_time: 10,
+            dpms_enabled: true,
+            dpms_suspend_time: 15,
+            dpms_off_time: 20,
+            zoom_desktop: true,
+            zoom_max_factor: 8,
         }
     }
 }
@@ -30,6 +185,7 @@ impl SettingsManager {
         if let Err(e) = manager.load_xfconf().await {
             warn!("Failed to load Xfconf settings, using defaults: {}", e);
         }
+        manager.load_cursor(&XfceConfig::default()).await;
         
         Ok(manager)
     }
@@ -37,17 +193,7 @@ impl SettingsManager {
     async fn load_xfconf(&mut self) -> Result<()> {
         let conn = Connection::session().await?;
         
-        // org.xfce.Xfconf /org/xfce/Xfconf org.xfce.Xfconf
-        // Method: GetProperties(s channel, s property_base) -> a{sv}
-        
-        let reply: HashMap<String, zbus::zvariant::OwnedValue> = conn.call_method(
-            Some("org.xfce.Xfconf"),
-            "/org/xfce/Xfconf",
-            Some("org.xfce.Xfconf"),
-            "GetAllProperties",
-            &("xfwm4", "/"),
-        ).await?.body().deserialize()?;
-
+        let reply = get_all_properties(&conn, "xfwm4", "/").await?;
         debug!("Loaded {} properties from Xfconf", reply.len());
 
         if let Some(val) = reply.get("/general/double_click_action") {
@@ -55,7 +201,237 @@ impl SettingsManager {
                 self.current.double_click_action = s.to_string();
             }
         }
-        
+        let string = |key: &str| {
+            reply.get(key).and_then(|val| val.downcast_ref::<&str>().ok()).map(str::to_string).filter(|s| !s.is_empty())
+        };
+        if let Some(theme) = string("/general/theme") {
+            self.current.theme = theme;
+        }
+        self.current.title_font = string("/general/title_font");
+        self.current.button_layout = string("/general/button_layout");
+        self.current.title_alignment = string("/general/title_alignment");
+        if let Some(count) = reply.get("/general/workspace_count").and_then(workspace_count) {
+            self.current.workspace_count = count;
+        }
+        if let Some(names) = reply.get("/general/workspace_names").and_then(string_list) {
+            self.current.workspace_names = names;
+        }
+        let boolean = |key: &str| reply.get(key).and_then(|val| val.downcast_ref::<bool>().ok());
+        let number = |key: &str| reply.get(key).and_then(|val| val.downcast_ref::<i32>().ok()).and_then(|n| u32::try_from(n).ok());
+        if let Some(show) = boolean("/general/show_frame_shadow") {
+            self.current.show_frame_shadow = show;
+        }
+        if let Some(opacity) = number("/general/shadow_opacity") {
+            self.current.shadow_opacity = opacity.min(100);
+        }
+        if let Some(honor) = boolean("/general/use_window_opacity") {
+            self.current.use_window_opacity = honor;
+        }
+        if let Some(opacity) = number("/general/inactive_opacity") {
+            self.current.inactive_opacity = opacity.clamp(10, 100);
+        }
+        for (property, val) in &reply {
+            let Some(name) = property.strip_prefix("/general/opacity_rules/") else {
+                continue;
+            };
+            if let Some(opacity) = val.downcast_ref::<i32>().ok().and_then(|n| u32::try_from(n).ok()) {
+                self.current.opacity_rules.insert(name.to_string(), opacity.clamp(10, 100));
+            }
+        }
+        if let Some(fade) = boolean("/general/fade_windows") {
+            self.current.fade_windows = fade;
+        }
+        if let Some(duration) = number("/general/fade_duration") {
+            self.current.fade_duration = duration;
+        }
+        if let Some(mode) = string("/general/vblank_mode") {
+            self.current.vblank_mode = mode;
+        }
+        if let Some(tiling) = boolean("/general/tiling_layout") {
+            self.current.tiling_layout = tiling;
+        }
+        if let Some(ratio) = number("/general/tiling_master_ratio") {
+            self.current.tiling_master_ratio = ratio.clamp(10, 90);
+        }
+        if let Some(click) = boolean("/general/click_to_focus") {
+            self.current.click_to_focus = click;
+        }
+        if let Some(delay) = number("/general/focus_delay") {
+            self.current.focus_delay = delay.min(2000);
+        }
+        if let Some(raise) = boolean("/general/raise_on_focus") {
+            self.current.raise_on_focus = raise;
+        }
+        if let Some(raise) = boolean("/general/raise_on_click") {
+            self.current.raise_on_click = raise;
+        }
+        if let Some(focus) = boolean("/general/focus_new") {
+            self.current.focus_new = focus;
+        }
+        if let Some(prevent) = boolean("/general/prevent_focus_stealing") {
+            self.current.prevent_focus_stealing = prevent;
+        }
+        if let Some(snap) = boolean("/general/snap_to_border") {
+            self.current.snap_to_border = snap;
+        }
+        if let Some(snap) = boolean("/general/snap_to_windows") {
+            self.current.snap_to_windows = snap;
+        }
+        if let Some(width) = number("/general/snap_width") {
+            self.current.snap_width = width.min(100);
+        }
+        if let Some(resist) = boolean("/general/snap_resist") {
+            self.current.snap_resist = resist;
+        }
+        for (property, val) in &reply {
+            let Some(edge) = property.strip_prefix("/general/edge_actions/") else {
+                continue;
+            };
+            if let Ok(action) = val.downcast_ref::<&str>() {
+                self.current.edge_actions.insert(edge.to_string(), action.to_string());
+            }
+        }
+        if let Some(dwell) = number("/general/edge_dwell") {
+            self.current.edge_dwell = dwell.min(2000);
+        }
+        if let Some(wrap) = boolean("/general/wrap_windows") {
+            self.current.wrap_windows = wrap;
+        }
+        if let Some(delay) = number("/general/wrap_delay") {
+            self.current.wrap_delay = delay.min(2000);
+        }
+        if let Some(raise) = boolean("/general/raise_on_dnd") {
+            self.current.raise_on_dnd = raise;
+        }
+        if let Some(delay) = number("/general/dnd_raise_delay") {
+            self.current.dnd_raise_delay = delay.min(2000);
+        }
+        if let Some(step) = number("/general/keyboard_step") {
+            self.current.keyboard_step = step.clamp(1, 200);
+        }
+        if let Some(minutes) = number("/general/blank_time") {
+            self.current.blank_time = minutes;
+        }
+        if let Some(enabled) = boolean("/general/dpms_enabled") {
+            self.current.dpms_enabled = enabled;
+        }
+        if let Some(minutes) = number("/general/dpms_suspend_time") {
+            self.current.dpms_suspend_time = minutes;
+        }
+        if let Some(minutes) = number("/general/dpms_off_time") {
+            self.current.dpms_off_time = minutes;
+        }
+        if let Some(zoom) = boolean("/general/zoom_desktop") {
+            self.current.zoom_desktop = zoom;
+        }
+        if let Some(factor) = number("/general/zoom_max_factor") {
+            self.current.zoom_max_factor = factor.clamp(2, 32);
+        }
+
+        // Shortcuts are edited by the keyboard settings dialog, under
+        // /xfwm4/custom/<chord> = action
+        match get_all_properties(&conn, "xfce4-keyboard-shortcuts", "/xfwm4/custom").await {
+            Ok(reply) => {
+                for (property, val) in &reply {
+                    let Some(chord) = property.strip_prefix("/xfwm4/custom/") else {
+                        continue;
+                    };
+                    if let Ok(action) = val.downcast_ref::<&str>() {
+                        self.current.shortcuts.insert(chord.to_string(), action.to_string());
+                    }
+                }
+                debug!("Loaded {} window manager shortcuts", self.current.shortcuts.len());
+            }
+            Err(e) => debug!("No shortcuts in Xfconf: {}", e),
+        }
+
         Ok(())
     }
+
+    /// The cursor is shared with GTK apps, so it lives with their settings
+    async fn load_cursor(&mut self, config: &XfceConfig) {
+        self.current.cursor_theme = config
+            .get_string(XSETTINGS_CHANNEL, "/Gtk/CursorThemeName")
+            .await
+            .ok()
+            .filter(|theme| !theme.is_empty());
+        self.current.cursor_size = config
+            .get_int(XSETTINGS_CHANNEL, "/Gtk/CursorThemeSize")
+            .await
+            .ok()
+            .and_then(|size| u32::try_from(size).ok())
+            .filter(|&size| size > 0);
+    }
+}
+
+/// A workspace setting changed in Xfconf, e.g. in the settings dialog
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceSetting {
+    Count(u32),
+    Names(Vec<String>),
+}
+
+/// Call `on_change` with each change to xfwm4's workspace settings while
+/// the window manager runs. Must be called within a Tokio runtime.
+pub async fn watch_workspace_settings<F>(on_change: F) -> Result<()>
+where
+    F: Fn(WorkspaceSetting) + Send + 'static,
+{
+    let conn = Connection::session().await?;
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.xfce.Xfconf")?
+        .member("PropertyChanged")?
+        .build();
+    let mut signals = MessageStream::for_match_rule(rule, &conn, None).await?;
+
+    tokio::spawn(async move {
+        while let Some(Ok(message)) = signals.next().await {
+            let Ok((channel, property, value)) = message.body().deserialize::<(String, String, OwnedValue)>() else {
+                continue;
+            };
+            if channel != "xfwm4" {
+                continue;
+            }
+            let setting = match property.as_str() {
+                "/general/workspace_count" => workspace_count(&value).map(WorkspaceSetting::Count),
+                "/general/workspace_names" => string_list(&value).map(WorkspaceSetting::Names),
+                _ => None,
+            };
+            if let Some(setting) = setting {
+                debug!("Workspace setting changed: {:?}", setting);
+                on_change(setting);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn workspace_count(value: &OwnedValue) -> Option<u32> {
+    value.downcast_ref::<i32>().ok().and_then(|count| u32::try_from(count).ok()).filter(|&count| count > 0)
+}
+
+/// Xfconf sends string lists as arrays of variants
+fn string_list(value: &OwnedValue) -> Option<Vec<String>> {
+    let array = value.downcast_ref::<&Array>().ok()?;
+    let text = |item: &Value| match item {
+        Value::Value(inner) => inner.downcast_ref::<&str>().ok().map(str::to_string),
+        other => other.downcast_ref::<&str>().ok().map(str::to_string),
+    };
+    Some(array.iter().filter_map(text).collect())
+}
+
+/// org.xfce.Xfconf GetAllProperties(s channel, s property_base) -> a{sv}
+async fn get_all_properties(
+    conn: &Connection,
+    channel: &str,
+    property_base: &str,
+) -> Result<HashMap<String, zbus::zvariant::OwnedValue>> {
+    Ok(conn.call_method(
+        Some("org.xfce.Xfconf"),
+        "/org/xfce/Xfconf",
+        Some("org.xfce.Xfconf"),
+        "GetAllProperties",
+        &(channel, property_base),
+    ).await?.body().deserialize()?)
 }
@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use x11rb::connection::Connection;
+use x11rb::protocol::render::{Color, ConnectionExt as RenderExt, CreatePictureAux, PictOp, PictType, Picture, Repeat};
+use x11rb::protocol::xproto::{ConnectionExt, ImageFormat, Rectangle, Window};
+
+/// How far shadows reach past the frame
+pub const SHADOW_RADIUS: u16 = 12;
+/// Shadows fall a little below the frame, as if lit from above
+const SHADOW_OFFSET_Y: i16 = 4;
+
+/// Cumulative normal distribution, by the Abramowitz and Stegun
+/// approximation of erf (7.1.26), good to 1.5e-7
+fn normal_cdf(t: f64) -> f64 {
+    let x = t.abs() / std::f64::consts::SQRT_2;
+    let k = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = k * (0.254829592 + k * (-0.284496736 + k * (1.421413741 + k * (-1.453152027 + k * 1.061405429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    0.5 * (1.0 + if t < 0.0 { -erf } else { erf })
+}
+
+/// Alpha across a blurred edge of a rectangle, 2 * `radius` values from
+/// outside to inside, the edge itself in the middle. The blur fades out
+/// over three standard deviations.
+pub fn edge_profile(radius: u16) -> Vec<u8> {
+    let sigma = radius as f64 / 3.0;
+    (0..2 * radius)
+        .map(|i| (normal_cdf((i as f64 + 0.5 - radius as f64) / sigma) * 255.0).round() as u8)
+        .collect()
+}
+
+/// Alpha of the top left corner, row by row: a gaussian blurred rectangle
+/// is the product of its blurred edges across and down
+pub fn corner(profile: &[u8]) -> Vec<u8> {
+    profile
+        .iter()
+        .flat_map(|&down| profile.iter().map(move |&across| ((down as u32 * across as u32 + 127) / 255) as u8))
+        .collect()
+}
+
+/// ZPixmap data of an 8-bit image, with rows padded to 32 bits
+fn pad_rows(alpha: &[u8], width: usize) -> Vec<u8> {
+    let stride = (width + 3) & !3;
+    alpha.chunks(width).flat_map(|row| row.iter().copied().chain(std::iter::repeat_n(0, stride - width))).collect()
+}
+
+/// The textures shadows are drawn with: the four corners and the four
+/// edges, which repeat along the sides
+#[derive(Debug)]
+pub struct ShadowTextures {
+    /// Top left, top right, bottom left, bottom right
+    corners: [Picture; 4],
+    /// Top, bottom, left, right
+    edges: [Picture; 4],
+}
+
+impl ShadowTextures {
+    pub fn new<C: Connection>(conn: &C, root: Window) -> Result<Self> {
+        let formats = conn.render_query_pict_formats()?.reply()?;
+        let a8 = formats
+            .formats
+            .iter()
+            .find(|f| f.type_ == PictType::DIRECT && f.depth == 8 && f.direct.alpha_mask == 0xff)
+            .ok_or_else(|| anyhow!("No A8 picture format"))?
+            .id;
+
+        let size = (2 * SHADOW_RADIUS) as usize;
+        let profile = edge_profile(SHADOW_RADIUS);
+        let tl = corner(&profile);
+        let flip_x = |data: &[u8], width: usize| -> Vec<u8> { data.chunks(width).flat_map(|row| row.iter().rev().copied()).collect() };
+        let flip_y = |data: &[u8], width: usize| -> Vec<u8> { data.chunks(width).rev().flatten().copied().collect() };
+        let tr = flip_x(&tl, size);
+        let bl = flip_y(&tl, size);
+        let br = flip_y(&tr, size);
+        let reversed: Vec<u8> = profile.iter().rev().copied().collect();
+
+        let texture = |alpha: &[u8], width: usize, height: usize, repeat: bool| -> Result<Picture> {
+            let pixmap = conn.generate_id()?;
+            conn.create_pixmap(8, pixmap, root, width as u16, height as u16)?;
+            let gc = conn.generate_id()?;
+            conn.create_gc(gc, pixmap, &Default::default())?;
+            conn.put_image(ImageFormat::Z_PIXMAP, pixmap, gc, width as u16, height as u16, 0, 0, 0, 8, &pad_rows(alpha, width))?;
+            let picture = conn.generate_id()?;
+            let mut aux = CreatePictureAux::new();
+            if repeat {
+                aux = aux.repeat(Repeat::NORMAL);
+            }
+            conn.render_create_picture(picture, pixmap, a8, &aux)?;
+            // The picture keeps the pixmap alive
+            conn.free_gc(gc)?;
+            conn.free_pixmap(pixmap)?;
+            Ok(picture)
+        };
+
+        Ok(Self {
+            corners: [
+                texture(&tl, size, size, false)?,
+                texture(&tr, size, size, false)?,
+                texture(&bl, size, size, false)?,
+                texture(&br, size, size, false)?,
+            ],
+            edges: [
+                texture(&profile, 1, size, true)?,
+                texture(&reversed, 1, size, true)?,
+                texture(&profile, size, 1, true)?,
+                texture(&reversed, size, 1, true)?,
+            ],
+        })
+    }
+
+    /// Draw the shadow of a frame at `frame` onto `target`, `alpha` at its
+    /// darkest
+    pub fn paint<C: Connection>(&self, conn: &C, target: Picture, frame: Rectangle, alpha: u16) -> Result<()> {
+        if alpha == 0 {
+            return Ok(());
+        }
+        let source = conn.generate_id()?;
+        conn.render_create_solid_fill(source, Color { red: 0, green: 0, blue: 0, alpha })?;
+
+        let size = (2 * SHADOW_RADIUS) as i16;
+        let radius = SHADOW_RADIUS as i16;
+        let (x, y) = (frame.x - radius, frame.y - radius + SHADOW_OFFSET_Y);
+        let (width, height) = ((frame.width + 2 * SHADOW_RADIUS) as i16, (frame.height + 2 * SHADOW_RADIUS) as i16);
+        // Small frames get the outer part of each corner
+        let (cw, ch) = (size.min(width / 2), size.min(height / 2));
+        let (right, bottom) = (x + width - cw, y + height - ch);
+        let (middle_w, middle_h) = (width - 2 * cw, height - 2 * ch);
+
+        let mut pieces = vec![
+            (self.corners[0], 0, 0, x, y, cw, ch),
+            (self.corners[1], size - cw, 0, right, y, cw, ch),
+            (self.corners[2], 0, size - ch, x, bottom, cw, ch),
+            (self.corners[3], size - cw, size - ch, right, bottom, cw, ch),
+        ];
+        if middle_w > 0 {
+            pieces.push((self.edges[0], 0, 0, x + cw, y, middle_w, ch));
+            pieces.push((self.edges[1], 0, size - ch, x + cw, bottom, middle_w, ch));
+        }
+        if middle_h > 0 {
+            pieces.push((self.edges[2], 0, 0, x, y + ch, cw, middle_h));
+            pieces.push((self.edges[3], size - cw, 0, right, y + ch, cw, middle_h));
+        }
+        if middle_w > 0 && middle_h > 0 {
+            pieces.push((x11rb::NONE, 0, 0, x + cw, y + ch, middle_w, middle_h));
+        }
+        for (mask, mask_x, mask_y, dst_x, dst_y, w, h) in pieces {
+            conn.render_composite(PictOp::OVER, source, mask, target, 0, 0, mask_x, mask_y, dst_x, dst_y, w as u16, h as u16)?;
+        }
+        conn.render_free_picture(source)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edge_profile() {
+        let profile = edge_profile(SHADOW_RADIUS);
+        assert_eq!(profile.len(), 24);
+        // Faint outside, dark inside, half way at the edge
+        assert!(profile[0] < 3);
+        assert!(profile[23] > 252);
+        assert!(profile[11] < 128 && profile[12] > 128);
+        assert!(profile.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-6);
+        assert!((normal_cdf(1.0) - 0.841345).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_corner_texture() {
+        let profile = [0, 128, 255];
+        let corner = corner(&profile);
+        assert_eq!(corner, [0, 0, 0, 0, 64, 128, 0, 128, 255]);
+        // Rows of 3 pad to 4 bytes
+        assert_eq!(pad_rows(&corner, 3), [0, 0, 0, 0, 0, 64, 128, 0, 0, 128, 255, 0]);
+    }
+}
@@ -0,0 +1,141 @@
+use x11rb::protocol::xproto::Rectangle;
+
+use crate::window::settings::Settings;
+
+/// Which edge of the moving frame a line catches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    /// Left or top
+    Start,
+    /// Right or bottom
+    End,
+}
+
+/// Pulls the edges of a window being moved onto the edges of the work
+/// area and of the windows around it, as in xfwm4
+#[derive(Debug, Clone, Copy)]
+pub struct Snapping {
+    /// Snap to the edges of the work area
+    pub to_border: bool,
+    /// Snap to the edges of other windows
+    pub to_windows: bool,
+    /// How close an edge gets before it snaps, in pixels
+    pub distance: u16,
+    /// Only hold windows back as they cross an edge, for that distance,
+    /// rather than pulling them onto edges from either side
+    pub resist: bool,
+}
+
+impl Snapping {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            to_border: settings.snap_to_border,
+            to_windows: settings.snap_to_windows,
+            distance: u16::try_from(settings.snap_width).unwrap_or(u16::MAX),
+            resist: settings.snap_resist,
+        }
+    }
+
+    /// Where a frame moved to `rect` goes: its edges caught by those of
+    /// `area`, the work area of its monitor, and of the frames `others`
+    pub fn snap(&self, rect: Rectangle, area: Rectangle, others: &[Rectangle]) -> (i16, i16) {
+        if self.distance == 0 || !(self.to_border || self.to_windows) {
+            return (rect.x, rect.y);
+        }
+        let span = |start: i16, len: u16| (start as i32, start as i32 + len as i32);
+        let (left, right) = span(rect.x, rect.width);
+        let (top, bottom) = span(rect.y, rect.height);
+        let near = |a: (i32, i32), b: (i32, i32)| a.0 <= b.1 + self.distance as i32 && b.0 <= a.1 + self.distance as i32;
+
+        let mut columns = Vec::new();
+        let mut rows = Vec::new();
+        if self.to_border {
+            let (area_left, area_right) = span(area.x, area.width);
+            let (area_top, area_bottom) = span(area.y, area.height);
+            columns.extend([(area_left, Edge::Start), (area_right, Edge::End)]);
+            rows.extend([(area_top, Edge::Start), (area_bottom, Edge::End)]);
+        }
+        if self.to_windows {
+            for other in others {
+                let (other_left, other_right) = span(other.x, other.width);
+                let (other_top, other_bottom) = span(other.y, other.height);
+                // Side by side only where they are level with each other
+                if near((top, bottom), (other_top, other_bottom)) {
+                    columns.extend([(other_right, Edge::Start), (other_left, Edge::End)]);
+                }
+                if near((left, right), (other_left, other_right)) {
+                    rows.extend([(other_bottom, Edge::Start), (other_top, Edge::End)]);
+                }
+            }
+        }
+        let x = self.snap_axis(left, right, &columns);
+        let y = self.snap_axis(top, bottom, &rows);
+        (x as i16, y as i16)
+    }
+
+    /// The start of a span from `start` to `end` moved by the nearest of
+    /// `lines` that catches one of its edges
+    fn snap_axis(&self, start: i32, end: i32, lines: &[(i32, Edge)]) -> i32 {
+        let distance = self.distance as i32;
+        lines
+            .iter()
+            .filter_map(|&(line, edge)| {
+                let offset = line - if edge == Edge::Start { start } else { end };
+                // Crossing the line is going left or up of it for the
+                // start edge, right or down for the end one
+                let crossing = if edge == Edge::Start { offset > 0 } else { offset < 0 };
+                (offset.abs() <= distance && (crossing || !self.resist)).then_some(offset)
+            })
+            .min_by_key(|offset| offset.abs())
+            .map_or(start, |offset| start + offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rectangle = Rectangle { x: 0, y: 30, width: 1920, height: 1050 };
+
+    fn snapping() -> Snapping {
+        Snapping { to_border: true, to_windows: true, distance: 10, resist: false }
+    }
+
+    fn at(x: i16, y: i16) -> Rectangle {
+        Rectangle { x, y, width: 400, height: 300 }
+    }
+
+    #[test]
+    fn test_snap_to_borders() {
+        let snapping = snapping();
+        assert_eq!(snapping.snap(at(6, 200), AREA, &[]), (0, 200));
+        // Pushed past the bottom right corner by less than the distance
+        assert_eq!(snapping.snap(at(1528, 786), AREA, &[]), (1520, 780));
+        assert_eq!(snapping.snap(at(500, 41), AREA, &[]), (500, 41));
+        let off = Snapping { to_border: false, to_windows: false, ..snapping };
+        assert_eq!(off.snap(at(6, 36), AREA, &[]), (6, 36));
+    }
+
+    #[test]
+    fn test_snap_to_windows() {
+        let snapping = snapping();
+        let neighbor = Rectangle { x: 800, y: 100, width: 500, height: 400 };
+        // Against its left side, and under it
+        assert_eq!(snapping.snap(at(395, 150), AREA, &[neighbor]), (400, 150));
+        assert_eq!(snapping.snap(at(850, 507), AREA, &[neighbor]), (850, 500));
+        // Not level with it, so nothing to snap to
+        assert_eq!(snapping.snap(at(395, 700), AREA, &[neighbor]), (395, 700));
+    }
+
+    #[test]
+    fn test_resistance() {
+        let resist = Snapping { resist: true, ..snapping() };
+        // Held at the edge while pushed over it, but not pulled to it
+        assert_eq!(resist.snap(at(-7, 200), AREA, &[]), (0, 200));
+        assert_eq!(resist.snap(at(7, 200), AREA, &[]), (7, 200));
+        assert_eq!(resist.snap(at(-11, 200), AREA, &[]), (-11, 200));
+        let neighbor = Rectangle { x: 800, y: 100, width: 500, height: 400 };
+        assert_eq!(resist.snap(at(405, 150), AREA, &[neighbor]), (400, 150));
+        assert_eq!(resist.snap(at(395, 150), AREA, &[neighbor]), (395, 150));
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::Window;
+
+/// How long a launch shows as busy when no window comes of it, as in
+/// xfwm4
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a startup notification message says about a launch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    New,
+    Change,
+    Remove,
+}
+
+/// A `_NET_STARTUP_INFO` message, as far as the window manager cares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupMessage {
+    pub kind: Kind,
+    /// The startup ID the launched application's windows carry
+    pub id: String,
+    /// Workspace the launch came from
+    pub desktop: Option<u32>,
+    /// X server time of what the user did to launch it
+    pub timestamp: Option<u32>,
+}
+
+impl StartupMessage {
+    /// Parse a message such as `new: ID="..." NAME="Terminal" DESKTOP=1`
+    pub fn parse(text: &str) -> Option<Self> {
+        let (kind, rest) = text.split_once(':')?;
+        let kind = match kind {
+            "new" => Kind::New,
+            "change" => Kind::Change,
+            "remove" => Kind::Remove,
+            _ => return None,
+        };
+        let fields = parse_fields(rest);
+        let id = fields.get("ID")?.clone();
+        let number = |key: &str| fields.get(key).and_then(|value| value.parse().ok());
+        Some(Self { kind, id, desktop: number("DESKTOP"), timestamp: number("TIMESTAMP") })
+    }
+}
+
+/// The `KEY=value` pairs of a message; values are quoted or run to the
+/// next space, with backslash escaping the next character
+fn parse_fields(text: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let key: String = std::iter::from_fn(|| chars.next_if(|&c| c != '=' && !c.is_whitespace())).collect();
+        if key.is_empty() || chars.next() != Some('=') {
+            return fields;
+        }
+        let mut value = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => value.extend(chars.next()),
+                '"' => quoted = !quoted,
+                c if c.is_whitespace() && !quoted => break,
+                c => value.push(c),
+            }
+        }
+        fields.insert(key, value);
+    }
+}
+
+/// The launch time a startup ID carries at its end, as in `..._TIME1234`
+pub fn id_timestamp(id: &str) -> Option<u32> {
+    id.rsplit_once("_TIME").and_then(|(_, time)| time.parse().ok())
+}
+
+/// Joins the 20-byte `_NET_STARTUP_INFO_BEGIN` and `_NET_STARTUP_INFO`
+/// pieces each sender's messages arrive in
+#[derive(Debug, Default)]
+pub struct Assembler {
+    partial: HashMap<Window, Vec<u8>>,
+}
+
+impl Assembler {
+    /// Add a piece from `sender`, `begin` for the first of a message;
+    /// returns the message once its terminating nul arrives
+    pub fn push(&mut self, sender: Window, begin: bool, data: &[u8]) -> Option<String> {
+        if begin {
+            self.partial.insert(sender, Vec::new());
+        }
+        let buffer = self.partial.get_mut(&sender)?;
+        match data.iter().position(|&b| b == 0) {
+            Some(end) => {
+                buffer.extend_from_slice(&data[..end]);
+                let message = self.partial.remove(&sender)?;
+                String::from_utf8(message).ok()
+            }
+            None => {
+                buffer.extend_from_slice(data);
+                None
+            }
+        }
+    }
+}
+
+/// A launch whose windows have yet to appear
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Launch {
+    pub desktop: Option<u32>,
+    pub timestamp: Option<u32>,
+    started: Instant,
+}
+
+/// Launches in progress, by startup ID
+#[derive(Debug, Default)]
+pub struct Launches {
+    pending: HashMap<String, Launch>,
+}
+
+impl Launches {
+    /// Follow a message received at `now`
+    pub fn update(&mut self, message: StartupMessage, now: Instant) {
+        match message.kind {
+            Kind::New => {
+                self.pending.insert(message.id, Launch { desktop: message.desktop, timestamp: message.timestamp, started: now });
+            }
+            Kind::Change => {
+                if let Some(launch) = self.pending.get_mut(&message.id) {
+                    launch.desktop = message.desktop.or(launch.desktop);
+                    launch.timestamp = message.timestamp.or(launch.timestamp);
+                }
+            }
+            Kind::Remove => {
+                self.pending.remove(&message.id);
+            }
+        }
+    }
+
+    /// A window with startup ID `id` appeared: its launch is done
+    pub fn complete(&mut self, id: &str) -> Option<Launch> {
+        self.pending.remove(id)
+    }
+
+    /// Drop launches that went on too long by `now`; whether there were any
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|_, launch| now < launch.started + STARTUP_TIMEOUT);
+        self.pending.len() != before
+    }
+
+    /// When the oldest launch times out
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|launch| launch.started + STARTUP_TIMEOUT).min()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        let message = StartupMessage::parse(r#"new: ID="xfce-rs-42_TIME1234" NAME="Text \"Editor\"" SCREEN=0 DESKTOP=2 TIMESTAMP=1234"#).unwrap();
+        assert_eq!(message.kind, Kind::New);
+        assert_eq!(message.id, "xfce-rs-42_TIME1234");
+        assert_eq!(message.desktop, Some(2));
+        assert_eq!(message.timestamp, Some(1234));
+        assert_eq!(StartupMessage::parse("remove: ID=abc\\ def").map(|m| m.id), Some(String::from("abc def")));
+        assert_eq!(StartupMessage::parse("remove: NAME=x"), None);
+        assert_eq!(StartupMessage::parse("bogus: ID=x"), None);
+
+        assert_eq!(id_timestamp("xfce-rs-42_TIME1234"), Some(1234));
+        assert_eq!(id_timestamp("no-time"), None);
+    }
+
+    #[test]
+    fn test_assemble_pieces() {
+        let text = b"remove: ID=\"a-rather-long-startup-id\"\0";
+        let mut assembler = Assembler::default();
+        let mut pieces = text.chunks(20);
+        assert_eq!(assembler.push(7, true, pieces.next().unwrap()), None);
+        // Another sender's message in between
+        assert_eq!(assembler.push(8, true, b"remove: ID=b\0"), Some(String::from("remove: ID=b")));
+        assert_eq!(assembler.push(7, false, pieces.next().unwrap()), Some(String::from("remove: ID=\"a-rather-long-startup-id\"")));
+        // Continuation without its beginning
+        assert_eq!(assembler.push(9, false, b"ID=x\0"), None);
+    }
+
+    #[test]
+    fn test_launches() {
+        let start = Instant::now();
+        let mut launches = Launches::default();
+        let message = |kind, id: &str, desktop| StartupMessage { kind, id: id.into(), desktop, timestamp: None };
+        launches.update(message(Kind::New, "a", Some(1)), start);
+        launches.update(message(Kind::New, "b", None), start + Duration::from_secs(10));
+        launches.update(message(Kind::Change, "b", Some(3)), start);
+        assert!(launches.is_busy());
+
+        assert_eq!(launches.complete("b").and_then(|launch| launch.desktop), Some(3));
+        assert_eq!(launches.complete("b"), None);
+        assert!(!launches.expire(start + Duration::from_secs(29)));
+        assert!(launches.expire(start + STARTUP_TIMEOUT));
+        assert!(!launches.is_busy());
+        assert_eq!(launches.deadline(), None);
+    }
+}
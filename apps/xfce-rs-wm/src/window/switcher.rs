@@ -0,0 +1,338 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ChangeGCAux, ConnectionExt, CreateGCAux, EventMask, ImageFormat, Rectangle, Window,
+};
+use tracing::debug;
+
+use crate::core::context::Context;
+use crate::window::overlay::{self, Painter};
+
+/// Width of one window's cell in the switcher
+const CELL_WIDTH: u16 = 176;
+/// Height of the preview area of a cell when the compositor draws thumbnails
+const THUMBNAIL_HEIGHT: u16 = 112;
+/// Size icons are drawn at
+pub const ICON_SIZE: u16 = 48;
+const LABEL_HEIGHT: u16 = 26;
+const PADDING: u16 = 12;
+/// Width of a character of the 10x20 font
+const CHAR_WIDTH: u16 = 10;
+
+const BACKGROUND: u32 = 0x2b2b2b;
+const SELECTED: u32 = 0x4a6a94;
+const TEXT: u32 = 0xe0e0e0;
+
+/// `_NET_WM_ICON` image, ARGB pixels row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Icon {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u32>,
+}
+
+impl Icon {
+    /// Pick the image to draw at `size` from a `_NET_WM_ICON` value: the
+    /// smallest at least that big, or else the biggest
+    pub fn from_property(data: &[u32], size: u32) -> Option<Self> {
+        let mut images = Vec::new();
+        let mut rest = data;
+        while let [width, height, tail @ ..] = rest {
+            let len = (*width as usize).checked_mul(*height as usize)?;
+            if len == 0 || tail.len() < len {
+                break;
+            }
+            images.push((*width, *height, &tail[..len]));
+            rest = &tail[len..];
+        }
+        let (width, height, pixels) = images
+            .iter()
+            .filter(|(width, _, _)| *width >= size)
+            .min_by_key(|(width, _, _)| *width)
+            .or_else(|| images.iter().max_by_key(|(width, _, _)| *width))?;
+        Some(Self { width: *width, height: *height, pixels: pixels.to_vec() })
+    }
+
+    /// The icon scaled to `size` square and blended over `background`, as
+    /// 32 bits per pixel ZPixmap data (B, G, R, unused)
+    pub fn to_image(&self, size: u16, background: u32) -> Vec<u8> {
+        let size = size as usize;
+        let mut image = Vec::with_capacity(size * size * 4);
+        for y in 0..size {
+            for x in 0..size {
+                let sx = x * self.width as usize / size;
+                let sy = y * self.height as usize / size;
+                let pixel = self.pixels[sy * self.width as usize + sx];
+                let alpha = pixel >> 24;
+                let blend = |shift: u32| {
+                    let fg = (pixel >> shift) & 0xff;
+                    let bg = (background >> shift) & 0xff;
+                    ((fg * alpha + bg * (255 - alpha)) / 255) as u8
+                };
+                image.extend_from_slice(&[blend(0), blend(8), blend(16), 0]);
+            }
+        }
+        image
+    }
+}
+
+/// A window as the switcher lists it
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub window: Window,
+    pub title: String,
+    pub icon: Option<Icon>,
+}
+
+/// Where the popup and its cells go: one row per as many cells as fit the
+/// screen, centered
+pub fn layout(count: usize, thumbnails: bool, screen_width: u16, screen_height: u16) -> (Rectangle, Vec<Rectangle>) {
+    let cell_height = if thumbnails { THUMBNAIL_HEIGHT } else { ICON_SIZE } + LABEL_HEIGHT + PADDING;
+    let per_row = (((screen_width.saturating_sub(PADDING)) / (CELL_WIDTH + PADDING)) as usize).clamp(1, count.max(1));
+    let rows = count.div_ceil(per_row).max(1);
+
+    let width = PADDING + per_row as u16 * (CELL_WIDTH + PADDING);
+    let height = PADDING + rows as u16 * (cell_height + PADDING);
+    let popup = Rectangle {
+        x: (screen_width as i16 - width as i16) / 2,
+        y: (screen_height as i16 - height as i16) / 2,
+        width,
+        height,
+    };
+    let cells = (0..count)
+        .map(|i| Rectangle {
+            x: (PADDING + (i % per_row) as u16 * (CELL_WIDTH + PADDING)) as i16,
+            y: (PADDING + (i / per_row) as u16 * (cell_height + PADDING)) as i16,
+            width: CELL_WIDTH,
+            height: cell_height,
+        })
+        .collect();
+    (popup, cells)
+}
+
+/// The largest rectangle with the aspect of `width` x `height` that fits in
+/// `area`, centered in it
+pub fn fit(width: u16, height: u16, area: Rectangle) -> Rectangle {
+    if width == 0 || height == 0 {
+        return Rectangle { width: 0, height: 0, ..area };
+    }
+    let scale = (area.width as f32 / width as f32).min(area.height as f32 / height as f32).min(1.0);
+    let fitted_width = ((width as f32 * scale) as u16).max(1);
+    let fitted_height = ((height as f32 * scale) as u16).max(1);
+    Rectangle {
+        x: area.x + ((area.width - fitted_width) / 2) as i16,
+        y: area.y + ((area.height - fitted_height) / 2) as i16,
+        width: fitted_width,
+        height: fitted_height,
+    }
+}
+
+/// `title` cut to `max_chars`, with characters the core font can't draw
+/// replaced
+pub fn label(title: &str, max_chars: usize) -> String {
+    let printable: Vec<char> = title.chars().map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' }).collect();
+    if printable.len() <= max_chars {
+        return printable.into_iter().collect();
+    }
+    let mut label: String = printable[..max_chars.saturating_sub(3)].iter().collect();
+    label.push_str("...");
+    label
+}
+
+/// The Alt+Tab popup: windows of the current workspace, most recently used
+/// first, with one of them selected until the modifiers are released
+pub struct Switcher {
+    entries: Vec<Entry>,
+    selected: usize,
+    /// Keycodes of the modifiers held when it opened; releasing one of them
+    /// picks the selection
+    pub modifier_keycodes: Vec<u8>,
+    popup: Window,
+    popup_rect: Rectangle,
+    cells: Vec<Rectangle>,
+    thumbnails: bool,
+}
+
+impl Switcher {
+    /// Select the window after the active one, or the last one when
+    /// `reverse`. Returns `None` when there is nothing to switch to.
+    pub fn new(entries: Vec<Entry>, reverse: bool, modifier_keycodes: Vec<u8>, thumbnails: bool) -> Option<Self> {
+        if entries.len() < 2 {
+            return None;
+        }
+        let selected = if reverse { entries.len() - 1 } else { 1 };
+        Some(Self {
+            entries,
+            selected,
+            modifier_keycodes,
+            popup: x11rb::NONE,
+            popup_rect: Rectangle { x: 0, y: 0, width: 0, height: 0 },
+            cells: Vec::new(),
+            thumbnails,
+        })
+    }
+
+    pub fn selected(&self) -> Window {
+        self.entries[self.selected].window
+    }
+
+    pub fn popup(&self) -> Window {
+        self.popup
+    }
+
+    /// Move the selection one window on, or back when `reverse`
+    pub fn advance(&mut self, reverse: bool) {
+        let count = self.entries.len();
+        self.selected = if reverse { (self.selected + count - 1) % count } else { (self.selected + 1) % count };
+    }
+
+    /// Create and map the popup window
+    pub fn show(&mut self, ctx: &Context) -> Result<()> {
+        let (popup_rect, cells) = layout(self.entries.len(), self.thumbnails, ctx.screen_width, ctx.screen_height);
+        self.popup = overlay::create(ctx, popup_rect, BACKGROUND, EventMask::EXPOSURE)?;
+        self.popup_rect = popup_rect;
+        self.cells = cells;
+        ctx.conn.map_window(self.popup)?;
+        self.draw(ctx)
+    }
+
+    /// Draw the cells, the selected one highlighted. Thumbnails go on top
+    /// later, in the compositor's paint.
+    pub fn draw(&self, ctx: &Context) -> Result<()> {
+        if self.popup == x11rb::NONE {
+            return Ok(());
+        }
+        let painter = Painter::new(ctx, self.popup, CreateGCAux::new().foreground(BACKGROUND))?;
+        let gc = painter.gc();
+        ctx.conn.poly_fill_rectangle(self.popup, gc, &[Rectangle { x: 0, y: 0, ..self.popup_rect }])?;
+
+        // Icons are blended for 24-bit visuals only, which is what X servers run
+        let draw_icons = ctx.root_depth == 24 || ctx.root_depth == 32;
+        for (i, (entry, cell)) in self.entries.iter().zip(&self.cells).enumerate() {
+            let background = if i == self.selected { SELECTED } else { BACKGROUND };
+            if i == self.selected {
+                ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(SELECTED))?;
+                ctx.conn.poly_fill_rectangle(self.popup, gc, &[*cell])?;
+            }
+
+            let preview = self.preview_area(cell);
+            if let (Some(icon), true) = (&entry.icon, draw_icons) {
+                let x = preview.x + ((preview.width - ICON_SIZE) / 2) as i16;
+                let y = preview.y + ((preview.height - ICON_SIZE) / 2) as i16;
+                if let Err(e) = ctx.conn.put_image(
+                    ImageFormat::Z_PIXMAP,
+                    self.popup,
+                    gc,
+                    ICON_SIZE,
+                    ICON_SIZE,
+                    x,
+                    y,
+                    0,
+                    ctx.root_depth,
+                    &icon.to_image(ICON_SIZE, background),
+                ) {
+                    debug!("Failed to draw icon of window {}: {}", entry.window, e);
+                }
+            }
+
+            if painter.has_font() {
+                let text = label(&entry.title, ((CELL_WIDTH - 8) / CHAR_WIDTH) as usize);
+                let text_x = cell.x + ((CELL_WIDTH - text.len() as u16 * CHAR_WIDTH) / 2) as i16;
+                let text_y = cell.y + (cell.height - PADDING / 2) as i16 - 4;
+                ctx.conn.change_gc(
+                    gc,
+                    &ChangeGCAux::new().foreground(TEXT).background(background),
+                )?;
+                if let Err(e) = ctx.conn.image_text8(self.popup, gc, text_x, text_y, text.as_bytes()) {
+                    debug!("Failed to draw switcher label: {}", e);
+                }
+                ctx.conn.change_gc(gc, &ChangeGCAux::new().foreground(BACKGROUND))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Part of a cell above the label, for the icon or the thumbnail
+    fn preview_area(&self, cell: &Rectangle) -> Rectangle {
+        Rectangle {
+            x: cell.x + (PADDING / 2) as i16,
+            y: cell.y + (PADDING / 2) as i16,
+            width: cell.width - PADDING,
+            height: cell.height - LABEL_HEIGHT - PADDING,
+        }
+    }
+
+    /// Where each window's thumbnail goes, in root coordinates
+    pub fn thumbnail_areas(&self) -> impl Iterator<Item = (Window, Rectangle)> + '_ {
+        self.entries.iter().zip(&self.cells).filter(|_| self.thumbnails).map(|(entry, cell)| {
+            let area = self.preview_area(cell);
+            (entry.window, Rectangle { x: self.popup_rect.x + area.x, y: self.popup_rect.y + area.y, ..area })
+        })
+    }
+
+    /// Destroy the popup
+    pub fn hide<C: Connection>(&mut self, conn: &C) {
+        overlay::destroy(conn, &mut self.popup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: u32) -> Vec<Entry> {
+        (1..=count).map(|window| Entry { window, title: String::new(), icon: None }).collect()
+    }
+
+    #[test]
+    fn test_cycling() {
+        assert!(Switcher::new(entries(1), false, Vec::new(), false).is_none());
+
+        let mut switcher = Switcher::new(entries(3), false, Vec::new(), false).unwrap();
+        assert_eq!(switcher.selected(), 2);
+        switcher.advance(false);
+        assert_eq!(switcher.selected(), 3);
+        switcher.advance(false);
+        assert_eq!(switcher.selected(), 1);
+        switcher.advance(true);
+        assert_eq!(switcher.selected(), 3);
+
+        let reverse = Switcher::new(entries(3), true, Vec::new(), false).unwrap();
+        assert_eq!(reverse.selected(), 3);
+    }
+
+    #[test]
+    fn test_layout_and_fit() {
+        let (popup, cells) = layout(3, false, 1920, 1080);
+        assert_eq!(popup.width, PADDING + 3 * (CELL_WIDTH + PADDING));
+        assert_eq!(popup.x, (1920 - popup.width as i16) / 2);
+        assert_eq!(cells[2].x, (PADDING + 2 * (CELL_WIDTH + PADDING)) as i16);
+
+        // Wraps to a second row on a narrow screen
+        let (popup, cells) = layout(5, true, 640, 480);
+        assert_eq!(cells.len(), 5);
+        assert_eq!(cells[3].x, PADDING as i16);
+        assert!(cells[3].y > cells[0].y);
+        assert!(popup.width <= 640);
+
+        let area = Rectangle { x: 10, y: 20, width: 160, height: 100 };
+        assert_eq!(fit(1920, 1080, area), Rectangle { x: 10, y: 25, width: 160, height: 90 });
+        assert_eq!(fit(50, 50, area), Rectangle { x: 65, y: 45, width: 50, height: 50 });
+    }
+
+    #[test]
+    fn test_icon_from_property() {
+        // A 1x1 and a 2x2 image
+        let data = [1, 1, 0xffff0000, 2, 2, 0xff00ff00, 0xff00ff00, 0xff00ff00, 0x00000000];
+        assert_eq!(Icon::from_property(&data, 2).map(|icon| icon.width), Some(2));
+        assert_eq!(Icon::from_property(&data, 1).map(|icon| icon.width), Some(1));
+        assert_eq!(Icon::from_property(&data, 48).map(|icon| icon.width), Some(2));
+        assert_eq!(Icon::from_property(&[4, 4, 0], 48), None);
+
+        let icon = Icon::from_property(&data, 2).unwrap();
+        let image = icon.to_image(2, 0x0000ff);
+        // Opaque green, then the transparent corner showing the background
+        assert_eq!(&image[..4], &[0x00, 0xff, 0x00, 0]);
+        assert_eq!(&image[12..], &[0xff, 0x00, 0x00, 0]);
+    }
+}
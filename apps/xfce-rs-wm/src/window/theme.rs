@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use tracing::{debug, info, warn};
+
+use crate::window::frame::{FramePart, TITLE_HEIGHT};
+use crate::window::settings::Settings;
+
+/// Colors of one focus state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateColors {
+    pub title: u32,
+    pub text: u32,
+    pub border: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TitleAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl TitleAlignment {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "left" => Some(Self::Left),
+            "center" => Some(Self::Center),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+}
+
+/// How a titlebar button is drawn, after xfwm4's image names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ButtonState {
+    Active,
+    Inactive,
+    /// Under the pointer
+    Prelight,
+    /// Held down with the pointer over it
+    Pressed,
+}
+
+impl ButtonState {
+    pub const ALL: [ButtonState; 4] = [Self::Active, Self::Inactive, Self::Prelight, Self::Pressed];
+
+    /// The state a button is drawn in when the pointer leaves it alone
+    pub fn resting(active: bool) -> Self {
+        if active { Self::Active } else { Self::Inactive }
+    }
+
+    fn file_suffix(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Inactive => "inactive",
+            Self::Prelight => "prelight",
+            Self::Pressed => "pressed",
+        }
+    }
+}
+
+/// Titlebar buttons in xfwm4's `button_layout` notation, e.g. `O|SHMC`:
+/// the buttons before the `|` go on the left, the others on the right.
+/// O is the window menu, S shade, H minimize, M maximize and C close; the
+/// other xfwm4 letters are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ButtonLayout {
+    pub left: Vec<FramePart>,
+    pub right: Vec<FramePart>,
+    pub size: u16,
+    pub spacing: u16,
+    /// Gap between the outer buttons and the frame edges
+    pub offset: u16,
+}
+
+impl Default for ButtonLayout {
+    fn default() -> Self {
+        Self::parse("|HMC")
+    }
+}
+
+impl ButtonLayout {
+    pub fn parse(layout: &str) -> Self {
+        let parts = |s: &str| -> Vec<FramePart> {
+            s.chars()
+                .filter_map(|c| match c {
+                    'O' => Some(FramePart::MenuButton),
+                    'S' => Some(FramePart::ShadeButton),
+                    'H' => Some(FramePart::MinimizeButton),
+                    'M' => Some(FramePart::MaximizeButton),
+                    'C' => Some(FramePart::CloseButton),
+                    _ => None,
+                })
+                .collect()
+        };
+        let (left, right) = layout.split_once('|').unwrap_or(("", layout));
+        Self { left: parts(left), right: parts(right), size: 12, spacing: 8, offset: 8 }
+    }
+
+    /// Top of the buttons, centered in the titlebar
+    pub fn y(&self) -> i16 {
+        (TITLE_HEIGHT.saturating_sub(self.size) / 2) as i16
+    }
+
+    /// Each button with the x of its left edge in a frame `width` wide
+    pub fn positions(&self, width: u16) -> Vec<(FramePart, i16)> {
+        let step = (self.size + self.spacing) as i16;
+        let left = self.left.iter().enumerate().map(|(i, &part)| (part, self.offset as i16 + i as i16 * step));
+        let right_start = width as i16 - self.offset as i16 - self.size as i16;
+        let right = self.right.iter().rev().enumerate().map(|(i, &part)| (part, right_start - i as i16 * step));
+        left.chain(right).collect()
+    }
+
+    /// The span of the titlebar the buttons leave for the title
+    pub fn title_span(&self, width: u16) -> (i16, i16) {
+        let step = self.size + self.spacing;
+        let start = self.offset + self.left.len() as u16 * step;
+        let end = width.saturating_sub(self.offset + self.right.len() as u16 * step);
+        (start as i16, end.max(start) as i16)
+    }
+
+    pub fn button_at(&self, width: u16, x: i16, y: i16) -> Option<FramePart> {
+        let top = self.y();
+        if y < top || y >= top + self.size as i16 {
+            return None;
+        }
+        self.positions(width)
+            .into_iter()
+            .find(|&(_, left)| x >= left && x < left + self.size as i16)
+            .map(|(part, _)| part)
+    }
+}
+
+/// RGBA image from a theme's PNG files
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u16,
+    pub height: u16,
+    pub rgba: Vec<u8>,
+}
+
+impl Image {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut decoder = png::Decoder::new(std::fs::File::open(path)?);
+        // Palettes and 16-bit channels become 8-bit RGB(A) or gray
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let pixels = &buffer[..info.buffer_size()];
+        let rgba = match (info.color_type, info.bit_depth) {
+            (png::ColorType::Rgba, png::BitDepth::Eight) => pixels.to_vec(),
+            (png::ColorType::Rgb, png::BitDepth::Eight) => pixels.chunks(3).flat_map(|p| [p[0], p[1], p[2], 0xff]).collect(),
+            (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight) => pixels.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            (png::ColorType::Grayscale, png::BitDepth::Eight) => pixels.iter().flat_map(|&p| [p, p, p, 0xff]).collect(),
+            (color_type, depth) => anyhow::bail!("Unsupported PNG format {:?} {:?}", color_type, depth),
+        };
+        Ok(Self { width: info.width as u16, height: info.height as u16, rgba })
+    }
+
+    /// 32 bits per pixel ZPixmap data (B, G, R, unused), blended over
+    /// `background`
+    pub fn to_zpixmap(&self, background: u32) -> Vec<u8> {
+        self.rgba
+            .chunks(4)
+            .flat_map(|p| {
+                let alpha = p[3] as u32;
+                let blend = |fg: u8, shift: u32| {
+                    let bg = (background >> shift) & 0xff;
+                    ((fg as u32 * alpha + bg * (255 - alpha)) / 255) as u8
+                };
+                [blend(p[2], 0), blend(p[1], 8), blend(p[0], 16), 0]
+            })
+            .collect()
+    }
+}
+
+/// How window frames look: an xfwm4 theme's themerc and button images,
+/// with the WM settings channel having the last word
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub active: StateColors,
+    pub inactive: StateColors,
+    pub title_alignment: TitleAlignment,
+    pub title_horizontal_offset: i16,
+    pub button_layout: ButtonLayout,
+    /// XLFD of the title font, tried before the built-in fallbacks
+    pub font: Option<String>,
+    /// Button images by button and state
+    pub buttons: HashMap<(FramePart, ButtonState), Image>,
+    /// Titlebar background tiles, focused and not
+    pub title_tiles: HashMap<bool, Image>,
+}
+
+impl Default for Theme {
+    /// The flat look the frames had before themes
+    fn default() -> Self {
+        Self {
+            active: StateColors { title: 0x3c3c3c, text: 0xe0e0e0, border: 0x3c3c3c },
+            inactive: StateColors { title: 0x2e2e2e, text: 0x9a9a9a, border: 0x2e2e2e },
+            title_alignment: TitleAlignment::Left,
+            title_horizontal_offset: 12,
+            button_layout: ButtonLayout::default(),
+            font: None,
+            buttons: HashMap::new(),
+            title_tiles: HashMap::new(),
+        }
+    }
+}
+
+impl Theme {
+    /// The theme named in the settings, falling back to the built-in look
+    pub fn load(settings: &Settings) -> Self {
+        let mut theme = match find_theme_dir(&settings.theme) {
+            Some(dir) => match Self::from_dir(&dir) {
+                Ok(theme) => {
+                    info!("🎨 Loaded window theme '{}' from {}", settings.theme, dir.display());
+                    theme
+                }
+                Err(e) => {
+                    warn!("Failed to load window theme '{}': {:#}", settings.theme, e);
+                    Self::default()
+                }
+            },
+            None => {
+                debug!("No xfwm4 theme named '{}', using the built-in one", settings.theme);
+                Self::default()
+            }
+        };
+        if let Some(layout) = &settings.button_layout {
+            // The theme still decides the button metrics
+            let ButtonLayout { size, spacing, offset, .. } = theme.button_layout;
+            theme.button_layout = ButtonLayout { size, spacing, offset, ..ButtonLayout::parse(layout) };
+        }
+        if let Some(alignment) = settings.title_alignment.as_deref().and_then(TitleAlignment::parse) {
+            theme.title_alignment = alignment;
+        }
+        if let Some(font) = &settings.title_font {
+            theme.font = Some(xlfd_from_pango(font));
+        }
+        theme
+    }
+
+    fn from_dir(dir: &Path) -> Result<Self> {
+        let themerc = std::fs::read_to_string(dir.join("themerc")).with_context(|| format!("reading {}/themerc", dir.display()))?;
+        let mut theme = Self::from_themerc(&themerc);
+
+        for (part, file) in [
+            (FramePart::CloseButton, "close"),
+            (FramePart::MaximizeButton, "maximize"),
+            (FramePart::MinimizeButton, "hide"),
+            (FramePart::ShadeButton, "shade"),
+            (FramePart::MenuButton, "menu"),
+        ] {
+            for state in ButtonState::ALL {
+                let path = dir.join(format!("{}-{}.png", file, state.file_suffix()));
+                if let Ok(image) = Image::load(&path) {
+                    theme.buttons.insert((part, state), image);
+                }
+            }
+        }
+        for (active, state) in [(true, "active"), (false, "inactive")] {
+            if let Ok(image) = Image::load(&dir.join(format!("title-3-{}.png", state))) {
+                theme.title_tiles.insert(active, image);
+            }
+        }
+        // Buttons as big as the theme draws them, within the titlebar
+        if let Some(image) = theme.buttons.values().next() {
+            theme.button_layout.size = image.width.min(TITLE_HEIGHT);
+        }
+        Ok(theme)
+    }
+
+    /// Read the keys of a themerc file this renderer supports; unknown ones
+    /// are left to xfwm4
+    pub fn from_themerc(themerc: &str) -> Self {
+        let mut theme = Self::default();
+        let values: HashMap<&str, &str> = themerc
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+
+        let color = |key: &str| values.get(key).and_then(|value| parse_color(value));
+        let number = |key: &str| values.get(key).and_then(|value| value.parse::<i16>().ok());
+
+        if let Some(c) = color("active_color_1") {
+            theme.active.title = c;
+            theme.active.border = c;
+        }
+        if let Some(c) = color("inactive_color_1") {
+            theme.inactive.title = c;
+            theme.inactive.border = c;
+        }
+        if let Some(c) = color("active_border_color") {
+            theme.active.border = c;
+        }
+        if let Some(c) = color("inactive_border_color") {
+            theme.inactive.border = c;
+        }
+        if let Some(c) = color("active_text_color") {
+            theme.active.text = c;
+        }
+        if let Some(c) = color("inactive_text_color") {
+            theme.inactive.text = c;
+        }
+        if let Some(alignment) = values.get("title_alignment").and_then(|value| TitleAlignment::parse(value)) {
+            theme.title_alignment = alignment;
+        }
+        if let Some(offset) = number("title_horizontal_offset") {
+            theme.title_horizontal_offset = offset;
+        }
+        if let Some(layout) = values.get("button_layout") {
+            theme.button_layout = ButtonLayout::parse(layout);
+        }
+        if let Some(spacing) = number("button_spacing") {
+            theme.button_layout.spacing = spacing.max(0) as u16;
+        }
+        if let Some(offset) = number("button_offset") {
+            theme.button_layout.offset = offset.max(0) as u16;
+        }
+        theme
+    }
+
+    pub fn colors(&self, active: bool) -> StateColors {
+        if active {
+            self.active
+        } else {
+            self.inactive
+        }
+    }
+}
+
+/// `#rrggbb` or `#rgb`
+fn parse_color(value: &str) -> Option<u32> {
+    let hex = value.strip_prefix('#')?;
+    match hex.len() {
+        6 => u32::from_str_radix(hex, 16).ok(),
+        3 => {
+            let c = u32::from_str_radix(hex, 16).ok()?;
+            let (r, g, b) = ((c >> 8) & 0xf, (c >> 4) & 0xf, c & 0xf);
+            Some(((r * 0x11) << 16) | ((g * 0x11) << 8) | (b * 0x11))
+        }
+        _ => None,
+    }
+}
+
+/// Core X font name for a Pango font description such as "Sans Bold 9",
+/// the format of xfwm4's `/general/title_font`
+pub fn xlfd_from_pango(description: &str) -> String {
+    let mut words: Vec<&str> = description.split_whitespace().collect();
+    let size = words.last().and_then(|w| w.parse::<f32>().ok());
+    if size.is_some() {
+        words.pop();
+    }
+    let mut weight = "medium";
+    let mut slant = "r";
+    words.retain(|word| match word.to_ascii_lowercase().as_str() {
+        "bold" => {
+            weight = "bold";
+            false
+        }
+        "italic" => {
+            slant = "i";
+            false
+        }
+        "oblique" => {
+            slant = "o";
+            false
+        }
+        _ => true,
+    });
+    let family = if words.is_empty() { "*".to_string() } else { words.join(" ").to_ascii_lowercase() };
+    let points = size.map_or("*".to_string(), |size| ((size * 10.0).round() as u32).to_string());
+    format!("-*-{}-{}-{}-normal--*-{}-*-*-*-*-iso8859-1", family, weight, slant, points)
+}
+
+/// `<theme>/xfwm4` in the user's and the system's theme directories
+fn find_theme_dir(name: &str) -> Option<PathBuf> {
+    let mut bases = Vec::new();
+    if let Some(data) = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        bases.push(data.join("themes"));
+    }
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        bases.push(home.join(".local/share/themes"));
+        bases.push(home.join(".themes"));
+    }
+    let data_dirs = std::env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+    bases.extend(data_dirs.split(':').filter(|d| !d.is_empty()).map(|d| Path::new(d).join("themes")));
+
+    bases.into_iter().map(|base| base.join(name).join("xfwm4")).find(|dir| dir.join("themerc").is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_button_layout() {
+        // The layout the frames always had: close, maximize and minimize
+        // 20 px apart from the right edge
+        let layout = ButtonLayout::default();
+        assert_eq!(
+            layout.positions(808),
+            [(FramePart::CloseButton, 788), (FramePart::MaximizeButton, 768), (FramePart::MinimizeButton, 748)]
+        );
+        assert_eq!(layout.button_at(808, 790, 8), Some(FramePart::CloseButton));
+        assert_eq!(layout.button_at(808, 790, 20), None);
+
+        let layout = ButtonLayout::parse("CH|M");
+        assert_eq!(layout.button_at(400, 10, 8), Some(FramePart::CloseButton));
+        assert_eq!(layout.button_at(400, 30, 8), Some(FramePart::MinimizeButton));
+        assert_eq!(layout.button_at(400, 385, 8), Some(FramePart::MaximizeButton));
+        assert_eq!(layout.title_span(400), (48, 372));
+
+        // xfwm4's default: the menu alone on the left
+        let layout = ButtonLayout::parse("O|SHMC");
+        assert_eq!(layout.left, [FramePart::MenuButton]);
+        assert_eq!(
+            layout.right,
+            [FramePart::ShadeButton, FramePart::MinimizeButton, FramePart::MaximizeButton, FramePart::CloseButton]
+        );
+        assert_eq!(layout.button_at(400, 10, 8), Some(FramePart::MenuButton));
+        assert_eq!(layout.button_at(400, 320, 8), Some(FramePart::ShadeButton));
+        assert_eq!(ButtonState::resting(false), ButtonState::Inactive);
+    }
+
+    #[test]
+    fn test_themerc() {
+        let themerc = "\
+# A theme
+active_text_color=#ffffff
+inactive_text_color=#888
+active_color_1=#204a87
+title_alignment=center
+button_layout=O|HMC
+button_spacing=2
+unknown_key=whatever
+";
+        let theme = Theme::from_themerc(themerc);
+        assert_eq!(theme.active, StateColors { title: 0x204a87, text: 0xffffff, border: 0x204a87 });
+        assert_eq!(theme.inactive.text, 0x888888);
+        assert_eq!(theme.inactive.title, Theme::default().inactive.title);
+        assert_eq!(theme.title_alignment, TitleAlignment::Center);
+        assert_eq!(theme.button_layout.left, [FramePart::MenuButton]);
+        assert_eq!(theme.button_layout.right.len(), 3);
+        assert_eq!(theme.button_layout.spacing, 2);
+    }
+
+    #[test]
+    fn test_xlfd_from_pango() {
+        assert_eq!(xlfd_from_pango("Sans Bold 9"), "-*-sans-bold-r-normal--*-90-*-*-*-*-iso8859-1");
+        assert_eq!(xlfd_from_pango("DejaVu Serif Italic 10.5"), "-*-dejavu serif-medium-i-normal--*-105-*-*-*-*-iso8859-1");
+        assert_eq!(xlfd_from_pango("Monospace"), "-*-monospace-medium-r-normal--*-*-*-*-*-*-iso8859-1");
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use x11rb::protocol::xproto::{Rectangle, Window};
+
+use crate::ewmh::desktops::MAX_WORKSPACES;
+use crate::window::client::Client;
+use crate::window::LAYER_NORMAL;
+
+/// Part of its monitor's work area a window is tiled to, from a shortcut or
+/// by dragging it against a screen edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapZone {
+    None,
+    Left,
+    Right,
+    /// Maximized
+    Top,
+    TopHalf,
+    BottomHalf,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    LeftThird,
+    CenterThird,
+    RightThird,
+}
+
+impl SnapZone {
+    /// Where the frame goes in `area`; `None` for no zone and for the top,
+    /// which maximizes instead. Odd pixels go to the right and bottom.
+    pub fn frame_rect(self, area: Rectangle) -> Option<Rectangle> {
+        let (half_w, half_h, third) = (area.width / 2, area.height / 2, area.width / 3);
+        let rect = |x: u16, y: u16, width: u16, height: u16| Rectangle { x: area.x + x as i16, y: area.y + y as i16, width, height };
+        let (right_w, bottom_h) = (area.width - half_w, area.height - half_h);
+        Some(match self {
+            SnapZone::None | SnapZone::Top => return None,
+            SnapZone::Left => rect(0, 0, half_w, area.height),
+            SnapZone::Right => rect(half_w, 0, right_w, area.height),
+            SnapZone::TopHalf => rect(0, 0, area.width, half_h),
+            SnapZone::BottomHalf => rect(0, half_h, area.width, bottom_h),
+            SnapZone::TopLeft => rect(0, 0, half_w, half_h),
+            SnapZone::TopRight => rect(half_w, 0, right_w, half_h),
+            SnapZone::BottomLeft => rect(0, half_h, half_w, bottom_h),
+            SnapZone::BottomRight => rect(half_w, half_h, right_w, bottom_h),
+            SnapZone::LeftThird => rect(0, 0, third, area.height),
+            SnapZone::CenterThird => rect(third, 0, third, area.height),
+            SnapZone::RightThird => rect(2 * third, 0, area.width - 2 * third, area.height),
+        })
+    }
+}
+
+/// Frames of `count` windows laid out in `area`: the master on the left,
+/// `ratio` of the width, and the others stacked on the right
+pub fn master_stack(area: Rectangle, count: usize, ratio: f64) -> Vec<Rectangle> {
+    if count <= 1 {
+        return (count == 1).then_some(area).into_iter().collect();
+    }
+    let master_w = (area.width as f64 * ratio.clamp(0.1, 0.9)) as u16;
+    let stack_w = area.width - master_w;
+    let stacked = (count - 1) as u16;
+    let row_h = area.height / stacked;
+    let mut rects = vec![Rectangle { x: area.x, y: area.y, width: master_w, height: area.height }];
+    rects.extend((0..stacked).map(|row| Rectangle {
+        x: area.x + master_w as i16,
+        y: area.y + (row * row_h) as i16,
+        width: stack_w,
+        // The last one takes what dividing left over
+        height: if row + 1 == stacked { area.height - row * row_h } else { row_h },
+    }));
+    rects
+}
+
+/// Whether `client` takes part in the automatic layout: normal windows
+/// that can be resized, shown on one workspace
+pub fn is_tileable(client: &Client) -> bool {
+    client.frame.is_some()
+        && client.layer == LAYER_NORMAL
+        && !client.is_desktop
+        && !client.is_dock
+        && !client.is_minimized
+        && !client.is_maximized
+        && !client.is_fullscreen
+        && !client.is_sticky
+        && !client.is_modal
+        && client.transient_for.is_none()
+        && !client.size_hints.is_fixed()
+}
+
+/// The automatic master/stack layout: which workspaces use it and the
+/// order windows take their places in
+#[derive(Debug, Clone)]
+pub struct Tiling {
+    workspaces: HashSet<u32>,
+    /// Share of the width the master gets
+    pub master_ratio: f64,
+    /// Managed windows, oldest first
+    order: Vec<Window>,
+}
+
+impl Tiling {
+    /// Tiling on every workspace when `enabled`, else on none until
+    /// toggled
+    pub fn new(enabled: bool, master_ratio: f64) -> Self {
+        let workspaces = if enabled { (0..MAX_WORKSPACES).collect() } else { HashSet::new() };
+        Self { workspaces, master_ratio, order: Vec::new() }
+    }
+
+    pub fn is_enabled(&self, workspace: u32) -> bool {
+        self.workspaces.contains(&workspace)
+    }
+
+    /// Turn the layout on or off for `workspace`, returning whether it is on
+    pub fn toggle(&mut self, workspace: u32) -> bool {
+        if !self.workspaces.remove(&workspace) {
+            self.workspaces.insert(workspace);
+        }
+        self.is_enabled(workspace)
+    }
+
+    /// A new window, which goes to the end of the stack
+    pub fn add(&mut self, window: Window) {
+        self.order.retain(|&w| w != window);
+        self.order.push(window);
+    }
+
+    pub fn remove(&mut self, window: Window) {
+        self.order.retain(|&w| w != window);
+    }
+
+    /// `windows` in layout order, the master first
+    pub fn ordered(&self, mut windows: Vec<Window>) -> Vec<Window> {
+        windows.sort_by_key(|w| self.order.iter().position(|o| o == w).unwrap_or(usize::MAX));
+        windows
+    }
+
+    /// Move each of `windows`, in layout order, up a place: the master
+    /// goes after the last
+    pub fn rotate(&mut self, windows: &[Window]) {
+        let [first, .., last] = windows else { return };
+        self.order.retain(|w| w != first);
+        let after = self.order.iter().position(|w| w == last).map_or(self.order.len(), |i| i + 1);
+        self.order.insert(after, *first);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rectangle = Rectangle { x: 0, y: 30, width: 1921, height: 1051 };
+
+    #[test]
+    fn test_snap_zones() {
+        let rect = |x, y, width, height| Some(Rectangle { x, y, width, height });
+        assert_eq!(SnapZone::Left.frame_rect(AREA), rect(0, 30, 960, 1051));
+        assert_eq!(SnapZone::Right.frame_rect(AREA), rect(960, 30, 961, 1051));
+        assert_eq!(SnapZone::BottomHalf.frame_rect(AREA), rect(0, 555, 1921, 526));
+        assert_eq!(SnapZone::TopRight.frame_rect(AREA), rect(960, 30, 961, 525));
+        assert_eq!(SnapZone::CenterThird.frame_rect(AREA), rect(640, 30, 640, 1051));
+        assert_eq!(SnapZone::RightThird.frame_rect(AREA), rect(1280, 30, 641, 1051));
+        assert_eq!(SnapZone::Top.frame_rect(AREA), None);
+    }
+
+    #[test]
+    fn test_master_stack() {
+        assert!(master_stack(AREA, 0, 0.5).is_empty());
+        assert_eq!(master_stack(AREA, 1, 0.5), [AREA]);
+        let rects = master_stack(AREA, 3, 0.6);
+        assert_eq!(rects[0], Rectangle { x: 0, y: 30, width: 1152, height: 1051 });
+        assert_eq!(rects[1], Rectangle { x: 1152, y: 30, width: 769, height: 525 });
+        assert_eq!(rects[2], Rectangle { x: 1152, y: 555, width: 769, height: 526 });
+    }
+
+    #[test]
+    fn test_layout_order() {
+        let mut tiling = Tiling::new(false, 0.5);
+        assert!(!tiling.is_enabled(0));
+        assert!(tiling.toggle(0));
+        for window in [1, 2, 3, 4] {
+            tiling.add(window);
+        }
+        tiling.remove(2);
+        assert_eq!(tiling.ordered(vec![4, 9, 1, 3]), [1, 3, 4, 9]);
+
+        // Cycling the windows of one workspace leaves the others be
+        tiling.rotate(&[1, 3]);
+        assert_eq!(tiling.ordered(vec![1, 3, 4]), [3, 1, 4]);
+        tiling.rotate(&[3, 1]);
+        assert_eq!(tiling.ordered(vec![1, 3, 4]), [1, 3, 4]);
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ButtonIndex, ConnectionExt, EventMask, GrabMode, KeyButMask, ModMask, Rectangle, Window};
+
+use crate::window::keybindings::IGNORED_MODIFIERS;
+use crate::window::settings::Settings;
+
+/// How much one scroll step zooms in or out
+const STEP: f64 = 1.25;
+/// Share of the way to the pointer the view pans each frame, so it glides
+/// rather than jumps
+const PAN_EASE: f64 = 0.35;
+
+/// Desktop zoom: Super+scroll magnifies the screen around the pointer,
+/// for those who need things bigger than they are.
+///
+/// The point under the pointer stays under it, so what is clicked is what
+/// the magnified view shows there, and moving the pointer pans the view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zoom {
+    /// `/general/zoom_desktop`
+    pub enabled: bool,
+    /// `/general/zoom_max_factor`
+    pub max_factor: f64,
+    /// 1 when not zoomed
+    factor: f64,
+    /// Top left of the magnified part of the screen, easing towards where
+    /// the pointer wants it
+    origin: (f64, f64),
+}
+
+impl Zoom {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            enabled: settings.zoom_desktop,
+            max_factor: f64::from(settings.zoom_max_factor),
+            factor: 1.0,
+            origin: (0.0, 0.0),
+        }
+    }
+
+    /// Take Super+scroll on `root`, whatever the lock keys
+    pub fn grab<C: Connection>(&self, conn: &C, root: Window) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        for button in [ButtonIndex::M4, ButtonIndex::M5] {
+            for ignored in IGNORED_MODIFIERS {
+                let modifiers = ModMask::from(u16::from(ModMask::M4) | ignored);
+                conn.grab_button(false, root, EventMask::BUTTON_PRESS, GrabMode::ASYNC, GrabMode::ASYNC, x11rb::NONE, x11rb::NONE, button, modifiers)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a press of `button` with `state` is a zoom step
+    pub fn is_zoom_button(&self, button: u8, state: u16) -> bool {
+        self.enabled && matches!(button, 4 | 5) && state & u16::from(KeyButMask::MOD4) != 0
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.factor > 1.0
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    /// Zoom in (`true`, scroll up) or out a step with the pointer at
+    /// `pointer` on a `screen` sized screen; returns whether the factor
+    /// changed
+    pub fn scroll(&mut self, zoom_in: bool, pointer: (i16, i16), screen: (u16, u16)) -> bool {
+        let was = self.factor;
+        let factor = if zoom_in { self.factor * STEP } else { self.factor / STEP };
+        // Snap back to 1 rather than stop a hair above it
+        self.factor = if factor < 1.0 + (STEP - 1.0) / 2.0 { 1.0 } else { factor.min(self.max_factor.max(1.0)) };
+        // Zooming keeps the point under the pointer still; only panning
+        // glides
+        self.origin = self.wanted_origin(pointer, screen);
+        self.factor != was
+    }
+
+    /// Back to 1:1
+    pub fn reset(&mut self) {
+        self.factor = 1.0;
+    }
+
+    /// Where the view's origin keeps the point under `pointer` there
+    fn wanted_origin(&self, pointer: (i16, i16), screen: (u16, u16)) -> (f64, f64) {
+        let keep = |p: i16, size: u16| f64::from(p).clamp(0.0, f64::from(size)) * (1.0 - 1.0 / self.factor);
+        (keep(pointer.0, screen.0), keep(pointer.1, screen.1))
+    }
+
+    /// Pan a frame's worth towards the pointer; returns whether the view
+    /// moved
+    pub fn follow(&mut self, pointer: (i16, i16), screen: (u16, u16)) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        let (wx, wy) = self.wanted_origin(pointer, screen);
+        let ease = |from: f64, to: f64| if (to - from).abs() < 0.5 { to } else { from + (to - from) * PAN_EASE };
+        let origin = (ease(self.origin.0, wx), ease(self.origin.1, wy));
+        let moved = origin != self.origin;
+        self.origin = origin;
+        moved
+    }
+
+    /// The part of a `screen` sized screen shown magnified over all of it,
+    /// `None` when not zoomed
+    pub fn source(&self, screen: (u16, u16)) -> Option<Rectangle> {
+        if !self.is_active() {
+            return None;
+        }
+        let side = |size: u16| ((f64::from(size) / self.factor).round() as u16).max(1);
+        let (width, height) = (side(screen.0), side(screen.1));
+        let start = |origin: f64, size: u16, side: u16| origin.round().clamp(0.0, f64::from(size - side)) as i16;
+        Some(Rectangle { x: start(self.origin.0, screen.0, width), y: start(self.origin.1, screen.1, height), width, height })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: (u16, u16) = (1000, 800);
+
+    fn zoom() -> Zoom {
+        Zoom { enabled: true, ..Zoom::from_settings(&Settings::default()) }
+    }
+
+    #[test]
+    fn test_scroll_steps() {
+        let mut zoom = zoom();
+        assert!(!zoom.is_active());
+        assert_eq!(zoom.source(SCREEN), None);
+        // Can't zoom out past 1:1
+        assert!(!zoom.scroll(false, (0, 0), SCREEN));
+
+        assert!(zoom.scroll(true, (0, 0), SCREEN));
+        assert_eq!(zoom.factor(), STEP);
+        for _ in 0..100 {
+            zoom.scroll(true, (0, 0), SCREEN);
+        }
+        assert_eq!(zoom.factor(), zoom.max_factor);
+        assert!(!zoom.scroll(true, (0, 0), SCREEN));
+        for _ in 0..100 {
+            zoom.scroll(false, (0, 0), SCREEN);
+        }
+        assert_eq!(zoom.factor(), 1.0);
+        assert!(!zoom.is_active());
+    }
+
+    #[test]
+    fn test_source_keeps_pointer_in_place() {
+        let mut zoom = zoom();
+        zoom.max_factor = 2.0;
+        for _ in 0..4 {
+            zoom.scroll(true, (500, 400), SCREEN);
+        }
+        // Centered: the screen's middle stays in the middle
+        assert_eq!(zoom.source(SCREEN), Some(Rectangle { x: 250, y: 200, width: 500, height: 400 }));
+
+        zoom.reset();
+        for _ in 0..4 {
+            zoom.scroll(true, (1000, 800), SCREEN);
+        }
+        // In the corner, the view ends at the screen's edge
+        assert_eq!(zoom.source(SCREEN), Some(Rectangle { x: 500, y: 400, width: 500, height: 400 }));
+    }
+
+    #[test]
+    fn test_follow_glides() {
+        let mut zoom = zoom();
+        zoom.max_factor = 2.0;
+        for _ in 0..4 {
+            zoom.scroll(true, (0, 0), SCREEN);
+        }
+        assert_eq!(zoom.source(SCREEN).map(|r| (r.x, r.y)), Some((0, 0)));
+
+        // Part of the way each frame, then all of it
+        assert!(zoom.follow((1000, 800), SCREEN));
+        let first = zoom.source(SCREEN).unwrap();
+        assert!(first.x > 0 && first.x < 500);
+        let mut frames = 1;
+        while zoom.follow((1000, 800), SCREEN) {
+            frames += 1;
+        }
+        assert!(frames > 2);
+        assert_eq!(zoom.source(SCREEN).map(|r| (r.x, r.y)), Some((500, 400)));
+
+        zoom.reset();
+        assert!(!zoom.follow((0, 0), SCREEN));
+    }
+}
@@ -0,0 +1,74 @@
+//! XSETTINGS manager for XFCE.rs
+//!
+//! Publishes the `xsettings` channel (GTK theme, icon theme, fonts, cursor)
+//! over the XSETTINGS protocol so GTK 2/3 and Qt applications follow the
+//! desktop's appearance settings, mirrors the font rendering options and the
+//! cursor as X resources, and republishes whenever they change.
+//! Pass `--replace` to take over from a running xfsettingsd.
+
+use std::sync::Arc;
+
+use tracing::{error, info, warn};
+use xfce_rs_config::XfceConfig;
+
+mod manager;
+mod settings;
+
+use manager::XSettingsManager;
+use settings::{Settings, CHANNEL};
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let replace = std::env::args().skip(1).any(|arg| arg == "--replace");
+    let manager = match XSettingsManager::acquire(replace) {
+        Ok(manager) => manager,
+        Err(e) => {
+            error!("Cannot become the XSETTINGS manager: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let config = Arc::new(XfceConfig::default());
+    config.register_defaults(CHANNEL, settings::defaults()).await;
+    let _reloader = match config.watch_files() {
+        Ok(reloader) => Some(reloader),
+        Err(e) => {
+            warn!("Appearance changes made outside XFCE.rs will need a restart: {}", e);
+            None
+        }
+    };
+    let mut changes = config.watch(CHANNEL, "");
+
+    let mut published = Settings::default();
+    publish(&config, &manager, &mut published).await;
+
+    let mut lost = manager.lost();
+    loop {
+        tokio::select! {
+            Ok(_) = changes.recv() => {
+                publish(&config, &manager, &mut published).await;
+            }
+            _ = &mut lost => {
+                info!("Another XSETTINGS manager took over, exiting");
+                break;
+            }
+        }
+    }
+}
+
+async fn publish(config: &XfceConfig, manager: &XSettingsManager, published: &mut Settings) {
+    if !published.replace(settings::load(config).await) {
+        return;
+    }
+    match manager.publish(&published.encode()) {
+        Ok(()) => info!("Published XSETTINGS serial {}", published.serial()),
+        Err(e) => error!("Failed to publish XSETTINGS: {:#}", e),
+    }
+    if let Err(e) = manager.publish_resources(&published.resources()) {
+        error!("Failed to publish X resources: {:#}", e);
+    }
+}
@@ -0,0 +1,156 @@
+//! Ownership of the `_XSETTINGS_Sn` selection
+//!
+//! The manager owns the selection with a hidden window and keeps the
+//! encoded settings in that window's `_XSETTINGS_SETTINGS` property, which
+//! GTK and Qt clients read and watch for changes. Font rendering options
+//! and the cursor are also kept in the root window's `RESOURCE_MANAGER`
+//! for Xlib clients.
+
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::{bail, Context, Result};
+use tokio::sync::oneshot;
+use tracing::debug;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ClientMessageEvent, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Timestamp,
+    Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, NONE};
+
+use crate::settings;
+
+pub struct XSettingsManager {
+    conn: Arc<RustConnection>,
+    root: Window,
+    window: Window,
+    selection: Atom,
+    settings_atom: Atom,
+}
+
+impl XSettingsManager {
+    /// Take the selection for the default screen. Fails if another manager
+    /// holds it, unless `replace` is set.
+    pub fn acquire(replace: bool) -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X server")?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let selection = intern(&conn, &format!("_XSETTINGS_S{}", screen_num))?;
+        let settings_atom = intern(&conn, "_XSETTINGS_SETTINGS")?;
+        let manager_atom = intern(&conn, "MANAGER")?;
+
+        if !replace && conn.get_selection_owner(selection)?.reply()?.owner != NONE {
+            bail!("Another XSETTINGS manager is running, use --replace to take over");
+        }
+
+        let window = conn.generate_id()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new()
+                .override_redirect(1)
+                .event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+
+        let time = server_time(&conn, window, settings_atom)?;
+        conn.set_selection_owner(window, selection, time)?;
+        if conn.get_selection_owner(selection)?.reply()?.owner != window {
+            bail!("Failed to take the XSETTINGS selection");
+        }
+
+        // Tell clients waiting for a manager that one is here
+        let announce = ClientMessageEvent::new(32, root, manager_atom, [time, selection, window, 0, 0]);
+        conn.send_event(false, root, EventMask::STRUCTURE_NOTIFY, announce)?;
+        conn.flush()?;
+
+        Ok(Self {
+            conn: Arc::new(conn),
+            root,
+            window,
+            selection,
+            settings_atom,
+        })
+    }
+
+    /// Replace the published settings with `encoded`
+    pub fn publish(&self, encoded: &[u8]) -> Result<()> {
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            self.window,
+            self.settings_atom,
+            self.settings_atom,
+            encoded,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Replace the lines of `RESOURCE_MANAGER` this manager owns with
+    /// `resources`, keeping the others
+    pub fn publish_resources(&self, resources: &[(&str, String)]) -> Result<()> {
+        let existing = self
+            .conn
+            .get_property(false, self.root, AtomEnum::RESOURCE_MANAGER, AtomEnum::STRING, 0, u32::MAX)?
+            .reply()?
+            .value;
+        let merged = settings::merge_resources(&String::from_utf8_lossy(&existing), resources);
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            self.root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            merged.as_bytes(),
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Resolves when another manager takes the selection or the X
+    /// connection closes
+    pub fn lost(&self) -> oneshot::Receiver<()> {
+        let (sender, receiver) = oneshot::channel();
+        let conn = Arc::clone(&self.conn);
+        let selection = self.selection;
+        thread::spawn(move || {
+            loop {
+                match conn.wait_for_event() {
+                    Ok(Event::SelectionClear(event)) if event.selection == selection => break,
+                    Ok(event) => debug!("Ignoring X event {:?}", event),
+                    Err(_) => break,
+                }
+            }
+            let _ = sender.send(());
+        });
+        receiver
+    }
+}
+
+fn intern(conn: &RustConnection, name: &str) -> Result<Atom> {
+    Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+}
+
+/// A real server timestamp for the selection request, obtained by touching
+/// a property and reading the time of the resulting PropertyNotify
+fn server_time(conn: &RustConnection, window: Window, atom: Atom) -> Result<Timestamp> {
+    conn.change_property8(PropMode::APPEND, window, atom, atom, &[])?;
+    conn.flush()?;
+    loop {
+        if let Event::PropertyNotify(event) = conn.wait_for_event()? {
+            if event.window == window {
+                return Ok(event.time);
+            }
+        }
+    }
+}
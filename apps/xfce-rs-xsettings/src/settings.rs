@@ -0,0 +1,296 @@
+//! XSETTINGS values and their wire format
+//!
+//! Every property of the `xsettings` channel becomes the setting of the same
+//! name without the leading slash, so `/Net/ThemeName` is published as
+//! `Net/ThemeName`. See the XSETTINGS specification at
+//! <https://specifications.freedesktop.org/xsettings-spec/> for the layout
+//! [`Settings::encode`] produces.
+
+use std::collections::BTreeMap;
+
+use tracing::debug;
+use xfce_rs_config::{ConfigValue, XfceConfig};
+
+pub const CHANNEL: &str = "xsettings";
+
+/// Published as 1024 times the value; `-1` leaves the DPI to the X server
+const DPI_SETTING: &str = "Xft/DPI";
+
+/// Settings mirrored as resources in `RESOURCE_MANAGER`, for clients that
+/// read font rendering options and the cursor through Xlib rather than
+/// XSETTINGS. This is the only writer of these lines.
+const RESOURCES: [(&str, &str); 7] = [
+    ("Xft/Antialias", "Xft.antialias"),
+    ("Xft/Hinting", "Xft.hinting"),
+    ("Xft/HintStyle", "Xft.hintstyle"),
+    ("Xft/RGBA", "Xft.rgba"),
+    (DPI_SETTING, "Xft.dpi"),
+    ("Gtk/CursorThemeName", "Xcursor.theme"),
+    ("Gtk/CursorThemeSize", "Xcursor.size"),
+];
+
+/// Appearance settings published when the user has set nothing
+pub fn defaults() -> Vec<(String, ConfigValue)> {
+    [
+        ("/Net/ThemeName", ConfigValue::String("Adwaita-dark".to_string())),
+        ("/Net/IconThemeName", ConfigValue::String("Adwaita".to_string())),
+        ("/Gtk/FontName", ConfigValue::String("Sans 10".to_string())),
+        ("/Gtk/MonospaceFontName", ConfigValue::String("Monospace 10".to_string())),
+        ("/Xft/Antialias", ConfigValue::Integer(1)),
+        ("/Xft/Hinting", ConfigValue::Integer(1)),
+        ("/Xft/HintStyle", ConfigValue::String("hintslight".to_string())),
+        ("/Xft/RGBA", ConfigValue::String("none".to_string())),
+        ("/Gtk/CursorThemeName", ConfigValue::String("Adwaita".to_string())),
+        ("/Gtk/CursorThemeSize", ConfigValue::Integer(24)),
+        ("/Net/CursorBlink", ConfigValue::Boolean(true)),
+        ("/Net/CursorBlinkTime", ConfigValue::Integer(1200)),
+        ("/Net/SoundThemeName", ConfigValue::String("freedesktop".to_string())),
+        ("/Net/EnableEventSounds", ConfigValue::Boolean(true)),
+    ]
+    .into_iter()
+    .map(|(property, value)| (property.to_string(), value))
+    .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XSetting {
+    Int(i32),
+    String(String),
+    /// Red, green, blue and alpha, 16 bits each
+    Color([u16; 4]),
+}
+
+impl XSetting {
+    /// The setting for a configuration value. Booleans become 0 or 1;
+    /// arrays, dictionaries and fractional numbers have no XSETTINGS type.
+    pub fn from_config(name: &str, value: &ConfigValue) -> Option<Self> {
+        let setting = match value {
+            ConfigValue::Integer(dpi) if name == DPI_SETTING => {
+                Self::Int(if *dpi > 0 { (*dpi).saturating_mul(1024).min(i32::MAX.into()) as i32 } else { -1 })
+            }
+            ConfigValue::Integer(i) => Self::Int((*i).clamp(i32::MIN.into(), i32::MAX.into()) as i32),
+            ConfigValue::Boolean(b) => Self::Int(i32::from(*b)),
+            ConfigValue::String(s) => Self::String(s.clone()),
+            ConfigValue::Color(rgba) => Self::Color(rgba.to_rgba8().map(|c| u16::from(c) * 257)),
+            ConfigValue::Float(_) | ConfigValue::Array(_) | ConfigValue::Dict(_) => return None,
+        };
+        Some(setting)
+    }
+
+    fn type_code(&self) -> u8 {
+        match self {
+            Self::Int(_) => 0,
+            Self::String(_) => 1,
+            Self::Color(_) => 2,
+        }
+    }
+}
+
+/// Setting name for a property of the `xsettings` channel
+pub fn setting_name(property: &str) -> &str {
+    property.trim_start_matches('/')
+}
+
+/// Every property of the channel that has an XSETTINGS type
+pub async fn load(config: &XfceConfig) -> BTreeMap<String, XSetting> {
+    let mut settings = BTreeMap::new();
+    for property in config.list_properties(CHANNEL).await.unwrap_or_default() {
+        let Ok(value) = config.get_property(CHANNEL, &property).await else {
+            continue;
+        };
+        let name = setting_name(&property);
+        match XSetting::from_config(name, &value) {
+            Some(setting) => {
+                settings.insert(name.to_string(), setting);
+            }
+            None => debug!("{} cannot be published as an XSETTINGS value", property),
+        }
+    }
+    settings
+}
+
+/// The published settings with the serial each last changed in
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    serial: u32,
+    values: BTreeMap<String, (XSetting, u32)>,
+}
+
+impl Settings {
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    pub fn get(&self, name: &str) -> Option<&XSetting> {
+        self.values.get(name).map(|(setting, _)| setting)
+    }
+
+    /// Replace all settings. Returns true and starts a new serial if
+    /// anything was added, changed or removed.
+    pub fn replace(&mut self, settings: BTreeMap<String, XSetting>) -> bool {
+        let unchanged = settings.len() == self.values.len()
+            && settings.iter().all(|(name, setting)| self.get(name) == Some(setting));
+        if unchanged {
+            return false;
+        }
+
+        self.serial = self.serial.wrapping_add(1);
+        let previous = std::mem::take(&mut self.values);
+        self.values = settings
+            .into_iter()
+            .map(|(name, setting)| {
+                let last_change = match previous.get(&name) {
+                    Some((old, serial)) if *old == setting => *serial,
+                    _ => self.serial,
+                };
+                (name, (setting, last_change))
+            })
+            .collect();
+        true
+    }
+
+    /// The resources for the published settings; `-1` values are left to
+    /// fontconfig and, like empty strings, have none
+    pub fn resources(&self) -> Vec<(&'static str, String)> {
+        RESOURCES
+            .iter()
+            .filter_map(|&(name, resource)| {
+                let value = match self.get(name)? {
+                    XSetting::Int(value) if *value < 0 => return None,
+                    XSetting::String(value) if value.is_empty() => return None,
+                    XSetting::Int(dpi) if name == DPI_SETTING => (dpi / 1024).to_string(),
+                    XSetting::Int(value) => value.to_string(),
+                    XSetting::String(value) => value.clone(),
+                    XSetting::Color(_) => return None,
+                };
+                Some((resource, value))
+            })
+            .collect()
+    }
+
+    /// Contents of the `_XSETTINGS_SETTINGS` property, little-endian
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![0, 0, 0, 0]; // LSBFirst, then padding
+        out.extend(self.serial.to_le_bytes());
+        out.extend((self.values.len() as u32).to_le_bytes());
+
+        for (name, (setting, last_change)) in &self.values {
+            out.push(setting.type_code());
+            out.push(0);
+            out.extend((name.len() as u16).to_le_bytes());
+            push_padded(&mut out, name.as_bytes());
+            out.extend(last_change.to_le_bytes());
+
+            match setting {
+                XSetting::Int(value) => out.extend(value.to_le_bytes()),
+                XSetting::String(value) => {
+                    out.extend((value.len() as u32).to_le_bytes());
+                    push_padded(&mut out, value.as_bytes());
+                }
+                XSetting::Color([red, green, blue, alpha]) => {
+                    // The specification orders the channels red, blue, green
+                    for channel in [red, blue, green, alpha] {
+                        out.extend(channel.to_le_bytes());
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// `resources` with the lines this manager owns replaced by `owned`
+pub fn merge_resources(resources: &str, owned: &[(&str, String)]) -> String {
+    let mut merged: Vec<String> = resources
+        .lines()
+        .filter(|line| {
+            let key = line.split(':').next().unwrap_or("").trim();
+            !RESOURCES.iter().any(|&(_, resource)| resource == key)
+        })
+        .map(str::to_string)
+        .collect();
+
+    merged.extend(owned.iter().map(|(resource, value)| format!("{}:\t{}", resource, value)));
+    merged.join("\n") + "\n"
+}
+
+/// Append `bytes` zero-padded to a multiple of four
+fn push_padded(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend(bytes);
+    out.resize(out.len() + (4 - bytes.len() % 4) % 4, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(pairs: &[(&str, XSetting)]) -> BTreeMap<String, XSetting> {
+        pairs.iter().map(|(name, setting)| (name.to_string(), setting.clone())).collect()
+    }
+
+    #[test]
+    fn test_encode_layout() {
+        let mut published = Settings::default();
+        published.replace(settings(&[
+            ("Net/ThemeName", XSetting::String("Adwaita".to_string())),
+            ("Xft/DPI", XSetting::Int(-1)),
+        ]));
+
+        let bytes = published.encode();
+        assert_eq!(&bytes[..12], &[0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+        // "Net/ThemeName" is 13 bytes, padded to 16; "Adwaita" is 7, padded to 8
+        assert_eq!(&bytes[12..16], &[1, 0, 13, 0]);
+        assert_eq!(&bytes[16..29], b"Net/ThemeName");
+        assert_eq!(&bytes[32..40], &[1, 0, 0, 0, 7, 0, 0, 0]);
+        assert_eq!(&bytes[40..47], b"Adwaita");
+        assert_eq!(&bytes[48..52], &[0, 0, 7, 0]);
+        assert_eq!(&bytes[bytes.len() - 4..], &(-1i32).to_le_bytes());
+        assert_eq!(bytes.len(), 48 + 4 + 8 + 4 + 4);
+    }
+
+    #[test]
+    fn test_replace_tracks_last_change_serial() {
+        let mut published = Settings::default();
+        let theme = |name: &str| ("Net/ThemeName", XSetting::String(name.to_string()));
+        let size = ("Gtk/CursorThemeSize", XSetting::Int(24));
+
+        assert!(published.replace(settings(&[theme("Adwaita"), size.clone()])));
+        assert!(!published.replace(settings(&[theme("Adwaita"), size.clone()])));
+        assert!(published.replace(settings(&[theme("Greybird"), size.clone()])));
+        assert_eq!(published.serial(), 2);
+        assert_eq!(published.values["Net/ThemeName"].1, 2);
+        assert_eq!(published.values["Gtk/CursorThemeSize"].1, 1);
+
+        assert!(published.replace(settings(&[theme("Greybird")])));
+        assert_eq!(published.get("Gtk/CursorThemeSize"), None);
+    }
+
+    #[test]
+    fn test_config_values_map_to_settings() {
+        assert_eq!(XSetting::from_config("Xft/DPI", &ConfigValue::Integer(96)), Some(XSetting::Int(96 * 1024)));
+        assert_eq!(XSetting::from_config("Xft/DPI", &ConfigValue::Integer(-1)), Some(XSetting::Int(-1)));
+        assert_eq!(XSetting::from_config("Net/CursorBlink", &ConfigValue::Boolean(true)), Some(XSetting::Int(1)));
+        assert_eq!(XSetting::from_config("Gtk/Scale", &ConfigValue::Float(1.5)), None);
+        assert_eq!(setting_name("/Net/ThemeName"), "Net/ThemeName");
+    }
+
+    #[test]
+    fn test_resources_replace_old_ones() {
+        let mut published = Settings::default();
+        published.replace(settings(&[
+            ("Xft/Antialias", XSetting::Int(1)),
+            ("Xft/Hinting", XSetting::Int(-1)),
+            ("Xft/HintStyle", XSetting::String("hintfull".to_string())),
+            ("Xft/DPI", XSetting::Int(144 * 1024)),
+            ("Gtk/CursorThemeName", XSetting::String(String::new())),
+            ("Gtk/CursorThemeSize", XSetting::Int(32)),
+        ]));
+
+        let resources = published.resources();
+        let existing = "Xcursor.size:\t24\nXcursor.theme:\tAdwaita\nXft.hinting:\t1\nXft.dpi:\t96\nEmacs.font:\tMono\n";
+        assert_eq!(
+            merge_resources(existing, &resources),
+            "Emacs.font:\tMono\nXft.antialias:\t1\nXft.hintstyle:\thintfull\nXft.dpi:\t144\nXcursor.size:\t32\n"
+        );
+    }
+}
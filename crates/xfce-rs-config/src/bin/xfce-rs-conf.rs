@@ -0,0 +1,400 @@
+//! Command line access to the XFCE.rs configuration store, mirroring
+//! `xfconf-query`
+
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use xfce_rs_config::{ConfigError, ConfigEventKind, ConfigValue, Rgba, XfceConfig};
+
+const USAGE: &str = "Usage:
+  xfce-rs-conf [OPTION...] - query and change XFCE.rs settings
+
+Options:
+  -c, --channel NAME     The channel to query or modify
+  -p, --property NAME    The property to query or modify
+  -s, --set VALUE        The new value to set; repeat for arrays
+  -t, --type TYPE        Type of the value(s) being set
+  -n, --create           Create the property if it does not exist
+  -a, --force-array      Store a single value as an array
+  -T, --toggle           Invert an existing boolean property
+  -r, --reset            Reset the property to its default
+  -R, --recursive        Reset every property below the given one
+  -l, --list             List channels, or properties of the channel
+  -v, --verbose          Print values when listing
+  -m, --monitor          Print changes to the channel as they happen
+  -V, --version          Print the version and exit
+  -h, --help             Print this help and exit";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    channel: Option<String>,
+    property: Option<String>,
+    values: Vec<String>,
+    types: Vec<String>,
+    create: bool,
+    force_array: bool,
+    toggle: bool,
+    reset: bool,
+    recursive: bool,
+    list: bool,
+    verbose: bool,
+    monitor: bool,
+    version: bool,
+    help: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("Missing argument for {}", name))
+        };
+
+        match flag.as_str() {
+            "-c" | "--channel" => options.channel = Some(value(&flag)?),
+            "-p" | "--property" => options.property = Some(value(&flag)?),
+            "-s" | "--set" => options.values.push(value(&flag)?),
+            "-t" | "--type" => options.types.push(value(&flag)?),
+            "-n" | "--create" => options.create = true,
+            "-a" | "--force-array" => options.force_array = true,
+            "-T" | "--toggle" => options.toggle = true,
+            "-r" | "--reset" => options.reset = true,
+            "-R" | "--recursive" => options.recursive = true,
+            "-l" | "--list" => options.list = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "-m" | "--monitor" => options.monitor = true,
+            "-V" | "--version" => options.version = true,
+            "-h" | "--help" => options.help = true,
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+/// Name of the `-t` type that describes `value`
+fn type_name(value: &ConfigValue) -> &'static str {
+    match value {
+        ConfigValue::String(_) => "string",
+        ConfigValue::Integer(_) => "int",
+        ConfigValue::Boolean(_) => "bool",
+        ConfigValue::Float(_) => "double",
+        ConfigValue::Color(_) => "color",
+        ConfigValue::Array(_) => "array",
+        ConfigValue::Dict(_) => "dict",
+    }
+}
+
+fn parse_value(kind: &str, text: &str) -> Result<ConfigValue, String> {
+    let invalid = || format!("Unable to convert \"{}\" to type \"{}\"", text, kind);
+
+    match kind {
+        "string" => Ok(ConfigValue::String(text.to_string())),
+        "int" | "int64" | "int16" | "char" => text.parse().map(ConfigValue::Integer).map_err(|_| invalid()),
+        "uint" | "uint64" | "uint16" | "uchar" => text
+            .parse::<u32>()
+            .map(|n| ConfigValue::Integer(i64::from(n)))
+            .or_else(|_| text.parse::<u64>().ok().and_then(|n| i64::try_from(n).ok()).map(ConfigValue::Integer).ok_or(()))
+            .map_err(|_| invalid()),
+        "double" | "float" => text.parse().map(ConfigValue::Float).map_err(|_| invalid()),
+        "bool" => match text.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => Ok(ConfigValue::Boolean(true)),
+            "false" | "no" | "0" => Ok(ConfigValue::Boolean(false)),
+            _ => Err(invalid()),
+        },
+        "color" | "rgba" => Rgba::from_hex(text).map(ConfigValue::Color).ok_or_else(invalid),
+        _ => Err(format!("Unknown value type \"{}\"", kind)),
+    }
+}
+
+/// Build the value for `-s`, taking types from `-t` or the current value
+fn build_value(options: &Options, current: Option<&ConfigValue>) -> Result<ConfigValue, String> {
+    let array = options.values.len() > 1 || options.force_array;
+
+    let types: Vec<String> = if !options.types.is_empty() {
+        options.types.clone()
+    } else {
+        match current {
+            Some(ConfigValue::Array(items)) if array => items.iter().map(|item| type_name(item).to_string()).collect(),
+            Some(value) if !array => vec![type_name(value).to_string()],
+            Some(_) => return Err("The value type must be given to change between arrays and single values".to_string()),
+            None => return Err("When creating a new property, the value type must be specified".to_string()),
+        }
+    };
+
+    let type_for = |index: usize| -> Result<&str, String> {
+        match types.len() {
+            1 => Ok(types[0].as_str()),
+            n if n == options.values.len() => Ok(types[index].as_str()),
+            _ => Err("The number of values and types must match".to_string()),
+        }
+    };
+
+    if array {
+        let items = options
+            .values
+            .iter()
+            .enumerate()
+            .map(|(index, text)| parse_value(type_for(index)?, text))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ConfigValue::Array(items))
+    } else {
+        parse_value(type_for(0)?, &options.values[0])
+    }
+}
+
+fn format_value(value: &ConfigValue) -> String {
+    match value {
+        ConfigValue::String(s) => s.clone(),
+        ConfigValue::Integer(i) => i.to_string(),
+        ConfigValue::Boolean(b) => b.to_string(),
+        ConfigValue::Float(f) => format!("{:.6}", f),
+        ConfigValue::Color(color) => color.to_hex(),
+        ConfigValue::Array(items) => {
+            let mut text = format!("Value is an array with {} items:\n", items.len());
+            for item in items {
+                text.push('\n');
+                text.push_str(&format_value(item));
+            }
+            text
+        }
+        ConfigValue::Dict(entries) => {
+            let mut keys: Vec<&String> = entries.keys().collect();
+            keys.sort();
+            keys.iter()
+                .map(|key| format!("{}: {}", key, format_value(&entries[*key])))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+}
+
+/// One line per array item, so `-l -v` stays one property per line
+fn format_inline(value: &ConfigValue) -> String {
+    match value {
+        ConfigValue::Array(items) => {
+            let items: Vec<String> = items.iter().map(format_inline).collect();
+            format!("[{}]", items.join(","))
+        }
+        ConfigValue::Dict(_) => format!("{{{}}}", format_value(value).replace('\n', ", ")),
+        other => format_value(other),
+    }
+}
+
+async fn run(options: Options) -> Result<(), String> {
+    let config = Arc::new(XfceConfig::default());
+    let error = |e: ConfigError| e.to_string();
+
+    let Some(channel) = options.channel.as_deref() else {
+        if options.list {
+            let mut channels = config.list_channels().await;
+            channels.sort();
+            println!("Channels:");
+            for channel in channels {
+                println!("  {}", channel);
+            }
+            return Ok(());
+        }
+        return Err("No channel specified".to_string());
+    };
+
+    if options.monitor {
+        return monitor(&config, channel, options.property.as_deref().unwrap_or("")).await;
+    }
+
+    if options.list {
+        let prefix = options.property.as_deref().unwrap_or("");
+        let mut properties: Vec<String> = match config.list_properties(channel).await {
+            Ok(properties) => properties,
+            Err(ConfigError::PropertyNotFound { .. }) => {
+                return Err(format!("Channel \"{}\" contains no properties", channel))
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        properties.retain(|property| property.starts_with(prefix));
+        properties.sort();
+
+        let width = properties.iter().map(String::len).max().unwrap_or(0);
+        for property in properties {
+            if options.verbose {
+                let value = config.get_property(channel, &property).await.map_err(error)?;
+                println!("{:width$}  {}", property, format_inline(&value), width = width);
+            } else {
+                println!("{}", property);
+            }
+        }
+        return Ok(());
+    }
+
+    let Some(property) = options.property.as_deref() else {
+        return Err("No property specified".to_string());
+    };
+
+    if options.reset {
+        let mut targets = vec![property.to_string()];
+        if options.recursive {
+            let prefix = format!("{}/", property.trim_end_matches('/'));
+            if let Ok(properties) = config.list_properties(channel).await {
+                targets.extend(properties.into_iter().filter(|name| name.starts_with(&prefix)));
+            }
+        }
+        for target in targets {
+            config.reset_property(channel, &target).await.map_err(error)?;
+        }
+        return config.save().await.map_err(error);
+    }
+
+    let current = match config.get_property(channel, property).await {
+        Ok(value) => Some(value),
+        Err(ConfigError::PropertyNotFound { .. }) => None,
+        Err(e) => return Err(e.to_string()),
+    };
+    let missing = || {
+        format!(
+            "Property \"{}\" does not exist on channel \"{}\". If a new property should be created, use the --create option",
+            property, channel
+        )
+    };
+
+    if options.toggle {
+        let value = match current {
+            Some(ConfigValue::Boolean(value)) => !value,
+            Some(_) => return Err("Only boolean values can be toggled".to_string()),
+            None => return Err(missing()),
+        };
+        config.set_property(channel, property, ConfigValue::Boolean(value)).await.map_err(error)?;
+        return config.save().await.map_err(error);
+    }
+
+    if !options.values.is_empty() {
+        if current.is_none() && !options.create {
+            return Err(missing());
+        }
+        let value = build_value(&options, current.as_ref())?;
+        config.set_property(channel, property, value).await.map_err(error)?;
+        return config.save().await.map_err(error);
+    }
+
+    match current {
+        Some(value) => {
+            println!("{}", format_value(&value));
+            Ok(())
+        }
+        None => Err(format!("Property \"{}\" does not exist on channel \"{}\"", property, channel)),
+    }
+}
+
+async fn monitor(config: &Arc<XfceConfig>, channel: &str, prefix: &str) -> Result<(), String> {
+    let _reloader = config.watch_files().map_err(|e| e.to_string())?;
+    let mut events = config.watch(channel, prefix);
+    println!("Start monitoring channel \"{}\":\n", channel);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return Ok(()),
+                };
+                match (event.kind, &event.new_value) {
+                    (ConfigEventKind::Set, Some(value)) => {
+                        println!("set: {} ({})", event.property, format_inline(value))
+                    }
+                    (ConfigEventKind::Reset, _) => println!("reset: {}", event.property),
+                    _ => println!("removed: {}", event.property),
+                }
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if options.version {
+        println!("xfce-rs-conf {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+
+    match run(options).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args(&["-c", "xfwm4", "--property=/general/theme", "-s", "Greybird", "-n", "-t", "string"])).unwrap();
+        assert_eq!(options.channel.as_deref(), Some("xfwm4"));
+        assert_eq!(options.property.as_deref(), Some("/general/theme"));
+        assert_eq!(options.values, ["Greybird"]);
+        assert_eq!(options.types, ["string"]);
+        assert!(options.create);
+
+        assert!(parse_args(args(&["-c"])).is_err());
+        assert!(parse_args(args(&["--bogus"])).is_err());
+    }
+
+    #[test]
+    fn test_build_value() {
+        let mut options = parse_args(args(&["-s", "1", "-s", "two", "-t", "int", "-t", "string"])).unwrap();
+        assert_eq!(
+            build_value(&options, None).unwrap(),
+            ConfigValue::Array(vec![ConfigValue::Integer(1), ConfigValue::String("two".to_string())])
+        );
+
+        // Without -t the current type is kept
+        options = parse_args(args(&["-s", "24"])).unwrap();
+        assert_eq!(build_value(&options, Some(&ConfigValue::Float(1.0))).unwrap(), ConfigValue::Float(24.0));
+        assert!(build_value(&options, None).is_err());
+
+        options = parse_args(args(&["-s", "-1", "-t", "uint"])).unwrap();
+        assert!(build_value(&options, None).is_err());
+
+        options = parse_args(args(&["-s", "#ff0000", "-t", "color", "-a"])).unwrap();
+        assert_eq!(
+            build_value(&options, None).unwrap(),
+            ConfigValue::Array(vec![ConfigValue::Color(Rgba::new(1.0, 0.0, 0.0, 1.0))])
+        );
+    }
+
+    #[test]
+    fn test_format_value() {
+        let array = ConfigValue::Array(vec![ConfigValue::Integer(1), ConfigValue::Boolean(true)]);
+        assert_eq!(format_value(&array), "Value is an array with 2 items:\n\n1\ntrue");
+        assert_eq!(format_inline(&array), "[1,true]");
+        assert_eq!(format_value(&ConfigValue::Float(0.5)), "0.500000");
+    }
+}
@@ -0,0 +1,37 @@
+//! xfconf-compatible configuration daemon for XFCE.rs
+
+use std::sync::Arc;
+
+use tracing::{error, info};
+use xfce_rs_config::XfceConfig;
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let config = Arc::new(XfceConfig::default());
+
+    // Let edits made with a text editor reach clients right away
+    let _reloader = match config.watch_files() {
+        Ok(reloader) => Some(reloader),
+        Err(e) => {
+            error!("Failed to watch configuration files: {}", e);
+            None
+        }
+    };
+
+    let _connection = match xfce_rs_config::xfconfd::serve(config).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            error!("Failed to start xfconf daemon: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Failed to wait for shutdown signal: {}", e);
+    }
+    info!("xfconf daemon shutting down");
+}
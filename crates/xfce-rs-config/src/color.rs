@@ -0,0 +1,92 @@
+//! RGBA colors stored in configuration
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// A color with components in `0.0..=1.0`, as GTK and xfconf store them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rgba {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    #[serde(default = "opaque")]
+    pub alpha: f64,
+}
+
+fn opaque() -> f64 {
+    1.0
+}
+
+impl Rgba {
+    pub const fn new(red: f64, green: f64, blue: f64, alpha: f64) -> Self {
+        Self { red, green, blue, alpha }
+    }
+
+    /// Build from 8-bit channels
+    pub fn from_rgba8(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        let unit = |c: u8| f64::from(c) / 255.0;
+        Self::new(unit(red), unit(green), unit(blue), unit(alpha))
+    }
+
+    /// Channels scaled to 8 bits, clamping out-of-range values
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        let byte = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [byte(self.red), byte(self.green), byte(self.blue), byte(self.alpha)]
+    }
+
+    /// Parse `#rgb`, `#rrggbb` or `#rrggbbaa`
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#')?;
+        if !digits.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize, width: usize| {
+            let value = u8::from_str_radix(&digits[i * width..(i + 1) * width], 16).ok()?;
+            Some(if width == 1 { value * 17 } else { value })
+        };
+
+        match digits.len() {
+            3 => Some(Self::from_rgba8(channel(0, 1)?, channel(1, 1)?, channel(2, 1)?, 255)),
+            6 => Some(Self::from_rgba8(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?, 255)),
+            8 => Some(Self::from_rgba8(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?, channel(3, 2)?)),
+            _ => None,
+        }
+    }
+
+    /// `#rrggbbaa`
+    pub fn to_hex(&self) -> String {
+        let [r, g, b, a] = self.to_rgba8();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl FromStr for Rgba {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s).ok_or_else(|| format!("invalid color \"{}\"", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_parsing() {
+        assert_eq!(Rgba::from_hex("#fff"), Some(Rgba::new(1.0, 1.0, 1.0, 1.0)));
+        assert_eq!(Rgba::from_hex("#00000080").unwrap().to_rgba8(), [0, 0, 0, 128]);
+        assert_eq!("#3c8fdd".parse::<Rgba>().unwrap().to_hex(), "#3c8fddff");
+        assert!(Rgba::from_hex("3c8fdd").is_none());
+        assert!(Rgba::from_hex("#3c8fd").is_none());
+        assert!(Rgba::from_hex("#ééé").is_none());
+    }
+}
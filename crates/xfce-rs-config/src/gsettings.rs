@@ -0,0 +1,318 @@
+//! Mirroring settings into GSettings for GTK and GNOME applications
+//!
+//! Applications that read GSettings instead of XSETTINGS (GTK 4, libadwaita,
+//! portals) only follow theme and font changes if the matching
+//! `org.gnome.desktop.*` keys change too. [`GSettingsBridge`] copies mapped
+//! properties to GSettings whenever they change and copies changes made
+//! through dconf (GNOME Tweaks, `gsettings set`) back.
+//!
+//! Writes go through the `gsettings` tool; dconf change notifications are
+//! picked up from the session bus.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures_util::StreamExt;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use zbus::{Connection, MatchRule, MessageStream};
+
+use crate::{ConfigError, ConfigValue, XfceConfig};
+
+/// D-Bus interface dconf announces changes on
+const DCONF_WRITER_INTERFACE: &str = "ca.desrt.dconf.Writer";
+
+/// GVariant type of a mirrored key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GSettingsKind {
+    String,
+    Int,
+    Uint,
+    Bool,
+    Double,
+}
+
+/// One property mirrored to one GSettings key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GSettingsMapping {
+    pub channel: String,
+    pub property: String,
+    pub schema: String,
+    pub key: String,
+    pub kind: GSettingsKind,
+}
+
+impl GSettingsMapping {
+    pub fn new(channel: &str, property: &str, schema: &str, key: &str, kind: GSettingsKind) -> Self {
+        Self {
+            channel: channel.to_string(),
+            property: property.to_string(),
+            schema: schema.to_string(),
+            key: key.to_string(),
+            kind,
+        }
+    }
+
+    /// Full dconf path of the key, for schemas stored at their id's path
+    pub fn dconf_path(&self) -> String {
+        format!("/{}/{}", self.schema.replace('.', "/"), self.key)
+    }
+}
+
+/// Appearance and keyboard settings GTK applications care about
+pub fn default_mappings() -> Vec<GSettingsMapping> {
+    use GSettingsKind::*;
+
+    const INTERFACE: &str = "org.gnome.desktop.interface";
+    const KEYBOARD: &str = "org.gnome.desktop.peripherals.keyboard";
+    vec![
+        GSettingsMapping::new("xsettings", "/Net/ThemeName", INTERFACE, "gtk-theme", String),
+        GSettingsMapping::new("xsettings", "/Net/IconThemeName", INTERFACE, "icon-theme", String),
+        GSettingsMapping::new("xsettings", "/Net/CursorBlink", INTERFACE, "cursor-blink", Bool),
+        GSettingsMapping::new("xsettings", "/Net/CursorBlinkTime", INTERFACE, "cursor-blink-time", Int),
+        GSettingsMapping::new("xsettings", "/Gtk/FontName", INTERFACE, "font-name", String),
+        GSettingsMapping::new("xsettings", "/Gtk/MonospaceFontName", INTERFACE, "monospace-font-name", String),
+        GSettingsMapping::new("xsettings", "/Gtk/CursorThemeName", INTERFACE, "cursor-theme", String),
+        GSettingsMapping::new("xsettings", "/Gtk/CursorThemeSize", INTERFACE, "cursor-size", Int),
+        GSettingsMapping::new("keyboards", "/Default/KeyRepeat", KEYBOARD, "repeat", Bool),
+        GSettingsMapping::new("keyboards", "/Default/KeyRepeat/Delay", KEYBOARD, "delay", Uint),
+    ]
+}
+
+/// Keeps mapped properties and GSettings keys in sync until dropped
+#[derive(Debug)]
+pub struct GSettingsBridge {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for GSettingsBridge {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Last value synced per mapping, in GVariant text form, to stop echoes
+type SyncedValues = Arc<Mutex<HashMap<usize, String>>>;
+
+impl GSettingsBridge {
+    /// Push the current values to GSettings, then follow changes on both
+    /// sides. Must be called within a Tokio runtime.
+    pub async fn start(config: Arc<XfceConfig>, mappings: Vec<GSettingsMapping>) -> Result<Self, ConfigError> {
+        let mappings = Arc::new(mappings);
+        let synced: SyncedValues = Arc::default();
+
+        for (index, mapping) in mappings.iter().enumerate() {
+            if let Ok(value) = config.get_property(&mapping.channel, &mapping.property).await {
+                push(index, mapping, &value, &synced).await;
+            }
+        }
+
+        let mut events = config.watch_all();
+        let to_gsettings = {
+            let mappings = mappings.clone();
+            let synced = synced.clone();
+            tokio::spawn(async move {
+                loop {
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                    };
+                    let Some(value) = event.new_value else {
+                        continue;
+                    };
+                    for (index, mapping) in mappings.iter().enumerate() {
+                        if mapping.channel == event.channel && mapping.property == event.property {
+                            push(index, mapping, &value, &synced).await;
+                        }
+                    }
+                }
+            })
+        };
+
+        let connection = Connection::session().await?;
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(DCONF_WRITER_INTERFACE)?
+            .member("Notify")?
+            .build();
+        let mut signals = MessageStream::for_match_rule(rule, &connection, None).await?;
+
+        let from_gsettings = tokio::spawn(async move {
+            let _connection = connection;
+            while let Some(message) = signals.next().await {
+                let Ok(message) = message else {
+                    continue;
+                };
+                let Ok((prefix, changes, _tag)) = message.body().deserialize::<(String, Vec<String>, String)>() else {
+                    continue;
+                };
+                let changed = changed_paths(&prefix, &changes);
+
+                for (index, mapping) in mappings.iter().enumerate() {
+                    let path = mapping.dconf_path();
+                    if changed.iter().any(|changed| path_matches(changed, &path)) {
+                        pull(index, mapping, &config, &synced).await;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            tasks: vec![to_gsettings, from_gsettings],
+        })
+    }
+}
+
+/// Write a config value to its GSettings key unless it is already there
+async fn push(index: usize, mapping: &GSettingsMapping, value: &ConfigValue, synced: &SyncedValues) {
+    let Some(text) = to_gvariant_text(mapping.kind, value) else {
+        warn!("Cannot mirror {} {} as {:?}", mapping.channel, mapping.property, mapping.kind);
+        return;
+    };
+    if synced.lock().unwrap_or_else(|e| e.into_inner()).get(&index) == Some(&text) {
+        return;
+    }
+
+    let status = Command::new("gsettings")
+        .args(["set", &mapping.schema, &mapping.key, &text])
+        .status()
+        .await;
+    match status {
+        Ok(status) if status.success() => {
+            synced.lock().unwrap_or_else(|e| e.into_inner()).insert(index, text);
+        }
+        Ok(status) => debug!("gsettings set {} {} failed with {}", mapping.schema, mapping.key, status),
+        Err(e) => debug!("Failed to run gsettings: {}", e),
+    }
+}
+
+/// Read a GSettings key and store it in the config if it changed
+async fn pull(index: usize, mapping: &GSettingsMapping, config: &XfceConfig, synced: &SyncedValues) {
+    let output = match Command::new("gsettings").args(["get", &mapping.schema, &mapping.key]).output().await {
+        Ok(output) if output.status.success() => output,
+        Ok(_) => return,
+        Err(e) => {
+            debug!("Failed to run gsettings: {}", e);
+            return;
+        }
+    };
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let Some(value) = from_gvariant_text(mapping.kind, &text) else {
+        return;
+    };
+    // Normalize so the echo of our own write compares equal
+    let Some(text) = to_gvariant_text(mapping.kind, &value) else {
+        return;
+    };
+
+    {
+        let mut synced = synced.lock().unwrap_or_else(|e| e.into_inner());
+        if synced.get(&index) == Some(&text) {
+            return;
+        }
+        synced.insert(index, text);
+    }
+    if let Err(e) = config.set_property(&mapping.channel, &mapping.property, value).await {
+        warn!("Failed to store {} from GSettings: {}", mapping.property, e);
+    }
+}
+
+/// Absolute paths named by a dconf `Notify` signal
+fn changed_paths(prefix: &str, changes: &[String]) -> Vec<String> {
+    if changes.is_empty() {
+        return vec![prefix.to_string()];
+    }
+    changes.iter().map(|change| format!("{}{}", prefix, change)).collect()
+}
+
+/// A changed path covers a key if it is the key or a directory above it
+fn path_matches(changed: &str, key_path: &str) -> bool {
+    if changed.ends_with('/') {
+        key_path.starts_with(changed)
+    } else {
+        changed == key_path
+    }
+}
+
+/// Format a value the way `gsettings set` parses it
+pub fn to_gvariant_text(kind: GSettingsKind, value: &ConfigValue) -> Option<String> {
+    Some(match (kind, value) {
+        (GSettingsKind::String, ConfigValue::String(s)) => {
+            format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+        }
+        (GSettingsKind::Int, ConfigValue::Integer(i)) => i32::try_from(*i).ok()?.to_string(),
+        (GSettingsKind::Uint, ConfigValue::Integer(i)) => u32::try_from(*i).ok()?.to_string(),
+        (GSettingsKind::Bool, ConfigValue::Boolean(b)) => b.to_string(),
+        (GSettingsKind::Double, ConfigValue::Float(f)) => format!("{:?}", f),
+        (GSettingsKind::Double, ConfigValue::Integer(i)) => format!("{:?}", *i as f64),
+        _ => return None,
+    })
+}
+
+/// Parse the output of `gsettings get`
+pub fn from_gvariant_text(kind: GSettingsKind, text: &str) -> Option<ConfigValue> {
+    // Non-default integer types are printed with their type, e.g. "uint32 500"
+    let text = text.rsplit(' ').next().filter(|_| kind != GSettingsKind::String).unwrap_or(text);
+
+    Some(match kind {
+        GSettingsKind::String => ConfigValue::String(unquote(text)?),
+        GSettingsKind::Int | GSettingsKind::Uint => ConfigValue::Integer(text.parse().ok()?),
+        GSettingsKind::Bool => ConfigValue::Boolean(text.parse().ok()?),
+        GSettingsKind::Double => ConfigValue::Float(text.parse().ok()?),
+    })
+}
+
+fn unquote(text: &str) -> Option<String> {
+    let quote = text.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let inner = text.strip_prefix(quote)?.strip_suffix(quote)?;
+
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            result.push(chars.next()?);
+        } else {
+            result.push(c);
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gvariant_text_round_trip() {
+        let cases = [
+            (GSettingsKind::String, ConfigValue::String("It's \\ Greybird".to_string())),
+            (GSettingsKind::Int, ConfigValue::Integer(-24)),
+            (GSettingsKind::Uint, ConfigValue::Integer(500)),
+            (GSettingsKind::Bool, ConfigValue::Boolean(true)),
+            (GSettingsKind::Double, ConfigValue::Float(1.5)),
+        ];
+        for (kind, value) in cases {
+            let text = to_gvariant_text(kind, &value).unwrap();
+            assert_eq!(from_gvariant_text(kind, &text), Some(value));
+        }
+
+        assert_eq!(from_gvariant_text(GSettingsKind::Uint, "uint32 660"), Some(ConfigValue::Integer(660)));
+        assert_eq!(to_gvariant_text(GSettingsKind::Uint, &ConfigValue::Integer(-1)), None);
+        assert_eq!(to_gvariant_text(GSettingsKind::Bool, &ConfigValue::String("true".to_string())), None);
+    }
+
+    #[test]
+    fn test_dconf_paths() {
+        let mapping = &default_mappings()[0];
+        assert_eq!(mapping.dconf_path(), "/org/gnome/desktop/interface/gtk-theme");
+
+        let changed = changed_paths("/org/gnome/desktop/interface/", &["gtk-theme".to_string()]);
+        assert!(path_matches(&changed[0], &mapping.dconf_path()));
+        assert!(path_matches("/org/gnome/", &mapping.dconf_path()));
+        assert!(!path_matches("/org/gnome/desktop/interface/gtk-theme-extra", &mapping.dconf_path()));
+    }
+}
@@ -1,9 +1,37 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use tracing::error;
 
+pub mod color;
+#[cfg(feature = "gsettings")]
+pub mod gsettings;
+pub mod migrate;
+pub mod persist;
+pub mod reload;
+pub mod secret;
+pub mod snapshot;
+pub mod system;
+pub mod transaction;
+pub mod typed;
+pub mod xfconf_xml;
+pub mod xfconfd;
+
+use persist::{PendingWrite, SaveScheduler, DEFAULT_SAVE_DELAY};
+
+pub use color::Rgba;
+pub use migrate::Migrations;
+pub use reload::ConfigReloader;
+pub use secret::{SecretChannel, SecretKey};
+pub use snapshot::SnapshotInfo;
+pub use system::LockList;
+pub use transaction::ConfigTransaction;
+pub use typed::{ConfigSection, FromConfigValue};
+
 /// Error types for configuration operations
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -13,14 +41,32 @@ pub enum ConfigError {
     #[error("Invalid configuration format: {reason}")]
     InvalidFormat { reason: String },
     
+    #[error("Invalid channel name: {channel}")]
+    InvalidChannel { channel: String },
+    
     #[error("Configuration property not found: {channel}.{property}")]
     PropertyNotFound { channel: String, property: String },
     
+    #[error("Configuration property {channel}.{property} is locked by the administrator")]
+    PropertyLocked { channel: String, property: String },
+    
+    #[error("Configuration property {channel}.{property} is not of type {expected}")]
+    TypeMismatch { channel: String, property: String, expected: &'static str },
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
     #[error("Parse error: {0}")]
     Parse(#[from] toml::de::Error),
+    
+    #[error("File watch error: {reason}")]
+    Watch { reason: String },
+    
+    #[error("Secret storage error: {reason}")]
+    Secret { reason: String },
+    
+    #[error("D-Bus error: {0}")]
+    DBus(#[from] zbus::Error),
 }
 
 /// Configuration value types
@@ -31,17 +77,23 @@ pub enum ConfigValue {
     Boolean(bool),
     Float(f64),
     Array(Vec<ConfigValue>),
+    Color(Rgba),
+    Dict(HashMap<String, ConfigValue>),
 }
 
 /// Configuration channel containing properties
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigChannel {
+    /// Layout version the properties follow, see [`Migrations`]
+    #[serde(default)]
+    pub schema_version: u32,
     pub properties: HashMap<String, ConfigValue>,
 }
 
 impl ConfigChannel {
     pub fn new() -> Self {
         Self {
+            schema_version: 0,
             properties: HashMap::new(),
         }
     }
@@ -57,76 +109,431 @@ impl ConfigChannel {
     pub fn remove(&mut self, property: &str) -> Option<ConfigValue> {
         self.properties.remove(property)
     }
+    
+    /// Move a value to a new property name, replacing any value there.
+    /// Returns `false` if `from` was not set.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        match self.properties.remove(from) {
+            Some(value) => {
+                self.properties.insert(to.to_string(), value);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Number of unread events a watcher may lag behind before losing some
+const WATCH_CAPACITY: usize = 64;
+
+/// What happened to a property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigEventKind {
+    /// A value was written
+    Set,
+    /// The user override was dropped in favor of the default
+    Reset,
+    /// The property was deleted
+    Removed,
+}
+
+/// Notification of a property change
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigEvent {
+    pub channel: String,
+    pub property: String,
+    pub kind: ConfigEventKind,
+    pub old_value: Option<ConfigValue>,
+    pub new_value: Option<ConfigValue>,
+}
+
+/// A `watch` subscription: channel (all if `None`), property prefix and its sender
+struct ConfigWatcher {
+    channel: Option<String>,
+    property_prefix: String,
+    sender: broadcast::Sender<ConfigEvent>,
+}
+
+impl ConfigWatcher {
+    fn matches(&self, channel: &str, property: &str) -> bool {
+        self.channel.as_deref().is_none_or(|watched| watched == channel)
+            && property.starts_with(&self.property_prefix)
+    }
+}
+
+/// Channels read from disk so far and which of them need writing
+#[derive(Debug, Default)]
+struct ChannelStore {
+    /// The user's own values
+    channels: HashMap<String, ConfigChannel>,
+    /// System-wide defaults, consulted when the user has no value
+    defaults: HashMap<String, ConfigChannel>,
+    /// Defaults registered by applications, below the system ones
+    schema: HashMap<String, ConfigChannel>,
+    /// Channels whose files have been looked for, whether or not they existed
+    loaded: HashSet<String>,
+    /// Hash of what was last written for each channel, `None` if deleted,
+    /// to tell our own writes from external edits
+    written: HashMap<String, Option<u64>>,
+    /// Schema version for channels created from scratch
+    schema_versions: HashMap<String, u32>,
+    /// Unsaved changes by channel: the properties changed since the last
+    /// write
+    dirty: HashMap<String, HashSet<String>>,
+}
+
+impl ChannelStore {
+    /// Record a change of `property` to be merged into the channel's file
+    fn mark_changed(&mut self, channel: &str, property: &str) {
+        self.dirty.entry(channel.to_string()).or_default().insert(property.to_string());
+    }
+    
+    fn default_value(&self, channel: &str, property: &str) -> Option<&ConfigValue> {
+        self.defaults
+            .get(channel)
+            .and_then(|entry| entry.get(property))
+            .or_else(|| self.schema.get(channel).and_then(|entry| entry.get(property)))
+    }
+    
+    /// The value in effect: the user's unless locked, else the default
+    fn value(&self, locks: &LockList, channel: &str, property: &str) -> Option<&ConfigValue> {
+        let user = if locks.is_locked(channel, property) {
+            None
+        } else {
+            self.channels.get(channel).and_then(|entry| entry.get(property))
+        };
+        user.or_else(|| self.default_value(channel, property))
+    }
+    
+    /// Store a user value, returning an event if the value in effect changed
+    fn set(&mut self, channel: &str, property: &str, value: ConfigValue) -> Option<ConfigEvent> {
+        let old_value = self
+            .channels
+            .get(channel)
+            .and_then(|entry| entry.get(property))
+            .or_else(|| self.default_value(channel, property))
+            .cloned();
+        
+        let schema_version = self.schema_versions.get(channel).copied().unwrap_or(0);
+        self.channels
+            .entry(channel.to_string())
+            .or_insert_with(|| ConfigChannel { schema_version, ..ConfigChannel::new() })
+            .set(property.to_string(), value.clone());
+        self.mark_changed(channel, property);
+        
+        (old_value.as_ref() != Some(&value)).then(|| ConfigEvent {
+            channel: channel.to_string(),
+            property: property.to_string(),
+            kind: ConfigEventKind::Set,
+            old_value,
+            new_value: Some(value),
+        })
+    }
+    
+    /// Drop a user value, falling back to the default if there is one.
+    /// Returns the dropped value and the resulting event.
+    fn remove(&mut self, channel: &str, property: &str) -> Option<(ConfigValue, ConfigEvent)> {
+        let old_value = self.channels.get_mut(channel)?.remove(property)?;
+        self.mark_changed(channel, property);
+        
+        let new_value = self.default_value(channel, property).cloned();
+        let event = ConfigEvent {
+            channel: channel.to_string(),
+            property: property.to_string(),
+            kind: if new_value.is_some() { ConfigEventKind::Reset } else { ConfigEventKind::Removed },
+            old_value: Some(old_value.clone()),
+            new_value,
+        };
+        Some((old_value, event))
+    }
+    
+    /// Swap in new user values for `channel`, describing what changed
+    fn replace(&mut self, locks: &LockList, channel: &str, updated: ConfigChannel) -> Vec<ConfigEvent> {
+        let old = self
+            .channels
+            .insert(channel.to_string(), updated)
+            .unwrap_or_else(ConfigChannel::new);
+        let new = &self.channels[channel];
+    
+        let mut properties: Vec<&String> = old
+            .properties
+            .keys()
+            .chain(new.properties.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        properties.sort();
+    
+        let mut events = Vec::new();
+        for property in properties {
+            let locked = locks.is_locked(channel, property);
+            let default = self.default_value(channel, property);
+            let effective = |layer: &ConfigChannel| {
+                let user = if locked { None } else { layer.get(property) };
+                user.or(default).cloned()
+            };
+    
+            let old_value = effective(&old);
+            let new_value = effective(new);
+            if old_value == new_value {
+                continue;
+            }
+    
+            let kind = match (&new_value, new.get(property)) {
+                (None, _) => ConfigEventKind::Removed,
+                (Some(_), None) => ConfigEventKind::Reset,
+                (Some(_), Some(_)) => ConfigEventKind::Set,
+            };
+            events.push(ConfigEvent {
+                channel: channel.to_string(),
+                property: property.clone(),
+                kind,
+                old_value,
+                new_value,
+            });
+        }
+        events
+    }
+    
+    /// The unsaved changes of every channel, to be written
+    fn take_writes(&mut self, dir: &Path) -> Vec<PendingWrite> {
+        std::mem::take(&mut self.dirty)
+            .into_iter()
+            .map(|(name, changed)| {
+                let schema_version = self.schema_versions.get(&name).copied().unwrap_or(0);
+                let contents = self
+                    .channels
+                    .get(&name)
+                    .cloned()
+                    .unwrap_or_else(|| ConfigChannel { schema_version, ..ConfigChannel::new() });
+                PendingWrite {
+                    path: persist::channel_path(dir, &name),
+                    channel: name,
+                    contents,
+                    changed,
+                }
+            })
+            .collect()
+    }
 }
 
-/// Configuration change watcher
-pub type ConfigWatcher = Box<dyn Fn(&str, &str, &ConfigValue) + Send + Sync>;
+/// Write the unsaved changes in `store` to the channel files in `dir`
+async fn flush(store: Arc<RwLock<ChannelStore>>, dir: PathBuf) -> Result<(), ConfigError> {
+    let writes = store.write().await.take_writes(&dir);
+    if writes.is_empty() {
+        return Ok(());
+    }
+    
+    let written = tokio::task::spawn_blocking(move || persist::apply_writes(&writes))
+        .await
+        .map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })??;
+    store.write().await.written.extend(written);
+    Ok(())
+}
 
 /// Main configuration system
+///
+/// Each channel lives in its own file inside the configuration directory
+/// and is read the first time it is accessed, layered over the defaults in
+/// the system directories (see [`system`]).
 pub struct XfceConfig {
-    channels: RwLock<HashMap<String, ConfigChannel>>,
-    config_path: String,
-    _watchers: Vec<ConfigWatcher>,
+    store: Arc<RwLock<ChannelStore>>,
+    config_dir: PathBuf,
+    system_dirs: Vec<PathBuf>,
+    locks: LockList,
+    migrations: Migrations,
+    watchers: std::sync::Mutex<Vec<ConfigWatcher>>,
+    saver: SaveScheduler,
 }
 
 impl std::fmt::Debug for XfceConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("XfceConfig")
-            .field("config_path", &self.config_path)
-            .field("channels", &"RwLock<HashMap<...>>")
-            .field("_watchers", &"<ConfigWatchers>")
+            .field("config_dir", &self.config_dir)
+            .field("system_dirs", &self.system_dirs)
+            .field("store", &"RwLock<ChannelStore>")
+            .field("watchers", &"<ConfigWatchers>")
             .finish()
     }
 }
 
 impl XfceConfig {
-    pub fn new(config_path: impl Into<String>) -> Result<Self, ConfigError> {
-        let config_path = config_path.into();
-        let config = Self::load_from_file(&config_path)?;
+    /// Use `config_dir` as the per-channel directory, without system
+    /// defaults. Nothing is read until a channel is first accessed.
+    pub fn new(config_dir: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let config_dir = config_dir.into();
+        if config_dir.is_file() {
+            return Err(ConfigError::InvalidFormat {
+                reason: format!("{} is a file, expected a channel directory", config_dir.display()),
+            });
+        }
         
         Ok(Self {
-            channels: RwLock::new(config),
-            config_path,
-            _watchers: Vec::new(),
+            store: Arc::new(RwLock::new(ChannelStore::default())),
+            config_dir,
+            system_dirs: Vec::new(),
+            locks: LockList::default(),
+            migrations: Migrations::new(),
+            watchers: std::sync::Mutex::new(Vec::new()),
+            saver: SaveScheduler::new(DEFAULT_SAVE_DELAY),
         })
     }
     
-    /// Load configuration from file
-    fn load_from_file(path: &str) -> Result<HashMap<String, ConfigChannel>, ConfigError> {
-        if !std::path::Path::new(path).exists() {
-            return Ok(HashMap::new());
+    /// `~/.config/xfce4/xfconf/xfce-perchannel-rs`, next to upstream
+    /// xfconf's `xfce-perchannel-xml`
+    pub fn default_config_dir() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("xfce4")
+            .join("xfconf")
+            .join("xfce-perchannel-rs")
+    }
+    
+    /// Layer defaults and locks from `dirs`, most important first, under
+    /// the user's values. Must be called before any channel is accessed.
+    pub fn with_system_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.locks = LockList::load(&dirs);
+        self.system_dirs = dirs;
+        self
+    }
+    
+    /// Upgrade outdated channel files as they are loaded. Must be called
+    /// before any channel is accessed.
+    pub fn with_migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = migrations;
+        self
+    }
+    
+    /// Directory holding the channel files
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+    
+    /// Whether an administrator has made `property` read-only
+    pub fn is_locked(&self, channel: &str, property: &str) -> bool {
+        self.locks.is_locked(channel, property)
+    }
+    
+    pub(crate) fn check_unlocked(&self, channel: &str, property: &str) -> Result<(), ConfigError> {
+        if self.is_locked(channel, property) {
+            return Err(ConfigError::PropertyLocked {
+                channel: channel.to_string(),
+                property: property.to_string(),
+            });
+        }
+        Ok(())
+    }
+    
+    fn channel_file(&self, channel: &str) -> Result<PathBuf, ConfigError> {
+        if channel.is_empty() || channel.starts_with('.') || channel.contains(['/', '\\']) {
+            return Err(ConfigError::InvalidChannel { channel: channel.to_string() });
+        }
+        Ok(persist::channel_path(&self.config_dir, channel))
+    }
+    
+    /// Read the files of `channels` that have not been looked at yet
+    pub(crate) async fn ensure_loaded(&self, channels: &[&str]) -> Result<(), ConfigError> {
+        {
+            let store = self.store.read().await;
+            if channels.iter().all(|name| store.loaded.contains(*name)) {
+                return Ok(());
+            }
         }
         
-        let content = std::fs::read_to_string(path)?;
-        let config: HashMap<String, ConfigChannel> = toml::from_str(&content)?;
-        Ok(config)
+        let mut store = self.store.write().await;
+        for name in channels {
+            if store.loaded.contains(*name) {
+                continue;
+            }
+            let path = self.channel_file(name)?;
+            if let Some(mut channel) = persist::read_channel(&path)? {
+                self.migrations.upgrade_file(name, &path, &mut channel)?;
+                store.channels.insert(name.to_string(), channel);
+            }
+            store
+                .schema_versions
+                .insert(name.to_string(), self.migrations.latest_version(name));
+            if let Some(defaults) = system::read_defaults(&self.system_dirs, name) {
+                store.defaults.insert(name.to_string(), defaults);
+            }
+            store.loaded.insert(name.to_string());
+        }
+        Ok(())
     }
     
-    /// Save configuration to file
+    /// Write every changed channel now, including any pending delayed write.
+    ///
+    /// Only the properties this config changed are written, merged into
+    /// what the files hold by then, so configs in other processes saving
+    /// the same channels keep their changes.
     pub async fn save(&self) -> Result<(), ConfigError> {
-        let channels = self.channels.read().await;
-        let content = toml::to_string_pretty(&*channels)
-            .map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?;
+        self.saver.mark_clean();
+        flush(self.store.clone(), self.config_dir.clone()).await
+    }
+    
+    /// How long changes are collected before being written to disk
+    pub fn save_delay(&self) -> Duration {
+        self.saver.delay()
+    }
+    
+    /// Change the write delay. Zero writes on every change.
+    pub fn set_save_delay(&mut self, delay: Duration) {
+        self.saver.set_delay(delay);
+    }
+    
+    /// Import xfce4's perchannel XML settings from one file or a directory
+    /// of them, such as [`xfconf_xml::default_xfconf_dir`]. Imported values
+    /// replace existing ones; locked properties are skipped. Returns the
+    /// number of properties imported.
+    pub async fn import_xfconf(&self, path: impl AsRef<Path>) -> Result<usize, ConfigError> {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            xfconf_xml::channel_files(path)?
+        } else {
+            vec![path.to_path_buf()]
+        };
         
-        if let Some(parent) = std::path::Path::new(&self.config_path).parent() {
-            tokio::fs::create_dir_all(parent).await?;
+        let mut transaction = self.begin();
+        for file in files {
+            let channel = xfconf_xml::read_channel_file(&file)?;
+            for (property, value) in channel.properties {
+                if !self.is_locked(&channel.name, &property) {
+                    transaction.set(&channel.name, &property, value);
+                }
+            }
         }
         
-        tokio::fs::write(&self.config_path, content).await?;
-        Ok(())
+        let count = transaction.len();
+        transaction.commit().await?;
+        Ok(count)
+    }
+    
+    /// Start a batch of changes that is applied and saved as one
+    pub fn begin(&self) -> ConfigTransaction<'_> {
+        ConfigTransaction::new(self)
+    }
+    
+    /// Persist after a change, coalescing bursts into one delayed write
+    pub(crate) async fn request_save(&self) -> Result<(), ConfigError> {
+        let store = self.store.clone();
+        let dir = self.config_dir.clone();
+        
+        let scheduled = self.saver.schedule(move || flush(store.clone(), dir.clone()));
+        
+        if scheduled {
+            Ok(())
+        } else {
+            self.save().await
+        }
     }
     
-    /// Get a configuration property
+    /// Get a configuration property, falling back to the system default
     pub async fn get_property(&self, channel: &str, property: &str) -> Result<ConfigValue, ConfigError> {
-        let channels = self.channels.read().await;
+        self.ensure_loaded(&[channel]).await?;
+        let store = self.store.read().await;
         
-        channels
-            .get(channel)
-            .ok_or_else(|| ConfigError::PropertyNotFound {
-                channel: channel.to_string(),
-                property: property.to_string(),
-            })?
-            .get(property)
+        store
+            .value(&self.locks, channel, property)
             .cloned()
             .ok_or_else(|| ConfigError::PropertyNotFound {
                 channel: channel.to_string(),
@@ -134,50 +541,286 @@ impl XfceConfig {
             })
     }
     
-    /// Set a configuration property
-    pub async fn set_property(&self, channel: &str, property: &str, value: ConfigValue) -> Result<(), ConfigError> {
+    /// Get a property converted to a Rust type
+    pub async fn get_typed<T: FromConfigValue>(&self, channel: &str, property: &str) -> Result<T, ConfigError> {
+        let value = self.get_property(channel, property).await?;
+        T::from_config_value(&value).ok_or_else(|| ConfigError::TypeMismatch {
+            channel: channel.to_string(),
+            property: property.to_string(),
+            expected: T::TYPE_NAME,
+        })
+    }
+    
+    /// Get a string property
+    pub async fn get_string(&self, channel: &str, property: &str) -> Result<String, ConfigError> {
+        self.get_typed(channel, property).await
+    }
+    
+    /// Get an integer property
+    pub async fn get_int(&self, channel: &str, property: &str) -> Result<i64, ConfigError> {
+        self.get_typed(channel, property).await
+    }
+    
+    /// Get a boolean property
+    pub async fn get_bool(&self, channel: &str, property: &str) -> Result<bool, ConfigError> {
+        self.get_typed(channel, property).await
+    }
+    
+    /// Get a floating point property; integers are widened
+    pub async fn get_double(&self, channel: &str, property: &str) -> Result<f64, ConfigError> {
+        self.get_typed(channel, property).await
+    }
+    
+    /// Register application defaults for `channel`. They apply when neither
+    /// the user nor the system configuration sets a property.
+    pub async fn register_defaults<I>(&self, channel: &str, properties: I)
+    where
+        I: IntoIterator<Item = (String, ConfigValue)>,
+    {
+        let mut store = self.store.write().await;
+        let schema = store.schema.entry(channel.to_string()).or_insert_with(ConfigChannel::new);
+        schema.properties.extend(properties);
+    }
+    
+    /// Register the field defaults of a section as application defaults
+    pub async fn register_section<T: ConfigSection>(&self) {
+        let properties = T::default()
+            .to_properties()
+            .into_iter()
+            .map(|(property, value)| (property.to_string(), value));
+        self.register_defaults(T::CHANNEL, properties).await;
+    }
+    
+    /// The value `property` has when the user has not set it
+    pub async fn get_default(&self, channel: &str, property: &str) -> Result<Option<ConfigValue>, ConfigError> {
+        self.ensure_loaded(&[channel]).await?;
+        let store = self.store.read().await;
+        Ok(store.default_value(channel, property).cloned())
+    }
+    
+    /// Whether `property` has its default value, i.e. the user has not
+    /// overridden it or an administrator has locked it
+    pub async fn is_default(&self, channel: &str, property: &str) -> Result<bool, ConfigError> {
+        if self.is_locked(channel, property) {
+            return Ok(true);
+        }
+        self.ensure_loaded(&[channel]).await?;
+        let store = self.store.read().await;
+        Ok(store.channels.get(channel).and_then(|entry| entry.get(property)).is_none())
+    }
+    
+    /// Drop the user's override of `property`, returning the default now
+    /// in effect. Resetting a property that has no override does nothing.
+    pub async fn reset_property(&self, channel: &str, property: &str) -> Result<Option<ConfigValue>, ConfigError> {
+        self.check_unlocked(channel, property)?;
+        self.ensure_loaded(&[channel]).await?;
+        let (default, event) = {
+            let mut store = self.store.write().await;
+            let event = store.remove(channel, property).map(|(_, event)| event);
+            (store.default_value(channel, property).cloned(), event)
+        };
+        
+        if let Some(event) = event {
+            self.request_save().await?;
+            self.notify(event);
+        }
+        Ok(default)
+    }
+    
+    /// Load a struct bound to a channel, using defaults for missing or
+    /// mistyped properties
+    pub async fn load_section<T: ConfigSection>(&self) -> T {
+        let mut section = T::default();
+        if let Err(e) = self.ensure_loaded(&[T::CHANNEL]).await {
+            error!("Failed to load channel {}: {}", T::CHANNEL, e);
+        }
+        let store = self.store.read().await;
+        
+        for (property, _) in section.to_properties() {
+            if let Some(value) = store.value(&self.locks, T::CHANNEL, property) {
+                section.apply_property(property, value);
+            }
+        }
+        section
+    }
+    
+    /// Store every field of a section, saving once. Locked fields are
+    /// left alone.
+    pub async fn save_section<T: ConfigSection>(&self, section: &T) -> Result<(), ConfigError> {
+        self.ensure_loaded(&[T::CHANNEL]).await?;
+        let mut events = Vec::new();
         {
-            let mut channels = self.channels.write().await;
+            let mut store = self.store.write().await;
             
-            let channel_entry = channels.entry(channel.to_string()).or_insert_with(ConfigChannel::new);
-            channel_entry.set(property.to_string(), value.clone());
+            for (property, value) in section.to_properties() {
+                if self.is_locked(T::CHANNEL, property)
+                    || store.value(&self.locks, T::CHANNEL, property) == Some(&value)
+                {
+                    continue;
+                }
+                events.extend(store.set(T::CHANNEL, property, value));
+            }
         }
         
-        self.save().await?;
+        self.request_save().await?;
+        
+        for event in events {
+            self.notify(event);
+        }
+        Ok(())
+    }
+    
+    /// Set a configuration property
+    pub async fn set_property(&self, channel: &str, property: &str, value: ConfigValue) -> Result<(), ConfigError> {
+        self.check_unlocked(channel, property)?;
+        self.ensure_loaded(&[channel]).await?;
+        let event = self.store.write().await.set(channel, property, value);
+        
+        self.request_save().await?;
+        
+        if let Some(event) = event {
+            self.notify(event);
+        }
         Ok(())
     }
     
-    /// List all channels
+    /// Remove the user's value of a property, returning it. A system
+    /// default, if any, takes effect again.
+    pub async fn remove_property(&self, channel: &str, property: &str) -> Result<ConfigValue, ConfigError> {
+        self.check_unlocked(channel, property)?;
+        self.ensure_loaded(&[channel]).await?;
+        let (old_value, event) = self
+            .store
+            .write()
+            .await
+            .remove(channel, property)
+            .ok_or_else(|| ConfigError::PropertyNotFound {
+                channel: channel.to_string(),
+                property: property.to_string(),
+            })?;
+        
+        self.request_save().await?;
+        
+        self.notify(event);
+        Ok(old_value)
+    }
+    
+    /// Subscribe to changes of properties in `channel` whose names start
+    /// with `property_prefix`. An empty prefix matches every property.
+    pub fn watch(&self, channel: &str, property_prefix: &str) -> broadcast::Receiver<ConfigEvent> {
+        self.add_watcher(Some(channel.to_string()), property_prefix)
+    }
+    
+    /// Subscribe to changes of every property in every channel
+    pub fn watch_all(&self) -> broadcast::Receiver<ConfigEvent> {
+        self.add_watcher(None, "")
+    }
+    
+    fn add_watcher(&self, channel: Option<String>, property_prefix: &str) -> broadcast::Receiver<ConfigEvent> {
+        let (sender, receiver) = broadcast::channel(WATCH_CAPACITY);
+        
+        let mut watchers = self.watchers.lock().unwrap_or_else(|e| e.into_inner());
+        watchers.push(ConfigWatcher {
+            channel,
+            property_prefix: property_prefix.to_string(),
+            sender,
+        });
+        receiver
+    }
+    
+    /// Deliver an event to matching watchers, dropping ones nobody listens to
+    fn notify(&self, event: ConfigEvent) {
+        let mut watchers = self.watchers.lock().unwrap_or_else(|e| e.into_inner());
+        watchers.retain(|watcher| watcher.sender.receiver_count() > 0);
+        
+        for watcher in watchers.iter().filter(|w| w.matches(&event.channel, &event.property)) {
+            let _ = watcher.sender.send(event.clone());
+        }
+    }
+    
+    /// List all channels, both on disk and created since startup
     pub async fn list_channels(&self) -> Vec<String> {
-        let channels = self.channels.read().await;
-        channels.keys().cloned().collect()
+        let store = self.store.read().await;
+        let mut channels: HashSet<String> = store
+            .channels
+            .keys()
+            .chain(store.defaults.keys())
+            .chain(store.schema.keys())
+            .cloned()
+            .collect();
+        
+        for dir in std::iter::once(&self.config_dir).chain(&self.system_dirs) {
+            channels.extend(
+                persist::channel_names(dir)
+                    .into_iter()
+                    .filter(|name| !store.loaded.contains(name)),
+            );
+        }
+        channels.into_iter().collect()
     }
     
-    /// List properties in a channel
+    /// List properties in a channel, including ones only set by default
     pub async fn list_properties(&self, channel: &str) -> Result<Vec<String>, ConfigError> {
-        let channels = self.channels.read().await;
+        self.ensure_loaded(&[channel]).await?;
+        let store = self.store.read().await;
         
-        let channel = channels.get(channel)
-            .ok_or_else(|| ConfigError::PropertyNotFound {
+        let layers: Vec<&ConfigChannel> = [store.channels.get(channel), store.defaults.get(channel), store.schema.get(channel)]
+            .into_iter()
+            .flatten()
+            .collect();
+        if layers.is_empty() {
+            return Err(ConfigError::PropertyNotFound {
                 channel: channel.to_string(),
                 property: "".to_string(),
-            })?;
+            });
+        }
         
-        Ok(channel.properties.keys().cloned().collect())
+        let properties: HashSet<&String> = layers.iter().flat_map(|layer| layer.properties.keys()).collect();
+        Ok(properties.into_iter().cloned().collect())
     }
 }
 
 impl Default for XfceConfig {
     fn default() -> Self {
+        let config_dir = Self::default_config_dir();
+        
+        // Older releases kept every channel in one file
+        let single_file = dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("xfce-rs")
+            .join("config.toml");
+        if single_file.is_file() && !config_dir.exists() {
+            match persist::migrate_single_file(&single_file, &config_dir) {
+                Ok(count) => tracing::info!("Migrated {} channels to {}", count, config_dir.display()),
+                Err(e) => error!("Failed to migrate {}: {}", single_file.display(), e),
+            }
+        }
+        
+        let system_dirs = system::system_config_dirs();
         Self {
-            channels: RwLock::new(HashMap::new()),
-            config_path: dirs::config_dir()
-                .unwrap_or_else(|| std::path::PathBuf::from("."))
-                .join("xfce-rs")
-                .join("config.toml")
-                .to_string_lossy()
-                .to_string(),
-            _watchers: Vec::new(),
+            store: Arc::new(RwLock::new(ChannelStore::default())),
+            config_dir,
+            locks: LockList::load(&system_dirs),
+            system_dirs,
+            migrations: Migrations::new(),
+            watchers: std::sync::Mutex::new(Vec::new()),
+            saver: SaveScheduler::new(DEFAULT_SAVE_DELAY),
+        }
+    }
+}
+
+impl Drop for XfceConfig {
+    fn drop(&mut self) {
+        // Persist changes whose delayed write has not happened yet
+        if !self.saver.is_dirty() {
+            return;
+        }
+        let Ok(mut store) = self.store.try_write() else {
+            return;
+        };
+        let writes = store.take_writes(&self.config_dir);
+        if let Err(e) = persist::apply_writes(&writes) {
+            error!("Failed to save configuration on shutdown: {}", e);
         }
     }
 }
@@ -190,8 +833,8 @@ mod tests {
     #[tokio::test]
     async fn test_config_basic_operations() {
         let temp_dir = tempdir().unwrap();
-        let config_path = temp_dir.path().join("test_config.toml");
-        let config = XfceConfig::new(config_path.to_string_lossy()).unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
         
         // Test setting and getting a property
         config.set_property("test", "string_prop", ConfigValue::String("test".to_string())).await.unwrap();
@@ -204,11 +847,334 @@ mod tests {
         assert_eq!(value, ConfigValue::Integer(42));
     }
     
+    #[tokio::test]
+    async fn test_property_watch() {
+        let temp_dir = tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
+        
+        let mut panel = config.watch("panel", "size");
+        let mut other = config.watch("wm", "");
+        
+        config.set_property("panel", "size", ConfigValue::Integer(32)).await.unwrap();
+        config.set_property("panel", "size", ConfigValue::Integer(32)).await.unwrap();
+        config.set_property("panel", "position", ConfigValue::Integer(1)).await.unwrap();
+        config.remove_property("panel", "size").await.unwrap();
+        
+        let event = panel.try_recv().unwrap();
+        assert_eq!(event.kind, ConfigEventKind::Set);
+        assert_eq!(event.old_value, None);
+        assert_eq!(event.new_value, Some(ConfigValue::Integer(32)));
+        
+        let event = panel.try_recv().unwrap();
+        assert_eq!(event.kind, ConfigEventKind::Removed);
+        assert_eq!(event.old_value, Some(ConfigValue::Integer(32)));
+        
+        assert!(panel.try_recv().is_err());
+        assert!(other.try_recv().is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_typed_accessors() {
+        let temp_dir = tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
+        
+        config.set_property("wm", "theme", ConfigValue::String("Greybird".to_string())).await.unwrap();
+        config.set_property("wm", "count", ConfigValue::Integer(4)).await.unwrap();
+        
+        assert_eq!(config.get_string("wm", "theme").await.unwrap(), "Greybird");
+        assert_eq!(config.get_int("wm", "count").await.unwrap(), 4);
+        assert_eq!(config.get_double("wm", "count").await.unwrap(), 4.0);
+        assert!(matches!(
+            config.get_bool("wm", "theme").await,
+            Err(ConfigError::TypeMismatch { expected: "bool", .. })
+        ));
+        
+        config_section! {
+            #[derive(Debug, PartialEq)]
+            struct Wm in "wm" {
+                theme: String = "theme" => "Default".to_string(),
+                count: u32 = "count" => 1,
+                compositing: bool = "compositing" => true,
+            }
+        }
+        
+        let mut wm: Wm = config.load_section().await;
+        assert_eq!(wm, Wm { theme: "Greybird".to_string(), count: 4, compositing: true });
+        
+        wm.compositing = false;
+        config.save_section(&wm).await.unwrap();
+        assert!(!config.get_bool("wm", "compositing").await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_delayed_save_and_transactions() {
+        let temp_dir = tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let mut config = XfceConfig::new(&config_dir).unwrap();
+        config.set_save_delay(Duration::from_secs(60));
+        
+        config.set_property("panel", "size", ConfigValue::Integer(30)).await.unwrap();
+        config.set_property("panel", "size", ConfigValue::Integer(31)).await.unwrap();
+        assert!(!config_dir.exists());
+        
+        let mut events = config.watch("panel", "");
+        let mut transaction = config.begin();
+        transaction.set("panel", "size", 32).set("panel", "autohide", true).remove("panel", "missing");
+        assert_eq!(config.get_int("panel", "size").await.unwrap(), 31);
+        transaction.commit().await.unwrap();
+        
+        assert_eq!(config.get_int("panel", "size").await.unwrap(), 32);
+        assert_eq!(events.try_recv().unwrap().property, "size");
+        assert_eq!(events.try_recv().unwrap().property, "autohide");
+        
+        config.save().await.unwrap();
+        let reloaded = XfceConfig::new(&config_dir).unwrap();
+        assert_eq!(reloaded.get_int("panel", "size").await.unwrap(), 32);
+        
+        // Dropping with unsaved changes writes them out
+        config.set_property("panel", "size", ConfigValue::Integer(40)).await.unwrap();
+        drop(config);
+        let reloaded = XfceConfig::new(&config_dir).unwrap();
+        assert_eq!(reloaded.get_int("panel", "size").await.unwrap(), 40);
+    }
+    
+    #[tokio::test]
+    async fn test_configs_sharing_a_directory_merge() {
+        let temp_dir = tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let panel = XfceConfig::new(&config_dir).unwrap();
+        let settings = XfceConfig::new(&config_dir).unwrap();
+        
+        panel.set_property("xfce4-panel", "/size", ConfigValue::Integer(28)).await.unwrap();
+        panel.set_property("xfce4-panel", "/autohide", ConfigValue::Boolean(true)).await.unwrap();
+        panel.save().await.unwrap();
+        
+        // Loaded before the other's write, saved after it
+        settings.set_property("xfce4-panel", "/position", ConfigValue::Integer(2)).await.unwrap();
+        panel.remove_property("xfce4-panel", "/autohide").await.unwrap();
+        panel.set_property("xfce4-panel", "/size", ConfigValue::Integer(32)).await.unwrap();
+        panel.save().await.unwrap();
+        settings.save().await.unwrap();
+        
+        let reloaded = XfceConfig::new(&config_dir).unwrap();
+        assert_eq!(reloaded.get_int("xfce4-panel", "/size").await.unwrap(), 32);
+        assert_eq!(reloaded.get_int("xfce4-panel", "/position").await.unwrap(), 2);
+        assert!(reloaded.get_property("xfce4-panel", "/autohide").await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_per_channel_files() {
+        let temp_dir = tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
+        
+        config.set_property("xfwm4", "/general/theme", ConfigValue::String("Greybird".to_string())).await.unwrap();
+        config.set_property("xfce4-panel", "/panels/size", ConfigValue::Integer(28)).await.unwrap();
+        config.save().await.unwrap();
+        assert!(config_dir.join("xfwm4.toml").is_file());
+        assert!(config_dir.join("xfce4-panel.toml").is_file());
+        
+        // A broken channel does not affect the others
+        std::fs::write(config_dir.join("xfce4-panel.toml"), "[[broken").unwrap();
+        let reloaded = XfceConfig::new(&config_dir).unwrap();
+        let mut channels = reloaded.list_channels().await;
+        channels.sort();
+        assert_eq!(channels, ["xfce4-panel", "xfwm4"]);
+        assert_eq!(reloaded.get_string("xfwm4", "/general/theme").await.unwrap(), "Greybird");
+        assert!(reloaded.get_property("xfce4-panel", "/panels/size").await.is_err());
+        
+        // Emptying a channel removes its file
+        reloaded.remove_property("xfwm4", "/general/theme").await.unwrap();
+        reloaded.save().await.unwrap();
+        assert!(!config_dir.join("xfwm4.toml").exists());
+        
+        assert!(matches!(
+            reloaded.set_property("../escape", "x", ConfigValue::Boolean(true)).await,
+            Err(ConfigError::InvalidChannel { .. })
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_system_defaults_and_locks() {
+        let temp_dir = tempdir().unwrap();
+        let system_dir = temp_dir.path().join("xdg").join("xfce-rs");
+        std::fs::create_dir_all(&system_dir).unwrap();
+        std::fs::write(
+            system_dir.join("xfwm4.toml"),
+            "[properties]\n\"/general/theme\" = { String = \"Corporate\" }\n\"/general/workspace_count\" = { Integer = 2 }\n",
+        )
+        .unwrap();
+        std::fs::write(system_dir.join(system::KIOSK_FILE), "[locked]\nxfwm4 = [\"/general/theme\"]\n").unwrap();
+        
+        let config_dir = temp_dir.path().join("channels");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("xfwm4.toml"),
+            "[properties]\n\"/general/theme\" = { String = \"Mine\" }\n",
+        )
+        .unwrap();
+        let config = XfceConfig::new(&config_dir).unwrap().with_system_dirs(vec![system_dir]);
+        
+        // Locked properties ignore the user's value and refuse writes
+        assert!(config.is_locked("xfwm4", "/general/theme"));
+        assert_eq!(config.get_string("xfwm4", "/general/theme").await.unwrap(), "Corporate");
+        assert!(matches!(
+            config.set_property("xfwm4", "/general/theme", ConfigValue::String("Other".to_string())).await,
+            Err(ConfigError::PropertyLocked { .. })
+        ));
+        let mut transaction = config.begin();
+        transaction.set("xfwm4", "/general/workspace_count", 6).set("xfwm4", "/general/theme", "Other");
+        assert!(transaction.commit().await.is_err());
+        assert_eq!(config.get_int("xfwm4", "/general/workspace_count").await.unwrap(), 2);
+        
+        // Unlocked defaults can be overridden and come back on removal
+        let mut events = config.watch("xfwm4", "");
+        config.set_property("xfwm4", "/general/workspace_count", ConfigValue::Integer(6)).await.unwrap();
+        assert_eq!(config.get_int("xfwm4", "/general/workspace_count").await.unwrap(), 6);
+        config.remove_property("xfwm4", "/general/workspace_count").await.unwrap();
+        assert_eq!(config.get_int("xfwm4", "/general/workspace_count").await.unwrap(), 2);
+        
+        assert_eq!(events.try_recv().unwrap().old_value, Some(ConfigValue::Integer(2)));
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.kind, ConfigEventKind::Reset);
+        assert_eq!(event.new_value, Some(ConfigValue::Integer(2)));
+        
+        let mut properties = config.list_properties("xfwm4").await.unwrap();
+        properties.sort();
+        assert_eq!(properties, ["/general/theme", "/general/workspace_count"]);
+    }
+    
+    #[tokio::test]
+    async fn test_rich_values_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
+        
+        let plugin = ConfigValue::Dict(HashMap::from([
+            ("name".to_string(), ConfigValue::String("clock".to_string())),
+            ("empty".to_string(), ConfigValue::Array(Vec::new())),
+            ("nested".to_string(), ConfigValue::Dict(HashMap::from([("mode".to_string(), ConfigValue::Integer(2))]))),
+        ]));
+        let values = [
+            ("/background", ConfigValue::Color(Rgba::new(0.2, 0.4, 0.6, 0.8))),
+            ("/plugins", ConfigValue::Array(vec![plugin.clone(), plugin])),
+            ("/mixed", ConfigValue::Array(vec![ConfigValue::Integer(1), ConfigValue::Float(1.0), ConfigValue::Boolean(false)])),
+            ("/empty", ConfigValue::Array(Vec::new())),
+        ];
+        for (property, value) in &values {
+            config.set_property("xfce4-panel", property, value.clone()).await.unwrap();
+        }
+        config.save().await.unwrap();
+        
+        let reloaded = XfceConfig::new(&config_dir).unwrap();
+        for (property, value) in &values {
+            assert_eq!(&reloaded.get_property("xfce4-panel", property).await.unwrap(), value);
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_migrations_on_load() {
+        let temp_dir = tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        let panel_file = config_dir.join("xfce4-panel.toml");
+        std::fs::write(&panel_file, "[properties]\n\"/size\" = { Integer = 28 }\n").unwrap();
+        
+        let migrations = || {
+            Migrations::new().add("xfce4-panel", 1, |channel| {
+                channel.rename("/size", "/row-size");
+            })
+        };
+        let config = XfceConfig::new(&config_dir).unwrap().with_migrations(migrations());
+        assert_eq!(config.get_int("xfce4-panel", "/row-size").await.unwrap(), 28);
+        assert!(config.get_property("xfce4-panel", "/size").await.is_err());
+        assert!(config_dir.join("xfce4-panel.toml.v0.bak").is_file());
+        
+        // New channels start at the latest version and are not migrated again
+        config.set_property("xfce4-panel", "/size", ConfigValue::Integer(1)).await.unwrap();
+        config.save().await.unwrap();
+        let reloaded = XfceConfig::new(&config_dir).unwrap().with_migrations(migrations());
+        assert_eq!(reloaded.get_int("xfce4-panel", "/size").await.unwrap(), 1);
+        assert_eq!(reloaded.get_int("xfce4-panel", "/row-size").await.unwrap(), 28);
+    }
+    
+    #[tokio::test]
+    async fn test_import_xfconf() {
+        let temp_dir = tempdir().unwrap();
+        let xml_dir = temp_dir.path().join("xfce-perchannel-xml");
+        std::fs::create_dir_all(&xml_dir).unwrap();
+        std::fs::write(
+            xml_dir.join("xsettings.xml"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<channel name="xsettings" version="1.0">
+  <property name="Net" type="empty">
+    <property name="ThemeName" type="string" value="Adwaita-dark"/>
+    <property name="CursorBlinkTime" type="int" value="1200"/>
+  </property>
+</channel>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            xml_dir.join("xfce4-panel.xml"),
+            r#"<channel name="xfce4-panel" version="1.0">
+  <property name="panels" type="array">
+    <value type="int" value="1"/>
+  </property>
+</channel>"#,
+        )
+        .unwrap();
+        
+        let config = XfceConfig::new(temp_dir.path().join("channels")).unwrap();
+        assert_eq!(config.import_xfconf(&xml_dir).await.unwrap(), 3);
+        assert_eq!(config.get_string("xsettings", "/Net/ThemeName").await.unwrap(), "Adwaita-dark");
+        assert_eq!(config.get_int("xsettings", "/Net/CursorBlinkTime").await.unwrap(), 1200);
+        assert_eq!(config.get_typed::<Vec<i32>>("xfce4-panel", "/panels").await.unwrap(), vec![1]);
+    }
+    
+    #[tokio::test]
+    async fn test_reset_and_defaults() {
+        let temp_dir = tempdir().unwrap();
+        let config = XfceConfig::new(temp_dir.path().join("channels")).unwrap();
+        
+        config_section! {
+            struct Panel in "xfce4-panel" {
+                size: u32 = "/size" => 32,
+                autohide: bool = "/autohide" => false,
+            }
+        }
+        config.register_section::<Panel>().await;
+        
+        assert_eq!(config.get_int("xfce4-panel", "/size").await.unwrap(), 32);
+        assert!(config.is_default("xfce4-panel", "/size").await.unwrap());
+        
+        config.set_property("xfce4-panel", "/size", ConfigValue::Integer(48)).await.unwrap();
+        assert!(!config.is_default("xfce4-panel", "/size").await.unwrap());
+        assert_eq!(config.get_default("xfce4-panel", "/size").await.unwrap(), Some(ConfigValue::Integer(32)));
+        
+        let mut events = config.watch("xfce4-panel", "");
+        let default = config.reset_property("xfce4-panel", "/size").await.unwrap();
+        assert_eq!(default, Some(ConfigValue::Integer(32)));
+        assert!(config.is_default("xfce4-panel", "/size").await.unwrap());
+        assert_eq!(config.get_int("xfce4-panel", "/size").await.unwrap(), 32);
+        
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.kind, ConfigEventKind::Reset);
+        assert_eq!(event.old_value, Some(ConfigValue::Integer(48)));
+        assert_eq!(event.new_value, Some(ConfigValue::Integer(32)));
+        
+        // Nothing to reset: no event, no error
+        assert_eq!(config.reset_property("xfce4-panel", "/size").await.unwrap(), Some(ConfigValue::Integer(32)));
+        assert_eq!(config.reset_property("xfce4-panel", "/unknown").await.unwrap(), None);
+        assert!(events.try_recv().is_err());
+    }
+    
     #[tokio::test]
     async fn test_channel_listing() {
         let temp_dir = tempdir().unwrap();
-        let config_path = temp_dir.path().join("test_config.toml");
-        let config = XfceConfig::new(config_path.to_string_lossy()).unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
         
         config.set_property("channel1", "prop1", ConfigValue::Boolean(true)).await.unwrap();
         config.set_property("channel2", "prop2", ConfigValue::Float(3.14)).await.unwrap();
@@ -0,0 +1,154 @@
+//! Upgrading stored channels when their layout changes between releases
+//!
+//! Every channel file records the `schema_version` it was written with. A
+//! [`Migrations`] registry lists, per channel, the steps that bring older
+//! data up to date:
+//!
+//! ```
+//! use xfce_rs_config::Migrations;
+//!
+//! let migrations = Migrations::new().add("xfce4-panel", 1, |channel| {
+//!     channel.rename("/panels/panel-1/size", "/panels/panel-1/row-size");
+//! });
+//! assert_eq!(migrations.latest_version("xfce4-panel"), 1);
+//! ```
+//!
+//! Steps run the first time an outdated channel is loaded. The file is
+//! copied to `<channel>.toml.v<old version>.bak` before the upgraded data
+//! replaces it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use tracing::{info, warn};
+
+use crate::{persist, ConfigChannel, ConfigError};
+
+type MigrationFn = Box<dyn Fn(&mut ConfigChannel) + Send + Sync>;
+
+struct MigrationStep {
+    version: u32,
+    apply: MigrationFn,
+}
+
+/// Registry of per-channel upgrade steps
+#[derive(Default)]
+pub struct Migrations {
+    channels: HashMap<String, Vec<MigrationStep>>,
+}
+
+impl fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let versions: HashMap<&str, u32> = self
+            .channels
+            .keys()
+            .map(|channel| (channel.as_str(), self.latest_version(channel)))
+            .collect();
+        f.debug_struct("Migrations").field("latest_versions", &versions).finish()
+    }
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the step that upgrades `channel` to `version`
+    pub fn add<F>(mut self, channel: &str, version: u32, apply: F) -> Self
+    where
+        F: Fn(&mut ConfigChannel) + Send + Sync + 'static,
+    {
+        let steps = self.channels.entry(channel.to_string()).or_default();
+        steps.push(MigrationStep {
+            version,
+            apply: Box::new(apply),
+        });
+        steps.sort_by_key(|step| step.version);
+        self
+    }
+
+    /// Version new data of `channel` is written with; 0 without migrations
+    pub fn latest_version(&self, channel: &str) -> u32 {
+        self.channels
+            .get(channel)
+            .and_then(|steps| steps.last())
+            .map_or(0, |step| step.version)
+    }
+
+    /// Run the steps newer than the channel's version. Returns whether
+    /// anything ran.
+    pub fn migrate(&self, name: &str, channel: &mut ConfigChannel) -> bool {
+        let Some(steps) = self.channels.get(name) else {
+            return false;
+        };
+
+        let current = channel.schema_version;
+        let mut migrated = false;
+        for step in steps.iter().filter(|step| step.version > current) {
+            (step.apply)(channel);
+            channel.schema_version = step.version;
+            migrated = true;
+        }
+        migrated
+    }
+
+    /// Upgrade a freshly read channel file, backing up the original and
+    /// writing the result in its place
+    pub(crate) fn upgrade_file(&self, name: &str, path: &Path, channel: &mut ConfigChannel) -> Result<(), ConfigError> {
+        let old_version = channel.schema_version;
+        let latest = self.latest_version(name);
+        if old_version > latest {
+            warn!(
+                "Channel {} has schema version {}, newer than this release's {}",
+                name, old_version, latest
+            );
+            return Ok(());
+        }
+
+        let mut upgraded = channel.clone();
+        if !self.migrate(name, &mut upgraded) {
+            return Ok(());
+        }
+
+        let mut backup = path.as_os_str().to_os_string();
+        backup.push(format!(".v{}.bak", old_version));
+        std::fs::copy(path, &backup)?;
+
+        let content = toml::to_string_pretty(&upgraded)
+            .map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?;
+        persist::write_atomic(path, content.as_bytes())?;
+        info!("Migrated channel {} from version {} to {}", name, old_version, upgraded.schema_version);
+
+        *channel = upgraded;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConfigValue;
+
+    #[test]
+    fn test_steps_run_in_order_once() {
+        let migrations = Migrations::new()
+            .add("xfce4-panel", 2, |channel| {
+                channel.rename("/row-size", "/icon-row-size");
+            })
+            .add("xfce4-panel", 1, |channel| {
+                channel.rename("/size", "/row-size");
+            });
+        assert_eq!(migrations.latest_version("xfce4-panel"), 2);
+        assert_eq!(migrations.latest_version("xfwm4"), 0);
+
+        let mut channel = ConfigChannel::new();
+        channel.set("/size".to_string(), ConfigValue::Integer(28));
+        assert!(migrations.migrate("xfce4-panel", &mut channel));
+        assert_eq!(channel.schema_version, 2);
+        assert_eq!(channel.get("/icon-row-size"), Some(&ConfigValue::Integer(28)));
+        assert!(channel.get("/size").is_none());
+
+        assert!(!migrations.migrate("xfce4-panel", &mut channel));
+    }
+}
@@ -0,0 +1,409 @@
+//! Crash-safe, coalesced writing of configuration files
+//!
+//! Every channel is stored in its own file, like xfconf's perchannel-xml
+//! directory, so a damaged file only loses one channel and a change only
+//! rewrites the channel it touched. Writes go to a temporary file that is
+//! synced and then renamed over the real one, so a crash leaves either the
+//! old or the new contents. Bursts of changes (a slider being dragged) are
+//! collapsed into one write after a short delay.
+//!
+//! Several configs, in one process or many, may share the directory. Each
+//! writes only the properties it changed, merged into the file as it is
+//! at that moment under a lock on the directory, so none of them drops
+//! the others' changes.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::{error, warn};
+
+use crate::{ConfigChannel, ConfigError};
+
+/// File in the channel directory locked while a channel file is rewritten
+pub const LOCK_FILE: &str = ".lock";
+
+/// Default delay between a change and the write that persists it
+pub const DEFAULT_SAVE_DELAY: Duration = Duration::from_millis(300);
+
+/// File extension of per-channel files
+pub const CHANNEL_EXTENSION: &str = "toml";
+
+/// Path of the file storing `channel` inside `dir`
+pub fn channel_path(dir: &Path, channel: &str) -> PathBuf {
+    dir.join(format!("{}.{}", channel, CHANNEL_EXTENSION))
+}
+
+/// Names of the channels that have a file in `dir`
+pub fn channel_names(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == CHANNEL_EXTENSION))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// Lock the channel directory `dir` against writers in this and other
+/// processes until the returned file is dropped
+pub(crate) fn lock_dir(dir: &Path) -> std::io::Result<std::fs::File> {
+    std::fs::create_dir_all(dir)?;
+    let file = std::fs::File::options().create(true).truncate(false).write(true).open(dir.join(LOCK_FILE))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Parse one channel file; a missing file is `None`
+pub(crate) fn parse_channel(path: &Path) -> Result<Option<ConfigChannel>, ConfigError> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(toml::from_str(&content)?))
+}
+
+/// Read one of the user's channel files. An unparsable one is moved aside
+/// to `<name>.corrupt` so later saves do not destroy it, and the channel
+/// starts out empty.
+pub(crate) fn read_channel(path: &Path) -> Result<Option<ConfigChannel>, ConfigError> {
+    match parse_channel(path) {
+        Err(ConfigError::Parse(e)) => {
+            let mut aside = path.as_os_str().to_os_string();
+            aside.push(".corrupt");
+            warn!("Ignoring corrupt configuration file {}: {}", path.display(), e);
+            std::fs::rename(path, &aside)?;
+            Ok(None)
+        }
+        other => other,
+    }
+}
+
+/// Split a single-file configuration from older releases into per-channel
+/// files in `dir`, then rename it to `<name>.migrated`. Returns the number
+/// of channels written.
+pub(crate) fn migrate_single_file(file: &Path, dir: &Path) -> Result<usize, ConfigError> {
+    let content = std::fs::read_to_string(file)?;
+    let channels: HashMap<String, ConfigChannel> = toml::from_str(&content)?;
+
+    for (name, channel) in &channels {
+        let content = toml::to_string_pretty(channel)
+            .map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?;
+        write_atomic(&channel_path(dir, name), content.as_bytes())?;
+    }
+
+    let mut migrated = file.as_os_str().to_os_string();
+    migrated.push(".migrated");
+    std::fs::rename(file, migrated)?;
+    Ok(channels.len())
+}
+
+/// Write `contents` to `path` atomically
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    rename_staged(stage(path, contents, 0o666)?, path)
+}
+
+/// Write `contents` to `path` atomically, readable by the owner only from
+/// the moment the file exists
+pub fn write_atomic_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    rename_staged(stage(path, contents, 0o600)?, path)
+}
+
+fn rename_staged(tmp_path: PathBuf, path: &Path) -> std::io::Result<()> {
+    let result = std::fs::rename(&tmp_path, path);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Replace several files together: all of them are staged before the
+/// first is renamed into place, so failing to write one leaves every file
+/// as it was. `None` removes the file.
+pub(crate) fn replace_all(files: &[(PathBuf, Option<String>)]) -> std::io::Result<()> {
+    let mut staged = Vec::new();
+    for (path, contents) in files {
+        let Some(contents) = contents else { continue };
+        match stage(path, contents.as_bytes(), 0o666) {
+            Ok(tmp_path) => staged.push((tmp_path, path)),
+            Err(e) => {
+                for (tmp_path, _) in staged {
+                    let _ = std::fs::remove_file(tmp_path);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    for (tmp_path, path) in staged {
+        std::fs::rename(tmp_path, path)?;
+    }
+    for (path, _) in files.iter().filter(|(_, contents)| contents.is_none()) {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Write `contents` to a synced temporary file next to `path`, created
+/// with permissions `mode` before the umask, returning its path
+fn stage(path: &Path, contents: &[u8], mode: u32) -> std::io::Result<PathBuf> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Unique per write, not just per process: two handles saving the same
+    // channel at once must not write into each other's temporary file
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.{}.tmp", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = (|| {
+        let mut options = std::fs::File::options();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+        #[cfg(not(unix))]
+        let _ = mode;
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()
+    })();
+
+    match result {
+        Ok(()) => Ok(tmp_path),
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Fingerprint of file contents, used to recognize files we wrote
+pub(crate) fn content_hash(contents: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Unsaved changes to one channel file
+#[derive(Debug)]
+pub(crate) struct PendingWrite {
+    pub(crate) channel: String,
+    pub(crate) path: PathBuf,
+    /// The channel as the config has it
+    pub(crate) contents: ConfigChannel,
+    /// Properties of `contents` to merge into the file
+    pub(crate) changed: HashSet<String>,
+}
+
+impl PendingWrite {
+    /// Bring the file up to date, removing it if the channel ends up
+    /// empty. Returns the hash of what was written, `None` if removed.
+    fn apply(&self) -> Result<Option<u64>, ConfigError> {
+        let _lock = lock_dir(self.path.parent().unwrap_or(Path::new(".")))?;
+
+        let mut channel = read_channel(&self.path)?.unwrap_or_else(|| ConfigChannel {
+            schema_version: self.contents.schema_version,
+            ..ConfigChannel::new()
+        });
+        for property in &self.changed {
+            match self.contents.get(property) {
+                Some(value) => channel.set(property.clone(), value.clone()),
+                None => {
+                    channel.remove(property);
+                }
+            }
+        }
+
+        if channel.properties.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(None),
+            };
+        }
+        let content = toml::to_string_pretty(&channel)
+            .map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?;
+        write_atomic(&self.path, content.as_bytes())?;
+        Ok(Some(content_hash(content.as_bytes())))
+    }
+}
+
+/// Apply every write, returning what each channel's file now hashes to,
+/// or the first failure after trying them all
+pub(crate) fn apply_writes(writes: &[PendingWrite]) -> Result<Vec<(String, Option<u64>)>, ConfigError> {
+    let mut written = Vec::new();
+    let mut first_error = None;
+    for write in writes {
+        match write.apply() {
+            Ok(hash) => written.push((write.channel.clone(), hash)),
+            Err(ConfigError::Io(e)) => {
+                let e = std::io::Error::new(e.kind(), format!("{}: {}", write.path.display(), e));
+                first_error.get_or_insert(e.into());
+            }
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    first_error.map_or(Ok(written), Err)
+}
+
+#[derive(Debug, Default)]
+struct SaveState {
+    /// Changes exist that have not been written
+    dirty: bool,
+    /// A background writer task is alive
+    running: bool,
+}
+
+/// Coalesces save requests into delayed background writes
+#[derive(Debug, Clone)]
+pub(crate) struct SaveScheduler {
+    delay: Duration,
+    state: Arc<Mutex<SaveState>>,
+}
+
+impl SaveScheduler {
+    pub(crate) fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            state: Arc::new(Mutex::new(SaveState::default())),
+        }
+    }
+
+    pub(crate) fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub(crate) fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.lock().dirty
+    }
+
+    /// Forget pending changes, typically because they were just written
+    pub(crate) fn mark_clean(&self) {
+        self.lock().dirty = false;
+    }
+
+    /// Record a change and make sure a writer will persist it.
+    ///
+    /// `flush` writes whatever is unsaved when it runs, so every change
+    /// made before the delay expires lands in one write.
+    /// Returns `false` if no background writer could be started and the
+    /// caller must write synchronously.
+    pub(crate) fn schedule<F, Fut>(&self, flush: F) -> bool
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<(), ConfigError>> + Send,
+    {
+        if self.delay.is_zero() {
+            return false;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return false;
+        };
+
+        {
+            let mut state = self.lock();
+            state.dirty = true;
+            if state.running {
+                return true;
+            }
+            state.running = true;
+        }
+
+        let delay = self.delay;
+        let state = self.state.clone();
+        runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(delay).await;
+
+                {
+                    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                    if !state.dirty {
+                        state.running = false;
+                        return;
+                    }
+                    state.dirty = false;
+                }
+
+                if let Err(e) = flush().await {
+                    error!("Failed to save configuration: {}", e);
+                }
+            }
+        });
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SaveState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("config.toml");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        let leftovers = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn test_concurrent_writes_do_not_mix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let contents: Vec<Vec<u8>> = (0..8u8).map(|n| vec![b'a' + n; 64 * 1024]).collect();
+
+        std::thread::scope(|scope| {
+            for contents in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..10 {
+                        write_atomic(path, contents).unwrap();
+                    }
+                });
+            }
+        });
+
+        // Whole, from one of the writers
+        assert!(contents.contains(&std::fs::read(&path).unwrap()));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_corrupt_channel_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = channel_path(dir.path(), "xfwm4");
+        std::fs::write(&path, "not [valid toml").unwrap();
+
+        assert!(read_channel(&path).unwrap().is_none());
+        assert!(!path.exists());
+        assert!(dir.path().join("xfwm4.toml.corrupt").exists());
+        assert!(channel_names(dir.path()).is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+//! Picking up channel files edited outside this process
+//!
+//! [`XfceConfig::watch_files`] watches the channel directory and merges
+//! external edits into the loaded channels, emitting the same events as
+//! [`XfceConfig::set_property`]. When a channel has unsaved changes and its
+//! file changes too, the changed properties stay as they are on top of the
+//! edit, and the pending save merges them into the file.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+use tracing::warn;
+use xfce_rs_utils::{DirWatcher, FsEvent, WatchOptions};
+
+use crate::{persist, ConfigChannel, ConfigError, XfceConfig};
+
+/// Keeps a config in sync with its files until dropped
+#[derive(Debug)]
+pub struct ConfigReloader {
+    task: JoinHandle<()>,
+}
+
+impl Drop for ConfigReloader {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl XfceConfig {
+    /// Reload channels whenever their files change on disk. Must be called
+    /// within a Tokio runtime.
+    pub fn watch_files(self: &Arc<Self>) -> Result<ConfigReloader, ConfigError> {
+        std::fs::create_dir_all(&self.config_dir)?;
+        let options = WatchOptions {
+            recursive: false,
+            ..WatchOptions::default()
+        };
+        let mut watcher = DirWatcher::with_options(&self.config_dir, options)
+            .map_err(|e| ConfigError::Watch { reason: e.to_string() })?;
+
+        let config = Arc::downgrade(self);
+        let dir = self.config_dir.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = watcher.next_event().await {
+                let Some(config) = config.upgrade() else {
+                    return;
+                };
+
+                let channels: Vec<String> = match event {
+                    FsEvent::Created(path) | FsEvent::Modified(path) | FsEvent::Removed(path) => {
+                        channel_of(&dir, &path).into_iter().collect()
+                    }
+                    FsEvent::Renamed { from, to } => {
+                        [from, to].iter().filter_map(|path| channel_of(&dir, path)).collect()
+                    }
+                    FsEvent::Rescan => config.store.read().await.loaded.iter().cloned().collect(),
+                };
+
+                for channel in channels {
+                    if let Err(e) = config.reload_channel(&channel).await {
+                        warn!("Failed to reload channel {}: {}", channel, e);
+                    }
+                }
+            }
+        });
+
+        Ok(ConfigReloader { task })
+    }
+
+    /// Merge the file of `channel` into memory if something other than
+    /// this config changed it. Channels not loaded yet are left alone.
+    pub async fn reload_channel(&self, channel: &str) -> Result<(), ConfigError> {
+        let path = self.channel_file(channel)?;
+        let contents = match std::fs::read(&path) {
+            Ok(contents) => Some(contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let events = {
+            let mut store = self.store.write().await;
+            if !store.loaded.contains(channel) {
+                return Ok(());
+            }
+
+            let hash = contents.as_deref().map(persist::content_hash);
+            if store.written.get(channel) == Some(&hash) {
+                return Ok(());
+            }
+
+            let changed = store.dirty.get(channel).cloned().unwrap_or_default();
+
+            let mut updated = match &contents {
+                Some(contents) => match toml::from_str::<ConfigChannel>(&String::from_utf8_lossy(contents)) {
+                    Ok(updated) => updated,
+                    Err(e) => {
+                        // Possibly caught mid-write; the next event retries
+                        warn!("Ignoring unparsable external edit of {}: {}", path.display(), e);
+                        return Ok(());
+                    }
+                },
+                None => ConfigChannel::new(),
+            };
+            let ours = store.channels.get(channel);
+            for property in changed {
+                match ours.and_then(|channel| channel.get(&property)) {
+                    Some(value) => updated.set(property, value.clone()),
+                    None => {
+                        updated.remove(&property);
+                    }
+                }
+            }
+
+            store.written.insert(channel.to_string(), hash);
+            store.replace(&self.locks, channel, updated)
+        };
+
+        for event in events {
+            self.notify(event);
+        }
+        Ok(())
+    }
+}
+
+/// The channel a file in the config directory stores, if any
+fn channel_of(dir: &Path, path: &Path) -> Option<String> {
+    if path.parent() != Some(dir) || path.extension().is_none_or(|ext| ext != persist::CHANNEL_EXTENSION) {
+        return None;
+    }
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConfigEventKind, ConfigValue};
+    use std::time::Duration;
+
+    #[test]
+    fn test_channel_of() {
+        let dir = Path::new("/home/user/.config/xfce4/xfconf/xfce-perchannel-rs");
+        assert_eq!(channel_of(dir, &dir.join("xfwm4.toml")), Some("xfwm4".to_string()));
+        assert_eq!(channel_of(dir, &dir.join("xfwm4.toml.1234.tmp")), None);
+        assert_eq!(channel_of(dir, &dir.join("nested").join("xfwm4.toml")), None);
+    }
+
+    #[tokio::test]
+    async fn test_external_edit_is_merged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let mut config = XfceConfig::new(&config_dir).unwrap();
+        config.set_save_delay(Duration::ZERO);
+        let config = Arc::new(config);
+
+        config.set_property("xfwm4", "/general/theme", ConfigValue::String("Default".to_string())).await.unwrap();
+        config.set_property("xfwm4", "/general/title_font", ConfigValue::String("Sans 9".to_string())).await.unwrap();
+        let mut events = config.watch("xfwm4", "");
+        let _reloader = config.watch_files().unwrap();
+
+        // Our own writes are not reported back
+        config.set_property("xfwm4", "/general/theme", ConfigValue::String("Greybird".to_string())).await.unwrap();
+        assert_eq!(events.recv().await.unwrap().new_value, Some(ConfigValue::String("Greybird".to_string())));
+
+        std::fs::write(
+            config_dir.join("xfwm4.toml"),
+            "[properties]\n\"/general/theme\" = { String = \"Adwaita\" }\n",
+        )
+        .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        assert_eq!(event.property, "/general/theme");
+        assert_eq!(event.kind, ConfigEventKind::Set);
+        assert_eq!(event.new_value, Some(ConfigValue::String("Adwaita".to_string())));
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        assert_eq!(event.property, "/general/title_font");
+        assert_eq!(event.kind, ConfigEventKind::Removed);
+
+        assert_eq!(config.get_string("xfwm4", "/general/theme").await.unwrap(), "Adwaita");
+    }
+}
@@ -0,0 +1,378 @@
+//! Encrypted channels for passwords and other credentials
+//!
+//! A [`SecretChannel`] holds the same kind of properties as a plain
+//! channel but is stored as `<config dir>/<channel>.secret`, encrypted
+//! with XChaCha20-Poly1305. Each channel has its own 256-bit key kept in
+//! the user's keyring through the freedesktop Secret Service API
+//! (gnome-keyring, KeePassXC, KWallet), so the file alone reveals nothing.
+//!
+//! Secret channels never take part in defaults, locks, snapshots or
+//! change notifications, and are written to disk on every change.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use zbus::proxy;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::Connection;
+use zeroize::Zeroizing;
+
+use crate::{persist, ConfigChannel, ConfigError, ConfigValue, XfceConfig};
+
+/// Extension of encrypted channel files
+pub const SECRET_EXTENSION: &str = "secret";
+
+/// Version of the on-disk format
+const FORMAT_VERSION: u32 = 1;
+
+/// Attribute identifying our items in the keyring
+const APPLICATION_ATTRIBUTE: &str = "xfce-rs";
+
+const DEFAULT_COLLECTION_PATH: &str = "/org/freedesktop/secrets/aliases/default";
+
+/// Secret Service `(session, parameters, value, content type)` tuple
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+/// `org.freedesktop.Secret.Service`
+#[proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets"
+)]
+trait SecretService {
+    fn open_session(&self, algorithm: &str, input: &Value<'_>) -> zbus::Result<(OwnedValue, OwnedObjectPath)>;
+
+    fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)>;
+
+    fn unlock(&self, objects: &[OwnedObjectPath]) -> zbus::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)>;
+
+    fn get_secrets(
+        &self,
+        items: &[OwnedObjectPath],
+        session: &ObjectPath<'_>,
+    ) -> zbus::Result<HashMap<OwnedObjectPath, Secret>>;
+}
+
+/// `org.freedesktop.Secret.Collection`
+#[proxy(interface = "org.freedesktop.Secret.Collection", default_service = "org.freedesktop.secrets")]
+trait SecretCollection {
+    fn create_item(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        secret: &(ObjectPath<'_>, Vec<u8>, Vec<u8>, &str),
+        replace: bool,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+}
+
+/// `org.freedesktop.Secret.Prompt`
+#[proxy(interface = "org.freedesktop.Secret.Prompt", default_service = "org.freedesktop.secrets")]
+trait SecretPrompt {
+    fn prompt(&self, window_id: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn completed(&self, dismissed: bool, result: Value<'_>) -> zbus::Result<()>;
+}
+
+fn secret_error(reason: impl Into<String>) -> ConfigError {
+    ConfigError::Secret { reason: reason.into() }
+}
+
+/// A 256-bit channel key, wiped from memory when dropped
+#[derive(Clone)]
+pub struct SecretKey(Zeroizing<[u8; 32]>);
+
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+impl SecretKey {
+    /// A new random key
+    pub fn generate() -> Self {
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        Self(Zeroizing::new(key.into()))
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(Zeroizing::new(bytes))
+    }
+
+    /// The keyring key of `channel`. Without one, a new key is stored if
+    /// `create`, and it is an error otherwise: data encrypted with the
+    /// lost key would be written over. May show an unlock prompt if the
+    /// keyring is locked.
+    pub async fn from_secret_service(channel: &str, create: bool) -> Result<Self, ConfigError> {
+        let connection = Connection::session().await?;
+        let service = SecretServiceProxy::new(&connection).await?;
+        // The session bus is private to the user, so the key travels
+        // unencrypted like it does with libsecret's plain sessions
+        let (_, session) = service.open_session("plain", &Value::from("")).await?;
+
+        let attributes = HashMap::from([("application", APPLICATION_ATTRIBUTE), ("channel", channel)]);
+        let (mut unlocked, locked) = service.search_items(attributes.clone()).await?;
+        if unlocked.is_empty() && !locked.is_empty() {
+            let (now_unlocked, prompt) = service.unlock(&locked).await?;
+            unlocked = now_unlocked;
+            if unlocked.is_empty() {
+                complete_prompt(&connection, prompt).await?;
+                unlocked = service.search_items(attributes.clone()).await?.0;
+            }
+        }
+
+        if let Some(item) = unlocked.first() {
+            let secrets = service.get_secrets(std::slice::from_ref(item), &session).await?;
+            let (_, _, value, _) = secrets
+                .into_values()
+                .next()
+                .ok_or_else(|| secret_error(format!("Keyring returned no key for {}", channel)))?;
+            let value = Zeroizing::new(value);
+            let bytes: [u8; 32] = value
+                .as_slice()
+                .try_into()
+                .map_err(|_| secret_error(format!("Keyring key for {} has the wrong length", channel)))?;
+            return Ok(Self::from_bytes(bytes));
+        }
+        if !create {
+            return Err(secret_error(format!("Keyring key missing for existing secret channel {}", channel)));
+        }
+
+        let key = Self::generate();
+        let collection = SecretCollectionProxy::builder(&connection)
+            .path(DEFAULT_COLLECTION_PATH)?
+            .build()
+            .await?;
+        let label = format!("XFCE.rs secret channel {}", channel);
+        let properties = HashMap::from([
+            ("org.freedesktop.Secret.Item.Label", Value::from(label)),
+            ("org.freedesktop.Secret.Item.Attributes", Value::from(attributes)),
+        ]);
+        let secret = (session.as_ref(), Vec::new(), key.0.to_vec(), "application/octet-stream");
+        let (_, prompt) = collection.create_item(properties, &secret, true).await?;
+        complete_prompt(&connection, prompt).await?;
+        Ok(key)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(self.0.as_ref().into())
+    }
+}
+
+/// Show a Secret Service prompt, if one was returned, and wait for the user
+async fn complete_prompt(connection: &Connection, prompt: OwnedObjectPath) -> Result<(), ConfigError> {
+    if prompt.as_str() == "/" {
+        return Ok(());
+    }
+
+    let prompt = SecretPromptProxy::builder(connection).path(prompt)?.build().await?;
+    let mut completed = prompt.receive_completed().await?;
+    prompt.prompt("").await?;
+    let signal = completed
+        .next()
+        .await
+        .ok_or_else(|| secret_error("Keyring prompt vanished"))?;
+    if signal.args()?.dismissed {
+        return Err(secret_error("Keyring prompt was dismissed"));
+    }
+    Ok(())
+}
+
+/// Layout of a `.secret` file
+#[derive(Serialize, Deserialize)]
+struct SecretFile {
+    version: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// An encrypted channel, decrypted in memory while open
+pub struct SecretChannel {
+    name: String,
+    path: PathBuf,
+    key: SecretKey,
+    channel: ConfigChannel,
+}
+
+impl std::fmt::Debug for SecretChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretChannel")
+            .field("name", &self.name)
+            .field("path", &self.path)
+            .field("properties", &self.channel.properties.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecretChannel {
+    /// Open the channel stored at `path` with `key`, starting empty if the
+    /// file does not exist yet
+    pub fn open(name: &str, path: impl Into<PathBuf>, key: SecretKey) -> Result<Self, ConfigError> {
+        let path = path.into();
+        let channel = match std::fs::read_to_string(&path) {
+            Ok(content) => decrypt(name, &key, &content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ConfigChannel::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            path,
+            key,
+            channel,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, property: &str) -> Option<&ConfigValue> {
+        self.channel.get(property)
+    }
+
+    /// Store a value and write the channel out
+    pub fn set(&mut self, property: &str, value: ConfigValue) -> Result<(), ConfigError> {
+        self.channel.set(property.to_string(), value);
+        self.save()
+    }
+
+    /// Remove a value and write the channel out
+    pub fn remove(&mut self, property: &str) -> Result<Option<ConfigValue>, ConfigError> {
+        let removed = self.channel.remove(property);
+        if removed.is_some() {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Stored property names, sorted
+    pub fn properties(&self) -> Vec<String> {
+        let mut properties: Vec<String> = self.channel.properties.keys().cloned().collect();
+        properties.sort();
+        properties
+    }
+
+    fn save(&self) -> Result<(), ConfigError> {
+        let content = encrypt(&self.name, &self.key, &self.channel)?;
+        persist::write_atomic_private(&self.path, content.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Serialize and encrypt a channel, binding the ciphertext to its name so
+/// files cannot be swapped between channels
+fn encrypt(name: &str, key: &SecretKey, channel: &ConfigChannel) -> Result<String, ConfigError> {
+    let plaintext = Zeroizing::new(
+        toml::to_string(channel).map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?,
+    );
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = key
+        .cipher()
+        .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: name.as_bytes() })
+        .map_err(|_| secret_error(format!("Failed to encrypt secret channel {}", name)))?;
+
+    let file = SecretFile {
+        version: FORMAT_VERSION,
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    toml::to_string(&file).map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })
+}
+
+fn decrypt(name: &str, key: &SecretKey, content: &str) -> Result<ConfigChannel, ConfigError> {
+    let file: SecretFile = toml::from_str(content)?;
+    if file.version != FORMAT_VERSION {
+        return Err(ConfigError::InvalidFormat {
+            reason: format!("Unsupported secret channel version {}", file.version),
+        });
+    }
+
+    let invalid = |what: &str| ConfigError::InvalidFormat {
+        reason: format!("Secret channel {} has an invalid {}", name, what),
+    };
+    let nonce = BASE64.decode(&file.nonce).map_err(|_| invalid("nonce"))?;
+    if nonce.len() != 24 {
+        return Err(invalid("nonce"));
+    }
+    let ciphertext = BASE64.decode(&file.ciphertext).map_err(|_| invalid("ciphertext"))?;
+
+    let plaintext = Zeroizing::new(
+        key.cipher()
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: name.as_bytes() })
+            .map_err(|_| secret_error(format!("Wrong key or tampered file for secret channel {}", name)))?,
+    );
+    let plaintext = std::str::from_utf8(&plaintext).map_err(|_| invalid("payload"))?;
+    Ok(toml::from_str(plaintext)?)
+}
+
+impl XfceConfig {
+    /// Open the encrypted channel `name`, fetching its key from the
+    /// user's keyring. A new channel gets a new key; an existing one whose
+    /// key is gone from the keyring fails to open rather than be replaced.
+    pub async fn secret_channel(&self, name: &str) -> Result<SecretChannel, ConfigError> {
+        let path = self.channel_file(name)?.with_extension(SECRET_EXTENSION);
+        let key = SecretKey::from_secret_service(name, !path.try_exists()?).await?;
+        SecretChannel::open(name, path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_channel_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("network.secret");
+        let key = SecretKey::generate();
+
+        let mut channel = SecretChannel::open("network", &path, key.clone()).unwrap();
+        channel.set("/wifi/home/psk", ConfigValue::String("hunter22".to_string())).unwrap();
+        channel.set("/vpn/work/otp", ConfigValue::Integer(123456)).unwrap();
+
+        let on_disk = std::fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains("hunter22"));
+        assert!(!on_disk.contains("/wifi"));
+
+        let mut reopened = SecretChannel::open("network", &path, key.clone()).unwrap();
+        assert_eq!(reopened.get("/wifi/home/psk"), Some(&ConfigValue::String("hunter22".to_string())));
+        assert_eq!(reopened.properties(), ["/vpn/work/otp", "/wifi/home/psk"]);
+        assert_eq!(reopened.remove("/vpn/work/otp").unwrap(), Some(ConfigValue::Integer(123456)));
+        assert_eq!(reopened.remove("/vpn/work/otp").unwrap(), None);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_wrong_key_or_channel_is_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("network.secret");
+        let key = SecretKey::generate();
+
+        let mut channel = SecretChannel::open("network", &path, key.clone()).unwrap();
+        channel.set("/wifi/home/psk", ConfigValue::String("hunter22".to_string())).unwrap();
+
+        assert!(matches!(
+            SecretChannel::open("network", &path, SecretKey::generate()),
+            Err(ConfigError::Secret { .. })
+        ));
+        assert!(matches!(SecretChannel::open("vpn", &path, key), Err(ConfigError::Secret { .. })));
+    }
+}
@@ -0,0 +1,270 @@
+//! Named copies of the whole configuration that can be restored later
+//!
+//! A snapshot stores every channel's user values in one archive under
+//! `<config dir>/.snapshots/<unix time>-<name>.toml`. Restoring swaps all
+//! channels at once and emits change events for whatever differs, so
+//! running components follow along.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{persist, ConfigChannel, ConfigError, XfceConfig};
+
+/// Directory inside the config directory holding the archives
+pub const SNAPSHOT_DIR: &str = ".snapshots";
+
+/// A stored snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub created: SystemTime,
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotArchive {
+    name: String,
+    /// Seconds since the Unix epoch
+    created: u64,
+    channels: HashMap<String, ConfigChannel>,
+}
+
+impl XfceConfig {
+    fn snapshot_dir(&self) -> PathBuf {
+        self.config_dir.join(SNAPSHOT_DIR)
+    }
+
+    /// Save the user values of every channel as snapshot `name`
+    pub async fn snapshot(&self, name: &str) -> Result<SnapshotInfo, ConfigError> {
+        if name.is_empty() || name.contains(['/', '\\']) {
+            return Err(ConfigError::InvalidFormat {
+                reason: format!("Invalid snapshot name \"{}\"", name),
+            });
+        }
+
+        let names = self.list_channels().await;
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        self.ensure_loaded(&names).await?;
+
+        let created = SystemTime::now();
+        let seconds = created.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let archive = {
+            let store = self.store.read().await;
+            SnapshotArchive {
+                name: name.to_string(),
+                created: seconds,
+                channels: store
+                    .channels
+                    .iter()
+                    .filter(|(_, channel)| !channel.properties.is_empty())
+                    .map(|(name, channel)| (name.clone(), channel.clone()))
+                    .collect(),
+            }
+        };
+
+        let content = toml::to_string_pretty(&archive)
+            .map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?;
+        let path = self.snapshot_dir().join(format!("{}-{}.toml", seconds, name));
+        persist::write_atomic(&path, content.as_bytes())?;
+
+        Ok(SnapshotInfo {
+            name: name.to_string(),
+            created: UNIX_EPOCH + Duration::from_secs(seconds),
+            path,
+        })
+    }
+
+    /// Every stored snapshot, oldest first
+    pub fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>, ConfigError> {
+        let entries = match std::fs::read_dir(self.snapshot_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut snapshots: Vec<SnapshotInfo> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| parse_file_name(&entry.path()))
+            .collect();
+        snapshots.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.name.cmp(&b.name)));
+        Ok(snapshots)
+    }
+
+    /// Replace the whole configuration with the newest snapshot called
+    /// `name`, writing it out before returning. Snapshots from older
+    /// releases go through the config's migrations first. Either every
+    /// channel file is replaced or, on failure, none is.
+    pub async fn restore(&self, name: &str) -> Result<(), ConfigError> {
+        let snapshot = self
+            .list_snapshots()?
+            .into_iter()
+            .rev()
+            .find(|snapshot| snapshot.name == name)
+            .ok_or_else(|| ConfigError::FileNotFound {
+                path: self.snapshot_dir().join(format!("*-{}.toml", name)).display().to_string(),
+            })?;
+        let archive: SnapshotArchive = toml::from_str(&std::fs::read_to_string(&snapshot.path)?)?;
+
+        let mut names: Vec<String> = self.list_channels().await;
+        names.extend(archive.channels.keys().cloned());
+        names.sort();
+        names.dedup();
+        let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+        self.ensure_loaded(&name_refs).await?;
+
+        let mut channels = archive.channels;
+        let restored: Vec<(String, ConfigChannel)> = names
+            .iter()
+            .map(|name| {
+                let mut channel = channels.remove(name).unwrap_or_else(ConfigChannel::new);
+                self.migrations.migrate(name, &mut channel);
+                (name.clone(), channel)
+            })
+            .collect();
+        let mut files = Vec::new();
+        for (name, channel) in &restored {
+            let contents = if channel.properties.is_empty() {
+                None
+            } else {
+                Some(toml::to_string_pretty(channel).map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?)
+            };
+            files.push((self.channel_file(name)?, contents));
+        }
+
+        // Held throughout, so no change slips in between the files and
+        // the store
+        let mut store = self.store.write().await;
+        let dir = self.config_dir.clone();
+        let hashes: Vec<Option<u64>> = files
+            .iter()
+            .map(|(_, contents)| contents.as_ref().map(|contents| persist::content_hash(contents.as_bytes())))
+            .collect();
+        tokio::task::spawn_blocking(move || {
+            let _lock = persist::lock_dir(&dir)?;
+            persist::replace_all(&files)
+        })
+        .await
+        .map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })??;
+
+        let mut events = Vec::new();
+        for ((name, channel), hash) in restored.into_iter().zip(hashes) {
+            events.extend(store.replace(&self.locks, &name, channel));
+            store.written.insert(name.clone(), hash);
+            store.dirty.remove(&name);
+        }
+        drop(store);
+
+        for event in events {
+            self.notify(event);
+        }
+        Ok(())
+    }
+
+    /// Delete every snapshot called `name`
+    pub fn delete_snapshot(&self, name: &str) -> Result<(), ConfigError> {
+        for snapshot in self.list_snapshots()?.into_iter().filter(|snapshot| snapshot.name == name) {
+            std::fs::remove_file(&snapshot.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split `<unix time>-<name>.toml`
+fn parse_file_name(path: &Path) -> Option<SnapshotInfo> {
+    if path.extension()? != persist::CHANNEL_EXTENSION {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let (seconds, name) = stem.split_once('-')?;
+    let seconds: u64 = seconds.parse().ok()?;
+
+    Some(SnapshotInfo {
+        name: name.to_string(),
+        created: UNIX_EPOCH + Duration::from_secs(seconds),
+        path: path.to_path_buf(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConfigEventKind, ConfigValue, Migrations};
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
+
+        config.set_property("xfwm4", "/general/theme", ConfigValue::String("Default".to_string())).await.unwrap();
+        config.set_property("xfce4-panel", "/size", ConfigValue::Integer(28)).await.unwrap();
+        let snapshot = config.snapshot("before-experiment").await.unwrap();
+        assert!(snapshot.path.starts_with(config_dir.join(SNAPSHOT_DIR)));
+
+        config.set_property("xfwm4", "/general/theme", ConfigValue::String("Neon".to_string())).await.unwrap();
+        config.remove_property("xfce4-panel", "/size").await.unwrap();
+        config.set_property("thunar", "/hidden", ConfigValue::Boolean(true)).await.unwrap();
+
+        let mut events = config.watch_all();
+        config.restore("before-experiment").await.unwrap();
+        assert_eq!(config.get_string("xfwm4", "/general/theme").await.unwrap(), "Default");
+        assert_eq!(config.get_int("xfce4-panel", "/size").await.unwrap(), 28);
+        assert!(config.get_property("thunar", "/hidden").await.is_err());
+
+        let mut changed = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            changed.push((event.channel, event.kind));
+        }
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            changed,
+            [
+                ("thunar".to_string(), ConfigEventKind::Removed),
+                ("xfce4-panel".to_string(), ConfigEventKind::Set),
+                ("xfwm4".to_string(), ConfigEventKind::Set),
+            ]
+        );
+
+        // The restored state reached the disk
+        let reloaded = XfceConfig::new(&config_dir).unwrap();
+        assert_eq!(reloaded.get_int("xfce4-panel", "/size").await.unwrap(), 28);
+        assert!(!config_dir.join("thunar.toml").exists());
+
+        assert_eq!(config.list_snapshots().unwrap().len(), 1);
+        assert!(config.restore("missing").await.is_err());
+        config.delete_snapshot("before-experiment").unwrap();
+        assert!(config.list_snapshots().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_migrates_old_snapshots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_dir = temp_dir.path().join("channels");
+        let config = XfceConfig::new(&config_dir).unwrap();
+        config.set_property("xfce4-panel", "/size", ConfigValue::Integer(28)).await.unwrap();
+        config.snapshot("old-release").await.unwrap();
+
+        let migrations = Migrations::new().add("xfce4-panel", 1, |channel| {
+            channel.rename("/size", "/row-size");
+        });
+        let upgraded = XfceConfig::new(&config_dir).unwrap().with_migrations(migrations);
+        upgraded.restore("old-release").await.unwrap();
+        assert_eq!(upgraded.get_int("xfce4-panel", "/row-size").await.unwrap(), 28);
+        assert!(upgraded.get_property("xfce4-panel", "/size").await.is_err());
+
+        let reloaded = XfceConfig::new(&config_dir).unwrap();
+        assert_eq!(reloaded.get_int("xfce4-panel", "/row-size").await.unwrap(), 28);
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        let info = parse_file_name(Path::new("/s/1700000000-pre-theme.toml")).unwrap();
+        assert_eq!(info.name, "pre-theme");
+        assert_eq!(info.created, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        assert!(parse_file_name(Path::new("/s/notes.txt")).is_none());
+        assert!(parse_file_name(Path::new("/s/xfwm4.toml")).is_none());
+    }
+}
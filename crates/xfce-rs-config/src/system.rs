@@ -0,0 +1,155 @@
+//! System-wide defaults and kiosk locks
+//!
+//! Administrators ship channel files in `$XDG_CONFIG_DIRS/xfce-rs`
+//! (normally `/etc/xdg/xfce-rs`) using the same format as the user's
+//! per-channel files. Values there apply whenever the user has not set the
+//! property. A `kiosk.toml` next to them lists properties users may not
+//! change:
+//!
+//! ```toml
+//! [locked]
+//! xfwm4 = ["/general/theme"]
+//! xfce4-panel = ["/panels/*"]
+//! ```
+//!
+//! A trailing `*` locks every property starting with what precedes it, so
+//! `"*"` locks the whole channel.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{persist, ConfigChannel};
+
+/// Name of the lock list inside a system configuration directory
+pub const KIOSK_FILE: &str = "kiosk.toml";
+
+/// `xfce-rs` inside every `$XDG_CONFIG_DIRS` entry, most important first
+pub fn system_config_dirs() -> Vec<PathBuf> {
+    let dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+
+    dirs.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join("xfce-rs"))
+        .collect()
+}
+
+/// Merge the defaults for `channel` from `dirs`, earlier directories
+/// overriding later ones. Unreadable files are skipped.
+pub(crate) fn read_defaults(dirs: &[PathBuf], channel: &str) -> Option<ConfigChannel> {
+    let mut merged: Option<ConfigChannel> = None;
+
+    for dir in dirs.iter().rev() {
+        let path = persist::channel_path(dir, channel);
+        let channel = match persist::parse_channel(&path) {
+            Ok(Some(channel)) => channel,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Ignoring system defaults in {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        merged
+            .get_or_insert_with(ConfigChannel::new)
+            .properties
+            .extend(channel.properties);
+    }
+    merged
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KioskFile {
+    #[serde(default)]
+    locked: HashMap<String, Vec<String>>,
+}
+
+/// Properties an administrator has made read-only
+#[derive(Debug, Clone, Default)]
+pub struct LockList {
+    rules: HashMap<String, Vec<String>>,
+}
+
+impl LockList {
+    /// Combine the `kiosk.toml` files of every directory in `dirs`
+    pub fn load(dirs: &[PathBuf]) -> Self {
+        let mut list = Self::default();
+
+        for dir in dirs {
+            let path = dir.join(KIOSK_FILE);
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            match toml::from_str::<KioskFile>(&content) {
+                Ok(file) => {
+                    for (channel, patterns) in file.locked {
+                        list.rules.entry(channel).or_default().extend(patterns);
+                    }
+                }
+                Err(e) => warn!("Ignoring invalid lock list {}: {}", path.display(), e),
+            }
+        }
+        list
+    }
+
+    /// Lock `pattern` in `channel`
+    pub fn lock(&mut self, channel: &str, pattern: &str) {
+        self.rules.entry(channel.to_string()).or_default().push(pattern.to_string());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.values().all(Vec::is_empty)
+    }
+
+    pub fn is_locked(&self, channel: &str, property: &str) -> bool {
+        self.rules.get(channel).is_some_and(|patterns| {
+            patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => property.starts_with(prefix),
+                None => pattern == property,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConfigValue;
+
+    #[test]
+    fn test_defaults_and_locks() {
+        let vendor = tempfile::tempdir().unwrap();
+        let site = tempfile::tempdir().unwrap();
+        std::fs::write(
+            vendor.path().join("xfwm4.toml"),
+            "[properties]\n\"/general/theme\" = { String = \"Default\" }\n\"/general/workspace_count\" = { Integer = 4 }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            site.path().join("xfwm4.toml"),
+            "[properties]\n\"/general/theme\" = { String = \"Corporate\" }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            site.path().join(KIOSK_FILE),
+            "[locked]\nxfwm4 = [\"/general/theme\"]\nxfce4-panel = [\"/panels/*\"]\n",
+        )
+        .unwrap();
+
+        let dirs = vec![site.path().to_path_buf(), vendor.path().to_path_buf()];
+        let defaults = read_defaults(&dirs, "xfwm4").unwrap();
+        assert_eq!(defaults.get("/general/theme"), Some(&ConfigValue::String("Corporate".to_string())));
+        assert_eq!(defaults.get("/general/workspace_count"), Some(&ConfigValue::Integer(4)));
+        assert!(read_defaults(&dirs, "thunar").is_none());
+
+        let locks = LockList::load(&dirs);
+        assert!(locks.is_locked("xfwm4", "/general/theme"));
+        assert!(!locks.is_locked("xfwm4", "/general/theme_extra"));
+        assert!(locks.is_locked("xfce4-panel", "/panels/panel-1/size"));
+        assert!(!locks.is_locked("thunar", "/panels/panel-1/size"));
+    }
+}
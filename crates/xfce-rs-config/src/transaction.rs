@@ -0,0 +1,103 @@
+//! Grouped property changes applied all at once
+
+use crate::{ConfigError, ConfigValue, XfceConfig};
+
+enum Change {
+    Set { channel: String, property: String, value: ConfigValue },
+    Remove { channel: String, property: String },
+}
+
+/// A batch of changes started with [`XfceConfig::begin`].
+///
+/// Nothing is visible to readers or written to disk until [`commit`] is
+/// called; dropping the transaction discards it.
+///
+/// [`commit`]: ConfigTransaction::commit
+#[must_use = "a transaction does nothing unless committed"]
+pub struct ConfigTransaction<'a> {
+    config: &'a XfceConfig,
+    changes: Vec<Change>,
+}
+
+impl<'a> ConfigTransaction<'a> {
+    pub(crate) fn new(config: &'a XfceConfig) -> Self {
+        Self {
+            config,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Queue a property write
+    pub fn set(&mut self, channel: &str, property: &str, value: impl Into<ConfigValue>) -> &mut Self {
+        self.changes.push(Change::Set {
+            channel: channel.to_string(),
+            property: property.to_string(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Queue a property removal; removing a missing property is not an error
+    pub fn remove(&mut self, channel: &str, property: &str) -> &mut Self {
+        self.changes.push(Change::Remove {
+            channel: channel.to_string(),
+            property: property.to_string(),
+        });
+        self
+    }
+
+    /// Number of queued changes
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Apply every queued change under one lock, persist once and notify.
+    /// Fails without changing anything if a property is locked.
+    pub async fn commit(self) -> Result<(), ConfigError> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+
+        // A single locked property refuses the whole batch
+        for change in &self.changes {
+            let (Change::Set { channel, property, .. } | Change::Remove { channel, property }) = change;
+            self.config.check_unlocked(channel, property)?;
+        }
+
+        let names: Vec<&str> = self
+            .changes
+            .iter()
+            .map(|change| match change {
+                Change::Set { channel, .. } | Change::Remove { channel, .. } => channel.as_str(),
+            })
+            .collect();
+        self.config.ensure_loaded(&names).await?;
+
+        let mut events = Vec::new();
+        {
+            let mut store = self.config.store.write().await;
+
+            for change in self.changes {
+                let event = match change {
+                    Change::Set { channel, property, value } => store.set(&channel, &property, value),
+                    Change::Remove { channel, property } => store.remove(&channel, &property).map(|(_, event)| event),
+                };
+                events.extend(event);
+            }
+        }
+
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        self.config.request_save().await?;
+        for event in events {
+            self.config.notify(event);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+//! Strongly-typed access to configuration values
+//!
+//! [`FromConfigValue`] converts stored values into Rust types, and
+//! [`ConfigSection`] binds a whole struct to a channel. The
+//! [`config_section!`](crate::config_section) macro writes the binding for
+//! plain structs so settings code never matches on [`ConfigValue`] by hand.
+//! Anything implementing serde's traits, such as keybinding or plugin
+//! descriptions, converts with [`ConfigValue::from_serialize`] and
+//! [`ConfigValue::deserialize`].
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{ConfigError, ConfigValue, Rgba};
+
+/// Conversion from a stored [`ConfigValue`]
+pub trait FromConfigValue: Sized {
+    /// Human-readable type name used in mismatch errors
+    const TYPE_NAME: &'static str;
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self>;
+}
+
+impl FromConfigValue for ConfigValue {
+    const TYPE_NAME: &'static str = "any";
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromConfigValue for String {
+    const TYPE_NAME: &'static str = "string";
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for bool {
+    const TYPE_NAME: &'static str = "bool";
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for f64 {
+    const TYPE_NAME: &'static str = "double";
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Float(f) => Some(*f),
+            ConfigValue::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for f32 {
+    const TYPE_NAME: &'static str = "float";
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        f64::from_config_value(value).map(|f| f as f32)
+    }
+}
+
+macro_rules! impl_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl FromConfigValue for $ty {
+                const TYPE_NAME: &'static str = "int";
+
+                fn from_config_value(value: &ConfigValue) -> Option<Self> {
+                    match value {
+                        ConfigValue::Integer(i) => <$ty>::try_from(*i).ok(),
+                        _ => None,
+                    }
+                }
+            }
+
+            impl From<$ty> for ConfigValue {
+                fn from(value: $ty) -> Self {
+                    ConfigValue::Integer(value as i64)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i64, i32, u32, u16, u8, usize);
+
+impl<T: FromConfigValue> FromConfigValue for Vec<T> {
+    const TYPE_NAME: &'static str = "array";
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Array(items) => items.iter().map(T::from_config_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for Rgba {
+    const TYPE_NAME: &'static str = "color";
+
+    /// Also accepts `#rrggbbaa` strings and xfconf's four-double arrays
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Color(color) => Some(*color),
+            ConfigValue::String(s) => Rgba::from_hex(s),
+            ConfigValue::Array(items) if items.len() == 4 => {
+                let c: Vec<f64> = items.iter().map(f64::from_config_value).collect::<Option<_>>()?;
+                Some(Rgba::new(c[0], c[1], c[2], c[3]))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromConfigValue> FromConfigValue for HashMap<String, T> {
+    const TYPE_NAME: &'static str = "dict";
+
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Dict(entries) => entries
+                .iter()
+                .map(|(key, value)| Some((key.clone(), T::from_config_value(value)?)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for ConfigValue {
+    fn from(value: String) -> Self {
+        ConfigValue::String(value)
+    }
+}
+
+impl From<&str> for ConfigValue {
+    fn from(value: &str) -> Self {
+        ConfigValue::String(value.to_string())
+    }
+}
+
+impl From<bool> for ConfigValue {
+    fn from(value: bool) -> Self {
+        ConfigValue::Boolean(value)
+    }
+}
+
+impl From<f64> for ConfigValue {
+    fn from(value: f64) -> Self {
+        ConfigValue::Float(value)
+    }
+}
+
+impl From<f32> for ConfigValue {
+    fn from(value: f32) -> Self {
+        ConfigValue::Float(value as f64)
+    }
+}
+
+impl<T: Into<ConfigValue>> From<Vec<T>> for ConfigValue {
+    fn from(value: Vec<T>) -> Self {
+        ConfigValue::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Rgba> for ConfigValue {
+    fn from(value: Rgba) -> Self {
+        ConfigValue::Color(value)
+    }
+}
+
+impl<T: Into<ConfigValue>> From<HashMap<String, T>> for ConfigValue {
+    fn from(value: HashMap<String, T>) -> Self {
+        ConfigValue::Dict(value.into_iter().map(|(key, value)| (key, value.into())).collect())
+    }
+}
+
+impl ConfigValue {
+    /// Convert any serializable value. Structs and maps become
+    /// [`ConfigValue::Dict`], sequences [`ConfigValue::Array`]; `None`
+    /// fields are left out. `None` anywhere else, and integers past
+    /// `i64::MAX`, have no configuration value and are errors.
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> Result<Self, ConfigError> {
+        let json = serde_json::to_value(value).map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })?;
+        from_json(json)
+    }
+
+    /// Convert into any deserializable type, the inverse of
+    /// [`from_serialize`](Self::from_serialize)
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, ConfigError> {
+        serde_json::from_value(to_json(self)).map_err(|e| ConfigError::InvalidFormat { reason: e.to_string() })
+    }
+}
+
+fn from_json(value: serde_json::Value) -> Result<ConfigValue, ConfigError> {
+    use serde_json::Value;
+
+    let invalid = |reason: String| ConfigError::InvalidFormat { reason };
+    Ok(match value {
+        Value::Null => return Err(invalid("null cannot be stored as a configuration value".to_string())),
+        Value::Bool(b) => ConfigValue::Boolean(b),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => ConfigValue::Integer(i),
+            (None, Some(f)) if !n.is_u64() => ConfigValue::Float(f),
+            _ => return Err(invalid(format!("{} is out of range for a configuration integer", n))),
+        },
+        Value::String(s) => ConfigValue::String(s),
+        Value::Array(items) => ConfigValue::Array(items.into_iter().map(from_json).collect::<Result<_, _>>()?),
+        Value::Object(entries) => ConfigValue::Dict(
+            entries
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| Ok((key, from_json(value)?)))
+                .collect::<Result<_, ConfigError>>()?,
+        ),
+    })
+}
+
+fn to_json(value: &ConfigValue) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        ConfigValue::String(s) => Value::String(s.clone()),
+        ConfigValue::Integer(i) => Value::from(*i),
+        ConfigValue::Boolean(b) => Value::Bool(*b),
+        ConfigValue::Float(f) => Value::from(*f),
+        ConfigValue::Array(items) => Value::Array(items.iter().map(to_json).collect()),
+        ConfigValue::Color(color) => serde_json::to_value(color).unwrap_or(Value::Null),
+        ConfigValue::Dict(entries) => {
+            Value::Object(entries.iter().map(|(key, value)| (key.clone(), to_json(value))).collect())
+        }
+    }
+}
+
+/// A struct stored as a set of properties in one channel
+pub trait ConfigSection: Default {
+    /// Channel the section lives in
+    const CHANNEL: &'static str;
+
+    /// Update one field from a stored property. Returns `false` if the
+    /// property is unknown or has the wrong type.
+    fn apply_property(&mut self, property: &str, value: &ConfigValue) -> bool;
+
+    /// Every field as a property name and value
+    fn to_properties(&self) -> Vec<(&'static str, ConfigValue)>;
+}
+
+/// Declare a struct bound to a configuration channel.
+///
+/// Each field names the property it is stored under and its default value.
+///
+/// ```
+/// use xfce_rs_config::config_section;
+///
+/// config_section! {
+///     /// Panel appearance
+///     #[derive(Debug, Clone, PartialEq)]
+///     pub struct PanelAppearance in "xfce4-panel" {
+///         pub size: u32 = "/panels/panel-1/size" => 32,
+///         pub dark_mode: bool = "/panels/dark-mode" => false,
+///     }
+/// }
+///
+/// assert_eq!(PanelAppearance::default().size, 32);
+/// ```
+#[macro_export]
+macro_rules! config_section {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident in $channel:literal {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $ty:ty = $property:literal => $default:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl ::std::default::Default for $name {
+            fn default() -> Self {
+                Self {
+                    $( $field: $default, )*
+                }
+            }
+        }
+
+        impl $crate::ConfigSection for $name {
+            const CHANNEL: &'static str = $channel;
+
+            fn apply_property(&mut self, property: &str, value: &$crate::ConfigValue) -> bool {
+                match property {
+                    $(
+                        $property => match <$ty as $crate::FromConfigValue>::from_config_value(value) {
+                            Some(parsed) => {
+                                self.$field = parsed;
+                                true
+                            }
+                            None => false,
+                        },
+                    )*
+                    _ => false,
+                }
+            }
+
+            fn to_properties(&self) -> Vec<(&'static str, $crate::ConfigValue)> {
+                vec![
+                    $( ($property, $crate::ConfigValue::from(self.$field.clone())), )*
+                ]
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::config_section! {
+        #[derive(Debug, Clone, PartialEq)]
+        struct WmGeneral in "xfwm4" {
+            theme: String = "/general/theme" => "Default".to_string(),
+            workspace_count: u32 = "/general/workspace_count" => 4,
+            snap_width: f64 = "/general/snap_width" => 10.0,
+            button_layout: Vec<String> = "/general/button_layout" => Vec::<String>::new(),
+        }
+    }
+
+    #[test]
+    fn test_section_binding() {
+        let mut section = WmGeneral::default();
+        assert_eq!(section.theme, "Default");
+        assert_eq!(section.workspace_count, 4);
+
+        assert!(section.apply_property("/general/workspace_count", &ConfigValue::Integer(2)));
+        assert!(!section.apply_property("/general/workspace_count", &ConfigValue::Integer(-1)));
+        assert!(!section.apply_property("/general/theme", &ConfigValue::Boolean(true)));
+        assert!(!section.apply_property("/general/unknown", &ConfigValue::Boolean(true)));
+        assert_eq!(section.workspace_count, 2);
+
+        let properties = section.to_properties();
+        assert_eq!(properties.len(), 4);
+        assert!(properties.contains(&("/general/theme", ConfigValue::String("Default".to_string()))));
+    }
+
+    #[test]
+    fn test_serde_helpers() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Keybinding {
+            command: String,
+            keys: Vec<String>,
+            workspace: Option<u32>,
+            highlight: Rgba,
+        }
+
+        let bindings = vec![Keybinding {
+            command: "xfce4-terminal".to_string(),
+            keys: vec!["<Super>Return".to_string()],
+            workspace: None,
+            highlight: Rgba::from_rgba8(255, 0, 0, 255),
+        }];
+
+        let value = ConfigValue::from_serialize(&bindings).unwrap();
+        let ConfigValue::Array(items) = &value else {
+            panic!("expected an array, got {:?}", value);
+        };
+        let ConfigValue::Dict(entry) = &items[0] else {
+            panic!("expected a dict, got {:?}", items[0]);
+        };
+        assert!(!entry.contains_key("workspace"));
+        assert_eq!(value.deserialize::<Vec<Keybinding>>().unwrap(), bindings);
+
+        assert!(ConfigValue::from_serialize(&Option::<u32>::None).is_err());
+        assert!(ConfigValue::from_serialize(&vec![Some(1), None, Some(3)]).is_err());
+        assert!(ConfigValue::from_serialize(&u64::MAX).is_err());
+        assert_eq!(ConfigValue::from_serialize(&(i64::MAX as u64)).unwrap(), ConfigValue::Integer(i64::MAX));
+    }
+
+    #[test]
+    fn test_value_conversions() {
+        assert_eq!(u8::from_config_value(&ConfigValue::Integer(300)), None);
+        assert_eq!(f64::from_config_value(&ConfigValue::Integer(3)), Some(3.0));
+        assert_eq!(
+            Vec::<i32>::from_config_value(&ConfigValue::from(vec![1, 2, 3])),
+            Some(vec![1, 2, 3])
+        );
+
+        let xfconf_color = ConfigValue::from(vec![1.0, 0.5, 0.0, 1.0]);
+        assert_eq!(Rgba::from_config_value(&xfconf_color), Some(Rgba::new(1.0, 0.5, 0.0, 1.0)));
+        assert_eq!(
+            Rgba::from_config_value(&ConfigValue::from("#ff8000")).map(|c| c.to_rgba8()),
+            Some([255, 128, 0, 255])
+        );
+
+        let sizes = HashMap::from([("panel-1".to_string(), 32u32)]);
+        assert_eq!(HashMap::<String, u32>::from_config_value(&ConfigValue::from(sizes.clone())), Some(sizes));
+    }
+}
@@ -0,0 +1,358 @@
+//! Reading xfconf's `xfce-perchannel-xml` files
+//!
+//! The C xfconfd stores each channel as a tree of `<property>` elements:
+//!
+//! ```xml
+//! <channel name="xfwm4" version="1.0">
+//!   <property name="general" type="empty">
+//!     <property name="theme" type="string" value="Greybird"/>
+//!     <property name="button_layout" type="array">
+//!       <value type="string" value="O|HMC"/>
+//!     </property>
+//!   </property>
+//! </channel>
+//! ```
+//!
+//! Nested names are joined into xfconf property paths such as
+//! `/general/theme`. Only the small XML subset xfconfd writes is
+//! understood.
+
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::{ConfigError, ConfigValue};
+
+/// `~/.config/xfce4/xfconf/xfce-perchannel-xml`
+pub fn default_xfconf_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("xfce4")
+        .join("xfconf")
+        .join("xfce-perchannel-xml")
+}
+
+/// A channel read from an xfconf XML file
+#[derive(Debug, Clone, PartialEq)]
+pub struct XfconfChannel {
+    pub name: String,
+    /// Property paths and values in file order
+    pub properties: Vec<(String, ConfigValue)>,
+}
+
+/// Read one perchannel XML file
+pub fn read_channel_file(path: &Path) -> Result<XfconfChannel, ConfigError> {
+    let content = std::fs::read_to_string(path)?;
+    parse_channel(&content).map_err(|e| match e {
+        ConfigError::InvalidFormat { reason } => ConfigError::InvalidFormat {
+            reason: format!("{}: {}", path.display(), reason),
+        },
+        other => other,
+    })
+}
+
+/// The `.xml` files in an xfconf directory, sorted by name
+pub fn channel_files(dir: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "xml"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn invalid(reason: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidFormat { reason: reason.into() }
+}
+
+/// An open `<property>` element
+struct Frame {
+    path: String,
+    /// Collected `<value>` children of an array property
+    array: Option<Vec<ConfigValue>>,
+}
+
+/// Parse the text of a perchannel XML file
+pub fn parse_channel(content: &str) -> Result<XfconfChannel, ConfigError> {
+    let mut name = None;
+    let mut properties = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut channel_open = false;
+
+    for tag in Tags::new(content) {
+        let tag = tag?;
+        match (tag.name, tag.closing) {
+            ("channel", false) => {
+                name = Some(tag.attribute("name").ok_or_else(|| invalid("<channel> has no name"))?);
+                channel_open = !tag.self_closing;
+            }
+            ("channel", true) => channel_open = false,
+            ("property", false) if channel_open => {
+                let property = tag.attribute("name").ok_or_else(|| invalid("<property> has no name"))?;
+                let kind = tag.attribute("type").unwrap_or_default();
+                let parent = stack.last().map_or("", |frame| frame.path.as_str());
+                let path = format!("{}/{}", parent, property);
+
+                let mut frame = Frame { path, array: None };
+                if kind == "array" {
+                    frame.array = Some(Vec::new());
+                } else if let Some(value) = convert(&kind, tag.attribute("value"), &frame.path) {
+                    properties.push((frame.path.clone(), value));
+                }
+
+                if tag.self_closing {
+                    close(frame, &mut properties);
+                } else {
+                    stack.push(frame);
+                }
+            }
+            ("property", true) => {
+                let frame = stack.pop().ok_or_else(|| invalid("unbalanced </property>"))?;
+                close(frame, &mut properties);
+            }
+            ("value", false) => {
+                let Some(frame) = stack.last_mut() else {
+                    return Err(invalid("<value> outside of a property"));
+                };
+                let kind = tag.attribute("type").unwrap_or_default();
+                if let Some(array) = frame.array.as_mut() {
+                    if let Some(value) = convert(&kind, tag.attribute("value"), &frame.path) {
+                        array.push(value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err(invalid("unclosed <property>"));
+    }
+    Ok(XfconfChannel {
+        name: name.ok_or_else(|| invalid("no <channel> element"))?,
+        properties,
+    })
+}
+
+fn close(frame: Frame, properties: &mut Vec<(String, ConfigValue)>) {
+    if let Some(array) = frame.array {
+        properties.push((frame.path, ConfigValue::Array(array)));
+    }
+}
+
+/// Convert a typed xfconf value; `None` for containers and bad values
+fn convert(kind: &str, value: Option<String>, path: &str) -> Option<ConfigValue> {
+    if kind == "empty" {
+        return None;
+    }
+    let value = value.unwrap_or_default();
+
+    let converted = match kind {
+        "string" => return Some(ConfigValue::String(value)),
+        "bool" => match value.as_str() {
+            "true" => Some(ConfigValue::Boolean(true)),
+            "false" => Some(ConfigValue::Boolean(false)),
+            _ => None,
+        },
+        "int" | "uint" | "int64" | "uint64" | "int16" | "uint16" | "char" | "uchar" => {
+            value.trim().parse::<i64>().ok().map(ConfigValue::Integer)
+        }
+        "double" | "float" => value.trim().parse::<f64>().ok().map(ConfigValue::Float),
+        _ => None,
+    };
+
+    if converted.is_none() {
+        warn!("Skipping xfconf property {} with unsupported {} value \"{}\"", path, kind, value);
+    }
+    converted
+}
+
+/// One start, end or empty-element tag
+struct Tag<'a> {
+    name: &'a str,
+    attributes: &'a str,
+    closing: bool,
+    self_closing: bool,
+}
+
+impl Tag<'_> {
+    fn attribute(&self, wanted: &str) -> Option<String> {
+        let mut rest = self.attributes;
+        loop {
+            rest = rest.trim_start();
+            let (key, after) = rest.split_once('=')?;
+            let after = after.trim_start();
+            let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let (value, remaining) = after[1..].split_once(quote)?;
+            if key.trim() == wanted {
+                return Some(unescape(value));
+            }
+            rest = remaining;
+        }
+    }
+}
+
+/// Iterator over the tags of a document, skipping text, comments and
+/// processing instructions
+struct Tags<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tags<'a> {
+    fn new(content: &'a str) -> Self {
+        Self { rest: content }
+    }
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Result<Tag<'a>, ConfigError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.rest.find('<')?;
+            self.rest = &self.rest[start..];
+
+            let skip_to = if self.rest.starts_with("<!--") {
+                Some("-->")
+            } else if self.rest.starts_with("<?") {
+                Some("?>")
+            } else if self.rest.starts_with("<!") {
+                Some(">")
+            } else {
+                None
+            };
+            if let Some(terminator) = skip_to {
+                let Some(end) = self.rest.find(terminator) else {
+                    self.rest = "";
+                    return Some(Err(invalid("unterminated markup")));
+                };
+                self.rest = &self.rest[end + terminator.len()..];
+                continue;
+            }
+
+            let Some(end) = self.rest.find('>') else {
+                self.rest = "";
+                return Some(Err(invalid("unterminated tag")));
+            };
+            let mut inner = &self.rest[1..end];
+            self.rest = &self.rest[end + 1..];
+
+            let closing = inner.starts_with('/');
+            if closing {
+                inner = &inner[1..];
+            }
+            let self_closing = inner.ends_with('/');
+            if self_closing {
+                inner = &inner[..inner.len() - 1];
+            }
+
+            let name_end = inner.find(|c: char| c.is_whitespace()).unwrap_or(inner.len());
+            return Some(Ok(Tag {
+                name: &inner[..name_end],
+                attributes: &inner[name_end..],
+                closing,
+                self_closing,
+            }));
+        }
+    }
+}
+
+/// Resolve the predefined and numeric character entities
+fn unescape(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XFWM4: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+
+<channel name="xfwm4" version="1.0">
+  <!-- window manager -->
+  <property name="general" type="empty">
+    <property name="theme" type="string" value="Greybird &amp; co"/>
+    <property name="workspace_count" type="int" value="4"/>
+    <property name="box_move" type="bool" value="false"/>
+    <property name="frame_opacity" type="double" value="0.5"/>
+    <property name="workspace_names" type="array">
+      <value type="string" value="Main"/>
+      <value type="string" value="Web"/>
+    </property>
+  </property>
+  <property name="empty-array" type="array"/>
+  <property name="odd" type="uint" value="not a number"/>
+</channel>
+"#;
+
+    #[test]
+    fn test_parse_channel() {
+        let channel = parse_channel(XFWM4).unwrap();
+        assert_eq!(channel.name, "xfwm4");
+        assert_eq!(
+            channel.properties,
+            vec![
+                ("/general/theme".to_string(), ConfigValue::String("Greybird & co".to_string())),
+                ("/general/workspace_count".to_string(), ConfigValue::Integer(4)),
+                ("/general/box_move".to_string(), ConfigValue::Boolean(false)),
+                ("/general/frame_opacity".to_string(), ConfigValue::Float(0.5)),
+                (
+                    "/general/workspace_names".to_string(),
+                    ConfigValue::Array(vec![
+                        ConfigValue::String("Main".to_string()),
+                        ConfigValue::String("Web".to_string()),
+                    ])
+                ),
+                ("/empty-array".to_string(), ConfigValue::Array(Vec::new())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_documents() {
+        assert!(parse_channel("<channel name=\"x\"><property name=\"a\" type=\"empty\">").is_err());
+        assert!(parse_channel("<property name=\"a\"/>").is_err());
+        assert_eq!(unescape("&lt;Super&gt;&#x41;&#66;&bogus;"), "<Super>AB&bogus;");
+    }
+}
@@ -0,0 +1,298 @@
+//! `org.xfce.Xfconf` D-Bus service backed by [`XfceConfig`]
+//!
+//! Implements the subset of the xfconfd protocol used by `xfconf-query` and
+//! the xfce4-settings dialogs, so they read and write the Rust store.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tracing::{debug, info, warn};
+use zbus::object_server::SignalContext;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{interface, Connection, DBusError};
+
+use crate::{ConfigError, ConfigEventKind, ConfigValue, XfceConfig};
+
+/// Well-known bus name of the xfconf daemon
+pub const XFCONF_SERVICE: &str = "org.xfce.Xfconf";
+/// Object path the xfconf interface is served at
+pub const XFCONF_PATH: &str = "/org/xfce/Xfconf";
+
+/// Errors reported to D-Bus clients, named like upstream xfconfd's
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.xfce.Xfconf.Error")]
+pub enum XfconfError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    InvalidChannel(String),
+    InvalidProperty(String),
+    PropertyNotFound(String),
+    PermissionDenied(String),
+    InvalidValue(String),
+    InternalError(String),
+}
+
+impl From<ConfigError> for XfconfError {
+    fn from(err: ConfigError) -> Self {
+        match err {
+            ConfigError::PropertyNotFound { channel, property } => {
+                XfconfError::PropertyNotFound(format!("Property \"{}\" does not exist on channel \"{}\"", property, channel))
+            }
+            ConfigError::PropertyLocked { channel, property } => {
+                XfconfError::PermissionDenied(format!("Property \"{}\" on channel \"{}\" is locked", property, channel))
+            }
+            ConfigError::InvalidChannel { channel } => {
+                XfconfError::InvalidChannel(format!("Channel name \"{}\" is invalid", channel))
+            }
+            other => XfconfError::InternalError(other.to_string()),
+        }
+    }
+}
+
+/// The `org.xfce.Xfconf` interface object
+pub struct XfconfService {
+    config: Arc<XfceConfig>,
+}
+
+impl XfconfService {
+    pub fn new(config: Arc<XfceConfig>) -> Self {
+        Self { config }
+    }
+}
+
+#[interface(name = "org.xfce.Xfconf")]
+impl XfconfService {
+    async fn set_property(&self, channel: &str, property: &str, value: Value<'_>) -> Result<(), XfconfError> {
+        validate_names(channel, property)?;
+        let value = config_value_from_variant(&value)
+            .ok_or_else(|| XfconfError::InvalidValue(format!("Unsupported value type \"{}\"", value.value_signature())))?;
+
+        self.config.set_property(channel, property, value).await?;
+        Ok(())
+    }
+
+    async fn get_property(&self, channel: &str, property: &str) -> Result<OwnedValue, XfconfError> {
+        validate_names(channel, property)?;
+        let value = self.config.get_property(channel, property).await?;
+        to_owned_variant(&value)
+    }
+
+    async fn get_all_properties(
+        &self,
+        channel: &str,
+        property_base: &str,
+    ) -> Result<HashMap<String, OwnedValue>, XfconfError> {
+        let properties = match self.config.list_properties(channel).await {
+            Ok(properties) => properties,
+            Err(ConfigError::PropertyNotFound { .. }) => {
+                return Err(XfconfError::InvalidChannel(format!("Channel \"{}\" does not exist", channel)));
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let base = property_base.trim_end_matches('/');
+        let mut result = HashMap::new();
+        for property in properties {
+            if !base.is_empty() && property != base && !property.starts_with(&format!("{}/", base)) {
+                continue;
+            }
+            if let Ok(value) = self.config.get_property(channel, &property).await {
+                result.insert(property, to_owned_variant(&value)?);
+            }
+        }
+        Ok(result)
+    }
+
+    async fn property_exists(&self, channel: &str, property: &str) -> bool {
+        self.config.get_property(channel, property).await.is_ok()
+    }
+
+    async fn reset_property(&self, channel: &str, property: &str, recursive: bool) -> Result<(), XfconfError> {
+        validate_names(channel, property)?;
+
+        let mut targets = vec![property.to_string()];
+        if recursive {
+            let prefix = format!("{}/", property.trim_end_matches('/'));
+            if let Ok(properties) = self.config.list_properties(channel).await {
+                targets.extend(properties.into_iter().filter(|name| name.starts_with(&prefix)));
+            }
+        }
+
+        for target in targets {
+            self.config.reset_property(channel, &target).await?;
+        }
+        Ok(())
+    }
+
+    async fn list_channels(&self) -> Vec<String> {
+        let mut channels = self.config.list_channels().await;
+        channels.sort();
+        channels
+    }
+
+    async fn is_property_locked(&self, channel: &str, property: &str) -> bool {
+        self.config.is_locked(channel, property)
+    }
+
+    #[zbus(signal)]
+    async fn property_changed(
+        ctxt: &SignalContext<'_>,
+        channel: &str,
+        property: &str,
+        value: Value<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn property_removed(ctxt: &SignalContext<'_>, channel: &str, property: &str) -> zbus::Result<()>;
+}
+
+/// Claim `org.xfce.Xfconf` on the session bus and serve `config` until the
+/// returned connection is dropped. Must be called within a Tokio runtime.
+pub async fn serve(config: Arc<XfceConfig>) -> Result<Connection, ConfigError> {
+    let mut events = config.watch_all();
+
+    let connection = zbus::connection::Builder::session()?
+        .name(XFCONF_SERVICE)?
+        .serve_at(XFCONF_PATH, XfconfService::new(config))?
+        .build()
+        .await?;
+    info!("xfconf compatibility daemon running as {}", XFCONF_SERVICE);
+
+    // Forward store changes, whoever made them, as xfconf signals
+    let signal_connection = connection.clone();
+    tokio::spawn(async move {
+        let Ok(ctxt) = SignalContext::new(&signal_connection, XFCONF_PATH) else {
+            return;
+        };
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("xfconfd dropped {} change notifications", missed);
+                    continue;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+            };
+
+            let result = match (&event.kind, &event.new_value) {
+                (ConfigEventKind::Set, Some(value)) | (ConfigEventKind::Reset, Some(value)) => {
+                    XfconfService::property_changed(&ctxt, &event.channel, &event.property, to_variant(value)).await
+                }
+                _ => XfconfService::property_removed(&ctxt, &event.channel, &event.property).await,
+            };
+            if let Err(err) = result {
+                debug!("Failed to emit xfconf signal: {}", err);
+            }
+        }
+    });
+
+    Ok(connection)
+}
+
+fn validate_names(channel: &str, property: &str) -> Result<(), XfconfError> {
+    if channel.is_empty() || channel.contains('/') {
+        return Err(XfconfError::InvalidChannel(format!("Channel name \"{}\" is invalid", channel)));
+    }
+    if property.is_empty() || property.contains("//") {
+        return Err(XfconfError::InvalidProperty(format!("Property name \"{}\" is invalid", property)));
+    }
+    Ok(())
+}
+
+/// Convert a stored value into a D-Bus variant the way xfconf types it
+pub fn to_variant(value: &ConfigValue) -> Value<'static> {
+    match value {
+        ConfigValue::String(s) => Value::from(s.clone()),
+        ConfigValue::Integer(i) => match i32::try_from(*i) {
+            Ok(small) => Value::from(small),
+            Err(_) => Value::from(*i),
+        },
+        ConfigValue::Boolean(b) => Value::from(*b),
+        ConfigValue::Float(f) => Value::from(*f),
+        ConfigValue::Array(items) => {
+            let items: Vec<Value<'static>> = items.iter().map(|item| Value::Value(Box::new(to_variant(item)))).collect();
+            Value::from(items)
+        }
+        // xfconf stores colors as four doubles
+        ConfigValue::Color(color) => {
+            let channels = [color.red, color.green, color.blue, color.alpha];
+            Value::from(channels.iter().map(|c| Value::Value(Box::new(Value::from(*c)))).collect::<Vec<_>>())
+        }
+        ConfigValue::Dict(entries) => {
+            let entries: HashMap<String, Value<'static>> = entries
+                .iter()
+                .map(|(key, value)| (key.clone(), to_variant(value)))
+                .collect();
+            Value::from(entries)
+        }
+    }
+}
+
+fn to_owned_variant(value: &ConfigValue) -> Result<OwnedValue, XfconfError> {
+    OwnedValue::try_from(to_variant(value)).map_err(|err| XfconfError::InternalError(err.to_string()))
+}
+
+/// Convert a D-Bus variant from an xfconf client into a stored value
+pub fn config_value_from_variant(value: &Value<'_>) -> Option<ConfigValue> {
+    Some(match value {
+        Value::Value(inner) => return config_value_from_variant(inner),
+        Value::Str(s) => ConfigValue::String(s.to_string()),
+        Value::Bool(b) => ConfigValue::Boolean(*b),
+        Value::U8(n) => ConfigValue::Integer(i64::from(*n)),
+        Value::I16(n) => ConfigValue::Integer(i64::from(*n)),
+        Value::U16(n) => ConfigValue::Integer(i64::from(*n)),
+        Value::I32(n) => ConfigValue::Integer(i64::from(*n)),
+        Value::U32(n) => ConfigValue::Integer(i64::from(*n)),
+        Value::I64(n) => ConfigValue::Integer(*n),
+        Value::U64(n) => ConfigValue::Integer(i64::try_from(*n).ok()?),
+        Value::F64(f) => ConfigValue::Float(*f),
+        Value::Array(array) => ConfigValue::Array(
+            array
+                .iter()
+                .map(config_value_from_variant)
+                .collect::<Option<Vec<_>>>()?,
+        ),
+        Value::Dict(dict) => ConfigValue::Dict(
+            dict.iter()
+                .map(|(key, value)| match key {
+                    Value::Str(key) => Some((key.to_string(), config_value_from_variant(value)?)),
+                    _ => None,
+                })
+                .collect::<Option<HashMap<_, _>>>()?,
+        ),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_round_trip() {
+        let values = [
+            ConfigValue::String("Greybird".to_string()),
+            ConfigValue::Integer(24),
+            ConfigValue::Integer(1 << 40),
+            ConfigValue::Boolean(true),
+            ConfigValue::Float(0.5),
+            ConfigValue::Array(vec![ConfigValue::Integer(1), ConfigValue::String("two".to_string())]),
+            ConfigValue::Dict(HashMap::from([
+                ("size".to_string(), ConfigValue::Integer(32)),
+                ("plugins".to_string(), ConfigValue::Array(vec![ConfigValue::String("clock".to_string())])),
+            ])),
+        ];
+
+        for value in values {
+            assert_eq!(config_value_from_variant(&to_variant(&value)), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_name_validation() {
+        assert!(validate_names("xfwm4", "/general/theme").is_ok());
+        assert!(validate_names("", "/general/theme").is_err());
+        assert!(validate_names("xfwm4", "/general//theme").is_err());
+    }
+}
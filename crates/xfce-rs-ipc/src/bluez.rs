@@ -0,0 +1,232 @@
+//! D-Bus proxies for the BlueZ Bluetooth daemon
+//!
+//! Covers what the audio app needs for headsets and speakers: the paired
+//! devices and their audio profiles, connecting and disconnecting, the
+//! battery level headsets report and the codec of the active transport.
+
+use std::collections::HashMap;
+
+use zbus::fdo::ObjectManagerProxy;
+use zbus::names::OwnedInterfaceName;
+use zbus::proxy;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::Connection;
+
+use crate::mpris::{flag, text, unwrap_variant};
+use crate::IpcError;
+
+/// Well-known bus name of the BlueZ daemon
+pub const BLUEZ_SERVICE: &str = "org.bluez";
+
+pub const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+pub const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+pub const MEDIA_TRANSPORT_INTERFACE: &str = "org.bluez.MediaTransport1";
+
+/// Service class UUIDs of the audio profiles, short forms
+const AUDIO_PROFILES: &[&str] = &[
+    "0000110b", // A2DP sink
+    "0000110a", // A2DP source
+    "00001108", // HSP headset
+    "00001112", // HSP audio gateway
+    "0000111e", // HFP hands-free
+    "0000111f", // HFP audio gateway
+    "00001850", // LE Audio volume control
+    "0000184e", // LE Audio stream control
+];
+
+/// `org.bluez.Device1`
+#[proxy(interface = "org.bluez.Device1", default_service = "org.bluez")]
+pub trait BluezDevice {
+    fn connect(&self) -> zbus::Result<()>;
+
+    fn disconnect(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn address(&self) -> zbus::Result<String>;
+
+    /// User-set name, falling back to the remote name
+    #[zbus(property)]
+    fn alias(&self) -> zbus::Result<String>;
+
+    /// Freedesktop icon name such as "audio-headset"
+    #[zbus(property)]
+    fn icon(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn paired(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn bonded(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn connected(&self) -> zbus::Result<bool>;
+
+    #[zbus(property, name = "UUIDs")]
+    fn uuids(&self) -> zbus::Result<Vec<String>>;
+}
+
+/// `org.bluez.Battery1`, present while a device reports its charge
+#[proxy(interface = "org.bluez.Battery1", default_service = "org.bluez")]
+pub trait BluezBattery {
+    #[zbus(property)]
+    fn percentage(&self) -> zbus::Result<u8>;
+}
+
+/// A Bluetooth audio device for display
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BluetoothAudioDevice {
+    pub path: String,
+    /// `XX:XX:XX:XX:XX:XX`
+    pub address: String,
+    pub name: String,
+    pub icon: Option<String>,
+    /// Paired or bonded
+    pub paired: bool,
+    pub connected: bool,
+    pub battery: Option<u8>,
+    /// Codec of the audio transport while streaming, e.g. "SBC"
+    pub codec: Option<String>,
+}
+
+impl BluetoothAudioDevice {
+    /// The address as PulseAudio and PipeWire put it in sink names,
+    /// e.g. `bluez_output.XX_XX_XX_XX_XX_XX.1`
+    pub fn sink_name_part(&self) -> String {
+        self.address.replace(':', "_")
+    }
+}
+
+/// Whether any of `uuids` is an audio profile
+pub fn has_audio_profile<S: AsRef<str>>(uuids: &[S]) -> bool {
+    uuids.iter().any(|uuid| {
+        let uuid = uuid.as_ref().to_ascii_lowercase();
+        AUDIO_PROFILES.iter().any(|profile| uuid.starts_with(profile))
+    })
+}
+
+/// Name of an A2DP codec id as carried by `MediaTransport1.Codec`
+pub fn codec_name(codec: u8) -> &'static str {
+    match codec {
+        0x00 => "SBC",
+        0x01 => "MP3",
+        0x02 => "AAC",
+        0x04 => "ATRAC",
+        0x06 => "LC3",
+        // aptX, LDAC and the rest are vendor codecs, not told apart here
+        _ => "Vendor",
+    }
+}
+
+type Interfaces = HashMap<OwnedInterfaceName, HashMap<String, OwnedValue>>;
+
+fn property<'a>(interfaces: &'a Interfaces, interface: &str, name: &str) -> Option<&'a Value<'a>> {
+    interfaces
+        .iter()
+        .find(|(found, _)| found.as_str() == interface)
+        .and_then(|(_, properties)| properties.get(name))
+        .map(|value| unwrap_variant(value))
+}
+
+/// Paired audio devices, connected ones first, then by name
+pub async fn audio_devices(connection: &Connection) -> Result<Vec<BluetoothAudioDevice>, IpcError> {
+    let manager = ObjectManagerProxy::builder(connection)
+        .destination(BLUEZ_SERVICE)?
+        .path("/")?
+        .build()
+        .await?;
+    let objects = manager.get_managed_objects().await?;
+
+    let mut devices: Vec<BluetoothAudioDevice> = objects
+        .iter()
+        .filter_map(|(path, interfaces)| {
+            let uuids: Vec<String> = match property(interfaces, DEVICE_INTERFACE, "UUIDs")? {
+                Value::Array(array) => array.inner().iter().filter_map(text).collect(),
+                _ => Vec::new(),
+            };
+            let paired = property(interfaces, DEVICE_INTERFACE, "Paired").and_then(flag).unwrap_or(false)
+                || property(interfaces, DEVICE_INTERFACE, "Bonded").and_then(flag).unwrap_or(false);
+            if !paired || !has_audio_profile(&uuids) {
+                return None;
+            }
+
+            let address = property(interfaces, DEVICE_INTERFACE, "Address").and_then(text).unwrap_or_default();
+            let battery = match property(interfaces, BATTERY_INTERFACE, "Percentage") {
+                Some(Value::U8(percentage)) => Some(*percentage),
+                _ => None,
+            };
+            Some(BluetoothAudioDevice {
+                path: path.to_string(),
+                name: property(interfaces, DEVICE_INTERFACE, "Alias")
+                    .and_then(text)
+                    .unwrap_or_else(|| address.clone()),
+                address,
+                icon: property(interfaces, DEVICE_INTERFACE, "Icon").and_then(text),
+                paired,
+                connected: property(interfaces, DEVICE_INTERFACE, "Connected").and_then(flag).unwrap_or(false),
+                battery,
+                codec: transport_codec(&objects, path),
+            })
+        })
+        .collect();
+
+    devices.sort_by(|a, b| b.connected.cmp(&a.connected).then_with(|| a.name.cmp(&b.name)));
+    Ok(devices)
+}
+
+/// Codec of a transport below `device`, such as `<device>/sep1/fd0`
+fn transport_codec(objects: &HashMap<OwnedObjectPath, Interfaces>, device: &OwnedObjectPath) -> Option<String> {
+    let prefix = format!("{}/", device.as_str());
+    objects
+        .iter()
+        .filter(|(path, _)| path.as_str().starts_with(&prefix))
+        .find_map(|(_, interfaces)| match property(interfaces, MEDIA_TRANSPORT_INTERFACE, "Codec")? {
+            Value::U8(codec) => Some(codec_name(*codec).to_string()),
+            _ => None,
+        })
+}
+
+async fn device(connection: &Connection, path: &str) -> Result<BluezDeviceProxy<'static>, IpcError> {
+    Ok(BluezDeviceProxy::builder(connection).path(path.to_string())?.build().await?)
+}
+
+/// Connect the audio profiles of the device at `path`
+pub async fn connect(connection: &Connection, path: &str) -> Result<(), IpcError> {
+    Ok(device(connection, path).await?.connect().await?)
+}
+
+pub async fn disconnect(connection: &Connection, path: &str) -> Result<(), IpcError> {
+    Ok(device(connection, path).await?.disconnect().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_profiles() {
+        assert!(has_audio_profile(&["0000110B-0000-1000-8000-00805f9b34fb"]));
+        assert!(has_audio_profile(&["00001124-0000-1000-8000-00805f9b34fb", "0000111e-0000-1000-8000-00805f9b34fb"]));
+        // A keyboard: HID only
+        assert!(!has_audio_profile(&["00001124-0000-1000-8000-00805f9b34fb"]));
+        assert!(!has_audio_profile::<&str>(&[]));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(codec_name(0), "SBC");
+        assert_eq!(codec_name(2), "AAC");
+        assert_eq!(codec_name(0xff), "Vendor");
+
+        let device = BluetoothAudioDevice {
+            path: "/org/bluez/hci0/dev_00_1B_66_AA_BB_CC".to_string(),
+            address: "00:1B:66:AA:BB:CC".to_string(),
+            name: "Headphones".to_string(),
+            icon: None,
+            paired: true,
+            connected: false,
+            battery: None,
+            codec: None,
+        };
+        assert_eq!(device.sink_name_part(), "00_1B_66_AA_BB_CC");
+    }
+}
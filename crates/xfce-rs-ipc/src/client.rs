@@ -0,0 +1,193 @@
+//! Client side of the `org.xfce_rs.Service` D-Bus service
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::stream::{BoxStream, Stream, StreamExt};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+use zbus::{proxy, Connection};
+
+use crate::service::{SERVICE_NAME, SERVICE_PATH};
+use crate::{IpcError, IpcMessage};
+
+/// How long a call may take before it is abandoned
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `org.xfce_rs.Service`
+#[proxy(
+    interface = "org.xfce_rs.Service",
+    default_service = "org.xfce_rs.Service",
+    default_path = "/org/xfce_rs/Service"
+)]
+pub trait XfceService {
+    /// Deliver a JSON-encoded [`IpcMessage`]
+    fn send(&self, message: &str) -> zbus::Result<String>;
+
+    fn get_status(&self) -> zbus::Result<String>;
+
+    /// Returns the id the subscription's `Event` signals carry
+    fn subscribe(&self, patterns: &[&str], capacity: u32) -> zbus::Result<u64>;
+
+    fn unsubscribe(&self, id: u64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn event(&self, subscription: u64, topic: &str, message: &str, dropped: u64) -> zbus::Result<()>;
+}
+
+/// Messages of one subscription made with [`XfceIpcClient::subscribe`];
+/// unsubscribes when dropped
+pub struct EventSubscription {
+    id: u64,
+    proxy: XfceServiceProxy<'static>,
+    messages: BoxStream<'static, IpcMessage>,
+}
+
+impl std::fmt::Debug for EventSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSubscription").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+impl Stream for EventSubscription {
+    type Item = IpcMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<IpcMessage>> {
+        self.messages.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        // The service also forgets subscriptions when the connection closes
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let proxy = self.proxy.clone();
+            let id = self.id;
+            runtime.spawn(async move {
+                let _ = proxy.unsubscribe(id).await;
+            });
+        }
+    }
+}
+
+/// IPC client for communicating with service
+#[derive(Debug)]
+pub struct XfceIpcClient {
+    proxy: Mutex<Option<XfceServiceProxy<'static>>>,
+    timeout: Duration,
+}
+
+impl XfceIpcClient {
+    pub fn new() -> Self {
+        Self {
+            proxy: Mutex::new(None),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Give up on calls after `timeout` instead of [`DEFAULT_TIMEOUT`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Connect to the session bus. Calls connect on demand, so this only
+    /// surfaces connection problems early.
+    pub async fn connect(&mut self) -> Result<(), IpcError> {
+        self.proxy().await?;
+        info!("XFCE.rs IPC client connected to {}", SERVICE_NAME);
+        Ok(())
+    }
+
+    /// Send a message to IPC service
+    pub async fn send_message(&self, message: IpcMessage) -> Result<String, IpcError> {
+        let message = serde_json::to_string(&message)?;
+        let message = message.as_str();
+        self.call(|proxy| async move { proxy.send(message).await }).await
+    }
+
+    /// Get service status
+    pub async fn get_status(&self) -> Result<String, IpcError> {
+        self.call(|proxy| async move { proxy.get_status().await }).await
+    }
+
+    /// Messages whose topic matches one of `patterns`, such as
+    /// `config.*` or `window.focus`
+    pub async fn subscribe(&self, patterns: &[&str]) -> Result<EventSubscription, IpcError> {
+        let proxy = self.proxy().await?;
+        // Listen first so no event is missed between subscribing and
+        // receiving
+        let signals = proxy.receive_event().await?;
+        let id = tokio::time::timeout(self.timeout, proxy.subscribe(patterns, 0))
+            .await
+            .map_err(|_| IpcError::Timeout(self.timeout))??;
+
+        let messages = signals
+            .filter_map(move |signal| async move {
+                let args = signal.args().ok()?;
+                if args.subscription != id {
+                    return None;
+                }
+                if args.dropped > 0 {
+                    debug!("Missed {} IPC events on {}", args.dropped, args.topic);
+                }
+                serde_json::from_str(args.message).ok()
+            })
+            .boxed();
+        Ok(EventSubscription { id, proxy, messages })
+    }
+
+    /// Every message the service publishes
+    pub async fn events(&self) -> Result<EventSubscription, IpcError> {
+        self.subscribe(&["*"]).await
+    }
+
+    async fn proxy(&self) -> Result<XfceServiceProxy<'static>, IpcError> {
+        let mut proxy = self.proxy.lock().await;
+        if let Some(proxy) = proxy.as_ref() {
+            return Ok(proxy.clone());
+        }
+
+        let connection = tokio::time::timeout(self.timeout, Connection::session())
+            .await
+            .map_err(|_| IpcError::Timeout(self.timeout))??;
+        let connected = XfceServiceProxy::builder(&connection)
+            .destination(SERVICE_NAME)?
+            .path(SERVICE_PATH)?
+            .build()
+            .await?;
+        *proxy = Some(connected.clone());
+        Ok(connected)
+    }
+
+    /// Run a call with the timeout, reconnecting once if the bus
+    /// connection was lost since the last call
+    async fn call<T, F, Fut>(&self, call: F) -> Result<T, IpcError>
+    where
+        F: Fn(XfceServiceProxy<'static>) -> Fut,
+        Fut: Future<Output = zbus::Result<T>>,
+    {
+        let mut retried = false;
+        loop {
+            let proxy = self.proxy().await?;
+            match tokio::time::timeout(self.timeout, call(proxy)).await {
+                Err(_) => return Err(IpcError::Timeout(self.timeout)),
+                Ok(Ok(value)) => return Ok(value),
+                Ok(Err(zbus::Error::InputOutput(e))) if !retried => {
+                    debug!("IPC connection lost ({}), reconnecting", e);
+                    *self.proxy.lock().await = None;
+                    retried = true;
+                }
+                Ok(Err(e)) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Default for XfceIpcClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,296 @@
+//! Topic-based publish/subscribe for IPC messages
+//!
+//! Every [`IpcMessage`] has a dotted topic such as `config.xsettings` or
+//! `window.focus`. Subscribers name the topics they want with patterns
+//! where `*` stands for one segment, or for the rest of the topic when it
+//! comes last. Each subscriber has its own bounded queue: when it falls
+//! behind, its oldest messages are dropped instead of slowing down the
+//! publisher or other subscribers.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use tokio::sync::Notify;
+
+use crate::IpcMessage;
+
+/// Messages a subscriber may have waiting before the oldest is dropped
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+impl IpcMessage {
+    /// Topic the message is published under
+    pub fn topic(&self) -> String {
+        match self {
+            IpcMessage::ConfigChange { channel, .. } => format!("config.{}", channel),
+            IpcMessage::WindowEvent { event_type, .. } => format!("window.{}", event_type),
+            IpcMessage::DesktopNotification { .. } => "notification".to_string(),
+            IpcMessage::SessionEvent { event_type, .. } => format!("session.{}", event_type),
+        }
+    }
+}
+
+/// Whether `topic` matches `pattern`
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let mut patterns = pattern.split('.').peekable();
+    let mut segments = topic.split('.');
+    while let Some(expected) = patterns.next() {
+        let Some(segment) = segments.next() else {
+            return false;
+        };
+        if expected == "*" {
+            if patterns.peek().is_none() {
+                // A trailing wildcard takes the rest of the topic
+                return !segment.is_empty();
+            }
+        } else if expected != segment {
+            return false;
+        }
+    }
+    segments.next().is_none()
+}
+
+struct Queue {
+    patterns: Vec<String>,
+    capacity: usize,
+    messages: Mutex<VecDeque<IpcMessage>>,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+    notify: Notify,
+}
+
+impl Queue {
+    fn wants(&self, topic: &str) -> bool {
+        self.patterns.iter().any(|pattern| topic_matches(pattern, topic))
+    }
+
+    fn push(&self, message: IpcMessage) {
+        {
+            let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+            if messages.len() >= self.capacity {
+                messages.pop_front();
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            messages.push_back(message);
+        }
+        self.notify.notify_one();
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.notify.notify_one();
+    }
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    queues: HashMap<u64, Arc<Queue>>,
+}
+
+impl Drop for Subscribers {
+    fn drop(&mut self) {
+        for queue in self.queues.values() {
+            queue.close();
+        }
+    }
+}
+
+/// Delivers published messages to the subscribers whose patterns match;
+/// clones share subscribers
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn subscribers(&self) -> std::sync::MutexGuard<'_, Subscribers> {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Receive messages whose topic matches any of `patterns`, keeping at
+    /// most `capacity` of them waiting
+    pub fn subscribe<S: Into<String>>(&self, patterns: impl IntoIterator<Item = S>, capacity: usize) -> Subscription {
+        let queue = Arc::new(Queue {
+            patterns: patterns.into_iter().map(Into::into).collect(),
+            capacity: capacity.max(1),
+            messages: Mutex::new(VecDeque::new()),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            notify: Notify::new(),
+        });
+
+        let mut subscribers = self.subscribers();
+        subscribers.next_id += 1;
+        let id = subscribers.next_id;
+        subscribers.queues.insert(id, queue.clone());
+        Subscription {
+            id,
+            queue,
+            bus: Arc::downgrade(&self.subscribers),
+        }
+    }
+
+    /// Queue `message` for every interested subscriber, returning how many
+    /// there were
+    pub fn publish(&self, message: &IpcMessage) -> usize {
+        let topic = message.topic();
+        let interested: Vec<Arc<Queue>> = self
+            .subscribers()
+            .queues
+            .values()
+            .filter(|queue| queue.wants(&topic))
+            .cloned()
+            .collect();
+
+        for queue in &interested {
+            queue.push(message.clone());
+        }
+        interested.len()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers().queues.len()
+    }
+}
+
+/// Messages for one subscriber; unsubscribes when dropped
+pub struct Subscription {
+    id: u64,
+    queue: Arc<Queue>,
+    bus: Weak<Mutex<Subscribers>>,
+}
+
+impl std::fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .field("patterns", &self.queue.patterns)
+            .finish()
+    }
+}
+
+impl Subscription {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.queue.patterns
+    }
+
+    /// Wait for the next message; `None` once the bus is gone and the
+    /// queue is empty
+    pub async fn recv(&mut self) -> Option<IpcMessage> {
+        loop {
+            if let Some(message) = self.try_recv() {
+                return Some(message);
+            }
+            if self.queue.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.queue.notify.notified().await;
+        }
+    }
+
+    /// A message that is already waiting, without blocking
+    pub fn try_recv(&mut self) -> Option<IpcMessage> {
+        self.queue.messages.lock().unwrap_or_else(|e| e.into_inner()).pop_front()
+    }
+
+    /// How many messages were dropped for falling behind since the last
+    /// call
+    pub fn take_dropped(&self) -> u64 {
+        self.queue.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(bus) = self.bus.upgrade() {
+            bus.lock().unwrap_or_else(|e| e.into_inner()).queues.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_change(channel: &str, value: i64) -> IpcMessage {
+        IpcMessage::ConfigChange {
+            channel: channel.to_string(),
+            property: "/value".to_string(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("config.*", "config.xsettings"));
+        assert!(topic_matches("window.focus", "window.focus"));
+        assert!(topic_matches("*", "notification"));
+        assert!(topic_matches("*", "window.focus"));
+        assert!(topic_matches("*.focus", "window.focus"));
+        assert!(!topic_matches("*.focus", "window.focus.extra"));
+        assert!(!topic_matches("config.*", "config"));
+        assert!(!topic_matches("window.focus", "window.focus.extra"));
+        assert!(!topic_matches("window.focus", "window"));
+        assert!(!topic_matches("config.*", "window.focus"));
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_get_matching_messages() {
+        let bus = EventBus::new();
+        let mut config = bus.subscribe(["config.*"], DEFAULT_QUEUE_CAPACITY);
+        let mut focus = bus.subscribe(["window.focus"], DEFAULT_QUEUE_CAPACITY);
+
+        let focused = IpcMessage::WindowEvent {
+            window_id: "0x1a00003".to_string(),
+            event_type: "focus".to_string(),
+            data: serde_json::Value::Null,
+        };
+        assert_eq!(bus.publish(&config_change("xsettings", 1)), 1);
+        assert_eq!(bus.publish(&focused), 1);
+
+        assert_eq!(config.recv().await.map(|message| message.topic()).as_deref(), Some("config.xsettings"));
+        assert!(config.try_recv().is_none());
+        assert_eq!(focus.recv().await.map(|message| message.topic()).as_deref(), Some("window.focus"));
+
+        drop(focus);
+        assert_eq!(bus.subscriber_count(), 1);
+        drop(bus);
+        assert!(config.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_slow_subscriber_drops_oldest() {
+        let bus = EventBus::new();
+        let mut slow = bus.subscribe(["config.*"], 2);
+        for value in 0..5 {
+            bus.publish(&config_change("panel", value));
+        }
+
+        let values: Vec<serde_json::Value> = std::iter::from_fn(|| slow.try_recv())
+            .map(|message| match message {
+                IpcMessage::ConfigChange { value, .. } => value,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(values, [3, 4]);
+        assert_eq!(slow.take_dropped(), 3);
+        assert_eq!(slow.take_dropped(), 0);
+    }
+}
@@ -1,7 +1,35 @@
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tracing::{info, error};
+use std::time::Duration;
+
+pub mod bluez;
+pub mod client;
+pub mod events;
+pub mod login1;
+pub mod mpris;
+pub mod networkmanager;
+pub mod notifications;
+pub mod polkit;
+pub mod portal;
+pub mod screensaver;
+pub mod service;
+pub mod single_instance;
+pub mod sound;
+pub mod tray;
+pub mod udisks2;
+pub mod upower;
+
+pub use client::XfceIpcClient;
+pub use events::{EventBus, Subscription};
+pub use mpris::{MprisEvent, MprisManager, PlayerState};
+pub use notifications::{Notification, NotificationDaemon, NotificationRenderer};
+pub use polkit::{AuthenticationRequest, PolkitAgent};
+pub use portal::{PortalBackend, PortalRequest};
+pub use screensaver::{IdleSource, ScreenSaverService};
+pub use service::XfceIpcService;
+pub use single_instance::{claim_instance, ensure_single_instance, Activation, Instance};
+pub use tray::{TrayEvent, TrayItem, TrayModel};
 
 /// Error types for IPC operations
 #[derive(Error, Debug)]
@@ -14,96 +42,40 @@ pub enum IpcError {
     
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
-}
-
-/// IPC message types
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum IpcMessage {
-    ConfigChange { channel: String, property: String, value: serde_json::Value },
-    WindowEvent { window_id: String, event_type: String, data: serde_json::Value },
-    DesktopNotification { title: String, body: String, urgency: String },
-    SessionEvent { event_type: String, data: HashMap<String, serde_json::Value> },
-}
-
-/// Main IPC service for XFCE.rs
-pub struct XfceIpcService {
-}
-
-impl std::fmt::Debug for XfceIpcService {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("XfceIpcService")
-            .finish()
-    }
-}
-
-type MessageHandler = Box<dyn Fn(IpcMessage) -> Result<(), IpcError> + Send + Sync>;
-
-impl XfceIpcService {
-    pub fn new() -> Self {
-        Self {
-        }
-    }
-    
-    /// Add a message handler
-    pub async fn add_handler(&self, _handler: MessageHandler) {
-        // Placeholder implementation
-    }
     
-    /// Start IPC service (placeholder)
-    pub async fn start(&self) -> Result<(), IpcError> {
-        info!("XFCE.rs IPC service started (placeholder implementation)");
-        
-        // Keep service alive with a simple loop
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
-    }
-}
-
-/// IPC client for communicating with service
-#[derive(Debug)]
-pub struct XfceIpcClient {
-    connection: Option<String>, // Placeholder for connection state
+    #[error("Timed out after {0:?}")]
+    Timeout(Duration),
 }
 
-impl XfceIpcClient {
-    pub fn new() -> Self {
-        Self {
-            connection: None,
+impl From<zbus::Error> for IpcError {
+    fn from(err: zbus::Error) -> Self {
+        match err {
+            zbus::Error::MethodError(name, description, _) => {
+                IpcError::MethodCallFailed(format!("{}: {}", name, description.unwrap_or_default()))
+            }
+            other => IpcError::ConnectionFailed(other.to_string()),
         }
     }
-    
-    /// Connect to IPC service (placeholder)
-    pub async fn connect(&mut self) -> Result<(), IpcError> {
-        self.connection = Some("connected".to_string());
-        info!("XFCE.rs IPC client connected (placeholder)");
-        Ok(())
-    }
-    
-    /// Send a message to IPC service
-    pub async fn send_message(&self, message: IpcMessage) -> Result<String, IpcError> {
-        info!("Sending IPC message: {:?}", message);
-        Ok("Message sent successfully".to_string())
-    }
-    
-    /// Get service status
-    pub async fn get_status(&self) -> Result<String, IpcError> {
-        Ok("XFCE.rs IPC Service running".to_string())
-    }
 }
 
-impl Default for XfceIpcService {
-    fn default() -> Self {
-        Self::new()
+impl From<zbus::fdo::Error> for IpcError {
+    fn from(err: zbus::fdo::Error) -> Self {
+        zbus::Error::from(err).into()
     }
 }
 
-impl Default for XfceIpcClient {
-    fn default() -> Self {
-        Self::new()
-    }
+/// IPC message types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IpcMessage {
+    ConfigChange { channel: String, property: String, value: serde_json::Value },
+    WindowEvent { window_id: String, event_type: String, data: serde_json::Value },
+    DesktopNotification { title: String, body: String, urgency: String },
+    SessionEvent { event_type: String, data: HashMap<String, serde_json::Value> },
 }
 
+/// Callback invoked for every message the service receives
+pub type MessageHandler = Box<dyn Fn(IpcMessage) -> Result<(), IpcError> + Send + Sync>;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,214 @@
+//! D-Bus proxies for systemd-logind
+//!
+//! Power actions (suspend, hibernate, shutdown), inhibitor locks and the
+//! `PrepareForSleep` signal used to lock the screen before suspending.
+
+use zbus::proxy;
+use zbus::zvariant::{OwnedFd, OwnedObjectPath};
+
+/// Well-known bus name of logind
+pub const LOGIN1_SERVICE: &str = "org.freedesktop.login1";
+/// Object path of the logind manager object
+pub const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+
+/// `(what, who, why, mode, uid, pid)` as returned by `ListInhibitors`
+pub type InhibitorInfo = (String, String, String, String, u32, u32);
+
+/// `org.freedesktop.login1.Manager`
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+pub trait Login1Manager {
+    /// `interactive` allows a polkit authentication dialog
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn hybrid_sleep(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn suspend_then_hibernate(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+
+    /// See [`CanResult`]
+    fn can_suspend(&self) -> zbus::Result<String>;
+
+    fn can_hibernate(&self) -> zbus::Result<String>;
+
+    fn can_hybrid_sleep(&self) -> zbus::Result<String>;
+
+    fn can_suspend_then_hibernate(&self) -> zbus::Result<String>;
+
+    fn can_power_off(&self) -> zbus::Result<String>;
+
+    fn can_reboot(&self) -> zbus::Result<String>;
+
+    /// Take an inhibitor lock, held until the returned descriptor is
+    /// closed. Prefer [`inhibit`].
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    fn list_inhibitors(&self) -> zbus::Result<Vec<InhibitorInfo>>;
+
+    fn get_session(&self, session_id: &str) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(name = "GetSessionByPID")]
+    fn get_session_by_pid(&self, pid: u32) -> zbus::Result<OwnedObjectPath>;
+
+    /// Sent with `true` before suspending and `false` after resuming
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn prepare_for_shutdown(&self, start: bool) -> zbus::Result<()>;
+
+    /// Colon-separated list of block inhibitors in effect
+    #[zbus(property)]
+    fn block_inhibited(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn delay_inhibited(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn handle_lid_switch(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn idle_hint(&self) -> zbus::Result<bool>;
+}
+
+/// `org.freedesktop.login1.Session`
+#[proxy(interface = "org.freedesktop.login1.Session", default_service = "org.freedesktop.login1")]
+pub trait Login1Session {
+    fn activate(&self) -> zbus::Result<()>;
+
+    fn lock(&self) -> zbus::Result<()>;
+
+    fn unlock(&self) -> zbus::Result<()>;
+
+    fn terminate(&self) -> zbus::Result<()>;
+
+    fn set_idle_hint(&self, idle: bool) -> zbus::Result<()>;
+
+    fn set_locked_hint(&self, locked: bool) -> zbus::Result<()>;
+
+    /// Asks the session's screen locker to lock
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn unlock(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+}
+
+/// Answer of the `Can*` methods
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanResult {
+    Yes,
+    No,
+    /// Allowed after authentication
+    Challenge,
+    /// Not supported by the hardware or configuration
+    NotAvailable,
+}
+
+impl CanResult {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "yes" => CanResult::Yes,
+            "challenge" => CanResult::Challenge,
+            "na" => CanResult::NotAvailable,
+            _ => CanResult::No,
+        }
+    }
+
+    /// Whether offering the action makes sense
+    pub fn is_possible(self) -> bool {
+        matches!(self, CanResult::Yes | CanResult::Challenge)
+    }
+}
+
+/// Operations an inhibitor lock can hold off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InhibitWhat {
+    Shutdown,
+    Sleep,
+    Idle,
+    HandlePowerKey,
+    HandleSuspendKey,
+    HandleHibernateKey,
+    HandleLidSwitch,
+}
+
+impl InhibitWhat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InhibitWhat::Shutdown => "shutdown",
+            InhibitWhat::Sleep => "sleep",
+            InhibitWhat::Idle => "idle",
+            InhibitWhat::HandlePowerKey => "handle-power-key",
+            InhibitWhat::HandleSuspendKey => "handle-suspend-key",
+            InhibitWhat::HandleHibernateKey => "handle-hibernate-key",
+            InhibitWhat::HandleLidSwitch => "handle-lid-switch",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InhibitMode {
+    /// Prevent the operation while held
+    Block,
+    /// Postpone the operation briefly, e.g. to lock the screen first
+    Delay,
+}
+
+impl InhibitMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InhibitMode::Block => "block",
+            InhibitMode::Delay => "delay",
+        }
+    }
+}
+
+/// A held inhibitor lock, released when dropped
+#[derive(Debug)]
+pub struct InhibitorLock {
+    _fd: OwnedFd,
+}
+
+/// Take an inhibitor lock for `what`
+pub async fn inhibit(
+    manager: &Login1ManagerProxy<'_>,
+    what: &[InhibitWhat],
+    who: &str,
+    why: &str,
+    mode: InhibitMode,
+) -> zbus::Result<InhibitorLock> {
+    let what: Vec<&str> = what.iter().map(|what| what.as_str()).collect();
+    let fd = manager.inhibit(&what.join(":"), who, why, mode.as_str()).await?;
+    Ok(InhibitorLock { _fd: fd })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_can_result() {
+        assert_eq!(CanResult::from_name("yes"), CanResult::Yes);
+        assert!(CanResult::from_name("challenge").is_possible());
+        assert!(!CanResult::from_name("na").is_possible());
+        assert_eq!(CanResult::from_name("bogus"), CanResult::No);
+    }
+}
@@ -0,0 +1,780 @@
+//! Tracking and controlling MPRIS media players
+//!
+//! [`MprisManager`] follows every `org.mpris.MediaPlayer2.*` name on the
+//! session bus, keeps each player's status and metadata current from its
+//! `PropertiesChanged` signals, and picks an active player for widgets that
+//! only show one (panel media plugin, lock screen, volume popup).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::debug;
+use zbus::fdo::{DBusProxy, PropertiesProxy};
+use zbus::names::InterfaceName;
+use zbus::proxy;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::Connection;
+
+use crate::IpcError;
+
+/// Bus name prefix every MPRIS player owns a name under
+pub const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+/// Object path MPRIS players are served at
+pub const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+pub const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// `org.mpris.MediaPlayer2`
+#[proxy(interface = "org.mpris.MediaPlayer2", default_path = "/org/mpris/MediaPlayer2")]
+pub trait MediaPlayer2 {
+    fn raise(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn identity(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> zbus::Result<String>;
+}
+
+/// `org.mpris.MediaPlayer2.Player`
+#[proxy(interface = "org.mpris.MediaPlayer2.Player", default_path = "/org/mpris/MediaPlayer2")]
+pub trait MediaPlayer2Player {
+    fn next(&self) -> zbus::Result<()>;
+
+    fn previous(&self) -> zbus::Result<()>;
+
+    fn pause(&self) -> zbus::Result<()>;
+
+    fn play_pause(&self) -> zbus::Result<()>;
+
+    fn stop(&self) -> zbus::Result<()>;
+
+    fn play(&self) -> zbus::Result<()>;
+
+    /// Move by `offset` microseconds
+    fn seek(&self, offset: i64) -> zbus::Result<()>;
+
+    /// Jump to `position` microseconds into `track_id`
+    fn set_position(&self, track_id: &ObjectPath<'_>, position: i64) -> zbus::Result<()>;
+
+    /// Position in microseconds; never announced through signals
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> zbus::Result<i64>;
+
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn seeked(&self, position: i64) -> zbus::Result<()>;
+}
+
+/// Playback state of a player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+impl PlaybackStatus {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "Playing" => PlaybackStatus::Playing,
+            "Paused" => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        }
+    }
+}
+
+/// The commonly used `xesam:` and `mpris:` metadata fields
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Metadata {
+    pub track_id: Option<String>,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub art_url: Option<String>,
+    pub url: Option<String>,
+    pub length: Option<Duration>,
+}
+
+impl Metadata {
+    fn from_value(value: &Value<'_>) -> Self {
+        let mut metadata = Metadata::default();
+        let Value::Dict(dict) = unwrap_variant(value) else {
+            return metadata;
+        };
+
+        for (key, value) in dict.iter() {
+            let Value::Str(key) = key else {
+                continue;
+            };
+            let value = unwrap_variant(value);
+            match key.as_str() {
+                "mpris:trackid" => metadata.track_id = text(value),
+                "mpris:length" => metadata.length = micros(value),
+                "mpris:artUrl" => metadata.art_url = text(value),
+                "xesam:title" => metadata.title = text(value),
+                "xesam:album" => metadata.album = text(value),
+                "xesam:url" => metadata.url = text(value),
+                // Some players send a single string instead of a list
+                "xesam:artist" => match value {
+                    Value::Array(array) => metadata.artists = array.inner().iter().filter_map(text).collect(),
+                    other => metadata.artists = text(other).into_iter().collect(),
+                },
+                _ => {}
+            }
+        }
+        metadata
+    }
+}
+
+pub(crate) fn unwrap_variant<'a>(value: &'a Value<'a>) -> &'a Value<'a> {
+    match value {
+        Value::Value(inner) => unwrap_variant(inner),
+        other => other,
+    }
+}
+
+pub(crate) fn text(value: &Value<'_>) -> Option<String> {
+    match unwrap_variant(value) {
+        Value::Str(s) => Some(s.to_string()),
+        Value::ObjectPath(path) => Some(path.to_string()),
+        _ => None,
+    }
+}
+
+/// Microsecond counts arrive as any integer type depending on the player
+fn micros(value: &Value<'_>) -> Option<Duration> {
+    let micros = match unwrap_variant(value) {
+        Value::I64(v) => u64::try_from(*v).ok()?,
+        Value::U64(v) => *v,
+        Value::I32(v) => u64::try_from(*v).ok()?,
+        Value::U32(v) => u64::from(*v),
+        Value::F64(v) if *v >= 0.0 => *v as u64,
+        _ => return None,
+    };
+    Some(Duration::from_micros(micros))
+}
+
+pub(crate) fn flag(value: &Value<'_>) -> Option<bool> {
+    match unwrap_variant(value) {
+        Value::Bool(b) => Some(*b),
+        _ => None,
+    }
+}
+
+/// What is known about one player
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerState {
+    /// Full bus name, e.g. `org.mpris.MediaPlayer2.vlc`
+    pub bus_name: String,
+    /// Human readable name, e.g. "VLC media player"
+    pub identity: String,
+    pub desktop_entry: Option<String>,
+    pub status: PlaybackStatus,
+    pub metadata: Metadata,
+    pub volume: Option<f64>,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    pub can_play: bool,
+    pub can_pause: bool,
+    pub can_seek: bool,
+    /// Playback speed, 1.0 being normal
+    pub rate: f64,
+    position: Duration,
+    position_at: Instant,
+}
+
+impl PlayerState {
+    fn new(bus_name: &str) -> Self {
+        Self {
+            bus_name: bus_name.to_string(),
+            identity: bus_name.strip_prefix(MPRIS_PREFIX).unwrap_or(bus_name).to_string(),
+            desktop_entry: None,
+            status: PlaybackStatus::Stopped,
+            metadata: Metadata::default(),
+            volume: None,
+            can_go_next: false,
+            can_go_previous: false,
+            can_play: false,
+            can_pause: false,
+            can_seek: false,
+            rate: 1.0,
+            position: Duration::ZERO,
+            position_at: Instant::now(),
+        }
+    }
+
+    /// Estimated playback position, advanced at the playback rate by the
+    /// time since the player last reported it
+    pub fn position(&self) -> Duration {
+        let position = match self.status {
+            PlaybackStatus::Playing => self.position + self.position_at.elapsed().mul_f64(self.rate),
+            _ => self.position,
+        };
+        match self.metadata.length {
+            Some(length) => position.min(length),
+            None => position,
+        }
+    }
+
+    fn set_position(&mut self, position: Duration) {
+        self.position = position;
+        self.position_at = Instant::now();
+    }
+
+    fn apply<'a>(&mut self, properties: impl IntoIterator<Item = (&'a str, &'a Value<'a>)>) {
+        for (name, value) in properties {
+            match name {
+                "PlaybackStatus" => {
+                    if let Some(status) = text(value) {
+                        // Freeze or restart the position estimate
+                        let position = self.position();
+                        self.status = PlaybackStatus::from_name(&status);
+                        self.set_position(position);
+                    }
+                }
+                "Rate" => {
+                    if let Value::F64(rate) = unwrap_variant(value) {
+                        let position = self.position();
+                        self.rate = if rate.is_finite() { rate.max(0.0) } else { 1.0 };
+                        self.set_position(position);
+                    }
+                }
+                "Metadata" => self.metadata = Metadata::from_value(value),
+                "Position" => {
+                    if let Some(position) = micros(value) {
+                        self.set_position(position);
+                    }
+                }
+                "Volume" => {
+                    if let Value::F64(volume) = unwrap_variant(value) {
+                        self.volume = Some(*volume);
+                    }
+                }
+                "CanGoNext" => self.can_go_next = flag(value).unwrap_or(self.can_go_next),
+                "CanGoPrevious" => self.can_go_previous = flag(value).unwrap_or(self.can_go_previous),
+                "CanPlay" => self.can_play = flag(value).unwrap_or(self.can_play),
+                "CanPause" => self.can_pause = flag(value).unwrap_or(self.can_pause),
+                "CanSeek" => self.can_seek = flag(value).unwrap_or(self.can_seek),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Changes reported by [`MprisManager::subscribe`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MprisEvent {
+    PlayerAdded(String),
+    PlayerRemoved(String),
+    /// Status, metadata or capabilities of a player changed
+    PlayerChanged(String),
+    /// A player jumped to a new position
+    Seeked { player: String, position: Duration },
+    ActivePlayerChanged(Option<String>),
+}
+
+struct Tracked {
+    state: PlayerState,
+    /// When the player last started playing, for picking the active one
+    played_at: Option<Instant>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+struct Selection {
+    /// Name patterns in order of preference
+    priority: Vec<String>,
+    /// Player chosen explicitly by the user
+    pinned: Option<String>,
+    active: Option<String>,
+}
+
+struct Inner {
+    connection: Connection,
+    players: Mutex<HashMap<String, Tracked>>,
+    selection: Mutex<Selection>,
+    events: broadcast::Sender<MprisEvent>,
+    watcher: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(watcher) = self.watcher.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            watcher.abort();
+        }
+    }
+}
+
+/// Follows all MPRIS players on the session bus; clones share state
+#[derive(Clone)]
+pub struct MprisManager {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for MprisManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MprisManager")
+            .field("players", &self.players().len())
+            .field("active", &self.selection().active)
+            .finish()
+    }
+}
+
+impl MprisManager {
+    /// Connect to the session bus and start following players. Must be
+    /// called within a Tokio runtime.
+    pub async fn connect() -> Result<Self, IpcError> {
+        Self::with_connection(Connection::session().await?).await
+    }
+
+    /// Follow players on an existing connection
+    pub async fn with_connection(connection: Connection) -> Result<Self, IpcError> {
+        let (events, _) = broadcast::channel(64);
+        let manager = Self {
+            inner: Arc::new(Inner {
+                connection,
+                players: Mutex::new(HashMap::new()),
+                selection: Mutex::new(Selection::default()),
+                events,
+                watcher: Mutex::new(None),
+            }),
+        };
+
+        let dbus = DBusProxy::new(&manager.inner.connection).await?;
+        let mut owner_changes = dbus.receive_name_owner_changed().await?;
+
+        let weak = Arc::downgrade(&manager.inner);
+        let watcher = tokio::spawn(async move {
+            while let Some(signal) = owner_changes.next().await {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                let name = args.name().to_string();
+                if !name.starts_with(MPRIS_PREFIX) {
+                    continue;
+                }
+                let Some(inner) = weak.upgrade() else {
+                    return;
+                };
+
+                let manager = MprisManager { inner };
+                manager.remove_player(&name);
+                if args.new_owner().is_some() {
+                    manager.add_player(&name).await;
+                }
+            }
+        });
+        *manager.inner.watcher.lock().unwrap_or_else(|e| e.into_inner()) = Some(watcher);
+
+        for name in dbus.list_names().await? {
+            if name.starts_with(MPRIS_PREFIX) {
+                manager.add_player(&name).await;
+            }
+        }
+        Ok(manager)
+    }
+
+    fn players_lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Tracked>> {
+        self.inner.players.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn selection(&self) -> std::sync::MutexGuard<'_, Selection> {
+        self.inner.selection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Receive player changes as they happen
+    pub fn subscribe(&self) -> broadcast::Receiver<MprisEvent> {
+        self.inner.events.subscribe()
+    }
+
+    fn send(&self, event: MprisEvent) {
+        // Nobody listening is fine
+        let _ = self.inner.events.send(event);
+    }
+
+    /// All known players, sorted by bus name
+    pub fn players(&self) -> Vec<PlayerState> {
+        let mut players: Vec<PlayerState> = self.players_lock().values().map(|tracked| tracked.state.clone()).collect();
+        players.sort_by(|a, b| a.bus_name.cmp(&b.bus_name));
+        players
+    }
+
+    pub fn player(&self, bus_name: &str) -> Option<PlayerState> {
+        self.players_lock().get(bus_name).map(|tracked| tracked.state.clone())
+    }
+
+    /// The player widgets showing a single player should use
+    pub fn active(&self) -> Option<PlayerState> {
+        let active = self.selection().active.clone()?;
+        self.player(&active)
+    }
+
+    /// Prefer players whose bus name or identity contains one of
+    /// `patterns`, earlier patterns first
+    pub fn set_priority(&self, patterns: Vec<String>) {
+        self.selection().priority = patterns;
+        self.update_active();
+    }
+
+    /// Make `bus_name` the active player regardless of what else plays,
+    /// or go back to automatic selection with `None`
+    pub fn pin(&self, bus_name: Option<&str>) {
+        self.selection().pinned = bus_name.map(str::to_string);
+        self.update_active();
+    }
+
+    async fn add_player(&self, bus_name: &str) {
+        let state = match self.fetch_state(bus_name).await {
+            Ok(state) => state,
+            Err(e) => {
+                debug!("Ignoring MPRIS player {}: {}", bus_name, e);
+                return;
+            }
+        };
+
+        let task = match self.follow(bus_name).await {
+            Ok(task) => task,
+            Err(e) => {
+                debug!("Cannot follow MPRIS player {}: {}", bus_name, e);
+                return;
+            }
+        };
+
+        let played_at = (state.status == PlaybackStatus::Playing).then(Instant::now);
+        self.players_lock().insert(
+            bus_name.to_string(),
+            Tracked {
+                state,
+                played_at,
+                task,
+            },
+        );
+        self.send(MprisEvent::PlayerAdded(bus_name.to_string()));
+        self.update_active();
+    }
+
+    fn remove_player(&self, bus_name: &str) {
+        if self.players_lock().remove(bus_name).is_some() {
+            self.send(MprisEvent::PlayerRemoved(bus_name.to_string()));
+            self.update_active();
+        }
+    }
+
+    async fn fetch_state(&self, bus_name: &str) -> Result<PlayerState, IpcError> {
+        let mut state = PlayerState::new(bus_name);
+
+        let root = MediaPlayer2Proxy::builder(&self.inner.connection)
+            .destination(bus_name.to_string())?
+            .build()
+            .await?;
+        if let Ok(identity) = root.identity().await {
+            state.identity = identity;
+        }
+        state.desktop_entry = root.desktop_entry().await.ok();
+
+        let properties = self.properties_proxy(bus_name).await?;
+        let values = properties
+            .get_all(Some(InterfaceName::from_static_str_unchecked(PLAYER_INTERFACE)).into())
+            .await?;
+        state.apply(values.iter().map(|(name, value)| (name.as_str(), &**value)));
+        Ok(state)
+    }
+
+    async fn properties_proxy(&self, bus_name: &str) -> Result<PropertiesProxy<'static>, IpcError> {
+        Ok(PropertiesProxy::builder(&self.inner.connection)
+            .destination(bus_name.to_string())?
+            .path(MPRIS_PATH)?
+            .build()
+            .await?)
+    }
+
+    /// Apply a player's property changes and seeks until it goes away
+    async fn follow(&self, bus_name: &str) -> Result<JoinHandle<()>, IpcError> {
+        let properties = self.properties_proxy(bus_name).await?;
+        let mut changes = properties.receive_properties_changed().await?;
+        let mut seeks = self.player_proxy(bus_name).await?.receive_seeked().await?;
+
+        let weak = Arc::downgrade(&self.inner);
+        let bus_name = bus_name.to_string();
+        Ok(tokio::spawn(async move {
+            loop {
+                let (changed, seeked) = tokio::select! {
+                    Some(signal) = changes.next() => {
+                        let Ok(args) = signal.args() else {
+                            continue;
+                        };
+                        if args.interface_name().as_str() != PLAYER_INTERFACE {
+                            continue;
+                        }
+                        let changed: Vec<(String, OwnedValue)> = args
+                            .changed_properties()
+                            .iter()
+                            .filter_map(|(name, value)| Some((name.to_string(), value.try_to_owned().ok()?)))
+                            .collect();
+                        (changed, None)
+                    }
+                    Some(signal) = seeks.next() => {
+                        let Ok(args) = signal.args() else {
+                            continue;
+                        };
+                        (Vec::new(), Some(Duration::from_micros(u64::try_from(args.position).unwrap_or(0))))
+                    }
+                    else => return,
+                };
+                let Some(inner) = weak.upgrade() else {
+                    return;
+                };
+                let manager = MprisManager { inner };
+
+                {
+                    let mut players = manager.players_lock();
+                    let Some(tracked) = players.get_mut(&bus_name) else {
+                        return;
+                    };
+                    let was_playing = tracked.state.status == PlaybackStatus::Playing;
+                    tracked.state.apply(changed.iter().map(|(name, value)| (name.as_str(), &**value)));
+                    if let Some(position) = seeked {
+                        tracked.state.set_position(position);
+                    }
+                    if !was_playing && tracked.state.status == PlaybackStatus::Playing {
+                        tracked.played_at = Some(Instant::now());
+                    }
+                }
+
+                match seeked {
+                    Some(position) => manager.send(MprisEvent::Seeked {
+                        player: bus_name.clone(),
+                        position,
+                    }),
+                    None => manager.send(MprisEvent::PlayerChanged(bus_name.clone())),
+                }
+                manager.update_active();
+            }
+        }))
+    }
+
+    /// Recompute the active player and announce it if it changed
+    fn update_active(&self) {
+        let candidates: Vec<Candidate> = self
+            .players_lock()
+            .values()
+            .map(|tracked| Candidate {
+                bus_name: tracked.state.bus_name.clone(),
+                identity: tracked.state.identity.clone(),
+                status: tracked.state.status,
+                played_at: tracked.played_at,
+            })
+            .collect();
+
+        let changed = {
+            let mut selection = self.selection();
+            let active = select_active(&candidates, &selection.priority, selection.pinned.as_deref());
+            if active == selection.active {
+                None
+            } else {
+                selection.active = active.clone();
+                Some(active)
+            }
+        };
+        if let Some(active) = changed {
+            self.send(MprisEvent::ActivePlayerChanged(active));
+        }
+    }
+
+    async fn player_proxy(&self, bus_name: &str) -> Result<MediaPlayer2PlayerProxy<'static>, IpcError> {
+        if !self.players_lock().contains_key(bus_name) && !bus_name.starts_with(MPRIS_PREFIX) {
+            return Err(IpcError::MethodCallFailed(format!("{} is not an MPRIS player", bus_name)));
+        }
+        Ok(MediaPlayer2PlayerProxy::builder(&self.inner.connection)
+            .destination(bus_name.to_string())?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?)
+    }
+
+    pub async fn play_pause(&self, bus_name: &str) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.play_pause().await?)
+    }
+
+    pub async fn play(&self, bus_name: &str) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.play().await?)
+    }
+
+    pub async fn pause(&self, bus_name: &str) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.pause().await?)
+    }
+
+    pub async fn stop(&self, bus_name: &str) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.stop().await?)
+    }
+
+    pub async fn next(&self, bus_name: &str) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.next().await?)
+    }
+
+    pub async fn previous(&self, bus_name: &str) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.previous().await?)
+    }
+
+    /// Ask the player to bring its window to the front
+    pub async fn raise(&self, bus_name: &str) -> Result<(), IpcError> {
+        let root = MediaPlayer2Proxy::builder(&self.inner.connection)
+            .destination(bus_name.to_string())?
+            .build()
+            .await?;
+        Ok(root.raise().await?)
+    }
+
+    /// Move forward, or backward for a negative offset
+    pub async fn seek(&self, bus_name: &str, offset_micros: i64) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.seek(offset_micros).await?)
+    }
+
+    /// Jump to `position` in the current track
+    pub async fn set_position(&self, bus_name: &str, position: Duration) -> Result<(), IpcError> {
+        let track_id = self
+            .player(bus_name)
+            .and_then(|state| state.metadata.track_id)
+            .ok_or_else(|| IpcError::MethodCallFailed(format!("{} has no current track", bus_name)))?;
+        let track_id = ObjectPath::try_from(track_id.as_str()).map_err(|e| IpcError::MethodCallFailed(e.to_string()))?;
+        let micros = i64::try_from(position.as_micros()).unwrap_or(i64::MAX);
+        Ok(self.player_proxy(bus_name).await?.set_position(&track_id, micros).await?)
+    }
+
+    /// Ask the player for its exact position, since players do not
+    /// announce it while playing
+    pub async fn refresh_position(&self, bus_name: &str) -> Result<Duration, IpcError> {
+        let micros = self.player_proxy(bus_name).await?.position().await?;
+        let position = Duration::from_micros(u64::try_from(micros).unwrap_or(0));
+        if let Some(tracked) = self.players_lock().get_mut(bus_name) {
+            tracked.state.set_position(position);
+        }
+        Ok(position)
+    }
+
+    pub async fn set_volume(&self, bus_name: &str, volume: f64) -> Result<(), IpcError> {
+        Ok(self.player_proxy(bus_name).await?.set_volume(volume.clamp(0.0, 1.0)).await?)
+    }
+}
+
+/// What [`select_active`] needs to know about a player
+struct Candidate {
+    bus_name: String,
+    identity: String,
+    status: PlaybackStatus,
+    played_at: Option<Instant>,
+}
+
+/// Pick the active player: a pinned player wins, then playing over paused
+/// over stopped, then the earliest matching priority pattern, then the
+/// player that most recently started playing
+fn select_active(candidates: &[Candidate], priority: &[String], pinned: Option<&str>) -> Option<String> {
+    if let Some(pinned) = pinned {
+        if candidates.iter().any(|candidate| candidate.bus_name == pinned) {
+            return Some(pinned.to_string());
+        }
+    }
+
+    let rank = |candidate: &Candidate| {
+        let name = candidate.bus_name.to_lowercase();
+        let identity = candidate.identity.to_lowercase();
+        priority
+            .iter()
+            .position(|pattern| {
+                let pattern = pattern.to_lowercase();
+                name.contains(&pattern) || identity.contains(&pattern)
+            })
+            .unwrap_or(priority.len())
+    };
+    let status_rank = |status: PlaybackStatus| match status {
+        PlaybackStatus::Playing => 0,
+        PlaybackStatus::Paused => 1,
+        PlaybackStatus::Stopped => 2,
+    };
+
+    candidates
+        .iter()
+        .min_by(|a, b| {
+            status_rank(a.status)
+                .cmp(&status_rank(b.status))
+                .then_with(|| rank(a).cmp(&rank(b)))
+                .then_with(|| b.played_at.cmp(&a.played_at))
+                .then_with(|| a.bus_name.cmp(&b.bus_name))
+        })
+        .map(|candidate| candidate.bus_name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::{Dict, Type};
+
+    fn candidate(name: &str, status: PlaybackStatus, played_secs_ago: Option<u64>) -> Candidate {
+        Candidate {
+            bus_name: format!("{}{}", MPRIS_PREFIX, name),
+            identity: name.to_string(),
+            status,
+            played_at: played_secs_ago.map(|secs| Instant::now() - Duration::from_secs(secs)),
+        }
+    }
+
+    #[test]
+    fn test_select_active() {
+        let players = [
+            candidate("vlc", PlaybackStatus::Paused, Some(60)),
+            candidate("firefox", PlaybackStatus::Playing, Some(30)),
+            candidate("spotify", PlaybackStatus::Playing, Some(5)),
+        ];
+        let name = |name: &str| Some(format!("{}{}", MPRIS_PREFIX, name));
+
+        assert_eq!(select_active(&players, &[], None), name("spotify"));
+        assert_eq!(select_active(&players, &["Firefox".to_string()], None), name("firefox"));
+        assert_eq!(select_active(&players, &["vlc".to_string()], None), name("spotify"));
+        assert_eq!(select_active(&players, &[], name("vlc").as_deref()), name("vlc"));
+        assert_eq!(select_active(&players, &[], Some("org.mpris.MediaPlayer2.gone")), name("spotify"));
+        assert_eq!(select_active(&[], &[], None), None);
+    }
+
+    #[test]
+    fn test_apply_properties() {
+        let mut metadata = Dict::new(<&str>::signature(), Value::signature());
+        metadata.add("mpris:trackid", Value::from(ObjectPath::try_from("/org/mpris/track/7").unwrap())).unwrap();
+        metadata.add("mpris:length", Value::U64(215_000_000)).unwrap();
+        metadata.add("xesam:title", Value::from("Windowlicker")).unwrap();
+        metadata.add("xesam:artist", Value::from(vec!["Aphex Twin"])).unwrap();
+        let metadata = Value::Dict(metadata);
+        let status = Value::from("Paused");
+        let position = Value::I64(61_000_000);
+        let rate = Value::F64(1.5);
+
+        let mut state = PlayerState::new("org.mpris.MediaPlayer2.vlc");
+        state.apply([
+            ("Metadata", &metadata),
+            ("PlaybackStatus", &status),
+            ("Position", &position),
+            ("Rate", &rate),
+        ]);
+
+        assert_eq!(state.identity, "vlc");
+        assert_eq!(state.status, PlaybackStatus::Paused);
+        assert_eq!(state.metadata.track_id.as_deref(), Some("/org/mpris/track/7"));
+        assert_eq!(state.metadata.title.as_deref(), Some("Windowlicker"));
+        assert_eq!(state.metadata.artists, ["Aphex Twin"]);
+        assert_eq!(state.metadata.length, Some(Duration::from_secs(215)));
+        assert_eq!(state.position(), Duration::from_secs(61));
+        assert_eq!(state.rate, 1.5);
+    }
+}
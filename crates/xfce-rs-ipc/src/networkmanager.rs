@@ -0,0 +1,599 @@
+//! D-Bus proxies for NetworkManager
+//!
+//! Covers what the network plugin and network settings need: devices and
+//! their state, Wi-Fi access points and scanning, saved connections, and
+//! activating or deactivating them.
+
+use std::collections::HashMap;
+
+use zbus::proxy;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+/// Well-known bus name of NetworkManager
+pub const NM_SERVICE: &str = "org.freedesktop.NetworkManager";
+/// Object path of the NetworkManager manager object
+pub const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+/// Object path of the saved connections
+pub const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+
+/// Connection settings, keyed by setting name (`connection`,
+/// `802-11-wireless`, `ipv4`, ...) and then by property
+pub type ConnectionSettings = HashMap<String, HashMap<String, OwnedValue>>;
+
+/// Connection settings to send to NetworkManager
+pub type NewConnectionSettings<'a> = HashMap<&'a str, HashMap<&'a str, Value<'a>>>;
+
+/// `org.freedesktop.NetworkManager`
+#[proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+pub trait NetworkManager {
+    /// Realized devices, excluding placeholders for software devices
+    fn get_devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    fn get_all_devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Activate a saved connection. Pass `/` as `device` to let
+    /// NetworkManager pick one, and as `specific_object` unless choosing
+    /// an access point. Returns the active connection.
+    fn activate_connection(
+        &self,
+        connection: &ObjectPath<'_>,
+        device: &ObjectPath<'_>,
+        specific_object: &ObjectPath<'_>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    /// Save and activate a new connection, completing missing settings
+    /// from the device and access point. Returns the saved connection and
+    /// the active connection.
+    fn add_and_activate_connection(
+        &self,
+        connection: NewConnectionSettings<'_>,
+        device: &ObjectPath<'_>,
+        specific_object: &ObjectPath<'_>,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+
+    fn deactivate_connection(&self, active_connection: &ObjectPath<'_>) -> zbus::Result<()>;
+
+    /// See [`Connectivity`]
+    fn check_connectivity(&self) -> zbus::Result<u32>;
+
+    /// See [`NmState`]
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<u32>;
+
+    /// See [`Connectivity`]
+    #[zbus(property)]
+    fn connectivity(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn networking_enabled(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn wireless_enabled(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_wireless_enabled(&self, enabled: bool) -> zbus::Result<()>;
+
+    /// Whether the Wi-Fi hardware switch allows Wi-Fi
+    #[zbus(property)]
+    fn wireless_hardware_enabled(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn active_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Active connection providing the default route, `/` if none
+    #[zbus(property)]
+    fn primary_connection(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property)]
+    fn version(&self) -> zbus::Result<String>;
+
+    #[zbus(signal)]
+    fn device_added(&self, device: OwnedObjectPath) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn device_removed(&self, device: OwnedObjectPath) -> zbus::Result<()>;
+}
+
+/// `org.freedesktop.NetworkManager.Device`
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Device",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+pub trait NmDevice {
+    /// Deactivate the device and keep it from autoconnecting
+    fn disconnect(&self) -> zbus::Result<()>;
+
+    /// Kernel interface name, such as `wlan0`
+    #[zbus(property)]
+    fn interface(&self) -> zbus::Result<String>;
+
+    /// See [`DeviceType`]
+    #[zbus(property)]
+    fn device_type(&self) -> zbus::Result<u32>;
+
+    /// See [`DeviceState`]
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<u32>;
+
+    /// `/` when the device is not activated
+    #[zbus(property)]
+    fn active_connection(&self) -> zbus::Result<OwnedObjectPath>;
+
+    /// Saved connections that could be activated on this device
+    #[zbus(property)]
+    fn available_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    #[zbus(property)]
+    fn managed(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn autoconnect(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn driver(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn hw_address(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn ip4_config(&self) -> zbus::Result<OwnedObjectPath>;
+
+    /// Sent with the new state, the old state and a reason code, unlike
+    /// changes of the `State` property
+    #[zbus(signal, name = "StateChanged")]
+    fn device_state_changed(&self, new_state: u32, old_state: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// `org.freedesktop.NetworkManager.Device.Wireless`
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Device.Wireless",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+pub trait NmWireless {
+    /// Access points, including those with hidden SSIDs
+    fn get_all_access_points(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Start a scan. `LastScan` changes once it completes. Pass
+    /// `{"ssids": [..]}` in `options` to probe for hidden networks.
+    fn request_scan(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn access_points(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// `/` when not associated
+    #[zbus(property)]
+    fn active_access_point(&self) -> zbus::Result<OwnedObjectPath>;
+
+    /// `CLOCK_BOOTTIME` milliseconds of the last completed scan, -1 if
+    /// none has completed
+    #[zbus(property)]
+    fn last_scan(&self) -> zbus::Result<i64>;
+
+    /// Kbit/s
+    #[zbus(property)]
+    fn bitrate(&self) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn access_point_added(&self, access_point: OwnedObjectPath) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn access_point_removed(&self, access_point: OwnedObjectPath) -> zbus::Result<()>;
+}
+
+/// `org.freedesktop.NetworkManager.AccessPoint`
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.AccessPoint",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+pub trait NmAccessPoint {
+    /// Raw SSID bytes, not necessarily UTF-8; see [`ssid_to_string`]
+    #[zbus(property)]
+    fn ssid(&self) -> zbus::Result<Vec<u8>>;
+
+    /// Percent
+    #[zbus(property)]
+    fn strength(&self) -> zbus::Result<u8>;
+
+    /// MHz
+    #[zbus(property)]
+    fn frequency(&self) -> zbus::Result<u32>;
+
+    /// BSSID
+    #[zbus(property)]
+    fn hw_address(&self) -> zbus::Result<String>;
+
+    /// 1 when the network requires WEP or another privacy mechanism
+    #[zbus(property)]
+    fn flags(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn wpa_flags(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn rsn_flags(&self) -> zbus::Result<u32>;
+}
+
+/// `org.freedesktop.NetworkManager.Connection.Active`
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Connection.Active",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+pub trait NmActiveConnection {
+    /// Name of the connection, such as the Wi-Fi network
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn uuid(&self) -> zbus::Result<String>;
+
+    /// Setting name of the connection type, such as `802-11-wireless`
+    #[zbus(property, name = "Type")]
+    fn kind(&self) -> zbus::Result<String>;
+
+    /// See [`ActiveConnectionState`]
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// The saved connection that was activated
+    #[zbus(property)]
+    fn connection(&self) -> zbus::Result<OwnedObjectPath>;
+
+    /// Access point in use for Wi-Fi connections
+    #[zbus(property)]
+    fn specific_object(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property)]
+    fn vpn(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn default(&self) -> zbus::Result<bool>;
+}
+
+/// `org.freedesktop.NetworkManager.Settings`
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Settings",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager/Settings"
+)]
+pub trait NmSettings {
+    fn list_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    fn get_connection_by_uuid(&self, uuid: &str) -> zbus::Result<OwnedObjectPath>;
+
+    /// Save a connection without activating it
+    fn add_connection(&self, connection: NewConnectionSettings<'_>) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(signal)]
+    fn new_connection(&self, connection: OwnedObjectPath) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn connection_removed(&self, connection: OwnedObjectPath) -> zbus::Result<()>;
+}
+
+/// `org.freedesktop.NetworkManager.Settings.Connection`
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Settings.Connection",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+pub trait NmConnection {
+    /// Settings without secrets
+    fn get_settings(&self) -> zbus::Result<ConnectionSettings>;
+
+    fn update(&self, settings: NewConnectionSettings<'_>) -> zbus::Result<()>;
+
+    fn delete(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn updated(&self) -> zbus::Result<()>;
+}
+
+/// Value of the manager `State` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NmState {
+    Unknown,
+    Asleep,
+    Disconnected,
+    Disconnecting,
+    Connecting,
+    /// Connected without a default route
+    ConnectedLocal,
+    /// Connected with a default route that does not reach the internet
+    ConnectedSite,
+    ConnectedGlobal,
+}
+
+impl NmState {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            10 => NmState::Asleep,
+            20 => NmState::Disconnected,
+            30 => NmState::Disconnecting,
+            40 => NmState::Connecting,
+            50 => NmState::ConnectedLocal,
+            60 => NmState::ConnectedSite,
+            70 => NmState::ConnectedGlobal,
+            _ => NmState::Unknown,
+        }
+    }
+
+    pub fn is_connected(self) -> bool {
+        self >= NmState::ConnectedLocal
+    }
+}
+
+/// Value of the manager `Connectivity` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Unknown,
+    None,
+    /// Behind a captive portal
+    Portal,
+    Limited,
+    Full,
+}
+
+impl Connectivity {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Connectivity::None,
+            2 => Connectivity::Portal,
+            3 => Connectivity::Limited,
+            4 => Connectivity::Full,
+            _ => Connectivity::Unknown,
+        }
+    }
+}
+
+/// Value of the device `DeviceType` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceType {
+    Unknown,
+    Ethernet,
+    Wifi,
+    Bluetooth,
+    Modem,
+    Bridge,
+    Loopback,
+    WireGuard,
+    /// Bonds, VLANs, tunnels and the other virtual device kinds
+    Other(u32),
+}
+
+impl DeviceType {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            0 => DeviceType::Unknown,
+            1 => DeviceType::Ethernet,
+            2 => DeviceType::Wifi,
+            5 => DeviceType::Bluetooth,
+            8 => DeviceType::Modem,
+            13 => DeviceType::Bridge,
+            29 => DeviceType::WireGuard,
+            32 => DeviceType::Loopback,
+            other => DeviceType::Other(other),
+        }
+    }
+}
+
+/// Value of the device `State` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceState {
+    Unknown,
+    Unmanaged,
+    Unavailable,
+    Disconnected,
+    Prepare,
+    Config,
+    /// Waiting for secrets, such as a Wi-Fi password
+    NeedAuth,
+    IpConfig,
+    IpCheck,
+    Secondaries,
+    Activated,
+    Deactivating,
+    Failed,
+}
+
+impl DeviceState {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            10 => DeviceState::Unmanaged,
+            20 => DeviceState::Unavailable,
+            30 => DeviceState::Disconnected,
+            40 => DeviceState::Prepare,
+            50 => DeviceState::Config,
+            60 => DeviceState::NeedAuth,
+            70 => DeviceState::IpConfig,
+            80 => DeviceState::IpCheck,
+            90 => DeviceState::Secondaries,
+            100 => DeviceState::Activated,
+            110 => DeviceState::Deactivating,
+            120 => DeviceState::Failed,
+            _ => DeviceState::Unknown,
+        }
+    }
+
+    /// Between starting activation and being activated
+    pub fn is_activating(self) -> bool {
+        self >= DeviceState::Prepare && self < DeviceState::Activated
+    }
+}
+
+/// Value of the active connection `State` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveConnectionState {
+    Unknown,
+    Activating,
+    Activated,
+    Deactivating,
+    Deactivated,
+}
+
+impl ActiveConnectionState {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => ActiveConnectionState::Activating,
+            2 => ActiveConnectionState::Activated,
+            3 => ActiveConnectionState::Deactivating,
+            4 => ActiveConnectionState::Deactivated,
+            _ => ActiveConnectionState::Unknown,
+        }
+    }
+}
+
+/// Security an access point advertises
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiSecurity {
+    None,
+    Wep,
+    /// WPA or WPA2 with a pre-shared key
+    WpaPsk,
+    /// WPA3 personal
+    Sae,
+    /// WPA or WPA2 enterprise
+    Enterprise,
+}
+
+// Key management bits of the WpaFlags and RsnFlags properties
+const KEY_MGMT_PSK: u32 = 0x100;
+const KEY_MGMT_802_1X: u32 = 0x200;
+const KEY_MGMT_SAE: u32 = 0x400;
+
+impl WifiSecurity {
+    pub fn from_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> Self {
+        let key_mgmt = wpa_flags | rsn_flags;
+        if key_mgmt & KEY_MGMT_802_1X != 0 {
+            WifiSecurity::Enterprise
+        } else if key_mgmt & KEY_MGMT_SAE != 0 {
+            WifiSecurity::Sae
+        } else if key_mgmt & KEY_MGMT_PSK != 0 {
+            WifiSecurity::WpaPsk
+        } else if flags & 1 != 0 {
+            WifiSecurity::Wep
+        } else {
+            WifiSecurity::None
+        }
+    }
+}
+
+/// Snapshot of an access point for a network list
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessPointInfo {
+    pub path: OwnedObjectPath,
+    /// Empty for hidden networks
+    pub ssid: String,
+    pub strength: u8,
+    pub frequency: u32,
+    pub bssid: String,
+    pub security: WifiSecurity,
+}
+
+impl AccessPointInfo {
+    pub async fn fetch(access_point: &NmAccessPointProxy<'_>) -> zbus::Result<Self> {
+        Ok(Self {
+            path: access_point.inner().path().to_owned().into(),
+            ssid: ssid_to_string(&access_point.ssid().await?),
+            strength: access_point.strength().await?,
+            frequency: access_point.frequency().await?,
+            bssid: access_point.hw_address().await?,
+            security: WifiSecurity::from_flags(
+                access_point.flags().await?,
+                access_point.wpa_flags().await?,
+                access_point.rsn_flags().await?,
+            ),
+        })
+    }
+}
+
+/// Access points of a Wi-Fi device, strongest first, keeping only the
+/// strongest access point of each network
+pub async fn list_networks(wireless: &NmWirelessProxy<'_>) -> zbus::Result<Vec<AccessPointInfo>> {
+    let mut networks: Vec<AccessPointInfo> = Vec::new();
+    for path in wireless.get_all_access_points().await? {
+        let access_point = NmAccessPointProxy::builder(wireless.inner().connection())
+            .path(path)?
+            .build()
+            .await?;
+        // Access points can vanish between listing and reading them
+        let Ok(info) = AccessPointInfo::fetch(&access_point).await else {
+            continue;
+        };
+        networks.push(info);
+    }
+    Ok(strongest_per_network(networks))
+}
+
+fn strongest_per_network(mut access_points: Vec<AccessPointInfo>) -> Vec<AccessPointInfo> {
+    access_points.sort_by_key(|info| std::cmp::Reverse(info.strength));
+    let mut seen = std::collections::HashSet::new();
+    access_points.retain(|info| info.ssid.is_empty() || seen.insert(info.ssid.clone()));
+    access_points
+}
+
+/// Display form of a raw SSID
+pub fn ssid_to_string(ssid: &[u8]) -> String {
+    String::from_utf8_lossy(ssid).into_owned()
+}
+
+/// Whether an object path property is NetworkManager's "none" value
+pub fn is_unset(path: &ObjectPath<'_>) -> bool {
+    path.as_str() == "/"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access_point(path: &str, ssid: &str, strength: u8) -> AccessPointInfo {
+        AccessPointInfo {
+            path: OwnedObjectPath::try_from(path).unwrap(),
+            ssid: ssid.to_string(),
+            strength,
+            frequency: 2412,
+            bssid: String::new(),
+            security: WifiSecurity::WpaPsk,
+        }
+    }
+
+    #[test]
+    fn test_enum_values() {
+        assert_eq!(NmState::from_u32(70), NmState::ConnectedGlobal);
+        assert!(NmState::from_u32(50).is_connected());
+        assert!(!NmState::from_u32(40).is_connected());
+        assert_eq!(DeviceType::from_u32(2), DeviceType::Wifi);
+        assert_eq!(DeviceType::from_u32(10), DeviceType::Other(10));
+        assert!(DeviceState::from_u32(60).is_activating());
+        assert!(!DeviceState::from_u32(100).is_activating());
+        assert_eq!(ActiveConnectionState::from_u32(2), ActiveConnectionState::Activated);
+        assert_eq!(Connectivity::from_u32(2), Connectivity::Portal);
+    }
+
+    #[test]
+    fn test_wifi_security() {
+        assert_eq!(WifiSecurity::from_flags(0, 0, 0), WifiSecurity::None);
+        assert_eq!(WifiSecurity::from_flags(1, 0, 0), WifiSecurity::Wep);
+        assert_eq!(WifiSecurity::from_flags(1, 0, 0x188), WifiSecurity::WpaPsk);
+        assert_eq!(WifiSecurity::from_flags(1, 0, 0x588), WifiSecurity::Sae);
+        assert_eq!(WifiSecurity::from_flags(1, 0x288, 0x288), WifiSecurity::Enterprise);
+    }
+
+    #[test]
+    fn test_strongest_per_network() {
+        let networks = strongest_per_network(vec![
+            access_point("/ap/1", "home", 40),
+            access_point("/ap/2", "", 30),
+            access_point("/ap/3", "home", 80),
+            access_point("/ap/4", "", 20),
+            access_point("/ap/5", "café", 60),
+        ]);
+        let paths: Vec<&str> = networks.iter().map(|info| info.path.as_str()).collect();
+        assert_eq!(paths, ["/ap/3", "/ap/5", "/ap/2", "/ap/4"]);
+    }
+}
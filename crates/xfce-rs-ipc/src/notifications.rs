@@ -0,0 +1,569 @@
+//! `org.freedesktop.Notifications` server
+//!
+//! Implements the Desktop Notifications specification 1.2 so applications
+//! using libnotify, `notify-send` or the portal can show popups. Drawing is
+//! left to a [`NotificationRenderer`]; the daemon keeps track of ids,
+//! replacement, expiry and the `NotificationClosed`/`ActionInvoked`
+//! signals.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::{debug, info};
+use zbus::object_server::SignalContext;
+use zbus::zvariant::Value;
+use zbus::{interface, Connection};
+
+use crate::{IpcError, IpcMessage, MessageHandler};
+
+/// Well-known bus name of the notification daemon
+pub const NOTIFICATIONS_SERVICE: &str = "org.freedesktop.Notifications";
+/// Object path the notification interface is served at
+pub const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+/// Version of the specification implemented
+pub const SPEC_VERSION: &str = "1.2";
+
+/// How long notifications stay up when the sender leaves it to the server
+pub const DEFAULT_EXPIRY: Duration = Duration::from_secs(5);
+
+/// Urgency hint of a notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => Urgency::Low,
+            2 => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+
+    /// Parse "low", "normal" or "critical", as used by `notify-send`
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "low" => Urgency::Low,
+            "critical" => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+}
+
+/// When a notification goes away by itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// The server decides, see [`DEFAULT_EXPIRY`]
+    Default,
+    Never,
+    After(Duration),
+}
+
+impl Expiry {
+    fn from_timeout(milliseconds: i32) -> Self {
+        match milliseconds {
+            0 => Expiry::Never,
+            ms if ms < 0 => Expiry::Default,
+            ms => Expiry::After(Duration::from_millis(ms as u64)),
+        }
+    }
+}
+
+/// Reason sent with `NotificationClosed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    Expired = 1,
+    Dismissed = 2,
+    Closed = 3,
+    Undefined = 4,
+}
+
+/// A notification as requested by an application
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub id: u32,
+    pub app_name: String,
+    pub app_icon: String,
+    pub summary: String,
+    pub body: String,
+    /// `(key, label)` pairs in the order given
+    pub actions: Vec<(String, String)>,
+    pub urgency: Urgency,
+    pub category: Option<String>,
+    pub desktop_entry: Option<String>,
+    pub image_path: Option<String>,
+    /// Should not be kept in a history
+    pub transient: bool,
+    /// Stays after an action is invoked
+    pub resident: bool,
+    /// Theme event sound to play, e.g. "message-new-instant"
+    pub sound_name: Option<String>,
+    pub suppress_sound: bool,
+    /// Level in percent to draw as a bar, e.g. the volume
+    pub value: Option<u8>,
+    /// Tag under which a new notification from the same application
+    /// replaces the open one rather than stacking, as volume popups do
+    pub synchronous: Option<String>,
+    pub expiry: Expiry,
+}
+
+impl Notification {
+    /// A plain notification with no actions or hints
+    pub fn new(app_name: &str, summary: &str, body: &str) -> Self {
+        Self {
+            id: 0,
+            app_name: app_name.to_string(),
+            app_icon: String::new(),
+            summary: summary.to_string(),
+            body: body.to_string(),
+            actions: Vec::new(),
+            urgency: Urgency::Normal,
+            category: None,
+            desktop_entry: None,
+            image_path: None,
+            transient: false,
+            resident: false,
+            sound_name: None,
+            suppress_sound: false,
+            value: None,
+            synchronous: None,
+            expiry: Expiry::Default,
+        }
+    }
+
+    fn apply_hints(&mut self, hints: &HashMap<&str, Value<'_>>) {
+        for (name, value) in hints {
+            let value = match value {
+                Value::Value(inner) => inner.as_ref(),
+                other => other,
+            };
+            match (*name, value) {
+                ("urgency", Value::U8(byte)) => self.urgency = Urgency::from_byte(*byte),
+                ("category", Value::Str(s)) => self.category = Some(s.to_string()),
+                ("desktop-entry", Value::Str(s)) => self.desktop_entry = Some(s.to_string()),
+                ("image-path" | "image_path", Value::Str(s)) => self.image_path = Some(s.to_string()),
+                ("transient", Value::Bool(b)) => self.transient = *b,
+                ("resident", Value::Bool(b)) => self.resident = *b,
+                ("sound-name", Value::Str(s)) => self.sound_name = Some(s.to_string()),
+                ("suppress-sound", Value::Bool(b)) => self.suppress_sound = *b,
+                ("value", Value::I32(value)) => self.value = Some((*value).clamp(0, 100) as u8),
+                ("x-canonical-private-synchronous", Value::Str(s)) => self.synchronous = Some(s.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    /// How long the notification stays up, `None` for until dismissed.
+    /// Critical notifications never expire on their own.
+    pub fn timeout(&self) -> Option<Duration> {
+        match self.expiry {
+            Expiry::Never => None,
+            Expiry::After(duration) => Some(duration),
+            Expiry::Default if self.urgency == Urgency::Critical => None,
+            Expiry::Default => Some(DEFAULT_EXPIRY),
+        }
+    }
+}
+
+/// Draws notifications on screen
+///
+/// Renderers report clicks and dismissals back through
+/// [`NotificationDaemon::invoke_action`] and [`NotificationDaemon::close`].
+pub trait NotificationRenderer: Send + Sync {
+    /// Show a new notification or update the one with the same id
+    fn show(&self, notification: &Notification);
+
+    /// Take a notification off screen
+    fn close(&self, id: u32);
+
+    /// Optional features of the specification the renderer supports
+    fn capabilities(&self) -> Vec<String> {
+        vec!["body".to_string(), "actions".to_string()]
+    }
+}
+
+/// Renderer that only logs, for headless sessions and tests
+#[derive(Debug, Default)]
+pub struct LogRenderer;
+
+impl NotificationRenderer for LogRenderer {
+    fn show(&self, notification: &Notification) {
+        info!("Notification {}: {} - {}", notification.id, notification.summary, notification.body);
+    }
+
+    fn close(&self, id: u32) {
+        debug!("Notification {} closed", id);
+    }
+}
+
+#[derive(Default)]
+struct State {
+    last_id: u32,
+    /// Open notifications with a serial that changes on every update, so
+    /// stale expiry timers can tell they no longer apply
+    open: HashMap<u32, (Notification, u64)>,
+    serial: u64,
+}
+
+struct Inner {
+    renderer: Box<dyn NotificationRenderer>,
+    state: Mutex<State>,
+    connection: Mutex<Option<Connection>>,
+    /// Runtime `start` ran on, for timers and signals raised from D-Bus
+    /// calls, which zbus runs outside of Tokio
+    runtime: Mutex<Option<tokio::runtime::Handle>>,
+}
+
+/// The notification daemon; clones share the same notifications
+#[derive(Clone)]
+pub struct NotificationDaemon {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for NotificationDaemon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationDaemon")
+            .field("open", &self.open().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl NotificationDaemon {
+    pub fn new(renderer: impl NotificationRenderer + 'static) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                renderer: Box::new(renderer),
+                state: Mutex::new(State::default()),
+                connection: Mutex::new(None),
+                runtime: Mutex::new(None),
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn runtime(&self) -> Option<tokio::runtime::Handle> {
+        tokio::runtime::Handle::try_current()
+            .ok()
+            .or_else(|| self.inner.runtime.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    /// Claim `org.freedesktop.Notifications` on the session bus. Fails if
+    /// another notification daemon is running. Must be called within a
+    /// Tokio runtime.
+    pub async fn start(&self) -> Result<(), IpcError> {
+        let connection = zbus::connection::Builder::session()?
+            .name(NOTIFICATIONS_SERVICE)?
+            .serve_at(NOTIFICATIONS_PATH, NotificationsInterface { daemon: self.clone() })?
+            .build()
+            .await?;
+        info!("Notification daemon running as {}", NOTIFICATIONS_SERVICE);
+
+        *self.inner.runtime.lock().unwrap_or_else(|e| e.into_inner()) = Some(tokio::runtime::Handle::current());
+        *self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()) = Some(connection);
+        Ok(())
+    }
+
+    /// Show `notification`, replacing the open one with `replaces_id` if
+    /// there is one, or else the one with the same synchronous tag, and
+    /// return its id. Expiry needs a Tokio runtime: the caller's, or the
+    /// one the daemon was started on.
+    pub fn notify(&self, mut notification: Notification, mut replaces_id: u32) -> u32 {
+        let (timeout, serial) = {
+            let mut state = self.state();
+            if replaces_id == 0 && notification.synchronous.is_some() {
+                replaces_id = state
+                    .open
+                    .values()
+                    .find(|(open, _)| open.app_name == notification.app_name && open.synchronous == notification.synchronous)
+                    .map_or(0, |(open, _)| open.id);
+            }
+            notification.id = if replaces_id != 0 && state.open.contains_key(&replaces_id) {
+                replaces_id
+            } else {
+                state.last_id = state.last_id.checked_add(1).unwrap_or(1);
+                state.last_id
+            };
+            state.serial += 1;
+            let serial = state.serial;
+            state.open.insert(notification.id, (notification.clone(), serial));
+            (notification.timeout(), serial)
+        };
+
+        self.inner.renderer.show(&notification);
+
+        if let Some(timeout) = timeout {
+            if let Some(runtime) = self.runtime() {
+                let daemon = self.clone();
+                let id = notification.id;
+                runtime.spawn(async move {
+                    tokio::time::sleep(timeout).await;
+                    daemon.close_if_current(id, serial, CloseReason::Expired);
+                });
+            }
+        }
+        notification.id
+    }
+
+    /// Close a notification, telling its sender why. Returns whether it
+    /// was open.
+    pub fn close(&self, id: u32, reason: CloseReason) -> bool {
+        if self.state().open.remove(&id).is_none() {
+            return false;
+        }
+        self.closed(id, reason);
+        true
+    }
+
+    fn close_if_current(&self, id: u32, serial: u64, reason: CloseReason) {
+        {
+            let mut state = self.state();
+            if state.open.get(&id).map(|(_, current)| *current) != Some(serial) {
+                return;
+            }
+            state.open.remove(&id);
+        }
+        self.closed(id, reason);
+    }
+
+    fn closed(&self, id: u32, reason: CloseReason) {
+        self.inner.renderer.close(id);
+        self.emit(move |ctxt| async move {
+            NotificationsInterface::notification_closed(&ctxt, id, reason as u32).await
+        });
+    }
+
+    /// Report that the user picked action `key`. Non-resident
+    /// notifications are dismissed afterwards.
+    pub fn invoke_action(&self, id: u32, key: &str) {
+        let resident = match self.state().open.get(&id) {
+            Some((notification, _)) => notification.resident,
+            None => return,
+        };
+
+        let key = key.to_string();
+        self.emit(move |ctxt| async move { NotificationsInterface::action_invoked(&ctxt, id, &key).await });
+        if !resident {
+            self.close(id, CloseReason::Dismissed);
+        }
+    }
+
+    /// Notifications currently on screen, oldest first
+    pub fn open(&self) -> Vec<Notification> {
+        let mut open: Vec<Notification> = self.state().open.values().map(|(notification, _)| notification.clone()).collect();
+        open.sort_by_key(|notification| notification.id);
+        open
+    }
+
+    /// An IPC handler showing [`IpcMessage::DesktopNotification`]s
+    pub fn ipc_handler(&self) -> MessageHandler {
+        let daemon = self.clone();
+        Box::new(move |message| {
+            if let IpcMessage::DesktopNotification { title, body, urgency } = message {
+                let mut notification = Notification::new("XFCE.rs", &title, &body);
+                notification.urgency = Urgency::from_name(&urgency);
+                daemon.notify(notification, 0);
+            }
+            Ok(())
+        })
+    }
+
+    /// Send a signal in the background once the daemon is on the bus
+    fn emit<F, Fut>(&self, signal: F)
+    where
+        F: FnOnce(SignalContext<'static>) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = zbus::Result<()>> + Send,
+    {
+        let Some(connection) = self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+            return;
+        };
+        let Some(runtime) = self.runtime() else {
+            return;
+        };
+        runtime.spawn(async move {
+            let Ok(ctxt) = SignalContext::new(&connection, NOTIFICATIONS_PATH) else {
+                return;
+            };
+            if let Err(e) = signal(ctxt.into_owned()).await {
+                debug!("Failed to emit notification signal: {}", e);
+            }
+        });
+    }
+}
+
+/// The `org.freedesktop.Notifications` interface object
+struct NotificationsInterface {
+    daemon: NotificationDaemon,
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl NotificationsInterface {
+    #[allow(clippy::too_many_arguments)]
+    async fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: Vec<String>,
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+        #[zbus(connection)] connection: &Connection,
+    ) -> u32 {
+        let mut notification = Notification::new(app_name, summary, body);
+        notification.app_icon = app_icon.to_string();
+        notification.actions = actions
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        notification.apply_hints(&hints);
+        notification.expiry = Expiry::from_timeout(expire_timeout);
+
+        let sound = notification.sound_name.clone().filter(|_| !notification.suppress_sound);
+        let id = self.daemon.notify(notification, replaces_id);
+        if let Some(sound) = sound {
+            // Played alongside; the sender has waited long enough for its id
+            let connection = connection.clone();
+            tokio::spawn(async move {
+                // Sessions without the audio daemon just stay quiet
+                if let Err(e) = crate::sound::play_event_sound(&connection, &sound).await {
+                    debug!("Failed to play notification sound {}: {}", sound, e);
+                }
+            });
+        }
+        id
+    }
+
+    async fn close_notification(&self, id: u32) {
+        self.daemon.close(id, CloseReason::Closed);
+    }
+
+    async fn get_capabilities(&self, #[zbus(connection)] connection: &Connection) -> Vec<String> {
+        let mut capabilities = self.daemon.inner.renderer.capabilities();
+        // Played through the event sound service, when it runs
+        if crate::sound::event_sounds_available(connection).await {
+            capabilities.push("sound".to_string());
+        }
+        capabilities
+    }
+
+    async fn get_server_information(&self) -> (String, String, String, String) {
+        (
+            "XFCE.rs".to_string(),
+            "XFCE.rs".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+            SPEC_VERSION.to_string(),
+        )
+    }
+
+    #[zbus(signal)]
+    async fn notification_closed(ctxt: &SignalContext<'_>, id: u32, reason: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn action_invoked(ctxt: &SignalContext<'_>, id: u32, action_key: &str) -> zbus::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct RecordingRenderer {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl NotificationRenderer for RecordingRenderer {
+        fn show(&self, notification: &Notification) {
+            self.events.lock().unwrap().push(format!("show {} {}", notification.id, notification.summary));
+        }
+
+        fn close(&self, id: u32) {
+            self.events.lock().unwrap().push(format!("close {}", id));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_replace_close_and_expire() {
+        let renderer = RecordingRenderer::default();
+        let daemon = NotificationDaemon::new(renderer.clone());
+
+        let mut volume = Notification::new("audio", "Volume 40%", "");
+        volume.expiry = Expiry::After(Duration::from_secs(2));
+        let id = daemon.notify(volume.clone(), 0);
+        volume.summary = "Volume 45%".to_string();
+        assert_eq!(daemon.notify(volume, id), id);
+
+        let mut battery = Notification::new("power", "Battery low", "");
+        battery.urgency = Urgency::Critical;
+        let battery_id = daemon.notify(battery, 0);
+        assert_ne!(battery_id, id);
+
+        // The replaced notification's timer must not close the update early
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert_eq!(daemon.open().len(), 1);
+        assert!(!daemon.close(id, CloseReason::Closed));
+
+        daemon.invoke_action(battery_id, "default");
+        assert!(daemon.open().is_empty());
+
+        assert_eq!(
+            *renderer.events.lock().unwrap(),
+            [
+                format!("show {} Volume 40%", id),
+                format!("show {} Volume 45%", id),
+                format!("show {} Battery low", battery_id),
+                format!("close {}", id),
+                format!("close {}", battery_id),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_synchronous_replaces() {
+        let daemon = NotificationDaemon::new(RecordingRenderer::default());
+        let mut volume = Notification::new("audio", "Volume", "");
+        volume.synchronous = Some("volume".to_string());
+        let id = daemon.notify(volume.clone(), 0);
+        assert_eq!(daemon.notify(volume.clone(), 0), id);
+
+        // Other applications don't share the tag
+        let mut other = volume.clone();
+        other.app_name = "player".to_string();
+        assert_ne!(daemon.notify(other, 0), id);
+        volume.synchronous = None;
+        assert_ne!(daemon.notify(volume, 0), id);
+        assert_eq!(daemon.open().len(), 3);
+    }
+
+    #[test]
+    fn test_hints() {
+        let mut notification = Notification::new("firefox", "Download finished", "report.pdf");
+        let hints = HashMap::from([
+            ("urgency", Value::U8(0)),
+            ("category", Value::from("transfer.complete")),
+            ("transient", Value::Bool(true)),
+            ("sound-name", Value::from("message-new-instant")),
+            ("value", Value::Value(Box::new(Value::I32(140)))),
+        ]);
+        notification.apply_hints(&hints);
+
+        assert_eq!(notification.urgency, Urgency::Low);
+        assert_eq!(notification.category.as_deref(), Some("transfer.complete"));
+        assert!(notification.transient);
+        assert_eq!(notification.sound_name.as_deref(), Some("message-new-instant"));
+        assert!(!notification.suppress_sound);
+        assert_eq!(notification.value, Some(100));
+        assert_eq!(notification.synchronous, None);
+        assert_eq!(Expiry::from_timeout(-1), Expiry::Default);
+        assert_eq!(Expiry::from_timeout(0), Expiry::Never);
+        assert_eq!(notification.timeout(), Some(DEFAULT_EXPIRY));
+    }
+}
@@ -0,0 +1,503 @@
+//! PolicyKit authentication agent
+//!
+//! polkitd asks the agent registered for the user's session to
+//! authenticate whenever a program such as pkexec or UDisks2 needs
+//! administrator rights. [`PolkitAgent`] registers for the current session
+//! and hands each request to whichever component reads the
+//! [`AuthenticationRequest`]s. Passwords are checked by polkit's setuid
+//! helper, which reports the outcome to polkitd itself.
+
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{interface, proxy, Connection, DBusError};
+
+use crate::login1::{Login1ManagerProxy, Login1SessionProxy};
+use crate::IpcError;
+
+pub const POLKIT_SERVICE: &str = "org.freedesktop.PolicyKit1";
+pub const AUTHORITY_PATH: &str = "/org/freedesktop/PolicyKit1/Authority";
+/// Where the agent object is served on the system bus
+pub const AGENT_PATH: &str = "/org/xfce_rs/PolicyKit1/AuthenticationAgent";
+
+/// Locations of `polkit-agent-helper-1` across distributions
+const HELPER_PATHS: &[&str] = &[
+    "/usr/lib/polkit-1/polkit-agent-helper-1",
+    "/usr/libexec/polkit-agent-helper-1",
+    "/usr/lib/policykit-1/polkit-agent-helper-1",
+];
+
+/// `(kind, details)` as polkit encodes subjects and identities
+type Subject<'a> = (&'a str, HashMap<&'a str, Value<'a>>);
+
+/// `org.freedesktop.PolicyKit1.Authority`
+#[proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+pub trait PolkitAuthority {
+    fn register_authentication_agent(
+        &self,
+        subject: Subject<'_>,
+        locale: &str,
+        object_path: &str,
+    ) -> zbus::Result<()>;
+
+    fn unregister_authentication_agent(&self, subject: Subject<'_>, object_path: &str) -> zbus::Result<()>;
+}
+
+/// Errors reported to polkitd
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.freedesktop.PolicyKit1.Error")]
+pub enum PolkitError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Failed(String),
+    Cancelled(String),
+}
+
+/// Why an authentication attempt did not succeed
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    /// Wrong password or a PAM error; the user may try again
+    #[error("{0}")]
+    Failed(String),
+    #[error("Failed to run {}: {}", .0.display(), .1)]
+    Helper(PathBuf, std::io::Error),
+}
+
+/// A user who may authenticate for a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub uid: u32,
+    pub name: String,
+    /// Full name from the GECOS field, or the user name
+    pub display_name: String,
+}
+
+/// Expand the `unix-user` and `unix-group` identities polkit accepts into
+/// users, given the contents of `/etc/passwd` and `/etc/group`. Groups
+/// count both members listed in `/etc/group` and users whose primary group
+/// it is. `current_uid` is moved to the front when present.
+fn resolve_identities(
+    identities: &[(String, HashMap<String, OwnedValue>)],
+    passwd: &str,
+    group: &str,
+    current_uid: Option<u32>,
+) -> Vec<Identity> {
+    // (name, uid, gid, display name)
+    let users: Vec<(&str, u32, u32, &str)> = passwd
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let [name, _, uid, gid, gecos, ..] = fields[..] else {
+                return None;
+            };
+            Some((name, uid.parse().ok()?, gid.parse().ok()?, gecos.split(',').next().unwrap_or("")))
+        })
+        .collect();
+    let identity = |&(name, uid, _, gecos): &(&str, u32, u32, &str)| Identity {
+        uid,
+        name: name.to_string(),
+        display_name: if gecos.is_empty() { name } else { gecos }.to_string(),
+    };
+
+    let mut resolved: Vec<Identity> = Vec::new();
+    for (kind, details) in identities {
+        let id = |key: &str| match details.get(key).map(|value| &**value) {
+            Some(Value::U32(id)) => Some(*id),
+            _ => None,
+        };
+        let found: Vec<Identity> = match kind.as_str() {
+            "unix-user" => id("uid")
+                .and_then(|uid| users.iter().find(|user| user.1 == uid))
+                .map(identity)
+                .into_iter()
+                .collect(),
+            "unix-group" => {
+                let Some(gid) = id("gid") else { continue };
+                let members: Vec<&str> = group
+                    .lines()
+                    .map(|line| line.split(':').collect::<Vec<_>>())
+                    .find(|fields| fields.get(2).and_then(|id| id.parse().ok()) == Some(gid))
+                    .and_then(|fields| fields.get(3).copied())
+                    .map(|members| members.split(',').filter(|name| !name.is_empty()).collect())
+                    .unwrap_or_default();
+                users
+                    .iter()
+                    .filter(|user| user.2 == gid || members.contains(&user.0))
+                    .map(identity)
+                    .collect()
+            }
+            other => {
+                debug!("Ignoring polkit identity of kind {}", other);
+                Vec::new()
+            }
+        };
+        for user in found {
+            if !resolved.iter().any(|known| known.uid == user.uid) {
+                resolved.push(user);
+            }
+        }
+    }
+
+    if let Some(index) = resolved.iter().position(|user| Some(user.uid) == current_uid) {
+        let current = resolved.remove(index);
+        resolved.insert(0, current);
+    }
+    resolved
+}
+
+/// A line `polkit-agent-helper-1` writes while talking to PAM
+#[derive(Debug, PartialEq, Eq)]
+enum HelperLine<'a> {
+    Prompt { text: &'a str, echo: bool },
+    Info(&'a str),
+    Error(&'a str),
+    Success,
+    Failure,
+}
+
+fn parse_helper_line(line: &str) -> Option<HelperLine<'_>> {
+    let line = line.trim_end_matches('\n');
+    let (tag, text) = line.split_once(' ').unwrap_or((line, ""));
+    Some(match tag {
+        "PAM_PROMPT_ECHO_OFF" => HelperLine::Prompt { text, echo: false },
+        "PAM_PROMPT_ECHO_ON" => HelperLine::Prompt { text, echo: true },
+        "PAM_TEXT_INFO" => HelperLine::Info(text),
+        "PAM_ERROR_MSG" => HelperLine::Error(text),
+        "SUCCESS" => HelperLine::Success,
+        "FAILURE" => HelperLine::Failure,
+        _ => return None,
+    })
+}
+
+fn find_helper() -> Option<&'static Path> {
+    HELPER_PATHS.iter().map(Path::new).find(|path| path.exists())
+}
+
+struct SessionInner {
+    cookie: String,
+    helper: &'static Path,
+    done: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+/// Handle for answering one request; clones refer to the same request.
+/// Dropping every clone without succeeding dismisses it.
+#[derive(Clone)]
+pub struct AuthSession {
+    inner: Arc<SessionInner>,
+}
+
+impl std::fmt::Debug for AuthSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthSession").finish_non_exhaustive()
+    }
+}
+
+impl AuthSession {
+    fn done(&self) -> std::sync::MutexGuard<'_, Option<oneshot::Sender<()>>> {
+        self.inner.done.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Authenticate as `identity`, answering every PAM prompt with
+    /// `password`. Must be called within a Tokio runtime.
+    pub async fn authenticate(&self, identity: &Identity, password: &str) -> Result<(), AuthError> {
+        let helper = self.inner.helper;
+        let io_error = |e| AuthError::Helper(helper.to_path_buf(), e);
+        let mut child = Command::new(helper)
+            .arg(&identity.name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(io_error)?;
+        let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(AuthError::Failed("Authentication helper has no pipes".to_string()));
+        };
+
+        stdin
+            .write_all(format!("{}\n", self.inner.cookie).as_bytes())
+            .await
+            .map_err(io_error)?;
+        let mut lines = BufReader::new(stdout).lines();
+        let mut errors = Vec::new();
+        while let Some(line) = lines.next_line().await.map_err(io_error)? {
+            match parse_helper_line(&line) {
+                Some(HelperLine::Prompt { text, echo }) => {
+                    debug!("PAM prompt {:?} (echo {})", text, echo);
+                    stdin
+                        .write_all(format!("{}\n", password).as_bytes())
+                        .await
+                        .map_err(io_error)?;
+                }
+                Some(HelperLine::Info(text)) => debug!("PAM: {}", text),
+                Some(HelperLine::Error(text)) => errors.push(text.to_string()),
+                Some(HelperLine::Success) => {
+                    if let Some(done) = self.done().take() {
+                        let _ = done.send(());
+                    }
+                    return Ok(());
+                }
+                Some(HelperLine::Failure) => break,
+                None => debug!("Unexpected authentication helper output: {}", line),
+            }
+        }
+
+        let _ = child.wait().await;
+        if errors.is_empty() {
+            Err(AuthError::Failed("Authentication failed".to_string()))
+        } else {
+            Err(AuthError::Failed(errors.join("\n")))
+        }
+    }
+
+    /// Dismiss the request
+    pub fn cancel(&self) {
+        self.done().take();
+    }
+
+    /// Whether the request is over: answered, dismissed, or cancelled by
+    /// polkitd, so the dialog can close itself
+    pub fn is_closed(&self) -> bool {
+        self.done().as_ref().is_none_or(|done| done.is_closed())
+    }
+}
+
+/// A credential prompt for the desktop to show, read from
+/// [`PolkitAgent::new`]'s receiver
+#[derive(Debug, Clone)]
+pub struct AuthenticationRequest {
+    pub action_id: String,
+    /// Explanation of the action, already translated
+    pub message: String,
+    pub icon_name: String,
+    pub details: HashMap<String, String>,
+    /// Users who may authenticate, the current user first when allowed
+    pub identities: Vec<Identity>,
+    pub session: AuthSession,
+}
+
+struct Inner {
+    requests: mpsc::UnboundedSender<AuthenticationRequest>,
+    /// Cancellation of the requests in progress, by cookie
+    pending: Mutex<HashMap<String, oneshot::Sender<()>>>,
+    connection: Mutex<Option<Connection>>,
+}
+
+/// The session's authentication agent
+#[derive(Clone)]
+pub struct PolkitAgent {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for PolkitAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PolkitAgent").finish_non_exhaustive()
+    }
+}
+
+impl PolkitAgent {
+    /// Create the agent and the receiver its requests arrive on
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<AuthenticationRequest>) {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let agent = Self {
+            inner: Arc::new(Inner {
+                requests,
+                pending: Mutex::new(HashMap::new()),
+                connection: Mutex::new(None),
+            }),
+        };
+        (agent, receiver)
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<()>>> {
+        self.inner.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register as the agent of the current login session. Fails if
+    /// another agent already serves it.
+    pub async fn start(&self) -> Result<(), IpcError> {
+        let connection = zbus::connection::Builder::system()?
+            .serve_at(AGENT_PATH, AgentInterface { agent: self.clone() })?
+            .build()
+            .await?;
+
+        let session_id = session_id(&connection).await?;
+        let subject = ("unix-session", HashMap::from([("session-id", Value::from(session_id.as_str()))]));
+        PolkitAuthorityProxy::new(&connection)
+            .await?
+            .register_authentication_agent(subject, &locale(), AGENT_PATH)
+            .await?;
+        info!("PolicyKit agent registered for session {}", session_id);
+
+        *self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()) = Some(connection);
+        Ok(())
+    }
+
+    async fn begin(
+        &self,
+        action_id: String,
+        message: String,
+        icon_name: String,
+        details: HashMap<String, String>,
+        cookie: String,
+        identities: Vec<(String, HashMap<String, OwnedValue>)>,
+    ) -> Result<(), PolkitError> {
+        let Some(helper) = find_helper() else {
+            return Err(PolkitError::Failed("polkit-agent-helper-1 not found".to_string()));
+        };
+        let read = |path: &str| std::fs::read_to_string(path).unwrap_or_default();
+        let current_uid = std::fs::metadata("/proc/self").map(|meta| meta.uid()).ok();
+        let identities = resolve_identities(&identities, &read("/etc/passwd"), &read("/etc/group"), current_uid);
+        if identities.is_empty() {
+            return Err(PolkitError::Failed("No user may authenticate".to_string()));
+        }
+
+        let (done, answered) = oneshot::channel();
+        let (cancel, cancelled) = oneshot::channel();
+        self.pending().insert(cookie.clone(), cancel);
+        let request = AuthenticationRequest {
+            action_id,
+            message,
+            icon_name,
+            details,
+            identities,
+            session: AuthSession {
+                inner: Arc::new(SessionInner {
+                    cookie: cookie.clone(),
+                    helper,
+                    done: Mutex::new(Some(done)),
+                }),
+            },
+        };
+        if self.inner.requests.send(request).is_err() {
+            self.pending().remove(&cookie);
+            return Err(PolkitError::Failed("No component shows authentication dialogs".to_string()));
+        }
+
+        let outcome = tokio::select! {
+            answer = answered => match answer {
+                Ok(()) => Ok(()),
+                Err(_) => Err(PolkitError::Cancelled("Dismissed by the user".to_string())),
+            },
+            // Dropping the answer receiver lets the dialog see is_closed()
+            _ = cancelled => Err(PolkitError::Cancelled("Cancelled by polkit".to_string())),
+        };
+        self.pending().remove(&cookie);
+        outcome
+    }
+}
+
+/// The login session this process belongs to
+async fn session_id(connection: &Connection) -> Result<String, IpcError> {
+    if let Ok(id) = std::env::var("XDG_SESSION_ID") {
+        if !id.is_empty() {
+            return Ok(id);
+        }
+    }
+    let path = Login1ManagerProxy::new(connection)
+        .await?
+        .get_session_by_pid(std::process::id())
+        .await?;
+    let session = Login1SessionProxy::builder(connection).path(path)?.build().await?;
+    Ok(session.id().await?)
+}
+
+fn locale() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_else(|| "C".to_string())
+}
+
+/// `org.freedesktop.PolicyKit1.AuthenticationAgent`
+struct AgentInterface {
+    agent: PolkitAgent,
+}
+
+#[interface(name = "org.freedesktop.PolicyKit1.AuthenticationAgent")]
+impl AgentInterface {
+    async fn begin_authentication(
+        &self,
+        action_id: String,
+        message: String,
+        icon_name: String,
+        details: HashMap<String, String>,
+        cookie: String,
+        identities: Vec<(String, HashMap<String, OwnedValue>)>,
+    ) -> Result<(), PolkitError> {
+        debug!("Authentication for {} requested", action_id);
+        self.agent
+            .begin(action_id, message, icon_name, details, cookie, identities)
+            .await
+    }
+
+    async fn cancel_authentication(&self, cookie: String) {
+        match self.agent.pending().remove(&cookie) {
+            Some(cancel) => {
+                let _ = cancel.send(());
+            }
+            None => warn!("polkit cancelled an unknown authentication"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0::/root:/bin/bash\n\
+        alice:x:1000:1000:Alice Liddell,,,:/home/alice:/bin/bash\n\
+        bob:x:1001:10::/home/bob:/bin/zsh\n\
+        carol:x:1002:1002:Carol:/home/carol:/bin/sh\n";
+    const GROUP: &str = "root:x:0:\nwheel:x:10:alice,carol\nalice:x:1000:\n";
+
+    fn identity(kind: &str, key: &str, id: u32) -> (String, HashMap<String, OwnedValue>) {
+        (kind.to_string(), HashMap::from([(key.to_string(), OwnedValue::from(id))]))
+    }
+
+    #[test]
+    fn test_resolve_identities() {
+        let wheel = [identity("unix-group", "gid", 10)];
+        let names = |identities: Vec<Identity>| -> Vec<String> {
+            identities.into_iter().map(|identity| identity.name).collect()
+        };
+        assert_eq!(names(resolve_identities(&wheel, PASSWD, GROUP, None)), ["alice", "bob", "carol"]);
+        assert_eq!(names(resolve_identities(&wheel, PASSWD, GROUP, Some(1002))), ["carol", "alice", "bob"]);
+
+        let mixed = [
+            identity("unix-user", "uid", 0),
+            identity("unix-group", "gid", 10),
+            identity("unix-netgroup", "gid", 10),
+        ];
+        let resolved = resolve_identities(&mixed, PASSWD, GROUP, None);
+        assert_eq!(names(resolved.clone()), ["root", "alice", "bob", "carol"]);
+        assert_eq!(resolved[0].display_name, "root");
+        assert_eq!(resolved[1].display_name, "Alice Liddell");
+
+        assert!(resolve_identities(&[identity("unix-user", "uid", 4242)], PASSWD, GROUP, None).is_empty());
+    }
+
+    #[test]
+    fn test_parse_helper_line() {
+        assert_eq!(
+            parse_helper_line("PAM_PROMPT_ECHO_OFF Password: "),
+            Some(HelperLine::Prompt { text: "Password: ", echo: false })
+        );
+        assert_eq!(parse_helper_line("PAM_ERROR_MSG Sorry"), Some(HelperLine::Error("Sorry")));
+        assert_eq!(parse_helper_line("SUCCESS"), Some(HelperLine::Success));
+        assert_eq!(parse_helper_line("FAILURE\n"), Some(HelperLine::Failure));
+        assert_eq!(parse_helper_line("garbage"), None);
+    }
+}
@@ -0,0 +1,606 @@
+//! Backend for xdg-desktop-portal
+//!
+//! Sandboxed applications such as Flatpaks reach the desktop through
+//! xdg-desktop-portal, which forwards their requests to the backend listed
+//! in `xfce-rs.portal`. [`PortalBackend`] serves the FileChooser, Screenshot
+//! and Settings backend interfaces. Settings are answered from its own
+//! store; dialogs are handed to whichever component reads the
+//! [`PortalRequest`]s.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{interface, Connection, DBusError, ObjectServer, SignalContext};
+
+use crate::mpris::{flag, text, unwrap_variant};
+use crate::IpcError;
+
+/// Bus name named in `xfce-rs.portal`
+pub const PORTAL_SERVICE: &str = "org.freedesktop.impl.portal.desktop.xfce_rs";
+pub const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+/// Settings namespace holding the dark-mode preference
+pub const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+
+/// Errors reported to xdg-desktop-portal
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.freedesktop.portal.Error")]
+pub enum PortalError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotFound(String),
+}
+
+/// Outcome of a dialog request, as sent back to the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Success,
+    /// The user dismissed the dialog
+    Cancelled,
+    /// The request failed or nothing could handle it
+    Other,
+}
+
+impl Response {
+    pub fn code(self) -> u32 {
+        match self {
+            Response::Success => 0,
+            Response::Cancelled => 1,
+            Response::Other => 2,
+        }
+    }
+}
+
+/// Value of `org.freedesktop.appearance` `color-scheme`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    #[default]
+    NoPreference,
+    PreferDark,
+    PreferLight,
+}
+
+impl ColorScheme {
+    pub fn code(self) -> u32 {
+        match self {
+            ColorScheme::NoPreference => 0,
+            ColorScheme::PreferDark => 1,
+            ColorScheme::PreferLight => 2,
+        }
+    }
+
+    /// Guess the preference from a GTK theme name such as `Adwaita-dark`
+    pub fn from_theme_name(name: &str) -> Self {
+        if name.is_empty() {
+            ColorScheme::NoPreference
+        } else if name.to_lowercase().contains("dark") {
+            ColorScheme::PreferDark
+        } else {
+            ColorScheme::PreferLight
+        }
+    }
+}
+
+/// A named set of file patterns offered in a file chooser
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FileFilter {
+    pub name: String,
+    /// Shell globs such as `*.png`
+    pub globs: Vec<String>,
+    pub mime_types: Vec<String>,
+}
+
+impl FileFilter {
+    /// Parse the `(sa(us))` filter structure
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        let Value::Structure(filter) = unwrap_variant(value) else {
+            return None;
+        };
+        let [name, Value::Array(patterns)] = filter.fields() else {
+            return None;
+        };
+
+        let mut parsed = FileFilter {
+            name: text(name)?,
+            ..FileFilter::default()
+        };
+        for pattern in patterns.inner() {
+            let Value::Structure(pattern) = unwrap_variant(pattern) else {
+                continue;
+            };
+            match pattern.fields() {
+                [Value::U32(0), pattern] => parsed.globs.extend(text(pattern)),
+                [Value::U32(1), pattern] => parsed.mime_types.extend(text(pattern)),
+                _ => {}
+            }
+        }
+        Some(parsed)
+    }
+}
+
+/// What an application asked of a file chooser
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChooserOptions {
+    pub title: String,
+    /// Label for the accept button, with `_` marking the mnemonic
+    pub accept_label: Option<String>,
+    pub modal: bool,
+    pub multiple: bool,
+    /// Choose folders instead of files
+    pub directory: bool,
+    pub filters: Vec<FileFilter>,
+    pub current_filter: Option<FileFilter>,
+    /// Suggested file name when saving
+    pub current_name: Option<String>,
+    pub current_folder: Option<PathBuf>,
+    /// File being saved again, when saving
+    pub current_file: Option<PathBuf>,
+}
+
+impl FileChooserOptions {
+    fn from_options(title: String, options: &HashMap<String, OwnedValue>) -> Self {
+        let get = |key: &str| options.get(key).map(|value| &**value);
+        Self {
+            title,
+            accept_label: get("accept_label").and_then(text),
+            modal: get("modal").and_then(flag).unwrap_or(true),
+            multiple: get("multiple").and_then(flag).unwrap_or(false),
+            directory: get("directory").and_then(flag).unwrap_or(false),
+            filters: match get("filters").map(unwrap_variant) {
+                Some(Value::Array(filters)) => filters.inner().iter().filter_map(FileFilter::from_value).collect(),
+                _ => Vec::new(),
+            },
+            current_filter: get("current_filter").and_then(FileFilter::from_value),
+            current_name: get("current_name").and_then(text),
+            current_folder: get("current_folder").and_then(path_bytes),
+            current_file: get("current_file").and_then(path_bytes),
+        }
+    }
+}
+
+/// Paths are sent as nul-terminated byte arrays
+fn path_bytes(value: &Value<'_>) -> Option<PathBuf> {
+    let Value::Array(bytes) = unwrap_variant(value) else {
+        return None;
+    };
+    let bytes: Vec<u8> = bytes
+        .inner()
+        .iter()
+        .filter_map(|byte| match byte {
+            Value::U8(byte) => Some(*byte),
+            _ => None,
+        })
+        .take_while(|byte| *byte != 0)
+        .collect();
+    (!bytes.is_empty()).then(|| PathBuf::from(OsStr::from_bytes(&bytes)))
+}
+
+/// Answer to a dialog request. Dropping it unanswered reports a failure
+/// to the application.
+#[derive(Debug)]
+pub struct PortalReply<T> {
+    sender: oneshot::Sender<Option<T>>,
+}
+
+impl<T> PortalReply<T> {
+    fn new() -> (Self, oneshot::Receiver<Option<T>>) {
+        let (sender, receiver) = oneshot::channel();
+        (Self { sender }, receiver)
+    }
+
+    pub fn succeed(self, value: T) {
+        let _ = self.sender.send(Some(value));
+    }
+
+    pub fn cancel(self) {
+        let _ = self.sender.send(None);
+    }
+
+    /// Whether the application withdrew the request, so the dialog can
+    /// close itself
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+/// A dialog for the desktop to show, read from [`PortalBackend::new`]'s
+/// receiver
+#[derive(Debug)]
+pub enum PortalRequest {
+    /// Answered with the chosen `file://` URIs
+    OpenFile {
+        app_id: String,
+        /// `x11:<xid>` or `wayland:<handle>`, empty if unknown
+        parent_window: String,
+        options: FileChooserOptions,
+        reply: PortalReply<Vec<String>>,
+    },
+    /// Answered with the URI to save to
+    SaveFile {
+        app_id: String,
+        parent_window: String,
+        options: FileChooserOptions,
+        reply: PortalReply<Vec<String>>,
+    },
+    /// Answered with the URI of the saved image
+    Screenshot {
+        app_id: String,
+        parent_window: String,
+        /// Let the user pick the area and options first
+        interactive: bool,
+        reply: PortalReply<String>,
+    },
+    /// Answered with the picked color as linear RGB in 0..=1
+    PickColor {
+        app_id: String,
+        parent_window: String,
+        reply: PortalReply<(f64, f64, f64)>,
+    },
+}
+
+type Settings = HashMap<String, HashMap<String, OwnedValue>>;
+
+/// Whether a `ReadAll` namespace pattern selects `namespace`; patterns
+/// ending in `*` match by prefix
+fn namespace_matches(pattern: &str, namespace: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => namespace.starts_with(prefix),
+        None => pattern == namespace,
+    }
+}
+
+struct Inner {
+    requests: mpsc::UnboundedSender<PortalRequest>,
+    settings: Mutex<Settings>,
+    connection: Mutex<Option<Connection>>,
+}
+
+/// The portal backend; clones share the same settings
+#[derive(Clone)]
+pub struct PortalBackend {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for PortalBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PortalBackend").finish_non_exhaustive()
+    }
+}
+
+impl PortalBackend {
+    /// Create the backend and the receiver its dialog requests arrive on
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<PortalRequest>) {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let mut settings = Settings::new();
+        settings.entry(APPEARANCE_NAMESPACE.to_string()).or_default().insert(
+            "color-scheme".to_string(),
+            OwnedValue::from(ColorScheme::NoPreference.code()),
+        );
+
+        let backend = Self {
+            inner: Arc::new(Inner {
+                requests,
+                settings: Mutex::new(settings),
+                connection: Mutex::new(None),
+            }),
+        };
+        (backend, receiver)
+    }
+
+    fn settings(&self) -> std::sync::MutexGuard<'_, Settings> {
+        self.inner.settings.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Claim the backend name on the session bus. Must be called within a
+    /// Tokio runtime.
+    pub async fn start(&self) -> Result<(), IpcError> {
+        let connection = zbus::connection::Builder::session()?
+            .name(PORTAL_SERVICE)?
+            .serve_at(PORTAL_PATH, FileChooserInterface { backend: self.clone() })?
+            .serve_at(PORTAL_PATH, ScreenshotInterface { backend: self.clone() })?
+            .serve_at(PORTAL_PATH, SettingsInterface { backend: self.clone() })?
+            .build()
+            .await?;
+        info!("Portal backend running as {}", PORTAL_SERVICE);
+
+        *self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()) = Some(connection);
+        Ok(())
+    }
+
+    /// Store a setting and tell applications when it changed
+    pub async fn set_setting(&self, namespace: &str, key: &str, value: Value<'_>) -> Result<(), IpcError> {
+        let value = value.try_to_owned().map_err(zbus::Error::from)?;
+        let changed = {
+            let mut settings = self.settings();
+            let keys = settings.entry(namespace.to_string()).or_default();
+            if keys.get(key) == Some(&value) {
+                false
+            } else {
+                keys.insert(key.to_string(), value.try_clone().map_err(zbus::Error::from)?);
+                true
+            }
+        };
+        if !changed {
+            return Ok(());
+        }
+
+        let connection = self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some(connection) = connection {
+            let ctxt = SignalContext::new(&connection, PORTAL_PATH)?;
+            SettingsInterface::setting_changed(&ctxt, namespace, key, Value::from(value)).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn set_color_scheme(&self, scheme: ColorScheme) -> Result<(), IpcError> {
+        debug!("Portal color scheme: {:?}", scheme);
+        self.set_setting(APPEARANCE_NAMESPACE, "color-scheme", Value::U32(scheme.code()))
+            .await
+    }
+
+    /// Hand `request` to the desktop and wait for the answer, or for the
+    /// application to close the request object at `handle`
+    async fn ask<T>(
+        &self,
+        server: &ObjectServer,
+        handle: ObjectPath<'_>,
+        request: PortalRequest,
+        answer: oneshot::Receiver<Option<T>>,
+    ) -> (Response, Option<T>) {
+        if self.inner.requests.send(request).is_err() {
+            debug!("No component handles portal dialogs");
+            return (Response::Other, None);
+        }
+
+        let (close, closed) = oneshot::channel();
+        let handle = OwnedObjectPath::from(handle.to_owned());
+        let served = server
+            .at(&handle, RequestInterface { close: Mutex::new(Some(close)) })
+            .await
+            .unwrap_or(false);
+
+        let outcome = tokio::select! {
+            answer = answer => match answer {
+                Ok(Some(value)) => (Response::Success, Some(value)),
+                Ok(None) => (Response::Cancelled, None),
+                Err(_) => (Response::Other, None),
+            },
+            // Dropping the answer receiver lets the dialog see is_closed()
+            _ = closed => (Response::Cancelled, None),
+        };
+
+        if served {
+            let _ = server.remove::<RequestInterface, _>(&handle).await;
+        }
+        outcome
+    }
+}
+
+fn results<I>(entries: I) -> HashMap<String, OwnedValue>
+where
+    I: IntoIterator<Item = (&'static str, Value<'static>)>,
+{
+    entries
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.try_to_owned().ok()?)))
+        .collect()
+}
+
+/// `org.freedesktop.impl.portal.Request`, served at the request handle
+/// while a dialog is open
+struct RequestInterface {
+    close: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+#[interface(name = "org.freedesktop.impl.portal.Request")]
+impl RequestInterface {
+    async fn close(&self) {
+        if let Some(close) = self.close.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = close.send(());
+        }
+    }
+}
+
+struct FileChooserInterface {
+    backend: PortalBackend,
+}
+
+#[interface(name = "org.freedesktop.impl.portal.FileChooser")]
+impl FileChooserInterface {
+    async fn open_file(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        handle: ObjectPath<'_>,
+        app_id: String,
+        parent_window: String,
+        title: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> (u32, HashMap<String, OwnedValue>) {
+        let (reply, answer) = PortalReply::new();
+        let request = PortalRequest::OpenFile {
+            app_id,
+            parent_window,
+            options: FileChooserOptions::from_options(title, &options),
+            reply,
+        };
+        let (response, uris) = self.backend.ask(server, handle, request, answer).await;
+        (response.code(), results(uris.map(|uris| ("uris", Value::from(uris)))))
+    }
+
+    async fn save_file(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        handle: ObjectPath<'_>,
+        app_id: String,
+        parent_window: String,
+        title: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> (u32, HashMap<String, OwnedValue>) {
+        let (reply, answer) = PortalReply::new();
+        let request = PortalRequest::SaveFile {
+            app_id,
+            parent_window,
+            options: FileChooserOptions::from_options(title, &options),
+            reply,
+        };
+        let (response, uris) = self.backend.ask(server, handle, request, answer).await;
+        (response.code(), results(uris.map(|uris| ("uris", Value::from(uris)))))
+    }
+}
+
+struct ScreenshotInterface {
+    backend: PortalBackend,
+}
+
+#[interface(name = "org.freedesktop.impl.portal.Screenshot")]
+impl ScreenshotInterface {
+    async fn screenshot(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        handle: ObjectPath<'_>,
+        app_id: String,
+        parent_window: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> (u32, HashMap<String, OwnedValue>) {
+        let (reply, answer) = PortalReply::new();
+        let request = PortalRequest::Screenshot {
+            app_id,
+            parent_window,
+            interactive: options.get("interactive").and_then(|value| flag(value)).unwrap_or(false),
+            reply,
+        };
+        let (response, uri) = self.backend.ask(server, handle, request, answer).await;
+        (response.code(), results(uri.map(|uri| ("uri", Value::from(uri)))))
+    }
+
+    async fn pick_color(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        handle: ObjectPath<'_>,
+        app_id: String,
+        parent_window: String,
+        _options: HashMap<String, OwnedValue>,
+    ) -> (u32, HashMap<String, OwnedValue>) {
+        let (reply, answer) = PortalReply::new();
+        let request = PortalRequest::PickColor {
+            app_id,
+            parent_window,
+            reply,
+        };
+        let (response, color) = self.backend.ask(server, handle, request, answer).await;
+        (response.code(), results(color.map(|color| ("color", Value::from(color)))))
+    }
+
+    #[zbus(property, name = "version")]
+    async fn version(&self) -> u32 {
+        2
+    }
+}
+
+struct SettingsInterface {
+    backend: PortalBackend,
+}
+
+#[interface(name = "org.freedesktop.impl.portal.Settings")]
+impl SettingsInterface {
+    /// All settings in the namespaces matching `namespaces`, or in every
+    /// namespace if it is empty
+    async fn read_all(&self, namespaces: Vec<String>) -> Result<Settings, PortalError> {
+        let settings = self.backend.settings();
+        let mut selected = Settings::new();
+        for (namespace, keys) in settings.iter() {
+            let wanted =
+                namespaces.is_empty() || namespaces.iter().any(|pattern| namespace_matches(pattern, namespace));
+            if !wanted {
+                continue;
+            }
+            let mut copied = HashMap::new();
+            for (key, value) in keys {
+                copied.insert(key.clone(), value.try_clone().map_err(zbus::Error::from)?);
+            }
+            selected.insert(namespace.clone(), copied);
+        }
+        Ok(selected)
+    }
+
+    async fn read(&self, namespace: &str, key: &str) -> Result<OwnedValue, PortalError> {
+        let settings = self.backend.settings();
+        let value = settings
+            .get(namespace)
+            .and_then(|keys| keys.get(key))
+            .ok_or_else(|| PortalError::NotFound(format!("Requested setting {}.{} not found", namespace, key)))?;
+        Ok(value.try_clone().map_err(zbus::Error::from)?)
+    }
+
+    #[zbus(signal)]
+    async fn setting_changed(ctxt: &SignalContext<'_>, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+
+    #[zbus(property, name = "version")]
+    async fn version(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::{Array, StructureBuilder, Type};
+
+    #[test]
+    fn test_color_scheme() {
+        assert_eq!(ColorScheme::from_theme_name("Adwaita-dark"), ColorScheme::PreferDark);
+        assert_eq!(ColorScheme::from_theme_name("Greybird"), ColorScheme::PreferLight);
+        assert_eq!(ColorScheme::from_theme_name(""), ColorScheme::NoPreference);
+        assert_eq!(ColorScheme::PreferDark.code(), 1);
+    }
+
+    #[test]
+    fn test_namespace_matches() {
+        assert!(namespace_matches("org.freedesktop.appearance", APPEARANCE_NAMESPACE));
+        assert!(namespace_matches("org.freedesktop.*", APPEARANCE_NAMESPACE));
+        assert!(!namespace_matches("org.gnome.*", APPEARANCE_NAMESPACE));
+        assert!(!namespace_matches("org.freedesktop", APPEARANCE_NAMESPACE));
+    }
+
+    #[test]
+    fn test_file_chooser_options() {
+        let mut patterns = Array::new(<(u32, &str)>::signature());
+        patterns.append(Value::from((0u32, "*.png"))).unwrap();
+        patterns.append(Value::from((1u32, "image/jpeg"))).unwrap();
+        let images = Value::from(
+            StructureBuilder::new()
+                .add_field("Images")
+                .append_field(Value::Array(patterns))
+                .build(),
+        );
+        let mut filters = Array::new(images.value_signature());
+        filters.append(images.try_clone().unwrap()).unwrap();
+
+        let mut options = HashMap::new();
+        options.insert("multiple".to_string(), OwnedValue::from(true));
+        options.insert("filters".to_string(), Value::Array(filters).try_to_owned().unwrap());
+        options.insert("current_filter".to_string(), images.try_to_owned().unwrap());
+        options.insert(
+            "current_folder".to_string(),
+            Value::from(b"/home/user/Pictures\0".to_vec()).try_to_owned().unwrap(),
+        );
+
+        let options = FileChooserOptions::from_options("Open Image".to_string(), &options);
+        let expected = FileFilter {
+            name: "Images".to_string(),
+            globs: vec!["*.png".to_string()],
+            mime_types: vec!["image/jpeg".to_string()],
+        };
+        assert_eq!(options.title, "Open Image");
+        assert!(options.multiple && options.modal && !options.directory);
+        assert_eq!(options.filters, std::slice::from_ref(&expected));
+        assert_eq!(options.current_filter, Some(expected));
+        assert_eq!(options.current_folder, Some(PathBuf::from("/home/user/Pictures")));
+        assert_eq!(options.current_name, None);
+    }
+}
@@ -0,0 +1,498 @@
+//! `org.freedesktop.ScreenSaver` server
+//!
+//! Video players and presentation tools call `Inhibit` while they play so
+//! that the screen is neither locked nor blanked. [`ScreenSaverService`]
+//! keeps track of those inhibitors, forgetting them when their application
+//! leaves the bus, and decides when the screensaver activates from the
+//! idle time an [`IdleSource`] reports. Lock screens follow
+//! [`ScreenSaverEvent`]s or the `ActiveChanged` signal.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+use zbus::fdo::DBusProxy;
+use zbus::message::Header;
+use zbus::object_server::SignalContext;
+use zbus::{interface, Connection};
+
+use crate::IpcError;
+
+/// Well-known bus name of the screensaver service
+pub const SCREENSAVER_SERVICE: &str = "org.freedesktop.ScreenSaver";
+/// Object paths the interface is served at; older applications use the
+/// short one
+pub const SCREENSAVER_PATHS: [&str; 2] = ["/org/freedesktop/ScreenSaver", "/ScreenSaver"];
+
+/// Where idle time comes from, such as the X server's screen saver
+/// extension
+pub trait IdleSource: Send + Sync {
+    /// Time since the last keyboard or pointer input, `None` if unknown
+    fn idle_time(&self) -> Option<Duration>;
+
+    /// Count now as user activity, which also keeps the display from
+    /// powering down
+    fn reset(&self);
+}
+
+/// An application keeping the screensaver off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inhibitor {
+    pub cookie: u32,
+    pub application: String,
+    pub reason: String,
+    /// Unique bus name of the caller, `None` for inhibitors taken in
+    /// process
+    pub owner: Option<String>,
+}
+
+/// Change reported by [`ScreenSaverService::subscribe`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScreenSaverEvent {
+    ActiveChanged(bool),
+    /// An application asked for the screen to be locked now
+    LockRequested,
+    Inhibited(Inhibitor),
+    Uninhibited(Inhibitor),
+}
+
+/// What [`ScreenSaverService::poll`] did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    None,
+    /// Inhibited, so the idle timer was reset
+    Reset,
+    Activated,
+    /// The user came back
+    Deactivated,
+}
+
+#[derive(Default)]
+struct State {
+    last_cookie: u32,
+    inhibitors: BTreeMap<u32, Inhibitor>,
+    /// When the screensaver activated, if it is active
+    active_since: Option<Instant>,
+}
+
+struct Inner {
+    idle: Box<dyn IdleSource>,
+    state: Mutex<State>,
+    events: broadcast::Sender<ScreenSaverEvent>,
+    connection: Mutex<Option<Connection>>,
+    /// Runtime `start` ran on, for signals raised from D-Bus calls, which
+    /// zbus runs outside of Tokio
+    runtime: Mutex<Option<tokio::runtime::Handle>>,
+    /// Drops the inhibitors of applications that left the bus
+    reaper: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(reaper) = self.reaper.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            reaper.abort();
+        }
+    }
+}
+
+/// The screensaver service; clones share the same inhibitors
+#[derive(Clone)]
+pub struct ScreenSaverService {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for ScreenSaverService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("ScreenSaverService")
+            .field("inhibitors", &state.inhibitors.len())
+            .field("active", &state.active_since.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScreenSaverService {
+    pub fn new(idle: impl IdleSource + 'static) -> Self {
+        let (events, _) = broadcast::channel(32);
+        Self {
+            inner: Arc::new(Inner {
+                idle: Box::new(idle),
+                state: Mutex::new(State::default()),
+                events,
+                connection: Mutex::new(None),
+                runtime: Mutex::new(None),
+                reaper: Mutex::new(None),
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn runtime(&self) -> Option<tokio::runtime::Handle> {
+        tokio::runtime::Handle::try_current()
+            .ok()
+            .or_else(|| self.inner.runtime.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    /// Claim `org.freedesktop.ScreenSaver` on the session bus. Fails if
+    /// another screensaver is running. Must be called within a Tokio
+    /// runtime.
+    pub async fn start(&self) -> Result<(), IpcError> {
+        let mut builder = zbus::connection::Builder::session()?.name(SCREENSAVER_SERVICE)?;
+        for path in SCREENSAVER_PATHS {
+            builder = builder.serve_at(path, ScreenSaverInterface { service: self.clone() })?;
+        }
+        let connection = builder.build().await?;
+        info!("Screensaver service running as {}", SCREENSAVER_SERVICE);
+
+        let mut owner_changes = DBusProxy::new(&connection).await?.receive_name_owner_changed().await?;
+        // A weak reference, so the reaper does not keep the service alive
+        let service = Arc::downgrade(&self.inner);
+        let reaper = tokio::spawn(async move {
+            while let Some(signal) = owner_changes.next().await {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                if args.new_owner().is_some() {
+                    continue;
+                }
+                let Some(inner) = service.upgrade() else {
+                    break;
+                };
+                ScreenSaverService { inner }.release_owner(args.name().as_str());
+            }
+        });
+
+        *self.inner.runtime.lock().unwrap_or_else(|e| e.into_inner()) = Some(tokio::runtime::Handle::current());
+        *self.inner.reaper.lock().unwrap_or_else(|e| e.into_inner()) = Some(reaper);
+        *self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()) = Some(connection);
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ScreenSaverEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Keep the screensaver off until [`uninhibit`](Self::uninhibit) is
+    /// called with the returned cookie
+    pub fn inhibit(&self, application: &str, reason: &str) -> u32 {
+        self.add_inhibitor(application, reason, None)
+    }
+
+    fn add_inhibitor(&self, application: &str, reason: &str, owner: Option<String>) -> u32 {
+        let inhibitor = {
+            let mut state = self.state();
+            state.last_cookie = state.last_cookie.checked_add(1).unwrap_or(1);
+            let inhibitor = Inhibitor {
+                cookie: state.last_cookie,
+                application: application.to_string(),
+                reason: reason.to_string(),
+                owner,
+            };
+            state.inhibitors.insert(inhibitor.cookie, inhibitor.clone());
+            inhibitor
+        };
+        info!("Screensaver inhibited by {}: {}", inhibitor.application, inhibitor.reason);
+
+        let cookie = inhibitor.cookie;
+        let _ = self.inner.events.send(ScreenSaverEvent::Inhibited(inhibitor));
+        cookie
+    }
+
+    /// Release an inhibitor. Returns whether it existed.
+    pub fn uninhibit(&self, cookie: u32) -> bool {
+        let Some(inhibitor) = self.state().inhibitors.remove(&cookie) else {
+            return false;
+        };
+        debug!("Screensaver no longer inhibited by {}", inhibitor.application);
+        let _ = self.inner.events.send(ScreenSaverEvent::Uninhibited(inhibitor));
+        true
+    }
+
+    fn release_owner(&self, owner: &str) {
+        let cookies: Vec<u32> = self
+            .state()
+            .inhibitors
+            .values()
+            .filter(|inhibitor| inhibitor.owner.as_deref() == Some(owner))
+            .map(|inhibitor| inhibitor.cookie)
+            .collect();
+        for cookie in cookies {
+            self.uninhibit(cookie);
+        }
+    }
+
+    pub fn inhibitors(&self) -> Vec<Inhibitor> {
+        self.state().inhibitors.values().cloned().collect()
+    }
+
+    pub fn is_inhibited(&self) -> bool {
+        !self.state().inhibitors.is_empty()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state().active_since.is_some()
+    }
+
+    /// How long the screensaver has been active
+    pub fn active_time(&self) -> Option<Duration> {
+        self.state().active_since.map(|since| since.elapsed())
+    }
+
+    pub fn idle_time(&self) -> Option<Duration> {
+        self.inner.idle.idle_time()
+    }
+
+    /// Activate or deactivate the screensaver, telling applications when
+    /// that changed. Activating while inhibited is refused; returns
+    /// whether the screensaver is now in the requested state.
+    pub fn set_active(&self, active: bool) -> bool {
+        {
+            let mut state = self.state();
+            if state.active_since.is_some() == active {
+                return true;
+            }
+            if active && !state.inhibitors.is_empty() {
+                return false;
+            }
+            state.active_since = active.then(Instant::now);
+        }
+        info!("Screensaver {}", if active { "activated" } else { "deactivated" });
+
+        let _ = self.inner.events.send(ScreenSaverEvent::ActiveChanged(active));
+        self.emit(move |ctxt| async move { ScreenSaverInterface::active_changed(&ctxt, active).await });
+        true
+    }
+
+    /// Lock the screen now, regardless of inhibitors
+    pub fn lock(&self) {
+        let _ = self.inner.events.send(ScreenSaverEvent::LockRequested);
+        {
+            let mut state = self.state();
+            if state.active_since.is_some() {
+                return;
+            }
+            state.active_since = Some(Instant::now());
+        }
+        let _ = self.inner.events.send(ScreenSaverEvent::ActiveChanged(true));
+        self.emit(|ctxt| async move { ScreenSaverInterface::active_changed(&ctxt, true).await });
+    }
+
+    /// Count now as user activity
+    pub fn simulate_activity(&self) {
+        self.inner.idle.reset();
+        self.set_active(false);
+    }
+
+    /// Check the idle time against `timeout` and act on it: reset the idle
+    /// timer while inhibited, activate once idle for `timeout`, deactivate
+    /// when the user comes back. Call it about once a second; a `None`
+    /// timeout never activates.
+    pub fn poll(&self, timeout: Option<Duration>) -> IdleAction {
+        if self.is_inhibited() {
+            self.inner.idle.reset();
+            return IdleAction::Reset;
+        }
+        let Some(idle) = self.idle_time() else {
+            return IdleAction::None;
+        };
+
+        match self.active_time() {
+            // Input since activating
+            Some(active) if idle < active => {
+                self.set_active(false);
+                IdleAction::Deactivated
+            }
+            Some(_) => IdleAction::None,
+            None => match timeout {
+                Some(timeout) if idle >= timeout => {
+                    self.set_active(true);
+                    IdleAction::Activated
+                }
+                _ => IdleAction::None,
+            },
+        }
+    }
+
+    /// Send a signal at every path in the background once the service is
+    /// on the bus
+    fn emit<F, Fut>(&self, signal: F)
+    where
+        F: Fn(SignalContext<'static>) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = zbus::Result<()>> + Send,
+    {
+        let Some(connection) = self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+            return;
+        };
+        let Some(runtime) = self.runtime() else {
+            return;
+        };
+        runtime.spawn(async move {
+            for path in SCREENSAVER_PATHS {
+                let Ok(ctxt) = SignalContext::new(&connection, path) else {
+                    continue;
+                };
+                if let Err(e) = signal(ctxt.into_owned()).await {
+                    debug!("Failed to emit screensaver signal: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// The `org.freedesktop.ScreenSaver` interface object
+struct ScreenSaverInterface {
+    service: ScreenSaverService,
+}
+
+#[interface(name = "org.freedesktop.ScreenSaver")]
+impl ScreenSaverInterface {
+    async fn inhibit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        application_name: &str,
+        reason_for_inhibit: &str,
+    ) -> u32 {
+        let owner = header.sender().map(|sender| sender.to_string());
+        self.service.add_inhibitor(application_name, reason_for_inhibit, owner)
+    }
+
+    /// Only the application that took an inhibitor may release it
+    #[zbus(name = "UnInhibit")]
+    async fn uninhibit(&self, #[zbus(header)] header: Header<'_>, cookie: u32) {
+        let caller = header.sender().map(|sender| sender.to_string());
+        let owned = self
+            .service
+            .state()
+            .inhibitors
+            .get(&cookie)
+            .is_some_and(|inhibitor| inhibitor.owner.is_some() && inhibitor.owner == caller);
+        if owned {
+            self.service.uninhibit(cookie);
+        }
+    }
+
+    async fn get_active(&self) -> bool {
+        self.service.is_active()
+    }
+
+    async fn set_active(&self, active: bool) -> bool {
+        self.service.set_active(active)
+    }
+
+    /// Seconds the screensaver has been active, 0 if it is not
+    async fn get_active_time(&self) -> u32 {
+        seconds(self.service.active_time())
+    }
+
+    /// Seconds since the last user input
+    async fn get_session_idle_time(&self) -> u32 {
+        seconds(self.service.idle_time())
+    }
+
+    async fn simulate_user_activity(&self) {
+        self.service.simulate_activity();
+    }
+
+    async fn lock(&self) {
+        self.service.lock();
+    }
+
+    #[zbus(signal)]
+    async fn active_changed(ctxt: &SignalContext<'_>, new_value: bool) -> zbus::Result<()>;
+}
+
+fn seconds(duration: Option<Duration>) -> u32 {
+    duration.map_or(0, |duration| u32::try_from(duration.as_secs()).unwrap_or(u32::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Idle time set by the test, counting resets
+    #[derive(Clone, Default)]
+    struct FakeIdle {
+        idle: Arc<Mutex<Duration>>,
+        resets: Arc<Mutex<u32>>,
+    }
+
+    impl FakeIdle {
+        fn set(&self, idle: Duration) {
+            *self.idle.lock().unwrap() = idle;
+        }
+    }
+
+    impl IdleSource for FakeIdle {
+        fn idle_time(&self) -> Option<Duration> {
+            Some(*self.idle.lock().unwrap())
+        }
+
+        fn reset(&self) {
+            *self.idle.lock().unwrap() = Duration::ZERO;
+            *self.resets.lock().unwrap() += 1;
+        }
+    }
+
+    #[test]
+    fn test_inhibitors() {
+        let service = ScreenSaverService::new(FakeIdle::default());
+        let mut events = service.subscribe();
+        let local = service.inhibit("mpv", "Playing video");
+        let remote = service.add_inhibitor("firefox", "Playing video", Some(":1.42".to_string()));
+        assert_ne!(local, remote);
+        assert!(service.is_inhibited());
+        assert!(!service.set_active(true));
+
+        service.release_owner(":1.42");
+        assert_eq!(service.inhibitors().len(), 1);
+        assert!(service.uninhibit(local));
+        assert!(!service.uninhibit(local));
+        assert!(!service.is_inhibited());
+
+        let received: Vec<ScreenSaverEvent> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert!(matches!(&received[..], [
+            ScreenSaverEvent::Inhibited(_),
+            ScreenSaverEvent::Inhibited(_),
+            ScreenSaverEvent::Uninhibited(Inhibitor { application, .. }),
+            ScreenSaverEvent::Uninhibited(_),
+        ] if application == "firefox"));
+    }
+
+    #[test]
+    fn test_poll() {
+        let idle = FakeIdle::default();
+        let service = ScreenSaverService::new(idle.clone());
+        let timeout = Some(Duration::from_secs(300));
+
+        idle.set(Duration::from_secs(100));
+        assert_eq!(service.poll(timeout), IdleAction::None);
+        idle.set(Duration::from_secs(300));
+        assert_eq!(service.poll(None), IdleAction::None);
+        assert_eq!(service.poll(timeout), IdleAction::Activated);
+        assert!(service.is_active());
+        // Still idle: the idle time keeps growing past the active time
+        idle.set(Duration::from_secs(301));
+        assert_eq!(service.poll(timeout), IdleAction::None);
+        idle.set(Duration::ZERO);
+        assert_eq!(service.poll(timeout), IdleAction::Deactivated);
+        assert!(!service.is_active());
+
+        let cookie = service.inhibit("vlc", "Playing video");
+        idle.set(Duration::from_secs(600));
+        assert_eq!(service.poll(timeout), IdleAction::Reset);
+        assert_eq!(service.idle_time(), Some(Duration::ZERO));
+        assert_eq!(*idle.resets.lock().unwrap(), 1);
+        assert!(!service.is_active());
+
+        service.uninhibit(cookie);
+        service.lock();
+        assert!(service.is_active());
+    }
+}
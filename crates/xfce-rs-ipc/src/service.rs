@@ -0,0 +1,353 @@
+//! The `org.xfce_rs.Service` D-Bus service
+//!
+//! Messages arrive as JSON-encoded [`IpcMessage`]s through the `Send`
+//! method and are handed to every registered handler. They are then
+//! published on the service's [`EventBus`]: clients `Subscribe` to topic
+//! patterns and get the matching messages as `Event` signals addressed to
+//! them alone, each through its own bounded queue.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use futures_util::StreamExt;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+use zbus::fdo::DBusProxy;
+use zbus::message::Header;
+use zbus::names::BusName;
+use zbus::object_server::SignalContext;
+use zbus::{interface, Connection, DBusError};
+
+use crate::events::{EventBus, Subscription, DEFAULT_QUEUE_CAPACITY};
+use crate::{IpcError, IpcMessage, MessageHandler};
+
+/// Well-known bus name of the IPC service
+pub const SERVICE_NAME: &str = "org.xfce_rs.Service";
+/// Object path the service interface is served at
+pub const SERVICE_PATH: &str = "/org/xfce_rs/Service";
+
+/// Errors reported to D-Bus callers
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.xfce_rs.Service.Error")]
+pub enum ServiceError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    InvalidMessage(String),
+    HandlerFailed(String),
+    UnknownSubscription(String),
+}
+
+type Handlers = Arc<RwLock<Vec<MessageHandler>>>;
+
+/// Hand `message` to every handler, returning how many ran. All handlers
+/// run even if one fails; the first failure is reported.
+pub(crate) fn dispatch(handlers: &Handlers, message: &IpcMessage) -> Result<usize, IpcError> {
+    let handlers = handlers.read().unwrap_or_else(|e| e.into_inner());
+    let mut first_error = None;
+    for handler in handlers.iter() {
+        if let Err(e) = handler(message.clone()) {
+            first_error.get_or_insert(e);
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(handlers.len()),
+    }
+}
+
+/// A D-Bus client's subscription, forwarded by its own task
+struct RemoteSubscription {
+    /// Unique bus name of the subscriber
+    owner: String,
+    task: JoinHandle<()>,
+}
+
+impl Drop for RemoteSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+type RemoteSubscriptions = Arc<Mutex<HashMap<u64, RemoteSubscription>>>;
+
+/// The `org.xfce_rs.Service` interface object
+struct ServiceInterface {
+    handlers: Handlers,
+    events: EventBus,
+    remote: RemoteSubscriptions,
+    /// zbus runs methods on its own executor; forwarders need Tokio
+    runtime: tokio::runtime::Handle,
+}
+
+#[interface(name = "org.xfce_rs.Service")]
+impl ServiceInterface {
+    /// Deliver a JSON-encoded message to the service's handlers and
+    /// subscribers. Subscribers get it even if a handler fails.
+    async fn send(&self, message: &str) -> Result<String, ServiceError> {
+        let message: IpcMessage =
+            serde_json::from_str(message).map_err(|e| ServiceError::InvalidMessage(e.to_string()))?;
+
+        let delivered = dispatch(&self.handlers, &message);
+        self.events.publish(&message);
+        let delivered = delivered.map_err(|e| ServiceError::HandlerFailed(e.to_string()))?;
+        Ok(format!("Delivered to {} handlers", delivered))
+    }
+
+    async fn get_status(&self) -> String {
+        let handlers = self.handlers.read().unwrap_or_else(|e| e.into_inner()).len();
+        format!(
+            "XFCE.rs IPC Service running with {} handlers and {} subscribers",
+            handlers,
+            self.events.subscriber_count()
+        )
+    }
+
+    /// Receive messages matching `patterns` as `Event` signals, keeping at
+    /// most `capacity` waiting (0 for the default). Returns the id
+    /// `Event` signals carry.
+    async fn subscribe(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        patterns: Vec<String>,
+        capacity: u32,
+    ) -> Result<u64, ServiceError> {
+        let owner = header
+            .sender()
+            .ok_or_else(|| ServiceError::InvalidMessage("Subscription without a sender".to_string()))?
+            .to_owned();
+        let capacity = match capacity {
+            0 => DEFAULT_QUEUE_CAPACITY,
+            capacity => capacity as usize,
+        };
+
+        let subscription = self.events.subscribe(patterns, capacity);
+        let id = subscription.id();
+        let ctxt = SignalContext::new(connection, SERVICE_PATH)?
+            .set_destination(BusName::Unique(owner.clone()))
+            .into_owned();
+        let task = self.runtime.spawn(forward(subscription, ctxt));
+
+        debug!("{} subscribed as {}", owner, id);
+        self.remote.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            id,
+            RemoteSubscription {
+                owner: owner.to_string(),
+                task,
+            },
+        );
+        Ok(id)
+    }
+
+    async fn unsubscribe(&self, #[zbus(header)] header: Header<'_>, id: u64) -> Result<(), ServiceError> {
+        let sender = header.sender().map(|sender| sender.as_str()).unwrap_or_default();
+        let mut remote = self.remote.lock().unwrap_or_else(|e| e.into_inner());
+        match remote.get(&id) {
+            Some(subscription) if subscription.owner == sender => {
+                remote.remove(&id);
+                Ok(())
+            }
+            _ => Err(ServiceError::UnknownSubscription(format!("No subscription {} for {}", id, sender))),
+        }
+    }
+
+    /// A message for subscription `subscription`; `dropped` counts the
+    /// messages discarded before it because the subscriber fell behind
+    #[zbus(signal)]
+    async fn event(
+        ctxt: &SignalContext<'_>,
+        subscription: u64,
+        topic: &str,
+        message: &str,
+        dropped: u64,
+    ) -> zbus::Result<()>;
+}
+
+/// Send a subscription's messages to its D-Bus client as they come
+async fn forward(mut subscription: Subscription, ctxt: SignalContext<'static>) {
+    while let Some(message) = subscription.recv().await {
+        let json = match serde_json::to_string(&message) {
+            Ok(json) => json,
+            Err(e) => {
+                debug!("Cannot encode IPC message: {}", e);
+                continue;
+            }
+        };
+        let dropped = subscription.take_dropped();
+        if let Err(e) = ServiceInterface::event(&ctxt, subscription.id(), &message.topic(), &json, dropped).await {
+            debug!("Failed to emit IPC event: {}", e);
+        }
+    }
+}
+
+/// Main IPC service for XFCE.rs
+pub struct XfceIpcService {
+    handlers: Handlers,
+    events: EventBus,
+    remote: RemoteSubscriptions,
+    connection: Mutex<Option<Connection>>,
+    /// Drops the subscriptions of clients that left the bus
+    reaper: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl std::fmt::Debug for XfceIpcService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XfceIpcService")
+            .field("handlers", &self.handlers.read().map(|handlers| handlers.len()).unwrap_or_default())
+            .field("subscribers", &self.events.subscriber_count())
+            .field("running", &self.connection().is_some())
+            .finish()
+    }
+}
+
+impl XfceIpcService {
+    pub fn new() -> Self {
+        Self {
+            handlers: Arc::default(),
+            events: EventBus::new(),
+            remote: Arc::default(),
+            connection: Mutex::new(None),
+            reaper: Mutex::new(None),
+        }
+    }
+
+    /// Add a message handler
+    pub async fn add_handler(&self, handler: MessageHandler) {
+        self.handlers.write().unwrap_or_else(|e| e.into_inner()).push(handler);
+    }
+
+    /// Receive messages published through this service whose topic
+    /// matches one of `patterns`, such as `config.*` or `window.focus`
+    pub fn subscribe<S: Into<String>>(&self, patterns: impl IntoIterator<Item = S>) -> Subscription {
+        self.events.subscribe(patterns, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// The bus subscriptions are served from, for custom queue sizes
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Claim `org.xfce_rs.Service` on the session bus. The service keeps
+    /// answering calls until it is dropped. Must be called within a Tokio
+    /// runtime.
+    pub async fn start(&self) -> Result<(), IpcError> {
+        if self.connection().is_some() {
+            return Ok(());
+        }
+
+        let interface = ServiceInterface {
+            handlers: self.handlers.clone(),
+            events: self.events.clone(),
+            remote: self.remote.clone(),
+            runtime: tokio::runtime::Handle::current(),
+        };
+        let connection = zbus::connection::Builder::session()?
+            .name(SERVICE_NAME)?
+            .serve_at(SERVICE_PATH, interface)?
+            .build()
+            .await?;
+        info!("XFCE.rs IPC service running as {}", SERVICE_NAME);
+
+        let mut owner_changes = DBusProxy::new(&connection).await?.receive_name_owner_changed().await?;
+        let remote = self.remote.clone();
+        let reaper = tokio::spawn(async move {
+            while let Some(signal) = owner_changes.next().await {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                if args.new_owner().is_none() {
+                    let name = args.name().as_str();
+                    remote
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .retain(|_, subscription| subscription.owner != name);
+                }
+            }
+        });
+
+        *self.reaper.lock().unwrap_or_else(|e| e.into_inner()) = Some(reaper);
+        *self.connection.lock().unwrap_or_else(|e| e.into_inner()) = Some(connection);
+        Ok(())
+    }
+
+    /// Deliver a message raised inside this process, as if a client had
+    /// sent it. Subscribers get it even if a handler fails.
+    pub async fn publish(&self, message: IpcMessage) -> Result<(), IpcError> {
+        let delivered = dispatch(&self.handlers, &message);
+        self.events.publish(&message);
+        delivered.map(|_| ())
+    }
+
+    fn connection(&self) -> Option<Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl Drop for XfceIpcService {
+    fn drop(&mut self) {
+        if let Some(reaper) = self.reaper.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            reaper.abort();
+        }
+    }
+}
+
+impl Default for XfceIpcService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_publish_reaches_every_handler() {
+        let service = XfceIpcService::new();
+        let mut notifications = service.subscribe(["notification"]);
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        for fail in [true, false] {
+            let calls = calls.clone();
+            service
+                .add_handler(Box::new(move |_| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    if fail {
+                        Err(IpcError::MethodCallFailed("rejected".to_string()))
+                    } else {
+                        Ok(())
+                    }
+                }))
+                .await;
+        }
+
+        let message = IpcMessage::DesktopNotification {
+            title: "Battery low".to_string(),
+            body: "10% remaining".to_string(),
+            urgency: "critical".to_string(),
+        };
+        assert!(matches!(service.publish(message).await, Err(IpcError::MethodCallFailed(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        // A failing handler doesn't keep it from subscribers
+        assert!(matches!(notifications.recv().await, Some(IpcMessage::DesktopNotification { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscribers() {
+        let service = XfceIpcService::new();
+        let mut theme = service.subscribe(["config.xsettings"]);
+        let mut windows = service.subscribe(["window.*"]);
+
+        let message = IpcMessage::ConfigChange {
+            channel: "xsettings".to_string(),
+            property: "/Net/ThemeName".to_string(),
+            value: "Adwaita-dark".into(),
+        };
+        service.publish(message).await.unwrap();
+
+        assert!(matches!(theme.recv().await, Some(IpcMessage::ConfigChange { .. })));
+        assert!(windows.try_recv().is_none());
+    }
+}
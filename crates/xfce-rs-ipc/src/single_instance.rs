@@ -0,0 +1,177 @@
+//! Keeping applications to a single running instance
+//!
+//! The first instance of an application owns its id as a bus name and
+//! serves `org.freedesktop.Application` at the matching object path, like
+//! GApplication does. Later launches forward their arguments to it through
+//! `Activate` or `Open` and exit instead of opening another window.
+
+use std::collections::HashMap;
+
+use tokio::sync::mpsc;
+use tracing::info;
+use zbus::fdo::{RequestNameFlags, RequestNameReply};
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{interface, proxy, Connection};
+
+use crate::IpcError;
+
+/// Why the running instance was activated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Activation {
+    /// Launched again without arguments; raise the window
+    Activate { startup_id: Option<String> },
+    /// Launched again with URIs or paths to open
+    Open { uris: Vec<String>, startup_id: Option<String> },
+}
+
+/// `org.freedesktop.Application` as seen from another instance
+#[proxy(interface = "org.freedesktop.Application")]
+trait Application {
+    fn activate(&self, platform_data: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    fn open(&self, uris: &[&str], platform_data: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+}
+
+/// The `org.freedesktop.Application` interface object
+struct ApplicationInterface {
+    activations: mpsc::UnboundedSender<Activation>,
+}
+
+#[interface(name = "org.freedesktop.Application")]
+impl ApplicationInterface {
+    async fn activate(&self, platform_data: HashMap<String, OwnedValue>) {
+        let _ = self.activations.send(Activation::Activate {
+            startup_id: startup_id(&platform_data),
+        });
+    }
+
+    async fn open(&self, uris: Vec<String>, platform_data: HashMap<String, OwnedValue>) {
+        let _ = self.activations.send(Activation::Open {
+            uris,
+            startup_id: startup_id(&platform_data),
+        });
+    }
+
+    async fn activate_action(&self, _action_name: &str, _parameter: Vec<OwnedValue>, platform_data: HashMap<String, OwnedValue>) {
+        let _ = self.activations.send(Activation::Activate {
+            startup_id: startup_id(&platform_data),
+        });
+    }
+}
+
+fn startup_id(platform_data: &HashMap<String, OwnedValue>) -> Option<String> {
+    ["activation-token", "desktop-startup-id"].iter().find_map(|key| {
+        match &**platform_data.get(*key)? {
+            Value::Str(id) => Some(id.to_string()),
+            _ => None,
+        }
+    })
+}
+
+/// The running instance, receiving activations from later launches
+#[derive(Debug)]
+pub struct PrimaryInstance {
+    _connection: Connection,
+    activations: mpsc::UnboundedReceiver<Activation>,
+}
+
+impl PrimaryInstance {
+    /// Wait for the next launch; `None` once the bus connection is gone
+    pub async fn next_activation(&mut self) -> Option<Activation> {
+        self.activations.recv().await
+    }
+
+    /// An activation that already arrived, without waiting
+    pub fn try_next_activation(&mut self) -> Option<Activation> {
+        self.activations.try_recv().ok()
+    }
+}
+
+/// Outcome of [`claim_instance`]
+#[derive(Debug)]
+pub enum Instance {
+    Primary(PrimaryInstance),
+    /// Another instance was running and has been handed the arguments
+    Secondary,
+}
+
+/// Object path for an application id, per the D-Bus activation spec:
+/// `org.xfce_rs.Navigator` is served at `/org/xfce_rs/Navigator`
+pub fn object_path(app_id: &str) -> String {
+    format!("/{}", app_id.replace('.', "/").replace('-', "_"))
+}
+
+/// Become the instance of `app_id`, or forward `uris` to the one already
+/// running. Must be called within a Tokio runtime.
+pub async fn claim_instance(app_id: &str, uris: &[String]) -> Result<Instance, IpcError> {
+    let path = object_path(app_id);
+    let (sender, activations) = mpsc::unbounded_channel();
+    let connection = zbus::connection::Builder::session()?
+        .serve_at(path.as_str(), ApplicationInterface { activations: sender })?
+        .build()
+        .await?;
+
+    match connection.request_name_with_flags(app_id, RequestNameFlags::DoNotQueue.into()).await {
+        Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner) => {
+            return Ok(Instance::Primary(PrimaryInstance {
+                _connection: connection,
+                activations,
+            }));
+        }
+        Ok(_) | Err(zbus::Error::NameTaken) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let running = ApplicationProxy::builder(&connection)
+        .destination(app_id.to_string())?
+        .path(path)?
+        .build()
+        .await?;
+
+    let mut platform_data = HashMap::new();
+    let token = std::env::var("XDG_ACTIVATION_TOKEN").or_else(|_| std::env::var("DESKTOP_STARTUP_ID"));
+    if let Ok(token) = &token {
+        platform_data.insert("activation-token", Value::from(token.as_str()));
+        platform_data.insert("desktop-startup-id", Value::from(token.as_str()));
+    }
+
+    if uris.is_empty() {
+        running.activate(platform_data).await?;
+    } else {
+        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+        running.open(&uris, platform_data).await?;
+    }
+    info!("{} is already running, handed over to it", app_id);
+    Ok(Instance::Secondary)
+}
+
+/// Like [`claim_instance`], but exits the process when another instance
+/// took over
+pub async fn ensure_single_instance(app_id: &str, uris: &[String]) -> Result<PrimaryInstance, IpcError> {
+    match claim_instance(app_id, uris).await? {
+        Instance::Primary(instance) => Ok(instance),
+        Instance::Secondary => std::process::exit(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_path() {
+        assert_eq!(object_path("org.xfce_rs.Navigator"), "/org/xfce_rs/Navigator");
+        assert_eq!(object_path("org.xfce-rs.audio-popup"), "/org/xfce_rs/audio_popup");
+    }
+
+    #[test]
+    fn test_startup_id() {
+        let mut platform_data = HashMap::new();
+        assert_eq!(startup_id(&platform_data), None);
+        platform_data.insert(
+            "desktop-startup-id".to_string(),
+            OwnedValue::try_from(Value::from("xfce-rs-panel-42_TIME1234")).unwrap(),
+        );
+        assert_eq!(startup_id(&platform_data).as_deref(), Some("xfce-rs-panel-42_TIME1234"));
+    }
+}
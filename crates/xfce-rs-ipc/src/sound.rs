@@ -0,0 +1,157 @@
+//! Event sounds over D-Bus
+//!
+//! `xfce-rs-audio --daemon` serves `org.xfce_rs.EventSounds`, so the window
+//! manager, the notification daemon and the file manager can play theme
+//! sounds such as "bell" or "complete" by their freedesktop event id
+//! without linking an audio library. [`play_event_sound`] is the client
+//! side; [`EventSoundService`] serves a [`SoundPlayer`].
+
+use std::sync::{Arc, Mutex};
+
+use tracing::info;
+use zbus::{interface, proxy, Connection};
+
+use crate::IpcError;
+
+/// Well-known bus name of the event sound service
+pub const EVENT_SOUNDS_SERVICE: &str = "org.xfce_rs.EventSounds";
+pub const EVENT_SOUNDS_PATH: &str = "/org/xfce_rs/EventSounds";
+
+/// Finds and plays the sound of an event
+pub trait SoundPlayer: Send + Sync {
+    /// Start playing the theme sound for `event_id`. Returns false if event
+    /// sounds are turned off or no theme has a sound for it.
+    fn play(&self, event_id: &str) -> bool;
+}
+
+/// `org.xfce_rs.EventSounds`
+#[proxy(
+    interface = "org.xfce_rs.EventSounds",
+    default_service = "org.xfce_rs.EventSounds",
+    default_path = "/org/xfce_rs/EventSounds"
+)]
+pub trait EventSounds {
+    fn play_event_sound(&self, event_id: &str) -> zbus::Result<bool>;
+}
+
+/// Ask the event sound service to play `event_id`, e.g. "bell" or
+/// "dialog-warning". Returns whether a sound is playing.
+pub async fn play_event_sound(connection: &Connection, event_id: &str) -> Result<bool, IpcError> {
+    let proxy = EventSoundsProxy::new(connection).await?;
+    Ok(proxy.play_event_sound(event_id).await?)
+}
+
+/// Whether the event sound service is running on the bus of `connection`
+pub async fn event_sounds_available(connection: &Connection) -> bool {
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(connection).await else {
+        return false;
+    };
+    let Ok(name) = zbus::names::BusName::try_from(EVENT_SOUNDS_SERVICE) else {
+        return false;
+    };
+    dbus.name_has_owner(name).await.unwrap_or(false)
+}
+
+/// Event ids are lowercase words joined by dashes, like
+/// "message-new-instant"; anything else could reach outside the sound
+/// theme directories
+pub fn is_valid_event_id(event_id: &str) -> bool {
+    !event_id.is_empty()
+        && event_id.len() <= 128
+        && event_id
+            .split('-')
+            .all(|word| !word.is_empty() && word.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_'))
+}
+
+struct Inner {
+    player: Box<dyn SoundPlayer>,
+    connection: Mutex<Option<Connection>>,
+}
+
+/// The event sound service; clones share the same player
+#[derive(Clone)]
+pub struct EventSoundService {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for EventSoundService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSoundService").finish_non_exhaustive()
+    }
+}
+
+impl EventSoundService {
+    pub fn new(player: impl SoundPlayer + 'static) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                player: Box::new(player),
+                connection: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Claim `org.xfce_rs.EventSounds` on the session bus
+    pub async fn start(&self) -> Result<(), IpcError> {
+        let connection = zbus::connection::Builder::session()?
+            .name(EVENT_SOUNDS_SERVICE)?
+            .serve_at(EVENT_SOUNDS_PATH, EventSoundsInterface { service: self.clone() })?
+            .build()
+            .await?;
+        info!("Event sound service running as {}", EVENT_SOUNDS_SERVICE);
+
+        *self.inner.connection.lock().unwrap_or_else(|e| e.into_inner()) = Some(connection);
+        Ok(())
+    }
+
+    /// Play `event_id` in process
+    pub fn play(&self, event_id: &str) -> bool {
+        is_valid_event_id(event_id) && self.inner.player.play(event_id)
+    }
+}
+
+struct EventSoundsInterface {
+    service: EventSoundService,
+}
+
+#[interface(name = "org.xfce_rs.EventSounds")]
+impl EventSoundsInterface {
+    async fn play_event_sound(&self, event_id: &str) -> bool {
+        self.service.play(event_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl SoundPlayer for Arc<Recorder> {
+        fn play(&self, event_id: &str) -> bool {
+            self.0.lock().unwrap().push(event_id.to_string());
+            true
+        }
+    }
+
+    #[test]
+    fn test_event_ids() {
+        assert!(is_valid_event_id("bell"));
+        assert!(is_valid_event_id("message-new-instant"));
+        assert!(is_valid_event_id("audio-channel-front-left"));
+        assert!(!is_valid_event_id(""));
+        assert!(!is_valid_event_id("../../../etc/passwd"));
+        assert!(!is_valid_event_id("bell/../x"));
+        assert!(!is_valid_event_id("dialog--warning"));
+        assert!(!is_valid_event_id("Bell"));
+    }
+
+    #[test]
+    fn test_play_checks_the_id() {
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        let service = EventSoundService::new(recorder.clone());
+
+        assert!(service.play("complete"));
+        assert!(!service.play("../complete"));
+        assert_eq!(*recorder.0.lock().unwrap(), vec!["complete".to_string()]);
+    }
+}
@@ -0,0 +1,1073 @@
+//! System tray through StatusNotifierItem
+//!
+//! Applications such as nm-applet, Discord or Steam export their tray icon
+//! as an `org.kde.StatusNotifierItem` and register it with the
+//! `org.kde.StatusNotifierWatcher`. [`TrayModel`] runs that watcher when
+//! the session has none, registers itself as a host and follows every item,
+//! including its `com.canonical.dbusmenu` context menu.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures_util::StreamExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::debug;
+use zbus::fdo::{DBusProxy, PropertiesProxy};
+use zbus::message::Header;
+use zbus::names::{BusName, InterfaceName};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::{interface, proxy, Connection, MatchRule, MessageStream, SignalContext};
+
+use crate::mpris::{flag, text, unwrap_variant};
+use crate::IpcError;
+
+/// Well-known bus name of the watcher
+pub const WATCHER_SERVICE: &str = "org.kde.StatusNotifierWatcher";
+pub const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+pub const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+/// Object path of items registered by bus name alone
+pub const ITEM_PATH: &str = "/StatusNotifierItem";
+/// Hosts own `org.kde.StatusNotifierHost-<pid>-<n>`
+pub const HOST_PREFIX: &str = "org.kde.StatusNotifierHost-";
+pub const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+
+/// Numbers the hosts of this process
+static HOST_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// `org.kde.StatusNotifierWatcher` as seen by hosts and items
+#[proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher"
+)]
+pub trait StatusNotifierWatcher {
+    fn register_status_notifier_item(&self, service: &str) -> zbus::Result<()>;
+
+    fn register_status_notifier_host(&self, service: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn status_notifier_item_registered(&self, service: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn status_notifier_item_unregistered(&self, service: String) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn registered_status_notifier_items(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn is_status_notifier_host_registered(&self) -> zbus::Result<bool>;
+}
+
+/// `org.kde.StatusNotifierItem`
+#[proxy(interface = "org.kde.StatusNotifierItem", default_path = "/StatusNotifierItem")]
+pub trait StatusNotifierItem {
+    /// Primary click at screen coordinates
+    fn activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+
+    /// Middle click
+    fn secondary_activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+
+    /// For items without a dbusmenu that draw their own menu
+    fn context_menu(&self, x: i32, y: i32) -> zbus::Result<()>;
+
+    /// `orientation` is "vertical" or "horizontal"
+    fn scroll(&self, delta: i32, orientation: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn new_title(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn new_icon(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn new_status(&self, status: String) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn status(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn menu(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// `(id, properties, children)` of a dbusmenu node; children are variants
+/// holding the same structure
+pub type MenuLayout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+/// `com.canonical.dbusmenu`
+#[proxy(interface = "com.canonical.dbusmenu")]
+pub trait DBusMenu {
+    /// `recursion_depth` -1 fetches the whole tree; empty
+    /// `property_names` fetches all properties
+    fn get_layout(&self, parent_id: i32, recursion_depth: i32, property_names: &[&str]) -> zbus::Result<(u32, MenuLayout)>;
+
+    /// `event_id` is "clicked", "hovered", "opened" or "closed"
+    fn event(&self, id: i32, event_id: &str, data: &Value<'_>, timestamp: u32) -> zbus::Result<()>;
+
+    /// Returns whether the layout of `id` needs fetching again
+    fn about_to_show(&self, id: i32) -> zbus::Result<bool>;
+
+    #[zbus(signal)]
+    fn layout_updated(&self, revision: u32, parent: i32) -> zbus::Result<()>;
+}
+
+/// The watcher served when the session has none
+#[derive(Default)]
+struct Watcher {
+    state: Arc<Mutex<WatcherState>>,
+}
+
+#[derive(Default)]
+struct WatcherState {
+    items: Vec<String>,
+    hosts: Vec<String>,
+}
+
+impl Watcher {
+    fn state(&self) -> std::sync::MutexGuard<'_, WatcherState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[interface(name = "org.kde.StatusNotifierWatcher")]
+impl Watcher {
+    /// `service` is a bus name, or an object path on the caller's
+    /// connection as libappindicator sends it
+    async fn register_status_notifier_item(
+        &self,
+        service: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        let sender = header.sender().map(|sender| sender.to_string()).unwrap_or_default();
+        let address = item_address(service, &sender);
+        let added = {
+            let mut state = self.state();
+            let added = !state.items.contains(&address);
+            if added {
+                state.items.push(address.clone());
+            }
+            added
+        };
+
+        if added {
+            debug!("Tray item registered: {}", address);
+            Self::status_notifier_item_registered(&ctxt, &address).await?;
+            self.registered_status_notifier_items_changed(&ctxt).await?;
+        }
+        Ok(())
+    }
+
+    async fn register_status_notifier_host(
+        &self,
+        service: &str,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        let first = {
+            let mut state = self.state();
+            if !state.hosts.iter().any(|host| host == service) {
+                state.hosts.push(service.to_string());
+            }
+            state.hosts.len() == 1
+        };
+
+        if first {
+            Self::status_notifier_host_registered(&ctxt).await?;
+            self.is_status_notifier_host_registered_changed(&ctxt).await?;
+        }
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn registered_status_notifier_items(&self) -> Vec<String> {
+        self.state().items.clone()
+    }
+
+    #[zbus(property)]
+    async fn is_status_notifier_host_registered(&self) -> bool {
+        !self.state().hosts.is_empty()
+    }
+
+    #[zbus(property)]
+    async fn protocol_version(&self) -> i32 {
+        0
+    }
+
+    #[zbus(signal)]
+    async fn status_notifier_item_registered(ctxt: &SignalContext<'_>, service: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn status_notifier_item_unregistered(ctxt: &SignalContext<'_>, service: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn status_notifier_host_registered(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn status_notifier_host_unregistered(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+}
+
+/// Serve the watcher and queue for its name, taking over whenever the
+/// current watcher leaves. The returned task drops items and hosts whose
+/// bus names vanish.
+async fn serve_watcher(connection: &Connection) -> Result<JoinHandle<()>, IpcError> {
+    let watcher = Watcher::default();
+    let state = watcher.state.clone();
+    connection.object_server().at(WATCHER_PATH, watcher).await?;
+    connection
+        .request_name_with_flags(WATCHER_SERVICE, zbus::fdo::RequestNameFlags::AllowReplacement.into())
+        .await?;
+
+    let dbus = DBusProxy::new(connection).await?;
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+    let ctxt = SignalContext::new(connection, WATCHER_PATH)?.into_owned();
+
+    Ok(tokio::spawn(async move {
+        while let Some(signal) = owner_changes.next().await {
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+            if args.new_owner().is_some() {
+                continue;
+            }
+            let name = args.name().as_str();
+
+            let (gone, no_hosts) = {
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                let gone: Vec<String> = state
+                    .items
+                    .iter()
+                    .filter(|address| parse_address(address).0 == name)
+                    .cloned()
+                    .collect();
+                state.items.retain(|address| !gone.contains(address));
+                let had_hosts = !state.hosts.is_empty();
+                state.hosts.retain(|host| host != name);
+                (gone, had_hosts && state.hosts.is_empty())
+            };
+
+            for address in &gone {
+                debug!("Tray item unregistered: {}", address);
+                let _ = Watcher::status_notifier_item_unregistered(&ctxt, address).await;
+            }
+            if no_hosts {
+                let _ = Watcher::status_notifier_host_unregistered(&ctxt).await;
+            }
+        }
+    }))
+}
+
+/// Address of an item the way the watcher lists it: the bus name, followed
+/// by the object path when it is not [`ITEM_PATH`]
+fn item_address(service: &str, sender: &str) -> String {
+    if service.starts_with('/') {
+        format!("{}{}", sender, service)
+    } else {
+        service.to_string()
+    }
+}
+
+/// Split an item address into bus name and object path
+pub fn parse_address(address: &str) -> (&str, &str) {
+    match address.find('/') {
+        Some(index) => address.split_at(index),
+        None => (address, ITEM_PATH),
+    }
+}
+
+/// Value of the item `Status` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemStatus {
+    /// Nothing going on; hosts may hide the item
+    Passive,
+    #[default]
+    Active,
+    NeedsAttention,
+}
+
+impl ItemStatus {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "Passive" => ItemStatus::Passive,
+            "NeedsAttention" => ItemStatus::NeedsAttention,
+            _ => ItemStatus::Active,
+        }
+    }
+}
+
+/// Value of the item `Category` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemCategory {
+    #[default]
+    ApplicationStatus,
+    Communications,
+    SystemServices,
+    Hardware,
+}
+
+impl ItemCategory {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "Communications" => ItemCategory::Communications,
+            "SystemServices" => ItemCategory::SystemServices,
+            "Hardware" => ItemCategory::Hardware,
+            _ => ItemCategory::ApplicationStatus,
+        }
+    }
+}
+
+/// An icon image sent by an item, converted to RGBA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixmap {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Pixmap {
+    /// Convert the ARGB32 data in network byte order items send
+    pub fn from_argb(width: i32, height: i32, argb: &[u8]) -> Option<Self> {
+        let width = u32::try_from(width).ok().filter(|width| *width > 0)?;
+        let height = u32::try_from(height).ok().filter(|height| *height > 0)?;
+        if argb.len() != width as usize * height as usize * 4 {
+            return None;
+        }
+
+        let rgba = argb
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[1], pixel[2], pixel[3], pixel[0]])
+            .collect();
+        Some(Self { width, height, rgba })
+    }
+
+    /// The smallest of `pixmaps` at least `size` pixels tall, or else the
+    /// largest one
+    pub fn best(pixmaps: &[Pixmap], size: u32) -> Option<&Pixmap> {
+        pixmaps
+            .iter()
+            .filter(|pixmap| pixmap.height >= size)
+            .min_by_key(|pixmap| pixmap.height)
+            .or_else(|| pixmaps.iter().max_by_key(|pixmap| pixmap.height))
+    }
+}
+
+/// Parse an `a(iiay)` icon pixmap list
+fn pixmaps(value: &Value<'_>) -> Vec<Pixmap> {
+    let Value::Array(array) = unwrap_variant(value) else {
+        return Vec::new();
+    };
+
+    array
+        .inner()
+        .iter()
+        .filter_map(|pixmap| match unwrap_variant(pixmap) {
+            Value::Structure(pixmap) => match pixmap.fields() {
+                [Value::I32(width), Value::I32(height), Value::Array(data)] => {
+                    let data: Vec<u8> = data
+                        .inner()
+                        .iter()
+                        .filter_map(|byte| match byte {
+                            Value::U8(byte) => Some(*byte),
+                            _ => None,
+                        })
+                        .collect();
+                    Pixmap::from_argb(*width, *height, &data)
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ToolTip {
+    pub icon_name: Option<String>,
+    pub icon_pixmaps: Vec<Pixmap>,
+    pub title: String,
+    /// May contain a subset of HTML markup
+    pub description: String,
+}
+
+impl ToolTip {
+    /// Parse the `(sa(iiay)ss)` tooltip structure
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        let Value::Structure(tool_tip) = unwrap_variant(value) else {
+            return None;
+        };
+        match tool_tip.fields() {
+            [icon_name, icon_pixmaps, title, description] => Some(Self {
+                icon_name: non_empty(icon_name),
+                icon_pixmaps: pixmaps(icon_pixmaps),
+                title: text(title).unwrap_or_default(),
+                description: text(description).unwrap_or_default(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Items send empty strings and placeholder paths for absent values
+fn non_empty(value: &Value<'_>) -> Option<String> {
+    text(value).filter(|text| !text.is_empty() && text != "/" && text != "/NO_DBUSMENU")
+}
+
+/// What is known about one tray item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrayItem {
+    /// Address the item registered under, e.g.
+    /// `:1.42/org/ayatana/NotificationItem/nm_applet`
+    pub address: String,
+    pub bus_name: String,
+    pub path: String,
+    pub id: String,
+    pub title: String,
+    pub category: ItemCategory,
+    pub status: ItemStatus,
+    pub icon_name: Option<String>,
+    pub icon_pixmaps: Vec<Pixmap>,
+    pub attention_icon_name: Option<String>,
+    pub attention_pixmaps: Vec<Pixmap>,
+    pub overlay_icon_name: Option<String>,
+    /// Extra directory to look icon names up in
+    pub icon_theme_path: Option<String>,
+    pub tool_tip: Option<ToolTip>,
+    /// Object path of the item's dbusmenu
+    pub menu: Option<String>,
+    /// The item only offers a menu; clicks should open it instead of
+    /// calling `Activate`
+    pub item_is_menu: bool,
+}
+
+impl TrayItem {
+    fn new(address: &str) -> Self {
+        let (bus_name, path) = parse_address(address);
+        Self {
+            address: address.to_string(),
+            bus_name: bus_name.to_string(),
+            path: path.to_string(),
+            id: String::new(),
+            title: String::new(),
+            category: ItemCategory::default(),
+            status: ItemStatus::default(),
+            icon_name: None,
+            icon_pixmaps: Vec::new(),
+            attention_icon_name: None,
+            attention_pixmaps: Vec::new(),
+            overlay_icon_name: None,
+            icon_theme_path: None,
+            tool_tip: None,
+            menu: None,
+            item_is_menu: false,
+        }
+    }
+
+    fn apply<'a>(&mut self, properties: impl IntoIterator<Item = (&'a str, &'a Value<'a>)>) {
+        for (name, value) in properties {
+            match name {
+                "Id" => self.id = text(value).unwrap_or_default(),
+                "Title" => self.title = text(value).unwrap_or_default(),
+                "Category" => self.category = ItemCategory::from_name(&text(value).unwrap_or_default()),
+                "Status" => self.status = ItemStatus::from_name(&text(value).unwrap_or_default()),
+                "IconName" => self.icon_name = non_empty(value),
+                "IconPixmap" => self.icon_pixmaps = pixmaps(value),
+                "AttentionIconName" => self.attention_icon_name = non_empty(value),
+                "AttentionIconPixmap" => self.attention_pixmaps = pixmaps(value),
+                "OverlayIconName" => self.overlay_icon_name = non_empty(value),
+                "IconThemePath" => self.icon_theme_path = non_empty(value),
+                "ToolTip" => self.tool_tip = ToolTip::from_value(value),
+                "Menu" => self.menu = non_empty(value),
+                "ItemIsMenu" => self.item_is_menu = flag(value).unwrap_or(false),
+                _ => {}
+            }
+        }
+    }
+
+    /// Icon to draw now: the attention icon while the item needs
+    /// attention and has one, the normal icon otherwise
+    pub fn current_icon(&self) -> (Option<&str>, &[Pixmap]) {
+        let wants_attention = self.status == ItemStatus::NeedsAttention
+            && (self.attention_icon_name.is_some() || !self.attention_pixmaps.is_empty());
+        if wants_attention {
+            (self.attention_icon_name.as_deref(), &self.attention_pixmaps)
+        } else {
+            (self.icon_name.as_deref(), &self.icon_pixmaps)
+        }
+    }
+}
+
+/// Whether a checkable menu entry is a checkbox or a radio button
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuToggle {
+    Checkmark(bool),
+    Radio(bool),
+}
+
+/// One entry of an item's dbusmenu
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuItem {
+    pub id: i32,
+    /// With `_` marking the mnemonic, as in GTK labels
+    pub label: String,
+    pub enabled: bool,
+    pub visible: bool,
+    pub separator: bool,
+    pub icon_name: Option<String>,
+    pub toggle: Option<MenuToggle>,
+    /// Non-empty for entries opening a submenu
+    pub children: Vec<MenuItem>,
+}
+
+impl MenuItem {
+    fn from_layout<'a>(
+        id: i32,
+        properties: impl IntoIterator<Item = (&'a str, &'a Value<'a>)>,
+        children: impl IntoIterator<Item = &'a Value<'a>>,
+    ) -> Self {
+        let mut item = MenuItem {
+            id,
+            label: String::new(),
+            enabled: true,
+            visible: true,
+            separator: false,
+            icon_name: None,
+            toggle: None,
+            children: children.into_iter().filter_map(MenuItem::from_value).collect(),
+        };
+
+        let mut toggle_type = None;
+        let mut toggled = false;
+        for (name, value) in properties {
+            match name {
+                "type" => item.separator = text(value).as_deref() == Some("separator"),
+                "label" => item.label = text(value).unwrap_or_default(),
+                "enabled" => item.enabled = flag(value).unwrap_or(true),
+                "visible" => item.visible = flag(value).unwrap_or(true),
+                "icon-name" => item.icon_name = non_empty(value),
+                "toggle-type" => toggle_type = text(value),
+                "toggle-state" => toggled = matches!(unwrap_variant(value), Value::I32(1)),
+                _ => {}
+            }
+        }
+        item.toggle = match toggle_type.as_deref() {
+            Some("checkmark") => Some(MenuToggle::Checkmark(toggled)),
+            Some("radio") => Some(MenuToggle::Radio(toggled)),
+            _ => None,
+        };
+        item
+    }
+
+    /// Parse a child node, a variant holding `(ia{sv}av)`
+    fn from_value<'a>(value: &'a Value<'a>) -> Option<Self> {
+        let Value::Structure(node) = unwrap_variant(value) else {
+            return None;
+        };
+        match node.fields() {
+            [Value::I32(id), Value::Dict(properties), Value::Array(children)] => {
+                let properties = properties.iter().filter_map(|(name, value)| match name {
+                    Value::Str(name) => Some((name.as_str(), value)),
+                    _ => None,
+                });
+                Some(Self::from_layout(*id, properties, children.inner()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Changes reported by [`TrayModel::subscribe`], by item address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayEvent {
+    ItemAdded(String),
+    ItemRemoved(String),
+    /// Icon, title, status or tooltip changed
+    ItemChanged(String),
+    /// The item's menu should be fetched again before showing it
+    MenuChanged(String),
+}
+
+/// Scroll direction for [`TrayModel::scroll`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+impl Orientation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Orientation::Horizontal => "horizontal",
+            Orientation::Vertical => "vertical",
+        }
+    }
+}
+
+struct Tracked {
+    item: TrayItem,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Inner {
+    connection: Connection,
+    host_name: String,
+    items: Mutex<HashMap<String, Tracked>>,
+    events: broadcast::Sender<TrayEvent>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        for task in self.tasks.get_mut().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            task.abort();
+        }
+    }
+}
+
+/// Tray items of the session, as a StatusNotifierHost; clones share state
+#[derive(Clone)]
+pub struct TrayModel {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for TrayModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrayModel")
+            .field("host_name", &self.inner.host_name)
+            .field("items", &self.items_lock().len())
+            .finish()
+    }
+}
+
+impl TrayModel {
+    /// Connect to the session bus and start hosting tray items. Must be
+    /// called within a Tokio runtime.
+    pub async fn connect() -> Result<Self, IpcError> {
+        Self::with_connection(Connection::session().await?).await
+    }
+
+    /// Host tray items on an existing connection
+    pub async fn with_connection(connection: Connection) -> Result<Self, IpcError> {
+        let pruner = serve_watcher(&connection).await?;
+
+        let host_name = format!("{}{}-{}", HOST_PREFIX, std::process::id(), HOST_COUNT.fetch_add(1, Ordering::Relaxed));
+        connection.request_name(host_name.as_str()).await?;
+
+        let (events, _) = broadcast::channel(64);
+        let model = Self {
+            inner: Arc::new(Inner {
+                connection,
+                host_name,
+                items: Mutex::new(HashMap::new()),
+                events,
+                tasks: Mutex::new(vec![pruner]),
+            }),
+        };
+
+        let watcher = StatusNotifierWatcherProxy::builder(&model.inner.connection)
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?;
+        let mut registered = watcher.receive_status_notifier_item_registered().await?;
+        let mut unregistered = watcher.receive_status_notifier_item_unregistered().await?;
+
+        let weak = Arc::downgrade(&model.inner);
+        let follower_watcher = watcher.clone();
+        let follower = tokio::spawn(async move {
+            let Ok(mut owner_changes) = follower_watcher.inner().receive_owner_changed().await else {
+                return;
+            };
+            loop {
+                enum Change {
+                    Registered(String),
+                    Unregistered(String),
+                    NewWatcher,
+                }
+                let change = tokio::select! {
+                    Some(signal) = registered.next() => match signal.args() {
+                        Ok(args) => Change::Registered(args.service),
+                        Err(_) => continue,
+                    },
+                    Some(signal) = unregistered.next() => match signal.args() {
+                        Ok(args) => Change::Unregistered(args.service),
+                        Err(_) => continue,
+                    },
+                    Some(owner) = owner_changes.next() => match owner {
+                        Some(_) => Change::NewWatcher,
+                        None => continue,
+                    },
+                    else => return,
+                };
+                let Some(inner) = weak.upgrade() else {
+                    return;
+                };
+                let model = TrayModel { inner };
+
+                match change {
+                    Change::Registered(address) => model.add_item(&address).await,
+                    Change::Unregistered(address) => model.remove_item(&address),
+                    Change::NewWatcher => {
+                        if let Err(e) = model.sync(&follower_watcher).await {
+                            debug!("Cannot register with the new tray watcher: {}", e);
+                        }
+                    }
+                }
+            }
+        });
+        model.tasks().push(follower);
+
+        model.sync(&watcher).await?;
+        Ok(model)
+    }
+
+    fn items_lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Tracked>> {
+        self.inner.items.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn tasks(&self) -> std::sync::MutexGuard<'_, Vec<JoinHandle<()>>> {
+        self.inner.tasks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Receive item changes as they happen
+    pub fn subscribe(&self) -> broadcast::Receiver<TrayEvent> {
+        self.inner.events.subscribe()
+    }
+
+    fn send(&self, event: TrayEvent) {
+        // Nobody listening is fine
+        let _ = self.inner.events.send(event);
+    }
+
+    /// Bus name this host registered with the watcher
+    pub fn host_name(&self) -> &str {
+        &self.inner.host_name
+    }
+
+    /// All items, sorted by address so icons keep their place
+    pub fn items(&self) -> Vec<TrayItem> {
+        let mut items: Vec<TrayItem> = self.items_lock().values().map(|tracked| tracked.item.clone()).collect();
+        items.sort_by(|a, b| a.address.cmp(&b.address));
+        items
+    }
+
+    pub fn item(&self, address: &str) -> Option<TrayItem> {
+        self.items_lock().get(address).map(|tracked| tracked.item.clone())
+    }
+
+    /// Register as host with the current watcher and catch up on its items
+    async fn sync(&self, watcher: &StatusNotifierWatcherProxy<'_>) -> Result<(), IpcError> {
+        watcher.register_status_notifier_host(&self.inner.host_name).await?;
+        let addresses = watcher.registered_status_notifier_items().await?;
+
+        let stale: Vec<String> = self
+            .items_lock()
+            .keys()
+            .filter(|address| !addresses.contains(address))
+            .cloned()
+            .collect();
+        for address in stale {
+            self.remove_item(&address);
+        }
+        for address in addresses {
+            self.add_item(&address).await;
+        }
+        Ok(())
+    }
+
+    async fn add_item(&self, address: &str) {
+        if self.items_lock().contains_key(address) {
+            return;
+        }
+
+        let mut item = TrayItem::new(address);
+        let properties = match self.fetch(&mut item).await {
+            Ok(properties) => properties,
+            Err(e) => {
+                debug!("Ignoring tray item {}: {}", address, e);
+                return;
+            }
+        };
+        let task = match self.follow(&item, properties).await {
+            Ok(task) => task,
+            Err(e) => {
+                debug!("Cannot follow tray item {}: {}", address, e);
+                return;
+            }
+        };
+
+        {
+            let mut items = self.items_lock();
+            if items.contains_key(address) {
+                return;
+            }
+            items.insert(address.to_string(), Tracked { item, task });
+        }
+        self.send(TrayEvent::ItemAdded(address.to_string()));
+    }
+
+    fn remove_item(&self, address: &str) {
+        if self.items_lock().remove(address).is_some() {
+            self.send(TrayEvent::ItemRemoved(address.to_string()));
+        }
+    }
+
+    /// Read all item properties into `item`, returning the proxy to read
+    /// them again
+    async fn fetch(&self, item: &mut TrayItem) -> Result<PropertiesProxy<'static>, IpcError> {
+        let properties = PropertiesProxy::builder(&self.inner.connection)
+            .destination(item.bus_name.clone())?
+            .path(item.path.clone())?
+            .build()
+            .await?;
+        let values = properties
+            .get_all(Some(InterfaceName::from_static_str_unchecked(ITEM_INTERFACE)).into())
+            .await?;
+        item.apply(values.iter().map(|(name, value)| (name.as_str(), &**value)));
+        Ok(properties)
+    }
+
+    /// Re-read an item whenever it announces a change, and pass on menu
+    /// layout changes, until it goes away
+    async fn follow(&self, item: &TrayItem, properties: PropertiesProxy<'static>) -> Result<JoinHandle<()>, IpcError> {
+        // Signals come from the unique name even for items registered by
+        // a well-known one
+        let dbus = DBusProxy::new(&self.inner.connection).await?;
+        let owner = dbus.get_name_owner(BusName::try_from(item.bus_name.as_str()).map_err(zbus::Error::from)?).await?;
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender(owner.as_str())?
+            .build()
+            .into_owned();
+        let mut signals = MessageStream::for_match_rule(rule, &self.inner.connection, None).await?;
+
+        let weak = Arc::downgrade(&self.inner);
+        let address = item.address.clone();
+        let path = item.path.clone();
+        Ok(tokio::spawn(async move {
+            while let Some(message) = signals.next().await {
+                let Ok(message) = message else {
+                    continue;
+                };
+                let header = message.header();
+                let (Some(interface), Some(signal_path)) = (header.interface(), header.path()) else {
+                    continue;
+                };
+                let Some(inner) = weak.upgrade() else {
+                    return;
+                };
+                let model = TrayModel { inner };
+
+                if interface.as_str() == ITEM_INTERFACE && signal_path.as_str() == path {
+                    let values = match properties
+                        .get_all(Some(InterfaceName::from_static_str_unchecked(ITEM_INTERFACE)).into())
+                        .await
+                    {
+                        Ok(values) => values,
+                        Err(e) => {
+                            debug!("Cannot refresh tray item {}: {}", address, e);
+                            continue;
+                        }
+                    };
+                    {
+                        let mut items = model.items_lock();
+                        let Some(tracked) = items.get_mut(&address) else {
+                            return;
+                        };
+                        tracked.item.apply(values.iter().map(|(name, value)| (name.as_str(), &**value)));
+                    }
+                    model.send(TrayEvent::ItemChanged(address.clone()));
+                } else if interface.as_str() == MENU_INTERFACE {
+                    let is_menu = model
+                        .item(&address)
+                        .and_then(|item| item.menu)
+                        .is_some_and(|menu| menu == signal_path.as_str());
+                    if is_menu {
+                        model.send(TrayEvent::MenuChanged(address.clone()));
+                    }
+                }
+            }
+        }))
+    }
+
+    async fn item_proxy(&self, address: &str) -> Result<StatusNotifierItemProxy<'static>, IpcError> {
+        let (bus_name, path) = parse_address(address);
+        Ok(StatusNotifierItemProxy::builder(&self.inner.connection)
+            .destination(bus_name.to_string())?
+            .path(path.to_string())?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?)
+    }
+
+    /// Primary click at screen coordinates `x`, `y`
+    pub async fn activate(&self, address: &str, x: i32, y: i32) -> Result<(), IpcError> {
+        Ok(self.item_proxy(address).await?.activate(x, y).await?)
+    }
+
+    /// Middle click
+    pub async fn secondary_activate(&self, address: &str, x: i32, y: i32) -> Result<(), IpcError> {
+        Ok(self.item_proxy(address).await?.secondary_activate(x, y).await?)
+    }
+
+    /// Ask an item without dbusmenu to show its own menu
+    pub async fn context_menu(&self, address: &str, x: i32, y: i32) -> Result<(), IpcError> {
+        Ok(self.item_proxy(address).await?.context_menu(x, y).await?)
+    }
+
+    pub async fn scroll(&self, address: &str, delta: i32, orientation: Orientation) -> Result<(), IpcError> {
+        Ok(self.item_proxy(address).await?.scroll(delta, orientation.as_str()).await?)
+    }
+
+    async fn menu_proxy(&self, address: &str) -> Result<DBusMenuProxy<'static>, IpcError> {
+        let item = self
+            .item(address)
+            .ok_or_else(|| IpcError::MethodCallFailed(format!("No tray item {}", address)))?;
+        let menu = item
+            .menu
+            .ok_or_else(|| IpcError::MethodCallFailed(format!("Tray item {} has no menu", address)))?;
+        Ok(DBusMenuProxy::builder(&self.inner.connection)
+            .destination(item.bus_name)?
+            .path(menu)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?)
+    }
+
+    /// Fetch an item's whole menu; the entries are the root's children
+    pub async fn menu(&self, address: &str) -> Result<MenuItem, IpcError> {
+        let menu = self.menu_proxy(address).await?;
+        // Lets lazy menus fill themselves in; not every item implements it
+        let _ = menu.about_to_show(0).await;
+        let (_, (id, properties, children)) = menu.get_layout(0, -1, &[]).await?;
+        Ok(MenuItem::from_layout(
+            id,
+            properties.iter().map(|(name, value)| (name.as_str(), &**value)),
+            children.iter().map(|child| &**child),
+        ))
+    }
+
+    /// Tell the item a menu entry was chosen
+    pub async fn menu_clicked(&self, address: &str, id: i32) -> Result<(), IpcError> {
+        let menu = self.menu_proxy(address).await?;
+        Ok(menu.event(id, "clicked", &Value::from(0i32), 0).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::{Array, Dict, ObjectPath, StructureBuilder, Type};
+
+    fn pixmap_value(width: i32, height: i32, data: Vec<u8>) -> Value<'static> {
+        Value::from(StructureBuilder::new().add_field(width).add_field(height).add_field(data).build())
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("org.kde.StatusNotifierItem-1234-1"), ("org.kde.StatusNotifierItem-1234-1", ITEM_PATH));
+        assert_eq!(
+            parse_address(":1.42/org/ayatana/NotificationItem/nm_applet"),
+            (":1.42", "/org/ayatana/NotificationItem/nm_applet")
+        );
+        assert_eq!(item_address("/org/ayatana/NotificationItem/steam", ":1.7"), ":1.7/org/ayatana/NotificationItem/steam");
+        assert_eq!(item_address("org.kde.StatusNotifierItem-99-1", ":1.7"), "org.kde.StatusNotifierItem-99-1");
+    }
+
+    #[test]
+    fn test_pixmaps() {
+        // One opaque red pixel and one half transparent blue pixel
+        let argb = vec![0xff, 0xff, 0x00, 0x00, 0x80, 0x00, 0x00, 0xff];
+        let pixmap = Pixmap::from_argb(2, 1, &argb).unwrap();
+        assert_eq!(pixmap.rgba, [0xff, 0x00, 0x00, 0xff, 0x00, 0x00, 0xff, 0x80]);
+        assert_eq!(Pixmap::from_argb(2, 2, &argb), None);
+        assert_eq!(Pixmap::from_argb(0, 0, &[]), None);
+
+        let mut array = Array::new(<(i32, i32, Vec<u8>)>::signature());
+        array.append(pixmap_value(16, 16, vec![0; 16 * 16 * 4])).unwrap();
+        array.append(pixmap_value(32, 32, vec![0; 32 * 32 * 4])).unwrap();
+        array.append(pixmap_value(22, 22, vec![0; 3])).unwrap();
+        let parsed = pixmaps(&Value::Array(array));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(Pixmap::best(&parsed, 24).map(|pixmap| pixmap.width), Some(32));
+        assert_eq!(Pixmap::best(&parsed, 8).map(|pixmap| pixmap.width), Some(16));
+        assert_eq!(Pixmap::best(&parsed, 48).map(|pixmap| pixmap.width), Some(32));
+    }
+
+    #[test]
+    fn test_apply_properties() {
+        let id = Value::from("nm-applet");
+        let status = Value::from("NeedsAttention");
+        let icon = Value::from("network-wireless");
+        let attention = Value::from("network-error");
+        let menu = Value::from(ObjectPath::try_from("/org/ayatana/NotificationItem/nm_applet/Menu").unwrap());
+        let no_overlay = Value::from("");
+
+        let mut item = TrayItem::new(":1.42/org/ayatana/NotificationItem/nm_applet");
+        item.apply([
+            ("Id", &id),
+            ("Status", &status),
+            ("IconName", &icon),
+            ("AttentionIconName", &attention),
+            ("OverlayIconName", &no_overlay),
+            ("Menu", &menu),
+        ]);
+
+        assert_eq!(item.bus_name, ":1.42");
+        assert_eq!(item.id, "nm-applet");
+        assert_eq!(item.overlay_icon_name, None);
+        assert_eq!(item.menu.as_deref(), Some("/org/ayatana/NotificationItem/nm_applet/Menu"));
+        assert_eq!(item.current_icon().0, Some("network-error"));
+        item.status = ItemStatus::Active;
+        assert_eq!(item.current_icon().0, Some("network-wireless"));
+    }
+
+    fn menu_node(id: i32, properties: Vec<(&'static str, Value<'static>)>, children: Vec<Value<'static>>) -> Value<'static> {
+        let mut dict = Dict::new(<&str>::signature(), Value::signature());
+        for (name, value) in properties {
+            dict.add(name, value).unwrap();
+        }
+        let mut array = Array::new(Value::signature());
+        for child in children {
+            array.append(Value::Value(Box::new(child))).unwrap();
+        }
+        Value::from(
+            StructureBuilder::new()
+                .add_field(id)
+                .append_field(Value::Dict(dict))
+                .append_field(Value::Array(array))
+                .build(),
+        )
+    }
+
+    #[test]
+    fn test_menu_layout() {
+        let wifi = menu_node(
+            2,
+            vec![
+                ("label", Value::from("Enable _Wi-Fi")),
+                ("toggle-type", Value::from("checkmark")),
+                ("toggle-state", Value::I32(1)),
+            ],
+            Vec::new(),
+        );
+        let separator = menu_node(3, vec![("type", Value::from("separator"))], Vec::new());
+        let quit = menu_node(4, vec![("label", Value::from("Quit")), ("enabled", Value::Bool(false))], Vec::new());
+        let children = [wifi, separator, quit];
+
+        let root = MenuItem::from_layout(0, [], children.iter());
+        assert_eq!(root.children.len(), 3);
+        assert_eq!(root.children[0].label, "Enable _Wi-Fi");
+        assert_eq!(root.children[0].toggle, Some(MenuToggle::Checkmark(true)));
+        assert!(root.children[1].separator);
+        assert!(!root.children[2].enabled);
+        assert!(root.children[2].visible);
+    }
+}
@@ -0,0 +1,122 @@
+//! D-Bus proxies for the UDisks2 storage daemon
+//!
+//! Only the parts of the API the desktop needs are described here: block
+//! device identity, filesystem mounting and drive ejection.
+
+use std::collections::HashMap;
+
+use zbus::proxy;
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+/// Well-known bus name of the UDisks2 daemon
+pub const UDISKS2_SERVICE: &str = "org.freedesktop.UDisks2";
+/// Root object path exported by UDisks2
+pub const UDISKS2_PATH: &str = "/org/freedesktop/UDisks2";
+
+pub const BLOCK_INTERFACE: &str = "org.freedesktop.UDisks2.Block";
+pub const FILESYSTEM_INTERFACE: &str = "org.freedesktop.UDisks2.Filesystem";
+pub const DRIVE_INTERFACE: &str = "org.freedesktop.UDisks2.Drive";
+
+/// Options dictionary passed to UDisks2 methods
+pub type UDisks2Options<'a> = HashMap<&'a str, Value<'a>>;
+
+/// `org.freedesktop.UDisks2.Block`
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Block",
+    default_service = "org.freedesktop.UDisks2"
+)]
+pub trait UDisks2Block {
+    /// Device file as a NUL-terminated byte string
+    #[zbus(property)]
+    fn device(&self) -> zbus::Result<Vec<u8>>;
+
+    #[zbus(property)]
+    fn preferred_device(&self) -> zbus::Result<Vec<u8>>;
+
+    #[zbus(property)]
+    fn size(&self) -> zbus::Result<u64>;
+
+    #[zbus(property)]
+    fn read_only(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn drive(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property)]
+    fn id_usage(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn id_type(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn id_label(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "IdUUID")]
+    fn id_uuid(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn hint_ignore(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn hint_system(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn hint_name(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn hint_icon_name(&self) -> zbus::Result<String>;
+}
+
+/// `org.freedesktop.UDisks2.Filesystem`
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Filesystem",
+    default_service = "org.freedesktop.UDisks2"
+)]
+pub trait UDisks2Filesystem {
+    /// Mount the filesystem, returning the mount point
+    fn mount(&self, options: UDisks2Options<'_>) -> zbus::Result<String>;
+
+    fn unmount(&self, options: UDisks2Options<'_>) -> zbus::Result<()>;
+
+    /// Mount points as NUL-terminated byte strings
+    #[zbus(property)]
+    fn mount_points(&self) -> zbus::Result<Vec<Vec<u8>>>;
+}
+
+/// `org.freedesktop.UDisks2.Drive`
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Drive",
+    default_service = "org.freedesktop.UDisks2"
+)]
+pub trait UDisks2Drive {
+    fn eject(&self, options: UDisks2Options<'_>) -> zbus::Result<()>;
+
+    fn power_off(&self, options: UDisks2Options<'_>) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn vendor(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn model(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn connection_bus(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn removable(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn media_removable(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn ejectable(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn can_power_off(&self) -> zbus::Result<bool>;
+}
+
+/// Decode a UDisks2 byte-string property (NUL-terminated path)
+pub fn decode_bytestring(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
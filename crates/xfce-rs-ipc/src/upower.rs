@@ -0,0 +1,247 @@
+//! D-Bus proxies for the UPower power daemon
+//!
+//! Covers what the power manager and battery plugin show: AC/battery
+//! state, per-device charge and time estimates, and the laptop lid.
+
+use std::time::Duration;
+
+use zbus::proxy;
+use zbus::zvariant::OwnedObjectPath;
+
+/// Well-known bus name of the UPower daemon
+pub const UPOWER_SERVICE: &str = "org.freedesktop.UPower";
+/// Object path of the UPower manager object
+pub const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+
+/// `org.freedesktop.UPower`
+#[proxy(
+    interface = "org.freedesktop.UPower",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower"
+)]
+pub trait UPower {
+    fn enumerate_devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Composite device summarizing all batteries, for panel icons
+    fn get_display_device(&self) -> zbus::Result<OwnedObjectPath>;
+
+    /// "HybridSleep", "Hibernate" or "PowerOff"
+    fn get_critical_action(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn daemon_version(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn lid_is_closed(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn lid_is_present(&self) -> zbus::Result<bool>;
+
+    #[zbus(signal)]
+    fn device_added(&self, device: OwnedObjectPath) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn device_removed(&self, device: OwnedObjectPath) -> zbus::Result<()>;
+}
+
+/// `org.freedesktop.UPower.Device`
+#[proxy(interface = "org.freedesktop.UPower.Device", default_service = "org.freedesktop.UPower")]
+pub trait UPowerDevice {
+    fn refresh(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn native_path(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn vendor(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn model(&self) -> zbus::Result<String>;
+
+    /// See [`DeviceKind`]
+    #[zbus(property, name = "Type")]
+    fn kind(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn power_supply(&self) -> zbus::Result<bool>;
+
+    /// Whether a line power device is plugged in
+    #[zbus(property)]
+    fn online(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn is_present(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn percentage(&self) -> zbus::Result<f64>;
+
+    /// Watt-hours
+    #[zbus(property)]
+    fn energy(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn energy_full(&self) -> zbus::Result<f64>;
+
+    /// Watts, positive both when charging and discharging
+    #[zbus(property)]
+    fn energy_rate(&self) -> zbus::Result<f64>;
+
+    /// Seconds, 0 if unknown
+    #[zbus(property)]
+    fn time_to_empty(&self) -> zbus::Result<i64>;
+
+    /// Seconds, 0 if unknown
+    #[zbus(property)]
+    fn time_to_full(&self) -> zbus::Result<i64>;
+
+    /// See [`BatteryState`]
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<u32>;
+
+    /// See [`WarningLevel`]
+    #[zbus(property)]
+    fn warning_level(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn icon_name(&self) -> zbus::Result<String>;
+}
+
+/// Value of the device `Type` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Unknown,
+    LinePower,
+    Battery,
+    Ups,
+    Monitor,
+    Mouse,
+    Keyboard,
+    Pda,
+    Phone,
+    /// Media players, tablets, headsets and the other peripheral kinds
+    Other(u32),
+}
+
+impl DeviceKind {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            0 => DeviceKind::Unknown,
+            1 => DeviceKind::LinePower,
+            2 => DeviceKind::Battery,
+            3 => DeviceKind::Ups,
+            4 => DeviceKind::Monitor,
+            5 => DeviceKind::Mouse,
+            6 => DeviceKind::Keyboard,
+            7 => DeviceKind::Pda,
+            8 => DeviceKind::Phone,
+            other => DeviceKind::Other(other),
+        }
+    }
+}
+
+/// Value of the device `State` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryState {
+    Unknown,
+    Charging,
+    Discharging,
+    Empty,
+    FullyCharged,
+    PendingCharge,
+    PendingDischarge,
+}
+
+impl BatteryState {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => BatteryState::Charging,
+            2 => BatteryState::Discharging,
+            3 => BatteryState::Empty,
+            4 => BatteryState::FullyCharged,
+            5 => BatteryState::PendingCharge,
+            6 => BatteryState::PendingDischarge,
+            _ => BatteryState::Unknown,
+        }
+    }
+}
+
+/// Value of the device `WarningLevel` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WarningLevel {
+    Unknown,
+    None,
+    /// Only used by UPS devices running on battery
+    Discharging,
+    Low,
+    Critical,
+    /// The critical action is about to be taken
+    Action,
+}
+
+impl WarningLevel {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => WarningLevel::None,
+            2 => WarningLevel::Discharging,
+            3 => WarningLevel::Low,
+            4 => WarningLevel::Critical,
+            5 => WarningLevel::Action,
+            _ => WarningLevel::Unknown,
+        }
+    }
+}
+
+/// Snapshot of a battery for display
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryStatus {
+    pub percentage: f64,
+    pub state: BatteryState,
+    pub warning_level: WarningLevel,
+    /// Until empty when discharging, until full when charging
+    pub time_remaining: Option<Duration>,
+    pub icon_name: String,
+}
+
+impl BatteryStatus {
+    /// Read the properties of a battery, or of the display device
+    pub async fn fetch(device: &UPowerDeviceProxy<'_>) -> zbus::Result<Self> {
+        let state = BatteryState::from_u32(device.state().await?);
+        let seconds = match state {
+            BatteryState::Charging => device.time_to_full().await?,
+            BatteryState::Discharging => device.time_to_empty().await?,
+            _ => 0,
+        };
+
+        Ok(Self {
+            percentage: device.percentage().await?,
+            state,
+            warning_level: WarningLevel::from_u32(device.warning_level().await?),
+            time_remaining: seconds_to_duration(seconds),
+            icon_name: device.icon_name().await?,
+        })
+    }
+}
+
+/// UPower reports unknown times as 0
+fn seconds_to_duration(seconds: i64) -> Option<Duration> {
+    u64::try_from(seconds).ok().filter(|seconds| *seconds > 0).map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enum_values() {
+        assert_eq!(DeviceKind::from_u32(2), DeviceKind::Battery);
+        assert_eq!(DeviceKind::from_u32(17), DeviceKind::Other(17));
+        assert_eq!(BatteryState::from_u32(4), BatteryState::FullyCharged);
+        assert_eq!(BatteryState::from_u32(99), BatteryState::Unknown);
+        assert!(WarningLevel::from_u32(4) > WarningLevel::Low);
+        assert_eq!(seconds_to_duration(0), None);
+        assert_eq!(seconds_to_duration(5400), Some(Duration::from_secs(5400)));
+    }
+}
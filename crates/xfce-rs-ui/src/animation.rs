@@ -0,0 +1,219 @@
+//! Eased animations for iced views
+//!
+//! Animations are plain values evaluated at an [`Instant`]: the app keeps
+//! the time of the latest frame in its state, reads values with it in
+//! `view`, and subscribes to [`frames`] only while something is moving so
+//! idle windows don't redraw.
+//!
+//! ```ignore
+//! Message::Frame(now) => self.now = now,
+//! ...
+//! animation::frames(self.popover.is_animating(self.now)).map(Message::Frame)
+//! ```
+
+use std::time::{Duration, Instant};
+
+use iced::{window, Color, Subscription};
+
+/// Length of open/close transitions and value changes
+pub const DEFAULT_DURATION: Duration = Duration::from_millis(180);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    Linear,
+    /// Fast start, gentle stop; suits things appearing
+    #[default]
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eased progress for linear progress `t` in `0.0..=1.0`
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+}
+
+/// A number that glides to new targets instead of jumping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animated {
+    from: f32,
+    to: f32,
+    start: Option<Instant>,
+    duration: Duration,
+    easing: Easing,
+}
+
+impl Animated {
+    pub fn new(value: f32) -> Self {
+        Self {
+            from: value,
+            to: value,
+            start: None,
+            duration: DEFAULT_DURATION,
+            easing: Easing::default(),
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Where the value is heading
+    pub fn target(&self) -> f32 {
+        self.to
+    }
+
+    pub fn value(&self, now: Instant) -> f32 {
+        self.from + (self.to - self.from) * self.easing.apply(self.progress(now))
+    }
+
+    pub fn is_animating(&self, now: Instant) -> bool {
+        self.progress(now) < 1.0
+    }
+
+    /// Jump to `value`, e.g. while the user drags a slider
+    pub fn set(&mut self, value: f32) {
+        *self = Self { from: value, to: value, start: None, ..*self };
+    }
+
+    /// Start moving towards `target` from wherever the value is now, so
+    /// changing the target mid-flight doesn't jump
+    pub fn go_to(&mut self, target: f32, now: Instant) {
+        if target == self.to {
+            return;
+        }
+        self.from = self.value(now);
+        self.to = target;
+        self.start = Some(now);
+    }
+
+    fn progress(&self, now: Instant) -> f32 {
+        let progress = match self.start {
+            Some(start) if !self.duration.is_zero() => {
+                now.saturating_duration_since(start).as_secs_f32() / self.duration.as_secs_f32()
+            }
+            _ => 1.0,
+        };
+        progress.min(1.0)
+    }
+}
+
+/// Enter and exit transition for overlays such as menus and popovers.
+/// Keep drawing the overlay while [`Transition::is_visible`], scaled by
+/// [`Transition::progress`], so it can fade out after being closed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    progress: Animated,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self::hidden()
+    }
+}
+
+impl Transition {
+    pub fn hidden() -> Self {
+        Self {
+            progress: Animated::new(0.0),
+        }
+    }
+
+    pub fn show(&mut self, now: Instant) {
+        self.progress.go_to(1.0, now);
+    }
+
+    pub fn hide(&mut self, now: Instant) {
+        self.progress.go_to(0.0, now);
+    }
+
+    /// Whether the overlay was last asked to show
+    pub fn is_open(&self) -> bool {
+        self.progress.target() > 0.0
+    }
+
+    /// 0.0 when hidden, 1.0 when fully shown
+    pub fn progress(&self, now: Instant) -> f32 {
+        self.progress.value(now)
+    }
+
+    /// Whether the overlay should be drawn, including while it fades out
+    pub fn is_visible(&self, now: Instant) -> bool {
+        self.is_open() || self.progress.is_animating(now)
+    }
+
+    pub fn is_animating(&self, now: Instant) -> bool {
+        self.progress.is_animating(now)
+    }
+}
+
+/// `color` with its alpha scaled by `progress`, for fading styles
+pub fn fade(color: Color, progress: f32) -> Color {
+    Color {
+        a: color.a * progress.clamp(0.0, 1.0),
+        ..color
+    }
+}
+
+/// Frame times while `animating`, nothing otherwise
+pub fn frames(animating: bool) -> Subscription<Instant> {
+    if animating {
+        window::frames()
+    } else {
+        Subscription::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animated_retargets_smoothly() {
+        let start = Instant::now();
+        let mut value = Animated::new(0.0).with_easing(Easing::Linear);
+        value.go_to(100.0, start);
+        assert_eq!(value.value(start), 0.0);
+        assert!(value.is_animating(start));
+
+        let halfway = start + DEFAULT_DURATION / 2;
+        assert!((value.value(halfway) - 50.0).abs() < 1e-3);
+
+        value.go_to(0.0, halfway);
+        assert!((value.value(halfway) - 50.0).abs() < 1e-3);
+        assert_eq!(value.value(halfway + DEFAULT_DURATION), 0.0);
+        assert!(!value.is_animating(halfway + DEFAULT_DURATION));
+
+        value.set(30.0);
+        assert_eq!(value.value(start), 30.0);
+    }
+
+    #[test]
+    fn test_transition_stays_visible_while_closing() {
+        let start = Instant::now();
+        let mut popover = Transition::hidden();
+        assert!(!popover.is_visible(start));
+
+        popover.show(start);
+        let shown = start + DEFAULT_DURATION;
+        assert_eq!(popover.progress(shown), 1.0);
+
+        popover.hide(shown);
+        assert!(!popover.is_open());
+        assert!(popover.is_visible(shown + DEFAULT_DURATION / 2));
+        assert!(!popover.is_visible(shown + DEFAULT_DURATION));
+    }
+}
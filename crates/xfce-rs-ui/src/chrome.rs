@@ -0,0 +1,129 @@
+//! Window chrome for undecorated glass windows
+//!
+//! [`window_frame`] draws the glass stack, the close/minimize/maximize
+//! controls and a drag layer around an app's content, reporting what the
+//! user did as a [`ChromeEvent`]. Apps wrap the event in their message
+//! type and hand it back to [`perform`]:
+//!
+//! ```ignore
+//! Message::Chrome(event) => chrome::perform(event),
+//! ...
+//! chrome::window_frame(text("Audio Control"), content, Message::Chrome)
+//! ```
+
+use iced::keyboard::{self, key, Key};
+use iced::widget::{button, column, container, mouse_area, row, space, Stack};
+use iced::{event, window, Alignment, Element, Event, Length, Subscription, Task};
+
+use crate::{colors, styles};
+
+/// Height of the row holding the controls and title
+pub const HEADER_HEIGHT: f32 = 40.0;
+
+/// Diameter of the window control blobs
+const CONTROL_SIZE: f32 = 12.0;
+
+/// Something the user asked the window to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromeEvent {
+    Drag,
+    Close,
+    Minimize,
+    ToggleMaximize,
+}
+
+/// Apply `event` to the app's latest window
+pub fn perform<Message>(event: ChromeEvent) -> Task<Message>
+where
+    Message: Send + 'static,
+{
+    window::latest().and_then(move |id| match event {
+        ChromeEvent::Drag => window::drag(id),
+        ChromeEvent::Close => window::close(id),
+        ChromeEvent::Minimize => window::minimize(id, true),
+        ChromeEvent::ToggleMaximize => window::toggle_maximize(id),
+    })
+}
+
+/// Glass window with controls and a title row above `content`. Pressing
+/// anywhere the content doesn't handle drags the window; double-clicking
+/// toggles maximize.
+pub fn window_frame<'a, Message>(
+    title: impl Into<Element<'a, Message>>,
+    content: impl Into<Element<'a, Message>>,
+    on_event: impl Fn(ChromeEvent) -> Message,
+) -> Element<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let header = row![
+        row![
+            control(on_event(ChromeEvent::Close), colors::control_close),
+            control(on_event(ChromeEvent::Minimize), colors::control_min),
+            control(on_event(ChromeEvent::ToggleMaximize), colors::control_max),
+        ]
+        .spacing(8)
+        .padding(10),
+        container(title).width(Length::Fill).align_y(Alignment::Center),
+    ]
+    .height(HEADER_HEIGHT)
+    .align_y(Alignment::Center);
+
+    let layers: Vec<Element<'a, Message>> = vec![
+        // Base glass and its edge highlights
+        fill().style(|theme| styles::glass_base(theme)).into(),
+        fill().style(|theme| styles::glass_highlight_top(theme)).into(),
+        fill().style(|theme| styles::glass_highlight_bottom(theme)).into(),
+        fill().style(|theme| styles::glass_highlight_left(theme)).into(),
+        fill().style(|theme| styles::glass_highlight_right(theme)).into(),
+        // Drag listener below the content
+        mouse_area(fill())
+            .on_press(on_event(ChromeEvent::Drag))
+            .on_double_click(on_event(ChromeEvent::ToggleMaximize))
+            .into(),
+        container(column![header, content.into()].spacing(15).padding(20))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into(),
+    ];
+
+    Stack::with_children(layers).into()
+}
+
+/// Keyboard shortcuts for the window: Ctrl+W closes, Ctrl+M minimizes and
+/// F11 toggles maximize. Keys a focused widget consumed are left alone.
+pub fn shortcuts() -> Subscription<ChromeEvent> {
+    event::listen_with(shortcut)
+}
+
+fn shortcut(event: Event, status: event::Status, _window: window::Id) -> Option<ChromeEvent> {
+    if status == event::Status::Captured {
+        return None;
+    }
+    let Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) = event else {
+        return None;
+    };
+
+    match key.as_ref() {
+        Key::Character("w") if modifiers.command() => Some(ChromeEvent::Close),
+        Key::Character("m") if modifiers.command() => Some(ChromeEvent::Minimize),
+        Key::Named(key::Named::F11) => Some(ChromeEvent::ToggleMaximize),
+        _ => None,
+    }
+}
+
+fn control<'a, Message: Clone + 'a>(
+    on_press: Message,
+    color: fn() -> iced::Color,
+) -> Element<'a, Message> {
+    button(space().width(CONTROL_SIZE).height(CONTROL_SIZE))
+        .on_press(on_press)
+        .style(move |theme, status| styles::window_control(theme, status, color()))
+        .width(CONTROL_SIZE)
+        .height(CONTROL_SIZE)
+        .into()
+}
+
+fn fill<'a, Message: 'a>() -> container::Container<'a, Message> {
+    container(space()).width(Length::Fill).height(Length::Fill)
+}
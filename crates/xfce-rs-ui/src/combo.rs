@@ -0,0 +1,238 @@
+//! Searchable dropdown for long option lists
+//!
+//! Typing into the field filters the options by label and group; Enter
+//! picks the first match. The app keeps a [`SearchableCombo`] in its state,
+//! draws it with [`combo_box_searchable`] and passes the [`ComboEvent`]s
+//! back to [`SearchableCombo::update`], which returns the picked value.
+
+use iced::widget::{button, column, container, row, scrollable, text, text_input};
+use iced::{Alignment, Element, Length};
+
+use crate::{colors, fonts, styles};
+
+/// Tallest the open list grows before it scrolls
+pub const LIST_MAX_HEIGHT: f32 = 240.0;
+
+/// A value with the label shown for it and an optional group heading
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComboOption<T> {
+    pub value: T,
+    pub label: String,
+    pub group: Option<String>,
+}
+
+impl<T> ComboOption<T> {
+    pub fn new(value: T, label: impl Into<String>) -> Self {
+        Self {
+            value,
+            label: label.into(),
+            group: None,
+        }
+    }
+
+    /// Options sharing a group are listed under one heading, so keep them
+    /// next to each other
+    pub fn in_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComboEvent {
+    Query(String),
+    Toggle,
+    /// Enter in the field: pick the first match, if anything was typed
+    Submit,
+    /// Pick the option at this index
+    Pick(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchableCombo<T> {
+    options: Vec<ComboOption<T>>,
+    query: String,
+    open: bool,
+}
+
+impl<T: Clone> SearchableCombo<T> {
+    pub fn new(options: Vec<ComboOption<T>>) -> Self {
+        Self {
+            options,
+            query: String::new(),
+            open: false,
+        }
+    }
+
+    pub fn options(&self) -> &[ComboOption<T>] {
+        &self.options
+    }
+
+    /// Replace the options, e.g. when devices come and go
+    pub fn set_options(&mut self, options: Vec<ComboOption<T>>) {
+        self.options = options;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Indices of options matching the query, in their original order
+    pub fn matches(&self) -> Vec<usize> {
+        filter(&self.options, &self.query)
+    }
+
+    /// Returns the value the user picked, if any
+    pub fn update(&mut self, event: ComboEvent) -> Option<T> {
+        let picked = match event {
+            ComboEvent::Query(query) => {
+                self.open = true;
+                self.query = query;
+                return None;
+            }
+            ComboEvent::Toggle => {
+                self.open = !self.open;
+                return None;
+            }
+            // An empty query matches everything; Enter on it should not
+            // silently pick the first option
+            ComboEvent::Submit if self.query.trim().is_empty() => return None,
+            ComboEvent::Submit => self.matches().first().copied()?,
+            ComboEvent::Pick(index) => index,
+        };
+
+        let value = self.options.get(picked)?.value.clone();
+        self.query.clear();
+        self.open = false;
+        Some(value)
+    }
+}
+
+/// Case-insensitive substring match on the label or group
+fn filter<T>(options: &[ComboOption<T>], query: &str) -> Vec<usize> {
+    let query = query.trim().to_lowercase();
+    options
+        .iter()
+        .enumerate()
+        .filter(|(_, option)| {
+            query.is_empty()
+                || option.label.to_lowercase().contains(&query)
+                || option.group.as_ref().is_some_and(|g| g.to_lowercase().contains(&query))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Text field showing `selected` that filters the options as the user types,
+/// with the matches listed below it under their group headings while open
+pub fn combo_box_searchable<'a, T, Message>(
+    state: &'a SearchableCombo<T>,
+    placeholder: &str,
+    selected: Option<&T>,
+    on_event: impl Fn(ComboEvent) -> Message + 'a,
+) -> Element<'a, Message>
+where
+    T: Clone + PartialEq,
+    Message: Clone + 'a,
+{
+    let selected_label = selected
+        .and_then(|value| state.options.iter().find(|option| &option.value == value))
+        .map(|option| option.label.as_str());
+
+    let list = state.open.then(|| -> Element<'a, Message> {
+        let matches = state.matches();
+        if matches.is_empty() {
+            return text("No matches").size(fonts::relative(13.0)).color(colors::text_secondary()).into();
+        }
+
+        let mut entries = column![].spacing(2);
+        let mut current_group = None;
+        for index in matches {
+            let option = &state.options[index];
+            if option.group.is_some() && option.group != current_group {
+                current_group = option.group.clone();
+                entries = entries.push(
+                    container(text(option.group.clone().unwrap_or_default()).size(fonts::relative(11.0)).color(colors::text_secondary()))
+                        .padding([6, 8]),
+                );
+            }
+
+            let is_selected = selected == Some(&option.value);
+            entries = entries.push(
+                button(text(option.label.as_str()).size(fonts::relative(14.0)))
+                    .on_press(on_event(ComboEvent::Pick(index)))
+                    .width(Length::Fill)
+                    .padding([6, 12])
+                    .style(move |theme, status| styles::menu_item(theme, status, is_selected, true)),
+            );
+        }
+
+        container(scrollable(entries).height(Length::Shrink))
+            .max_height(LIST_MAX_HEIGHT)
+            .padding(4)
+            .style(|theme| styles::menu_surface(theme))
+            .into()
+    });
+
+    let toggle = button(text(if state.open { "▴" } else { "▾" }).size(fonts::relative(14.0)))
+        .on_press(on_event(ComboEvent::Toggle))
+        .padding([8, 12])
+        .style(|theme, status| styles::app_card(theme, status));
+
+    let submit = on_event(ComboEvent::Submit);
+    let field = text_input(selected_label.unwrap_or(placeholder), &state.query)
+        .on_input(move |query| on_event(ComboEvent::Query(query)))
+        .on_submit(submit)
+        .padding(8)
+        .style(|theme, status| styles::search_input(theme, status));
+
+    let mut content = column![row![field, toggle].spacing(6).align_y(Alignment::Center)].spacing(6);
+    if let Some(list) = list {
+        content = content.push(list);
+    }
+    content.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layouts() -> SearchableCombo<&'static str> {
+        SearchableCombo::new(vec![
+            ComboOption::new("us", "English (US)").in_group("English"),
+            ComboOption::new("gb", "English (UK)").in_group("English"),
+            ComboOption::new("de", "German").in_group("European"),
+            ComboOption::new("fr", "French").in_group("European"),
+        ])
+    }
+
+    #[test]
+    fn test_filter_matches_label_or_group() {
+        let mut combo = layouts();
+        assert_eq!(combo.matches(), vec![0, 1, 2, 3]);
+        combo.update(ComboEvent::Query("UK".to_string()));
+        assert_eq!(combo.matches(), vec![1]);
+        combo.update(ComboEvent::Query("europ".to_string()));
+        assert_eq!(combo.matches(), vec![2, 3]);
+        combo.update(ComboEvent::Query("klingon".to_string()));
+        assert!(combo.matches().is_empty());
+    }
+
+    #[test]
+    fn test_submit_picks_first_match_and_resets() {
+        let mut combo = layouts();
+        assert_eq!(combo.update(ComboEvent::Submit), None);
+        combo.update(ComboEvent::Toggle);
+        assert_eq!(combo.update(ComboEvent::Submit), None);
+
+        combo.update(ComboEvent::Query("fr".to_string()));
+        assert!(combo.is_open());
+        assert_eq!(combo.update(ComboEvent::Submit), Some("fr"));
+        assert!(!combo.is_open());
+        assert_eq!(combo.matches().len(), 4);
+
+        combo.update(ComboEvent::Query("klingon".to_string()));
+        assert_eq!(combo.update(ComboEvent::Submit), None);
+        assert_eq!(combo.update(ComboEvent::Pick(2)), Some("de"));
+    }
+}
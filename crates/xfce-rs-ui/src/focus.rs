@@ -0,0 +1,142 @@
+//! Keyboard focus for views built from buttons
+//!
+//! iced buttons don't take keyboard focus, so apps track it themselves: a
+//! [`FocusRing`] remembers which of the focusable items is focused, in tab
+//! order, [`keyboard`] turns Tab, Shift+Tab, the arrow keys, Enter/Space
+//! and Escape into [`FocusEvent`]s, and
+//! [`styles::with_focus_ring`](crate::styles::with_focus_ring) draws the
+//! ring around the focused item.
+
+use iced::keyboard::{self, key, Key};
+use iced::{event, window, Event, Subscription};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusEvent {
+    Next,
+    Previous,
+    /// Press the focused item
+    Activate,
+    Clear,
+}
+
+/// Which of `len` items has keyboard focus
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FocusRing {
+    len: usize,
+    focused: Option<usize>,
+}
+
+impl FocusRing {
+    pub fn new(len: usize) -> Self {
+        Self { len, focused: None }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Change the number of items, e.g. after filtering a list. Focus is
+    /// dropped if its item no longer exists.
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len;
+        self.focused = self.focused.filter(|&index| index < len);
+    }
+
+    pub fn focused(&self) -> Option<usize> {
+        self.focused
+    }
+
+    pub fn is_focused(&self, index: usize) -> bool {
+        self.focused == Some(index)
+    }
+
+    pub fn focus(&mut self, index: Option<usize>) {
+        self.focused = index.filter(|&index| index < self.len);
+    }
+
+    /// Move focus, wrapping at either end. Returns the item to press for
+    /// [`FocusEvent::Activate`].
+    pub fn update(&mut self, event: FocusEvent) -> Option<usize> {
+        if self.len == 0 {
+            self.focused = None;
+            return None;
+        }
+
+        match event {
+            FocusEvent::Next => {
+                self.focused = Some(self.focused.map_or(0, |index| (index + 1) % self.len));
+            }
+            FocusEvent::Previous => {
+                self.focused = Some(self.focused.map_or(self.len - 1, |index| (index + self.len - 1) % self.len));
+            }
+            FocusEvent::Activate => return self.focused,
+            FocusEvent::Clear => self.focused = None,
+        }
+        None
+    }
+}
+
+/// Focus keys. Tab always moves focus; the other keys are left to a
+/// widget that handled them, so typing a space into a text field doesn't
+/// press the focused button.
+pub fn keyboard() -> Subscription<FocusEvent> {
+    event::listen_with(focus_key)
+}
+
+fn focus_key(event: Event, status: event::Status, _window: window::Id) -> Option<FocusEvent> {
+    let Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) = event else {
+        return None;
+    };
+
+    match key.as_ref() {
+        Key::Named(key::Named::Tab) if modifiers.shift() => Some(FocusEvent::Previous),
+        Key::Named(key::Named::Tab) => Some(FocusEvent::Next),
+        _ if status == event::Status::Captured => None,
+        Key::Named(key::Named::ArrowDown | key::Named::ArrowRight) => Some(FocusEvent::Next),
+        Key::Named(key::Named::ArrowUp | key::Named::ArrowLeft) => Some(FocusEvent::Previous),
+        Key::Named(key::Named::Enter | key::Named::Space) => Some(FocusEvent::Activate),
+        Key::Named(key::Named::Escape) => Some(FocusEvent::Clear),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_focus_wraps_both_ways() {
+        let mut ring = FocusRing::new(3);
+        assert_eq!(ring.update(FocusEvent::Activate), None);
+
+        ring.update(FocusEvent::Previous);
+        assert_eq!(ring.focused(), Some(2));
+        ring.update(FocusEvent::Next);
+        assert_eq!(ring.focused(), Some(0));
+        ring.update(FocusEvent::Next);
+        assert_eq!(ring.update(FocusEvent::Activate), Some(1));
+
+        ring.update(FocusEvent::Clear);
+        assert_eq!(ring.focused(), None);
+    }
+
+    #[test]
+    fn test_shrinking_drops_lost_focus() {
+        let mut ring = FocusRing::new(5);
+        ring.focus(Some(4));
+        ring.set_len(2);
+        assert_eq!(ring.focused(), None);
+
+        ring.focus(Some(1));
+        ring.set_len(2);
+        assert!(ring.is_focused(1));
+
+        ring.set_len(0);
+        assert_eq!(ring.update(FocusEvent::Next), None);
+        assert_eq!(ring.focused(), None);
+    }
+}
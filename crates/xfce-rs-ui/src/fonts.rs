@@ -0,0 +1,276 @@
+//! Interface fonts
+//!
+//! Fonts are set in the `xsettings` channel next to the other GTK
+//! appearance settings, so XFCE.rs apps and GTK apps read the same values
+//! and the XSETTINGS manager publishes them without extra work:
+//!
+//! ```text
+//! /Gtk/FontName             "Sans 10", a Pango description: family, styles, size in points
+//! /Gtk/MonospaceFontName    "Monospace 10"
+//! /Xft/Antialias            1 on, 0 off, -1 leave to fontconfig
+//! /Xft/Hinting              1 on, 0 off, -1 leave to fontconfig
+//! /Xft/HintStyle            "hintnone", "hintslight", "hintmedium" or "hintfull"
+//! /Xft/RGBA                 "none", "rgb", "bgr", "vrgb" or "vbgr"
+//! ```
+//!
+//! iced takes its default font and text size once, at startup, so apps
+//! call [`load`] before running and pass [`settings`] to the application.
+//! Text that needs the configured size or the monospace font at runtime
+//! uses [`relative`] and [`monospace`], which follow changes through the
+//! theme [`subscription`](crate::theme::subscription).
+
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+
+use iced::font::{Family, Style, Weight};
+use iced::Font;
+use tokio::sync::watch;
+use tracing::warn;
+use xfce_rs_config::{config_section, XfceConfig};
+
+pub const XSETTINGS_CHANNEL: &str = "xsettings";
+
+/// Size in points of the default interface font, `"Sans 10"`, which
+/// [`relative`] sizes are designed against
+pub const DEFAULT_POINTS: f32 = 10.0;
+
+config_section! {
+    /// Font settings shared with GTK
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct FontConfig in "xsettings" {
+        /// Pango description of the interface font
+        pub font_name: String = "/Gtk/FontName" => "Sans 10".to_string(),
+        pub monospace_font_name: String = "/Gtk/MonospaceFontName" => "Monospace 10".to_string(),
+        pub antialias: i32 = "/Xft/Antialias" => 1,
+        pub hinting: i32 = "/Xft/Hinting" => 1,
+        pub hint_style: String = "/Xft/HintStyle" => "hintslight".to_string(),
+        /// Subpixel order for subpixel antialiasing
+        pub rgba: String = "/Xft/RGBA" => "none".to_string(),
+    }
+}
+
+/// A parsed Pango font description such as `"Cantarell Bold Italic 11"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontDescription {
+    pub family: String,
+    pub weight: Weight,
+    pub italic: bool,
+    pub points: f32,
+}
+
+impl FontDescription {
+    /// Parse `description`. Only the first of several comma-separated
+    /// families is kept, and a missing size means 10 points. Sizes may be
+    /// given in pixels with a `px` suffix.
+    pub fn parse(description: &str) -> Self {
+        let mut words: Vec<&str> = description.split_whitespace().collect();
+        let size = words.last().and_then(|word| match word.strip_suffix("px") {
+            Some(pixels) => pixels.parse::<f32>().ok().map(|pixels| pixels * 72.0 / 96.0),
+            None => word.parse::<f32>().ok(),
+        });
+        let points = match size {
+            Some(points) if points > 0.0 => {
+                words.pop();
+                points
+            }
+            _ => DEFAULT_POINTS,
+        };
+
+        let mut weight = Weight::Normal;
+        let mut italic = false;
+        while let Some(&word) = words.last() {
+            match style_word(word) {
+                Some(StyleWord::Weight(found)) => weight = found,
+                Some(StyleWord::Italic) => italic = true,
+                Some(StyleWord::Ignored) => {}
+                None => break,
+            }
+            words.pop();
+        }
+
+        let family = words.join(" ");
+        let family = family.split(',').next().unwrap_or_default().trim();
+        Self {
+            family: if family.is_empty() { "Sans".to_string() } else { family.to_string() },
+            weight,
+            italic,
+            points,
+        }
+    }
+
+    /// Text size in logical pixels, at 96 DPI
+    pub fn pixels(&self) -> f32 {
+        self.points * 96.0 / 72.0
+    }
+
+    pub fn to_font(&self) -> Font {
+        let family = match self.family.to_lowercase().as_str() {
+            "sans" | "sans-serif" => Family::SansSerif,
+            "serif" => Family::Serif,
+            "monospace" | "mono" => Family::Monospace,
+            _ => Family::Name(intern(&self.family)),
+        };
+        Font {
+            family,
+            weight: self.weight,
+            style: if self.italic { Style::Italic } else { Style::Normal },
+            ..Font::DEFAULT
+        }
+    }
+}
+
+enum StyleWord {
+    Weight(Weight),
+    Italic,
+    /// Stretch and variant words iced has no use for
+    Ignored,
+}
+
+fn style_word(word: &str) -> Option<StyleWord> {
+    let weight = match word.to_lowercase().as_str() {
+        "thin" => Weight::Thin,
+        "ultra-light" | "extra-light" => Weight::ExtraLight,
+        "light" => Weight::Light,
+        "regular" | "normal" | "book" => Weight::Normal,
+        "medium" => Weight::Medium,
+        "semi-bold" | "semibold" | "demi-bold" => Weight::Semibold,
+        "bold" => Weight::Bold,
+        "ultra-bold" | "extra-bold" => Weight::ExtraBold,
+        "heavy" | "black" => Weight::Black,
+        "italic" | "oblique" => return Some(StyleWord::Italic),
+        "condensed" | "expanded" | "small-caps" => return Some(StyleWord::Ignored),
+        _ => return None,
+    };
+    Some(StyleWord::Weight(weight))
+}
+
+/// Font families iced refers to by `&'static str`; each distinct name is
+/// leaked once
+fn intern(name: &str) -> &'static str {
+    static NAMES: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(Default::default);
+
+    let mut names = NAMES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(&interned) = names.get(name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// The fonts in use by this process
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveFonts {
+    pub config: FontConfig,
+    pub ui: FontDescription,
+    pub monospace: FontDescription,
+}
+
+impl ActiveFonts {
+    fn new(config: FontConfig) -> Self {
+        Self {
+            ui: FontDescription::parse(&config.font_name),
+            monospace: FontDescription::parse(&config.monospace_font_name),
+            config,
+        }
+    }
+}
+
+static ACTIVE: LazyLock<watch::Sender<ActiveFonts>> =
+    LazyLock::new(|| watch::Sender::new(ActiveFonts::new(FontConfig::default())));
+
+pub fn current() -> ActiveFonts {
+    ACTIVE.borrow().clone()
+}
+
+/// The interface font
+pub fn ui() -> Font {
+    ACTIVE.borrow().ui.to_font()
+}
+
+/// Size of the interface font in logical pixels
+pub fn text_size() -> f32 {
+    ACTIVE.borrow().ui.pixels()
+}
+
+pub fn monospace() -> Font {
+    ACTIVE.borrow().monospace.to_font()
+}
+
+pub fn monospace_size() -> f32 {
+    ACTIVE.borrow().monospace.pixels()
+}
+
+/// A text size designed against the default interface font, grown or
+/// shrunk with the configured one, e.g. `text(title).size(fonts::relative(20.0))`
+pub fn relative(base: f32) -> f32 {
+    base * ACTIVE.borrow().ui.points / DEFAULT_POINTS
+}
+
+/// iced settings with the configured default font and text size
+pub fn settings() -> iced::Settings {
+    let active = ACTIVE.borrow();
+    iced::Settings {
+        default_font: active.ui.to_font(),
+        default_text_size: active.ui.pixels().into(),
+        antialiasing: active.config.antialias != 0,
+        ..Default::default()
+    }
+}
+
+/// Make `config` the active font settings. Returns `false` if nothing
+/// changed.
+pub fn apply(config: FontConfig) -> bool {
+    ACTIVE.send_if_modified(|active| {
+        if active.config == config {
+            return false;
+        }
+        *active = ActiveFonts::new(config);
+        true
+    })
+}
+
+/// Apply the fonts configured in `config`
+pub async fn apply_from_config(config: &XfceConfig) -> bool {
+    apply(config.load_section::<FontConfig>().await)
+}
+
+/// Read the font settings before the iced application starts
+pub fn load() {
+    match tokio::runtime::Builder::new_current_thread().build() {
+        Ok(runtime) => {
+            runtime.block_on(apply_from_config(&XfceConfig::default()));
+        }
+        Err(e) => warn!("Failed to read the font settings: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pango_description() {
+        let font = FontDescription::parse("Noto Sans Semi-Bold Italic 11.5");
+        assert_eq!(font.family, "Noto Sans");
+        assert_eq!(font.weight, Weight::Semibold);
+        assert!(font.italic);
+        assert_eq!(font.points, 11.5);
+
+        let font = FontDescription::parse("Cantarell, Sans");
+        assert_eq!(font.family, "Cantarell");
+        assert_eq!(font.weight, Weight::Normal);
+        assert_eq!(font.points, 10.0);
+
+        assert_eq!(FontDescription::parse("Bold 9").family, "Sans");
+        assert_eq!(FontDescription::parse("Inter 16px").points, 12.0);
+    }
+
+    #[test]
+    fn test_generic_families_map_to_iced() {
+        assert_eq!(FontDescription::parse("Monospace 10").to_font().family, Family::Monospace);
+        assert_eq!(FontDescription::parse("sans-serif 10").to_font().family, Family::SansSerif);
+        assert_eq!(FontDescription::parse("Inter 10").to_font().family, Family::Name("Inter"));
+        assert!((FontDescription::parse("Sans 12").pixels() - 16.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,378 @@
+//! Controls for settings dialogs
+//!
+//! Toggle switches, spin buttons, color swatches and a shortcut capture
+//! field, drawn in the glass style so every settings app looks alike. Lay
+//! them out with [`form_row`] to line up the labels.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use iced::keyboard::{self, key, Key, Modifiers};
+use iced::widget::{button, container, row, space, text};
+use iced::{event, window, Alignment, Background, Border, Color, Element, Event, Length, Subscription};
+
+use crate::{colors, fonts, styles};
+
+/// Width of the label column in [`form_row`]
+pub const LABEL_WIDTH: f32 = 150.0;
+
+const SWITCH_WIDTH: f32 = 40.0;
+const SWITCH_HEIGHT: f32 = 22.0;
+const KNOB_SIZE: f32 = 16.0;
+const SWATCH_SIZE: f32 = 22.0;
+
+/// Label on the left, control on the right
+pub fn form_row<'a, Message: 'a>(label: &'a str, control: impl Into<Element<'a, Message>>) -> Element<'a, Message> {
+    row![
+        text(label).size(fonts::relative(14.0)).color(colors::text_secondary()).width(LABEL_WIDTH),
+        control.into(),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+/// On/off switch; clicking it reports the new state
+pub fn toggle_switch<'a, Message: Clone + 'a>(
+    is_on: bool,
+    on_toggle: impl Fn(bool) -> Message,
+) -> Element<'a, Message> {
+    let knob = container(space())
+        .width(KNOB_SIZE)
+        .height(KNOB_SIZE)
+        .style(|_theme| container::Style {
+            background: Some(Background::Color(colors::text_primary())),
+            border: Border {
+                color: Color::TRANSPARENT,
+                width: 0.0,
+                radius: (KNOB_SIZE / 2.0).into(),
+            },
+            ..Default::default()
+        });
+
+    let track = container(knob)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(if is_on { Alignment::End } else { Alignment::Start })
+        .align_y(Alignment::Center);
+
+    button(track)
+        .on_press(on_toggle(!is_on))
+        .width(SWITCH_WIDTH)
+        .height(SWITCH_HEIGHT)
+        .padding(3)
+        .style(move |theme, status| styles::switch_track(theme, status, is_on))
+        .into()
+}
+
+/// `value` moved by `steps` increments of `step`, kept inside `range`
+pub fn step_value(value: u32, range: &RangeInclusive<u32>, step: u32, steps: i32) -> u32 {
+    let delta = i64::from(step) * i64::from(steps);
+    let moved = (i64::from(value) + delta).clamp(i64::from(*range.start()), i64::from(*range.end()));
+    moved as u32
+}
+
+/// `value` with its unit between minus and plus buttons. The buttons stop
+/// at the ends of `range`.
+pub fn spin_button<'a, Message: Clone + 'a>(
+    value: u32,
+    range: RangeInclusive<u32>,
+    step: u32,
+    unit: &str,
+    on_change: impl Fn(u32) -> Message,
+) -> Element<'a, Message> {
+    let down = step_value(value, &range, step, -1);
+    let up = step_value(value, &range, step, 1);
+
+    let arrow = |label: &'static str, target: u32| {
+        button(text(label).size(fonts::relative(14.0)).align_x(Alignment::Center).width(Length::Fill))
+            .on_press_maybe((target != value).then(|| on_change(target)))
+            .width(32)
+            .padding([4, 0])
+            .style(|theme, status| styles::app_card(theme, status))
+    };
+
+    let shown = if unit.is_empty() { value.to_string() } else { format!("{} {}", value, unit) };
+
+    row![
+        arrow("−", down),
+        text(shown)
+            .size(fonts::relative(14.0))
+            .color(colors::text_primary())
+            .width(70)
+            .align_x(Alignment::Center),
+        arrow("+", up),
+    ]
+    .spacing(6)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+/// A row of color swatches; the one equal to `selected` is ringed
+pub fn color_swatches<'a, Message: Clone + 'a>(
+    choices: &[Color],
+    selected: Option<Color>,
+    on_pick: impl Fn(Color) -> Message,
+) -> Element<'a, Message> {
+    let swatches = choices.iter().map(|&color| {
+        let is_selected = selected == Some(color);
+        button(space())
+            .on_press(on_pick(color))
+            .width(SWATCH_SIZE)
+            .height(SWATCH_SIZE)
+            .style(move |theme, status| styles::color_swatch(theme, status, color, is_selected))
+            .into()
+    });
+
+    row(swatches).spacing(8).align_y(Alignment::Center).into()
+}
+
+/// A key with the modifiers held while pressing it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub super_key: bool,
+    /// Key name as GTK accelerators spell it, e.g. `t`, `F11` or `Return`
+    pub key: String,
+}
+
+impl Chord {
+    /// The chord for a key press, or `None` while only modifiers are held
+    pub fn from_key(key: &Key, modifiers: Modifiers) -> Option<Self> {
+        let name = match key.as_ref() {
+            Key::Character(c) => c.to_lowercase(),
+            Key::Named(named) => named_key(named)?,
+            Key::Unidentified => return None,
+        };
+
+        Some(Self {
+            control: modifiers.control(),
+            alt: modifiers.alt(),
+            shift: modifiers.shift(),
+            super_key: modifiers.logo(),
+            key: name,
+        })
+    }
+
+    /// Accelerator string in the format xfconf stores shortcuts in,
+    /// e.g. `<Primary><Alt>t`
+    pub fn accelerator(&self) -> String {
+        let mut out = String::new();
+        for (held, name) in [
+            (self.control, "<Primary>"),
+            (self.alt, "<Alt>"),
+            (self.shift, "<Shift>"),
+            (self.super_key, "<Super>"),
+        ] {
+            if held {
+                out.push_str(name);
+            }
+        }
+        out.push_str(&self.key);
+        out
+    }
+
+    /// Parse an accelerator written by [`Chord::accelerator`] or GTK
+    pub fn parse(accelerator: &str) -> Option<Self> {
+        let mut chord = Self {
+            control: false,
+            alt: false,
+            shift: false,
+            super_key: false,
+            key: String::new(),
+        };
+
+        let mut rest = accelerator.trim();
+        while let Some(tail) = rest.strip_prefix('<') {
+            let (modifier, tail) = tail.split_once('>')?;
+            match modifier.to_lowercase().as_str() {
+                "primary" | "control" | "ctrl" => chord.control = true,
+                "alt" | "mod1" => chord.alt = true,
+                "shift" => chord.shift = true,
+                "super" | "mod4" => chord.super_key = true,
+                _ => return None,
+            }
+            rest = tail;
+        }
+
+        if rest.is_empty() {
+            return None;
+        }
+        chord.key = rest.to_string();
+        Some(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    /// Human-readable form, e.g. `Ctrl+Alt+T`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.control, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+            (self.super_key, "Super+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        if self.key.chars().count() == 1 {
+            f.write_str(&self.key.to_uppercase())
+        } else {
+            f.write_str(&self.key)
+        }
+    }
+}
+
+/// GTK name of a non-character key; `None` for modifiers
+fn named_key(named: key::Named) -> Option<String> {
+    let name = match named {
+        key::Named::Control | key::Named::Alt | key::Named::Shift | key::Named::Super | key::Named::Meta => {
+            return None
+        }
+        key::Named::Enter => "Return",
+        key::Named::Space => "space",
+        key::Named::ArrowUp => "Up",
+        key::Named::ArrowDown => "Down",
+        key::Named::ArrowLeft => "Left",
+        key::Named::ArrowRight => "Right",
+        key::Named::PageUp => "Page_Up",
+        key::Named::PageDown => "Page_Down",
+        key::Named::PrintScreen => "Print",
+        key::Named::Backspace => "BackSpace",
+        other => return Some(format!("{:?}", other)),
+    };
+    Some(name.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShortcutEvent {
+    /// The field was clicked: start or stop recording
+    Toggle,
+    Pressed(Key, Modifiers),
+}
+
+/// Shortcut field state. While recording, the next chord replaces the
+/// current one; Escape cancels and Backspace clears the shortcut.
+#[derive(Debug, Clone, Default)]
+pub struct ShortcutField {
+    chord: Option<Chord>,
+    recording: bool,
+}
+
+impl ShortcutField {
+    pub fn new(chord: Option<Chord>) -> Self {
+        Self { chord, recording: false }
+    }
+
+    pub fn chord(&self) -> Option<&Chord> {
+        self.chord.as_ref()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Returns true if the shortcut changed
+    pub fn update(&mut self, event: ShortcutEvent) -> bool {
+        match event {
+            ShortcutEvent::Toggle => {
+                self.recording = !self.recording;
+                false
+            }
+            ShortcutEvent::Pressed(..) if !self.recording => false,
+            ShortcutEvent::Pressed(Key::Named(key::Named::Escape), modifiers) if modifiers.is_empty() => {
+                self.recording = false;
+                false
+            }
+            ShortcutEvent::Pressed(Key::Named(key::Named::Backspace), modifiers) if modifiers.is_empty() => {
+                self.recording = false;
+                self.chord.take().is_some()
+            }
+            ShortcutEvent::Pressed(key, modifiers) => {
+                let Some(chord) = Chord::from_key(&key, modifiers) else {
+                    return false;
+                };
+                self.recording = false;
+                let changed = self.chord.as_ref() != Some(&chord);
+                self.chord = Some(chord);
+                changed
+            }
+        }
+    }
+}
+
+/// Button showing the current shortcut, or a prompt while recording
+pub fn shortcut_field<'a, Message: Clone + 'a>(
+    state: &ShortcutField,
+    on_event: impl Fn(ShortcutEvent) -> Message,
+) -> Element<'a, Message> {
+    let (label, color) = match (state.recording, &state.chord) {
+        (true, _) => ("Press a shortcut…".to_string(), colors::accent_primary()),
+        (false, Some(chord)) => (chord.to_string(), colors::text_primary()),
+        (false, None) => ("Disabled".to_string(), colors::text_secondary()),
+    };
+
+    button(text(label).size(fonts::relative(14.0)).color(color))
+        .on_press(on_event(ShortcutEvent::Toggle))
+        .width(200)
+        .padding([6, 12])
+        .style(|theme, status| styles::app_card(theme, status))
+        .into()
+}
+
+/// Key presses to feed to a recording [`ShortcutField`]. Only subscribe
+/// while [`ShortcutField::is_recording`], so normal typing isn't swallowed.
+pub fn capture() -> Subscription<ShortcutEvent> {
+    event::listen_with(key_press)
+}
+
+fn key_press(event: Event, _status: event::Status, _window: window::Id) -> Option<ShortcutEvent> {
+    match event {
+        Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+            Some(ShortcutEvent::Pressed(key, modifiers))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_value_clamps_to_range() {
+        assert_eq!(step_value(4, &(1..=6), 1, 1), 5);
+        assert_eq!(step_value(6, &(1..=6), 1, 1), 6);
+        assert_eq!(step_value(2, &(0..=100), 5, -1), 0);
+    }
+
+    #[test]
+    fn test_chord_accelerator_round_trip() {
+        let chord = Chord::from_key(&Key::Character("T".into()), Modifiers::CTRL | Modifiers::ALT).unwrap();
+        assert_eq!(chord.accelerator(), "<Primary><Alt>t");
+        assert_eq!(chord.to_string(), "Ctrl+Alt+T");
+        assert_eq!(Chord::parse("<Primary><Alt>t"), Some(chord));
+
+        assert_eq!(Chord::from_key(&Key::Named(key::Named::Shift), Modifiers::SHIFT), None);
+        assert_eq!(Chord::parse("<Primary>"), None);
+    }
+
+    #[test]
+    fn test_shortcut_field_records_next_chord() {
+        let mut field = ShortcutField::default();
+        let press = |c: &str| ShortcutEvent::Pressed(Key::Character(c.into()), Modifiers::LOGO);
+
+        assert!(!field.update(press("e")));
+        field.update(ShortcutEvent::Toggle);
+        assert!(field.update(press("e")));
+        assert_eq!(field.chord().map(Chord::accelerator).as_deref(), Some("<Super>e"));
+        assert!(!field.is_recording());
+
+        field.update(ShortcutEvent::Toggle);
+        assert!(field.update(ShortcutEvent::Pressed(Key::Named(key::Named::Backspace), Modifiers::empty())));
+        assert_eq!(field.chord(), None);
+    }
+}
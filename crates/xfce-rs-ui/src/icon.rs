@@ -0,0 +1,255 @@
+//! Icons from the icon theme, loaded in the background
+//!
+//! [`icon`] never blocks the view: the first request for a `(name, size)`
+//! pair queues it on a loader thread and draws [`FALLBACK_GLYPH`] until the
+//! decoded handle lands in the cache. Apps add [`subscription`] so they
+//! re-render when icons arrive.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc as std_mpsc, LazyLock, Mutex};
+use std::thread;
+
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use iced::widget::{image, svg, text};
+use iced::{Element, Subscription};
+use tokio::sync::watch;
+use tracing::{debug, error};
+
+use crate::scale;
+
+/// Drawn while an icon loads or when none is found
+pub const FALLBACK_GLYPH: &str = "📦";
+
+const PIXMAP_DIR: &str = "/usr/share/pixmaps";
+
+/// Finds icon files for `.desktop` Icon keys, following the xfce4-panel
+/// fallback strategy:
+/// 1. Absolute path -> use directly
+/// 2. Icon theme lookup
+/// 3. Strip extension and try icon theme again
+/// 4. Look in the pixmap directories
+#[derive(Debug, Clone)]
+pub struct IconResolver {
+    pixmap_dirs: Vec<PathBuf>,
+}
+
+impl Default for IconResolver {
+    fn default() -> Self {
+        Self {
+            pixmap_dirs: vec![PathBuf::from(PIXMAP_DIR)],
+        }
+    }
+}
+
+impl IconResolver {
+    pub fn with_pixmap_dirs(pixmap_dirs: Vec<PathBuf>) -> Self {
+        Self { pixmap_dirs }
+    }
+
+    /// Path of a drawable file for `name_or_path` at `size` pixels
+    pub fn resolve(&self, name_or_path: &str, size: u16) -> Option<PathBuf> {
+        let path = Path::new(name_or_path);
+        if path.is_absolute() {
+            return (path.exists() && is_supported(path)).then(|| path.to_path_buf());
+        }
+
+        if let Some(found) = lookup_theme(name_or_path, size) {
+            return Some(found);
+        }
+
+        // e.g. "app.png" -> "app"
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name_or_path);
+        if stem != name_or_path {
+            if let Some(found) = lookup_theme(stem, size) {
+                return Some(found);
+            }
+        }
+
+        self.pixmap_dirs
+            .iter()
+            .flat_map(|dir| ["svg", "png"].map(|ext| dir.join(format!("{}.{}", stem, ext))))
+            .find(|candidate| candidate.exists())
+    }
+}
+
+fn lookup_theme(name: &str, size: u16) -> Option<PathBuf> {
+    linicon::lookup_icon(name)
+        .with_size(size)
+        .filter_map(Result::ok)
+        .map(|found| found.path)
+        .find(|path| is_supported(path))
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+fn is_supported(path: &Path) -> bool {
+    matches!(extension(path).as_str(), "svg" | "png" | "jpg" | "jpeg")
+}
+
+/// An icon ready to draw
+#[derive(Debug, Clone)]
+pub enum IconHandle {
+    Svg(svg::Handle),
+    Raster(image::Handle),
+}
+
+impl IconHandle {
+    /// Read `path` and decode it by extension. PNGs are decoded to RGBA
+    /// here so the renderer doesn't have to.
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        let handle = match extension(path).as_str() {
+            "svg" => IconHandle::Svg(svg::Handle::from_memory(bytes)),
+            "png" => {
+                let (width, height, pixels) = decode_png(&bytes)?;
+                IconHandle::Raster(image::Handle::from_rgba(width, height, pixels))
+            }
+            _ => IconHandle::Raster(image::Handle::from_bytes(bytes)),
+        };
+        Some(handle)
+    }
+}
+
+/// Decode a PNG to 8-bit RGBA
+fn decode_png(bytes: &[u8]) -> Option<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).ok()?;
+    buffer.truncate(frame.buffer_size());
+
+    let pixels = match frame.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return None,
+    };
+    Some((frame.width, frame.height, pixels))
+}
+
+/// Icon name or path and size in pixels
+type CacheKey = (String, u16);
+
+enum Slot {
+    Loading,
+    Ready(Option<IconHandle>),
+}
+
+static CACHE: LazyLock<Mutex<HashMap<CacheKey, Slot>>> = LazyLock::new(Default::default);
+
+/// Bumped every time an icon finishes loading
+static LOADED: LazyLock<watch::Sender<u64>> = LazyLock::new(|| watch::Sender::new(0));
+
+static QUEUE: LazyLock<std_mpsc::Sender<CacheKey>> = LazyLock::new(|| {
+    let (sender, receiver) = std_mpsc::channel::<CacheKey>();
+    let spawned = thread::Builder::new().name("icon-loader".into()).spawn(move || {
+        let resolver = IconResolver::default();
+        for (name, size) in receiver {
+            let handle = resolver.resolve(&name, size).and_then(|path| IconHandle::load(&path));
+            if handle.is_none() {
+                debug!("No icon found for {} at {}px", name, size);
+            }
+            CACHE
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert((name, size), Slot::Ready(handle));
+            LOADED.send_modify(|generation| *generation += 1);
+        }
+    });
+    if let Err(e) = spawned {
+        error!("Failed to start icon loader: {}", e);
+    }
+    sender
+});
+
+/// The cached icon for `name_or_path`, queueing a load on first use.
+/// `None` while loading or if the icon doesn't exist.
+pub fn lookup(name_or_path: &str, size: u16) -> Option<IconHandle> {
+    if name_or_path.is_empty() {
+        return None;
+    }
+
+    let key = (name_or_path.to_string(), size);
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    match cache.get(&key) {
+        Some(Slot::Ready(handle)) => handle.clone(),
+        Some(Slot::Loading) => None,
+        None => {
+            cache.insert(key.clone(), Slot::Loading);
+            let _ = QUEUE.send(key);
+            None
+        }
+    }
+}
+
+/// Square icon of `size` logical pixels, or the fallback glyph until it
+/// loads. The file is picked for the [`scale`](crate::scale)d size so it
+/// stays sharp on high-DPI screens.
+pub fn icon<'a, Message: 'a>(name_or_path: &str, size: u16) -> Element<'a, Message> {
+    let side = f32::from(size);
+    match lookup(name_or_path, scale::current().pixels(size)) {
+        Some(IconHandle::Svg(handle)) => svg(handle).width(side).height(side).into(),
+        Some(IconHandle::Raster(handle)) => image(handle).width(side).height(side).into(),
+        None => text(FALLBACK_GLYPH).size(side).into(),
+    }
+}
+
+/// Fires whenever queued icons finish loading
+pub fn subscription() -> Subscription<()> {
+    Subscription::run(follow_loads)
+}
+
+fn follow_loads() -> impl Stream<Item = ()> {
+    iced::stream::channel(1, async |mut output: mpsc::Sender<()>| {
+        let mut loaded = LOADED.subscribe();
+        while loaded.changed().await.is_ok() {
+            if output.send(()).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_png_expands_to_rgba() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+        }
+
+        let (width, height, pixels) = decode_png(&bytes).unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(pixels, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_resolver_falls_back_to_pixmaps() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("xfce-rs-test-app.svg"), "<svg/>").unwrap();
+
+        let resolver = IconResolver::with_pixmap_dirs(vec![dir.path().to_path_buf()]);
+        assert_eq!(
+            resolver.resolve("xfce-rs-test-app.png", 32),
+            Some(dir.path().join("xfce-rs-test-app.svg"))
+        );
+        assert_eq!(resolver.resolve("xfce-rs-missing-app", 32), None);
+    }
+}
@@ -0,0 +1,64 @@
+//! One window per app, however often it is launched
+//!
+//! [`claim`] runs before the app starts: a later launch hands its
+//! arguments to the running instance and exits. The running instance
+//! hears of each such launch through [`activations`] and usually answers
+//! with [`raise`]:
+//!
+//! ```ignore
+//! instance::claim("org.xfce_rs.Navigator", &[]);
+//! ...
+//! instance::activations().map(Message::Activated)
+//! ...
+//! Message::Activated(_) => instance::raise(),
+//! ```
+
+use std::sync::{Mutex, OnceLock};
+
+use iced::futures::channel::mpsc;
+use iced::futures::SinkExt;
+use iced::{window, Subscription, Task};
+use tracing::warn;
+use xfce_rs_ipc::single_instance::PrimaryInstance;
+use xfce_rs_ipc::Activation;
+
+/// The claimed instance, until [`activations`] takes it over
+static INSTANCE: Mutex<Option<PrimaryInstance>> = Mutex::new(None);
+
+/// Become the running instance of `app_id`, or hand `uris` to the one
+/// already running and exit. Without a session bus the app starts anyway.
+pub fn claim(app_id: &str, uris: &[String]) {
+    // Serves the bus name for as long as the app runs
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    let runtime = RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().expect("Failed to start Tokio runtime"));
+    match runtime.block_on(xfce_rs_ipc::ensure_single_instance(app_id, uris)) {
+        Ok(instance) => *INSTANCE.lock().unwrap_or_else(|e| e.into_inner()) = Some(instance),
+        Err(e) => warn!("Single-instance check failed, starting anyway: {}", e),
+    }
+}
+
+/// Later launches of the app, once [`claim`]ed
+pub fn activations() -> Subscription<Activation> {
+    Subscription::run(follow_activations)
+}
+
+fn follow_activations() -> impl iced::futures::Stream<Item = Activation> {
+    iced::stream::channel(4, async |mut output: mpsc::Sender<Activation>| {
+        let Some(mut instance) = INSTANCE.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        while let Some(activation) = instance.next_activation().await {
+            if output.send(activation).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Show the app's window again, unminimized and focused
+pub fn raise<Message>() -> Task<Message>
+where
+    Message: Send + 'static,
+{
+    window::latest().and_then(|id| Task::batch([window::minimize(id, false), window::gain_focus(id)]))
+}
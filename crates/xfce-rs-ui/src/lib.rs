@@ -1,57 +1,118 @@
-/// Design System Constants - Dark Gray Slate Glass Theme
+pub mod animation;
+pub mod chrome;
+pub mod combo;
+pub mod focus;
+pub mod fonts;
+pub mod form;
+pub mod icon;
+pub mod instance;
+pub mod menu;
+pub mod scale;
+pub mod theme;
+
+pub use combo::combo_box_searchable;
+pub use icon::icon;
+
+/// Colors of the active theme, see [`theme`]
 pub mod colors {
     use iced::Color;
 
+    use super::theme::current;
+
     // Glassmorphism base colors
-    pub const GLASS_BASE: Color = Color::from_rgba(0.07, 0.07, 0.08, 0.96);
-    // near-black slate
+    pub fn glass_base() -> Color {
+        current().bg_primary
+    }
 
     // Shine effects (neutral, soft)
-    pub const SHINE_WHITE: Color = Color::from_rgba(0.8, 0.8, 0.8, 0.08);
-    pub const SHINE_TRANSPARENT: Color = Color::from_rgba(1.0, 1.0, 1.0, 0.0);
+    pub fn shine_white() -> Color {
+        current().shine
+    }
+
+    pub fn shine_transparent() -> Color {
+        Color { a: 0.0, ..current().shine }
+    }
 
     // UI Elements
-    pub const BG_CARD: Color = Color::from_rgba(0.14, 0.15, 0.17, 0.92);
-    pub const BG_CARD_HOVER: Color = Color::from_rgba(0.55, 0.60, 0.70, 0.18);
-    pub const BG_INPUT: Color = Color::from_rgba(0.10, 0.11, 0.13, 0.75);
+    pub fn bg_card() -> Color {
+        current().bg_card
+    }
+
+    pub fn bg_card_hover() -> Color {
+        current().bg_hover
+    }
 
-    // Accents (cool slate)
-    pub const ACCENT_PRIMARY: Color = Color::from_rgb(0.65, 0.70, 0.80);
-    pub const ACCENT_GLOW: Color = Color::from_rgba(0.65, 0.70, 0.80, 0.35);
+    pub fn bg_input() -> Color {
+        current().bg_input
+    }
+
+    // Accents
+    pub fn accent_primary() -> Color {
+        current().accent
+    }
+
+    pub fn accent_glow() -> Color {
+        current().accent_glow
+    }
 
     // Text
-    pub const TEXT_PRIMARY: Color = Color::from_rgb(0.95, 0.95, 0.95);
-    pub const TEXT_SECONDARY: Color = Color::from_rgb(0.72, 0.74, 0.78);
+    pub fn text_primary() -> Color {
+        current().text_primary
+    }
+
+    pub fn text_secondary() -> Color {
+        current().text_secondary
+    }
 
     // Borders
-    pub const GLASS_BORDER: Color = Color::from_rgba(1.0, 1.0, 1.0, 0.20);
-    
+    pub fn glass_border() -> Color {
+        current().border
+    }
+
     // Window Controls
-    pub const CONTROL_CLOSE: Color = Color::from_rgb(0.9, 0.35, 0.35);
-    pub const CONTROL_MIN: Color = Color::from_rgb(0.9, 0.7, 0.3);
-    pub const CONTROL_MAX: Color = Color::from_rgb(0.3, 0.7, 0.4);
+    pub fn control_close() -> Color {
+        current().danger
+    }
+
+    pub fn control_min() -> Color {
+        current().warning
+    }
+
+    pub fn control_max() -> Color {
+        current().success
+    }
 }
 
 /// Custom Styles for Iced Widgets
 pub mod styles {
     use iced::widget::{button, container, text_input};
     use iced::{Background, Color, Vector, Border, Shadow, gradient, Radians};
-    use super::colors;
+    use super::{colors, theme};
+
+    /// `base` scaled by the corner radius setting
+    fn corner(base: f32) -> iced::border::Radius {
+        theme::style().radius(base).into()
+    }
+
+    /// `base` scaled by the shadow strength setting
+    fn shadow(base: Shadow) -> Shadow {
+        theme::style().shadow(base)
+    }
 
     /// Base layer of the glass
     pub fn glass_base(_theme: &iced::Theme) -> container::Style {
         container::Style {
-            background: Some(Background::Color(colors::GLASS_BASE)),
+            background: Some(Background::Color(colors::glass_base())),
             border: Border {
-                color: colors::GLASS_BORDER,
+                color: colors::glass_border(),
                 width: 1.0,
-                radius: 20.0.into(),
+                radius: corner(20.0),
             },
-            shadow: Shadow {
+            shadow: shadow(Shadow {
                 color: Color::from_rgba(0.0, 0.0, 0.0, 0.5),
                 offset: Vector::new(0.0, 12.0),
                 blur_radius: 40.0,
-            },
+            }),
             ..Default::default()
         }
     }
@@ -59,15 +120,15 @@ pub mod styles {
     /// Top-down highlight
     pub fn glass_highlight_top(_theme: &iced::Theme) -> container::Style {
         let gradient = gradient::Linear::new(Radians(1.5708)) // 90 degrees
-            .add_stop(0.0, colors::SHINE_WHITE)
-            .add_stop(0.1, colors::SHINE_TRANSPARENT);
+            .add_stop(0.0, colors::shine_white())
+            .add_stop(0.1, colors::shine_transparent());
 
         container::Style {
             background: Some(Background::Gradient(iced::Gradient::Linear(gradient))),
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
-                radius: 20.0.into(),
+                radius: corner(20.0),
             },
             ..Default::default()
         }
@@ -76,15 +137,15 @@ pub mod styles {
     /// Bottom-up highlight
     pub fn glass_highlight_bottom(_theme: &iced::Theme) -> container::Style {
         let gradient = gradient::Linear::new(Radians(4.7124)) // 270 degrees
-            .add_stop(0.0, colors::SHINE_WHITE)
-            .add_stop(0.1, colors::SHINE_TRANSPARENT);
+            .add_stop(0.0, colors::shine_white())
+            .add_stop(0.1, colors::shine_transparent());
 
         container::Style {
             background: Some(Background::Gradient(iced::Gradient::Linear(gradient))),
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
-                radius: 20.0.into(),
+                radius: corner(20.0),
             },
             ..Default::default()
         }
@@ -93,15 +154,15 @@ pub mod styles {
     /// Left highlight
     pub fn glass_highlight_left(_theme: &iced::Theme) -> container::Style {
         let gradient = gradient::Linear::new(Radians(0.0)) // 0 degrees (left to right)
-            .add_stop(0.0, colors::SHINE_WHITE)
-            .add_stop(0.1, colors::SHINE_TRANSPARENT);
+            .add_stop(0.0, colors::shine_white())
+            .add_stop(0.1, colors::shine_transparent());
 
         container::Style {
             background: Some(Background::Gradient(iced::Gradient::Linear(gradient))),
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
-                radius: 20.0.into(),
+                radius: corner(20.0),
             },
             ..Default::default()
         }
@@ -110,15 +171,15 @@ pub mod styles {
     /// Right highlight
     pub fn glass_highlight_right(_theme: &iced::Theme) -> container::Style {
         let gradient = gradient::Linear::new(Radians(3.1416)) // 180 degrees (right to left)
-            .add_stop(0.0, colors::SHINE_WHITE)
-            .add_stop(0.1, colors::SHINE_TRANSPARENT);
+            .add_stop(0.0, colors::shine_white())
+            .add_stop(0.1, colors::shine_transparent());
 
         container::Style {
             background: Some(Background::Gradient(iced::Gradient::Linear(gradient))),
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
-                radius: 20.0.into(),
+                radius: corner(20.0),
             },
             ..Default::default()
         }
@@ -127,24 +188,24 @@ pub mod styles {
     /// Styled search input
     pub fn search_input(_theme: &iced::Theme, status: text_input::Status) -> text_input::Style {
         let base = text_input::Style {
-            background: Background::Color(colors::BG_INPUT),
+            background: Background::Color(colors::bg_input()),
             border: Border {
-                color: colors::GLASS_BORDER,
+                color: colors::glass_border(),
                 width: 1.0,
-                radius: 12.0.into(),
+                radius: corner(12.0),
             },
-            icon: colors::TEXT_SECONDARY,
-            placeholder: colors::TEXT_SECONDARY,
-            value: colors::TEXT_PRIMARY,
-            selection: colors::ACCENT_PRIMARY,
+            icon: colors::text_secondary(),
+            placeholder: colors::text_secondary(),
+            value: colors::text_primary(),
+            selection: colors::accent_primary(),
         };
 
         match status {
             text_input::Status::Focused { .. } => text_input::Style {
                 border: Border {
-                    color: colors::ACCENT_PRIMARY,
+                    color: colors::accent_primary(),
                     width: 1.5,
-                    radius: 12.0.into(),
+                    radius: corner(12.0),
                 },
                 ..base
             },
@@ -155,42 +216,42 @@ pub mod styles {
     pub fn app_card(_theme: &iced::Theme, status: button::Status) -> button::Style {
         match status {
             button::Status::Active => button::Style {
-                background: Some(Background::Color(colors::BG_CARD)),
-                text_color: colors::TEXT_PRIMARY,
+                background: Some(Background::Color(colors::bg_card())),
+                text_color: colors::text_primary(),
                 border: Border {
-                    color: colors::GLASS_BORDER,
+                    color: colors::glass_border(),
                     width: 1.0,
-                    radius: 14.0.into(),
+                    radius: corner(14.0),
                 },
-                shadow: Shadow {
+                shadow: shadow(Shadow {
                     color: Color::from_rgba(0.0, 0.0, 0.0, 0.2),
                     offset: Vector::new(0.0, 4.0),
                     blur_radius: 8.0,
-                },
+                }),
                 ..Default::default()
             },
             button::Status::Hovered => button::Style {
-                background: Some(Background::Color(colors::BG_CARD_HOVER)),
-                text_color: colors::TEXT_PRIMARY,
+                background: Some(Background::Color(colors::bg_card_hover())),
+                text_color: colors::text_primary(),
                 border: Border {
-                    color: colors::ACCENT_PRIMARY,
+                    color: colors::accent_primary(),
                     width: 1.0,
-                    radius: 14.0.into(),
+                    radius: corner(14.0),
                 },
-                shadow: Shadow {
-                    color: colors::ACCENT_GLOW,
+                shadow: shadow(Shadow {
+                    color: colors::accent_glow(),
                     offset: Vector::new(0.0, 0.0),
                     blur_radius: 16.0,
-                },
+                }),
                 ..Default::default()
             },
             button::Status::Pressed => button::Style {
-                background: Some(Background::Color(colors::BG_CARD)),
-                text_color: colors::TEXT_PRIMARY,
+                background: Some(Background::Color(colors::bg_card())),
+                text_color: colors::text_primary(),
                 border: Border {
-                    color: colors::ACCENT_PRIMARY,
+                    color: colors::accent_primary(),
                     width: 1.0,
-                    radius: 14.0.into(),
+                    radius: corner(14.0),
                 },
                 shadow: Shadow::default(),
                 ..Default::default()
@@ -199,6 +260,99 @@ pub mod styles {
         }
     }
 
+    /// Popup surface of a context menu
+    pub fn menu_surface(_theme: &iced::Theme) -> container::Style {
+        container::Style {
+            background: Some(Background::Color(colors::glass_base())),
+            border: Border {
+                color: colors::glass_border(),
+                width: 1.0,
+                radius: corner(10.0),
+            },
+            shadow: shadow(Shadow {
+                color: Color::from_rgba(0.0, 0.0, 0.0, 0.4),
+                offset: Vector::new(0.0, 6.0),
+                blur_radius: 18.0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Context menu entry; `highlighted` follows the pointer and arrow keys
+    pub fn menu_item(_theme: &iced::Theme, status: button::Status, highlighted: bool, enabled: bool) -> button::Style {
+        let hovered = highlighted || matches!(status, button::Status::Hovered | button::Status::Pressed);
+        button::Style {
+            background: (enabled && hovered).then(|| Background::Color(colors::bg_card_hover())),
+            text_color: if enabled { colors::text_primary() } else { colors::text_secondary() },
+            border: Border {
+                color: Color::TRANSPARENT,
+                width: 0.0,
+                radius: corner(6.0),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// `style` with a keyboard focus ring when `focused`: an accent border
+    /// and glow, so the focused control stands out from hovered ones
+    pub fn with_focus_ring(style: button::Style, focused: bool) -> button::Style {
+        if !focused {
+            return style;
+        }
+        button::Style {
+            border: Border {
+                color: colors::accent_primary(),
+                width: 2.0,
+                ..style.border
+            },
+            shadow: Shadow {
+                color: colors::accent_glow(),
+                offset: Vector::new(0.0, 0.0),
+                blur_radius: 12.0,
+            },
+            ..style
+        }
+    }
+
+    /// Track of a toggle switch, accent-filled when on
+    pub fn switch_track(_theme: &iced::Theme, status: button::Status, is_on: bool) -> button::Style {
+        let hovered = matches!(status, button::Status::Hovered | button::Status::Pressed);
+        let background = match (is_on, hovered) {
+            (true, _) => colors::accent_primary(),
+            (false, true) => colors::bg_card_hover(),
+            (false, false) => colors::bg_input(),
+        };
+        button::Style {
+            background: Some(Background::Color(background)),
+            border: Border {
+                color: if is_on { Color::TRANSPARENT } else { colors::glass_border() },
+                width: 1.0,
+                radius: 100.0.into(),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Color swatch; the selected one gets an accent ring
+    pub fn color_swatch(_theme: &iced::Theme, status: button::Status, color: Color, selected: bool) -> button::Style {
+        let ring = if selected {
+            colors::accent_primary()
+        } else if matches!(status, button::Status::Hovered) {
+            colors::text_secondary()
+        } else {
+            colors::glass_border()
+        };
+        button::Style {
+            background: Some(Background::Color(color)),
+            border: Border {
+                color: ring,
+                width: if selected { 2.5 } else { 1.0 },
+                radius: corner(6.0),
+            },
+            ..Default::default()
+        }
+    }
+
     /// Window Control Button (Mac-style blobs)
     pub fn window_control(_theme: &iced::Theme, status: button::Status, color: Color) -> button::Style {
         let base = button::Style {
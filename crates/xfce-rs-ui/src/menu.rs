@@ -0,0 +1,445 @@
+//! Context menus drawn as an overlay layer
+//!
+//! A [`ContextMenu`] holds its items and which one is highlighted. The app
+//! keeps it in its state while open, stacks [`ContextMenu::view`] on top of
+//! its content and feeds the resulting [`MenuEvent`]s back through
+//! [`ContextMenu::update`], which reports the chosen item's message.
+//! Menus open away from the window edges so they are never clipped.
+
+use iced::keyboard::{self, key, Key};
+use iced::widget::{button, column, container, mouse_area, responsive, row, space, text, Stack};
+use iced::{event, window, Element, Event, Length, Padding, Point, Size, Subscription};
+
+use crate::{colors, fonts, styles};
+
+pub const MENU_WIDTH: f32 = 200.0;
+pub const ITEM_HEIGHT: f32 = 32.0;
+pub const SEPARATOR_HEIGHT: f32 = 9.0;
+/// Space between the menu border and its items
+pub const MENU_PADDING: f32 = 5.0;
+
+/// One entry of a menu
+#[derive(Debug, Clone)]
+pub enum MenuItem<Message> {
+    Action {
+        label: String,
+        message: Message,
+        enabled: bool,
+    },
+    Submenu {
+        label: String,
+        items: Vec<MenuItem<Message>>,
+    },
+    Separator,
+}
+
+impl<Message> MenuItem<Message> {
+    pub fn action(label: impl Into<String>, message: Message) -> Self {
+        MenuItem::Action {
+            label: label.into(),
+            message,
+            enabled: true,
+        }
+    }
+
+    pub fn submenu(label: impl Into<String>, items: Vec<MenuItem<Message>>) -> Self {
+        MenuItem::Submenu {
+            label: label.into(),
+            items,
+        }
+    }
+
+    pub fn separator() -> Self {
+        MenuItem::Separator
+    }
+
+    /// Grey the item out; only affects actions
+    pub fn disabled(mut self) -> Self {
+        if let MenuItem::Action { enabled, .. } = &mut self {
+            *enabled = false;
+        }
+        self
+    }
+
+    fn is_selectable(&self) -> bool {
+        match self {
+            MenuItem::Action { enabled, .. } => *enabled,
+            MenuItem::Submenu { items, .. } => !items.is_empty(),
+            MenuItem::Separator => false,
+        }
+    }
+
+    fn height(&self) -> f32 {
+        match self {
+            MenuItem::Separator => SEPARATOR_HEIGHT,
+            _ => ITEM_HEIGHT,
+        }
+    }
+}
+
+/// Input for [`ContextMenu::update`]. Paths index into nested submenus,
+/// e.g. `[2, 0]` is the first item of the submenu at position 2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuEvent {
+    Hover(Vec<usize>),
+    Click(Vec<usize>),
+    Up,
+    Down,
+    /// Enter the highlighted submenu
+    Open,
+    /// Leave the innermost submenu
+    Back,
+    /// Choose the highlighted item
+    Activate,
+    Dismiss,
+}
+
+/// What the app should do after [`ContextMenu::update`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuOutcome<Message> {
+    /// Keep the menu open
+    Open,
+    /// The user chose an item; close the menu and handle its message
+    Selected(Message),
+    Dismissed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextMenu<Message> {
+    anchor: Point,
+    items: Vec<MenuItem<Message>>,
+    /// Path of the highlighted item; submenus along it are open
+    highlight: Vec<usize>,
+}
+
+impl<Message: Clone> ContextMenu<Message> {
+    /// Menu opening at `anchor`, usually the pointer position
+    pub fn new(anchor: Point, items: Vec<MenuItem<Message>>) -> Self {
+        Self {
+            anchor,
+            items,
+            highlight: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, event: MenuEvent) -> MenuOutcome<Message> {
+        match event {
+            MenuEvent::Hover(path) => {
+                if self.item(&path).is_some_and(MenuItem::is_selectable) {
+                    self.highlight = path;
+                }
+                MenuOutcome::Open
+            }
+            MenuEvent::Click(path) => {
+                if !self.item(&path).is_some_and(MenuItem::is_selectable) {
+                    return MenuOutcome::Open;
+                }
+                self.highlight = path;
+                self.activate()
+            }
+            MenuEvent::Up => {
+                self.step(false);
+                MenuOutcome::Open
+            }
+            MenuEvent::Down => {
+                self.step(true);
+                MenuOutcome::Open
+            }
+            MenuEvent::Open => {
+                self.enter_submenu();
+                MenuOutcome::Open
+            }
+            MenuEvent::Back => {
+                if self.highlight.len() > 1 {
+                    self.highlight.pop();
+                }
+                MenuOutcome::Open
+            }
+            MenuEvent::Activate => self.activate(),
+            MenuEvent::Dismiss => MenuOutcome::Dismissed,
+        }
+    }
+
+    fn activate(&mut self) -> MenuOutcome<Message> {
+        match self.item(&self.highlight) {
+            Some(MenuItem::Action {
+                message, enabled: true, ..
+            }) => MenuOutcome::Selected(message.clone()),
+            Some(MenuItem::Submenu { .. }) => {
+                self.enter_submenu();
+                MenuOutcome::Open
+            }
+            _ => MenuOutcome::Open,
+        }
+    }
+
+    fn enter_submenu(&mut self) {
+        if let Some(MenuItem::Submenu { items, .. }) = self.item(&self.highlight) {
+            if let Some(first) = items.iter().position(MenuItem::is_selectable) {
+                self.highlight.push(first);
+            }
+        }
+    }
+
+    /// Move the highlight within the innermost level, wrapping around
+    fn step(&mut self, forward: bool) {
+        let Some(current) = self.highlight.last().copied() else {
+            if let Some(first) = self.items.iter().position(MenuItem::is_selectable) {
+                self.highlight.push(first);
+            }
+            return;
+        };
+
+        let depth = self.highlight.len() - 1;
+        let items = self.level(&self.highlight[..depth]);
+        let count = items.len();
+        let next = (1..=count)
+            .map(|offset| if forward { (current + offset) % count } else { (current + count - offset) % count })
+            .find(|&i| items[i].is_selectable());
+        if let Some(next) = next {
+            self.highlight[depth] = next;
+        }
+    }
+
+    /// Items of the menu reached by following `path` through submenus
+    fn level(&self, path: &[usize]) -> &[MenuItem<Message>] {
+        path.iter().fold(&self.items[..], |items, &i| match items.get(i) {
+            Some(MenuItem::Submenu { items, .. }) => items.as_slice(),
+            _ => &[],
+        })
+    }
+
+    fn item(&self, path: &[usize]) -> Option<&MenuItem<Message>> {
+        let (last, parents) = path.split_last()?;
+        self.level(parents).get(*last)
+    }
+
+    /// Top-left corners of the root menu and each open submenu
+    pub fn layout(&self, bounds: Size) -> Vec<Point> {
+        let mut origins = vec![place(self.anchor, menu_size(&self.items), bounds)];
+        for depth in 0..self.highlight.len() {
+            let parent = &self.highlight[..=depth];
+            let Some(MenuItem::Submenu { items, .. }) = self.item(parent) else {
+                break;
+            };
+            let siblings = self.level(&self.highlight[..depth]);
+            let row_y = origins[depth].y + MENU_PADDING + item_offset(siblings, parent[depth]);
+            origins.push(place_beside(origins[depth].x, row_y, menu_size(items), bounds));
+        }
+        origins
+    }
+
+    /// The menu and a transparent layer that dismisses it when clicked.
+    /// Stack it above the app's content.
+    pub fn view<'a>(&'a self, on_event: impl Fn(MenuEvent) -> Message + 'a) -> Element<'a, Message>
+    where
+        Message: 'a,
+    {
+        responsive(move |bounds| {
+            let mut layers: Vec<Element<'a, Message>> = vec![mouse_area(
+                container(space()).width(Length::Fill).height(Length::Fill),
+            )
+            .on_press(on_event(MenuEvent::Dismiss))
+            .on_right_press(on_event(MenuEvent::Dismiss))
+            .into()];
+
+            for (depth, origin) in self.layout(bounds).into_iter().enumerate() {
+                let path = &self.highlight[..depth];
+                let panel = self.view_level(path, &on_event);
+                layers.push(
+                    container(panel)
+                        .padding(Padding {
+                            top: origin.y,
+                            left: origin.x,
+                            right: 0.0,
+                            bottom: 0.0,
+                        })
+                        .into(),
+                );
+            }
+            Stack::with_children(layers).into()
+        })
+        .into()
+    }
+
+    fn view_level<'a>(&'a self, path: &[usize], on_event: &impl Fn(MenuEvent) -> Message) -> Element<'a, Message> {
+        let highlighted = self.highlight.get(path.len()).copied();
+        let rows = self.level(path).iter().enumerate().map(|(i, item)| -> Element<'a, Message> {
+            let mut item_path = path.to_vec();
+            item_path.push(i);
+
+            let (label, selectable, arrow) = match item {
+                MenuItem::Separator => {
+                    return container(
+                        container(space())
+                            .width(Length::Fill)
+                            .height(1)
+                            .style(|_| container::Style {
+                                background: Some(colors::glass_border().into()),
+                                ..Default::default()
+                            }),
+                    )
+                    .center_y(SEPARATOR_HEIGHT)
+                    .into();
+                }
+                MenuItem::Action { label, enabled, .. } => (label, *enabled, ""),
+                MenuItem::Submenu { label, items } => (label, !items.is_empty(), "›"),
+            };
+
+            let is_highlighted = highlighted == Some(i);
+            let mut entry = button(
+                row![
+                    text(label.as_str()).size(fonts::relative(14.0)).width(Length::Fill),
+                    text(arrow).size(fonts::relative(14.0)),
+                ]
+                .align_y(iced::Alignment::Center),
+            )
+            .width(Length::Fill)
+            .height(ITEM_HEIGHT)
+            .padding([6, 12])
+            .style(move |theme, status| styles::menu_item(theme, status, is_highlighted, selectable));
+            if selectable {
+                entry = entry.on_press(on_event(MenuEvent::Click(item_path.clone())));
+            }
+
+            mouse_area(entry).on_enter(on_event(MenuEvent::Hover(item_path))).into()
+        });
+
+        container(column(rows))
+            .width(MENU_WIDTH)
+            .padding(MENU_PADDING)
+            .style(|theme| styles::menu_surface(theme))
+            .into()
+    }
+}
+
+/// Arrow keys move and open or close submenus, Enter chooses and Escape
+/// closes. Subscribe only while a menu is open.
+pub fn keyboard() -> Subscription<MenuEvent> {
+    event::listen_with(menu_key)
+}
+
+fn menu_key(event: Event, _status: event::Status, _window: window::Id) -> Option<MenuEvent> {
+    let Event::Keyboard(keyboard::Event::KeyPressed { key, .. }) = event else {
+        return None;
+    };
+    match key {
+        Key::Named(key::Named::ArrowUp) => Some(MenuEvent::Up),
+        Key::Named(key::Named::ArrowDown) => Some(MenuEvent::Down),
+        Key::Named(key::Named::ArrowRight) => Some(MenuEvent::Open),
+        Key::Named(key::Named::ArrowLeft) => Some(MenuEvent::Back),
+        Key::Named(key::Named::Enter) => Some(MenuEvent::Activate),
+        Key::Named(key::Named::Escape) => Some(MenuEvent::Dismiss),
+        _ => None,
+    }
+}
+
+fn menu_size<Message>(items: &[MenuItem<Message>]) -> Size {
+    let content: f32 = items.iter().map(MenuItem::height).sum();
+    Size::new(MENU_WIDTH, content + 2.0 * MENU_PADDING)
+}
+
+fn item_offset<Message>(items: &[MenuItem<Message>], index: usize) -> f32 {
+    items.iter().take(index).map(MenuItem::height).sum()
+}
+
+/// Open down and to the right of `anchor`, flipping to the other side of
+/// it on either axis that would overflow `bounds`
+pub fn place(anchor: Point, size: Size, bounds: Size) -> Point {
+    let x = if anchor.x + size.width > bounds.width {
+        anchor.x - size.width
+    } else {
+        anchor.x
+    };
+    let y = if anchor.y + size.height > bounds.height {
+        anchor.y - size.height
+    } else {
+        anchor.y
+    };
+    clamp(Point::new(x, y), size, bounds)
+}
+
+/// Open a submenu to the right of its parent, or to the left if there is
+/// no room, aligned with the parent row at `row_y`
+pub fn place_beside(parent_x: f32, row_y: f32, size: Size, bounds: Size) -> Point {
+    let right = parent_x + MENU_WIDTH;
+    let x = if right + size.width > bounds.width {
+        parent_x - size.width
+    } else {
+        right
+    };
+    let y = (row_y - MENU_PADDING).min(bounds.height - size.height);
+    clamp(Point::new(x, y), size, bounds)
+}
+
+/// Keep as much of the menu visible as fits, favoring its top-left corner
+fn clamp(origin: Point, size: Size, bounds: Size) -> Point {
+    Point::new(
+        origin.x.min(bounds.width - size.width).max(0.0),
+        origin.y.min(bounds.height - size.height).max(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds() -> Size {
+        Size::new(800.0, 600.0)
+    }
+
+    fn menu() -> ContextMenu<u8> {
+        ContextMenu::new(
+            Point::new(10.0, 10.0),
+            vec![
+                MenuItem::action("Open", 1),
+                MenuItem::separator(),
+                MenuItem::action("Remove", 2).disabled(),
+                MenuItem::submenu("Send to", vec![MenuItem::action("Desktop", 3), MenuItem::action("Mail", 4)]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_place_flips_at_edges() {
+        let size = Size::new(200.0, 100.0);
+        assert_eq!(place(Point::new(10.0, 10.0), size, bounds()), Point::new(10.0, 10.0));
+        assert_eq!(place(Point::new(750.0, 550.0), size, bounds()), Point::new(550.0, 450.0));
+        // Too tall either way: pinned to the top
+        assert_eq!(place(Point::new(10.0, 50.0), Size::new(200.0, 700.0), bounds()), Point::new(10.0, 0.0));
+        // Submenu of a menu near the right edge opens to its left
+        assert_eq!(place_beside(650.0, 40.0, size, bounds()), Point::new(450.0, 35.0));
+    }
+
+    #[test]
+    fn test_keyboard_navigation_skips_separators_and_disabled() {
+        let mut menu = menu();
+        menu.update(MenuEvent::Down);
+        assert_eq!(menu.highlight, vec![0]);
+        menu.update(MenuEvent::Down);
+        assert_eq!(menu.highlight, vec![3]);
+        menu.update(MenuEvent::Down);
+        assert_eq!(menu.highlight, vec![0]);
+        menu.update(MenuEvent::Up);
+        assert_eq!(menu.highlight, vec![3]);
+
+        menu.update(MenuEvent::Open);
+        assert_eq!(menu.highlight, vec![3, 0]);
+        menu.update(MenuEvent::Down);
+        assert_eq!(menu.update(MenuEvent::Activate), MenuOutcome::Selected(4));
+        menu.update(MenuEvent::Back);
+        assert_eq!(menu.highlight, vec![3]);
+        assert_eq!(menu.update(MenuEvent::Click(vec![2])), MenuOutcome::Open);
+        assert_eq!(menu.update(MenuEvent::Dismiss), MenuOutcome::Dismissed);
+    }
+
+    #[test]
+    fn test_layout_places_open_submenu_beside_its_row() {
+        let mut menu = menu();
+        menu.update(MenuEvent::Hover(vec![3]));
+        let origins = menu.layout(bounds());
+        assert_eq!(origins.len(), 2);
+        let row_y = 10.0 + MENU_PADDING + ITEM_HEIGHT + SEPARATOR_HEIGHT + ITEM_HEIGHT;
+        assert_eq!(origins[1], Point::new(10.0 + MENU_WIDTH, row_y - MENU_PADDING));
+    }
+}
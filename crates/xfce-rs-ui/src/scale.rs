@@ -0,0 +1,140 @@
+//! Interface scale for high-DPI displays
+//!
+//! iced multiplies every logical size (fonts, paddings, borders, icons) by
+//! the factor an app returns from its `scale_factor` callback, so apps
+//! return [`factor`] there, or [`factor_for`] from a daemon whose windows
+//! may differ. [`icon`](crate::icon()) rasterizes at the scaled size so
+//! icons stay sharp.
+//!
+//! The factor is `/ui-scale` in the `xfce-rs-appearance` channel. At `0`,
+//! the default, it is 1.0 and the interface follows the monitor's own
+//! scale, which iced already applies. The theme
+//! [`subscription`](crate::theme::subscription) keeps it current.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use iced::window;
+use tokio::sync::watch;
+use xfce_rs_config::{config_section, XfceConfig};
+
+pub const MIN_SCALE: f64 = 0.5;
+pub const MAX_SCALE: f64 = 4.0;
+
+config_section! {
+    /// Size of the interface
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct ScaleConfig in "xfce-rs-appearance" {
+        /// Multiplies every size; 0 leaves it to the monitor
+        pub ui_scale: f64 = "/ui-scale" => 0.0,
+    }
+}
+
+/// Factor between the designed and the drawn size of the interface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UiScale(f64);
+
+impl Default for UiScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl UiScale {
+    /// `factor` limited to [`MIN_SCALE`]..=[`MAX_SCALE`]
+    pub fn new(factor: f64) -> Self {
+        if factor.is_finite() && factor > 0.0 {
+            Self(factor.clamp(MIN_SCALE, MAX_SCALE))
+        } else {
+            Self::default()
+        }
+    }
+
+    /// The configured scale, or 1.0 when it is `0` or less. The monitor's
+    /// scale is not part of it; iced applies that on its own.
+    pub fn resolve(configured: f64) -> Self {
+        if configured > 0.0 {
+            Self::new(configured)
+        } else {
+            Self::default()
+        }
+    }
+
+    /// The factor as iced's `scale_factor` callbacks take it
+    pub fn factor(self) -> f32 {
+        self.0 as f32
+    }
+
+    /// Device pixels for `size` logical pixels, for images rasterized
+    /// before iced scales them
+    pub fn pixels(self, size: u16) -> u16 {
+        (f64::from(size) * self.0).round().min(f64::from(u16::MAX)) as u16
+    }
+}
+
+static GLOBAL: LazyLock<watch::Sender<UiScale>> = LazyLock::new(|| watch::Sender::new(UiScale::default()));
+
+static OVERRIDES: LazyLock<Mutex<HashMap<window::Id, UiScale>>> = LazyLock::new(Default::default);
+
+/// Scale of this process's windows
+pub fn current() -> UiScale {
+    *GLOBAL.borrow()
+}
+
+/// For iced's `scale_factor` callback
+pub fn factor() -> f32 {
+    current().factor()
+}
+
+/// For a daemon's per-window `scale_factor` callback
+pub fn factor_for(window: window::Id) -> f32 {
+    let overrides = OVERRIDES.lock().unwrap_or_else(|e| e.into_inner());
+    overrides.get(&window).copied().unwrap_or_else(current).factor()
+}
+
+/// Draw `window` at `scale` regardless of the global one, or follow the
+/// global scale again with `None`
+pub fn set_override(window: window::Id, scale: Option<UiScale>) {
+    let mut overrides = OVERRIDES.lock().unwrap_or_else(|e| e.into_inner());
+    match scale {
+        Some(scale) => overrides.insert(window, scale),
+        None => overrides.remove(&window),
+    };
+}
+
+/// Make `scale` the global scale. Returns `false` if nothing changed.
+pub fn apply(scale: UiScale) -> bool {
+    GLOBAL.send_if_modified(|current| {
+        if *current == scale {
+            return false;
+        }
+        *current = scale;
+        true
+    })
+}
+
+/// Apply the scale configured in `config`
+pub async fn apply_from_config(config: &XfceConfig) -> bool {
+    apply(UiScale::resolve(config.load_section::<ScaleConfig>().await.ui_scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_only_scales_when_configured() {
+        assert_eq!(UiScale::resolve(1.5).factor(), 1.5);
+        assert_eq!(UiScale::resolve(0.0).factor(), 1.0);
+        assert_eq!(UiScale::resolve(-1.0).factor(), 1.0);
+        assert_eq!(UiScale::resolve(20.0).factor(), MAX_SCALE as f32);
+        assert_eq!(UiScale::new(f64::NAN), UiScale::default());
+    }
+
+    #[test]
+    fn test_pixels_round_to_device_size() {
+        assert_eq!(UiScale::new(1.5).pixels(32), 48);
+        assert_eq!(UiScale::new(1.25).pixels(22), 28);
+        assert_eq!(UiScale::default().pixels(16), 16);
+    }
+}
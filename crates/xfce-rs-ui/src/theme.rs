@@ -0,0 +1,609 @@
+//! Runtime themes
+//!
+//! The active [`Palette`] lives in a process-wide slot that [`colors`](crate::colors)
+//! and [`styles`](crate::styles) read on every draw, so replacing it restyles
+//! every widget on the next frame. Apps follow the user's choice with
+//! [`subscription`] and return [`iced_theme`] from their `theme` function.
+//!
+//! Themes are selected by name in the `xfce-rs-theme` channel:
+//!
+//! ```text
+//! /theme                          "dark", "light", "high-contrast" or a custom name
+//! /custom/<name>/base             built-in theme the custom one starts from
+//! /custom/<name>/<token>          "#rrggbb" or "#rrggbbaa", e.g. /custom/ocean/accent
+//! ```
+//!
+//! The `xfce-rs-appearance` channel holds a [`StyleConfig`] laid over
+//! whichever theme is active: accent hue, glass opacity, corner radius and
+//! shadow strength.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, LazyLock};
+
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use iced::{Color, Shadow, Subscription};
+use tokio::sync::{broadcast, watch};
+use tracing::warn;
+use xfce_rs_config::{config_section, ConfigError, ConfigValue, Rgba, XfceConfig};
+
+use crate::{fonts, scale};
+
+pub const THEME_CHANNEL: &str = "xfce-rs-theme";
+pub const THEME_PROPERTY: &str = "/theme";
+pub const CUSTOM_PREFIX: &str = "/custom/";
+pub const DEFAULT_THEME: &str = "dark";
+pub const APPEARANCE_CHANNEL: &str = "xfce-rs-appearance";
+
+/// Semantic color roles, named as they appear in custom themes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Token {
+    BgPrimary,
+    BgCard,
+    BgHover,
+    BgInput,
+    Accent,
+    AccentGlow,
+    TextPrimary,
+    TextSecondary,
+    Border,
+    Shine,
+    Danger,
+    Warning,
+    Success,
+}
+
+impl Token {
+    pub const ALL: [Token; 13] = [
+        Token::BgPrimary,
+        Token::BgCard,
+        Token::BgHover,
+        Token::BgInput,
+        Token::Accent,
+        Token::AccentGlow,
+        Token::TextPrimary,
+        Token::TextSecondary,
+        Token::Border,
+        Token::Shine,
+        Token::Danger,
+        Token::Warning,
+        Token::Success,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Token::BgPrimary => "bg.primary",
+            Token::BgCard => "bg.card",
+            Token::BgHover => "bg.hover",
+            Token::BgInput => "bg.input",
+            Token::Accent => "accent",
+            Token::AccentGlow => "accent.glow",
+            Token::TextPrimary => "text.primary",
+            Token::TextSecondary => "text.secondary",
+            Token::Border => "border",
+            Token::Shine => "shine",
+            Token::Danger => "danger",
+            Token::Warning => "warning",
+            Token::Success => "success",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|token| token.name() == name)
+    }
+}
+
+/// Colors for every [`Token`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    /// Whether widgets without a token, like scrollbars, use iced's dark look
+    pub is_dark: bool,
+    pub bg_primary: Color,
+    pub bg_card: Color,
+    pub bg_hover: Color,
+    pub bg_input: Color,
+    pub accent: Color,
+    pub accent_glow: Color,
+    pub text_primary: Color,
+    pub text_secondary: Color,
+    pub border: Color,
+    pub shine: Color,
+    pub danger: Color,
+    pub warning: Color,
+    pub success: Color,
+}
+
+impl Palette {
+    /// Dark gray slate glass, the original XFCE.rs look
+    pub const DARK: Palette = Palette {
+        is_dark: true,
+        bg_primary: Color::from_rgba(0.07, 0.07, 0.08, 0.96),
+        bg_card: Color::from_rgba(0.14, 0.15, 0.17, 0.92),
+        bg_hover: Color::from_rgba(0.55, 0.60, 0.70, 0.18),
+        bg_input: Color::from_rgba(0.10, 0.11, 0.13, 0.75),
+        accent: Color::from_rgb(0.65, 0.70, 0.80),
+        accent_glow: Color::from_rgba(0.65, 0.70, 0.80, 0.35),
+        text_primary: Color::from_rgb(0.95, 0.95, 0.95),
+        text_secondary: Color::from_rgb(0.72, 0.74, 0.78),
+        border: Color::from_rgba(1.0, 1.0, 1.0, 0.20),
+        shine: Color::from_rgba(0.8, 0.8, 0.8, 0.08),
+        danger: Color::from_rgb(0.9, 0.35, 0.35),
+        warning: Color::from_rgb(0.9, 0.7, 0.3),
+        success: Color::from_rgb(0.3, 0.7, 0.4),
+    };
+
+    /// Frosted white glass with slate blue accents
+    pub const LIGHT: Palette = Palette {
+        is_dark: false,
+        bg_primary: Color::from_rgba(0.96, 0.96, 0.97, 0.96),
+        bg_card: Color::from_rgba(1.0, 1.0, 1.0, 0.92),
+        bg_hover: Color::from_rgba(0.35, 0.45, 0.60, 0.14),
+        bg_input: Color::from_rgba(0.90, 0.91, 0.93, 0.85),
+        accent: Color::from_rgb(0.25, 0.38, 0.60),
+        accent_glow: Color::from_rgba(0.25, 0.38, 0.60, 0.30),
+        text_primary: Color::from_rgb(0.10, 0.10, 0.12),
+        text_secondary: Color::from_rgb(0.36, 0.38, 0.42),
+        border: Color::from_rgba(0.0, 0.0, 0.0, 0.14),
+        shine: Color::from_rgba(1.0, 1.0, 1.0, 0.50),
+        danger: Color::from_rgb(0.80, 0.22, 0.22),
+        warning: Color::from_rgb(0.85, 0.58, 0.10),
+        success: Color::from_rgb(0.20, 0.58, 0.30),
+    };
+
+    /// Opaque black and white with yellow focus, no translucency
+    pub const HIGH_CONTRAST: Palette = Palette {
+        is_dark: true,
+        bg_primary: Color::from_rgb(0.0, 0.0, 0.0),
+        bg_card: Color::from_rgb(0.05, 0.05, 0.05),
+        bg_hover: Color::from_rgba(1.0, 1.0, 0.0, 0.25),
+        bg_input: Color::from_rgb(0.0, 0.0, 0.0),
+        accent: Color::from_rgb(1.0, 1.0, 0.0),
+        accent_glow: Color::from_rgba(1.0, 1.0, 0.0, 0.50),
+        text_primary: Color::from_rgb(1.0, 1.0, 1.0),
+        text_secondary: Color::from_rgb(0.9, 0.9, 0.9),
+        border: Color::from_rgb(1.0, 1.0, 1.0),
+        shine: Color::from_rgba(1.0, 1.0, 1.0, 0.0),
+        danger: Color::from_rgb(1.0, 0.3, 0.3),
+        warning: Color::from_rgb(1.0, 0.8, 0.0),
+        success: Color::from_rgb(0.3, 1.0, 0.4),
+    };
+
+    pub fn get(&self, token: Token) -> Color {
+        match token {
+            Token::BgPrimary => self.bg_primary,
+            Token::BgCard => self.bg_card,
+            Token::BgHover => self.bg_hover,
+            Token::BgInput => self.bg_input,
+            Token::Accent => self.accent,
+            Token::AccentGlow => self.accent_glow,
+            Token::TextPrimary => self.text_primary,
+            Token::TextSecondary => self.text_secondary,
+            Token::Border => self.border,
+            Token::Shine => self.shine,
+            Token::Danger => self.danger,
+            Token::Warning => self.warning,
+            Token::Success => self.success,
+        }
+    }
+
+    pub fn set(&mut self, token: Token, color: Color) {
+        let slot = match token {
+            Token::BgPrimary => &mut self.bg_primary,
+            Token::BgCard => &mut self.bg_card,
+            Token::BgHover => &mut self.bg_hover,
+            Token::BgInput => &mut self.bg_input,
+            Token::Accent => &mut self.accent,
+            Token::AccentGlow => &mut self.accent_glow,
+            Token::TextPrimary => &mut self.text_primary,
+            Token::TextSecondary => &mut self.text_secondary,
+            Token::Border => &mut self.border,
+            Token::Shine => &mut self.shine,
+            Token::Danger => &mut self.danger,
+            Token::Warning => &mut self.warning,
+            Token::Success => &mut self.success,
+        };
+        *slot = color;
+    }
+
+    /// An iced theme whose built-in widgets match this palette
+    pub fn to_iced_theme(&self, name: &str) -> iced::Theme {
+        let base = if self.is_dark {
+            iced::theme::Palette::DARK
+        } else {
+            iced::theme::Palette::LIGHT
+        };
+        let palette = iced::theme::Palette {
+            background: Color { a: 1.0, ..self.bg_primary },
+            text: self.text_primary,
+            primary: self.accent,
+            success: self.success,
+            danger: self.danger,
+            ..base
+        };
+        iced::Theme::custom(name.to_string(), palette)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::DARK
+    }
+}
+
+/// Parse a token value, either a stored color or a `#rrggbb[aa]` string
+pub fn parse_color(value: &ConfigValue) -> Option<Color> {
+    let rgba = match value {
+        ConfigValue::Color(rgba) => *rgba,
+        ConfigValue::String(hex) => Rgba::from_hex(hex.trim())?,
+        _ => return None,
+    };
+    Some(Color::from_rgba(
+        rgba.red as f32,
+        rgba.green as f32,
+        rgba.blue as f32,
+        rgba.alpha as f32,
+    ))
+}
+
+/// Built-in and user-defined themes by name
+#[derive(Debug, Clone)]
+pub struct ThemeRegistry {
+    themes: BTreeMap<String, Palette>,
+}
+
+impl Default for ThemeRegistry {
+    fn default() -> Self {
+        let themes = [
+            ("dark", Palette::DARK),
+            ("light", Palette::LIGHT),
+            ("high-contrast", Palette::HIGH_CONTRAST),
+        ]
+        .into_iter()
+        .map(|(name, palette)| (name.to_string(), palette))
+        .collect();
+        Self { themes }
+    }
+}
+
+impl ThemeRegistry {
+    /// Built-in themes plus the custom ones defined in `config`
+    pub async fn load(config: &XfceConfig) -> Self {
+        let properties = config.list_properties(THEME_CHANNEL).await.unwrap_or_default();
+        let mut entries = Vec::new();
+        for property in properties.into_iter().filter(|p| p.starts_with(CUSTOM_PREFIX)) {
+            if let Ok(value) = config.get_property(THEME_CHANNEL, &property).await {
+                entries.push((property, value));
+            }
+        }
+        Self::default().with_custom(entries)
+    }
+
+    /// Add custom themes from `/custom/<name>/<token>` properties. Tokens
+    /// are laid over `/custom/<name>/base`, the built-in theme of the same
+    /// name, or the default theme, in that order.
+    pub fn with_custom<I>(mut self, properties: I) -> Self
+    where
+        I: IntoIterator<Item = (String, ConfigValue)>,
+    {
+        let mut bases: BTreeMap<String, String> = BTreeMap::new();
+        let mut tokens: BTreeMap<String, Vec<(Token, Color)>> = BTreeMap::new();
+
+        for (property, value) in properties {
+            let Some((name, key)) = property
+                .strip_prefix(CUSTOM_PREFIX)
+                .and_then(|rest| rest.split_once('/'))
+            else {
+                continue;
+            };
+            if key == "base" {
+                if let ConfigValue::String(base) = value {
+                    bases.insert(name.to_string(), base);
+                }
+                continue;
+            }
+            match (Token::from_name(key), parse_color(&value)) {
+                (Some(token), Some(color)) => tokens.entry(name.to_string()).or_default().push((token, color)),
+                _ => warn!("Ignoring theme property {}: {:?}", property, value),
+            }
+        }
+
+        let names: BTreeSet<String> = bases.keys().chain(tokens.keys()).cloned().collect();
+        for name in names {
+            let mut palette = bases
+                .get(&name)
+                .and_then(|base| self.get(base))
+                .or_else(|| self.get(&name))
+                .unwrap_or_default();
+            for (token, color) in tokens.remove(&name).unwrap_or_default() {
+                palette.set(token, color);
+            }
+            self.themes.insert(name, palette);
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Palette> {
+        self.themes.get(name).copied()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, palette: Palette) {
+        self.themes.insert(name.into(), palette);
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.keys().map(String::as_str)
+    }
+
+    /// The theme named in `/theme`, falling back to the default one
+    pub async fn selected(&self, config: &XfceConfig) -> (String, Palette) {
+        let name = config
+            .get_string(THEME_CHANNEL, THEME_PROPERTY)
+            .await
+            .unwrap_or_else(|_| DEFAULT_THEME.to_string());
+        match self.get(&name) {
+            Some(palette) => (name, palette),
+            None => {
+                warn!("Unknown theme {}, using {}", name, DEFAULT_THEME);
+                (DEFAULT_THEME.to_string(), Palette::DARK)
+            }
+        }
+    }
+}
+
+config_section! {
+    /// Look adjustments that apply on top of any theme
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct StyleConfig in "xfce-rs-appearance" {
+        /// Hue of the accent color in degrees; negative keeps the theme's
+        pub accent_hue: f64 = "/accent-hue" => -1.0,
+        /// Multiplies the opacity of glass surfaces, 0.0 to 1.0
+        pub opacity: f64 = "/opacity" => 1.0,
+        /// Multiplies every corner radius; 0.0 gives square corners
+        pub corner_radius: f64 = "/corner-radius" => 1.0,
+        /// Multiplies shadow darkness and blur; 0.0 turns shadows off
+        pub shadow_strength: f64 = "/shadow-strength" => 1.0,
+    }
+}
+
+impl StyleConfig {
+    /// `palette` with the accent rotated to `accent_hue` and the glass
+    /// surfaces made more transparent
+    pub fn apply(&self, mut palette: Palette) -> Palette {
+        if self.accent_hue >= 0.0 {
+            let hue = self.accent_hue.rem_euclid(360.0) as f32;
+            palette.accent = with_hue(palette.accent, hue);
+            palette.accent_glow = with_hue(palette.accent_glow, hue);
+        }
+
+        let opacity = self.opacity.clamp(0.0, 1.0) as f32;
+        for color in [&mut palette.bg_primary, &mut palette.bg_card, &mut palette.bg_input] {
+            color.a *= opacity;
+        }
+        palette
+    }
+
+    /// A corner radius the styles use as `base` at the default setting
+    pub fn radius(&self, base: f32) -> f32 {
+        base * self.corner_radius.max(0.0) as f32
+    }
+
+    pub fn shadow(&self, shadow: Shadow) -> Shadow {
+        let strength = self.shadow_strength.max(0.0) as f32;
+        Shadow {
+            color: Color {
+                a: (shadow.color.a * strength).min(1.0),
+                ..shadow.color
+            },
+            blur_radius: shadow.blur_radius * strength,
+            ..shadow
+        }
+    }
+}
+
+/// `color` with its HSL hue replaced, keeping saturation, lightness and alpha
+fn with_hue(color: Color, hue: f32) -> Color {
+    let max = color.r.max(color.g).max(color.b);
+    let min = color.r.min(color.g).min(color.b);
+    let lightness = (max + min) / 2.0;
+    let saturation = if max == min {
+        0.0
+    } else {
+        (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+    };
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    Color::from_rgba(r + m, g + m, b + m, color.a)
+}
+
+/// The theme in use by this process
+#[derive(Debug, Clone)]
+pub struct ActiveTheme {
+    pub name: String,
+    /// The theme's palette with the style applied
+    pub palette: Palette,
+    pub style: StyleConfig,
+    base: Palette,
+    iced: iced::Theme,
+}
+
+impl ActiveTheme {
+    fn new(name: &str, base: Palette, style: StyleConfig) -> Self {
+        let palette = style.apply(base);
+        Self {
+            name: name.to_string(),
+            palette,
+            style,
+            base,
+            iced: palette.to_iced_theme(name),
+        }
+    }
+}
+
+static ACTIVE: LazyLock<watch::Sender<ActiveTheme>> = LazyLock::new(|| {
+    watch::Sender::new(ActiveTheme::new(DEFAULT_THEME, Palette::DARK, StyleConfig::default()))
+});
+
+/// Palette of the active theme
+pub fn current() -> Palette {
+    ACTIVE.borrow().palette
+}
+
+/// Style adjustments in effect
+pub fn style() -> StyleConfig {
+    ACTIVE.borrow().style
+}
+
+/// Name of the active theme
+pub fn current_name() -> String {
+    ACTIVE.borrow().name.clone()
+}
+
+/// The active theme for iced's `theme` callback
+pub fn iced_theme() -> iced::Theme {
+    ACTIVE.borrow().iced.clone()
+}
+
+/// Make `palette` the active theme, keeping the current style. Returns
+/// `false` if nothing changed.
+pub fn apply(name: &str, palette: Palette) -> bool {
+    apply_with_style(name, palette, style())
+}
+
+/// Make `palette` the active theme with `style` laid over it
+pub fn apply_with_style(name: &str, palette: Palette, style: StyleConfig) -> bool {
+    ACTIVE.send_if_modified(|active| {
+        if active.name == name && active.base == palette && active.style == style {
+            return false;
+        }
+        *active = ActiveTheme::new(name, palette, style);
+        true
+    })
+}
+
+/// Stream of theme switches in this process
+pub fn changes() -> watch::Receiver<ActiveTheme> {
+    ACTIVE.subscribe()
+}
+
+/// Apply the theme and style selected in `config`
+pub async fn apply_from_config(config: &XfceConfig) -> bool {
+    let (name, palette) = ThemeRegistry::load(config).await.selected(config).await;
+    let style = config.load_section::<StyleConfig>().await;
+    apply_with_style(&name, palette, style)
+}
+
+/// Names of the built-in and custom themes, for a theme selector
+pub async fn available() -> Vec<String> {
+    ThemeRegistry::load(&XfceConfig::default()).await.names().map(str::to_string).collect()
+}
+
+/// Switch every XFCE.rs app to the theme `name`
+pub async fn select(name: String) -> Result<(), ConfigError> {
+    let config = XfceConfig::default();
+    config.set_property(THEME_CHANNEL, THEME_PROPERTY, ConfigValue::String(name)).await?;
+    config.save().await
+}
+
+/// Follow the theme, style, [`scale`](crate::scale) and [`fonts`](crate::fonts)
+/// settings, yielding the theme name whenever any of them changes.
+/// Handling the message is enough to re-render with the new colors.
+pub fn subscription() -> Subscription<String> {
+    Subscription::run(follow_config)
+}
+
+fn follow_config() -> impl Stream<Item = String> {
+    iced::stream::channel(4, async |mut output: mpsc::Sender<String>| {
+        let config = Arc::new(XfceConfig::default());
+        let _reloader = match config.watch_files() {
+            Ok(reloader) => Some(reloader),
+            Err(e) => {
+                warn!("Theme changes will need a restart: {}", e);
+                None
+            }
+        };
+        let mut theme_changes = config.watch(THEME_CHANNEL, "");
+        let mut style_changes = config.watch(APPEARANCE_CHANNEL, "");
+        let mut xsettings_changes = config.watch(fonts::XSETTINGS_CHANNEL, "");
+
+        loop {
+            let theme_changed = apply_from_config(&config).await;
+            let scale_changed = scale::apply_from_config(&config).await;
+            let fonts_changed = fonts::apply_from_config(&config).await;
+            if (theme_changed || scale_changed || fonts_changed) && output.send(current_name()).await.is_err() {
+                break;
+            }
+            let received = tokio::select! {
+                received = theme_changes.recv() => received,
+                received = style_changes.recv() => received,
+                received = xsettings_changes.recv() => received,
+            };
+            if let Err(broadcast::error::RecvError::Closed) = received {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_names_round_trip() {
+        for token in Token::ALL {
+            assert_eq!(Token::from_name(token.name()), Some(token));
+        }
+        assert_eq!(Token::from_name("bg.secondary"), None);
+    }
+
+    #[test]
+    fn test_custom_theme_overlays_base() {
+        let registry = ThemeRegistry::default().with_custom([
+            ("/custom/ocean/base".to_string(), ConfigValue::String("light".into())),
+            ("/custom/ocean/accent".to_string(), ConfigValue::String("#0080ff".into())),
+            ("/custom/ocean/text.secondary".to_string(), ConfigValue::Integer(3)),
+            ("/custom/night/bg.primary".to_string(), ConfigValue::String("#000000cc".into())),
+        ]);
+
+        let ocean = registry.get("ocean").unwrap();
+        assert!((ocean.accent.g - 128.0 / 255.0).abs() < 1e-6);
+        assert_eq!(ocean.accent.b, 1.0);
+        assert_eq!(ocean.text_secondary, Palette::LIGHT.text_secondary);
+        assert!(!ocean.is_dark);
+
+        let night = registry.get("night").unwrap();
+        assert!((night.bg_primary.a - 0.8).abs() < 1e-6);
+        assert_eq!(night.accent, Palette::DARK.accent);
+    }
+
+    #[test]
+    fn test_style_config_adjusts_palette() {
+        let style = StyleConfig {
+            accent_hue: 120.0,
+            opacity: 0.5,
+            ..StyleConfig::default()
+        };
+        let palette = style.apply(Palette::LIGHT);
+        assert!(palette.accent.g > palette.accent.r && palette.accent.g > palette.accent.b);
+        assert!((palette.bg_card.a - Palette::LIGHT.bg_card.a * 0.5).abs() < 1e-6);
+        assert_eq!(palette.text_primary, Palette::LIGHT.text_primary);
+
+        assert_eq!(StyleConfig::default().apply(Palette::DARK), Palette::DARK);
+        let square = StyleConfig {
+            corner_radius: 0.0,
+            ..StyleConfig::default()
+        };
+        assert_eq!(square.radius(20.0), 0.0);
+    }
+}
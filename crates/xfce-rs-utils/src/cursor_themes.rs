@@ -0,0 +1,164 @@
+//! Installed XCursor themes
+//!
+//! A cursor theme is a directory in one of the icon search paths that holds
+//! a `cursors/` subdirectory. Its `index.theme` may give a display name and
+//! the themes it inherits missing cursors from. The search path follows
+//! libXcursor: `$XCURSOR_PATH` if set, otherwise the user and system icon
+//! directories.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// Cursor size libXcursor uses when none is configured
+pub const DEFAULT_CURSOR_SIZE: u32 = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorTheme {
+    /// Directory name, which is what `XCURSOR_THEME` expects
+    pub name: String,
+    /// `Name=` from `index.theme`, or the directory name
+    pub display_name: String,
+    pub path: PathBuf,
+    /// Themes that fill in cursors this one lacks
+    pub inherits: Vec<String>,
+}
+
+/// Directories searched for cursor themes, most specific first
+pub fn search_dirs() -> Vec<PathBuf> {
+    if let Ok(path) = std::env::var("XCURSOR_PATH") {
+        return std::env::split_paths(&path).collect();
+    }
+
+    let mut dirs = Vec::new();
+    if let Some(data) = dirs::data_dir() {
+        dirs.push(data.join("icons"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".icons"));
+    }
+    dirs.push(PathBuf::from("/usr/share/icons"));
+    dirs.push(PathBuf::from("/usr/share/pixmaps"));
+    dirs
+}
+
+/// Cursor themes in `dirs`, such as [`search_dirs`], sorted by display
+/// name. A theme found in an earlier directory hides one of the same name
+/// in a later one.
+pub fn list_cursor_themes_in(dirs: &[PathBuf]) -> Vec<CursorTheme> {
+    let mut seen = HashSet::new();
+    let mut themes = Vec::new();
+
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.join("cursors").is_dir() {
+                continue;
+            }
+            let Some(name) = path.file_name().and_then(|n| n.to_str()).map(str::to_string) else {
+                continue;
+            };
+            if seen.insert(name.clone()) {
+                themes.push(read_theme(name, path));
+            }
+        }
+    }
+
+    themes.sort_by_key(|theme| theme.display_name.to_lowercase());
+    themes
+}
+
+/// Whether a theme called `name` is installed in `dirs`
+pub fn is_installed(name: &str, dirs: &[PathBuf]) -> bool {
+    dirs.iter().any(|dir| dir.join(name).join("cursors").is_dir())
+}
+
+fn read_theme(name: String, path: PathBuf) -> CursorTheme {
+    let index = fs::read_to_string(path.join("index.theme")).unwrap_or_default();
+    let (display_name, inherits) = parse_index(&index);
+    CursorTheme {
+        display_name: display_name.unwrap_or_else(|| name.clone()),
+        name,
+        path,
+        inherits,
+    }
+}
+
+/// `Name=` and `Inherits=` from the `[Icon Theme]` group of an index file
+fn parse_index(contents: &str) -> (Option<String>, Vec<String>) {
+    let mut in_group = false;
+    let mut name = None;
+    let mut inherits = Vec::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_group = line == "[Icon Theme]";
+            continue;
+        }
+        if !in_group {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "Name" => name = Some(value.trim().to_string()),
+            "Inherits" => {
+                inherits = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|theme| !theme.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+    (name, inherits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn make_theme(dir: &Path, name: &str, index: Option<&str>) {
+        fs::create_dir_all(dir.join(name).join("cursors")).unwrap();
+        if let Some(index) = index {
+            fs::write(dir.join(name).join("index.theme"), index).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_list_cursor_themes() {
+        let user = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        make_theme(user.path(), "Bibata", Some("[Icon Theme]\nName=Bibata Modern\nInherits=Adwaita, hicolor\n"));
+        make_theme(system.path(), "Bibata", None);
+        make_theme(system.path(), "Adwaita", None);
+        // An icon theme without cursors
+        fs::create_dir_all(system.path().join("hicolor").join("48x48")).unwrap();
+
+        let dirs = vec![user.path().to_path_buf(), system.path().to_path_buf()];
+        let themes = list_cursor_themes_in(&dirs);
+        let names: Vec<&str> = themes.iter().map(|theme| theme.name.as_str()).collect();
+        assert_eq!(names, vec!["Adwaita", "Bibata"]);
+
+        assert_eq!(themes[1].display_name, "Bibata Modern");
+        assert_eq!(themes[1].path, user.path().join("Bibata"));
+        assert_eq!(themes[1].inherits, vec!["Adwaita", "hicolor"]);
+        assert!(is_installed("Adwaita", &dirs));
+        assert!(!is_installed("hicolor", &dirs));
+    }
+
+    #[test]
+    fn test_parse_index_ignores_other_groups() {
+        let (name, inherits) = parse_index("[Other]\nName=Wrong\n\n[Icon Theme]\nComment=x\nName=Right\n");
+        assert_eq!(name.as_deref(), Some("Right"));
+        assert!(inherits.is_empty());
+    }
+}
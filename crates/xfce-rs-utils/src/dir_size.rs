@@ -0,0 +1,192 @@
+//! Recursive directory size calculation
+//!
+//! The walk runs on a blocking thread and publishes running totals through a
+//! watch channel, so a properties dialog can show numbers climbing while a
+//! large tree is scanned.
+
+use std::collections::HashSet;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::UtilError;
+
+/// How often running totals are published while walking
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Running or final totals of a directory walk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirSizeProgress {
+    /// Apparent size of all regular files and symlinks, in bytes
+    pub bytes: u64,
+    /// Number of non-directory entries seen
+    pub files: u64,
+    /// Number of directories visited, including the root
+    pub dirs: u64,
+    /// Entries that could not be read
+    pub errors: u64,
+    /// Whether the walk has finished
+    pub finished: bool,
+}
+
+/// Options controlling a directory walk
+#[derive(Debug, Clone, Copy)]
+pub struct DirSizeOptions {
+    /// Do not descend into directories on other filesystems
+    pub same_filesystem: bool,
+}
+
+impl Default for DirSizeOptions {
+    fn default() -> Self {
+        Self {
+            same_filesystem: true,
+        }
+    }
+}
+
+/// Handle to a directory size calculation running in the background
+pub struct DirSizeTask {
+    progress: watch::Receiver<DirSizeProgress>,
+    cancelled: Arc<AtomicBool>,
+    handle: JoinHandle<Result<DirSizeProgress, UtilError>>,
+}
+
+impl DirSizeTask {
+    /// Start walking `path`. Must be called from within a Tokio runtime.
+    pub fn spawn(path: impl Into<PathBuf>, options: DirSizeOptions) -> Self {
+        let path = path.into();
+        let (sender, progress) = watch::channel(DirSizeProgress::default());
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let flag = cancelled.clone();
+        let handle = tokio::task::spawn_blocking(move || walk(&path, options, &flag, &sender));
+
+        Self {
+            progress,
+            cancelled,
+            handle,
+        }
+    }
+
+    /// Latest published totals
+    pub fn progress(&self) -> DirSizeProgress {
+        *self.progress.borrow()
+    }
+
+    /// A receiver that is notified whenever totals change
+    pub fn subscribe(&self) -> watch::Receiver<DirSizeProgress> {
+        self.progress.clone()
+    }
+
+    /// Ask the walk to stop as soon as possible
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Wait for the walk to finish and return the final totals
+    pub async fn wait(self) -> Result<DirSizeProgress, UtilError> {
+        self.handle.await.map_err(|_| UtilError::Cancelled)?
+    }
+}
+
+fn walk(
+    root: &Path,
+    options: DirSizeOptions,
+    cancelled: &AtomicBool,
+    sender: &watch::Sender<DirSizeProgress>,
+) -> Result<DirSizeProgress, UtilError> {
+    let root_meta = fs::symlink_metadata(root)?;
+    let root_dev = root_meta.dev();
+
+    let mut totals = DirSizeProgress::default();
+    // Hard-linked files are only counted once
+    let mut seen_inodes = HashSet::new();
+    let mut last_report = Instant::now();
+
+    if !root_meta.is_dir() {
+        totals.files = 1;
+        totals.bytes = root_meta.len();
+        totals.finished = true;
+        sender.send_replace(totals);
+        return Ok(totals);
+    }
+
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            sender.send_replace(totals);
+            return Err(UtilError::Cancelled);
+        }
+
+        totals.dirs += 1;
+        let Ok(entries) = fs::read_dir(&dir) else {
+            totals.errors += 1;
+            continue;
+        };
+
+        for entry in entries {
+            let Ok((path, meta)) = entry.and_then(|entry| {
+                let path = entry.path();
+                fs::symlink_metadata(&path).map(|meta| (path, meta))
+            }) else {
+                totals.errors += 1;
+                continue;
+            };
+
+            if meta.is_dir() {
+                if !options.same_filesystem || meta.dev() == root_dev {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            if meta.nlink() > 1 && !seen_inodes.insert((meta.dev(), meta.ino())) {
+                continue;
+            }
+            totals.files += 1;
+            totals.bytes += meta.len();
+        }
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            sender.send_replace(totals);
+            last_report = Instant::now();
+        }
+    }
+
+    totals.finished = true;
+    sender.send_replace(totals);
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dir_size_counts_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), vec![0u8; 100]).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/b"), vec![0u8; 50]).unwrap();
+        fs::hard_link(dir.path().join("sub/b"), dir.path().join("sub/c")).unwrap();
+
+        let task = DirSizeTask::spawn(dir.path(), DirSizeOptions::default());
+        let totals = task.wait().await.unwrap();
+
+        assert_eq!(totals.bytes, 150);
+        assert_eq!(totals.files, 2);
+        assert_eq!(totals.dirs, 2);
+        assert!(totals.finished);
+    }
+}
@@ -0,0 +1,93 @@
+//! Fixed-length sample history for graphing
+
+use std::collections::VecDeque;
+
+/// Ring buffer keeping the most recent `capacity` samples, oldest first
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryBuffer<T> {
+    samples: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> HistoryBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Append a sample, dropping the oldest one when full
+    pub fn push(&mut self, sample: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Change how many samples are kept, discarding the oldest if shrinking
+    pub fn set_capacity(&mut self, capacity: usize) {
+        while self.samples.len() > capacity {
+            self.samples.pop_front();
+        }
+        self.capacity = capacity;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Most recent sample
+    pub fn latest(&self) -> Option<&T> {
+        self.samples.back()
+    }
+
+    /// Samples from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.samples.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+impl<T: Clone> HistoryBuffer<T> {
+    /// Copy of the samples from oldest to newest
+    pub fn to_vec(&self) -> Vec<T> {
+        self.samples.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_buffer_wraps() {
+        let mut history = HistoryBuffer::new(3);
+        for sample in 1..=5 {
+            history.push(sample);
+        }
+        assert_eq!(history.to_vec(), vec![3, 4, 5]);
+        assert_eq!(history.latest(), Some(&5));
+
+        history.set_capacity(2);
+        assert_eq!(history.to_vec(), vec![4, 5]);
+
+        history.set_capacity(0);
+        history.push(6);
+        assert!(history.is_empty());
+    }
+}
@@ -1,8 +1,28 @@
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use sysinfo::System;
 use regex::Regex;
 use tokio::process;
 use tracing::error;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub mod cursor_themes;
+pub mod dir_size;
+pub mod history;
+pub mod mime;
+pub mod trash;
+pub mod user_dirs;
+pub mod volumes;
+pub mod watcher;
+
+pub use cursor_themes::CursorTheme;
+pub use dir_size::{DirSizeOptions, DirSizeProgress, DirSizeTask};
+pub use history::HistoryBuffer;
+pub use mime::MimeDetector;
+pub use trash::{Trash, TrashItem};
+pub use user_dirs::{UserDirKind, UserDirs, UserDirsWatcher};
+pub use volumes::{Volume, VolumeError, VolumeEvent, VolumeMonitor};
+pub use watcher::{DirWatcher, FsEvent, WatchOptions};
 
 /// Error types for utilities
 #[derive(Error, Debug)]
@@ -19,20 +39,46 @@ pub enum UtilError {
     #[error("Regex compilation failed: {0}")]
     RegexError(#[from] regex::Error),
     
+    #[error("Trash operation failed: {reason}")]
+    Trash { reason: String },
+    
+    #[error("File watch failed: {reason}")]
+    Watch { reason: String },
+    
+    #[error("Operation cancelled")]
+    Cancelled,
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
 
+/// Default number of samples kept in each history buffer
+pub const DEFAULT_HISTORY_LENGTH: usize = 60;
+
 /// System information utilities
 pub struct SystemInfo {
     system: System,
+    cpu_history: HistoryBuffer<f32>,
+    core_history: Vec<HistoryBuffer<f32>>,
+    load_history: HistoryBuffer<LoadAverage>,
 }
 
 impl SystemInfo {
     pub fn new() -> Self {
+        Self::with_history_length(DEFAULT_HISTORY_LENGTH)
+    }
+    
+    /// Create with history buffers holding `length` samples each
+    pub fn with_history_length(length: usize) -> Self {
         let mut system = System::new_all();
         system.refresh_all();
-        Self { system }
+        let cores = system.cpus().len();
+        Self {
+            system,
+            cpu_history: HistoryBuffer::new(length),
+            core_history: (0..cores).map(|_| HistoryBuffer::new(length)).collect(),
+            load_history: HistoryBuffer::new(length),
+        }
     }
     
     /// Get CPU usage percentage
@@ -40,6 +86,64 @@ impl SystemInfo {
         self.system.global_cpu_info().cpu_usage()
     }
     
+    /// Usage percentage of each logical core, as of the last refresh
+    pub fn per_core_usage(&self) -> Vec<f32> {
+        self.system.cpus().iter().map(|cpu| cpu.cpu_usage()).collect()
+    }
+    
+    /// System load averages over 1, 5 and 15 minutes
+    pub fn load_average(&self) -> LoadAverage {
+        let load = System::load_average();
+        LoadAverage {
+            one: load.one,
+            five: load.five,
+            fifteen: load.fifteen,
+        }
+    }
+    
+    /// Refresh CPU statistics and append a sample to every history buffer.
+    ///
+    /// Call this at a steady interval; CPU usage is measured between calls.
+    pub fn sample(&mut self) {
+        self.system.refresh_cpu();
+        
+        self.cpu_history.push(self.cpu_usage());
+        
+        let usage = self.per_core_usage();
+        let length = self.cpu_history.capacity();
+        self.core_history.resize_with(usage.len(), || HistoryBuffer::new(length));
+        for (history, value) in self.core_history.iter_mut().zip(usage) {
+            history.push(value);
+        }
+        
+        let load = self.load_average();
+        self.load_history.push(load);
+    }
+    
+    /// Change how many samples each history buffer keeps
+    pub fn set_history_length(&mut self, length: usize) {
+        self.cpu_history.set_capacity(length);
+        self.load_history.set_capacity(length);
+        for history in &mut self.core_history {
+            history.set_capacity(length);
+        }
+    }
+    
+    /// Overall CPU usage history
+    pub fn cpu_history(&self) -> &HistoryBuffer<f32> {
+        &self.cpu_history
+    }
+    
+    /// Usage history for each logical core
+    pub fn core_history(&self) -> &[HistoryBuffer<f32>] {
+        &self.core_history
+    }
+    
+    /// Load average history
+    pub fn load_history(&self) -> &HistoryBuffer<LoadAverage> {
+        &self.load_history
+    }
+    
     /// Get memory usage information
     pub fn memory_usage(&self) -> (u64, u64) {
         let total = self.system.total_memory();
@@ -100,6 +204,14 @@ pub struct ProcessInfo {
     pub cmd: String,
 }
 
+/// System load averages
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
 /// Disk usage information
 #[derive(Debug, Clone)]
 pub struct DiskUsage {
@@ -150,6 +262,14 @@ impl FileSystemUtils {
         .to_string()
     }
     
+    /// Calculate the total size of a directory tree in the background.
+    ///
+    /// The walk stays on the filesystem `path` lives on; use
+    /// [`DirSizeTask::spawn`] directly to cross mount points.
+    pub fn dir_size(path: impl Into<std::path::PathBuf>) -> DirSizeTask {
+        DirSizeTask::spawn(path, DirSizeOptions::default())
+    }
+    
     /// Check if path exists
     pub fn path_exists(path: &str) -> bool {
         std::path::Path::new(path).exists()
@@ -218,14 +338,61 @@ impl ProcessUtils {
     
     /// Check if a command is available in PATH
     pub async fn command_exists(command: &str) -> bool {
-        match process::Command::new("which")
-            .arg(command)
-            .output()
-            .await
-        {
-            Ok(output) => output.status.success(),
-            Err(_) => false,
+        Self::resolve_executable(command).is_some()
+    }
+    
+    /// Resolve a command name to the executable it would run, searching PATH.
+    ///
+    /// Names containing a `/` are treated as paths and checked directly.
+    pub fn resolve_executable(name: &str) -> Option<PathBuf> {
+        if name.is_empty() {
+            return None;
         }
+        
+        if name.contains('/') {
+            let path = PathBuf::from(name);
+            return is_executable(&path).then_some(path);
+        }
+        
+        Self::path_dirs()
+            .into_iter()
+            .map(|dir| dir.join(name))
+            .find(|candidate| is_executable(candidate))
+    }
+    
+    /// List the names of all executables reachable through PATH.
+    ///
+    /// Earlier PATH entries shadow later ones, so each name appears once.
+    pub fn path_executables() -> Vec<String> {
+        let mut names = std::collections::BTreeSet::new();
+        
+        for dir in Self::path_dirs() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            
+            for entry in entries.flatten() {
+                if !is_executable(&entry.path()) {
+                    continue;
+                }
+                if let Some(name) = entry.file_name().to_str() {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        
+        names.into_iter().collect()
+    }
+    
+    /// Directories listed in PATH, in search order
+    fn path_dirs() -> Vec<PathBuf> {
+        std::env::var_os("PATH")
+            .map(|path| {
+                std::env::split_paths(&path)
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
     }
     
     /// Kill process by PID
@@ -245,19 +412,89 @@ impl ProcessUtils {
     }
 }
 
+/// Whether a path is a regular file with any execute bit set
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    
+    std::fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Where `StringUtils::truncate_with` places the ellipsis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ellipsis {
+    Start,
+    Middle,
+    #[default]
+    End,
+}
+
 /// String utilities
 pub struct StringUtils;
 
 impl StringUtils {
-    /// Truncate string to specified length
+    /// Truncate string to at most `max_length` characters, ending in "..."
     pub fn truncate(s: &str, max_length: usize) -> String {
-        if s.len() <= max_length {
-            s.to_string()
+        Self::truncate_with(s, max_length, Ellipsis::End)
+    }
+    
+    /// Truncate string to at most `max_length` user-perceived characters.
+    ///
+    /// Lengths count grapheme clusters, so combining marks and emoji
+    /// sequences are never split.
+    pub fn truncate_with(s: &str, max_length: usize, ellipsis: Ellipsis) -> String {
+        const DOTS: &str = "...";
+        
+        let graphemes: Vec<&str> = s.graphemes(true).collect();
+        if graphemes.len() <= max_length {
+            return s.to_string();
+        }
+        
+        // No room for the ellipsis
+        if max_length < DOTS.len() {
+            return graphemes[..max_length].concat();
+        }
+        
+        let keep = max_length - DOTS.len();
+        match ellipsis {
+            Ellipsis::End => format!("{}{}", graphemes[..keep].concat(), DOTS),
+            Ellipsis::Start => format!("{}{}", DOTS, graphemes[graphemes.len() - keep..].concat()),
+            Ellipsis::Middle => {
+                let head = keep.div_ceil(2);
+                let tail = keep - head;
+                format!(
+                    "{}{}{}",
+                    graphemes[..head].concat(),
+                    DOTS,
+                    graphemes[graphemes.len() - tail..].concat()
+                )
+            }
+        }
+    }
+    
+    /// Format a duration in seconds as e.g. "45s", "2m 05s", "1h 03m" or "2d 04h"
+    pub fn format_duration(secs: u64) -> String {
+        const MINUTE: u64 = 60;
+        const HOUR: u64 = 60 * MINUTE;
+        const DAY: u64 = 24 * HOUR;
+        
+        if secs < MINUTE {
+            format!("{}s", secs)
+        } else if secs < HOUR {
+            format!("{}m {:02}s", secs / MINUTE, secs % MINUTE)
+        } else if secs < DAY {
+            format!("{}h {:02}m", secs / HOUR, (secs % HOUR) / MINUTE)
         } else {
-            format!("{}...", &s[..max_length.saturating_sub(3)])
+            format!("{}d {:02}h", secs / DAY, (secs % DAY) / HOUR)
         }
     }
     
+    /// Format a transfer rate, e.g. "1.5 MB/s"
+    pub fn format_rate(bytes_per_sec: u64) -> String {
+        format!("{}/s", FileSystemUtils::format_file_size(bytes_per_sec))
+    }
+    
     /// Extract number from string using regex
     pub fn extract_number(s: &str) -> Option<f64> {
         let re = Regex::new(r"[-+]?\d*\.?\d+").ok()?;
@@ -320,10 +557,42 @@ mod tests {
     fn test_string_utilities() {
         assert_eq!(StringUtils::truncate("short", 10), "short");
         assert_eq!(StringUtils::truncate("very long string", 10), "very lo...");
+        assert_eq!(StringUtils::truncate("héllo wörld ünïcode", 8), "héllo...");
+        assert_eq!(StringUtils::truncate_with("e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}", 4, Ellipsis::End), "e\u{301}...");
+        assert_eq!(StringUtils::truncate_with("/home/user/Documents/report.pdf", 15, Ellipsis::Middle), "/home/...rt.pdf");
+        assert_eq!(StringUtils::truncate_with("abcdefghij", 6, Ellipsis::Start), "...hij");
+        assert_eq!(StringUtils::truncate("abcdef", 2), "ab");
+        assert_eq!(StringUtils::truncate_with("abcdef", 0, Ellipsis::Middle), "");
+        assert_eq!(StringUtils::format_duration(45), "45s");
+        assert_eq!(StringUtils::format_duration(125), "2m 05s");
+        assert_eq!(StringUtils::format_duration(3780), "1h 03m");
+        assert_eq!(StringUtils::format_duration(180000), "2d 02h");
+        assert_eq!(StringUtils::format_rate(1536), "1.5 KB/s");
         assert_eq!(StringUtils::extract_number("Version 2.3.1"), Some(2.3));
         assert_eq!(StringUtils::to_title_case("hello world"), "Hello World");
     }
     
+    #[test]
+    fn test_resolve_executable() {
+        assert!(ProcessUtils::resolve_executable("sh").is_some());
+        assert!(ProcessUtils::resolve_executable("/bin/sh").is_some());
+        assert!(ProcessUtils::resolve_executable("definitely-not-a-command-xfce").is_none());
+        assert!(ProcessUtils::resolve_executable("").is_none());
+        assert!(ProcessUtils::path_executables().iter().any(|name| name == "sh"));
+    }
+    
+    #[test]
+    fn test_system_info_history() {
+        let mut info = SystemInfo::with_history_length(2);
+        for _ in 0..3 {
+            info.sample();
+        }
+        assert_eq!(info.cpu_history().len(), 2);
+        assert_eq!(info.load_history().len(), 2);
+        assert_eq!(info.core_history().len(), info.per_core_usage().len());
+        assert!(info.core_history().iter().all(|history| history.len() == 2));
+    }
+    
     #[test]
     fn test_disk_usage_percent() {
         let usage = DiskUsage {
@@ -0,0 +1,599 @@
+//! MIME type detection backed by the shared-mime-info database
+//!
+//! Reads `globs2`, `magic`, `aliases`, `subclasses`, `icons` and
+//! `generic-icons` from every `mime/` directory in the XDG data dirs.
+//! Detection follows the shared-mime-info recommendation: a single
+//! unambiguous glob wins, otherwise the file contents are sniffed.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+/// Fallback for binary data nothing matched
+pub const MIME_OCTET_STREAM: &str = "application/octet-stream";
+/// Fallback for textual data nothing matched
+pub const MIME_TEXT_PLAIN: &str = "text/plain";
+/// Type reported for directories
+pub const MIME_DIRECTORY: &str = "inode/directory";
+
+/// How many bytes are read when sniffing a file with no magic rules loaded
+const DEFAULT_SNIFF_LEN: usize = 4096;
+
+#[derive(Debug, Clone)]
+struct GlobRule {
+    weight: u32,
+    mime: String,
+    pattern: String,
+    case_sensitive: bool,
+}
+
+#[derive(Debug, Clone)]
+struct MagicMatch {
+    indent: usize,
+    offset: usize,
+    range: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+struct MagicSection {
+    priority: u32,
+    mime: String,
+    matches: Vec<MagicMatch>,
+}
+
+/// MIME type detector using globs and magic sniffing
+#[derive(Debug, Clone, Default)]
+pub struct MimeDetector {
+    globs: Vec<GlobRule>,
+    magic: Vec<MagicSection>,
+    aliases: HashMap<String, String>,
+    parents: HashMap<String, Vec<String>>,
+    icons: HashMap<String, String>,
+    generic_icons: HashMap<String, String>,
+    sniff_len: usize,
+}
+
+impl MimeDetector {
+    /// Load the database from the standard XDG data directories
+    pub fn load() -> Self {
+        Self::from_dirs(&mime_dirs())
+    }
+
+    /// Load the database from explicit `mime/` directories.
+    ///
+    /// Earlier directories take precedence, matching XDG lookup order.
+    pub fn from_dirs(dirs: &[PathBuf]) -> Self {
+        let mut detector = Self::default();
+
+        // Load lowest priority first so later inserts override
+        for dir in dirs.iter().rev() {
+            if let Ok(contents) = fs::read_to_string(dir.join("globs2")) {
+                detector.load_globs(&contents);
+            }
+            if let Ok(data) = fs::read(dir.join("magic")) {
+                detector.load_magic(&data);
+            }
+            if let Ok(contents) = fs::read_to_string(dir.join("aliases")) {
+                for (alias, mime) in parse_pairs(&contents, ' ') {
+                    detector.aliases.insert(alias, mime);
+                }
+            }
+            if let Ok(contents) = fs::read_to_string(dir.join("subclasses")) {
+                for (mime, parent) in parse_pairs(&contents, ' ') {
+                    detector.parents.entry(mime).or_default().push(parent);
+                }
+            }
+            if let Ok(contents) = fs::read_to_string(dir.join("icons")) {
+                detector.icons.extend(parse_pairs(&contents, ':'));
+            }
+            if let Ok(contents) = fs::read_to_string(dir.join("generic-icons")) {
+                detector.generic_icons.extend(parse_pairs(&contents, ':'));
+            }
+        }
+
+        detector.magic.sort_by_key(|section| std::cmp::Reverse(section.priority));
+        detector.sniff_len = detector
+            .magic
+            .iter()
+            .flat_map(|section| section.matches.iter())
+            .map(|rule| rule.offset + rule.range + rule.value.len())
+            .max()
+            .unwrap_or(DEFAULT_SNIFF_LEN)
+            .max(DEFAULT_SNIFF_LEN);
+
+        debug!(
+            "Loaded MIME database: {} globs, {} magic sections",
+            detector.globs.len(),
+            detector.magic.len()
+        );
+        detector
+    }
+
+    /// Detect the MIME type of a file from its name and contents
+    pub fn detect_path(&self, path: impl AsRef<Path>) -> String {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return MIME_DIRECTORY.to_string();
+        }
+
+        let globbed = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| self.glob_matches(name))
+            .unwrap_or_default();
+        if globbed.len() == 1 {
+            return globbed[0].clone();
+        }
+
+        let data = read_head(path, self.sniff_len).unwrap_or_default();
+        if let Some(sniffed) = self.magic_match(&data) {
+            // Prefer a glob candidate the sniffed type confirms
+            return globbed
+                .into_iter()
+                .find(|candidate| self.is_a(candidate, &sniffed))
+                .unwrap_or(sniffed);
+        }
+
+        if let Some(first) = globbed.into_iter().next() {
+            return first;
+        }
+
+        fallback_for(&data).to_string()
+    }
+
+    /// Detect a MIME type purely from data, ignoring any file name
+    pub fn detect_data(&self, data: &[u8]) -> String {
+        self.magic_match(data)
+            .unwrap_or_else(|| fallback_for(data).to_string())
+    }
+
+    /// MIME types whose glob patterns match a file name, best first
+    pub fn detect_filename(&self, name: &str) -> Vec<String> {
+        self.glob_matches(name)
+    }
+
+    /// Resolve an alias to its canonical MIME type
+    pub fn unalias<'a>(&'a self, mime: &'a str) -> &'a str {
+        self.aliases.get(mime).map(String::as_str).unwrap_or(mime)
+    }
+
+    /// Whether `mime` equals or is a subclass of `parent`
+    pub fn is_a(&self, mime: &str, parent: &str) -> bool {
+        let mime = self.unalias(mime);
+        let parent = self.unalias(parent);
+        if mime == parent {
+            return true;
+        }
+
+        // Implicit hierarchy from the shared-mime-info spec
+        if parent == MIME_OCTET_STREAM && !mime.starts_with("inode/") {
+            return true;
+        }
+        if parent == MIME_TEXT_PLAIN && mime.starts_with("text/") {
+            return true;
+        }
+
+        self.parents
+            .get(mime)
+            .is_some_and(|parents| parents.iter().any(|p| self.is_a(p, parent)))
+    }
+
+    /// Themed icon name for a MIME type
+    pub fn icon_name(&self, mime: &str) -> String {
+        let mime = self.unalias(mime);
+        if let Some(icon) = self.icons.get(mime) {
+            return icon.clone();
+        }
+        if mime == MIME_DIRECTORY {
+            return "folder".to_string();
+        }
+        mime.replace('/', "-")
+    }
+
+    /// Generic fallback icon name for a MIME type
+    pub fn generic_icon_name(&self, mime: &str) -> String {
+        let mime = self.unalias(mime);
+        if let Some(icon) = self.generic_icons.get(mime) {
+            return icon.clone();
+        }
+        let media = mime.split('/').next().unwrap_or("text");
+        format!("{}-x-generic", media)
+    }
+
+    fn load_globs(&mut self, contents: &str) {
+        for line in contents.lines() {
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(4, ':');
+            let (Some(weight), Some(mime), Some(pattern)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+
+            if pattern == "__NOGLOBS__" {
+                self.globs.retain(|glob| glob.mime != mime);
+                continue;
+            }
+
+            let flags = fields.next().unwrap_or("");
+            self.globs.push(GlobRule {
+                weight: weight.parse().unwrap_or(50),
+                mime: mime.to_string(),
+                pattern: pattern.to_string(),
+                case_sensitive: flags.split(',').any(|flag| flag == "cs"),
+            });
+        }
+    }
+
+    fn load_magic(&mut self, data: &[u8]) {
+        const HEADER: &[u8] = b"MIME-Magic\0\n";
+        let Some(mut rest) = data.strip_prefix(HEADER) else {
+            return;
+        };
+
+        while !rest.is_empty() {
+            let Some((section, remaining)) = parse_magic_section(rest) else {
+                debug!("Stopped parsing malformed magic file");
+                return;
+            };
+            self.magic.retain(|existing| existing.mime != section.mime);
+            self.magic.push(section);
+            rest = remaining;
+        }
+    }
+
+    fn glob_matches(&self, name: &str) -> Vec<String> {
+        let lower = name.to_lowercase();
+        let mut best: Vec<(u32, bool, usize, &str)> = Vec::new();
+
+        for glob in &self.globs {
+            let subject = if glob.case_sensitive { name } else { lower.as_str() };
+            let pattern = if glob.case_sensitive {
+                glob.pattern.clone()
+            } else {
+                glob.pattern.to_lowercase()
+            };
+            if glob_match(&pattern, subject) {
+                best.push((glob.weight, glob.case_sensitive, glob.pattern.len(), &glob.mime));
+            }
+        }
+
+        // Highest weight wins, then case-sensitive patterns, then the
+        // longest (most specific) pattern
+        best.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(b.2.cmp(&a.2)));
+        let Some(&(top_weight, top_cs, top_len, _)) = best.first() else {
+            return Vec::new();
+        };
+
+        let mut result: Vec<String> = Vec::new();
+        for (weight, case_sensitive, len, mime) in best {
+            if weight != top_weight || case_sensitive != top_cs || len != top_len {
+                break;
+            }
+            if !result.iter().any(|existing| existing == mime) {
+                result.push(mime.to_string());
+            }
+        }
+        result
+    }
+
+    fn magic_match(&self, data: &[u8]) -> Option<String> {
+        if data.is_empty() {
+            return None;
+        }
+        self.magic
+            .iter()
+            .find(|section| matches_tree(&section.matches, data))
+            .map(|section| section.mime.clone())
+    }
+}
+
+/// `mime/` directories in XDG lookup order
+fn mime_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local/share")));
+    dirs.extend(data_home);
+
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs.extend(data_dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from));
+
+    dirs.into_iter().map(|dir| dir.join("mime")).collect()
+}
+
+fn parse_pairs(contents: &str, separator: char) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(separator))
+        .map(|(a, b)| (a.trim().to_string(), b.trim().to_string()))
+        .collect()
+}
+
+fn read_head(path: &Path, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(len);
+    fs::File::open(path)?.take(len as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// `text/plain` for data without NULs that decodes as UTF-8, else binary
+fn fallback_for(data: &[u8]) -> &'static str {
+    let head = &data[..data.len().min(128)];
+    if head.contains(&0) {
+        return MIME_OCTET_STREAM;
+    }
+    // A multi-byte character may be cut at the end of the sample
+    match std::str::from_utf8(head) {
+        Ok(_) => MIME_TEXT_PLAIN,
+        Err(err) if err.error_len().is_none() => MIME_TEXT_PLAIN,
+        Err(_) => MIME_OCTET_STREAM,
+    }
+}
+
+/// Minimal fnmatch supporting `*`, `?` and `[...]` classes
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_match_from(&pattern, &name)
+}
+
+fn glob_match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob_match_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(close) = pattern.iter().position(|&c| c == ']') else {
+                return name.first() == Some(&'[') && glob_match_from(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let class = &pattern[1..close];
+            let (negated, class) = match class.first() {
+                Some('!') | Some('^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+
+            matched != negated && glob_match_from(&pattern[close + 1..], &name[1..])
+        }
+        Some(&literal) => name.first() == Some(&literal) && glob_match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Parse one `[priority:mime]` section followed by its match lines
+fn parse_magic_section(data: &[u8]) -> Option<(MagicSection, &[u8])> {
+    let data = data.strip_prefix(b"[")?;
+    let end = data.iter().position(|&b| b == b']')?;
+    let header = std::str::from_utf8(&data[..end]).ok()?;
+    let (priority, mime) = header.split_once(':')?;
+    let mut rest = data[end + 1..].strip_prefix(b"\n")?;
+
+    let mut matches = Vec::new();
+    while !rest.is_empty() && rest[0] != b'[' {
+        let (rule, remaining) = parse_magic_match(rest)?;
+        matches.push(rule);
+        rest = remaining;
+    }
+
+    Some((
+        MagicSection {
+            priority: priority.parse().ok()?,
+            mime: mime.to_string(),
+            matches,
+        },
+        rest,
+    ))
+}
+
+/// Parse `[indent]>offset=<len><value>[&mask][~word][+range]\n`
+fn parse_magic_match(data: &[u8]) -> Option<(MagicMatch, &[u8])> {
+    let (indent, data) = take_number(data).unwrap_or((0, data));
+    let data = data.strip_prefix(b">")?;
+    let (offset, data) = take_number(data)?;
+    let data = data.strip_prefix(b"=")?;
+
+    if data.len() < 2 {
+        return None;
+    }
+    let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+    let data = &data[2..];
+    if data.len() < len {
+        return None;
+    }
+    let mut value = data[..len].to_vec();
+    let mut data = &data[len..];
+
+    let mut mask = None;
+    if let Some(rest) = data.strip_prefix(b"&") {
+        if rest.len() < len {
+            return None;
+        }
+        mask = Some(rest[..len].to_vec());
+        data = &rest[len..];
+    }
+
+    let mut word_size = 1;
+    if let Some(rest) = data.strip_prefix(b"~") {
+        let (size, rest) = take_number(rest)?;
+        word_size = size;
+        data = rest;
+    }
+
+    let mut range = 1;
+    if let Some(rest) = data.strip_prefix(b"+") {
+        let (length, rest) = take_number(rest)?;
+        range = length.max(1);
+        data = rest;
+    }
+
+    // Values are stored big-endian; swap words to host order
+    if cfg!(target_endian = "little") && word_size > 1 {
+        for chunk in value.chunks_mut(word_size) {
+            chunk.reverse();
+        }
+        if let Some(mask) = mask.as_mut() {
+            for chunk in mask.chunks_mut(word_size) {
+                chunk.reverse();
+            }
+        }
+    }
+
+    // Skip any extension fields up to the end of the line
+    let line_end = data.iter().position(|&b| b == b'\n')?;
+    Some((
+        MagicMatch {
+            indent,
+            offset,
+            range,
+            value,
+            mask,
+        },
+        &data[line_end + 1..],
+    ))
+}
+
+fn take_number(data: &[u8]) -> Option<(usize, &[u8])> {
+    let digits = data.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let number = std::str::from_utf8(&data[..digits]).ok()?.parse().ok()?;
+    Some((number, &data[digits..]))
+}
+
+/// A section matches when any top-level rule matches together with, if it
+/// has nested rules, at least one of them.
+fn matches_tree(rules: &[MagicMatch], data: &[u8]) -> bool {
+    let mut i = 0;
+    while i < rules.len() {
+        let indent = rules[i].indent;
+        let children_end = rules[i + 1..]
+            .iter()
+            .position(|rule| rule.indent <= indent)
+            .map(|pos| i + 1 + pos)
+            .unwrap_or(rules.len());
+
+        if rule_matches(&rules[i], data) {
+            let children = &rules[i + 1..children_end];
+            if children.is_empty() || matches_tree(children, data) {
+                return true;
+            }
+        }
+        i = children_end;
+    }
+    false
+}
+
+fn rule_matches(rule: &MagicMatch, data: &[u8]) -> bool {
+    let len = rule.value.len();
+    (rule.offset..rule.offset + rule.range).any(|start| {
+        let Some(window) = data.get(start..start + len) else {
+            return false;
+        };
+        match &rule.mask {
+            Some(mask) => window
+                .iter()
+                .zip(&rule.value)
+                .zip(mask)
+                .all(|((byte, value), mask)| byte & mask == value & mask),
+            None => window == rule.value.as_slice(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_database() -> (tempfile::TempDir, MimeDetector) {
+        let dir = tempfile::tempdir().unwrap();
+        let mime = dir.path().join("mime");
+        fs::create_dir(&mime).unwrap();
+
+        fs::write(
+            mime.join("globs2"),
+            "50:image/png:*.png\n50:text/x-csrc:*.c\n50:text/x-c++src:*.C:cs\n50:application/x-tar:*.tar\n",
+        )
+        .unwrap();
+
+        let mut magic = b"MIME-Magic\0\n".to_vec();
+        magic.extend_from_slice(b"[50:image/png]\n>0=\x00\x04\x89PNG\n");
+        magic.extend_from_slice(b"[50:application/x-tar]\n>257=\x00\x05ustar\n");
+        fs::write(mime.join("magic"), magic).unwrap();
+
+        fs::write(mime.join("aliases"), "image/x-png image/png\n").unwrap();
+        fs::write(mime.join("generic-icons"), "image/png:image-x-generic\n").unwrap();
+
+        let detector = MimeDetector::from_dirs(&[mime]);
+        (dir, detector)
+    }
+
+    #[test]
+    fn test_detect_by_content() {
+        let (dir, detector) = test_database();
+
+        // Misnamed PNG is still recognised by its signature
+        let path = dir.path().join("photo.dat");
+        fs::write(&path, b"\x89PNG\r\n\x1a\n....").unwrap();
+        assert_eq!(detector.detect_path(&path), "image/png");
+
+        let text = dir.path().join("notes");
+        fs::write(&text, "plain words").unwrap();
+        assert_eq!(detector.detect_path(&text), MIME_TEXT_PLAIN);
+
+        let binary = dir.path().join("blob");
+        fs::write(&binary, [0u8, 1, 2, 3]).unwrap();
+        assert_eq!(detector.detect_path(&binary), MIME_OCTET_STREAM);
+
+        let mut tar = vec![0u8; 300];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(detector.detect_data(&tar), "application/x-tar");
+    }
+
+    #[test]
+    fn test_glob_rules() {
+        let (_dir, detector) = test_database();
+        assert_eq!(detector.detect_filename("MAIN.c"), vec!["text/x-csrc"]);
+        assert_eq!(detector.detect_filename("main.C"), vec!["text/x-c++src"]);
+        assert!(detector.detect_filename("main.rs").is_empty());
+        assert!(glob_match("[a-c]?.txt", "bz.txt"));
+        assert!(!glob_match("[!a-c]*", "apple"));
+    }
+
+    #[test]
+    fn test_aliases_and_icons() {
+        let (_dir, detector) = test_database();
+        assert!(detector.is_a("image/x-png", "image/png"));
+        assert!(detector.is_a("text/x-csrc", MIME_TEXT_PLAIN));
+        assert_eq!(detector.icon_name("image/png"), "image-png");
+        assert_eq!(detector.generic_icon_name("image/x-png"), "image-x-generic");
+        assert_eq!(detector.generic_icon_name("audio/ogg"), "audio-x-generic");
+        assert_eq!(detector.icon_name(MIME_DIRECTORY), "folder");
+    }
+}
@@ -0,0 +1,452 @@
+//! freedesktop.org Trash specification support
+//!
+//! Files on the home filesystem go to `$XDG_DATA_HOME/Trash`. Files on other
+//! mounts go to `$topdir/.Trash/$uid` when the administrator has prepared a
+//! shared sticky `.Trash` directory, otherwise to `$topdir/.Trash-$uid`.
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDateTime};
+use tracing::warn;
+
+use crate::UtilError;
+
+const DELETION_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// An entry in one of the trash directories
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashItem {
+    /// File name inside the trash `files/` directory
+    pub name: String,
+    /// Absolute path the file had before it was trashed
+    pub original_path: PathBuf,
+    /// When the file was trashed, if recorded
+    pub deletion_date: Option<NaiveDateTime>,
+    /// Trash directory that holds this item
+    pub trash_dir: PathBuf,
+}
+
+impl TrashItem {
+    /// Path of the trashed file itself
+    pub fn files_path(&self) -> PathBuf {
+        self.trash_dir.join("files").join(&self.name)
+    }
+
+    /// Path of the `.trashinfo` metadata file
+    pub fn info_path(&self) -> PathBuf {
+        self.trash_dir
+            .join("info")
+            .join(format!("{}.trashinfo", self.name))
+    }
+}
+
+/// Trash can operations
+pub struct Trash;
+
+impl Trash {
+    /// Move a file or directory to the trash
+    pub fn trash_file(path: impl AsRef<Path>) -> Result<TrashItem, UtilError> {
+        let path = absolute(path.as_ref())?;
+        let meta = fs::symlink_metadata(&path)?;
+
+        let (trash_dir, topdir) = Self::trash_dir_for(&path, meta.dev())?;
+        let files_dir = trash_dir.join("files");
+        let info_dir = trash_dir.join("info");
+        fs::create_dir_all(&files_dir)?;
+        fs::create_dir_all(&info_dir)?;
+
+        // Paths in a mount-local trash are stored relative to its top directory
+        let recorded_path = match &topdir {
+            Some(topdir) => path.strip_prefix(topdir).unwrap_or(&path).to_path_buf(),
+            None => path.clone(),
+        };
+        let deletion_date = Local::now().naive_local();
+
+        let base_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| UtilError::InvalidPath {
+                path: path.display().to_string(),
+            })?
+            .to_string();
+
+        // Reserve a unique name by exclusively creating the info file first
+        let mut counter = 1;
+        let (name, mut info_file) = loop {
+            let candidate = if counter == 1 {
+                base_name.clone()
+            } else {
+                format!("{}.{}", base_name, counter)
+            };
+            let info_path = info_dir.join(format!("{}.trashinfo", candidate));
+
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+            {
+                Ok(file) if !files_dir.join(&candidate).exists() => break (candidate, file),
+                Ok(_) => {
+                    let _ = fs::remove_file(&info_path);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+            counter += 1;
+        };
+
+        let item = TrashItem {
+            name,
+            original_path: path.clone(),
+            deletion_date: Some(deletion_date),
+            trash_dir,
+        };
+
+        let contents = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(&recorded_path.to_string_lossy()),
+            deletion_date.format(DELETION_DATE_FORMAT)
+        );
+        if let Err(err) = info_file.write_all(contents.as_bytes()) {
+            let _ = fs::remove_file(item.info_path());
+            return Err(err.into());
+        }
+
+        if let Err(err) = fs::rename(&path, item.files_path()) {
+            let _ = fs::remove_file(item.info_path());
+            return Err(err.into());
+        }
+
+        Ok(item)
+    }
+
+    /// List the contents of every trash directory the user can reach
+    pub fn list_trash() -> Result<Vec<TrashItem>, UtilError> {
+        let mut items = Vec::new();
+
+        for (trash_dir, topdir) in Self::trash_dirs() {
+            let Ok(entries) = fs::read_dir(trash_dir.join("info")) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let file_name = entry.file_name();
+                let Some(name) = file_name
+                    .to_str()
+                    .and_then(|name| name.strip_suffix(".trashinfo"))
+                else {
+                    continue;
+                };
+
+                match parse_trash_info(&entry.path(), topdir.as_deref()) {
+                    Some((original_path, deletion_date)) => items.push(TrashItem {
+                        name: name.to_string(),
+                        original_path,
+                        deletion_date,
+                        trash_dir: trash_dir.clone(),
+                    }),
+                    None => warn!("Ignoring malformed trash info: {}", entry.path().display()),
+                }
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Move a trashed item back to its original location
+    pub fn restore(item: &TrashItem) -> Result<PathBuf, UtilError> {
+        if item.original_path.exists() {
+            return Err(UtilError::Trash {
+                reason: format!("{} already exists", item.original_path.display()),
+            });
+        }
+
+        if let Some(parent) = item.original_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(item.files_path(), &item.original_path)?;
+        fs::remove_file(item.info_path())?;
+
+        Ok(item.original_path.clone())
+    }
+
+    /// Permanently delete a single trashed item
+    pub fn delete(item: &TrashItem) -> Result<(), UtilError> {
+        remove_any(&item.files_path())?;
+        match fs::remove_file(item.info_path()) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Permanently delete everything in every trash directory
+    pub fn empty_trash() -> Result<(), UtilError> {
+        for (trash_dir, _) in Self::trash_dirs() {
+            for sub in ["files", "info"] {
+                let Ok(entries) = fs::read_dir(trash_dir.join(sub)) else {
+                    continue;
+                };
+                for entry in entries.flatten() {
+                    remove_any(&entry.path())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The user's home trash directory
+    pub fn home_trash_dir() -> PathBuf {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| dirs::home_dir().map(|home| home.join(".local/share")))
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("Trash")
+    }
+
+    /// Pick the trash directory for a file on device `dev`.
+    ///
+    /// Returns the directory and, for mount-local trashes, the mount's top
+    /// directory.
+    fn trash_dir_for(path: &Path, dev: u64) -> Result<(PathBuf, Option<PathBuf>), UtilError> {
+        let home_trash = Self::home_trash_dir();
+        let home_dev = nearest_existing(&home_trash)
+            .and_then(|existing| fs::metadata(existing).ok())
+            .map(|meta| meta.dev());
+
+        if home_dev == Some(dev) {
+            return Ok((home_trash, None));
+        }
+
+        let topdir = mount_topdir(path, dev);
+        let uid = current_uid();
+
+        let shared = topdir.join(".Trash");
+        if is_valid_shared_trash(&shared) {
+            let dir = shared.join(uid.to_string());
+            if fs::create_dir_all(&dir).is_ok() {
+                return Ok((dir, Some(topdir)));
+            }
+        }
+
+        let dir = topdir.join(format!(".Trash-{}", uid));
+        if !dir.exists() {
+            fs::create_dir(&dir)?;
+            fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        }
+        Ok((dir, Some(topdir)))
+    }
+
+    /// All trash directories for the current user, with mount top directories
+    fn trash_dirs() -> Vec<(PathBuf, Option<PathBuf>)> {
+        let mut dirs = vec![(Self::home_trash_dir(), None)];
+        let uid = current_uid();
+
+        for topdir in mount_points() {
+            let shared = topdir.join(".Trash");
+            if is_valid_shared_trash(&shared) {
+                let dir = shared.join(uid.to_string());
+                if dir.is_dir() {
+                    dirs.push((dir, Some(topdir.clone())));
+                }
+            }
+
+            let private = topdir.join(format!(".Trash-{}", uid));
+            if private.is_dir() {
+                dirs.push((private, Some(topdir)));
+            }
+        }
+
+        dirs
+    }
+}
+
+/// Parse a `.trashinfo` file into its original path and deletion date
+fn parse_trash_info(
+    info_path: &Path,
+    topdir: Option<&Path>,
+) -> Option<(PathBuf, Option<NaiveDateTime>)> {
+    let contents = fs::read_to_string(info_path).ok()?;
+    let mut in_group = false;
+    let mut path = None;
+    let mut deletion_date = None;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_group = line == "[Trash Info]";
+            continue;
+        }
+        if !in_group {
+            continue;
+        }
+
+        if let Some(value) = line.strip_prefix("Path=") {
+            path = Some(PathBuf::from(percent_decode(value)));
+        } else if let Some(value) = line.strip_prefix("DeletionDate=") {
+            deletion_date = NaiveDateTime::parse_from_str(value, DELETION_DATE_FORMAT).ok();
+        }
+    }
+
+    let path = path?;
+    let path = match topdir {
+        Some(topdir) if path.is_relative() => topdir.join(path),
+        _ => path,
+    };
+    Some((path, deletion_date))
+}
+
+/// `$topdir/.Trash` must be a real directory with the sticky bit set
+fn is_valid_shared_trash(dir: &Path) -> bool {
+    fs::symlink_metadata(dir)
+        .map(|meta| meta.is_dir() && meta.permissions().mode() & 0o1000 != 0)
+        .unwrap_or(false)
+}
+
+/// Walk up from `path` to the top directory of the filesystem it lives on
+fn mount_topdir(path: &Path, dev: u64) -> PathBuf {
+    let mut topdir = path.parent().unwrap_or(path).to_path_buf();
+    while let Some(parent) = topdir.parent() {
+        match fs::metadata(parent) {
+            Ok(meta) if meta.dev() == dev => topdir = parent.to_path_buf(),
+            _ => break,
+        }
+    }
+    topdir
+}
+
+/// Mount points listed in `/proc/self/mounts`
+fn mount_points() -> Vec<PathBuf> {
+    fs::read_to_string("/proc/self/mounts")
+        .map(|mounts| {
+            mounts
+                .lines()
+                .filter_map(|line| line.split_whitespace().nth(1))
+                .map(|point| PathBuf::from(unescape_mount_field(point)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Decode the octal escapes (`\040` for space, ...) used in mount tables
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            if let Some(byte) = escaped_byte(&bytes[i + 1..i + 4], 8) {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The byte spelled by `digits` in `radix`. They come out of the raw
+/// bytes, as what follows an escape may not start a character.
+fn escaped_byte(digits: &[u8], radix: u32) -> Option<u8> {
+    std::str::from_utf8(digits).ok().and_then(|digits| u8::from_str_radix(digits, radix).ok())
+}
+
+fn nearest_existing(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| ancestor.exists())
+}
+
+fn current_uid() -> u32 {
+    fs::metadata("/proc/self").map(|meta| meta.uid()).unwrap_or(0)
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, UtilError> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+fn remove_any(path: &Path) -> std::io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// URL-escape a path the way the Trash spec requires, keeping `/` intact
+fn percent_encode(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(byte) = escaped_byte(&bytes[i + 1..i + 3], 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_round_trip() {
+        let path = "/home/user/My Files/ünïcode%.txt";
+        let encoded = percent_encode(path);
+        assert!(!encoded.contains(' '));
+        assert_eq!(percent_decode(&encoded), path);
+    }
+
+    #[test]
+    fn test_percent_decode_stray_percent() {
+        assert_eq!(percent_decode("50%é"), "50%é");
+        assert_eq!(percent_decode("%é%41"), "%éA");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn test_parse_trash_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = dir.path().join("a.txt.trashinfo");
+        fs::write(
+            &info,
+            "[Trash Info]\nPath=docs/a%20b.txt\nDeletionDate=2024-03-01T12:30:00\n",
+        )
+        .unwrap();
+
+        let (path, date) = parse_trash_info(&info, Some(Path::new("/media/usb"))).unwrap();
+        assert_eq!(path, PathBuf::from("/media/usb/docs/a b.txt"));
+        assert_eq!(
+            date.unwrap().format(DELETION_DATE_FORMAT).to_string(),
+            "2024-03-01T12:30:00"
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! XDG user directories (`~/.config/user-dirs.dirs`)
+//!
+//! The file is a shell fragment of `XDG_<NAME>_DIR="$HOME/..."` assignments
+//! maintained by `xdg-user-dirs-update`. Paths equal to `$HOME` mean the
+//! directory is disabled.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::watcher::watch_error;
+use crate::UtilError;
+
+/// The well-known user directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserDirKind {
+    Desktop,
+    Download,
+    Templates,
+    PublicShare,
+    Documents,
+    Music,
+    Pictures,
+    Videos,
+}
+
+impl UserDirKind {
+    /// All kinds, in the order `xdg-user-dirs-update` writes them
+    pub const ALL: [UserDirKind; 8] = [
+        UserDirKind::Desktop,
+        UserDirKind::Download,
+        UserDirKind::Templates,
+        UserDirKind::PublicShare,
+        UserDirKind::Documents,
+        UserDirKind::Music,
+        UserDirKind::Pictures,
+        UserDirKind::Videos,
+    ];
+
+    /// Name used in the `XDG_<NAME>_DIR` variable
+    pub fn key(&self) -> &'static str {
+        match self {
+            UserDirKind::Desktop => "DESKTOP",
+            UserDirKind::Download => "DOWNLOAD",
+            UserDirKind::Templates => "TEMPLATES",
+            UserDirKind::PublicShare => "PUBLICSHARE",
+            UserDirKind::Documents => "DOCUMENTS",
+            UserDirKind::Music => "MUSIC",
+            UserDirKind::Pictures => "PICTURES",
+            UserDirKind::Videos => "VIDEOS",
+        }
+    }
+
+    /// Themed icon name for the directory
+    pub fn icon_name(&self) -> &'static str {
+        match self {
+            UserDirKind::Desktop => "user-desktop",
+            UserDirKind::Download => "folder-download",
+            UserDirKind::Templates => "folder-templates",
+            UserDirKind::PublicShare => "folder-publicshare",
+            UserDirKind::Documents => "folder-documents",
+            UserDirKind::Music => "folder-music",
+            UserDirKind::Pictures => "folder-pictures",
+            UserDirKind::Videos => "folder-videos",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.key() == key)
+    }
+}
+
+/// Parsed contents of `user-dirs.dirs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserDirs {
+    dirs: HashMap<UserDirKind, PathBuf>,
+    home: PathBuf,
+    config_path: PathBuf,
+}
+
+impl UserDirs {
+    /// Load the user's directories, falling back to none configured
+    pub fn load() -> Self {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
+        let config_path = Self::config_path();
+        let contents = fs::read_to_string(&config_path).unwrap_or_default();
+
+        Self {
+            dirs: parse_user_dirs(&contents, &home),
+            home,
+            config_path,
+        }
+    }
+
+    /// Location of `user-dirs.dirs`
+    pub fn config_path() -> PathBuf {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+            .unwrap_or_else(|| PathBuf::from("/etc/xdg"))
+            .join("user-dirs.dirs")
+    }
+
+    /// Path of a user directory, or `None` if unset or disabled
+    pub fn get(&self, kind: UserDirKind) -> Option<&Path> {
+        self.dirs
+            .get(&kind)
+            .map(PathBuf::as_path)
+            .filter(|path| *path != self.home)
+    }
+
+    /// All configured directories
+    pub fn iter(&self) -> impl Iterator<Item = (UserDirKind, &Path)> {
+        UserDirKind::ALL
+            .into_iter()
+            .filter_map(|kind| self.get(kind).map(|path| (kind, path)))
+    }
+
+    /// Find which special directory `path` is, if any
+    pub fn kind_of(&self, path: &Path) -> Option<UserDirKind> {
+        self.iter().find(|(_, dir)| *dir == path).map(|(kind, _)| kind)
+    }
+
+    /// Change a directory and write the file back, preserving other lines
+    pub fn set_dir(&mut self, kind: UserDirKind, path: impl Into<PathBuf>) -> Result<(), UtilError> {
+        let path = path.into();
+        if !path.is_absolute() {
+            return Err(UtilError::InvalidPath {
+                path: path.display().to_string(),
+            });
+        }
+
+        self.dirs.insert(kind, path.clone());
+
+        let existing = fs::read_to_string(&self.config_path).unwrap_or_default();
+        let contents = replace_entry(&existing, kind, &self.encode_path(&path));
+
+        if let Some(parent) = self.config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.config_path.with_extension("dirs.tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.config_path)?;
+
+        Ok(())
+    }
+
+    /// Watch `user-dirs.dirs` and receive a fresh copy whenever it changes.
+    ///
+    /// The returned watcher must be kept alive for updates to keep flowing.
+    pub fn watch() -> Result<UserDirsWatcher, UtilError> {
+        let (sender, receiver) = watch::channel(Self::load());
+        let config_path = Self::config_path();
+        let watch_dir = config_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("/"));
+
+        let target = config_path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                Ok(event) if event.paths.contains(&target) => {
+                    sender.send_if_modified(|current| {
+                        let fresh = UserDirs::load();
+                        let changed = *current != fresh;
+                        *current = fresh;
+                        changed
+                    });
+                }
+                Ok(_) => {}
+                Err(err) => warn!("user-dirs watch error: {}", err),
+            }
+        })
+        .map_err(watch_error)?;
+
+        watcher
+            .watch(&watch_dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+
+        Ok(UserDirsWatcher {
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    /// Format a path the way `xdg-user-dirs-update` does
+    fn encode_path(&self, path: &Path) -> String {
+        match path.strip_prefix(&self.home) {
+            Ok(rest) if rest.as_os_str().is_empty() => "$HOME/".to_string(),
+            Ok(rest) => format!("$HOME/{}", rest.display()),
+            Err(_) => path.display().to_string(),
+        }
+    }
+}
+
+/// Keeps a filesystem watch on `user-dirs.dirs` alive
+pub struct UserDirsWatcher {
+    _watcher: RecommendedWatcher,
+    receiver: watch::Receiver<UserDirs>,
+}
+
+impl UserDirsWatcher {
+    /// Receiver that yields the latest directories after each change
+    pub fn subscribe(&self) -> watch::Receiver<UserDirs> {
+        self.receiver.clone()
+    }
+
+    /// Most recently loaded directories
+    pub fn current(&self) -> UserDirs {
+        self.receiver.borrow().clone()
+    }
+}
+
+fn parse_user_dirs(contents: &str, home: &Path) -> HashMap<UserDirKind, PathBuf> {
+    let mut dirs = HashMap::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let Some(kind) = name
+            .strip_prefix("XDG_")
+            .and_then(|name| name.strip_suffix("_DIR"))
+            .and_then(UserDirKind::from_key)
+        else {
+            continue;
+        };
+
+        let Some(value) = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+        else {
+            continue;
+        };
+        let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+
+        let path = if let Some(rest) = value.strip_prefix("$HOME") {
+            home.join(rest.trim_start_matches('/'))
+        } else if value.starts_with('/') {
+            PathBuf::from(value)
+        } else {
+            // Relative paths are not allowed by the format
+            continue;
+        };
+
+        dirs.insert(kind, path);
+    }
+
+    dirs
+}
+
+fn replace_entry(contents: &str, kind: UserDirKind, value: &str) -> String {
+    let key = format!("XDG_{}_DIR", kind.key());
+    let line = format!(
+        "{}=\"{}\"",
+        key,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    );
+
+    let mut replaced = false;
+    let mut lines: Vec<String> = contents
+        .lines()
+        .map(|existing| {
+            let is_entry = existing
+                .trim()
+                .split_once('=')
+                .is_some_and(|(name, _)| name == key);
+            if is_entry && !replaced {
+                replaced = true;
+                line.clone()
+            } else {
+                existing.to_string()
+            }
+        })
+        .collect();
+
+    if !replaced {
+        lines.push(line);
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"# This file is written by xdg-user-dirs-update
+XDG_DESKTOP_DIR="$HOME/Desktop"
+XDG_DOWNLOAD_DIR="$HOME/Descargas"
+XDG_MUSIC_DIR="/srv/music"
+XDG_VIDEOS_DIR="$HOME/"
+"#;
+
+    #[test]
+    fn test_parse_user_dirs() {
+        let home = Path::new("/home/user");
+        let dirs = UserDirs {
+            dirs: parse_user_dirs(SAMPLE, home),
+            home: home.to_path_buf(),
+            config_path: PathBuf::new(),
+        };
+
+        assert_eq!(
+            dirs.get(UserDirKind::Download),
+            Some(Path::new("/home/user/Descargas"))
+        );
+        assert_eq!(dirs.get(UserDirKind::Music), Some(Path::new("/srv/music")));
+        // Pointing at $HOME disables the directory
+        assert_eq!(dirs.get(UserDirKind::Videos), None);
+        assert_eq!(dirs.get(UserDirKind::Pictures), None);
+    }
+
+    #[test]
+    fn test_replace_entry_preserves_other_lines() {
+        let out = replace_entry(SAMPLE, UserDirKind::Download, "$HOME/Downloads");
+        assert!(out.starts_with("# This file"));
+        assert!(out.contains("XDG_DOWNLOAD_DIR=\"$HOME/Downloads\""));
+        assert!(!out.contains("Descargas"));
+
+        let out = replace_entry(SAMPLE, UserDirKind::Pictures, "$HOME/Pictures");
+        assert!(out.ends_with("XDG_PICTURES_DIR=\"$HOME/Pictures\"\n"));
+    }
+}
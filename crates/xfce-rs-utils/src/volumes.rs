@@ -0,0 +1,443 @@
+//! Mounted volumes and removable media, via UDisks2
+//!
+//! `VolumeMonitor` keeps a snapshot of the filesystems UDisks2 knows about
+//! and reports differences as [`VolumeEvent`]s. Mount, unmount and eject go
+//! through UDisks2 so polkit can prompt for authorization when needed.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use zbus::fdo::ObjectManagerProxy;
+use zbus::zvariant::{OwnedObjectPath, Value};
+use zbus::{Connection, MatchRule, MessageStream};
+
+use futures_util::StreamExt;
+use xfce_rs_ipc::udisks2::{
+    decode_bytestring, UDisks2BlockProxy, UDisks2DriveProxy, UDisks2FilesystemProxy,
+    UDisks2Options, BLOCK_INTERFACE, FILESYSTEM_INTERFACE, UDISKS2_PATH, UDISKS2_SERVICE,
+};
+
+/// Errors from volume operations
+#[derive(Error, Debug)]
+pub enum VolumeError {
+    #[error("Not authorized: {0}")]
+    NotAuthorized(String),
+
+    #[error("Authentication was dismissed")]
+    AuthenticationDismissed,
+
+    #[error("Device is busy: {0}")]
+    Busy(String),
+
+    #[error("Volume is already mounted")]
+    AlreadyMounted,
+
+    #[error("Volume is not mounted")]
+    NotMounted,
+
+    #[error("Volume has no filesystem")]
+    NotMountable,
+
+    #[error("Volume cannot be ejected")]
+    NotEjectable,
+
+    #[error("UDisks2 operation failed: {0}")]
+    Failed(String),
+
+    #[error("D-Bus error: {0}")]
+    DBus(zbus::Error),
+}
+
+impl VolumeError {
+    /// Whether retrying with user authentication could succeed
+    pub fn is_authorization_error(&self) -> bool {
+        matches!(
+            self,
+            VolumeError::NotAuthorized(_) | VolumeError::AuthenticationDismissed
+        )
+    }
+}
+
+impl From<zbus::Error> for VolumeError {
+    fn from(err: zbus::Error) -> Self {
+        let zbus::Error::MethodError(name, detail, _) = &err else {
+            return VolumeError::DBus(err);
+        };
+        let detail = detail.clone().unwrap_or_default();
+
+        match name.as_str() {
+            "org.freedesktop.UDisks2.Error.NotAuthorized"
+            | "org.freedesktop.UDisks2.Error.NotAuthorizedCanObtain" => {
+                VolumeError::NotAuthorized(detail)
+            }
+            "org.freedesktop.UDisks2.Error.NotAuthorizedDismissed" => {
+                VolumeError::AuthenticationDismissed
+            }
+            "org.freedesktop.UDisks2.Error.DeviceBusy" => VolumeError::Busy(detail),
+            "org.freedesktop.UDisks2.Error.AlreadyMounted" => VolumeError::AlreadyMounted,
+            "org.freedesktop.UDisks2.Error.NotMounted" => VolumeError::NotMounted,
+            name if name.starts_with("org.freedesktop.UDisks2.Error.") => {
+                VolumeError::Failed(detail)
+            }
+            _ => VolumeError::DBus(err),
+        }
+    }
+}
+
+impl From<zbus::fdo::Error> for VolumeError {
+    fn from(err: zbus::fdo::Error) -> Self {
+        VolumeError::from(zbus::Error::from(err))
+    }
+}
+
+/// A block device carrying a filesystem
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    /// UDisks2 object path, stable while the device is present
+    pub object_path: String,
+    /// Device node, e.g. `/dev/sdb1`
+    pub device: PathBuf,
+    pub label: Option<String>,
+    pub fs_type: Option<String>,
+    pub uuid: Option<String>,
+    pub size: u64,
+    pub read_only: bool,
+    pub mount_points: Vec<PathBuf>,
+    /// Object path of the drive this volume lives on
+    pub drive: Option<String>,
+    pub removable: bool,
+    pub ejectable: bool,
+    /// UDisks2 thinks this should not be shown in a file manager
+    pub hidden: bool,
+    pub icon_name: String,
+}
+
+impl Volume {
+    /// Name suitable for a sidebar entry
+    pub fn display_name(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        if self.size > 0 {
+            return format!("{} Volume", crate::FileSystemUtils::format_file_size(self.size));
+        }
+        self.device.display().to_string()
+    }
+
+    pub fn is_mounted(&self) -> bool {
+        !self.mount_points.is_empty()
+    }
+}
+
+/// Change in the set of known volumes
+#[derive(Debug, Clone, PartialEq)]
+pub enum VolumeEvent {
+    Added(Volume),
+    Removed(Volume),
+    Mounted(Volume),
+    Unmounted(Volume),
+    Changed(Volume),
+}
+
+/// Tracks volumes exposed by UDisks2
+pub struct VolumeMonitor {
+    connection: Connection,
+    volumes: Vec<Volume>,
+}
+
+impl VolumeMonitor {
+    /// Connect to the system bus and load the current volumes
+    pub async fn new() -> Result<Self, VolumeError> {
+        let connection = Connection::system().await?;
+        let volumes = scan(&connection).await?;
+        Ok(Self {
+            connection,
+            volumes,
+        })
+    }
+
+    /// Currently known volumes
+    pub fn volumes(&self) -> &[Volume] {
+        &self.volumes
+    }
+
+    /// Volumes that should be shown to the user
+    pub fn visible_volumes(&self) -> impl Iterator<Item = &Volume> {
+        self.volumes.iter().filter(|volume| !volume.hidden)
+    }
+
+    /// Re-read all volumes and return what changed
+    pub async fn refresh(&mut self) -> Result<Vec<VolumeEvent>, VolumeError> {
+        let fresh = scan(&self.connection).await?;
+        let events = diff(&self.volumes, &fresh);
+        self.volumes = fresh;
+        Ok(events)
+    }
+
+    /// Receive events as UDisks2 reports changes.
+    ///
+    /// Must be called within a Tokio runtime. The channel closes when the
+    /// connection to UDisks2 is lost.
+    pub async fn events(&self) -> Result<mpsc::UnboundedReceiver<VolumeEvent>, VolumeError> {
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender(UDISKS2_SERVICE)?
+            .path_namespace(UDISKS2_PATH)?
+            .build();
+        let mut signals = MessageStream::for_match_rule(rule, &self.connection, None).await?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let connection = self.connection.clone();
+        let mut known = self.volumes.clone();
+
+        tokio::spawn(async move {
+            while signals.next().await.is_some() {
+                let fresh = match scan(&connection).await {
+                    Ok(fresh) => fresh,
+                    Err(err) => {
+                        warn!("Failed to rescan volumes: {}", err);
+                        continue;
+                    }
+                };
+
+                for event in diff(&known, &fresh) {
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+                known = fresh;
+            }
+        });
+
+        Ok(receiver)
+    }
+
+    /// Mount a volume and return where it was mounted
+    pub async fn mount(&self, volume: &Volume) -> Result<PathBuf, VolumeError> {
+        if volume.fs_type.is_none() {
+            return Err(VolumeError::NotMountable);
+        }
+        let filesystem = self.filesystem_proxy(&volume.object_path).await?;
+        let mount_point = filesystem.mount(interactive_options()).await?;
+        Ok(PathBuf::from(mount_point))
+    }
+
+    /// Unmount a volume
+    pub async fn unmount(&self, volume: &Volume) -> Result<(), VolumeError> {
+        if !volume.is_mounted() {
+            return Err(VolumeError::NotMounted);
+        }
+        let filesystem = self.filesystem_proxy(&volume.object_path).await?;
+        filesystem.unmount(interactive_options()).await?;
+        Ok(())
+    }
+
+    /// Unmount every volume on the same drive and eject the media
+    pub async fn eject(&self, volume: &Volume) -> Result<(), VolumeError> {
+        let Some(drive_path) = volume.drive.as_deref().filter(|_| volume.ejectable) else {
+            return Err(VolumeError::NotEjectable);
+        };
+
+        for sibling in self
+            .volumes
+            .iter()
+            .filter(|other| other.drive.as_deref() == Some(drive_path) && other.is_mounted())
+        {
+            self.unmount(sibling).await?;
+        }
+
+        let drive = UDisks2DriveProxy::builder(&self.connection)
+            .path(drive_path)?
+            .build()
+            .await?;
+        drive.eject(interactive_options()).await?;
+        Ok(())
+    }
+
+    async fn filesystem_proxy(
+        &self,
+        object_path: &str,
+    ) -> Result<UDisks2FilesystemProxy<'static>, VolumeError> {
+        Ok(UDisks2FilesystemProxy::builder(&self.connection)
+            .path(object_path.to_string())?
+            .build()
+            .await?)
+    }
+}
+
+/// Let polkit show an authentication dialog when required
+fn interactive_options() -> UDisks2Options<'static> {
+    let mut options = HashMap::new();
+    options.insert("auth.no_user_interaction", Value::from(false));
+    options
+}
+
+/// Read every block device with a filesystem from UDisks2
+async fn scan(connection: &Connection) -> Result<Vec<Volume>, VolumeError> {
+    let manager = ObjectManagerProxy::builder(connection)
+        .destination(UDISKS2_SERVICE)?
+        .path(UDISKS2_PATH)?
+        .build()
+        .await?;
+    let objects = manager.get_managed_objects().await?;
+
+    let mut drives: HashMap<OwnedObjectPath, (bool, bool)> = HashMap::new();
+    let mut volumes = Vec::new();
+
+    for (path, interfaces) in &objects {
+        let has = |name: &str| interfaces.keys().any(|iface| iface.as_str() == name);
+        if !has(BLOCK_INTERFACE) || !has(FILESYSTEM_INTERFACE) {
+            continue;
+        }
+
+        match read_volume(connection, path, &mut drives).await {
+            Ok(volume) => volumes.push(volume),
+            Err(err) => debug!("Skipping {}: {}", path.as_str(), err),
+        }
+    }
+
+    volumes.sort_by(|a, b| a.device.cmp(&b.device));
+    Ok(volumes)
+}
+
+async fn read_volume(
+    connection: &Connection,
+    path: &OwnedObjectPath,
+    drives: &mut HashMap<OwnedObjectPath, (bool, bool)>,
+) -> Result<Volume, VolumeError> {
+    let block = UDisks2BlockProxy::builder(connection)
+        .path(path.clone())?
+        .build()
+        .await?;
+    let filesystem = UDisks2FilesystemProxy::builder(connection)
+        .path(path.clone())?
+        .build()
+        .await?;
+
+    let drive_path = block.drive().await?;
+    let has_drive = drive_path.as_str() != "/";
+    let (removable, ejectable) = if !has_drive {
+        (false, false)
+    } else if let Some(flags) = drives.get(&drive_path) {
+        *flags
+    } else {
+        let drive = UDisks2DriveProxy::builder(connection)
+            .path(drive_path.clone())?
+            .build()
+            .await?;
+        let flags = (
+            drive.removable().await.unwrap_or(false) || drive.media_removable().await.unwrap_or(false),
+            drive.ejectable().await.unwrap_or(false),
+        );
+        drives.insert(drive_path.clone(), flags);
+        flags
+    };
+
+    let non_empty = |value: String| Some(value).filter(|value| !value.is_empty());
+    let hint_name = non_empty(block.hint_name().await.unwrap_or_default());
+    let hint_icon = non_empty(block.hint_icon_name().await.unwrap_or_default());
+    let id_label = non_empty(block.id_label().await.unwrap_or_default());
+
+    Ok(Volume {
+        object_path: path.as_str().to_string(),
+        device: PathBuf::from(decode_bytestring(&block.preferred_device().await?)),
+        label: hint_name.or(id_label),
+        fs_type: non_empty(block.id_type().await.unwrap_or_default()),
+        uuid: non_empty(block.id_uuid().await.unwrap_or_default()),
+        size: block.size().await.unwrap_or(0),
+        read_only: block.read_only().await.unwrap_or(false),
+        mount_points: filesystem
+            .mount_points()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|point| PathBuf::from(decode_bytestring(point)))
+            .collect(),
+        drive: has_drive.then(|| drive_path.as_str().to_string()),
+        removable,
+        ejectable,
+        hidden: block.hint_ignore().await.unwrap_or(false)
+            || (block.hint_system().await.unwrap_or(true) && !removable),
+        icon_name: hint_icon.unwrap_or_else(|| {
+            if removable {
+                "drive-removable-media".to_string()
+            } else {
+                "drive-harddisk".to_string()
+            }
+        }),
+    })
+}
+
+/// Compare two snapshots and describe the transition
+fn diff(old: &[Volume], new: &[Volume]) -> Vec<VolumeEvent> {
+    let mut events = Vec::new();
+
+    for volume in new {
+        match old.iter().find(|o| o.object_path == volume.object_path) {
+            None => events.push(VolumeEvent::Added(volume.clone())),
+            Some(previous) if previous == volume => {}
+            Some(previous) => events.push(match (previous.is_mounted(), volume.is_mounted()) {
+                (false, true) => VolumeEvent::Mounted(volume.clone()),
+                (true, false) => VolumeEvent::Unmounted(volume.clone()),
+                _ => VolumeEvent::Changed(volume.clone()),
+            }),
+        }
+    }
+
+    for volume in old {
+        if !new.iter().any(|n| n.object_path == volume.object_path) {
+            events.push(VolumeEvent::Removed(volume.clone()));
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(path: &str, mounted: bool) -> Volume {
+        Volume {
+            object_path: path.to_string(),
+            device: PathBuf::from("/dev/sdb1"),
+            label: Some("USB".to_string()),
+            fs_type: Some("vfat".to_string()),
+            uuid: None,
+            size: 8_000_000_000,
+            read_only: false,
+            mount_points: if mounted {
+                vec![PathBuf::from("/run/media/user/USB")]
+            } else {
+                Vec::new()
+            },
+            drive: Some("/org/freedesktop/UDisks2/drives/usb".to_string()),
+            removable: true,
+            ejectable: true,
+            hidden: false,
+            icon_name: "drive-removable-media".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_reports_transitions() {
+        let a = volume("/a", false);
+        let b = volume("/b", true);
+        let a_mounted = volume("/a", true);
+        let c = volume("/c", false);
+
+        let events = diff(&[a.clone(), b.clone()], &[a_mounted.clone(), c.clone()]);
+        assert_eq!(
+            events,
+            vec![
+                VolumeEvent::Mounted(a_mounted),
+                VolumeEvent::Added(c),
+                VolumeEvent::Removed(b),
+            ]
+        );
+        assert!(diff(std::slice::from_ref(&a), std::slice::from_ref(&a)).is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+//! Debounced filesystem change watching
+//!
+//! `DirWatcher` wraps the platform watcher (inotify on Linux) and turns its
+//! raw notifications into a stream of typed [`FsEvent`]s. Bursts of events
+//! for the same path inside the debounce window are collapsed, and the two
+//! halves of a rename (`IN_MOVED_FROM`/`IN_MOVED_TO`) are paired by cookie.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::Stream;
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::UtilError;
+
+/// A filesystem change, after debouncing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsEvent {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+    /// Events were lost (e.g. queue overflow); consumers should re-read
+    Rescan,
+}
+
+/// Options for a [`DirWatcher`]
+#[derive(Debug, Clone, Copy)]
+pub struct WatchOptions {
+    /// Watch subdirectories, including ones created later
+    pub recursive: bool,
+    /// How long to collect events before emitting them
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            debounce: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Stream of debounced filesystem events for one or more paths
+pub struct DirWatcher {
+    watcher: RecommendedWatcher,
+    recursive: bool,
+    events: mpsc::UnboundedReceiver<FsEvent>,
+}
+
+impl DirWatcher {
+    /// Watch `path` with default options. Must be called within a Tokio runtime.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, UtilError> {
+        Self::with_options(path, WatchOptions::default())
+    }
+
+    /// Watch `path` with explicit options. Must be called within a Tokio runtime.
+    pub fn with_options(path: impl AsRef<Path>, options: WatchOptions) -> Result<Self, UtilError> {
+        let (raw_sender, raw_events) = mpsc::unbounded_channel();
+        let (sender, events) = mpsc::unbounded_channel();
+
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = raw_sender.send(event);
+        })
+        .map_err(watch_error)?;
+
+        tokio::spawn(debounce_loop(raw_events, sender, options.debounce));
+
+        let mut dir_watcher = Self {
+            watcher,
+            recursive: options.recursive,
+            events,
+        };
+        dir_watcher.add_path(path)?;
+        Ok(dir_watcher)
+    }
+
+    /// Start watching an additional path
+    pub fn add_path(&mut self, path: impl AsRef<Path>) -> Result<(), UtilError> {
+        let mode = if self.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        self.watcher.watch(path.as_ref(), mode).map_err(watch_error)
+    }
+
+    /// Stop watching a path
+    pub fn remove_path(&mut self, path: impl AsRef<Path>) -> Result<(), UtilError> {
+        self.watcher.unwatch(path.as_ref()).map_err(watch_error)
+    }
+
+    /// Wait for the next event
+    pub async fn next_event(&mut self) -> Option<FsEvent> {
+        self.events.recv().await
+    }
+}
+
+impl Stream for DirWatcher {
+    type Item = FsEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<FsEvent>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+pub(crate) fn watch_error(err: notify::Error) -> UtilError {
+    UtilError::Watch {
+        reason: err.to_string(),
+    }
+}
+
+/// What is known about a path within the current debounce window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    Created,
+    Modified,
+    Removed,
+}
+
+#[derive(Default)]
+struct Batch {
+    order: Vec<PathBuf>,
+    paths: HashMap<PathBuf, Pending>,
+    renames: Vec<(PathBuf, PathBuf)>,
+    /// `MOVED_FROM` halves waiting for their `MOVED_TO`, keyed by cookie
+    moved_from: HashMap<usize, PathBuf>,
+    rescan: bool,
+}
+
+impl Batch {
+    fn is_empty(&self) -> bool {
+        self.order.is_empty() && self.renames.is_empty() && self.moved_from.is_empty() && !self.rescan
+    }
+
+    fn record(&mut self, path: PathBuf, next: Pending) {
+        let merged = match (self.paths.get(&path).copied(), next) {
+            (None, next) => Some(next),
+            // Created then deleted inside the window: nothing happened
+            (Some(Pending::Created), Pending::Removed) => None,
+            (Some(Pending::Created), _) => Some(Pending::Created),
+            (Some(Pending::Removed), Pending::Created) => Some(Pending::Modified),
+            (Some(_), next) => Some(next),
+        };
+
+        match merged {
+            Some(state) => {
+                if self.paths.insert(path.clone(), state).is_none() {
+                    self.order.push(path);
+                }
+            }
+            None => {
+                self.paths.remove(&path);
+                self.order.retain(|existing| *existing != path);
+            }
+        }
+    }
+
+    fn apply(&mut self, event: notify::Event) {
+        if event.need_rescan() {
+            self.rescan = true;
+            return;
+        }
+
+        let tracker = event.attrs.tracker();
+        let mut paths = event.paths.into_iter();
+
+        match event.kind {
+            EventKind::Create(_) => paths.for_each(|path| self.record(path, Pending::Created)),
+            EventKind::Remove(_) => paths.for_each(|path| self.record(path, Pending::Removed)),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                    self.renames.push((from, to));
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                let Some(from) = paths.next() else { return };
+                match tracker {
+                    Some(cookie) => {
+                        self.moved_from.insert(cookie, from);
+                    }
+                    None => self.record(from, Pending::Removed),
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                let Some(to) = paths.next() else { return };
+                match tracker.and_then(|cookie| self.moved_from.remove(&cookie)) {
+                    Some(from) => self.renames.push((from, to)),
+                    // Moved in from outside the watched tree
+                    None => self.record(to, Pending::Created),
+                }
+            }
+            EventKind::Modify(_) => paths.for_each(|path| self.record(path, Pending::Modified)),
+            _ => {}
+        }
+    }
+
+    fn drain(&mut self) -> Vec<FsEvent> {
+        let mut events = Vec::new();
+        if std::mem::take(&mut self.rescan) {
+            events.push(FsEvent::Rescan);
+        }
+
+        let renames = std::mem::take(&mut self.renames);
+        // Renames whose other half never arrived moved out of the tree
+        let mut orphans: Vec<PathBuf> = self.moved_from.drain().map(|(_, from)| from).collect();
+        orphans.sort();
+
+        for path in std::mem::take(&mut self.order) {
+            match self.paths.remove(&path) {
+                Some(Pending::Created) => events.push(FsEvent::Created(path)),
+                Some(Pending::Modified) => events.push(FsEvent::Modified(path)),
+                Some(Pending::Removed) => events.push(FsEvent::Removed(path)),
+                None => {}
+            }
+        }
+        events.extend(
+            renames
+                .into_iter()
+                .map(|(from, to)| FsEvent::Renamed { from, to }),
+        );
+        events.extend(orphans.into_iter().map(FsEvent::Removed));
+        events
+    }
+}
+
+async fn debounce_loop(
+    mut raw_events: mpsc::UnboundedReceiver<notify::Result<notify::Event>>,
+    sender: mpsc::UnboundedSender<FsEvent>,
+    debounce: Duration,
+) {
+    let mut batch = Batch::default();
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let raw = match deadline {
+            Some(when) => tokio::select! {
+                raw = raw_events.recv() => raw,
+                _ = tokio::time::sleep_until(when) => {
+                    deadline = None;
+                    for event in batch.drain() {
+                        if sender.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    continue;
+                }
+            },
+            None => raw_events.recv().await,
+        };
+
+        match raw {
+            Some(Ok(event)) => batch.apply(event),
+            Some(Err(err)) => {
+                warn!("Filesystem watch error: {}", err);
+                continue;
+            }
+            None => {
+                // Watcher dropped: flush what is left and stop
+                for event in batch.drain() {
+                    let _ = sender.send(event);
+                }
+                return;
+            }
+        }
+
+        if deadline.is_none() && !batch.is_empty() {
+            deadline = Some(Instant::now() + debounce);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+
+    fn event(kind: EventKind, paths: &[&str]) -> notify::Event {
+        let mut event = notify::Event::new(kind);
+        for path in paths {
+            event = event.add_path(PathBuf::from(path));
+        }
+        event
+    }
+
+    #[test]
+    fn test_batch_collapses_bursts() {
+        let mut batch = Batch::default();
+        batch.apply(event(EventKind::Create(CreateKind::File), &["/a"]));
+        batch.apply(event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &["/a"]));
+        batch.apply(event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &["/b"]));
+        batch.apply(event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &["/b"]));
+        batch.apply(event(EventKind::Create(CreateKind::File), &["/tmp"]));
+        batch.apply(event(EventKind::Remove(RemoveKind::File), &["/tmp"]));
+
+        assert_eq!(
+            batch.drain(),
+            vec![
+                FsEvent::Created(PathBuf::from("/a")),
+                FsEvent::Modified(PathBuf::from("/b")),
+            ]
+        );
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn test_batch_pairs_renames_by_cookie() {
+        let mut batch = Batch::default();
+        batch.apply(
+            event(EventKind::Modify(ModifyKind::Name(RenameMode::From)), &["/old"]).set_tracker(7),
+        );
+        batch.apply(
+            event(EventKind::Modify(ModifyKind::Name(RenameMode::From)), &["/gone"]).set_tracker(8),
+        );
+        batch.apply(
+            event(EventKind::Modify(ModifyKind::Name(RenameMode::To)), &["/new"]).set_tracker(7),
+        );
+
+        assert_eq!(
+            batch.drain(),
+            vec![
+                FsEvent::Renamed {
+                    from: PathBuf::from("/old"),
+                    to: PathBuf::from("/new"),
+                },
+                FsEvent::Removed(PathBuf::from("/gone")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dir_watcher_reports_creation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = DirWatcher::with_options(
+            dir.path(),
+            WatchOptions {
+                recursive: true,
+                debounce: Duration::from_millis(20),
+            },
+        )
+        .unwrap();
+
+        let file = dir.path().join("created.txt");
+        std::fs::write(&file, "hello").unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), watcher.next_event())
+            .await
+            .unwrap();
+        assert_eq!(event, Some(FsEvent::Created(file)));
+    }
+}
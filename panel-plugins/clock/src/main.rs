@@ -5,6 +5,7 @@ use chrono::{DateTime, Local};
 use std::time::Duration;
 use xfce_rs_ui::styles;
 use xfce_rs_ui::colors;
+use xfce_rs_ui::theme;
 use tracing::info;
 
 pub fn main() -> iced::Result {
@@ -37,6 +38,7 @@ struct ClockApp {
 #[derive(Debug, Clone)]
 enum Message {
     Tick,
+    ThemeChanged,
 }
 
 impl ClockApp {
@@ -55,7 +57,7 @@ impl ClockApp {
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        theme::iced_theme()
     }
 
     fn style(&self, theme: &Theme) -> iced::theme::Style {
@@ -66,7 +68,10 @@ impl ClockApp {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        time::every(Duration::from_secs(1)).map(|_| Message::Tick)
+        Subscription::batch([
+            time::every(Duration::from_secs(1)).map(|_| Message::Tick),
+            theme::subscription().map(|_| Message::ThemeChanged),
+        ])
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
@@ -75,6 +80,7 @@ impl ClockApp {
                 self.current_time = Local::now();
                 Task::none()
             }
+            Message::ThemeChanged => Task::none(),
         }
     }
 
@@ -85,10 +91,10 @@ impl ClockApp {
         let content = column![
             text(time_str)
                 .size(18)
-                .color(colors::TEXT_PRIMARY),
+                .color(colors::text_primary()),
             text(date_str)
                 .size(12)
-                .color(colors::TEXT_SECONDARY),
+                .color(colors::text_secondary()),
         ]
         .spacing(4)
         .align_x(Alignment::Center);
@@ -1,11 +1,13 @@
 use iced::widget::{container, space};
-use iced::{Background, Border, Color, Length, Theme};
+use iced::{Background, Border, Color, Length, Subscription, Theme};
+use xfce_rs_ui::{colors, theme};
 
 pub fn main() -> iced::Result {
     iced::application(SeparatorApp::new, SeparatorApp::update, SeparatorApp::view)
         .title(SeparatorApp::title)
         .theme(SeparatorApp::theme)
         .style(SeparatorApp::style)
+        .subscription(SeparatorApp::subscription)
         .window(iced::window::Settings {
             size: iced::Size::new(8.0, 48.0),
             position: iced::window::Position::Centered,
@@ -39,7 +41,7 @@ enum SeparatorStyle {
 
 #[derive(Debug, Clone)]
 enum Message {
-    // No messages needed for a simple separator
+    ThemeChanged,
 }
 
 impl SeparatorApp {
@@ -57,7 +59,7 @@ impl SeparatorApp {
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        theme::iced_theme()
     }
 
     fn style(&self, theme: &Theme) -> iced::theme::Style {
@@ -67,8 +69,14 @@ impl SeparatorApp {
         }
     }
 
-    fn update(&mut self, _message: Message) -> iced::Task<Message> {
-        iced::Task::none()
+    fn subscription(&self) -> Subscription<Message> {
+        theme::subscription().map(|_| Message::ThemeChanged)
+    }
+
+    fn update(&mut self, message: Message) -> iced::Task<Message> {
+        match message {
+            Message::ThemeChanged => iced::Task::none(),
+        }
     }
 
     fn separator_style(style: SeparatorStyle) -> impl Fn(&Theme) -> iced::widget::container::Style {
@@ -83,7 +91,7 @@ impl SeparatorApp {
                     border: Border {
                         width: 1.0,
                         radius: 0.0.into(),
-                        color: Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+                        color: colors::glass_border(),
                     },
                     ..Default::default()
                 },
@@ -1,6 +1,6 @@
 use iced::widget::{button, container, text};
-use iced::{Alignment, Element, Length, Task, Theme};
-use xfce_rs_ui::styles;
+use iced::{Alignment, Element, Length, Subscription, Task, Theme};
+use xfce_rs_ui::{styles, theme};
 use tracing::{info, warn};
 use std::process::Command;
 
@@ -15,6 +15,7 @@ pub fn main() -> iced::Result {
         .title(ShowDesktopApp::title)
         .theme(ShowDesktopApp::theme)
         .style(ShowDesktopApp::style)
+        .subscription(ShowDesktopApp::subscription)
         .window(iced::window::Settings {
             size: iced::Size::new(48.0, 48.0),
             position: iced::window::Position::Centered,
@@ -32,6 +33,7 @@ struct ShowDesktopApp {
 #[derive(Debug, Clone)]
 enum Message {
     Toggle,
+    ThemeChanged,
 }
 
 impl ShowDesktopApp {
@@ -49,7 +51,11 @@ impl ShowDesktopApp {
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        theme::iced_theme()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        theme::subscription().map(|_| Message::ThemeChanged)
     }
 
     fn style(&self, theme: &Theme) -> iced::theme::Style {
@@ -66,6 +72,7 @@ impl ShowDesktopApp {
                 self.toggle_show_desktop();
                 Task::none()
             }
+            Message::ThemeChanged => Task::none(),
         }
     }
 